@@ -11,7 +11,9 @@
 #include <string>
 #include <iostream>
 #include <sstream>
+#include <type_traits>
 #include <utility>
+#include <vector>
 #include <gtest/gtest.h>
 
 #include "rust_interface/rust_tuple.h"
@@ -38,6 +40,11 @@
 #include "rust_interface/Boo.hpp"
 #include "rust_interface/TestReturnTuple.hpp"
 #include "rust_interface/TestCopy.hpp"
+#include "rust_interface/TestFallible.hpp"
+#include "rust_interface/TestPanic.hpp"
+#if (defined(HAS_STDCXX_17) && !defined(NO_HAVE_STD17_VARIANT)) || defined(USE_BOOST)
+#include "rust_interface/TestDataEnum.hpp"
+#endif
 
 using namespace rust;
 
@@ -429,6 +436,32 @@ TEST(TestEnumClass, smokeTest)
     ASSERT_EQ(ITEM1, TestEnumClass::next_enum(ITEM3));
 }
 
+#if (defined(HAS_STDCXX_17) && !defined(NO_HAVE_STD17_VARIANT)) || defined(USE_BOOST)
+TEST(TestDataEnum, smokeTest)
+{
+    EXPECT_NEAR(3., TestDataEnum::area(Circle{ 1. }), 1e-10);
+    EXPECT_NEAR(8., TestDataEnum::area(Rect{ 2., 4. }), 1e-10);
+    EXPECT_NEAR(4., TestDataEnum::area(Named{ "abcd", true }), 1e-10);
+    EXPECT_NEAR(-1., TestDataEnum::area(Named{ "abcd", false }), 1e-10);
+    EXPECT_NEAR(0., TestDataEnum::area(NoShape{}), 1e-10);
+    Shape named = TestDataEnum::make(2);
+#ifdef HAS_STDCXX_17
+    EXPECT_NEAR(3., std::get<Rect>(TestDataEnum::make(1)).h, 1e-10);
+    EXPECT_EQ(std::string("abc"), std::get<Named>(named).name);
+    EXPECT_TRUE(std::get<Named>(named).visible);
+    const int kind = std::visit(
+        ShapeVisitor{ [](const Named &) { return 1; }, [](const auto &) { return 2; } }, named);
+    EXPECT_EQ(1, kind);
+    EXPECT_TRUE(std::holds_alternative<NoShape>(TestDataEnum::make(3)));
+#endif // HAS_STDCXX_17
+#ifdef USE_BOOST
+    EXPECT_NEAR(3., boost::get<Rect>(TestDataEnum::make(1)).h, 1e-10);
+    EXPECT_EQ(std::string("abc"), boost::get<Named>(named).name);
+    EXPECT_TRUE(boost::get<Named>(named).visible);
+#endif // USE_BOOST
+}
+#endif
+
 TEST(TestPassPathAsParam, smokeTest)
 {
     TestPassPathAsParam x;
@@ -570,6 +603,37 @@ To carry that wonderful nose.)");
         auto val2 = y.f14(false);
         EXPECT_TRUE(!val2);
     }
+    {
+        TestOptional y;
+        auto val = y.f15({ int8_t(-127) });
+        ASSERT_TRUE(!!val);
+        EXPECT_EQ(-128, *val);
+        EXPECT_TRUE(!y.f15({}));
+        auto val2 = y.f16({ uint16_t(65534) });
+        ASSERT_TRUE(!!val2);
+        EXPECT_EQ(65535u, *val2);
+        EXPECT_TRUE(!y.f16({}));
+    }
+    {
+        TestOptional y;
+        auto val = y.f17({ "ab" });
+        ASSERT_TRUE(!!val);
+        ASSERT_EQ(2u, val->size());
+        EXPECT_EQ(int32_t('a'), (*val)[0]);
+        EXPECT_EQ(int32_t('b'), (*val)[1]);
+        auto empty = y.f17({ "" });
+        ASSERT_TRUE(!!empty);
+        EXPECT_EQ(0u, empty->size());
+        EXPECT_TRUE(!y.f17({}));
+    }
+    {
+        TestOptional y;
+        auto val = y.f18(true);
+        ASSERT_TRUE(!!val);
+        ASSERT_EQ(2u, val->size());
+        EXPECT_EQ(std::string("2"), (*val)[1].getName());
+        EXPECT_TRUE(!y.f18(false));
+    }
 }
 
 TEST(TestResult, smokeTest)
@@ -820,6 +884,40 @@ TEST(TestCopy, smokeTest)
     EXPECT_EQ(std::string("Chuvava"), tst3.get());
 }
 
+TEST(TestCopy, Move)
+{
+    static_assert(std::is_nothrow_move_constructible<Foo>::value, "Foo should be movable");
+    static_assert(std::is_nothrow_move_assignable<Foo>::value, "Foo should be movable");
+    static_assert(!std::is_copy_constructible<Foo>::value, "Foo can not be copied");
+    static_assert(!std::is_copy_assignable<Foo>::value, "Foo can not be copied");
+    static_assert(std::is_copy_constructible<TestCopy>::value, "TestCopy is Copy");
+
+    std::vector<Foo> foos;
+    for (int i = 0; i < 10; ++i) {
+        foos.emplace_back(i, "x");
+    }
+    for (int i = 0; i < 10; ++i) {
+        EXPECT_EQ(i + 2, foos[i].f(1, 1));
+    }
+    Foo f = std::move(foos[3]);
+    EXPECT_EQ(5, f.f(1, 1));
+    EXPECT_TRUE(static_cast<FooOpaque *>(foos[3]) == nullptr);
+    std::swap(f, f);
+    EXPECT_EQ(5, f.f(1, 1));
+    foos[3] = std::move(foos[4]);
+    EXPECT_EQ(6, foos[3].f(1, 1));
+
+    std::vector<TestCopy> copies(3, TestCopy{ "a" });
+    copies.push_back(copies[0]);
+    for (const auto &c : copies) {
+        EXPECT_EQ(std::string("a"), c.get());
+    }
+
+    RustString s = f.ret_string();
+    std::swap(s, s);
+    EXPECT_EQ(std::string("x"), s.to_std_string());
+}
+
 TEST(RustString, Copy)
 {
     Foo foo(1, "AAAA");
@@ -836,6 +934,28 @@ TEST(RustString, Copy)
     ASSERT_EQ("AAAA", s3.to_std_string());
 }
 
+TEST(TestFallible, smokeTest)
+{
+    EXPECT_EQ(4, TestFallible::square(2));
+    try {
+        TestFallible::square(-2);
+        ADD_FAILURE() << "conversation error should be thrown";
+    } catch (const std::runtime_error &err) {
+        EXPECT_EQ(std::string("-2 is not positive"), err.what());
+    }
+    EXPECT_EQ(9, TestFallible::square(3));
+}
+
+TEST(TestPanic, nulInMessage)
+{
+    try {
+        TestPanic::panic_with_nul();
+        ADD_FAILURE() << "panic should be thrown as exception";
+    } catch (const std::runtime_error &err) {
+        EXPECT_EQ(std::string("ab"), err.what());
+    }
+}
+
 int main(int argc, char *argv[])
 {
     ::testing::InitGoogleTest(&argc, argv);
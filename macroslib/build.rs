@@ -18,16 +18,21 @@ impl VisitMut for FilterSwigAttrs {
     }
 }
 
+#[allow(dead_code)]
 mod file_cache {
     include!("src/file_cache.rs");
 }
 
 fn main() {
     let out_dir = env::var("OUT_DIR").unwrap();
-    for include_path in &[
-        Path::new("src/java_jni/jni-include.rs"),
-        Path::new("src/cpp/cpp-include.rs"),
-    ] {
+    let mut include_paths = Vec::new();
+    if env::var_os("CARGO_FEATURE_JAVA").is_some() {
+        include_paths.push(Path::new("src/java_jni/jni-include.rs"));
+    }
+    if env::var_os("CARGO_FEATURE_CPP").is_some() {
+        include_paths.push(Path::new("src/cpp/cpp-include.rs"));
+    }
+    for include_path in &include_paths {
         let src_cnt_tail = std::fs::read_to_string(include_path)
             .expect(&format!("Error during read {}", include_path.display()));
         let mut src_cnt = r#"
@@ -0,0 +1,324 @@
+//! Description of generated API in JSON format, for tools that need
+//! to know which classes, methods, enums and interfaces were generated,
+//! like documentation generators or API compatibility checks.
+
+use std::fmt::Write;
+
+use crate::{
+    typemap::ast::{fn_arg_type, DisplayToTokens},
+    types::{
+        FnDecl, ForeignEnumInfo, ForeignInterface, ForeignerClassInfo, MethodAccess, MethodVariant,
+    },
+};
+
+/// Foreign types of method's arguments (without self) and return type
+pub(crate) struct ForeignFnTypes {
+    pub(crate) input: Vec<String>,
+    pub(crate) output: String,
+}
+
+#[derive(Default)]
+pub(crate) struct ApiManifest {
+    classes: Vec<ApiClass>,
+    enums: Vec<ApiEnum>,
+    interfaces: Vec<ApiInterface>,
+}
+
+struct ApiClass {
+    name: String,
+    self_type: Option<String>,
+    doc_comments: Vec<String>,
+    methods: Vec<ApiMethod>,
+}
+
+struct ApiMethod {
+    name: String,
+    kind: &'static str,
+    access: &'static str,
+    rust_path: String,
+    doc_comments: Vec<String>,
+    params: Vec<ApiType>,
+    ret: ApiType,
+}
+
+struct ApiType {
+    rust: String,
+    foreign: String,
+}
+
+struct ApiEnum {
+    name: String,
+    doc_comments: Vec<String>,
+    items: Vec<(String, Vec<String>)>,
+}
+
+struct ApiInterface {
+    name: String,
+    doc_comments: Vec<String>,
+    methods: Vec<ApiMethod>,
+}
+
+impl ApiManifest {
+    pub(crate) fn add_class(&mut self, class: &ForeignerClassInfo, f_methods: &[ForeignFnTypes]) {
+        assert_eq!(class.methods.len(), f_methods.len());
+        let methods = class
+            .methods
+            .iter()
+            .zip(f_methods.iter())
+            .map(|(method, f_method)| {
+                let mut ret = api_ret(&method.fn_decl, f_method);
+                // constructor creates instance of class
+                if method.variant == MethodVariant::Constructor {
+                    ret.foreign = class.name.to_string();
+                }
+                ApiMethod {
+                    name: method.short_name(),
+                    kind: match method.variant {
+                        MethodVariant::Constructor => "constructor",
+                        MethodVariant::Method(_) => "method",
+                        MethodVariant::StaticMethod => "static_method",
+                    },
+                    access: match method.access {
+                        MethodAccess::Private => "private",
+                        MethodAccess::Protected => "protected",
+                        MethodAccess::Public => "public",
+                    },
+                    rust_path: DisplayToTokens(&method.rust_id).to_string(),
+                    doc_comments: method.doc_comments.clone(),
+                    params: api_params(&method.fn_decl, f_method),
+                    ret,
+                }
+            })
+            .collect();
+        self.classes.push(ApiClass {
+            name: class.name.to_string(),
+            self_type: class
+                .self_desc
+                .as_ref()
+                .map(|x| DisplayToTokens(&x.self_type).to_string()),
+            doc_comments: class.doc_comments.clone(),
+            methods,
+        });
+    }
+
+    pub(crate) fn add_enum(&mut self, enum_info: &ForeignEnumInfo) {
+        self.enums.push(ApiEnum {
+            name: enum_info.name.to_string(),
+            doc_comments: enum_info.doc_comments.clone(),
+            items: enum_info
+                .items
+                .iter()
+                .map(|item| (item.name.to_string(), item.doc_comments.clone()))
+                .collect(),
+        });
+    }
+
+    pub(crate) fn add_interface(
+        &mut self,
+        interface: &ForeignInterface,
+        f_methods: &[ForeignFnTypes],
+    ) {
+        assert_eq!(interface.items.len(), f_methods.len());
+        let methods = interface
+            .items
+            .iter()
+            .zip(f_methods.iter())
+            .map(|(method, f_method)| ApiMethod {
+                name: method.name.to_string(),
+                kind: "callback",
+                access: "public",
+                rust_path: DisplayToTokens(&method.rust_name).to_string(),
+                doc_comments: method.doc_comments.clone(),
+                params: api_params(&method.fn_decl, f_method),
+                ret: api_ret(&method.fn_decl, f_method),
+            })
+            .collect();
+        self.interfaces.push(ApiInterface {
+            name: interface.name.to_string(),
+            doc_comments: interface.doc_comments.clone(),
+            methods,
+        });
+    }
+
+    pub(crate) fn to_json(&self) -> String {
+        let mut out = String::new();
+        out.push_str("{\n  \"classes\": [");
+        for (i, class) in self.classes.iter().enumerate() {
+            out.push_str(if i == 0 { "\n" } else { ",\n" });
+            write!(
+                &mut out,
+                "    {{\n      \"name\": {},\n      \"self_type\": {},\n      \"doc\": {},\n      \"methods\": ",
+                json_str(&class.name),
+                class.self_type.as_ref().map_or_else(|| "null".to_string(), |x| json_str(x)),
+                json_doc(&class.doc_comments),
+            )
+            .expect("mem I/O failed");
+            write_methods(&mut out, &class.methods);
+            out.push_str("\n    }");
+        }
+        out.push_str(if self.classes.is_empty() {
+            "],\n"
+        } else {
+            "\n  ],\n"
+        });
+
+        out.push_str("  \"enums\": [");
+        for (i, fenum) in self.enums.iter().enumerate() {
+            out.push_str(if i == 0 { "\n" } else { ",\n" });
+            write!(
+                &mut out,
+                "    {{\n      \"name\": {},\n      \"doc\": {},\n      \"items\": [",
+                json_str(&fenum.name),
+                json_doc(&fenum.doc_comments),
+            )
+            .expect("mem I/O failed");
+            for (j, (name, doc_comments)) in fenum.items.iter().enumerate() {
+                write!(
+                    &mut out,
+                    "{}\n        {{ \"name\": {}, \"doc\": {} }}",
+                    if j == 0 { "" } else { "," },
+                    json_str(name),
+                    json_doc(doc_comments)
+                )
+                .expect("mem I/O failed");
+            }
+            out.push_str(if fenum.items.is_empty() {
+                "]\n    }"
+            } else {
+                "\n      ]\n    }"
+            });
+        }
+        out.push_str(if self.enums.is_empty() {
+            "],\n"
+        } else {
+            "\n  ],\n"
+        });
+
+        out.push_str("  \"interfaces\": [");
+        for (i, interface) in self.interfaces.iter().enumerate() {
+            out.push_str(if i == 0 { "\n" } else { ",\n" });
+            write!(
+                &mut out,
+                "    {{\n      \"name\": {},\n      \"doc\": {},\n      \"methods\": ",
+                json_str(&interface.name),
+                json_doc(&interface.doc_comments),
+            )
+            .expect("mem I/O failed");
+            write_methods(&mut out, &interface.methods);
+            out.push_str("\n    }");
+        }
+        out.push_str(if self.interfaces.is_empty() {
+            "]\n}\n"
+        } else {
+            "\n  ]\n}\n"
+        });
+        out
+    }
+}
+
+fn api_params(fn_decl: &FnDecl, f_method: &ForeignFnTypes) -> Vec<ApiType> {
+    // skip self argument
+    let skip_n = fn_decl.inputs.len() - f_method.input.len();
+    fn_decl
+        .inputs
+        .iter()
+        .skip(skip_n)
+        .zip(f_method.input.iter())
+        .map(|(arg, foreign)| ApiType {
+            rust: DisplayToTokens(fn_arg_type(arg)).to_string(),
+            foreign: foreign.clone(),
+        })
+        .collect()
+}
+
+fn api_ret(fn_decl: &FnDecl, f_method: &ForeignFnTypes) -> ApiType {
+    ApiType {
+        rust: match fn_decl.output {
+            syn::ReturnType::Default => "()".into(),
+            syn::ReturnType::Type(_, ref ty) => DisplayToTokens(&**ty).to_string(),
+        },
+        foreign: f_method.output.clone(),
+    }
+}
+
+fn write_methods(out: &mut String, methods: &[ApiMethod]) {
+    out.push('[');
+    for (i, method) in methods.iter().enumerate() {
+        out.push_str(if i == 0 { "\n" } else { ",\n" });
+        write!(
+            out,
+            "        {{\n          \"name\": {},\n          \"kind\": {},\n          \"access\": {},\n          \
+             \"rust_path\": {},\n          \"doc\": {},\n          \"params\": [",
+            json_str(&method.name),
+            json_str(method.kind),
+            json_str(method.access),
+            json_str(&method.rust_path),
+            json_doc(&method.doc_comments),
+        )
+        .expect("mem I/O failed");
+        for (j, param) in method.params.iter().enumerate() {
+            if j > 0 {
+                out.push_str(", ");
+            }
+            write_type(out, param);
+        }
+        out.push_str("],\n          \"ret\": ");
+        write_type(out, &method.ret);
+        out.push_str("\n        }");
+    }
+    out.push_str(if methods.is_empty() { "]" } else { "\n      ]" });
+}
+
+fn write_type(out: &mut String, ty: &ApiType) {
+    write!(
+        out,
+        "{{ \"rust\": {}, \"foreign\": {} }}",
+        json_str(&ty.rust),
+        json_str(&ty.foreign)
+    )
+    .expect("mem I/O failed");
+}
+
+fn json_doc(doc_comments: &[String]) -> String {
+    json_str(
+        &doc_comments
+            .iter()
+            .map(|x| x.trim())
+            .collect::<Vec<_>>()
+            .join("\n"),
+    )
+}
+
+fn json_str(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len() + 2);
+    ret.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            '\r' => ret.push_str("\\r"),
+            '\t' => ret.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => {
+                write!(&mut ret, "\\u{:04x}", ch as u32).expect("mem I/O failed")
+            }
+            ch => ret.push(ch),
+        }
+    }
+    ret.push('"');
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_str() {
+        assert_eq!(r#""a\"b\\c\nd\u0001""#, json_str("a\"b\\c\nd\u{1}"));
+        assert_eq!(
+            "{\n  \"classes\": [],\n  \"enums\": [],\n  \"interfaces\": []\n}\n",
+            ApiManifest::default().to_json()
+        );
+    }
+}
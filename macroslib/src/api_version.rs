@@ -0,0 +1,92 @@
+//! Version stamp of generated API: user provided version plus hash
+//! of processed source code. It is embedded into Rust code and foreign code
+//! as class `ApiVersion`, so it is possible to check at runtime that
+//! foreign code and native library were generated together.
+
+use std::hash::{Hash, Hasher};
+
+use rustc_hash::FxHasher;
+
+use crate::LanguageConfig;
+
+pub(crate) const API_VERSION_CLASS: &str = "ApiVersion";
+const RUST_FUNC_NAME: &str = "rust_swig_api_version";
+
+pub(crate) fn api_version_stamp<'a, I: Iterator<Item = &'a str>>(
+    version: &str,
+    codes: I,
+) -> String {
+    let mut hasher = FxHasher::default();
+    for code in codes {
+        code.hash(&mut hasher);
+    }
+    format!("{}-{:016x}", version, hasher.finish())
+}
+
+/// Rust function that returns `stamp`
+pub(crate) fn api_version_rust_code(stamp: &str) -> String {
+    format!(
+        r#"fn {func}() -> String {{ {stamp:?}.to_string() }}"#,
+        func = RUST_FUNC_NAME,
+        stamp = stamp
+    )
+}
+
+/// `foreigner_class!` for class with static method that returns stamp
+/// embedded into native library, and foreign constant with the same stamp
+pub(crate) fn api_version_class_code(config: &LanguageConfig, stamp: &str) -> String {
+    let foreigner_code = match config {
+        #[cfg(feature = "java")]
+        LanguageConfig::JavaConfig(_) => format!(
+            r#"
+    public static final String GENERATED = {stamp:?};
+    /**
+     * Check that native library and Java code were generated together,
+     * should be called after System.loadLibrary
+     * @throws IllegalStateException on mismatch
+     */
+    public static void check() {{
+        String nativeVersion = nativeVersion();
+        if (!GENERATED.equals(nativeVersion)) {{
+            throw new IllegalStateException("API version mismatch: Java code " + GENERATED
+                                            + ", native library " + nativeVersion);
+        }}
+    }}
+"#,
+            stamp = stamp
+        ),
+        #[cfg(feature = "cpp")]
+        LanguageConfig::CppConfig(_) => format!(
+            r#"
+    static constexpr const char *GENERATED = {stamp:?};
+    /// Check that native library and C++ code were generated together,
+    /// return false on mismatch
+    static bool check() {{ return nativeVersion().to_std_string() == GENERATED; }}
+"#,
+            stamp = stamp
+        ),
+    };
+    format!(
+        r#"class {class} {{
+    static_method {func}() -> String;
+    alias nativeVersion;
+    foreigner_code {code:?};
+}}"#,
+        class = API_VERSION_CLASS,
+        func = RUST_FUNC_NAME,
+        code = foreigner_code
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_version_stamp() {
+        let stamp = api_version_stamp("1.0", ["a", "b"].iter().cloned());
+        assert!(stamp.starts_with("1.0-"));
+        assert_eq!(stamp, api_version_stamp("1.0", ["a", "b"].iter().cloned()));
+        assert_ne!(stamp, api_version_stamp("1.0", ["a", "c"].iter().cloned()));
+    }
+}
@@ -0,0 +1,116 @@
+//! Command line interface to `rust_swig`, to use it without `build.rs`,
+//! for example from other build systems.
+
+use std::{env, path::PathBuf, process};
+
+use rust_swig::Generator;
+
+const USAGE: &str = r#"Usage: rust-swig [OPTIONS] --config <swig.toml> <INPUT>... <OUTPUT>
+       rust-swig [OPTIONS] --config <swig.toml> --check <INPUT>...
+       rust-swig --explain <CODE>
+
+Expand foreigner_class!, foreign_enum! and foreign_interface! in INPUT files,
+write Rust code to OUTPUT, and foreign code to directory from config.
+
+Options:
+    --check                  only verify that INPUT files can be expanded,
+                             do not write any files
+    --config <PATH>          configuration file, see `Generator::from_config_file`
+    --crate-name <NAME>      name of crate, used in diagnostic messages
+    --explain <CODE>         print description of code of error or warning,
+                             like SWIG0001, and exit
+    --pointer-width <N>      target pointer width (32 or 64), overrides
+                             value from configuration file, required if it
+                             is not set there or via CARGO_CFG_TARGET_POINTER_WIDTH
+    -h, --help               print this help
+    -V, --version            print version"#;
+
+struct Args {
+    config: PathBuf,
+    crate_name: String,
+    pointer_width: Option<usize>,
+    inputs: Vec<PathBuf>,
+    /// `None` for `--check`
+    output: Option<PathBuf>,
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Args, String> {
+    let mut config = None;
+    let mut crate_name = "crate".to_string();
+    let mut pointer_width = None;
+    let mut check = false;
+    let mut free_args = vec![];
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("No value after {}", name))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            "-V" | "--version" => {
+                println!("rust-swig {}", env!("CARGO_PKG_VERSION"));
+                process::exit(0);
+            }
+            "--check" => check = true,
+            "--explain" => {
+                let code = value(&arg)?;
+                match rust_swig::explain_diagnostic(&code) {
+                    Some(text) => {
+                        println!("{}", text);
+                        process::exit(0);
+                    }
+                    None => return Err(format!("Unknown code of error or warning: {}", code)),
+                }
+            }
+            "--config" => config = Some(PathBuf::from(value(&arg)?)),
+            "--crate-name" => crate_name = value(&arg)?,
+            "--pointer-width" => {
+                let width = value(&arg)?;
+                pointer_width = Some(match width.as_str() {
+                    "32" => 32,
+                    "64" => 64,
+                    _ => return Err(format!("Invalid pointer width: {}", width)),
+                });
+            }
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {}", arg)),
+            _ => free_args.push(PathBuf::from(arg)),
+        }
+    }
+    let config = config.ok_or_else(|| "--config is required".to_string())?;
+    let output = if check {
+        if free_args.is_empty() {
+            return Err("Expect INPUT paths".into());
+        }
+        None
+    } else {
+        if free_args.len() < 2 {
+            return Err("Expect INPUT and OUTPUT paths".into());
+        }
+        free_args.pop()
+    };
+    Ok(Args {
+        config,
+        crate_name,
+        pointer_width,
+        inputs: free_args,
+        output,
+    })
+}
+
+fn main() {
+    let args = parse_args(env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("{}\n\n{}", err, USAGE);
+        process::exit(1);
+    });
+    let mut swig_gen = Generator::from_config_file(&args.config);
+    if let Some(pointer_width) = args.pointer_width {
+        swig_gen = swig_gen.with_pointer_target_width(pointer_width);
+    }
+    match args.output {
+        Some(output) => swig_gen.expand_many(&args.crate_name, &args.inputs, &output),
+        None => swig_gen.check(&args.crate_name, &args.inputs),
+    }
+}
@@ -0,0 +1,102 @@
+//! Catching of panics in generated functions, shared by generators.
+//! Panic that unwinds across FFI boundary is undefined behavior,
+//! so if it is enabled globally or for class via `#[catch_panics]`,
+//! bodies of generated functions are wrapped into `std::panic::catch_unwind`,
+//! and panic is converted to exception of foreign language or abort.
+
+use crate::{types::ForeignerClassInfo, PanicAction};
+
+/// Action for panics in methods of `class`, `None` if they are not caught.
+/// If class is marked with `#[catch_panics]`, but there is no global action,
+/// exception `default_exception` is thrown
+pub(crate) fn class_panic_action(
+    class: &ForeignerClassInfo,
+    global: Option<&PanicAction>,
+    default_exception: &str,
+) -> Option<PanicAction> {
+    match class.catch_panics {
+        Some(false) => None,
+        Some(true) => Some(
+            global
+                .cloned()
+                .unwrap_or_else(|| PanicAction::Throw(default_exception.into())),
+        ),
+        None => global.cloned(),
+    }
+}
+
+/// Action used for all classes that catch panics,
+/// classes can only turn catching on or off
+pub(crate) fn panic_action<'a>(
+    classes: impl Iterator<Item = &'a ForeignerClassInfo>,
+    global: Option<&PanicAction>,
+    default_exception: &str,
+) -> Option<PanicAction> {
+    classes
+        .filter_map(|class| class_panic_action(class, global, default_exception))
+        .next()
+}
+
+/// Rust code of `swig_panic_message`, that converts payload of panic to message
+/// without nul bytes. With `backtrace` there is also `swig_init_panic_hook`,
+/// that installs hook to remember backtrace of panic, it is appended to message
+pub(crate) fn panic_message_code(backtrace: bool) -> String {
+    let mut code = r#"
+fn swig_panic_message(payload: &(dyn ::std::any::Any + Send)) -> String {
+    let mut msg = if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    };
+"#
+    .to_string();
+    if backtrace {
+        code.push_str(
+            r#"    if let Some(backtrace) = SWIG_PANIC_BACKTRACE.with(|x| x.borrow_mut().take()) {
+        msg.push('\n');
+        msg.push_str(&backtrace);
+    }
+"#,
+        );
+    }
+    code.push_str(
+        r#"    msg.retain(|ch| ch != '\0');
+    msg
+}
+"#,
+    );
+    if backtrace {
+        code.push_str(
+            r#"
+thread_local! {
+    static SWIG_PANIC_BACKTRACE: ::std::cell::RefCell<Option<String>> =
+        ::std::cell::RefCell::new(None);
+}
+
+fn swig_init_panic_hook() {
+    static INIT: ::std::sync::Once = ::std::sync::Once::new();
+    INIT.call_once(|| {
+        let prev_hook = ::std::panic::take_hook();
+        ::std::panic::set_hook(Box::new(move |info| {
+            let backtrace = ::std::backtrace::Backtrace::force_capture().to_string();
+            SWIG_PANIC_BACKTRACE.with(|x| *x.borrow_mut() = Some(backtrace));
+            prev_hook(info);
+        }));
+    });
+}
+"#,
+        );
+    }
+    code
+}
+
+/// Call of `swig_init_panic_hook` if it is generated
+pub(crate) fn init_panic_hook_call(backtrace: bool) -> &'static str {
+    if backtrace {
+        "    swig_init_panic_hook();\n"
+    } else {
+        ""
+    }
+}
@@ -10,13 +10,17 @@ use syn::{
 };
 
 use crate::{
+    diagnostic_codes::DiagnosticCode,
     error::{DiagnosticError, Result},
     source_registry::SourceId,
-    typemap::ast::{normalize_ty_lifetimes, DisplayToTokens},
+    typemap::{
+        ast::{normalize_ty_lifetimes, DisplayToTokens},
+        TypeMapConvRuleInfo,
+    },
     types::{
-        ForeignEnumInfo, ForeignEnumItem, ForeignInterface, ForeignInterfaceMethod,
-        ForeignerClassInfo, ForeignerMethod, MethodAccess, MethodVariant, SelfTypeDesc,
-        SelfTypeVariant,
+        ForeignEnumInfo, ForeignEnumItem, ForeignEnumItemField, ForeignInterface,
+        ForeignInterfaceMethod, ForeignerClassInfo, ForeignerMethod, MethodAccess, MethodVariant,
+        SelfTypeDesc, SelfTypeVariant,
     },
     LanguageConfig, FOREIGNER_CODE, FOREIGN_CODE,
 };
@@ -27,21 +31,50 @@ pub(crate) fn parse_foreigner_class(
     tokens: TokenStream,
 ) -> Result<ForeignerClassInfo> {
     match config {
+        #[cfg(feature = "cpp")]
         LanguageConfig::CppConfig(_) => {
             let mut class: CppClass =
                 syn::parse2(tokens).map_err(|err| DiagnosticError::from_syn_err(src_id, err))?;
             class.0.src_id = src_id;
+            check_no_self_by_value(&class.0)?;
             Ok(class.0)
         }
+        #[cfg(feature = "java")]
         LanguageConfig::JavaConfig(_) => {
             let mut class: JavaClass =
                 syn::parse2(tokens).map_err(|err| DiagnosticError::from_syn_err(src_id, err))?;
             class.0.src_id = src_id;
+            check_no_self_by_value(&class.0)?;
             Ok(class.0)
         }
     }
 }
 
+/// Method, that consumes `self`, would leave foreign object with handle
+/// to moved-out Rust object, and other methods could be called via it
+fn check_no_self_by_value(class: &ForeignerClassInfo) -> Result<()> {
+    for method in &class.methods {
+        match method.variant {
+            MethodVariant::Method(SelfTypeVariant::Default)
+            | MethodVariant::Method(SelfTypeVariant::Mut) => {
+                return Err(DiagnosticError::new(
+                    class.src_id,
+                    method.fn_decl.inputs[0].span(),
+                    format!(
+                        "method {} of class {} takes self by value, foreign object \
+                         would keep handle to consumed Rust object, use &self or &mut self",
+                        DisplayToTokens(&method.rust_id),
+                        class.name
+                    ),
+                )
+                .with_code(DiagnosticCode::SelfByValue));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
 pub(crate) fn parse_foreign_enum(src_id: SourceId, tokens: TokenStream) -> Result<ForeignEnumInfo> {
     let mut f_enum: ForeignEnumInfoParser =
         syn::parse2(tokens).map_err(|err| DiagnosticError::from_syn_err(src_id, err))?;
@@ -95,11 +128,34 @@ mod kw {
 struct Attrs {
     doc_comments: Vec<String>,
     derive_list: Vec<String>,
+    cpp_namespace: Option<String>,
+    single_threaded: bool,
+    catch_panics: Option<bool>,
+    direct_buffer: bool,
+    critical_arrays: bool,
+    completable_future: bool,
+    fast_native: bool,
+    critical_native: bool,
+    awaitable: bool,
 }
 
-fn parse_attrs(input: ParseStream, parse_derive_attrs: bool) -> syn::Result<Attrs> {
+fn parse_attrs(
+    input: ParseStream,
+    parse_derive_attrs: bool,
+    method_attrs_lang: Option<Language>,
+) -> syn::Result<Attrs> {
+    let parse_method_attrs = method_attrs_lang.is_some();
     let mut doc_comments = vec![];
     let mut derive_list = vec![];
+    let mut cpp_namespace = None;
+    let mut single_threaded = false;
+    let mut catch_panics = None;
+    let mut direct_buffer = false;
+    let mut critical_arrays = false;
+    let mut completable_future = false;
+    let mut fast_native = false;
+    let mut critical_native = false;
+    let mut awaitable = false;
 
     if input.fork().call(syn::Attribute::parse_outer).is_ok() {
         let attr: Vec<syn::Attribute> = input.call(syn::Attribute::parse_outer)?;
@@ -126,6 +182,56 @@ fn parse_attrs(input: ParseStream, parse_derive_attrs: bool) -> syn::Result<Attr
                         }
                     }
                 }
+                syn::Meta::NameValue(syn::MetaNameValue {
+                    ref ident,
+                    lit: syn::Lit::Str(ref lit_str),
+                    ..
+                }) if ident == "cpp_namespace" && parse_derive_attrs => {
+                    let namespace = lit_str.value();
+                    if namespace
+                        .split("::")
+                        .any(|x| syn::parse_str::<Ident>(x).is_err())
+                    {
+                        return Err(syn::Error::new(
+                            lit_str.span(),
+                            format!("Invalid C++ namespace: {}", namespace),
+                        ));
+                    }
+                    cpp_namespace = Some(namespace);
+                }
+                syn::Meta::Word(ref ident) if ident == "single_threaded" && parse_derive_attrs => {
+                    single_threaded = true;
+                }
+                syn::Meta::Word(ref ident) if ident == "catch_panics" && parse_derive_attrs => {
+                    catch_panics = Some(true);
+                }
+                syn::Meta::NameValue(syn::MetaNameValue {
+                    ref ident,
+                    lit: syn::Lit::Bool(ref lit_bool),
+                    ..
+                }) if ident == "catch_panics" && parse_derive_attrs => {
+                    catch_panics = Some(lit_bool.value);
+                }
+                syn::Meta::Word(ref ident) if ident == "direct_buffer" && parse_method_attrs => {
+                    direct_buffer = true;
+                }
+                syn::Meta::Word(ref ident) if ident == "critical_arrays" && parse_method_attrs => {
+                    critical_arrays = true;
+                }
+                syn::Meta::Word(ref ident)
+                    if ident == "completable_future" && parse_method_attrs =>
+                {
+                    completable_future = true;
+                }
+                syn::Meta::Word(ref ident) if ident == "fast_native" && parse_method_attrs => {
+                    fast_native = true;
+                }
+                syn::Meta::Word(ref ident) if ident == "critical_native" && parse_method_attrs => {
+                    critical_native = true;
+                }
+                syn::Meta::Word(ref ident) if ident == "awaitable" && parse_method_attrs => {
+                    awaitable = true;
+                }
                 _ => {
                     return Err(syn::Error::new(
                         a.span(),
@@ -138,14 +244,36 @@ fn parse_attrs(input: ParseStream, parse_derive_attrs: bool) -> syn::Result<Attr
             }
         }
     }
+    // the same `foreigner_class!` may be processed for both languages,
+    // so method attributes of other language are accepted and ignored
+    match method_attrs_lang {
+        Some(Language::Cpp) => {
+            direct_buffer = false;
+            critical_arrays = false;
+            completable_future = false;
+            fast_native = false;
+            critical_native = false;
+        }
+        Some(Language::Java) => awaitable = false,
+        None => {}
+    }
     Ok(Attrs {
         doc_comments,
         derive_list,
+        cpp_namespace,
+        single_threaded,
+        catch_panics,
+        direct_buffer,
+        critical_arrays,
+        completable_future,
+        fast_native,
+        critical_native,
+        awaitable,
     })
 }
 
 fn parse_doc_comments(input: ParseStream) -> syn::Result<Vec<String>> {
-    let Attrs { doc_comments, .. } = parse_attrs(input, false)?;
+    let Attrs { doc_comments, .. } = parse_attrs(input, false, None)?;
     Ok(doc_comments)
 }
 
@@ -153,7 +281,11 @@ fn do_parse_foreigner_class(lang: Language, input: ParseStream) -> syn::Result<F
     let Attrs {
         doc_comments: class_doc_comments,
         derive_list,
-    } = parse_attrs(&input, lang == Language::Cpp)?;
+        cpp_namespace,
+        single_threaded,
+        catch_panics,
+        ..
+    } = parse_attrs(&input, true, None)?;
     debug!(
         "parse_foreigner_class: class comment {:?}",
         class_doc_comments
@@ -170,13 +302,24 @@ fn do_parse_foreigner_class(lang: Language, input: ParseStream) -> syn::Result<F
     let mut has_dummy_constructor = false;
     let mut constructor_ret_type: Option<Type> = None;
     let mut methods = Vec::with_capacity(10);
+    let mut typemaps = Vec::new();
 
     static CONSTRUCTOR: &str = "constructor";
     static METHOD: &str = "method";
     static STATIC_METHOD: &str = "static_method";
+    static FOREIGN_TYPEMAP: &str = "foreign_typemap";
 
     while !content.is_empty() {
-        let doc_comments = parse_doc_comments(&&content)?;
+        let Attrs {
+            doc_comments,
+            direct_buffer,
+            critical_arrays,
+            completable_future,
+            fast_native,
+            critical_native,
+            awaitable,
+            ..
+        } = parse_attrs(&content, false, Some(lang))?;
         let mut access = if content.peek(kw::private) {
             content.parse::<kw::private>()?;
             MethodAccess::Private
@@ -206,6 +349,16 @@ fn do_parse_foreigner_class(lang: Language, input: ParseStream) -> syn::Result<F
             continue;
         }
 
+        if func_type_name == FOREIGN_TYPEMAP {
+            content.parse::<Token![!]>()?;
+            let rules;
+            parenthesized!(rules in content);
+            typemaps.push(rules.parse::<TypeMapConvRuleInfo>()?);
+            debug!("class scoped typemap {:?}", typemaps.last());
+            content.parse::<Token![;]>()?;
+            continue;
+        }
+
         let mut func_type = match func_type_name {
             _ if func_type_name == CONSTRUCTOR => {
                 if has_dummy_constructor {
@@ -279,6 +432,12 @@ fn do_parse_foreigner_class(lang: Language, input: ParseStream) -> syn::Result<F
                 name_alias: None,
                 access,
                 doc_comments,
+                direct_buffer,
+                critical_arrays,
+                completable_future,
+                fast_native,
+                critical_native,
+                awaitable,
             });
             has_dummy_constructor = true;
             continue;
@@ -400,6 +559,12 @@ fn do_parse_foreigner_class(lang: Language, input: ParseStream) -> syn::Result<F
             name_alias: func_name_alias,
             access,
             doc_comments,
+            direct_buffer,
+            critical_arrays,
+            completable_future,
+            fast_native,
+            critical_native,
+            awaitable,
         });
     }
 
@@ -447,6 +612,10 @@ fn do_parse_foreigner_class(lang: Language, input: ParseStream) -> syn::Result<F
         foreigner_code,
         doc_comments: class_doc_comments,
         copy_derived,
+        cpp_namespace,
+        single_threaded,
+        catch_panics,
+        typemaps,
     })
 }
 
@@ -466,12 +635,14 @@ impl Parse for ForeignEnumInfoParser {
             let f_item_name = item_parser.parse::<Ident>()?;
             item_parser.parse::<Token![=]>()?;
             let item_name = item_parser.call(syn::Path::parse_mod_style)?;
+            let fields = parse_enum_item_fields(&item_parser)?;
             item_parser.parse::<Token![,]>()?;
 
             items.push(ForeignEnumItem {
                 name: f_item_name,
                 rust_name: item_name,
                 doc_comments,
+                fields,
             });
         }
 
@@ -484,6 +655,33 @@ impl Parse for ForeignEnumInfoParser {
     }
 }
 
+/// Parse payload of enum item: `(T1, T2)` or `{ a: T1, b: T2 }`
+fn parse_enum_item_fields(input: ParseStream) -> syn::Result<Vec<ForeignEnumItemField>> {
+    if input.peek(syn::token::Paren) {
+        let content;
+        parenthesized!(content in input);
+        let types: Punctuated<Type, Token![,]> = content.parse_terminated(Type::parse)?;
+        Ok(types
+            .into_iter()
+            .map(|ty| ForeignEnumItemField { name: None, ty })
+            .collect())
+    } else if input.peek(syn::token::Brace) {
+        let content;
+        braced!(content in input);
+        let fields: Punctuated<syn::Field, Token![,]> =
+            content.parse_terminated(syn::Field::parse_named)?;
+        Ok(fields
+            .into_iter()
+            .map(|f| ForeignEnumItemField {
+                name: f.ident,
+                ty: f.ty,
+            })
+            .collect())
+    } else {
+        Ok(vec![])
+    }
+}
+
 struct ForeignInterfaceParser(ForeignInterface);
 
 impl Parse for ForeignInterfaceParser {
@@ -572,6 +770,45 @@ mod tests {
         let java_class = test_parse::<JavaClass>(mac.tts);
         assert!(!java_class.0.copy_derived);
 
+        let mac: syn::Macro = parse_quote! {
+            foreigner_class!(#[derive(Copy)] class Foo {
+                self_type Foo;
+                constructor Foo::new(_: i32) -> Foo;
+                method Foo::f(&self) -> i32;
+                method Foo::clone(&self) -> Foo;
+            })
+        };
+        let java_class = test_parse::<JavaClass>(mac.tts);
+        assert!(java_class.0.copy_derived);
+
+        let mac: syn::Macro = parse_quote! {
+            foreigner_class!(class Foo {
+                self_type Foo;
+                constructor Foo::new() -> Foo;
+                #[direct_buffer]
+                method Foo::f(&self, _: &[u8]);
+                #[critical_arrays]
+                method Foo::g(&self, _: &[i8]);
+                #[completable_future]
+                method Foo::h(&self, _: Box<dyn FnOnce(i32) + Send>);
+                #[fast_native]
+                method Foo::i(&self) -> i32;
+                #[critical_native]
+                static_method Foo::j(_: i32) -> i32;
+            })
+        };
+        let java_class = test_parse::<JavaClass>(mac.tts);
+        assert!(java_class.0.methods[1].direct_buffer);
+        assert!(!java_class.0.methods[1].critical_arrays);
+        assert!(!java_class.0.methods[2].direct_buffer);
+        assert!(java_class.0.methods[2].critical_arrays);
+        assert!(!java_class.0.methods[2].completable_future);
+        assert!(java_class.0.methods[3].completable_future);
+        assert!(java_class.0.methods[4].fast_native);
+        assert!(!java_class.0.methods[4].critical_native);
+        assert!(!java_class.0.methods[5].fast_native);
+        assert!(java_class.0.methods[5].critical_native);
+
         let mac: syn::Macro = parse_quote! {
             foreigner_class!(class Foo {
                 self_type SomeType;
@@ -602,6 +839,36 @@ mod tests {
         };
         let enum_ = parse_foreign_enum(SourceId::none(), mac.tts).unwrap();
         assert_eq!("MyEnum", enum_.name.to_string());
+        assert!(!enum_.is_data_enum());
+    }
+
+    #[test]
+    fn test_parse_foreign_data_enum() {
+        let _ = env_logger::try_init();
+        let mac: syn::Macro = parse_quote! {
+            foreign_enum!(enum Shape {
+                Circle = Shape::Circle(f64),
+                Rect = Shape::Rect { w: f64, h: f64 },
+                Empty = Shape::Empty,
+            })
+        };
+        let enum_ = parse_foreign_enum(SourceId::none(), mac.tts).unwrap();
+        assert!(enum_.is_data_enum());
+        let fields: Vec<Vec<String>> = enum_
+            .items
+            .iter()
+            .map(|item| {
+                item.fields
+                    .iter()
+                    .enumerate()
+                    .map(|(i, f)| f.foreign_name(i))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            vec![vec!["f0".to_string()], vec!["w".into(), "h".into()], vec![]],
+            fields
+        );
     }
 
     #[test]
@@ -619,6 +886,150 @@ mod tests {
         assert!(class.0.copy_derived);
     }
 
+    #[test]
+    fn test_parse_foreign_class_single_threaded() {
+        let _ = env_logger::try_init();
+        let mac: syn::Macro = parse_quote! {
+            foreigner_class!(#[single_threaded] class Foo {
+                self_type Foo;
+                constructor Foo::new() -> Rc<RefCell<Foo>>;
+                method Foo::f(&self);
+            })
+        };
+        let class: JavaClass = test_parse(mac.tts);
+        assert!(class.0.single_threaded);
+        assert!(!class.0.copy_derived);
+    }
+
+    #[test]
+    fn test_parse_foreign_class_catch_panics() {
+        let _ = env_logger::try_init();
+        let mac: syn::Macro = parse_quote! {
+            foreigner_class!(#[catch_panics] class Foo {
+                self_type Foo;
+                constructor Foo::new() -> Foo;
+                method Foo::f(&self);
+            })
+        };
+        let class: JavaClass = test_parse(mac.tts);
+        assert_eq!(Some(true), class.0.catch_panics);
+        let mac: syn::Macro = parse_quote! {
+            foreigner_class!(#[catch_panics = false] class Foo {
+                self_type Foo;
+                constructor Foo::new() -> Foo;
+                method Foo::f(&self);
+            })
+        };
+        let class: CppClass = test_parse(mac.tts);
+        assert_eq!(Some(false), class.0.catch_panics);
+        assert!(!class.0.single_threaded);
+    }
+
+    #[test]
+    fn test_parse_foreign_class_with_cpp_namespace() {
+        let _ = env_logger::try_init();
+        let mac: syn::Macro = parse_quote! {
+            foreigner_class!(#[cpp_namespace = "acme::ui"] class Foo {
+                self_type SomeType;
+                private constructor = empty;
+                method SomeType::f(&self);
+            })
+        };
+        let class: CppClass = test_parse(mac.tts);
+        assert_eq!(
+            Some("acme::ui"),
+            class.0.cpp_namespace.as_ref().map(String::as_str)
+        );
+    }
+
+    #[test]
+    fn test_parse_foreign_class_with_awaitable() {
+        let _ = env_logger::try_init();
+        let mac: syn::Macro = parse_quote! {
+            foreigner_class!(class Foo {
+                self_type SomeType;
+                private constructor = empty;
+                method SomeType::f(&self);
+                #[awaitable]
+                method SomeType::g(&self, _: Box<dyn FnOnce(i32) + Send>);
+            })
+        };
+        let class: CppClass = test_parse(mac.tts.clone());
+        assert!(!class.0.methods[1].awaitable);
+        assert!(class.0.methods[2].awaitable);
+        let class: JavaClass = test_parse(mac.tts);
+        assert!(!class.0.methods[2].awaitable);
+    }
+
+    #[test]
+    fn test_parse_foreign_class_with_attrs_of_both_langs() {
+        let _ = env_logger::try_init();
+        let mac: syn::Macro = parse_quote! {
+            foreigner_class!(class Foo {
+                self_type Foo;
+                constructor Foo::new() -> Foo;
+                #[direct_buffer]
+                #[awaitable]
+                method Foo::f(&self, _: Box<dyn FnOnce(i32) + Send>);
+                #[critical_arrays]
+                method Foo::g(&self, _: &[i8]);
+                #[fast_native]
+                method Foo::h(&self) -> i32;
+                #[critical_native]
+                static_method Foo::i(_: i32) -> i32;
+                #[completable_future]
+                method Foo::j(&self, _: Box<dyn FnOnce(i32) + Send>);
+            })
+        };
+        let java_class: JavaClass = test_parse(mac.tts.clone());
+        let java_methods = &java_class.0.methods;
+        assert!(java_methods[1].direct_buffer && !java_methods[1].awaitable);
+        assert!(java_methods[2].critical_arrays);
+        assert!(java_methods[3].fast_native);
+        assert!(java_methods[4].critical_native);
+        assert!(java_methods[5].completable_future);
+
+        let cpp_class: CppClass = test_parse(mac.tts);
+        let cpp_methods = &cpp_class.0.methods;
+        assert!(cpp_methods[1].awaitable && !cpp_methods[1].direct_buffer);
+        assert!(!cpp_methods[2].critical_arrays);
+        assert!(!cpp_methods[3].fast_native);
+        assert!(!cpp_methods[4].critical_native);
+        assert!(!cpp_methods[5].completable_future);
+
+        let mac: syn::Macro = parse_quote! {
+            foreigner_class!(class Foo {
+                self_type Foo;
+                #[no_such_attr]
+                method Foo::f(&self);
+            })
+        };
+        assert!(syn::parse2::<JavaClass>(mac.tts.clone()).is_err());
+        assert!(syn::parse2::<CppClass>(mac.tts).is_err());
+    }
+
+    #[test]
+    fn test_parse_foreign_class_with_self_by_value() {
+        let _ = env_logger::try_init();
+        let mac: syn::Macro = parse_quote! {
+            foreigner_class!(class Foo {
+                self_type Foo;
+                constructor Foo::new() -> Foo;
+                method Foo::f(&self);
+                method Foo::into_bar(self) -> Bar;
+            })
+        };
+        let class: JavaClass = test_parse(mac.tts);
+        assert_eq!(
+            MethodVariant::Method(SelfTypeVariant::Default),
+            class.0.methods[2].variant
+        );
+        let err = check_no_self_by_value(&class.0).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("[SWIG0006] method Foo :: into_bar of class Foo takes self by value"));
+    }
+
     fn test_parse<T>(tokens: TokenStream) -> T
     where
         T: Parse,
@@ -628,5 +1039,4 @@ mod tests {
             syn::parse2(tokens).unwrap_or_else(|err| panic_on_syn_error("test_parse", code, err));
         class
     }
-
 }
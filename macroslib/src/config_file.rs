@@ -0,0 +1,885 @@
+//! Support of `swig.toml`: configuration of `Generator` in TOML format,
+//! for example:
+//!
+//! ```toml
+//! [java]
+//! output_dir = "src/main/java/com/example"
+//! package = "com.example"
+//! null_annotation_package = "android.support.annotation"
+//!
+//! [generator]
+//! type_maps = ["typemaps/common.rs"]
+//! features = ["foo"]
+//! warn_unused_rules = true
+//!
+//! [[generator.formatters]]
+//! extensions = ["java"]
+//! command = "google-java-format"
+//! args = ["-"]
+//! ```
+//!
+//! Instead of `output_dir` in `[java]` section `android_module = "path"`
+//! can be used, see `JavaConfig::android_library_module`.
+//! `module-info.java` is generated if there is `[java.module_info]` section
+//! with `name` and `native_library`, see `JavaConfig::module_info`.
+//! Section `[java.exceptions]` maps Rust error types to Java exceptions,
+//! like `ParseError = "ParseException"`, see `JavaConfig::exception_for_error`.
+//! Section `[java.native_library]` with `class`, `name` and optional `loader`
+//! generates loading of native library, see `JavaConfig::load_native_library`,
+//! and `[java.native_library.targets]` maps Rust targets to names of library.
+//! Key `log_bridge` in `[java]` section (`"android"`, `"java_util_logging"` or `"slf4j"`)
+//! or in `[cpp]` section (`true`) generates routing of Rust `log` crate records,
+//! see `JavaConfig::log_bridge` and `CppConfig::log_bridge`.
+//! Key `catch_panics` in `[java]` or `[cpp]` section (`"abort"` or name of exception)
+//! and `panic_backtrace` turn on catching of panics, see `JavaConfig::catch_panics`
+//! and `CppConfig::catch_panics`.
+//! Key `tracing_spans = true` in `[java]` or `[cpp]` section instruments generated
+//! functions with spans of `tracing` crate, see `JavaConfig::tracing_spans`.
+//!
+//! Relative paths are resolved relative to directory of configuration file.
+
+use std::path::{Path, PathBuf};
+
+use toml::{value::Table, Value};
+
+use crate::{diagnostic_codes::DiagnosticCode, Generator, LanguageConfig, PanicAction};
+#[cfg(feature = "cpp")]
+use crate::{CppConfig, CppOptional, CppResult, CppStandard, CppStrView, CppString, CppVariant};
+#[cfg(feature = "java")]
+use crate::{JavaConfig, JavaLogBackend};
+
+type Result<T> = std::result::Result<T, String>;
+
+pub(crate) fn generator_from_toml(base_dir: &Path, config: &str) -> Result<Generator> {
+    let mut config: Table = toml::from_str(config).map_err(|err| format!("{}", err))?;
+    let java = take_table(&mut config, "", "java")?;
+    let cpp = take_table(&mut config, "", "cpp")?;
+    let lang_cfg = match (java, cpp) {
+        #[cfg(feature = "java")]
+        (Some(java), None) => LanguageConfig::JavaConfig(parse_java_config(base_dir, java)?),
+        #[cfg(not(feature = "java"))]
+        (Some(_), None) => return Err("[java] section requires feature \"java\"".into()),
+        #[cfg(feature = "cpp")]
+        (None, Some(cpp)) => LanguageConfig::CppConfig(parse_cpp_config(base_dir, cpp)?),
+        #[cfg(not(feature = "cpp"))]
+        (None, Some(_)) => return Err("[cpp] section requires feature \"cpp\"".into()),
+        (Some(_), Some(_)) => return Err("both [java] and [cpp] sections are set".into()),
+        (None, None) => return Err("[java] or [cpp] section is required".into()),
+    };
+    let mut generator = Generator::new(lang_cfg);
+    if let Some(gen_cfg) = take_table(&mut config, "", "generator")? {
+        generator = parse_generator_config(base_dir, generator, gen_cfg)?;
+    }
+    check_all_used("", &config)?;
+    Ok(generator)
+}
+
+#[cfg(feature = "java")]
+fn parse_java_config(base_dir: &Path, mut cfg: Table) -> Result<JavaConfig> {
+    const SECTION: &str = "java";
+    let output_dir = take_path(&mut cfg, SECTION, "output_dir", base_dir)?;
+    let android_module = take_path(&mut cfg, SECTION, "android_module", base_dir)?;
+    let package = required(SECTION, "package", take_str(&mut cfg, SECTION, "package")?)?;
+    let mut java_cfg = match (output_dir, android_module) {
+        (Some(output_dir), None) => JavaConfig::new(output_dir, package),
+        (None, Some(module_dir)) => {
+            JavaConfig::new(PathBuf::new(), package).android_library_module(module_dir)
+        }
+        (Some(_), Some(_)) => {
+            return Err(format!(
+                "{}.output_dir and {}.android_module can not be used together",
+                SECTION, SECTION
+            ))
+        }
+        (None, None) => return Err(format!("{}.output_dir is required", SECTION)),
+    };
+    if let Some(package) = take_str(&mut cfg, SECTION, "null_annotation_package")? {
+        java_cfg = java_cfg.use_null_annotation_from_package(package);
+    }
+    if let Some(package) = take_str(&mut cfg, SECTION, "optional_package")? {
+        java_cfg = java_cfg.use_optional_package(package);
+    }
+    if let Some(enable) = take_bool(&mut cfg, SECTION, "optional")? {
+        java_cfg = java_cfg.use_optional(enable);
+    }
+    if let Some(enable) = take_bool(&mut cfg, SECTION, "register_natives")? {
+        java_cfg = java_cfg.use_register_natives(enable);
+    }
+    if let Some(enable) = take_bool(&mut cfg, SECTION, "records")? {
+        java_cfg = java_cfg.use_records(enable);
+    }
+    if let Some(enable) = take_bool(&mut cfg, SECTION, "lists")? {
+        java_cfg = java_cfg.use_java_lists(enable);
+    }
+    if let Some(enable) = take_bool(&mut cfg, SECTION, "streams")? {
+        java_cfg = java_cfg.use_java_streams(enable);
+    }
+    if let Some(enable) = take_bool(&mut cfg, SECTION, "unchecked_exceptions")? {
+        java_cfg = java_cfg.use_unchecked_exceptions(enable);
+    }
+    if let Some(enable) = take_bool(&mut cfg, SECTION, "kotlin_friendly")? {
+        java_cfg = java_cfg.use_kotlin_friendly(enable);
+    }
+    if let Some(level) = take_u32(&mut cfg, SECTION, "min_api_level")? {
+        java_cfg = java_cfg.min_api_level(level);
+    }
+    if let Some(backend) = take_str(&mut cfg, SECTION, "log_bridge")? {
+        java_cfg = java_cfg.log_bridge(match backend.as_str() {
+            "android" => JavaLogBackend::AndroidLog,
+            "java_util_logging" => JavaLogBackend::JavaUtilLogging,
+            "slf4j" => JavaLogBackend::Slf4j,
+            _ => {
+                return Err(format!(
+                    "{}: expect \"android\", \"java_util_logging\" or \"slf4j\", got \"{}\"",
+                    key_name(SECTION, "log_bridge"),
+                    backend
+                ));
+            }
+        });
+    }
+    if let Some(action) = take_str(&mut cfg, SECTION, "catch_panics")? {
+        java_cfg = java_cfg.catch_panics(panic_action(action));
+    }
+    if let Some(backtrace) = take_bool(&mut cfg, SECTION, "panic_backtrace")? {
+        java_cfg = java_cfg.panic_backtrace(backtrace);
+    }
+    if let Some(tracing_spans) = take_bool(&mut cfg, SECTION, "tracing_spans")? {
+        java_cfg = java_cfg.tracing_spans(tracing_spans);
+    }
+    if let Some(mut module_cfg) = take_table(&mut cfg, SECTION, "module_info")? {
+        const MODULE_SECTION: &str = "java.module_info";
+        let name = required(
+            MODULE_SECTION,
+            "name",
+            take_str(&mut module_cfg, MODULE_SECTION, "name")?,
+        )?;
+        let native_library = required(
+            MODULE_SECTION,
+            "native_library",
+            take_str(&mut module_cfg, MODULE_SECTION, "native_library")?,
+        )?;
+        check_all_used(MODULE_SECTION, &module_cfg)?;
+        java_cfg = java_cfg.module_info(name, native_library);
+    }
+    if let Some(mut exceptions_cfg) = take_table(&mut cfg, SECTION, "exceptions")? {
+        const EXCEPTIONS_SECTION: &str = "java.exceptions";
+        let rust_types: Vec<String> = exceptions_cfg.keys().cloned().collect();
+        for rust_ty in rust_types {
+            if let Some(class) = take_str(&mut exceptions_cfg, EXCEPTIONS_SECTION, &rust_ty)? {
+                java_cfg = java_cfg.exception_for_error(rust_ty, class);
+            }
+        }
+    }
+    if let Some(mut library_cfg) = take_table(&mut cfg, SECTION, "native_library")? {
+        const LIBRARY_SECTION: &str = "java.native_library";
+        let class = required(
+            LIBRARY_SECTION,
+            "class",
+            take_str(&mut library_cfg, LIBRARY_SECTION, "class")?,
+        )?;
+        let name = required(
+            LIBRARY_SECTION,
+            "name",
+            take_str(&mut library_cfg, LIBRARY_SECTION, "name")?,
+        )?;
+        java_cfg = java_cfg.load_native_library(class, name);
+        if let Some(loader) = take_str(&mut library_cfg, LIBRARY_SECTION, "loader")? {
+            java_cfg = java_cfg.native_library_loader(loader);
+        }
+        if let Some(mut targets_cfg) = take_table(&mut library_cfg, LIBRARY_SECTION, "targets")? {
+            const TARGETS_SECTION: &str = "java.native_library.targets";
+            let targets: Vec<String> = targets_cfg.keys().cloned().collect();
+            for target in targets {
+                if let Some(name) = take_str(&mut targets_cfg, TARGETS_SECTION, &target)? {
+                    java_cfg = java_cfg.native_library_for_target(target, name);
+                }
+            }
+        }
+        check_all_used(LIBRARY_SECTION, &library_cfg)?;
+    }
+    check_all_used(SECTION, &cfg)?;
+    Ok(java_cfg)
+}
+
+#[cfg(feature = "cpp")]
+fn parse_cpp_config(base_dir: &Path, mut cfg: Table) -> Result<CppConfig> {
+    const SECTION: &str = "cpp";
+    let output_dir = required(
+        SECTION,
+        "output_dir",
+        take_path(&mut cfg, SECTION, "output_dir", base_dir)?,
+    )?;
+    let namespace = required(
+        SECTION,
+        "namespace",
+        take_str(&mut cfg, SECTION, "namespace")?,
+    )?;
+    let mut cpp_cfg = CppConfig::new(output_dir, namespace);
+    // before other options, it only changes defaults
+    if let Some(standard) = take_str(&mut cfg, SECTION, "standard")? {
+        cpp_cfg = cpp_cfg.cpp_standard(match standard.as_str() {
+            "c++11" => CppStandard::Cpp11,
+            "c++14" => CppStandard::Cpp14,
+            "c++17" => CppStandard::Cpp17,
+            "c++20" => CppStandard::Cpp20,
+            _ => {
+                return Err(format!(
+                    "{}: expect \"c++11\", \"c++14\", \"c++17\" or \"c++20\", got \"{}\"",
+                    key_name(SECTION, "standard"),
+                    standard
+                ));
+            }
+        });
+    }
+    if take_bool(&mut cfg, SECTION, "use_boost")?.unwrap_or(false) {
+        cpp_cfg = cpp_cfg.use_boost();
+    }
+    if let Some(boost) = take_std17_or_boost(&mut cfg, SECTION, "optional")? {
+        cpp_cfg = cpp_cfg.cpp_optional(if boost {
+            CppOptional::Boost
+        } else {
+            CppOptional::Std17
+        });
+    }
+    if let Some(boost) = take_std17_or_boost(&mut cfg, SECTION, "variant")? {
+        cpp_cfg = cpp_cfg.cpp_variant(if boost {
+            CppVariant::Boost
+        } else {
+            CppVariant::Std17
+        });
+    }
+    if let Some(result) = take_str(&mut cfg, SECTION, "result")? {
+        cpp_cfg = cpp_cfg.cpp_result(match result.as_str() {
+            "variant" => CppResult::Variant,
+            "std_expected" => CppResult::Std23Expected,
+            "rust_expected" => CppResult::RustExpected,
+            _ => {
+                return Err(format!(
+                    "{}: expect \"variant\", \"std_expected\" or \"rust_expected\", got \"{}\"",
+                    key_name(SECTION, "result"),
+                    result
+                ));
+            }
+        });
+    }
+    if let Some(boost) = take_std17_or_boost(&mut cfg, SECTION, "str_view")? {
+        cpp_cfg = cpp_cfg.cpp_str_view(if boost {
+            CppStrView::Boost
+        } else {
+            CppStrView::Std17
+        });
+    }
+    if let Some(string) = take_str(&mut cfg, SECTION, "string")? {
+        cpp_cfg = cpp_cfg.cpp_string(match string.as_str() {
+            "utf8" => CppString::Utf8,
+            "u16string" => CppString::U16String,
+            "wstring" => CppString::WString,
+            _ => {
+                return Err(format!(
+                    "{}: expect \"utf8\", \"u16string\" or \"wstring\", got \"{}\"",
+                    key_name(SECTION, "string"),
+                    string
+                ));
+            }
+        });
+    }
+    if let Some(separate) = take_bool(&mut cfg, SECTION, "separate_impl_headers")? {
+        cpp_cfg = cpp_cfg.separate_impl_headers(separate);
+    }
+    if let Some(std_span) = take_bool(&mut cfg, SECTION, "std_span")? {
+        cpp_cfg = cpp_cfg.std_span(std_span);
+    }
+    if let Some(shared_ptr) = take_bool(&mut cfg, SECTION, "shared_ptr")? {
+        cpp_cfg = cpp_cfg.shared_ptr(shared_ptr);
+    }
+    if let Some(factories) = take_bool(&mut cfg, SECTION, "unique_ptr_factories")? {
+        cpp_cfg = cpp_cfg.unique_ptr_factories(factories);
+    }
+    if let Some(c_abi_only) = take_bool(&mut cfg, SECTION, "c_abi_only")? {
+        cpp_cfg = cpp_cfg.c_abi_only(c_abi_only);
+    }
+    if let Some(pimpl) = take_bool(&mut cfg, SECTION, "pimpl")? {
+        cpp_cfg = cpp_cfg.pimpl(pimpl);
+    }
+    if let Some(prefix) = take_str(&mut cfg, SECTION, "symbol_prefix")? {
+        cpp_cfg = cpp_cfg.symbol_prefix(prefix);
+    }
+    if let Some(name) = take_str(&mut cfg, SECTION, "linker_exports")? {
+        cpp_cfg = cpp_cfg.linker_exports(name);
+    }
+    if let Some(name) = take_str(&mut cfg, SECTION, "dll_api")? {
+        cpp_cfg = cpp_cfg.dll_api(name);
+    }
+    if let Some(log_bridge) = take_bool(&mut cfg, SECTION, "log_bridge")? {
+        cpp_cfg = cpp_cfg.log_bridge(log_bridge);
+    }
+    if let Some(action) = take_str(&mut cfg, SECTION, "catch_panics")? {
+        cpp_cfg = cpp_cfg.catch_panics(panic_action(action));
+    }
+    if let Some(backtrace) = take_bool(&mut cfg, SECTION, "panic_backtrace")? {
+        cpp_cfg = cpp_cfg.panic_backtrace(backtrace);
+    }
+    if let Some(tracing_spans) = take_bool(&mut cfg, SECTION, "tracing_spans")? {
+        cpp_cfg = cpp_cfg.tracing_spans(tracing_spans);
+    }
+    if let Some(name) = take_str(&mut cfg, SECTION, "umbrella_header")? {
+        cpp_cfg = cpp_cfg.umbrella_header(name);
+    }
+    if let Some(package) = take_str(&mut cfg, SECTION, "cmake_package")? {
+        let rust_library = required(
+            SECTION,
+            "rust_library",
+            take_path(&mut cfg, SECTION, "rust_library", base_dir)?,
+        )?;
+        cpp_cfg = cpp_cfg.cmake_package(package, rust_library);
+    }
+    check_all_used(SECTION, &cfg)?;
+    Ok(cpp_cfg)
+}
+
+fn parse_generator_config(
+    base_dir: &Path,
+    mut generator: Generator,
+    mut cfg: Table,
+) -> Result<Generator> {
+    const SECTION: &str = "generator";
+    if let Some(width) = take(&mut cfg, SECTION, "pointer_target_width", Value::as_integer)? {
+        if width != 32 && width != 64 {
+            return Err(format!(
+                "{}.pointer_target_width: expect 32 or 64, got {}",
+                SECTION, width
+            ));
+        }
+        generator = generator.with_pointer_target_width(width as usize);
+    }
+    if let Some(os) = take_str(&mut cfg, SECTION, "target_os")? {
+        generator = generator.with_target_os(&os);
+    }
+    if let Some(arch) = take_str(&mut cfg, SECTION, "target_arch")? {
+        generator = generator.with_target_arch(&arch);
+    }
+    if let Some(family) = take_str(&mut cfg, SECTION, "target_family")? {
+        generator = generator.with_target_family(&family);
+    }
+    for feature in take_str_array(&mut cfg, SECTION, "features")? {
+        generator = generator.with_feature(&feature);
+    }
+    for path in take_str_array(&mut cfg, SECTION, "type_maps")? {
+        generator = generator.merge_type_map_from_file(base_dir.join(path));
+    }
+    if let Some(rules) = take(&mut cfg, SECTION, "remove_conversions", |v| {
+        v.as_array().cloned()
+    })? {
+        for rule in rules {
+            match rule.as_array().map(|x| x.as_slice()) {
+                Some([Value::String(from), Value::String(to)]) => {
+                    generator = generator.remove_conversion(from, to);
+                }
+                _ => {
+                    return Err(format!(
+                        "{}.remove_conversions: expect array of [\"from\", \"to\"] pairs, got {}",
+                        SECTION, rule
+                    ))
+                }
+            }
+        }
+    }
+    if let Some(warn) = take_bool(&mut cfg, SECTION, "warn_unused_rules")? {
+        generator = generator.warn_unused_rules(warn);
+    }
+    if let Some(validate) = take_bool(&mut cfg, SECTION, "validate_conversation_graph")? {
+        generator = generator.validate_conversation_graph(validate);
+    }
+    for code in take_str_array(&mut cfg, SECTION, "allow_warnings")? {
+        match DiagnosticCode::from_id(&code) {
+            Some(x) if x.is_warning() => generator = generator.allow_warning(&code),
+            _ => {
+                return Err(format!(
+                    "{}: {} is not code of warning",
+                    key_name(SECTION, "allow_warnings"),
+                    code
+                ))
+            }
+        }
+    }
+    if let Some(dir) = take_path(&mut cfg, SECTION, "cache_builtin_type_maps", base_dir)? {
+        generator = generator.cache_builtin_type_maps(dir);
+    }
+    if let Some(dir) = take_path(&mut cfg, SECTION, "cache_expansion", base_dir)? {
+        generator = generator.cache_expansion(dir);
+    }
+    if let Some(path) = take_path(&mut cfg, SECTION, "dump_conversation_graph", base_dir)? {
+        generator = generator.dump_conversation_graph(path);
+    }
+    if let Some(path) = take_path(&mut cfg, SECTION, "dump_expanded_rust_code", base_dir)? {
+        generator = generator.dump_expanded_rust_code(path);
+    }
+    if let Some(path) = take_path(&mut cfg, SECTION, "api_manifest", base_dir)? {
+        generator = generator.write_api_manifest(path);
+    }
+    if let Some(targets) = take(&mut cfg, SECTION, "additional_targets", |v| {
+        v.as_array().cloned()
+    })? {
+        const TARGET_SECTION: &str = "generator.additional_targets";
+        for target in targets {
+            let mut target = match target {
+                Value::Table(x) => x,
+                _ => {
+                    return Err(format!(
+                        "{}: expect array of tables, got {}",
+                        TARGET_SECTION, target
+                    ))
+                }
+            };
+            let width = required(
+                TARGET_SECTION,
+                "pointer_width",
+                take(
+                    &mut target,
+                    TARGET_SECTION,
+                    "pointer_width",
+                    Value::as_integer,
+                )?,
+            )?;
+            if width != 32 && width != 64 {
+                return Err(format!(
+                    "{}.pointer_width: expect 32 or 64, got {}",
+                    TARGET_SECTION, width
+                ));
+            }
+            let os = take_str(&mut target, TARGET_SECTION, "os")?;
+            check_all_used(TARGET_SECTION, &target)?;
+            generator = generator.with_additional_target(width as usize, os.as_deref());
+        }
+    }
+    if let Some(version) = take_str(&mut cfg, SECTION, "api_version")? {
+        generator = generator.with_api_version(&version);
+    }
+    if let Some(enable) = take_bool(&mut cfg, SECTION, "report_errors_via_compile_error")? {
+        generator = generator.report_errors_via_compile_error(enable);
+    }
+    if let Some(enable) = take_bool(&mut cfg, SECTION, "print_profiling_report")? {
+        generator = generator.print_profiling_report(enable);
+    }
+    if let Some(enable) = take_bool(&mut cfg, SECTION, "source_location_comments")? {
+        generator = generator.source_location_comments(enable);
+    }
+    if let Some(enable) = take_bool(&mut cfg, SECTION, "check_rust_signatures")? {
+        generator = generator.check_rust_signatures(enable);
+    }
+    if let Some(enable) = take_bool(&mut cfg, SECTION, "check_send")? {
+        generator = generator.check_send(enable);
+    }
+    if let Some(enable) = take_bool(&mut cfg, SECTION, "strict_conversions")? {
+        generator = generator.strict_conversions(enable);
+    }
+    if let Some(formatters) = take(&mut cfg, SECTION, "formatters", |v| v.as_array().cloned())? {
+        const FMT_SECTION: &str = "generator.formatters";
+        for formatter in formatters {
+            let mut formatter = match formatter {
+                Value::Table(x) => x,
+                _ => {
+                    return Err(format!(
+                        "{}: expect array of tables, got {}",
+                        FMT_SECTION, formatter
+                    ))
+                }
+            };
+            let extensions = take_str_array(&mut formatter, FMT_SECTION, "extensions")?;
+            let command = required(
+                FMT_SECTION,
+                "command",
+                take_str(&mut formatter, FMT_SECTION, "command")?,
+            )?;
+            let args = take_str_array(&mut formatter, FMT_SECTION, "args")?;
+            check_all_used(FMT_SECTION, &formatter)?;
+            let extensions: Vec<&str> = extensions.iter().map(String::as_str).collect();
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            generator = generator.format_generated_files(&extensions, &command, &args);
+        }
+    }
+    check_all_used(SECTION, &cfg)?;
+    Ok(generator)
+}
+
+fn key_name(section: &str, key: &str) -> String {
+    if section.is_empty() {
+        key.into()
+    } else {
+        format!("{}.{}", section, key)
+    }
+}
+
+fn take<T, F>(cfg: &mut Table, section: &str, key: &str, conv: F) -> Result<Option<T>>
+where
+    F: FnOnce(&Value) -> Option<T>,
+{
+    match cfg.remove(key) {
+        Some(val) => conv(&val).map(Some).ok_or_else(|| {
+            format!(
+                "{}: unexpected type of value {}",
+                key_name(section, key),
+                val
+            )
+        }),
+        None => Ok(None),
+    }
+}
+
+fn take_table(cfg: &mut Table, section: &str, key: &str) -> Result<Option<Table>> {
+    take(cfg, section, key, |v| v.as_table().cloned())
+}
+
+fn take_str(cfg: &mut Table, section: &str, key: &str) -> Result<Option<String>> {
+    take(cfg, section, key, |v| v.as_str().map(str::to_string))
+}
+
+fn take_bool(cfg: &mut Table, section: &str, key: &str) -> Result<Option<bool>> {
+    take(cfg, section, key, Value::as_bool)
+}
+
+#[cfg(feature = "java")]
+fn take_u32(cfg: &mut Table, section: &str, key: &str) -> Result<Option<u32>> {
+    take(cfg, section, key, |v| {
+        let x = v.as_integer()?;
+        if i64::from(x as u32) == x {
+            Some(x as u32)
+        } else {
+            None
+        }
+    })
+}
+
+fn take_path(
+    cfg: &mut Table,
+    section: &str,
+    key: &str,
+    base_dir: &Path,
+) -> Result<Option<PathBuf>> {
+    Ok(take_str(cfg, section, key)?.map(|path| base_dir.join(path)))
+}
+
+fn take_str_array(cfg: &mut Table, section: &str, key: &str) -> Result<Vec<String>> {
+    let arr = take(cfg, section, key, |v| {
+        v.as_array()?
+            .iter()
+            .map(|x| x.as_str().map(str::to_string))
+            .collect::<Option<Vec<String>>>()
+    })?;
+    Ok(arr.unwrap_or_default())
+}
+
+/// true for "boost", false for "std17"
+#[cfg(feature = "cpp")]
+fn take_std17_or_boost(cfg: &mut Table, section: &str, key: &str) -> Result<Option<bool>> {
+    match take_str(cfg, section, key)? {
+        Some(ref x) if x == "boost" => Ok(Some(true)),
+        Some(ref x) if x == "std17" => Ok(Some(false)),
+        Some(x) => Err(format!(
+            "{}: expect \"std17\" or \"boost\", got \"{}\"",
+            key_name(section, key),
+            x
+        )),
+        None => Ok(None),
+    }
+}
+
+fn required<T>(section: &str, key: &str, val: Option<T>) -> Result<T> {
+    val.ok_or_else(|| format!("{} is required", key_name(section, key)))
+}
+
+/// "abort" or name of exception
+fn panic_action(action: String) -> PanicAction {
+    if action == "abort" {
+        PanicAction::Abort
+    } else {
+        PanicAction::Throw(action)
+    }
+}
+
+fn check_all_used(section: &str, cfg: &Table) -> Result<()> {
+    match cfg.keys().next() {
+        Some(key) => Err(format!("unknown option {}", key_name(section, key))),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(all(feature = "java", feature = "cpp"))]
+    #[test]
+    fn test_generator_from_toml() {
+        let base_dir = Path::new("/tmp/project");
+        let generator = generator_from_toml(
+            base_dir,
+            r#"
+[cpp]
+output_dir = "cpp-api"
+namespace = "example"
+use_boost = true
+optional = "std17"
+separate_impl_headers = true
+umbrella_header = "example.hpp"
+std_span = true
+result = "rust_expected"
+string = "u16string"
+shared_ptr = true
+unique_ptr_factories = true
+c_abi_only = true
+pimpl = true
+symbol_prefix = "example_"
+linker_exports = "example"
+dll_api = "EXAMPLE"
+log_bridge = true
+catch_panics = "abort"
+tracing_spans = true
+standard = "c++20"
+
+[generator]
+pointer_target_width = 32
+features = ["a", "b"]
+remove_conversions = [["jstring", "String"]]
+warn_unused_rules = true
+allow_warnings = ["SWIG1001", "swig1004"]
+print_profiling_report = true
+source_location_comments = true
+check_rust_signatures = true
+check_send = true
+strict_conversions = true
+api_version = "0.1"
+dump_conversation_graph = "graph.dot"
+api_manifest = "api.json"
+
+[[generator.formatters]]
+extensions = ["hpp", "h"]
+command = "clang-format"
+args = ["-style=file"]
+
+[[generator.additional_targets]]
+pointer_width = 64
+os = "ios"
+"#,
+        )
+        .unwrap();
+        match generator.config {
+            LanguageConfig::CppConfig(ref cpp_cfg) => {
+                assert_eq!(base_dir.join("cpp-api"), cpp_cfg.output_dir);
+                assert_eq!("example", cpp_cfg.namespace_name);
+                assert!(match cpp_cfg.cpp_optional {
+                    CppOptional::Std17 => true,
+                    CppOptional::Boost => false,
+                });
+                assert!(match cpp_cfg.cpp_variant {
+                    CppVariant::Std17 => false,
+                    CppVariant::Boost => true,
+                });
+                assert!(cpp_cfg.separate_impl_headers);
+                assert!(cpp_cfg.std_span);
+                assert!(cpp_cfg.shared_ptr);
+                assert!(cpp_cfg.unique_ptr_factories);
+                assert!(cpp_cfg.c_abi_only);
+                assert!(cpp_cfg.pimpl);
+                assert_eq!(Some("example_"), cpp_cfg.symbol_prefix.as_deref());
+                assert_eq!(Some("example"), cpp_cfg.linker_exports.as_deref());
+                assert_eq!(Some("EXAMPLE"), cpp_cfg.dll_api.as_deref());
+                assert!(cpp_cfg.log_bridge);
+                assert_eq!(Some(PanicAction::Abort), cpp_cfg.catch_panics);
+                assert!(!cpp_cfg.panic_backtrace);
+                assert!(cpp_cfg.tracing_spans);
+                assert_eq!(Some(CppStandard::Cpp20), cpp_cfg.cpp_standard);
+                assert_eq!(CppResult::RustExpected, cpp_cfg.cpp_result);
+                assert_eq!(CppString::U16String, cpp_cfg.cpp_string);
+                assert_eq!(Some("example.hpp"), cpp_cfg.umbrella_header.as_deref());
+            }
+            LanguageConfig::JavaConfig(_) => panic!("expect c++ config"),
+        }
+        assert_eq!(32, generator.pointer_target_width);
+        assert!(generator.warn_unused_rules);
+        assert!(generator
+            .conv_map
+            .warnings()
+            .is_allowed(DiagnosticCode::OverriddenConvRule));
+        assert!(generator
+            .conv_map
+            .warnings()
+            .is_allowed(DiagnosticCode::UnusedRule));
+        assert!(!generator
+            .conv_map
+            .warnings()
+            .is_allowed(DiagnosticCode::NoForeignClass));
+        assert!(generator.print_profiling_report);
+        assert!(generator.source_location_comments);
+        assert!(generator.check_rust_signatures);
+        assert!(generator.check_send);
+        assert!(generator.conv_map.strict_conversions());
+        assert!(!generator.validate_conv_graph);
+        assert_eq!(
+            vec![("jstring".to_string(), "String".to_string())],
+            generator.removed_conversions
+        );
+        assert_eq!(
+            Some(base_dir.join("graph.dot")),
+            generator.conv_graph_dump_path
+        );
+        assert_eq!(Some(base_dir.join("api.json")), generator.api_manifest_path);
+        assert_eq!(Some("0.1"), generator.api_version.as_deref());
+        assert_eq!(1, generator.formatters.len());
+        assert_eq!(vec!["hpp", "h"], generator.formatters[0].extensions);
+        assert_eq!("clang-format", generator.formatters[0].command);
+        assert_eq!(vec!["-style=file"], generator.formatters[0].args);
+        assert_eq!(
+            vec![(64, Some("ios".to_string()))],
+            generator.additional_targets
+        );
+
+        let generator = generator_from_toml(
+            base_dir,
+            r#"
+[java]
+output_dir = "/abs/java"
+package = "com.example"
+optional_package = "java8.util"
+optional = false
+register_natives = true
+records = true
+lists = true
+streams = true
+unchecked_exceptions = true
+kotlin_friendly = true
+min_api_level = 21
+log_bridge = "slf4j"
+catch_panics = "java.lang.IllegalStateException"
+panic_backtrace = true
+
+[java.module_info]
+name = "com.example.api"
+native_library = "example"
+
+[java.exceptions]
+ParseError = "ParseException"
+"io::Error" = "java.io.IOException"
+
+[java.native_library]
+class = "Api"
+name = "example"
+loader = "com.example.NativeLoader"
+
+[java.native_library.targets]
+"x86_64-pc-windows-gnu" = "example_win"
+"#,
+        )
+        .unwrap();
+        match generator.config {
+            LanguageConfig::JavaConfig(ref java_cfg) => {
+                assert_eq!(Path::new("/abs/java"), java_cfg.output_dir);
+                assert_eq!("com.example", java_cfg.package_name);
+                assert_eq!("java8.util", java_cfg.optional_package);
+                assert!(!java_cfg.java_optional);
+                assert_eq!(None, java_cfg.null_annotation_package);
+                assert!(java_cfg.register_natives);
+                assert!(java_cfg.records);
+                assert!(java_cfg.java_lists);
+                assert!(java_cfg.java_streams);
+                assert!(java_cfg.unchecked_exceptions);
+                assert!(java_cfg.kotlin_friendly);
+                assert_eq!(Some(JavaLogBackend::Slf4j), java_cfg.log_bridge);
+                assert_eq!(
+                    Some(PanicAction::Throw(
+                        "java.lang.IllegalStateException".to_string()
+                    )),
+                    java_cfg.catch_panics
+                );
+                assert!(java_cfg.panic_backtrace);
+                assert!(!java_cfg.tracing_spans);
+                assert_eq!(Some(21), java_cfg.min_api_level);
+                assert_eq!(
+                    Some(("com.example.api".to_string(), "example".to_string())),
+                    java_cfg.module_info
+                );
+                assert_eq!(
+                    vec![
+                        ("ParseError".to_string(), "ParseException".to_string()),
+                        ("io::Error".to_string(), "java.io.IOException".to_string())
+                    ],
+                    java_cfg.exceptions
+                );
+                assert_eq!(
+                    Some(("Api".to_string(), "example".to_string())),
+                    java_cfg.load_native_library
+                );
+                assert_eq!(
+                    Some("com.example.NativeLoader".to_string()),
+                    java_cfg.native_library_loader
+                );
+                assert_eq!(
+                    vec![(
+                        "x86_64-pc-windows-gnu".to_string(),
+                        "example_win".to_string()
+                    )],
+                    java_cfg.native_library_targets
+                );
+            }
+            LanguageConfig::CppConfig(_) => panic!("expect java config"),
+        }
+
+        let generator = generator_from_toml(
+            base_dir,
+            "[java]\nandroid_module = \"rust-lib\"\npackage = \"com.example.lib\"\n",
+        )
+        .unwrap();
+        match generator.config {
+            LanguageConfig::JavaConfig(ref java_cfg) => {
+                assert_eq!(
+                    base_dir.join("rust-lib/src/main/java/com/example/lib"),
+                    java_cfg.output_dir
+                );
+                assert_eq!(Some(base_dir.join("rust-lib")), java_cfg.android_module);
+            }
+            LanguageConfig::CppConfig(_) => panic!("expect java config"),
+        }
+
+        for (config, err) in &[
+            ("", "[java] or [cpp] section is required"),
+            (
+                "[java]\noutput_dir = \"java\"\n",
+                "java.package is required",
+            ),
+            (
+                "[java]\npackage = \"a\"\n",
+                "java.output_dir is required",
+            ),
+            (
+                "[java]\noutput_dir = \"java\"\npackage = \"a\"\n[java.module_info]\nname = \"a\"\n",
+                "java.module_info.native_library is required",
+            ),
+            (
+                "[java]\noutput_dir = \"java\"\npackage = \"a\"\n[java.native_library]\nname = \"a\"\n",
+                "java.native_library.class is required",
+            ),
+            (
+                "[java]\noutput_dir = \"java\"\npackage = \"a\"\npackag = \"b\"\n",
+                "unknown option java.packag",
+            ),
+            (
+                "[cpp]\noutput_dir = \"c\"\nnamespace = \"a\"\noptional = \"std14\"\n",
+                "cpp.optional: expect \"std17\" or \"boost\", got \"std14\"",
+            ),
+            (
+                "[cpp]\noutput_dir = \"c\"\nnamespace = \"a\"\n[generator]\nwarn_unused_rules = 1\n",
+                "generator.warn_unused_rules: unexpected type of value 1",
+            ),
+            (
+                "[cpp]\noutput_dir = \"c\"\nnamespace = \"a\"\n[[generator.formatters]]\nextensions = [\"h\"]\n",
+                "generator.formatters.command is required",
+            ),
+            (
+                "[cpp]\noutput_dir = \"c\"\nnamespace = \"a\"\n[generator]\nallow_warnings = [\"SWIG0001\"]\n",
+                "generator.allow_warnings: SWIG0001 is not code of warning",
+            ),
+        ] {
+            assert_eq!(*err, generator_from_toml(base_dir, config).err().unwrap());
+        }
+    }
+}
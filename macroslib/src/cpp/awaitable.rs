@@ -0,0 +1,194 @@
+use petgraph::Direction;
+use proc_macro2::TokenStream;
+use syn::{parse_quote, spanned::Spanned, Type};
+
+use crate::{
+    cpp::{map_type::map_type, CppForeignTypeInfo},
+    error::{DiagnosticError, Result},
+    typemap::{
+        ast::{fn_arg_type, if_box_fn_once_return_args, normalize_ty_lifetimes},
+        ForeignTypeInfo, FROM_VAR_TEMPLATE,
+    },
+    types::{ForeignerClassInfo, ForeignerMethod, MethodVariant},
+    CppConfig, TypeMap,
+};
+
+/// Name of generated Rust variable, that holds completion callback
+pub(in crate::cpp) const COMPLETION_VAR: &str = "swig_completion";
+
+/// `T` for completion callback `Box<dyn FnOnce(T) + Send>`, `None` for `FnOnce()`
+fn completion_arg(class: &ForeignerClassInfo, method: &ForeignerMethod) -> Result<Option<Type>> {
+    let invalid_method = |msg: &str| DiagnosticError::new(class.src_id, method.span(), msg);
+    if method.variant == MethodVariant::Constructor {
+        return Err(invalid_method(
+            "#[awaitable] can not be used with constructor",
+        ));
+    }
+    if let syn::ReturnType::Type(_, ref ty) = method.fn_decl.output {
+        return Err(DiagnosticError::new(
+            class.src_id,
+            ty.span(),
+            "#[awaitable] method should not return value",
+        ));
+    }
+    let skip_n = match method.variant {
+        MethodVariant::Method(_) => 1,
+        _ => 0,
+    };
+    let last_arg_ty = method
+        .fn_decl
+        .inputs
+        .iter()
+        .skip(skip_n)
+        .last()
+        .map(fn_arg_type)
+        .and_then(if_box_fn_once_return_args);
+    match last_arg_ty {
+        Some(mut args) if args.len() <= 1 => Ok(args.pop()),
+        _ => Err(invalid_method(
+            "#[awaitable] method should have Box<dyn FnOnce(T) + Send> as last argument",
+        )),
+    }
+}
+
+/// Value passed to completion callback of `#[awaitable]` method,
+/// `void` for `FnOnce()`
+pub(in crate::cpp) fn map_completion_type(
+    conv_map: &mut TypeMap,
+    cpp_cfg: &CppConfig,
+    class: &ForeignerClassInfo,
+    method: &ForeignerMethod,
+) -> Result<CppForeignTypeInfo> {
+    let mut ret: CppForeignTypeInfo = match completion_arg(class, method)? {
+        Some(ty) => {
+            let rust_ty = conv_map.find_or_alloc_rust_type(&ty, class.src_id);
+            map_type(
+                conv_map,
+                cpp_cfg,
+                rust_ty,
+                Direction::Outgoing,
+                (class.src_id, ty.span()),
+            )?
+        }
+        None => ForeignTypeInfo {
+            name: "void".into(),
+            correspoding_rust_type: conv_map.find_or_alloc_rust_type_no_src_id(&parse_type! { () }),
+        }
+        .into(),
+    };
+    ret.provides_by_module.push("\"rust_awaitable.h\"".into());
+    Ok(ret)
+}
+
+/// `RustAwaitable<T>` returned by C++ method
+pub(in crate::cpp) fn cpp_awaitable_type(completion: &CppForeignTypeInfo) -> String {
+    let value_ty = match completion.cpp_converter {
+        Some(ref conv) => conv.typename.as_str(),
+        None => completion.base.name.as_str(),
+    };
+    format!("RustAwaitable<{}>", value_ty.replace("struct", "").trim())
+}
+
+/// Parameters of C function for completion callback
+pub(in crate::cpp) fn c_completion_args(completion: &CppForeignTypeInfo) -> String {
+    if completion.base.name == "void" {
+        "void *swig_opaque, void (*swig_done)(void *)".into()
+    } else {
+        format!(
+            "void *swig_opaque, void (*swig_done)(void *, {})",
+            completion.base.name
+        )
+    }
+}
+
+/// Completion callback for C function, that completes `RustAwaitable` named `ret`
+pub(in crate::cpp) fn cpp_completion_args_for_c(completion: &CppForeignTypeInfo) -> String {
+    let awaitable = cpp_awaitable_type(completion);
+    if completion.base.name == "void" {
+        return format!(
+            "ret.opaque(), [](void *opaque) noexcept {{ {}::complete(opaque); }}",
+            awaitable
+        );
+    }
+    let value = match completion.cpp_converter {
+        Some(ref conv) => conv.converter.replace(FROM_VAR_TEMPLATE, "x"),
+        None => "x".into(),
+    };
+    format!(
+        "ret.opaque(), [](void *opaque, {c_ty} x) noexcept {{ {awaitable}::complete(opaque, {value}); }}",
+        c_ty = completion.base.name,
+        awaitable = awaitable,
+        value = value,
+    )
+}
+
+/// Parameters of generated Rust function for completion callback
+pub(in crate::cpp) fn rust_completion_args(
+    conv_map: &TypeMap,
+    completion: &CppForeignTypeInfo,
+) -> String {
+    let value_ty = if completion.base.name == "void" {
+        String::new()
+    } else {
+        format!(
+            ", {}",
+            conv_map[completion.base.correspoding_rust_type].typename()
+        )
+    };
+    format!(
+        "swig_opaque: *mut ::std::os::raw::c_void, \
+         swig_done: extern \"C\" fn(*mut ::std::os::raw::c_void{})",
+        value_ty
+    )
+}
+
+/// Code that creates completion callback for last argument of `#[awaitable]` method
+pub(in crate::cpp) fn rust_completion_code(
+    conv_map: &mut TypeMap,
+    class: &ForeignerClassInfo,
+    method: &ForeignerMethod,
+    completion: &CppForeignTypeInfo,
+) -> Result<(Vec<TokenStream>, String)> {
+    let completion_ty = method
+        .fn_decl
+        .inputs
+        .iter()
+        .last()
+        .map(|arg| normalize_ty_lifetimes(fn_arg_type(arg)))
+        .expect("completion argument was checked");
+    let (deps, closure_arg, conv, done_args) = match completion_arg(class, method)? {
+        Some(ty) => {
+            let rust_ty = conv_map.find_or_alloc_rust_type(&ty, class.src_id);
+            let (deps, conv) = conv_map.convert_rust_types(
+                rust_ty,
+                completion.base.correspoding_rust_type,
+                "x",
+                "()",
+                (class.src_id, ty.span()),
+            )?;
+            (
+                deps,
+                format!("x: {}", conv_map[rust_ty].normalized_name),
+                conv,
+                "swig_opaque, x",
+            )
+        }
+        None => (Vec::new(), String::new(), String::new(), "swig_opaque"),
+    };
+    let code = format!(
+        r#"
+    let {completion} = SwigAwaitCompletion::new(swig_opaque);
+    let {completion}: {completion_ty} = Box::new(move |{closure_arg}| {{
+        let swig_opaque = {completion}.take();
+{conv}
+        swig_done({done_args});
+    }});
+"#,
+        completion = COMPLETION_VAR,
+        completion_ty = completion_ty,
+        closure_arg = closure_arg,
+        conv = conv,
+        done_args = done_args,
+    );
+    Ok((deps, code))
+}
@@ -0,0 +1,79 @@
+use std::path::Path;
+
+use super::write_generated_file;
+use crate::{error::Result, CppConfig};
+
+/// Generate `<package>Config.cmake` and `<package>.pc` in output directory,
+/// so `find_package(<package> CONFIG)` or `pkg-config` can be used
+/// to find generated headers and Rust library,
+/// `sources` are generated C++ files that user should compile
+pub(in crate::cpp) fn generate_cmake_package(
+    cfg: &CppConfig,
+    package: &str,
+    rust_library: &Path,
+    sources: &[String],
+) -> Result<()> {
+    let include_dir = cfg.output_dir.display().to_string().replace('\\', "/");
+    let rust_library = rust_library.display().to_string().replace('\\', "/");
+    let rust_library_name = Path::new(&rust_library)
+        .file_stem()
+        .map(|x| x.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let link_name = rust_library_name
+        .strip_prefix("lib")
+        .unwrap_or(&rust_library_name);
+    let lib_dir = Path::new(&rust_library)
+        .parent()
+        .map(|x| x.display().to_string().replace('\\', "/"))
+        .unwrap_or_default();
+    let interface_sources = if sources.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "\n    INTERFACE_SOURCES \"{}\"",
+            sources
+                .iter()
+                .map(|src| format!("{}/{}", include_dir, src))
+                .collect::<Vec<_>>()
+                .join(";")
+        )
+    };
+
+    write_generated_file(
+        &cfg.output_dir.join(format!("{}Config.cmake", package)),
+        &format!(
+            r#"# Automaticaly generated by rust_swig
+if(NOT TARGET {package}::{package})
+  find_package(Threads REQUIRED)
+  add_library({package}::{package} UNKNOWN IMPORTED)
+  set_target_properties({package}::{package} PROPERTIES
+    IMPORTED_LOCATION "{rust_library}"
+    INTERFACE_INCLUDE_DIRECTORIES "{include_dir}"
+    INTERFACE_LINK_LIBRARIES "Threads::Threads;${{CMAKE_DL_LIBS}}"{interface_sources})
+endif()
+set({package}_FOUND TRUE)
+"#,
+            package = package,
+            rust_library = rust_library,
+            include_dir = include_dir,
+            interface_sources = interface_sources,
+        ),
+    )?;
+    write_generated_file(
+        &cfg.output_dir.join(format!("{}.pc", package)),
+        &format!(
+            r#"# Automaticaly generated by rust_swig
+Name: {package}
+Description: C++ API of Rust library {package}
+Version: 0
+Cflags: -I{include_dir}
+Libs: -L{lib_dir} -l{link_name}
+Libs.private: -lpthread -ldl
+"#,
+            package = package,
+            include_dir = include_dir,
+            lib_dir = lib_dir,
+            link_name = link_name,
+        ),
+    )
+}
@@ -41,6 +41,16 @@ mod swig_foreign_types_map {
     #![swig_rust_type = "CRustVecF32"]
     #![swig_foreigner_type = "struct CRustVecF64"]
     #![swig_rust_type = "CRustVecF64"]
+    #![swig_foreigner_type = "struct CRustVecI8"]
+    #![swig_rust_type = "CRustVecI8"]
+    #![swig_foreigner_type = "struct CRustVecI16"]
+    #![swig_rust_type = "CRustVecI16"]
+    #![swig_foreigner_type = "struct CRustVecU16"]
+    #![swig_rust_type = "CRustVecU16"]
+    #![swig_foreigner_type = "struct CRustVecI64"]
+    #![swig_rust_type = "CRustVecI64"]
+    #![swig_foreigner_type = "struct CRustVecU64"]
+    #![swig_rust_type = "CRustVecU64"]
     #![swig_foreigner_type = "struct CRustForeignVec"]
     #![swig_rust_type = "CRustForeignVec"]
     #![swig_foreigner_type = "struct CResultObjectString"]
@@ -63,6 +73,8 @@ mod swig_foreign_types_map {
     #![swig_rust_type = "CRustSliceI32"]
     #![swig_foreigner_type = "struct CRustSliceU32"]
     #![swig_rust_type = "CRustSliceU32"]
+    #![swig_foreigner_type = "struct CRustSliceU16"]
+    #![swig_rust_type = "CRustSliceU16"]
     #![swig_foreigner_type = "struct CRustSliceUsize"]
     #![swig_rust_type = "CRustSliceUsize"]
     #![swig_foreigner_type = "struct CRustOptionBool"]
@@ -71,6 +83,14 @@ mod swig_foreign_types_map {
     #![swig_rust_type = "CRustOptionF32"]
     #![swig_foreigner_type = "struct CRustOptionF64"]
     #![swig_rust_type = "CRustOptionF64"]
+    #![swig_foreigner_type = "struct CRustOptionI8"]
+    #![swig_rust_type = "CRustOptionI8"]
+    #![swig_foreigner_type = "struct CRustOptionU8"]
+    #![swig_rust_type = "CRustOptionU8"]
+    #![swig_foreigner_type = "struct CRustOptionI16"]
+    #![swig_rust_type = "CRustOptionI16"]
+    #![swig_foreigner_type = "struct CRustOptionU16"]
+    #![swig_rust_type = "CRustOptionU16"]
     #![swig_foreigner_type = "struct CRustOptionI32"]
     #![swig_rust_type = "CRustOptionI32"]
     #![swig_foreigner_type = "struct CRustOptionU32"]
@@ -136,6 +156,137 @@ trait SwigDerefMut {
     fn swig_deref_mut(&mut self) -> &mut Self::Target;
 }
 
+// how to handle result of `fallible` rule from `foreign_typemap!`,
+// error is saved by `swig_report_error` and C++ wrapper throws it as exception
+#[allow(dead_code)]
+#[swig_code = "let mut {to_var}: {to_var_type} = swig_unwrap_or_report!({from_var}, {function_ret_type});"]
+trait SwigFallible {}
+
+#[allow(unused_macros)]
+macro_rules! swig_unwrap_or_report {
+    ($result_value:expr, $func_ret_type:ty) => {{
+        match $result_value {
+            Ok(x) => x,
+            Err(msg) => {
+                swig_report_error(msg);
+                return <$func_ret_type>::invalid_value();
+            }
+        }
+    }};
+}
+
+// value that C function returns if conversation failed,
+// C++ wrapper throws exception instead of usage of it
+#[allow(dead_code)]
+trait SwigInvalidValue {
+    fn invalid_value() -> Self;
+}
+
+impl<T> SwigInvalidValue for *const T {
+    fn invalid_value() -> *const T {
+        ::std::ptr::null()
+    }
+}
+
+impl<T> SwigInvalidValue for *mut T {
+    fn invalid_value() -> *mut T {
+        ::std::ptr::null_mut()
+    }
+}
+
+impl SwigInvalidValue for () {
+    fn invalid_value() {}
+}
+
+macro_rules! impl_swig_invalid_value {
+    ($($type:ty => $value:expr,)*) => ($(
+        impl SwigInvalidValue for $type {
+            fn invalid_value() -> $type {
+                $value
+            }
+        }
+    )*)
+}
+
+impl_swig_invalid_value! {
+    bool => false,
+    i8 => 0,
+    u8 => 0,
+    i16 => 0,
+    u16 => 0,
+    i32 => 0,
+    u32 => 0,
+    i64 => 0,
+    u64 => 0,
+    isize => 0,
+    usize => 0,
+    f32 => 0.,
+    f64 => 0.,
+    CRustStrView => CRustStrView { data: ::std::ptr::null(), len: 0 },
+    CRustString => CRustString { data: ::std::ptr::null(), len: 0, capacity: 0 },
+    CRustVecU8 => CRustVecU8 { data: ::std::ptr::null(), len: 0, capacity: 0 },
+    CRustVecI8 => CRustVecI8 { data: ::std::ptr::null(), len: 0, capacity: 0 },
+    CRustVecU16 => CRustVecU16 { data: ::std::ptr::null(), len: 0, capacity: 0 },
+    CRustVecI16 => CRustVecI16 { data: ::std::ptr::null(), len: 0, capacity: 0 },
+    CRustVecU32 => CRustVecU32 { data: ::std::ptr::null(), len: 0, capacity: 0 },
+    CRustVecI32 => CRustVecI32 { data: ::std::ptr::null(), len: 0, capacity: 0 },
+    CRustVecU64 => CRustVecU64 { data: ::std::ptr::null(), len: 0, capacity: 0 },
+    CRustVecI64 => CRustVecI64 { data: ::std::ptr::null(), len: 0, capacity: 0 },
+    CRustVecUsize => CRustVecUsize { data: ::std::ptr::null(), len: 0, capacity: 0 },
+    CRustVecF32 => CRustVecF32 { data: ::std::ptr::null(), len: 0, capacity: 0 },
+    CRustVecF64 => CRustVecF64 { data: ::std::ptr::null(), len: 0, capacity: 0 },
+    CRustForeignVec => CRustForeignVec { data: ::std::ptr::null(), len: 0, capacity: 0, step: 0 },
+    CRustSliceU8 => CRustSliceU8 { data: ::std::ptr::null(), len: 0 },
+    CRustSliceU16 => CRustSliceU16 { data: ::std::ptr::null(), len: 0 },
+    CRustSliceI32 => CRustSliceI32 { data: ::std::ptr::null(), len: 0 },
+    CRustSliceU32 => CRustSliceU32 { data: ::std::ptr::null(), len: 0 },
+    CRustSliceUsize => CRustSliceUsize { data: ::std::ptr::null(), len: 0 },
+    CRustObjectSlice => CRustObjectSlice { data: ::std::ptr::null(), len: 0, step: 0 },
+    CRustObjectPair => CRustObjectPair { first: ::std::ptr::null_mut(), second: ::std::ptr::null_mut() },
+    CRustOptionBool => CRustOptionBool { val: 0, is_some: 0 },
+    CRustOptionF32 => CRustOptionF32 { val: 0., is_some: 0 },
+    CRustOptionF64 => CRustOptionF64 { val: 0., is_some: 0 },
+    CRustOptionI8 => CRustOptionI8 { val: 0, is_some: 0 },
+    CRustOptionU8 => CRustOptionU8 { val: 0, is_some: 0 },
+    CRustOptionI16 => CRustOptionI16 { val: 0, is_some: 0 },
+    CRustOptionU16 => CRustOptionU16 { val: 0, is_some: 0 },
+    CRustOptionI32 => CRustOptionI32 { val: 0, is_some: 0 },
+    CRustOptionU32 => CRustOptionU32 { val: 0, is_some: 0 },
+    CRustOptionI64 => CRustOptionI64 { val: 0, is_some: 0 },
+    CRustOptionU64 => CRustOptionU64 { val: 0, is_some: 0 },
+    CRustOptionUSize => CRustOptionUSize { val: 0, is_some: 0 },
+    CRustOptionStr => CRustOptionStr { val: CRustStrView::invalid_value(), is_some: 0 },
+    CRustOptionString => CRustOptionString { val: CRustString::invalid_value(), is_some: 0 },
+    CResultObjectString => CResultObjectString {
+        is_ok: 0,
+        data: CResultObjectStringUnion { err: CRustString::invalid_value() },
+    },
+    CResultCRustForeignVecString => CResultCRustForeignVecString {
+        is_ok: 0,
+        data: CResultCRustForeignVecStringUnion { err: CRustString::invalid_value() },
+    },
+    CResultObjectObject => CResultObjectObject {
+        is_ok: 0,
+        data: CResultObjectObjectUnion { err: ::std::ptr::null_mut() },
+    },
+    CResultVecObjectObject => CResultVecObjectObject {
+        is_ok: 0,
+        data: CResultVecObjectObjectUnion { err: ::std::ptr::null_mut() },
+    },
+    CResultCRustVecU8Object => CResultCRustVecU8Object {
+        is_ok: 0,
+        data: CRustVecU8ObjectUnion { err: ::std::ptr::null_mut() },
+    },
+    CResultObjectEnum => CResultObjectEnum {
+        is_ok: 0,
+        data: CResultObjectEnumUnion { err: 0 },
+    },
+    CResultI64Object => CResultI64Object {
+        is_ok: 0,
+        data: CResultI64ObjectUnion { err: ::std::ptr::null_mut() },
+    },
+}
+
 impl<'a> SwigInto<&'a ::std::ffi::CStr> for *const ::std::os::raw::c_char {
     fn swig_into(self) -> &'a ::std::ffi::CStr {
         assert!(!self.is_null());
@@ -166,6 +317,17 @@ impl<'a> SwigInto<Option<&'a str>> for *const ::std::os::raw::c_char {
     }
 }
 
+impl SwigInto<Option<String>> for *const ::std::os::raw::c_char {
+    fn swig_into(self) -> Option<String> {
+        if !self.is_null() {
+            let cstr = unsafe { ::std::ffi::CStr::from_ptr(self) };
+            Some(cstr.to_str().expect("wrong utf-8").to_string())
+        } else {
+            None
+        }
+    }
+}
+
 #[allow(dead_code)]
 #[repr(C)]
 pub struct CRustStrView {
@@ -181,6 +343,14 @@ impl CRustStrView {
             len: s.len(),
         }
     }
+    fn as_str<'a>(&self) -> &'a str {
+        if self.len == 0 {
+            return "";
+        }
+        assert!(!self.data.is_null());
+        let bytes = unsafe { ::std::slice::from_raw_parts(self.data as *const u8, self.len) };
+        ::std::str::from_utf8(bytes).expect("wrong utf-8")
+    }
 }
 
 impl<T> SwigDeref for Arc<Mutex<T>> {
@@ -446,6 +616,151 @@ pub extern "C" fn CRustVecF64_free(v: CRustVecF64) {
     drop(v);
 }
 
+#[allow(dead_code)]
+#[repr(C)]
+pub struct CRustVecI8 {
+    data: *const i8,
+    len: usize,
+    capacity: usize,
+}
+
+impl SwigFrom<Vec<i8>> for CRustVecI8 {
+    fn swig_from(mut v: Vec<i8>) -> CRustVecI8 {
+        let p = v.as_mut_ptr();
+        let len = v.len();
+        let cap = v.capacity();
+        ::std::mem::forget(v);
+        CRustVecI8 {
+            data: p,
+            len: len,
+            capacity: cap,
+        }
+    }
+}
+
+#[allow(private_no_mangle_fns)]
+#[no_mangle]
+pub extern "C" fn CRustVecI8_free(v: CRustVecI8) {
+    let v = unsafe { Vec::from_raw_parts(v.data as *mut i8, v.len, v.capacity) };
+    drop(v);
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+pub struct CRustVecI16 {
+    data: *const i16,
+    len: usize,
+    capacity: usize,
+}
+
+impl SwigFrom<Vec<i16>> for CRustVecI16 {
+    fn swig_from(mut v: Vec<i16>) -> CRustVecI16 {
+        let p = v.as_mut_ptr();
+        let len = v.len();
+        let cap = v.capacity();
+        ::std::mem::forget(v);
+        CRustVecI16 {
+            data: p,
+            len: len,
+            capacity: cap,
+        }
+    }
+}
+
+#[allow(private_no_mangle_fns)]
+#[no_mangle]
+pub extern "C" fn CRustVecI16_free(v: CRustVecI16) {
+    let v = unsafe { Vec::from_raw_parts(v.data as *mut i16, v.len, v.capacity) };
+    drop(v);
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+pub struct CRustVecU16 {
+    data: *const u16,
+    len: usize,
+    capacity: usize,
+}
+
+impl SwigFrom<Vec<u16>> for CRustVecU16 {
+    fn swig_from(mut v: Vec<u16>) -> CRustVecU16 {
+        let p = v.as_mut_ptr();
+        let len = v.len();
+        let cap = v.capacity();
+        ::std::mem::forget(v);
+        CRustVecU16 {
+            data: p,
+            len: len,
+            capacity: cap,
+        }
+    }
+}
+
+#[allow(private_no_mangle_fns)]
+#[no_mangle]
+pub extern "C" fn CRustVecU16_free(v: CRustVecU16) {
+    let v = unsafe { Vec::from_raw_parts(v.data as *mut u16, v.len, v.capacity) };
+    drop(v);
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+pub struct CRustVecI64 {
+    data: *const i64,
+    len: usize,
+    capacity: usize,
+}
+
+impl SwigFrom<Vec<i64>> for CRustVecI64 {
+    fn swig_from(mut v: Vec<i64>) -> CRustVecI64 {
+        let p = v.as_mut_ptr();
+        let len = v.len();
+        let cap = v.capacity();
+        ::std::mem::forget(v);
+        CRustVecI64 {
+            data: p,
+            len: len,
+            capacity: cap,
+        }
+    }
+}
+
+#[allow(private_no_mangle_fns)]
+#[no_mangle]
+pub extern "C" fn CRustVecI64_free(v: CRustVecI64) {
+    let v = unsafe { Vec::from_raw_parts(v.data as *mut i64, v.len, v.capacity) };
+    drop(v);
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+pub struct CRustVecU64 {
+    data: *const u64,
+    len: usize,
+    capacity: usize,
+}
+
+impl SwigFrom<Vec<u64>> for CRustVecU64 {
+    fn swig_from(mut v: Vec<u64>) -> CRustVecU64 {
+        let p = v.as_mut_ptr();
+        let len = v.len();
+        let cap = v.capacity();
+        ::std::mem::forget(v);
+        CRustVecU64 {
+            data: p,
+            len: len,
+            capacity: cap,
+        }
+    }
+}
+
+#[allow(private_no_mangle_fns)]
+#[no_mangle]
+pub extern "C" fn CRustVecU64_free(v: CRustVecU64) {
+    let v = unsafe { Vec::from_raw_parts(v.data as *mut u64, v.len, v.capacity) };
+    drop(v);
+}
+
 #[allow(dead_code)]
 #[repr(C)]
 #[derive(Copy, Clone)]
@@ -526,6 +841,32 @@ fn remove_foreign_class_from_vec<T: SwigForeignClass>(
     T::box_object(elem)
 }
 
+/// State of C++ `RustAwaitable` for completion callback of `#[awaitable]` method,
+/// C++ coroutine waits forever if callback is not called, so drop without call aborts
+#[allow(dead_code)]
+pub struct SwigAwaitCompletion(*mut ::std::os::raw::c_void);
+
+unsafe impl Send for SwigAwaitCompletion {}
+
+#[allow(dead_code)]
+impl SwigAwaitCompletion {
+    pub fn new(opaque: *mut ::std::os::raw::c_void) -> SwigAwaitCompletion {
+        SwigAwaitCompletion(opaque)
+    }
+    pub fn take(mut self) -> *mut ::std::os::raw::c_void {
+        ::std::mem::replace(&mut self.0, ::std::ptr::null_mut())
+    }
+}
+
+impl Drop for SwigAwaitCompletion {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            eprintln!("rust_swig: completion callback of #[awaitable] method dropped without call");
+            ::std::process::abort();
+        }
+    }
+}
+
 // &str -> &Path
 impl<'a> SwigInto<&'a Path> for &'a str {
     fn swig_into(self) -> &'a Path {
@@ -653,6 +994,26 @@ pub struct CRustSliceU8 {
     len: usize,
 }
 
+impl<'a> SwigFrom<CRustSliceU8> for &'a [u8] {
+    fn swig_from(s: CRustSliceU8) -> &'a [u8] {
+        if s.len == 0 {
+            return &[];
+        }
+        assert!(!s.data.is_null());
+        unsafe { ::std::slice::from_raw_parts(s.data, s.len) }
+    }
+}
+
+impl<'a> SwigFrom<CRustSliceU8> for &'a mut [u8] {
+    fn swig_from(s: CRustSliceU8) -> &'a mut [u8] {
+        if s.len == 0 {
+            return &mut [];
+        }
+        assert!(!s.data.is_null());
+        unsafe { ::std::slice::from_raw_parts_mut(s.data as *mut u8, s.len) }
+    }
+}
+
 impl<'a> SwigInto<CRustSliceU8> for &'a [u8] {
     fn swig_into(self) -> CRustSliceU8 {
         CRustSliceU8 {
@@ -671,11 +1032,24 @@ pub struct CRustSliceI32 {
 
 impl<'a> SwigFrom<CRustSliceI32> for &'a [i32] {
     fn swig_from(s: CRustSliceI32) -> &'a [i32] {
-        assert!(s.len == 0 || !s.data.is_null());
+        if s.len == 0 {
+            return &[];
+        }
+        assert!(!s.data.is_null());
         unsafe { ::std::slice::from_raw_parts(s.data, s.len) }
     }
 }
 
+impl<'a> SwigFrom<CRustSliceI32> for &'a mut [i32] {
+    fn swig_from(s: CRustSliceI32) -> &'a mut [i32] {
+        if s.len == 0 {
+            return &mut [];
+        }
+        assert!(!s.data.is_null());
+        unsafe { ::std::slice::from_raw_parts_mut(s.data as *mut i32, s.len) }
+    }
+}
+
 impl<'a> SwigInto<CRustSliceI32> for &'a [i32] {
     fn swig_into(self) -> CRustSliceI32 {
         CRustSliceI32 {
@@ -692,6 +1066,26 @@ pub struct CRustSliceU32 {
     len: usize,
 }
 
+impl<'a> SwigFrom<CRustSliceU32> for &'a [u32] {
+    fn swig_from(s: CRustSliceU32) -> &'a [u32] {
+        if s.len == 0 {
+            return &[];
+        }
+        assert!(!s.data.is_null());
+        unsafe { ::std::slice::from_raw_parts(s.data, s.len) }
+    }
+}
+
+impl<'a> SwigFrom<CRustSliceU32> for &'a mut [u32] {
+    fn swig_from(s: CRustSliceU32) -> &'a mut [u32] {
+        if s.len == 0 {
+            return &mut [];
+        }
+        assert!(!s.data.is_null());
+        unsafe { ::std::slice::from_raw_parts_mut(s.data as *mut u32, s.len) }
+    }
+}
+
 impl<'a> SwigInto<CRustSliceU32> for &'a [u32] {
     fn swig_into(self) -> CRustSliceU32 {
         CRustSliceU32 {
@@ -701,6 +1095,96 @@ impl<'a> SwigInto<CRustSliceU32> for &'a [u32] {
     }
 }
 
+#[allow(dead_code)]
+#[repr(C)]
+pub struct CRustSliceU16 {
+    data: *const u16,
+    len: usize,
+}
+
+impl<'a> SwigFrom<CRustSliceU16> for &'a [u16] {
+    fn swig_from(s: CRustSliceU16) -> &'a [u16] {
+        if s.len == 0 {
+            return &[];
+        }
+        assert!(!s.data.is_null());
+        unsafe { ::std::slice::from_raw_parts(s.data, s.len) }
+    }
+}
+
+impl<'a> SwigFrom<CRustSliceU16> for &'a mut [u16] {
+    fn swig_from(s: CRustSliceU16) -> &'a mut [u16] {
+        if s.len == 0 {
+            return &mut [];
+        }
+        assert!(!s.data.is_null());
+        unsafe { ::std::slice::from_raw_parts_mut(s.data as *mut u16, s.len) }
+    }
+}
+
+impl<'a> SwigInto<CRustSliceU16> for &'a [u16] {
+    fn swig_into(self) -> CRustSliceU16 {
+        CRustSliceU16 {
+            data: self.as_ptr(),
+            len: self.len(),
+        }
+    }
+}
+
+/// UTF-16 C++ string to Rust, shared by all methods in UTF-16 string mode
+#[allow(dead_code)]
+#[inline(never)]
+fn swig_string_from_utf16(s: CRustSliceU16) -> String {
+    let x: &[u16] = if s.len == 0 {
+        &[]
+    } else {
+        assert!(!s.data.is_null());
+        unsafe { ::std::slice::from_raw_parts(s.data, s.len) }
+    };
+    String::from_utf16(x).expect("wrong utf-16")
+}
+
+/// Rust string to UTF-16 C++ string, shared by all methods in UTF-16 string mode
+#[allow(dead_code)]
+#[inline(never)]
+fn swig_string_to_utf16(s: &str) -> CRustVecU16 {
+    let mut x: Vec<u16> = s.encode_utf16().collect();
+    let data = x.as_mut_ptr();
+    let len = x.len();
+    let capacity = x.capacity();
+    ::std::mem::forget(x);
+    CRustVecU16 {
+        data,
+        len,
+        capacity,
+    }
+}
+
+/// UTF-16 C++ optional string to Rust, `None` has `data == NULL`
+#[allow(dead_code)]
+#[inline(never)]
+fn swig_option_string_from_utf16(s: CRustSliceU16) -> Option<String> {
+    if s.data.is_null() {
+        None
+    } else {
+        Some(swig_string_from_utf16(s))
+    }
+}
+
+/// Rust optional string to UTF-16 C++ string, `None` has `data == NULL`
+#[allow(dead_code)]
+#[inline(never)]
+fn swig_option_string_to_utf16(s: &Option<String>) -> CRustVecU16 {
+    match s {
+        Some(s) => swig_string_to_utf16(s),
+        None => CRustVecU16 {
+            data: ::std::ptr::null(),
+            len: 0,
+            capacity: 0,
+        },
+    }
+}
+
 #[allow(dead_code)]
 #[repr(C)]
 pub struct CRustSliceUsize {
@@ -708,6 +1192,26 @@ pub struct CRustSliceUsize {
     len: usize,
 }
 
+impl<'a> SwigFrom<CRustSliceUsize> for &'a [usize] {
+    fn swig_from(s: CRustSliceUsize) -> &'a [usize] {
+        if s.len == 0 {
+            return &[];
+        }
+        assert!(!s.data.is_null());
+        unsafe { ::std::slice::from_raw_parts(s.data, s.len) }
+    }
+}
+
+impl<'a> SwigFrom<CRustSliceUsize> for &'a mut [usize] {
+    fn swig_from(s: CRustSliceUsize) -> &'a mut [usize] {
+        if s.len == 0 {
+            return &mut [];
+        }
+        assert!(!s.data.is_null());
+        unsafe { ::std::slice::from_raw_parts_mut(s.data as *mut usize, s.len) }
+    }
+}
+
 impl<'a> SwigInto<CRustSliceUsize> for &'a [usize] {
     fn swig_into(self) -> CRustSliceUsize {
         CRustSliceUsize {
@@ -868,6 +1372,110 @@ impl SwigInto<Option<f64>> for CRustOptionF64 {
     }
 }
 
+#[allow(dead_code)]
+#[repr(C)]
+pub struct CRustOptionI8 {
+    val: i8,
+    is_some: u8,
+}
+
+impl SwigFrom<Option<i8>> for CRustOptionI8 {
+    fn swig_from(x: Option<i8>) -> Self {
+        match x {
+            Some(x) => CRustOptionI8 { val: x, is_some: 1 },
+            None => CRustOptionI8 { val: 0, is_some: 0 },
+        }
+    }
+}
+
+impl SwigInto<Option<i8>> for CRustOptionI8 {
+    fn swig_into(self) -> Option<i8> {
+        if self.is_some != 0 {
+            Some(self.val)
+        } else {
+            None
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+pub struct CRustOptionU8 {
+    val: u8,
+    is_some: u8,
+}
+
+impl SwigFrom<Option<u8>> for CRustOptionU8 {
+    fn swig_from(x: Option<u8>) -> Self {
+        match x {
+            Some(x) => CRustOptionU8 { val: x, is_some: 1 },
+            None => CRustOptionU8 { val: 0, is_some: 0 },
+        }
+    }
+}
+
+impl SwigInto<Option<u8>> for CRustOptionU8 {
+    fn swig_into(self) -> Option<u8> {
+        if self.is_some != 0 {
+            Some(self.val)
+        } else {
+            None
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+pub struct CRustOptionI16 {
+    val: i16,
+    is_some: u8,
+}
+
+impl SwigFrom<Option<i16>> for CRustOptionI16 {
+    fn swig_from(x: Option<i16>) -> Self {
+        match x {
+            Some(x) => CRustOptionI16 { val: x, is_some: 1 },
+            None => CRustOptionI16 { val: 0, is_some: 0 },
+        }
+    }
+}
+
+impl SwigInto<Option<i16>> for CRustOptionI16 {
+    fn swig_into(self) -> Option<i16> {
+        if self.is_some != 0 {
+            Some(self.val)
+        } else {
+            None
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+pub struct CRustOptionU16 {
+    val: u16,
+    is_some: u8,
+}
+
+impl SwigFrom<Option<u16>> for CRustOptionU16 {
+    fn swig_from(x: Option<u16>) -> Self {
+        match x {
+            Some(x) => CRustOptionU16 { val: x, is_some: 1 },
+            None => CRustOptionU16 { val: 0, is_some: 0 },
+        }
+    }
+}
+
+impl SwigInto<Option<u16>> for CRustOptionU16 {
+    fn swig_into(self) -> Option<u16> {
+        if self.is_some != 0 {
+            Some(self.val)
+        } else {
+            None
+        }
+    }
+}
+
 #[allow(dead_code)]
 #[repr(C)]
 pub struct CRustOptionI32 {
@@ -1005,6 +1613,17 @@ pub struct CRustOptionStr {
     is_some: u8,
 }
 
+#[allow(dead_code)]
+impl CRustOptionStr {
+    fn as_option_str<'a>(&self) -> Option<&'a str> {
+        if self.is_some != 0 {
+            Some(self.val.as_str())
+        } else {
+            None
+        }
+    }
+}
+
 impl<'a> SwigFrom<Option<&'a str>> for CRustOptionStr {
     fn swig_from(x: Option<&'a str>) -> Self {
         match x {
@@ -1286,6 +1905,11 @@ foreign_typemap!(
         "boost::string_view{ $p.data, $p.len }";
     ($p:f_type, option = "CppStrView::Std17", req_modules = ["\"rust_str.h\"", "<string_view>"]) => "std::string_view"
         "std::string_view{ $p.data, $p.len }";
+    ($p:r_type) &str <= CRustStrView {
+        $out = $p.as_str()
+    };
+    ($p:f_type, option = "CppStandard::Cpp17", req_modules = ["\"rust_str.h\"", "<string_view>"]) <= "std::string_view"
+        "CRustStrView{ $p.data(), $p.size() }";
 );
 
 foreign_typemap!(
@@ -1362,12 +1986,14 @@ public:
     }
     RustString &operator=(RustString &&o) noexcept
     {
-        free_mem();
-        data = o.data;
-        len = o.len;
-        capacity = o.capacity;
+        if (this != &o) {
+            free_mem();
+            data = o.data;
+            len = o.len;
+            capacity = o.capacity;
 
-        reset(o);
+            reset(o);
+        }
         return *this;
     }
     ~RustString() noexcept { free_mem(); }
@@ -7,29 +7,137 @@ use syn::spanned::Spanned;
 
 use crate::{
     cpp::{fmt_write_err_map, map_any_err_to_our_err, CppForeignMethodSignature},
+    doc_links::{DocLink, DocLinkKind},
     error::{panic_on_syn_error, DiagnosticError},
+    name_collisions::NameRegistry,
     typemap::{ast::DisplayToTokens, CType, CTypes, TypeMap, FROM_VAR_TEMPLATE},
-    types::{ForeignEnumInfo, ForeignerClassInfo},
+    types::{ForeignEnumInfo, ForeignerClassInfo, ItemToExpand},
 };
 
+/// Doxygen block `/** ... */`, also valid in C headers
 pub(in crate::cpp) fn doc_comments_to_c_comments(
     doc_comments: &[String],
     class_comments: bool,
 ) -> String {
     use std::fmt::Write;
+    let indent = if class_comments { "" } else { "    " };
     let mut comments = String::new();
     for (i, comment) in doc_comments.iter().enumerate() {
         if i != 0 {
             comments.push('\n');
         }
-        if !class_comments {
-            comments.push_str("    ");
+        comments.push_str(indent);
+        if i == 0 {
+            comments.push_str("/**\n");
+            comments.push_str(indent);
+        }
+        write!(&mut comments, " * {}", comment.trim()).unwrap();
+        if i == doc_comments.len() - 1 {
+            comments.push('\n');
+            comments.push_str(indent);
+            comments.push_str(" */");
         }
-        write!(&mut comments, "//{}", comment.trim()).unwrap();
     }
     comments
 }
 
+/// Add `@param`/`@return` for C++ method to not empty documentation
+pub(in crate::cpp) fn cpp_method_doc_comments(
+    doc_comments: &[String],
+    f_method: &CppForeignMethodSignature,
+    constructor: bool,
+) -> Vec<String> {
+    let mut ret = doc_comments.to_vec();
+    if ret.is_empty() {
+        return ret;
+    }
+    for i in 0..f_method.input.len() {
+        ret.push(format!("@param a_{}", i));
+    }
+    if !constructor && (f_method.output.as_ref().name != "void" || f_method.completion.is_some()) {
+        ret.push("@return".into());
+    }
+    ret
+}
+
+/// Helpers used by C++ converters, that allocate and so may throw `std::bad_alloc`
+const THROWING_CPP_CONVERTERS: &[&str] = &["std::make_shared<", "rust_to_utf16_string<"];
+
+/// Exception specification of C++ method: `noexcept` if C++ code around
+/// Rust call can not throw. Panic in Rust code aborts and `Result`
+/// is returned as value, so only allocations in C++ code are taken into account.
+pub(in crate::cpp) fn cpp_method_noexcept(f_method: &CppForeignMethodSignature) -> &'static str {
+    let may_throw = f_method.completion.is_some()
+        || f_method
+            .input
+            .iter()
+            .chain(std::iter::once(&f_method.output))
+            .filter_map(|x| x.cpp_converter.as_ref())
+            .any(|conv| {
+                THROWING_CPP_CONVERTERS
+                    .iter()
+                    .any(|helper| conv.converter.contains(helper))
+            });
+    if may_throw {
+        "noexcept(false)"
+    } else {
+        "noexcept"
+    }
+}
+
+/// `namespace a::b {` with C++17 nested namespace definition,
+/// or `namespace a { namespace b {` for older standards
+pub(in crate::cpp) fn open_namespace(namespace: &str, nested_syntax: bool) -> String {
+    if nested_syntax {
+        format!("namespace {} {{", namespace)
+    } else {
+        namespace
+            .split("::")
+            .map(|x| format!("namespace {} {{", x))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+pub(in crate::cpp) fn close_namespace(namespace: &str, nested_syntax: bool) -> String {
+    let n = if nested_syntax {
+        1
+    } else {
+        namespace.split("::").count()
+    };
+    format!("{} // namespace {}", vec!["}"; n].join(" "), namespace)
+}
+
+/// Replace `placeholder` in helper's code with namespace name,
+/// and open/close of `placeholder` namespace according to `nested_syntax`
+pub(in crate::cpp) fn expand_namespace_placeholder(
+    code: &str,
+    placeholder: &str,
+    namespace: &str,
+    nested_syntax: bool,
+) -> String {
+    code.replace(
+        &format!("namespace {} {{", placeholder),
+        &open_namespace(namespace, nested_syntax),
+    )
+    .replace(
+        &format!("}} // namespace {}", placeholder),
+        &close_namespace(namespace, nested_syntax),
+    )
+    .replace(placeholder, namespace)
+}
+
+/// Doxygen `\ref` for rustdoc link, methods are documented
+/// in `FooWrapper` template and enum items are not scoped
+pub(in crate::cpp) fn doxygen_link(link: &DocLink) -> String {
+    match (link.kind, link.member) {
+        (DocLinkKind::Class, Some(member)) => format!("\\ref {}Wrapper::{}", link.item, member),
+        (DocLinkKind::Enum, Some(member)) => format!("\\ref {}", member),
+        (DocLinkKind::Interface, Some(member)) => format!("\\ref {}::{}", link.item, member),
+        (_, None) => format!("\\ref {}", link.item),
+    }
+}
+
 pub(in crate::cpp) fn c_generate_args_with_types(
     f_method: &CppForeignMethodSignature,
     append_comma_if_not_empty: bool,
@@ -106,6 +214,10 @@ pub(in crate::cpp) fn cpp_header_name(class: &ForeignerClassInfo) -> String {
     format!("{}.hpp", class.name)
 }
 
+pub(in crate::cpp) fn cpp_source_name(class: &ForeignerClassInfo) -> String {
+    format!("{}.cpp", class.name)
+}
+
 pub(in crate::cpp) fn c_header_name(class: &ForeignerClassInfo) -> String {
     format!("c_{}.h", class.name)
 }
@@ -114,6 +226,142 @@ pub(in crate::cpp) fn cpp_header_name_for_enum(enum_info: &ForeignEnumInfo) -> S
     format!("c_{}.h", enum_info.name)
 }
 
+pub(in crate::cpp) fn cpp_header_name_for_data_enum(enum_info: &ForeignEnumInfo) -> String {
+    format!("{}.hpp", enum_info.name)
+}
+
+pub(in crate::cpp) fn cpp_source_name_for_data_enum(enum_info: &ForeignEnumInfo) -> String {
+    format!("{}.cpp", enum_info.name)
+}
+
+/// Helpers from `cpp-include.rs` and helper headers visible to user's code
+const CPP_HELPER_NAMES: &[&str] = &[
+    "RustString",
+    "RustVec",
+    "RustSlice",
+    "RustForeignVec",
+    "RustForeignSlice",
+    "RustForeignVecIterator",
+    "RustAwaitable",
+    "RustExpected",
+    "RustUnexpected",
+    "CRustString",
+    "CRustStrView",
+    "CRustForeignVec",
+    "CRustObjectSlice",
+    "CRustObjectPair",
+];
+
+/// Suffixes of `RustVecU8`, `CRustVecU8` and so on
+const CPP_VEC_SUFFIXES: &[&str] = &[
+    "U8", "I8", "U16", "I16", "U32", "I32", "U64", "I64", "F32", "F64", "Usize",
+];
+
+/// Report all collisions of names of classes, enums, interfaces,
+/// names of C/C++ types generated for them and helpers
+pub(in crate::cpp) fn check_name_collisions(items: &[ItemToExpand]) -> Result<(), DiagnosticError> {
+    let mut names = NameRegistry::default();
+    let helpers = CPP_HELPER_NAMES.iter().map(|x| x.to_string()).chain(
+        CPP_VEC_SUFFIXES
+            .iter()
+            .flat_map(|x| vec![format!("RustVec{}", x), format!("CRustVec{}", x)]),
+    );
+    for helper in helpers {
+        names.reserve(helper, "C++ helper");
+    }
+    names.add_items(items);
+    for item in items {
+        let (generated, sp) = match item {
+            ItemToExpand::Class(fclass) => (
+                vec![
+                    format!("{}Ref", fclass.name),
+                    format!("{}Wrapper", fclass.name),
+                    c_class_type(fclass),
+                    format!("RustForeignVec{}", fclass.name),
+                ],
+                (fclass.src_id, fclass.span()),
+            ),
+            ItemToExpand::Enum(fenum) if fenum.is_data_enum() => {
+                let mut generated = vec![
+                    format!("{}Visitor", fenum.name),
+                    format!("{}Opaque", fenum.name),
+                ];
+                generated.extend(fenum.items.iter().map(|x| x.name.to_string()));
+                (generated, (fenum.src_id, fenum.span()))
+            }
+            // items of C enum are visible outside of enum
+            ItemToExpand::Enum(fenum) => (
+                fenum.items.iter().map(|x| x.name.to_string()).collect(),
+                (fenum.src_id, fenum.span()),
+            ),
+            ItemToExpand::Interface(finterface) => (
+                vec![format!("C_{}", finterface.name)],
+                finterface.src_id_span(),
+            ),
+        };
+        for name in generated {
+            names.add(name, sp, format_args!("C++ code for {}", item.descr()));
+        }
+    }
+    names.into_result()
+}
+
+/// `typedef` for opaque C type, guarded so the same type
+/// can be forward declared in several C headers
+pub(in crate::cpp) fn c_opaque_typedef(c_type: &str) -> String {
+    format!(
+        "#ifndef {0}_DEFINED\n#define {0}_DEFINED\n    typedef struct {0} {0};\n#endif",
+        c_type
+    )
+}
+
+/// Includes and forward declarations of opaque types
+/// to make C header usable without C++ headers
+pub(in crate::cpp) fn c_list_required_includes(
+    conv_map: &TypeMap,
+    methods: &[CppForeignMethodSignature],
+    self_c_header: &str,
+) -> (Vec<SmolStr>, Vec<String>) {
+    let self_c_header = format!("\"{}\"", self_c_header);
+    let mut includes = FxHashSet::<SmolStr>::default();
+    let mut opaque_types = FxHashSet::<String>::default();
+    for m in methods {
+        for ti in m
+            .input
+            .iter()
+            .chain(std::iter::once(&m.output))
+            .chain(m.completion.iter())
+        {
+            let c_type = ti.base.name.as_str();
+            let ftype = match conv_map.find_foreign_type_by_name(c_type) {
+                Some(x) => x,
+                None => continue,
+            };
+            let c_type = c_type
+                .trim_start_matches("const ")
+                .trim_end_matches(" * const")
+                .trim_end_matches(" *");
+            for module in &conv_map[ftype].provides_by_module {
+                if *module == self_c_header {
+                    continue;
+                }
+                let is_opaque = c_type.ends_with("Opaque")
+                    && *module == format!("\"c_{}.h\"", &c_type[..c_type.len() - 6]);
+                if is_opaque {
+                    opaque_types.insert(c_type.to_string());
+                } else {
+                    includes.insert(module.clone());
+                }
+            }
+        }
+    }
+    let mut includes: Vec<_> = includes.into_iter().collect();
+    includes.sort();
+    let mut opaque_types: Vec<_> = opaque_types.into_iter().collect();
+    opaque_types.sort();
+    (includes, opaque_types)
+}
+
 pub(in crate::cpp) fn cpp_list_required_includes(
     methods: &mut [CppForeignMethodSignature],
 ) -> Vec<SmolStr> {
@@ -123,6 +371,9 @@ pub(in crate::cpp) fn cpp_list_required_includes(
             includes.extend(mem::replace(&mut p.provides_by_module, Vec::new()).into_iter());
         }
         includes.extend(mem::replace(&mut m.output.provides_by_module, Vec::new()).into_iter());
+        if let Some(ref mut completion) = m.completion {
+            includes.extend(completion.provides_by_module.drain(..));
+        }
     }
 
     let mut ret: Vec<_> = includes.into_iter().collect();
@@ -174,7 +425,7 @@ struct My{name} {{
                         DiagnosticError::new(c_types.src_id, f.ty.span(), "unknown Rust type")
                     })?;
                     let field_fty = tmap
-                        .find_foreign_type_related_to_rust_ty(field_rty.to_idx())
+                        .find_foreign_type_related_to_rust_ty(field_rty)
                         .ok_or_else(|| {
                             DiagnosticError::new(
                                 c_types.src_id,
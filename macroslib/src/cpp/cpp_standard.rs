@@ -0,0 +1,85 @@
+use crate::{
+    diagnostic_codes::DiagnosticCode,
+    error::{DiagnosticError, Result},
+    types::ItemToExpand,
+    CppConfig, CppOptional, CppResult, CppStandard, CppStrView, CppString, CppVariant,
+};
+
+impl CppConfig {
+    /// Is `cpp_standard` at least `standard`, always true if `cpp_standard` is not set
+    pub(in crate::cpp) fn cpp_standard_at_least(&self, standard: CppStandard) -> bool {
+        match self.cpp_standard {
+            Some(cpp_standard) => cpp_standard >= standard,
+            None => true,
+        }
+    }
+
+    /// `[[nodiscard]] ` for methods that return value, only if C++17 or later is requested
+    pub(in crate::cpp) fn nodiscard(&self) -> &'static str {
+        match self.cpp_standard {
+            Some(cpp_standard) if cpp_standard >= CppStandard::Cpp17 => "[[nodiscard]] ",
+            _ => "",
+        }
+    }
+}
+
+/// Report usage of features, that are not available in `CppConfig::cpp_standard`
+pub(in crate::cpp) fn check_cpp_standard(cfg: &CppConfig, items: &[ItemToExpand]) -> Result<()> {
+    let cpp_standard = match cfg.cpp_standard {
+        Some(x) => x,
+        None => return Ok(()),
+    };
+    let unsupported = |feature: &str, required: &str| {
+        format!(
+            "{} requires {}, but cpp_standard is {}",
+            feature, required, cpp_standard
+        )
+    };
+    let mut features = Vec::new();
+    if cfg.cpp_optional == CppOptional::Std17 {
+        features.push(("std::optional", CppStandard::Cpp17));
+    }
+    if cfg.cpp_variant == CppVariant::Std17 {
+        features.push(("std::variant", CppStandard::Cpp17));
+    }
+    if cfg.cpp_str_view == CppStrView::Std17 {
+        features.push(("std::string_view", CppStandard::Cpp17));
+    }
+    if cfg.cpp_string != CppString::Utf8 {
+        features.push(("UTF-16 strings", CppStandard::Cpp17));
+    }
+    if cfg.std_span {
+        features.push(("std::span", CppStandard::Cpp20));
+    }
+    for (feature, required) in features {
+        if !cfg.cpp_standard_at_least(required) {
+            return Err(DiagnosticError::new_without_src_info(unsupported(
+                feature,
+                &required.to_string(),
+            ))
+            .with_code(DiagnosticCode::CppStandardTooOld));
+        }
+    }
+    if cfg.cpp_result == CppResult::Std23Expected {
+        return Err(
+            DiagnosticError::new_without_src_info(unsupported("std::expected", "C++23"))
+                .with_code(DiagnosticCode::CppStandardTooOld),
+        );
+    }
+    if cfg.cpp_standard_at_least(CppStandard::Cpp20) {
+        return Ok(());
+    }
+    for item in items {
+        if let ItemToExpand::Class(fclass) = item {
+            if let Some(method) = fclass.methods.iter().find(|m| m.awaitable) {
+                return Err(DiagnosticError::new(
+                    fclass.src_id,
+                    method.span(),
+                    unsupported("#[awaitable]", &CppStandard::Cpp20.to_string()),
+                )
+                .with_code(DiagnosticCode::CppStandardTooOld));
+            }
+        }
+    }
+    Ok(())
+}
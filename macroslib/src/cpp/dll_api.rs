@@ -0,0 +1,35 @@
+use super::write_generated_file;
+use crate::{error::Result, CppConfig};
+
+/// Header with `<NAME>_API` macro
+pub(in crate::cpp) fn dll_api_header_name(name: &str) -> String {
+    format!("{}_api.h", name.to_lowercase())
+}
+
+/// Generate header with `<NAME>_API` macro for declarations of exported C functions:
+/// `__declspec(dllexport)` if `<NAME>_EXPORTS` is defined and `__declspec(dllimport)`
+/// otherwise on Windows, default visibility for GCC and clang,
+/// and nothing if `<NAME>_STATIC` is defined
+pub(in crate::cpp) fn generate_dll_api_header(cfg: &CppConfig, name: &str) -> Result<()> {
+    let cnt = format!(
+        r#"// Automaticaly generated by rust_swig
+#pragma once
+
+#if defined({name}_STATIC)
+#define {name}_API
+#elif defined(_WIN32) || defined(__CYGWIN__)
+#ifdef {name}_EXPORTS
+#define {name}_API __declspec(dllexport)
+#else
+#define {name}_API __declspec(dllimport)
+#endif
+#elif defined(__GNUC__)
+#define {name}_API __attribute__((visibility("default")))
+#else
+#define {name}_API
+#endif
+"#,
+        name = name
+    );
+    write_generated_file(&cfg.output_dir.join(dll_api_header_name(name)), &cnt)
+}
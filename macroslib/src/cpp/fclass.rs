@@ -7,24 +7,31 @@ use smol_str::SmolStr;
 use syn::{parse_quote, spanned::Spanned, Type};
 
 use crate::{
+    catch_panics,
     cpp::{
-        c_func_name, cpp_code, map_type::map_type, n_arguments_list, rust_generate_args_with_types,
-        CppForeignMethodSignature, CppForeignTypeInfo, MethodContext,
+        awaitable, c_func_name, cpp_code,
+        map_type::map_type,
+        n_arguments_list,
+        panics::{self, catch_panic_code},
+        rust_generate_args_with_types, CppForeignMethodSignature, CppForeignTypeInfo,
+        MethodContext,
     },
     error::{panic_on_syn_error, DiagnosticError, Result},
     file_cache::FileWriteCache,
+    profiler::{self, Stage},
+    tracing_spans,
     typemap::{
         ast::{fn_arg_type, list_lifetimes, normalize_ty_lifetimes, DisplayToTokens},
-        ty::RustType,
         utils::{
             convert_to_heap_pointer, create_suitable_types_for_constructor_and_self,
             foreign_from_rust_convert_method_output, foreign_to_rust_convert_method_inputs,
             unpack_from_heap_pointer,
         },
-        ForeignTypeInfo, FROM_VAR_TEMPLATE, TO_VAR_TEMPLATE,
+        ForeignTypeInfo, RustTypeIdx, FROM_VAR_TEMPLATE, TO_VAR_TEMPLATE,
     },
     types::{ForeignerClassInfo, MethodAccess, MethodVariant, SelfTypeVariant},
-    CppConfig, TypeMap,
+    unsupported_types::UnsupportedTypes,
+    CppConfig, PanicAction, TypeMap,
 };
 
 pub(in crate::cpp) fn generate(
@@ -57,6 +64,31 @@ pub(in crate::cpp) fn generate(
     }
 
     let c_class_type = cpp_code::c_class_type(class);
+    let c_api = cfg.c_api();
+    let mut c_class_typedef = String::new();
+    let mut c_includes = String::new();
+    if cfg.c_abi_only {
+        let (includes, opaque_types) = cpp_code::c_list_required_includes(
+            conv_map,
+            methods_sign,
+            &cpp_code::c_header_name(class),
+        );
+        for inc in includes {
+            writeln!(&mut c_includes, "#include {}", inc).unwrap();
+        }
+        for opaque_type in opaque_types {
+            writeln!(
+                &mut c_class_typedef,
+                "{}",
+                cpp_code::c_opaque_typedef(&opaque_type)
+            )
+            .unwrap();
+        }
+    }
+    c_class_typedef.push_str(&cpp_code::c_opaque_typedef(&c_class_type));
+    let nested_namespace_syntax = cfg.nested_namespace_syntax();
+    let open_namespace = cpp_code::open_namespace(&cfg.namespace_name, nested_namespace_syntax);
+    let close_namespace = cpp_code::close_namespace(&cfg.namespace_name, nested_namespace_syntax);
     let class_doc_comments = cpp_code::doc_comments_to_c_comments(&class.doc_comments, true);
 
     write!(
@@ -67,28 +99,231 @@ pub(in crate::cpp) fn generate(
 
 //for (u)intX_t types
 #include <stdint.h>
-
+{api_include}{c_includes}
 #ifdef __cplusplus
 static_assert(sizeof(uintptr_t) == sizeof(uint8_t) * {sizeof_usize},
    "our conversation usize <-> uintptr_t is wrong");
 extern "C" {{
 #endif
 
-    typedef struct {c_class_type} {c_class_type};
+{c_class_typedef}
 
 "##,
         doc_comments = class_doc_comments,
-        c_class_type = c_class_type,
+        api_include = cfg.c_api_include(),
+        c_includes = c_includes,
+        c_class_typedef = c_class_typedef,
         sizeof_usize = target_pointer_width / 8,
     )
     .map_err(map_write_err!(c_path))?;
 
     let class_name = format!("{}Wrapper", class.name);
+    let panic_action = catch_panics::class_panic_action(
+        class,
+        cfg.catch_panics.as_ref(),
+        panics::DEFAULT_PANIC_EXCEPTION,
+    );
+    let throw_on_panic =
+        !cfg.c_abi_only && panic_action.is_some() && panic_action != Some(PanicAction::Abort);
+
+    let dummy_ty = parse_type! { () };
+    let dummy_rust_ty = conv_map.find_or_alloc_rust_type_no_src_id(&dummy_ty);
+    let mut gen_code = Vec::new();
+
+    let (this_type_for_method, code_box_this) =
+        if let Some(this_type) = class.self_desc.as_ref().map(|x| &x.constructor_ret_type) {
+            let this_type = conv_map.find_or_alloc_rust_type_that_implements(
+                this_type,
+                "SwigForeignClass",
+                class.src_id,
+            );
+
+            let (this_type_for_method, code_box_this) =
+                convert_to_heap_pointer(conv_map, this_type, "this");
+            let lifetimes = {
+                let mut ret = String::new();
+                let lifetimes = list_lifetimes(&conv_map[this_type].ty);
+                for (i, l) in lifetimes.iter().enumerate() {
+                    ret.push_str(&*l.as_str());
+                    if i != lifetimes.len() - 1 {
+                        ret.push(',');
+                    }
+                }
+                ret
+            };
+            let unpack_code = unpack_from_heap_pointer(&conv_map[this_type], TO_VAR_TEMPLATE, true);
+            let fclass_impl_code = format!(
+                r#"impl<{lifetimes}> SwigForeignClass for {class_name} {{
+    fn c_class_name() -> *const ::std::os::raw::c_char {{
+        swig_c_str!("{class_name}")
+    }}
+    fn box_object(this: Self) -> *mut ::std::os::raw::c_void {{
+{code_box_this}
+        this as *mut ::std::os::raw::c_void
+    }}
+    fn unbox_object(p: *mut ::std::os::raw::c_void) -> Self {{
+        let p = p as *mut {this_type_for_method};
+{unpack_code}
+       p
+    }}
+}}"#,
+                lifetimes = lifetimes,
+                class_name = DisplayToTokens(&conv_map[this_type].ty),
+                code_box_this = code_box_this,
+                unpack_code = unpack_code.replace(TO_VAR_TEMPLATE, "p"),
+                this_type_for_method = conv_map[this_type_for_method].normalized_name
+            );
+            gen_code.push(syn::parse_str(&fclass_impl_code).unwrap_or_else(|err| {
+                panic_on_syn_error("internal foreign class impl code", fclass_impl_code, err)
+            }));
+            (this_type_for_method, code_box_this)
+        } else {
+            (dummy_rust_ty, String::new())
+        };
+    let no_this_info = || {
+        DiagnosticError::new(
+            class.src_id,
+            class.span(),
+            format!(
+                "Class {} (namespace {}) has methods, but there is no constructor\n
+May be you need to use `private constructor = empty;` syntax?",
+                class.name, cfg.namespace_name,
+            ),
+        )
+    };
+
+    // Rust code is generated before C++ wrappers,
+    // because of they check error only after methods with fallible conversations
+    let mut methods_fallible = Vec::with_capacity(class.methods.len());
+    for (method, f_method) in class.methods.iter().zip(methods_sign) {
+        let _timer = profiler::timer(Stage::MethodConversions, || {
+            format!("{}::{}", class.name, method.short_name())
+        });
+        let fallible_conversations = conv_map.fallible_conversations();
+        let c_func_name = c_func_name(cfg, class, method);
+        let args_names = n_arguments_list(f_method.input.len());
+        let real_output_typename = match method.fn_decl.output {
+            syn::ReturnType::Default => "()".into(),
+            syn::ReturnType::Type(_, ref t) => normalize_ty_lifetimes(&*t),
+        };
+
+        let mut rust_args_with_types = rust_generate_args_with_types(conv_map, f_method)
+            .map_err(|err| DiagnosticError::new(class.src_id, class.span(), err))?;
+        let mut rust_args_names = args_names;
+        let mut completion_code = String::new();
+        if let Some(ref completion) = f_method.completion {
+            let (mut deps, code) =
+                awaitable::rust_completion_code(conv_map, class, method, completion)?;
+            gen_code.append(&mut deps);
+            completion_code = code;
+            rust_args_with_types.push_str(&awaitable::rust_completion_args(conv_map, completion));
+            if !rust_args_names.is_empty() {
+                rust_args_names.push_str(", ");
+            }
+            rust_args_names.push_str(awaitable::COMPLETION_VAR);
+        }
+        let method_ctx = MethodContext {
+            class,
+            method,
+            f_method,
+            c_func_name: &c_func_name,
+            decl_func_args: &rust_args_with_types,
+            args_names: &rust_args_names,
+            real_output_typename: &real_output_typename,
+            completion_code: &completion_code,
+            catch_panics: panic_action.is_some(),
+            tracing_spans: cfg.tracing_spans,
+        };
+
+        match method.variant {
+            MethodVariant::StaticMethod => {
+                gen_code.append(&mut generate_static_method(conv_map, &method_ctx)?);
+            }
+            MethodVariant::Method(ref self_variant) => {
+                gen_code.append(&mut generate_method(
+                    conv_map,
+                    &method_ctx,
+                    class,
+                    *self_variant,
+                    this_type_for_method,
+                )?);
+            }
+            MethodVariant::Constructor => {
+                if !method.is_dummy_constructor() {
+                    let constructor_ret_type = class
+                        .self_desc
+                        .as_ref()
+                        .map(|x| &x.constructor_ret_type)
+                        .ok_or_else(&no_this_info)?
+                        .clone();
+                    let this_type = constructor_ret_type.clone();
+                    gen_code.append(&mut generate_constructor(
+                        conv_map,
+                        &method_ctx,
+                        constructor_ret_type,
+                        this_type,
+                        &code_box_this,
+                    )?);
+                }
+            }
+        }
+        methods_fallible.push(conv_map.fallible_conversations() != fallible_conversations);
+    }
+    let throw_on_error = throw_on_panic || (!cfg.c_abi_only && methods_fallible.contains(&true));
 
+    let unique_ptr_factories = cfg.unique_ptr_factories
+        && class
+            .methods
+            .iter()
+            .any(|m| m.variant == MethodVariant::Constructor && !m.is_dummy_constructor());
+    if unique_ptr_factories {
+        if let Some(m) = class.methods.iter().find(|m| m.short_name() == "create") {
+            return Err(DiagnosticError::new(
+                class.src_id,
+                m.span(),
+                format!(
+                    "Class {}: method `create` conflicts with generated unique_ptr factory",
+                    class.name
+                ),
+            ));
+        }
+    }
+
+    let self_include = format!("\"{}\"", cpp_code::cpp_header_name(class));
     let mut includes = String::new();
     for inc in req_includes {
-        writeln!(&mut includes, r#"#include {}"#, inc).unwrap();
+        if cfg.pimpl && *inc == self_include {
+            continue;
+        }
+        if cfg.pimpl {
+            writeln!(
+                &mut includes,
+                r#"#include {}"#,
+                pimpl_include(conv_map, inc)
+            )
+            .unwrap();
+        } else {
+            writeln!(&mut includes, r#"#include {}"#, inc).unwrap();
+        }
+    }
+    if unique_ptr_factories && !req_includes.iter().any(|x| x == "<memory>") {
+        writeln!(&mut includes, "#include <memory>").unwrap();
     }
+    if throw_on_error {
+        writeln!(&mut includes, "#include \"{}\"", panics::PANIC_HEADER).unwrap();
+    }
+    // in PIMPL mode C functions are used only in `Foo.cpp`
+    let c_class_decl = if cfg.pimpl {
+        format!(
+            "//for (u)intX_t types\n#include <stdint.h>\n\n{}",
+            cpp_code::c_opaque_typedef(&c_class_type)
+        )
+    } else {
+        format!("#include \"c_{}.h\"", class.name)
+    };
+    // explicit instantiations of members implemented in `Foo.cpp`
+    let mut pimpl_instances = String::new();
+    let inline = if cfg.pimpl { "" } else { "inline " };
 
     write!(
         cpp_include_f,
@@ -103,9 +338,9 @@ extern "C" {{
 #include <type_traits>
 
 {includes}
-#include "c_{class_dot_name}.h"
+{c_class_decl}
 
-namespace {namespace} {{
+{open_namespace}
 
 template<bool>
 class {class_name};
@@ -128,10 +363,11 @@ public:
     }}
     {class_name} &operator=({class_name} &&o) noexcept
     {{
-        assert(this != &o);
-        free_mem(this->self_);
-        self_ = o.self_;
-        o.self_ = nullptr;
+        if (this != &o) {{
+            free_mem(this->self_);
+            self_ = o.self_;
+            o.self_ = nullptr;
+        }}
         return *this;
     }}
     explicit {class_name}(SelfType o) noexcept: self_(o) {{}}
@@ -149,10 +385,14 @@ public:
         class_name = class_name,
         class_dot_name = class.name,
         includes = includes,
+        c_class_decl = c_class_decl,
         doc_comments = class_doc_comments,
-        namespace = cfg.namespace_name,
+        open_namespace = open_namespace,
     ).map_err(map_write_err!(cpp_path))?;
 
+    //because of VC++ has problem with cross-references of types
+    let mut inline_impl = String::new();
+
     if !class.copy_derived {
         write!(
             cpp_include_f,
@@ -185,11 +425,63 @@ public:
                     ),
                 )
             })?;
-        let c_clone_func = c_func_name(class, &class.methods[pos]);
+        let c_clone_func = c_func_name(cfg, class, &class.methods[pos]);
 
-        write!(
-            cpp_include_f,
-            r#"
+        if cfg.pimpl {
+            write!(
+                cpp_include_f,
+                r#"
+    {class_name}(const {class_name}& o) noexcept;
+    {class_name} &operator=(const {class_name}& o) noexcept;
+"#,
+                class_name = class_name
+            )
+            .map_err(map_write_err!(cpp_path))?;
+            write!(
+                &mut inline_impl,
+                r#"
+    template<bool OWN_DATA>
+    {class_name}<OWN_DATA>::{class_name}(const {class_name}& o) noexcept
+    {{
+        static_assert(OWN_DATA, "copy possible only if class own data");
+        if (o.self_ != nullptr) {{
+            self_ = {c_clone_func}(o.self_);
+        }} else {{
+            self_ = nullptr;
+        }}
+    }}
+
+    template<bool OWN_DATA>
+    {class_name}<OWN_DATA> &{class_name}<OWN_DATA>::operator=(const {class_name}& o) noexcept
+    {{
+        static_assert(OWN_DATA, "copy possible only if class own data");
+        if (this != &o) {{
+            free_mem(this->self_);
+            if (o.self_ != nullptr) {{
+                self_ = {c_clone_func}(o.self_);
+            }} else {{
+                self_ = nullptr;
+            }}
+        }}
+        return *this;
+    }}
+"#,
+                c_clone_func = c_clone_func,
+                class_name = class_name
+            )
+            .unwrap();
+            write!(
+                &mut pimpl_instances,
+                r#"    template {class_name}<true>::{class_name}(const {class_name}<true>&) noexcept;
+    template {class_name}<true> &{class_name}<true>::operator=(const {class_name}<true>&) noexcept;
+"#,
+                class_name = class_name
+            )
+            .unwrap();
+        } else {
+            write!(
+                cpp_include_f,
+                r#"
             {class_name}(const {class_name}& o) noexcept {{
                 static_assert(OWN_DATA, "copy possible only if class own data");
 
@@ -212,85 +504,26 @@ public:
                 return *this;
             }}
         "#,
-            c_clone_func = c_clone_func,
-            class_name = class_name
-        )
-        .map_err(map_write_err!(cpp_path))?;
+                c_clone_func = c_clone_func,
+                class_name = class_name
+            )
+            .map_err(map_write_err!(cpp_path))?;
+        }
     }
 
     let mut last_cpp_access = Some("public");
 
-    let dummy_ty = parse_type! { () };
-    let dummy_rust_ty = conv_map.find_or_alloc_rust_type_no_src_id(&dummy_ty);
-    let mut gen_code = Vec::new();
-
-    let (this_type_for_method, code_box_this) =
-        if let Some(this_type) = class.self_desc.as_ref().map(|x| &x.constructor_ret_type) {
-            let this_type = conv_map.find_or_alloc_rust_type_that_implements(
-                this_type,
-                "SwigForeignClass",
-                class.src_id,
-            );
+    let mut need_destructor = false;
 
-            let (this_type_for_method, code_box_this) =
-                convert_to_heap_pointer(conv_map, &this_type, "this");
-            let lifetimes = {
-                let mut ret = String::new();
-                let lifetimes = list_lifetimes(&this_type.ty);
-                for (i, l) in lifetimes.iter().enumerate() {
-                    ret.push_str(&*l.as_str());
-                    if i != lifetimes.len() - 1 {
-                        ret.push(',');
-                    }
-                }
-                ret
-            };
-            let unpack_code = unpack_from_heap_pointer(&this_type, TO_VAR_TEMPLATE, true);
-            let fclass_impl_code = format!(
-                r#"impl<{lifetimes}> SwigForeignClass for {class_name} {{
-    fn c_class_name() -> *const ::std::os::raw::c_char {{
-        swig_c_str!("{class_name}")
-    }}
-    fn box_object(this: Self) -> *mut ::std::os::raw::c_void {{
-{code_box_this}
-        this as *mut ::std::os::raw::c_void
-    }}
-    fn unbox_object(p: *mut ::std::os::raw::c_void) -> Self {{
-        let p = p as *mut {this_type_for_method};
-{unpack_code}
-       p
-    }}
-}}"#,
-                lifetimes = lifetimes,
-                class_name = DisplayToTokens(&this_type.ty),
-                code_box_this = code_box_this,
-                unpack_code = unpack_code.replace(TO_VAR_TEMPLATE, "p"),
-                this_type_for_method = this_type_for_method.normalized_name.clone()
-            );
-            gen_code.push(syn::parse_str(&fclass_impl_code).unwrap_or_else(|err| {
-                panic_on_syn_error("internal foreign class impl code", fclass_impl_code, err)
-            }));
-            (this_type_for_method, code_box_this)
+    for ((method, f_method), fallible) in
+        class.methods.iter().zip(methods_sign).zip(methods_fallible)
+    {
+        // C++ wrapper throws exception, if C function caught panic or conversation failed
+        let check_panic = if throw_on_panic || (!cfg.c_abi_only && fallible) {
+            "\n        rust_check_panic();"
         } else {
-            (dummy_rust_ty.clone(), String::new())
+            ""
         };
-    let no_this_info = || {
-        DiagnosticError::new(
-            class.src_id,
-            class.span(),
-            format!(
-                "Class {} (namespace {}) has methods, but there is no constructor\n
-May be you need to use `private constructor = empty;` syntax?",
-                class.name, cfg.namespace_name,
-            ),
-        )
-    };
-
-    let mut need_destructor = false;
-    //because of VC++ has problem with cross-references of types
-    let mut inline_impl = String::new();
-
-    for (method, f_method) in class.methods.iter().zip(methods_sign) {
         write!(
             c_include_f,
             "{}",
@@ -310,11 +543,24 @@ May be you need to use `private constructor = empty;` syntax?",
             write!(cpp_include_f, "{}:\n", method_access).map_err(map_write_err!(cpp_path))?;
         }
         last_cpp_access = Some(method_access);
-        let cpp_comments = cpp_code::doc_comments_to_c_comments(&method.doc_comments, false);
+        let cpp_comments = cpp_code::doc_comments_to_c_comments(
+            &cpp_code::cpp_method_doc_comments(
+                &method.doc_comments,
+                f_method,
+                method.variant == MethodVariant::Constructor,
+            ),
+            false,
+        );
         write!(cpp_include_f, "{}", cpp_comments,).map_err(map_write_err!(cpp_path))?;
-        let c_func_name = c_func_name(class, method);
-        let c_args_with_types = cpp_code::c_generate_args_with_types(f_method, false)
+        let c_func_name = c_func_name(cfg, class, method);
+        let mut c_args_with_types = cpp_code::c_generate_args_with_types(f_method, false)
             .map_err(|err| DiagnosticError::new(class.src_id, class.span(), err))?;
+        if let Some(ref completion) = f_method.completion {
+            if !c_args_with_types.is_empty() {
+                c_args_with_types.push_str(", ");
+            }
+            c_args_with_types.push_str(&awaitable::c_completion_args(completion));
+        }
         let comma_c_args_with_types = if c_args_with_types.is_empty() {
             String::new()
         } else {
@@ -324,35 +570,35 @@ May be you need to use `private constructor = empty;` syntax?",
 
         let cpp_args_with_types = cpp_code::cpp_generate_args_with_types(f_method)
             .map_err(|err| DiagnosticError::new(class.src_id, class.span(), err))?;
-        let cpp_args_for_c = cpp_code::cpp_generate_args_to_call_c(f_method)
+        let mut cpp_args_for_c = cpp_code::cpp_generate_args_to_call_c(f_method)
             .map_err(|err| DiagnosticError::new(class.src_id, class.span(), err))?;
-        let real_output_typename = match method.fn_decl.output {
-            syn::ReturnType::Default => "()",
-            syn::ReturnType::Type(_, ref t) => normalize_ty_lifetimes(&*t),
+        if let Some(ref completion) = f_method.completion {
+            if !cpp_args_for_c.is_empty() {
+                cpp_args_for_c.push_str(", ");
+            }
+            cpp_args_for_c.push_str(&awaitable::cpp_completion_args_for_c(completion));
+        }
+        let method_name = method.short_name().as_str().to_string();
+        let noexcept = if !check_panic.is_empty() {
+            "noexcept(false)"
+        } else {
+            cpp_code::cpp_method_noexcept(f_method)
         };
-
-        let rust_args_with_types = rust_generate_args_with_types(f_method)
-            .map_err(|err| DiagnosticError::new(class.src_id, class.span(), err))?;
-        let method_ctx = MethodContext {
-            class,
-            method,
-            f_method,
-            c_func_name: &c_func_name,
-            decl_func_args: &rust_args_with_types,
-            args_names: &args_names,
-            real_output_typename: &real_output_typename,
+        let nodiscard = cfg.nodiscard();
+        let (cpp_ret_type, convert_ret_for_cpp) = if let Some(ref completion) = f_method.completion
+        {
+            (
+                awaitable::cpp_awaitable_type(completion).into(),
+                "ret".to_string(),
+            )
+        } else if let Some(cpp_converter) = f_method.output.cpp_converter.as_ref() {
+            (
+                cpp_converter.typename.clone(),
+                cpp_converter.converter.replace(FROM_VAR_TEMPLATE, "ret"),
+            )
+        } else {
+            (f_method.output.as_ref().name.clone(), "ret".to_string())
         };
-
-        let method_name = method.short_name().as_str().to_string();
-        let (cpp_ret_type, convert_ret_for_cpp) =
-            if let Some(cpp_converter) = f_method.output.cpp_converter.as_ref() {
-                (
-                    cpp_converter.typename.clone(),
-                    cpp_converter.converter.replace(FROM_VAR_TEMPLATE, "ret"),
-                )
-            } else {
-                (f_method.output.as_ref().name.clone(), "ret".to_string())
-            };
         //rename types like "struct Foo" to "Foo" to make VC++ compiler happy
         let cpp_ret_type = cpp_ret_type.as_str().replace("struct", "");
 
@@ -361,32 +607,70 @@ May be you need to use `private constructor = empty;` syntax?",
                 write!(
                     c_include_f,
                     r#"
-    {ret_type} {c_func_name}({args_with_types});
+    {api}{ret_type} {c_func_name}({args_with_types});
 "#,
+                    api = c_api,
                     ret_type = f_method.output.as_ref().name,
                     c_func_name = c_func_name,
                     args_with_types = c_args_with_types,
                 )
                 .map_err(map_write_err!(c_path))?;
 
-                if f_method.output.as_ref().name != "void" {
+                if f_method.completion.is_some() {
                     write!(
                         cpp_include_f,
                         r#"
-    static {cpp_ret_type} {method_name}({cpp_args_with_types}) noexcept;
+    {nodiscard}static {cpp_ret_type} {method_name}({cpp_args_with_types}) {noexcept};
 "#,
                         method_name = method_name,
                         cpp_ret_type = cpp_ret_type,
                         cpp_args_with_types = cpp_args_with_types,
+                        noexcept = noexcept,
+                        nodiscard = nodiscard,
                     )
                     .map_err(map_write_err!(cpp_path))?;
                     write!(
                         &mut inline_impl,
                         r#"
     template<bool OWN_DATA>
-    inline {cpp_ret_type} {class_name}<OWN_DATA>::{method_name}({cpp_args_with_types}) noexcept
+    {inline}{cpp_ret_type} {class_name}<OWN_DATA>::{method_name}({cpp_args_with_types}) {noexcept}
     {{
-        {c_ret_type} ret = {c_func_name}({cpp_args_for_c});
+        {cpp_ret_type} ret;
+        {c_func_name}({cpp_args_for_c});{check_panic}
+        return ret;
+    }}
+"#,
+                        cpp_args_for_c = cpp_args_for_c,
+                        c_func_name = c_func_name,
+                        check_panic = check_panic,
+                        cpp_ret_type = cpp_ret_type,
+                        class_name = class_name,
+                        method_name = method_name,
+                        cpp_args_with_types = cpp_args_with_types,
+                        noexcept = noexcept,
+                        inline = inline,
+                    )
+                    .unwrap();
+                } else if f_method.output.as_ref().name != "void" {
+                    write!(
+                        cpp_include_f,
+                        r#"
+    {nodiscard}static {cpp_ret_type} {method_name}({cpp_args_with_types}) {noexcept};
+"#,
+                        method_name = method_name,
+                        cpp_ret_type = cpp_ret_type,
+                        cpp_args_with_types = cpp_args_with_types,
+                        noexcept = noexcept,
+                        nodiscard = nodiscard,
+                    )
+                    .map_err(map_write_err!(cpp_path))?;
+                    write!(
+                        &mut inline_impl,
+                        r#"
+    template<bool OWN_DATA>
+    {inline}{cpp_ret_type} {class_name}<OWN_DATA>::{method_name}({cpp_args_with_types}) {noexcept}
+    {{
+        {c_ret_type} ret = {c_func_name}({cpp_args_for_c});{check_panic}
         return {convert_ret_for_cpp};
     }}
 "#,
@@ -394,40 +678,61 @@ May be you need to use `private constructor = empty;` syntax?",
                         convert_ret_for_cpp = convert_ret_for_cpp,
                         cpp_args_for_c = cpp_args_for_c,
                         c_func_name = c_func_name,
+                        check_panic = check_panic,
                         cpp_ret_type = cpp_ret_type,
                         class_name = class_name,
                         method_name = method_name,
                         cpp_args_with_types = cpp_args_with_types,
+                        noexcept = noexcept,
+                        inline = inline,
                     )
                     .unwrap();
                 } else {
                     write!(
                         cpp_include_f,
                         r#"
-    static void {method_name}({cpp_args_with_types}) noexcept;
+    static void {method_name}({cpp_args_with_types}) {noexcept};
 "#,
                         method_name = method_name,
                         cpp_args_with_types = cpp_args_with_types,
+                        noexcept = noexcept,
                     )
                     .map_err(map_write_err!(cpp_path))?;
                     write!(
                         &mut inline_impl,
                         r#"
     template<bool OWN_DATA>
-    inline void {class_name}<OWN_DATA>::{method_name}({cpp_args_with_types}) noexcept
+    {inline}void {class_name}<OWN_DATA>::{method_name}({cpp_args_with_types}) {noexcept}
     {{
-        {c_func_name}({cpp_args_for_c});
+        {c_func_name}({cpp_args_for_c});{check_panic}
     }}
 "#,
                         cpp_args_with_types = cpp_args_with_types,
+                        noexcept = noexcept,
                         class_name = class_name,
                         method_name = method_name,
                         c_func_name = c_func_name,
+                        check_panic = check_panic,
                         cpp_args_for_c = cpp_args_for_c,
+                        inline = inline,
                     )
                     .unwrap();
                 }
-                gen_code.append(&mut generate_static_method(conv_map, &method_ctx)?);
+                if cfg.pimpl {
+                    write_pimpl_instances(
+                        &mut pimpl_instances,
+                        &class_name,
+                        if f_method.output.as_ref().name != "void" || f_method.completion.is_some()
+                        {
+                            &cpp_ret_type
+                        } else {
+                            "void"
+                        },
+                        &method_name,
+                        &format!("({}) {}", cpp_args_with_types, noexcept),
+                        true,
+                    );
+                }
             }
             MethodVariant::Method(ref self_variant) => {
                 let const_if_readonly = if self_variant.is_read_only() {
@@ -438,8 +743,9 @@ May be you need to use `private constructor = empty;` syntax?",
                 write!(
                     c_include_f,
                     r#"
-    {ret_type} {func_name}({const_if_readonly}{c_class_type} * const self{args_with_types});
+    {api}{ret_type} {func_name}({const_if_readonly}{c_class_type} * const self{args_with_types});
 "#,
+                    api = c_api,
                     ret_type = f_method.output.as_ref().name,
                     c_class_type = c_class_type,
                     func_name = c_func_name,
@@ -448,23 +754,62 @@ May be you need to use `private constructor = empty;` syntax?",
                 )
                 .map_err(map_write_err!(c_path))?;
 
-                if f_method.output.as_ref().name != "void" {
+                if f_method.completion.is_some() {
                     write!(
                         cpp_include_f,
                         r#"
-    {cpp_ret_type} {method_name}({cpp_args_with_types}) {const_if_readonly} noexcept;
+    {nodiscard}{cpp_ret_type} {method_name}({cpp_args_with_types}) {const_if_readonly} {noexcept};
 "#,
                         method_name = method_name,
                         cpp_ret_type = cpp_ret_type,
                         cpp_args_with_types = cpp_args_with_types,
+                        noexcept = noexcept,
+                        nodiscard = nodiscard,
+                        const_if_readonly = const_if_readonly,
+                    )
+                    .map_err(map_write_err!(cpp_path))?;
+                    write!(
+                        &mut inline_impl,
+                        r#"
+    template<bool OWN_DATA>
+    {inline}{cpp_ret_type} {class_name}<OWN_DATA>::{method_name}({cpp_args_with_types}) {const_if_readonly} {noexcept}
+    {{
+        {cpp_ret_type} ret;
+        {c_func_name}(this->self_, {cpp_args_for_c});{check_panic}
+        return ret;
+    }}
+"#,
+                        method_name = method_name,
+                        class_name = class_name,
+                        cpp_ret_type = cpp_ret_type,
+                        c_func_name = c_func_name,
+                        check_panic = check_panic,
+                        cpp_args_with_types = cpp_args_with_types,
+                        noexcept = noexcept,
+                        cpp_args_for_c = cpp_args_for_c,
+                        const_if_readonly = const_if_readonly,
+                        inline = inline,
+                    )
+                    .unwrap();
+                } else if f_method.output.as_ref().name != "void" {
+                    write!(
+                        cpp_include_f,
+                        r#"
+    {nodiscard}{cpp_ret_type} {method_name}({cpp_args_with_types}) {const_if_readonly} {noexcept};
+"#,
+                        method_name = method_name,
+                        cpp_ret_type = cpp_ret_type,
+                        cpp_args_with_types = cpp_args_with_types,
+                        noexcept = noexcept,
+                        nodiscard = nodiscard,
                         const_if_readonly = const_if_readonly,
                     )
                     .map_err(map_write_err!(cpp_path))?;
                     write!(&mut inline_impl, r#"
     template<bool OWN_DATA>
-    inline {cpp_ret_type} {class_name}<OWN_DATA>::{method_name}({cpp_args_with_types}) {const_if_readonly} noexcept
+    {inline}{cpp_ret_type} {class_name}<OWN_DATA>::{method_name}({cpp_args_with_types}) {const_if_readonly} {noexcept}
     {{
-        {c_ret_type} ret = {c_func_name}(this->self_{cpp_args_for_c});
+        {c_ret_type} ret = {c_func_name}(this->self_{cpp_args_for_c});{check_panic}
         return {convert_ret_for_cpp};
     }}
 "#,
@@ -474,52 +819,69 @@ May be you need to use `private constructor = empty;` syntax?",
                            class_name = class_name,
                            cpp_ret_type = cpp_ret_type,
                            c_func_name = c_func_name,
+                        check_panic = check_panic,
                            cpp_args_with_types = cpp_args_with_types,
+                           noexcept = noexcept,
                                                    cpp_args_for_c = if args_names.is_empty() {
                             String::new()
                         } else {
                             format!(", {}", cpp_args_for_c)
                                                    },
                            const_if_readonly = const_if_readonly,
+                           inline = inline,
                     ).unwrap();
                 } else {
                     write!(
                         cpp_include_f,
                         r#"
-    void {method_name}({cpp_args_with_types}) {const_if_readonly} noexcept;
+    void {method_name}({cpp_args_with_types}) {const_if_readonly} {noexcept};
 "#,
                         method_name = method_name,
                         cpp_args_with_types = cpp_args_with_types,
+                        noexcept = noexcept,
                         const_if_readonly = const_if_readonly,
                     )
                     .map_err(map_write_err!(cpp_path))?;
                     write!(&mut inline_impl, r#"
     template<bool OWN_DATA>
-    inline void {class_name}<OWN_DATA>::{method_name}({cpp_args_with_types}) {const_if_readonly} noexcept
+    {inline}void {class_name}<OWN_DATA>::{method_name}({cpp_args_with_types}) {const_if_readonly} {noexcept}
     {{
-        {c_func_name}(this->self_{cpp_args_for_c});
+        {c_func_name}(this->self_{cpp_args_for_c});{check_panic}
     }}
 "#,
                            method_name = method_name,
                            c_func_name = c_func_name,
+                        check_panic = check_panic,
                            class_name = class_name,
                            cpp_args_with_types = cpp_args_with_types,
+                           noexcept = noexcept,
                            cpp_args_for_c = if args_names.is_empty() {
                                String::new()
                         } else {
                             format!(", {}", cpp_args_for_c)
                            },
                            const_if_readonly = const_if_readonly,
+                           inline = inline,
                     ).unwrap();
                 }
-
-                gen_code.append(&mut generate_method(
-                    conv_map,
-                    &method_ctx,
-                    class,
-                    *self_variant,
-                    &this_type_for_method,
-                )?);
+                if cfg.pimpl {
+                    write_pimpl_instances(
+                        &mut pimpl_instances,
+                        &class_name,
+                        if f_method.output.as_ref().name != "void" || f_method.completion.is_some()
+                        {
+                            &cpp_ret_type
+                        } else {
+                            "void"
+                        },
+                        &method_name,
+                        &format!(
+                            "({}) {}{}",
+                            cpp_args_with_types, const_if_readonly, noexcept
+                        ),
+                        self_variant.is_read_only(),
+                    );
+                }
             }
             MethodVariant::Constructor => {
                 need_destructor = true;
@@ -536,53 +898,140 @@ May be you need to use `private constructor = empty;` syntax?",
                     write!(
                         c_include_f,
                         r#"
-    {c_class_type} *{func_name}({args_with_types});
+    {api}{c_class_type} *{func_name}({args_with_types});
 "#,
+                        api = c_api,
                         c_class_type = c_class_type,
                         func_name = c_func_name,
                         args_with_types = c_args_with_types,
                     )
                     .map_err(map_write_err!(c_path))?;
 
-                    write!(
-                        cpp_include_f,
-                        r#"
-    {class_name}({cpp_args_with_types}) noexcept
+                    if cfg.pimpl {
+                        write!(
+                            cpp_include_f,
+                            r#"
+    {class_name}({cpp_args_with_types}) {noexcept};
+"#,
+                            cpp_args_with_types = cpp_args_with_types,
+                            noexcept = noexcept,
+                            class_name = class_name,
+                        )
+                        .map_err(map_write_err!(cpp_path))?;
+                        write!(
+                            &mut inline_impl,
+                            r#"
+    template<bool OWN_DATA>
+    {class_name}<OWN_DATA>::{class_name}({cpp_args_with_types}) {noexcept}
     {{
-        this->self_ = {c_func_name}({cpp_args_for_c});
+        this->self_ = {c_func_name}({cpp_args_for_c});{check_panic}
         if (this->self_ == nullptr) {{
             std::abort();
         }}
     }}
 "#,
-                        c_func_name = c_func_name,
-                        cpp_args_with_types = cpp_args_with_types,
-                        class_name = class_name,
-                        cpp_args_for_c = cpp_args_for_c,
-                    )
-                    .map_err(map_write_err!(cpp_path))?;
-
-                    let constructor_ret_type = class
-                        .self_desc
-                        .as_ref()
-                        .map(|x| &x.constructor_ret_type)
-                        .ok_or_else(&no_this_info)?
-                        .clone();
-                    let this_type = constructor_ret_type.clone();
-                    gen_code.append(&mut generate_constructor(
-                        conv_map,
-                        &method_ctx,
-                        constructor_ret_type,
-                        this_type,
-                        &code_box_this,
-                    )?);
+                            c_func_name = c_func_name,
+                            check_panic = check_panic,
+                            cpp_args_with_types = cpp_args_with_types,
+                            noexcept = noexcept,
+                            class_name = class_name,
+                            cpp_args_for_c = cpp_args_for_c,
+                        )
+                        .unwrap();
+                        write_pimpl_instances(
+                            &mut pimpl_instances,
+                            &class_name,
+                            "",
+                            &class_name,
+                            &format!("({}) {}", cpp_args_with_types, noexcept),
+                            true,
+                        );
+                    } else {
+                        write!(
+                            cpp_include_f,
+                            r#"
+    {class_name}({cpp_args_with_types}) {noexcept}
+    {{
+        this->self_ = {c_func_name}({cpp_args_for_c});{check_panic}
+        if (this->self_ == nullptr) {{
+            std::abort();
+        }}
+    }}
+"#,
+                            c_func_name = c_func_name,
+                            check_panic = check_panic,
+                            cpp_args_with_types = cpp_args_with_types,
+                            noexcept = noexcept,
+                            class_name = class_name,
+                            cpp_args_for_c = cpp_args_for_c,
+                        )
+                        .map_err(map_write_err!(cpp_path))?;
+                    }
+                    if unique_ptr_factories {
+                        let moved_args = (0..f_method.input.len())
+                            .map(|i| format!("std::move(a_{})", i))
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        let create_ret_type = format!("std::unique_ptr<{}<true>>", class_name);
+                        if cfg.pimpl {
+                            write!(
+                                cpp_include_f,
+                                r#"
+    {nodiscard}static {create_ret_type} create({cpp_args_with_types}) noexcept(false);
+"#,
+                                nodiscard = nodiscard,
+                                cpp_args_with_types = cpp_args_with_types,
+                                create_ret_type = create_ret_type,
+                            )
+                            .map_err(map_write_err!(cpp_path))?;
+                            write!(
+                                &mut inline_impl,
+                                r#"
+    template<bool OWN_DATA>
+    {create_ret_type} {class_name}<OWN_DATA>::create({cpp_args_with_types}) noexcept(false)
+    {{
+        return {create_ret_type}(new {class_name}<true>({moved_args}));
+    }}
+"#,
+                                cpp_args_with_types = cpp_args_with_types,
+                                class_name = class_name,
+                                create_ret_type = create_ret_type,
+                                moved_args = moved_args,
+                            )
+                            .unwrap();
+                            write_pimpl_instances(
+                                &mut pimpl_instances,
+                                &class_name,
+                                &create_ret_type,
+                                "create",
+                                &format!("({}) noexcept(false)", cpp_args_with_types),
+                                true,
+                            );
+                        } else {
+                            write!(
+                                cpp_include_f,
+                                r#"
+    {nodiscard}static {create_ret_type} create({cpp_args_with_types}) noexcept(false)
+    {{
+        return {create_ret_type}(new {class_name}<true>({moved_args}));
+    }}
+"#,
+                                nodiscard = nodiscard,
+                                cpp_args_with_types = cpp_args_with_types,
+                                class_name = class_name,
+                                create_ret_type = create_ret_type,
+                                moved_args = moved_args,
+                            )
+                            .map_err(map_write_err!(cpp_path))?;
+                        }
+                    }
                 }
             }
         }
     }
 
     if need_destructor {
-        let this_type: RustType = conv_map.find_or_alloc_rust_type(
+        let this_type: RustTypeIdx = conv_map.find_or_alloc_rust_type(
             class
                 .self_desc
                 .as_ref()
@@ -591,20 +1040,31 @@ May be you need to use `private constructor = empty;` syntax?",
             class.src_id,
         );
 
-        let unpack_code = unpack_from_heap_pointer(&this_type, "this", false);
-        let c_destructor_name = format!("{}_delete", class.name);
+        let unpack_code = unpack_from_heap_pointer(&conv_map[this_type], "this", false);
+        let c_destructor_name = cfg.c_symbol(&format!("{}_delete", class.name));
         let code = format!(
             r#"
 #[allow(unused_variables, unused_mut, non_snake_case)]
 #[no_mangle]
 pub extern "C" fn {c_destructor_name}(this: *mut {this_type}) {{
-{unpack_code}
-    drop(this);
+{body}
 }}
 "#,
             c_destructor_name = c_destructor_name,
-            unpack_code = unpack_code,
-            this_type = this_type_for_method.normalized_name,
+            this_type = conv_map[this_type_for_method].normalized_name,
+            body = catch_panic_code(
+                panic_action.is_some(),
+                "()",
+                format!(
+                    "{}{}\n    drop(this);",
+                    if cfg.tracing_spans {
+                        tracing_spans::destructor_span_code(class)
+                    } else {
+                        String::new()
+                    },
+                    unpack_code
+                )
+            ),
         );
         debug!("we generate and parse code: {}", code);
         gen_code.push(
@@ -615,16 +1075,58 @@ pub extern "C" fn {c_destructor_name}(this: *mut {this_type}) {{
         write!(
             c_include_f,
             r#"
-    void {c_destructor_name}(const {c_class_type} *self);
+    {api}void {c_destructor_name}(const {c_class_type} *self);
 "#,
+            api = c_api,
             c_class_type = c_class_type,
             c_destructor_name = c_destructor_name,
         )
         .map_err(map_write_err!(c_path))?;
 
-        write!(
-            cpp_include_f,
-            r#"
+        if cfg.pimpl {
+            write!(
+                cpp_include_f,
+                r#"
+private:
+   static void free_mem(SelfType &p) noexcept;
+public:
+    ~{class_name}() noexcept
+    {{
+        free_mem(this->self_);
+    }}
+"#,
+                class_name = class_name,
+            )
+            .map_err(map_write_err!(cpp_path))?;
+            write!(
+                &mut inline_impl,
+                r#"
+    template<bool OWN_DATA>
+    void {class_name}<OWN_DATA>::free_mem(SelfType &p) noexcept
+    {{
+        if (OWN_DATA && p != nullptr) {{
+            {c_destructor_name}(p);
+        }}
+        p = nullptr;
+    }}
+"#,
+                c_destructor_name = c_destructor_name,
+                class_name = class_name,
+            )
+            .unwrap();
+            for own_data in &["true", "false"] {
+                writeln!(
+                    &mut pimpl_instances,
+                    "    template void {class_name}<{own_data}>::free_mem({class_name}<{own_data}>::SelfType &) noexcept;",
+                    class_name = class_name,
+                    own_data = own_data,
+                )
+                .unwrap();
+            }
+        } else {
+            write!(
+                cpp_include_f,
+                r#"
 private:
    static void free_mem(SelfType &p) noexcept
    {{
@@ -639,10 +1141,11 @@ public:
         free_mem(this->self_);
     }}
 "#,
-            c_destructor_name = c_destructor_name,
-            class_name = class_name,
-        )
-        .map_err(map_write_err!(cpp_path))?;
+                c_destructor_name = c_destructor_name,
+                class_name = class_name,
+            )
+            .map_err(map_write_err!(cpp_path))?;
+        }
     } else {
         // not need_destructor
         write!(
@@ -681,14 +1184,53 @@ private:
     .map_err(map_write_err!(cpp_path))?;
 
     // Write method implementations.
-    if cfg.separate_impl_headers {
+    if cfg.pimpl {
         write!(
             cpp_include_f,
             r#"
 
-}} // namespace {namespace}
+{close_namespace}
 "#,
-            namespace = cfg.namespace_name
+            close_namespace = close_namespace,
+        )
+        .map_err(map_write_err!(cpp_path))?;
+        let cpp_src_path = cfg.output_dir.join(cpp_code::cpp_source_name(class));
+        let mut cpp_src_f = FileWriteCache::new(&cpp_src_path);
+        let mut src_includes = String::new();
+        for inc in req_includes.iter().filter(|inc| **inc != self_include) {
+            writeln!(&mut src_includes, r#"#include {}"#, inc).unwrap();
+        }
+        write!(
+            cpp_src_f,
+            r#"// Automaticaly generated by rust_swig
+#include "{class_name}.hpp"
+{src_includes}
+#include "c_{class_name}.h"
+
+{open_namespace}
+"#,
+            class_name = class.name,
+            src_includes = src_includes,
+            open_namespace = open_namespace,
+        )
+        .map_err(map_write_err!(cpp_src_path))?;
+        inline_impl.push('\n');
+        inline_impl.push_str(&pimpl_instances);
+        write_methods_impls(&mut cpp_src_f, &close_namespace, &inline_impl)
+            .map_err(map_write_err!(cpp_src_path))?;
+        if !cfg.c_abi_only {
+            cpp_src_f
+                .update_file_if_necessary()
+                .map_err(map_write_err!(cpp_src_path))?;
+        }
+    } else if cfg.separate_impl_headers {
+        write!(
+            cpp_include_f,
+            r#"
+
+{close_namespace}
+"#,
+            close_namespace = close_namespace,
         )
         .map_err(map_write_err!(cpp_path))?;
         let cpp_impl_path = cfg.output_dir.join(format!("{}_impl.hpp", class.name));
@@ -700,59 +1242,89 @@ private:
 
 #include "{class_name}.hpp"
 
-namespace {namespace} {{
+{open_namespace}
 "#,
             class_name = class.name,
-            namespace = cfg.namespace_name,
+            open_namespace = open_namespace,
         )
         .map_err(map_write_err!(cpp_impl_path))?;
-        write_methods_impls(&mut cpp_impl_f, &cfg.namespace_name, &inline_impl)
-            .map_err(map_write_err!(cpp_impl_path))?;
-        cpp_impl_f
-            .update_file_if_necessary()
+        write_methods_impls(&mut cpp_impl_f, &close_namespace, &inline_impl)
             .map_err(map_write_err!(cpp_impl_path))?;
+        if !cfg.c_abi_only {
+            cpp_impl_f
+                .update_file_if_necessary()
+                .map_err(map_write_err!(cpp_impl_path))?;
+        }
     } else {
-        write_methods_impls(&mut cpp_include_f, &cfg.namespace_name, &inline_impl)
+        write_methods_impls(&mut cpp_include_f, &close_namespace, &inline_impl)
             .map_err(map_write_err!(cpp_path))?;
     }
 
+    let class_namespace_aliases = match class.cpp_namespace {
+        Some(ref class_namespace) => format!(
+            r#"
+{open_namespace}
+using ::{namespace}::{class_name};
+using ::{namespace}::{class_name}Ref;
+{close_namespace}
+"#,
+            open_namespace = cpp_code::open_namespace(class_namespace, nested_namespace_syntax),
+            close_namespace = cpp_code::close_namespace(class_namespace, nested_namespace_syntax),
+            namespace = cfg.namespace_name,
+            class_name = class.name,
+        ),
+        None => String::new(),
+    };
+    write!(cpp_include_f, "{}", class_namespace_aliases).map_err(map_write_err!(cpp_path))?;
+
     write!(
         cpp_fwd_f,
         r#"// Automaticaly generated by rust_swig
 #pragma once
 
-namespace {namespace} {{
+{open_namespace}
 template<bool>
 class {base_class_name};
 using {class_name} = {base_class_name}<true>;
 using {class_name}Ref = {base_class_name}<false>;
-}} // namespace {namespace}
-"#,
-        namespace = cfg.namespace_name,
+{close_namespace}
+{class_namespace_aliases}"#,
+        open_namespace = open_namespace,
+        close_namespace = close_namespace,
+        class_namespace_aliases = class_namespace_aliases,
         class_name = class.name,
         base_class_name = class_name
     )
     .map_err(map_write_err!(cpp_fwd_path))?;
 
-    cpp_fwd_f
-        .update_file_if_necessary()
-        .map_err(map_write_err!(cpp_fwd_path))?;
     c_include_f
         .update_file_if_necessary()
         .map_err(map_write_err!(c_path))?;
-    cpp_include_f
-        .update_file_if_necessary()
-        .map_err(map_write_err!(cpp_path))?;
+    if !cfg.c_abi_only {
+        cpp_fwd_f
+            .update_file_if_necessary()
+            .map_err(map_write_err!(cpp_fwd_path))?;
+        cpp_include_f
+            .update_file_if_necessary()
+            .map_err(map_write_err!(cpp_path))?;
+    }
     Ok(gen_code)
 }
 
+/// Code that enters span of `tracing` crate and records sizes of arguments,
+/// if it is enabled
+fn tracing_span_code(mc: &MethodContext) -> (String, String) {
+    if mc.tracing_spans {
+        tracing_spans::method_span_code(mc.class, mc.method, mc.f_method.input.len())
+    } else {
+        (String::new(), String::new())
+    }
+}
+
 fn generate_static_method(conv_map: &mut TypeMap, mc: &MethodContext) -> Result<Vec<TokenStream>> {
-    let c_ret_type = mc
-        .f_method
-        .output
-        .as_ref()
-        .correspoding_rust_type
-        .typename();
+    let c_ret_type = conv_map[mc.f_method.output.as_ref().correspoding_rust_type]
+        .typename()
+        .to_string();
     let (mut deps_code_out, convert_output_code) = foreign_from_rust_convert_method_output(
         conv_map,
         mc.class.src_id,
@@ -770,25 +1342,37 @@ fn generate_static_method(conv_map: &mut TypeMap, mc: &MethodContext) -> Result<
         (0..n_args).map(|v| format!("a_{}", v)),
         &c_ret_type,
     )?;
+    let (span_code, record_code) = tracing_span_code(mc);
     let code = format!(
         r#"
 #[allow(non_snake_case, unused_variables, unused_mut)]
 #[no_mangle]
 pub extern "C" fn {func_name}({decl_func_args}) -> {c_ret_type} {{
-{convert_input_code}
-    let mut ret: {real_output_typename} = {rust_func_name}({args_names});
-{convert_output_code}
-    ret
+{body}
 }}
 "#,
         func_name = mc.c_func_name,
         decl_func_args = mc.decl_func_args,
         c_ret_type = c_ret_type,
-        convert_input_code = convert_input_code,
-        rust_func_name = DisplayToTokens(&mc.method.rust_id),
-        args_names = mc.args_names,
-        convert_output_code = convert_output_code,
-        real_output_typename = mc.real_output_typename,
+        body = catch_panic_code(
+            mc.catch_panics,
+            &c_ret_type,
+            format!(
+                r#"{span_code}{convert_input_code}
+{record_code}{completion_code}
+    let mut ret: {real_output_typename} = {rust_func_name}({args_names});
+{convert_output_code}
+    ret"#,
+                span_code = span_code,
+                convert_input_code = convert_input_code,
+                record_code = record_code,
+                completion_code = mc.completion_code,
+                rust_func_name = DisplayToTokens(&mc.method.rust_id),
+                args_names = mc.args_names,
+                convert_output_code = convert_output_code,
+                real_output_typename = mc.real_output_typename,
+            )
+        ),
     );
     let mut gen_code = deps_code_in;
     gen_code.append(&mut deps_code_out);
@@ -804,14 +1388,11 @@ fn generate_method(
     mc: &MethodContext,
     class: &ForeignerClassInfo,
     self_variant: SelfTypeVariant,
-    this_type_for_method: &RustType,
+    this_type_for_method: RustTypeIdx,
 ) -> Result<Vec<TokenStream>> {
-    let c_ret_type = mc
-        .f_method
-        .output
-        .as_ref()
-        .correspoding_rust_type
-        .typename();
+    let c_ret_type = conv_map[mc.f_method.output.as_ref().correspoding_rust_type]
+        .typename()
+        .to_string();
     let n_args = mc.f_method.input.len();
     let (deps_code_in, convert_input_code) = foreign_to_rust_convert_method_inputs(
         conv_map,
@@ -833,45 +1414,57 @@ fn generate_method(
     let (from_ty, to_ty): (Type, Type) = create_suitable_types_for_constructor_and_self(
         self_variant,
         class,
-        &this_type_for_method.ty,
+        &conv_map[this_type_for_method].ty,
     );
 
     let from_ty = conv_map.find_or_alloc_rust_type(&from_ty, class.src_id);
     let to_ty = conv_map.find_or_alloc_rust_type(&to_ty, class.src_id);
 
     let (mut deps_this, convert_this) = conv_map.convert_rust_types(
-        from_ty.to_idx(),
-        to_ty.to_idx(),
+        from_ty,
+        to_ty,
         "this",
         &c_ret_type,
         (mc.class.src_id, mc.method.span()),
     )?;
+    let (span_code, record_code) = tracing_span_code(mc);
     let code = format!(
         r#"
 #[allow(non_snake_case, unused_variables, unused_mut)]
 #[no_mangle]
 pub extern "C" fn {func_name}(this: *mut {this_type}, {decl_func_args}) -> {c_ret_type} {{
-{convert_input_code}
+{body}
+}}
+"#,
+        func_name = mc.c_func_name,
+        decl_func_args = mc.decl_func_args,
+        c_ret_type = c_ret_type,
+        this_type = conv_map[this_type_for_method].normalized_name,
+        body = catch_panic_code(
+            mc.catch_panics,
+            &c_ret_type,
+            format!(
+                r#"{span_code}{convert_input_code}
+{record_code}{completion_code}
     let this: {this_type_ref} = unsafe {{
         this.as_mut().unwrap()
     }};
 {convert_this}
     let mut ret: {real_output_typename} = {rust_func_name}(this, {args_names});
 {convert_output_code}
-    ret
-}}
-"#,
-        func_name = mc.c_func_name,
-        decl_func_args = mc.decl_func_args,
-        convert_input_code = convert_input_code,
-        c_ret_type = c_ret_type,
-        this_type_ref = from_ty.normalized_name,
-        this_type = this_type_for_method.normalized_name,
-        convert_this = convert_this,
-        rust_func_name = DisplayToTokens(&mc.method.rust_id),
-        args_names = mc.args_names,
-        convert_output_code = convert_output_code,
-        real_output_typename = mc.real_output_typename,
+    ret"#,
+                span_code = span_code,
+                convert_input_code = convert_input_code,
+                record_code = record_code,
+                completion_code = mc.completion_code,
+                this_type_ref = conv_map[from_ty].normalized_name,
+                convert_this = convert_this,
+                rust_func_name = DisplayToTokens(&mc.method.rust_id),
+                args_names = mc.args_names,
+                convert_output_code = convert_output_code,
+                real_output_typename = mc.real_output_typename,
+            )
+        ),
     );
 
     let mut gen_code = deps_code_in;
@@ -892,45 +1485,56 @@ fn generate_constructor(
     code_box_this: &str,
 ) -> Result<Vec<TokenStream>> {
     let n_args = mc.f_method.input.len();
-    let this_type: RustType = conv_map.ty_to_rust_type(&this_type);
-    let ret_type_name = this_type.normalized_name.as_str();
+    let this_type: RustTypeIdx = conv_map.ty_to_rust_type(&this_type);
+    // return type of C function
+    let ret_type_name = "*const ::std::os::raw::c_void";
     let (deps_code_in, convert_input_code) = foreign_to_rust_convert_method_inputs(
         conv_map,
         mc.class.src_id,
         mc.method,
         mc.f_method,
         (0..n_args).map(|v| format!("a_{}", v)),
-        &ret_type_name,
+        ret_type_name,
     )?;
-    let construct_ret_type: RustType = conv_map.ty_to_rust_type(&construct_ret_type);
+    let construct_ret_type: RustTypeIdx = conv_map.ty_to_rust_type(&construct_ret_type);
     let (mut deps_this, convert_this) = conv_map.convert_rust_types(
-        construct_ret_type.to_idx(),
-        this_type.to_idx(),
+        construct_ret_type,
+        this_type,
         "this",
-        &ret_type_name,
+        ret_type_name,
         (mc.class.src_id, mc.method.span()),
     )?;
 
+    let (span_code, record_code) = tracing_span_code(mc);
     let code = format!(
         r#"
 #[allow(unused_variables, unused_mut, non_snake_case)]
 #[no_mangle]
 pub extern "C" fn {func_name}({decl_func_args}) -> *const ::std::os::raw::c_void {{
-{convert_input_code}
-    let this: {real_output_typename} = {rust_func_name}({args_names});
-{convert_this}
-{box_this}
-    this as *const ::std::os::raw::c_void
+{body}
 }}
 "#,
         func_name = mc.c_func_name,
-        convert_this = convert_this,
         decl_func_args = mc.decl_func_args,
-        convert_input_code = convert_input_code,
-        rust_func_name = DisplayToTokens(&mc.method.rust_id),
-        args_names = mc.args_names,
-        box_this = code_box_this,
-        real_output_typename = &construct_ret_type.normalized_name.as_str(),
+        body = catch_panic_code(
+            mc.catch_panics,
+            ret_type_name,
+            format!(
+                r#"{span_code}{convert_input_code}
+{record_code}    let this: {real_output_typename} = {rust_func_name}({args_names});
+{convert_this}
+{box_this}
+    this as *const ::std::os::raw::c_void"#,
+                convert_this = convert_this,
+                span_code = span_code,
+                convert_input_code = convert_input_code,
+                record_code = record_code,
+                rust_func_name = DisplayToTokens(&mc.method.rust_id),
+                args_names = mc.args_names,
+                box_this = code_box_this,
+                real_output_typename = &conv_map[construct_ret_type].normalized_name.as_str(),
+            )
+        ),
     );
     let mut gen_code = deps_code_in;
     gen_code.append(&mut deps_this);
@@ -941,18 +1545,64 @@ pub extern "C" fn {func_name}({decl_func_args}) -> *const ::std::os::raw::c_void
     Ok(gen_code)
 }
 
+/// Explicit instantiation of `ret_type Foo<OWN_DATA>::member(args)`
+/// for `Foo` and, if `for_ref`, for `FooRef`
+fn write_pimpl_instances(
+    out: &mut String,
+    class_name: &str,
+    ret_type: &str,
+    member: &str,
+    args_and_qualifiers: &str,
+    for_ref: bool,
+) {
+    use std::fmt::Write;
+
+    let own_data_variants: &[&str] = if for_ref {
+        &["true", "false"]
+    } else {
+        &["true"]
+    };
+    for own_data in own_data_variants {
+        writeln!(
+            out,
+            "    template {ret_type}{space}{class_name}<{own_data}>::{member}{args};",
+            ret_type = ret_type,
+            space = if ret_type.is_empty() { "" } else { " " },
+            class_name = class_name,
+            own_data = own_data,
+            member = member,
+            args = args_and_qualifiers,
+        )
+        .unwrap();
+    }
+}
+
+/// In PIMPL mode header of other class is replaced by its forward declaration
+fn pimpl_include(conv_map: &TypeMap, inc: &SmolStr) -> SmolStr {
+    if inc.starts_with('"') && inc.ends_with(".hpp\"") {
+        let name = &inc[1..inc.len() - ".hpp\"".len()];
+        if conv_map
+            .find_foreign_type_by_name(&format!("{}Ref", name))
+            .is_some()
+        {
+            return format!("\"{}_fwd.hpp\"", name).into();
+        }
+    }
+    inc.clone()
+}
+
 fn write_methods_impls(
     file: &mut FileWriteCache,
-    namespace_name: &str,
+    close_namespace: &str,
     inline_impl: &str,
 ) -> std::io::Result<()> {
     write!(
         file,
         r#"
 {inline_impl}
-}} // namespace {namespace}
+{close_namespace}
 "#,
-        namespace = namespace_name,
+        close_namespace = close_namespace,
         inline_impl = inline_impl,
     )
 }
@@ -965,51 +1615,91 @@ pub(in crate::cpp) fn find_suitable_foreign_types_for_methods(
     let mut ret = Vec::<CppForeignMethodSignature>::with_capacity(class.methods.len());
     let dummy_ty = parse_type! { () };
     let dummy_rust_ty = conv_map.find_or_alloc_rust_type_no_src_id(&dummy_ty);
+    let mut unsupported = UnsupportedTypes::default();
 
     for method in &class.methods {
+        let _timer = profiler::timer(Stage::MethodConversions, || {
+            format!("{}::{}", class.name, method.short_name())
+        });
         //skip self argument
         let skip_n = match method.variant {
             MethodVariant::Method(_) => 1,
             _ => 0,
         };
         assert!(method.fn_decl.inputs.len() >= skip_n);
-        let mut input =
-            Vec::<CppForeignTypeInfo>::with_capacity(method.fn_decl.inputs.len() - skip_n);
-        for arg in method.fn_decl.inputs.iter().skip(skip_n) {
+        let completion = if method.awaitable {
+            Some(awaitable::map_completion_type(
+                conv_map, cpp_cfg, class, method,
+            )?)
+        } else {
+            None
+        };
+        //skip completion callback
+        let n_inputs = method.fn_decl.inputs.len() - skip_n - completion.iter().count();
+        let mut input = Vec::<CppForeignTypeInfo>::with_capacity(n_inputs);
+        let mut all_inputs_supported = true;
+        for (i, arg) in method
+            .fn_decl
+            .inputs
+            .iter()
+            .skip(skip_n)
+            .take(n_inputs)
+            .enumerate()
+        {
             let arg_rust_ty = conv_map.find_or_alloc_rust_type(fn_arg_type(arg), class.src_id);
-            input.push(map_type(
+            match map_type(
                 conv_map,
                 cpp_cfg,
-                &arg_rust_ty,
+                arg_rust_ty,
                 Direction::Incoming,
                 (class.src_id, fn_arg_type(arg).span()),
-            )?);
+            ) {
+                Ok(fti) => input.push(fti),
+                Err(err) => {
+                    unsupported.add(class, method, Some((i + 1, arg)), fn_arg_type(arg), err);
+                    all_inputs_supported = false;
+                }
+            }
         }
         let output: CppForeignTypeInfo = match method.variant {
             MethodVariant::Constructor => ForeignTypeInfo {
                 name: "".into(),
-                correspoding_rust_type: dummy_rust_ty.clone(),
+                correspoding_rust_type: dummy_rust_ty,
             }
             .into(),
             _ => match method.fn_decl.output {
                 syn::ReturnType::Default => ForeignTypeInfo {
                     name: "void".into(),
-                    correspoding_rust_type: dummy_rust_ty.clone(),
+                    correspoding_rust_type: dummy_rust_ty,
                 }
                 .into(),
                 syn::ReturnType::Type(_, ref rt) => {
                     let ret_rust_ty = conv_map.find_or_alloc_rust_type(rt, class.src_id);
-                    map_type(
+                    match map_type(
                         conv_map,
                         cpp_cfg,
-                        &ret_rust_ty,
+                        ret_rust_ty,
                         Direction::Outgoing,
                         (class.src_id, rt.span()),
-                    )?
+                    ) {
+                        Ok(fti) => fti,
+                        Err(err) => {
+                            unsupported.add(class, method, None, rt, err);
+                            continue;
+                        }
+                    }
                 }
             },
         };
-        ret.push(CppForeignMethodSignature { output, input });
+        if !all_inputs_supported {
+            continue;
+        }
+        ret.push(CppForeignMethodSignature {
+            output,
+            input,
+            completion,
+        });
     }
+    unsupported.into_result()?;
     Ok(ret)
 }
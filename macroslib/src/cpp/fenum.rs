@@ -2,15 +2,16 @@ use std::{io::Write, path::Path};
 
 use proc_macro2::TokenStream;
 use quote::ToTokens;
+use syn::spanned::Spanned;
 
 use crate::{
     cpp::{cpp_code, map_write_err},
-    error::{panic_on_syn_error, Result},
+    error::{panic_on_syn_error, DiagnosticError, Result},
     file_cache::FileWriteCache,
     source_registry::SourceId,
     typemap::ast::DisplayToTokens,
     types::ForeignEnumInfo,
-    TypeMap,
+    CppConfig, CppVariant, TypeMap,
 };
 
 pub(in crate::cpp) fn generate_code_for_enum(
@@ -213,3 +214,524 @@ impl SwigFrom<Option<{rust_enum_name}>> for Option<u32> {{
     conv_map.merge(SourceId::none(), &code, pointer_target_width)?;
     Ok(vec![trait_impl.into_token_stream()])
 }
+
+/// How field of enum item with payload is passed through C API
+#[derive(Clone, Copy, PartialEq)]
+enum FieldKind {
+    Plain,
+    Bool,
+    Str,
+}
+
+pub(in crate::cpp) struct DataEnumField {
+    kind: FieldKind,
+    rust_ty: String,
+    c_ty: &'static str,
+    cpp_ty: &'static str,
+}
+
+/// Check that all fields of enum items have supported types:
+/// primitive types and `String`
+pub(in crate::cpp) fn data_enum_fields(
+    enum_info: &ForeignEnumInfo,
+) -> Result<Vec<Vec<DataEnumField>>> {
+    let mut ret = Vec::with_capacity(enum_info.items.len());
+    for item in &enum_info.items {
+        let mut fields = Vec::with_capacity(item.fields.len());
+        for field in &item.fields {
+            let rust_ty = DisplayToTokens(&field.ty).to_string();
+            let (kind, c_ty, cpp_ty) = match rust_ty.as_str() {
+                "bool" => (FieldKind::Bool, "char", "bool"),
+                "i8" => (FieldKind::Plain, "int8_t", "int8_t"),
+                "u8" => (FieldKind::Plain, "uint8_t", "uint8_t"),
+                "i16" => (FieldKind::Plain, "int16_t", "int16_t"),
+                "u16" => (FieldKind::Plain, "uint16_t", "uint16_t"),
+                "i32" => (FieldKind::Plain, "int32_t", "int32_t"),
+                "u32" => (FieldKind::Plain, "uint32_t", "uint32_t"),
+                "i64" => (FieldKind::Plain, "int64_t", "int64_t"),
+                "u64" => (FieldKind::Plain, "uint64_t", "uint64_t"),
+                "isize" => (FieldKind::Plain, "intptr_t", "intptr_t"),
+                "usize" => (FieldKind::Plain, "uintptr_t", "uintptr_t"),
+                "f32" => (FieldKind::Plain, "float", "float"),
+                "f64" => (FieldKind::Plain, "double", "double"),
+                "String" => (FieldKind::Str, "struct CRustStrView", "std::string"),
+                _ => {
+                    return Err(DiagnosticError::new(
+                        enum_info.src_id,
+                        field.ty.span(),
+                        format!(
+                            "enum {}: unsupported type of field of item {}, \
+                             only primitive types and String are supported",
+                            enum_info.name, item.name
+                        ),
+                    ));
+                }
+            };
+            fields.push(DataEnumField {
+                kind,
+                rust_ty,
+                c_ty,
+                cpp_ty,
+            });
+        }
+        ret.push(fields);
+    }
+    Ok(ret)
+}
+
+pub(in crate::cpp) fn generate_code_for_data_enum(
+    cfg: &CppConfig,
+    enum_info: &ForeignEnumInfo,
+    fields: &[Vec<DataEnumField>],
+) -> std::result::Result<(), String> {
+    use std::fmt::Write;
+
+    let output_dir = &cfg.output_dir;
+    let nested_namespace_syntax = cfg.nested_namespace_syntax();
+
+    let enum_name = &enum_info.name;
+    // prefix of exported C functions
+    let c_fn = cfg.c_symbol(&enum_name.to_string());
+    let c_type = c_data_enum_type(enum_info);
+    let c_api = cfg.c_api();
+    let has_str = fields
+        .iter()
+        .any(|x| x.iter().any(|f| f.kind == FieldKind::Str));
+
+    let mut c_funcs = format!(
+        "    {api}uint32_t {c_fn}_tag(const {c_type} * self);\n",
+        api = c_api,
+        c_fn = c_fn,
+        c_type = c_type
+    );
+    for (item, item_fields) in enum_info.items.iter().zip(fields.iter()) {
+        let mut args = String::new();
+        for (i, (field, f_ty)) in item.fields.iter().zip(item_fields.iter()).enumerate() {
+            writeln!(
+                &mut c_funcs,
+                "    {api}{c_ty} {c_fn}_{item}_{field}(const {c_type} * self);",
+                api = c_api,
+                c_ty = f_ty.c_ty,
+                c_fn = c_fn,
+                item = item.name,
+                field = field.foreign_name(i),
+                c_type = c_type,
+            )
+            .unwrap();
+            if i != 0 {
+                args.push_str(", ");
+            }
+            write!(&mut args, "{} {}", f_ty.c_ty, field.foreign_name(i)).unwrap();
+        }
+        writeln!(
+            &mut c_funcs,
+            "    {api}{c_type} * {c_fn}_new_{item}({args});",
+            api = c_api,
+            c_type = c_type,
+            c_fn = c_fn,
+            item = item.name,
+            args = if args.is_empty() { "void" } else { &args },
+        )
+        .unwrap();
+    }
+    writeln!(
+        &mut c_funcs,
+        "    {api}void {c_fn}_delete({c_type} * self);",
+        api = c_api,
+        c_fn = c_fn,
+        c_type = c_type
+    )
+    .unwrap();
+
+    let c_path = output_dir.join(cpp_code::cpp_header_name_for_enum(enum_info));
+    let mut c_file = FileWriteCache::new(&c_path);
+    write!(
+        c_file,
+        r#"// Automaticaly generated by rust_swig
+#pragma once
+
+//for (u)intX_t types
+#include <stdint.h>
+{api_include}{str_include}
+#ifdef __cplusplus
+extern "C" {{
+#endif
+
+{c_type_typedef}
+
+{c_funcs}
+#ifdef __cplusplus
+}}
+#endif
+"#,
+        api_include = cfg.c_api_include(),
+        str_include = if has_str {
+            "#include \"rust_str.h\"\n"
+        } else {
+            ""
+        },
+        c_type_typedef = cpp_code::c_opaque_typedef(&c_type),
+        c_funcs = c_funcs,
+    )
+    .map_err(&map_write_err)?;
+    c_file.update_file_if_necessary().map_err(&map_write_err)?;
+
+    let (variant_include, variant_ty, variant_index, variant_get) = match cfg.cpp_variant {
+        CppVariant::Std17 => ("<variant>", "std::variant", "index", "std::get"),
+        CppVariant::Boost => (
+            "<boost/variant.hpp>",
+            "boost::variant",
+            "which",
+            "boost::get",
+        ),
+    };
+
+    let mut structs = String::new();
+    let mut from_rust = String::new();
+    let mut to_rust = String::new();
+    for (idx, (item, item_fields)) in enum_info.items.iter().zip(fields.iter()).enumerate() {
+        let item_doc_comments = cpp_code::doc_comments_to_c_comments(&item.doc_comments, true);
+        if !item_doc_comments.is_empty() {
+            writeln!(&mut structs, "{}", item_doc_comments).unwrap();
+        }
+        writeln!(&mut structs, "struct {} {{", item.name).unwrap();
+        writeln!(&mut from_rust, "    case {}: {{", idx).unwrap();
+        writeln!(&mut to_rust, "    case {}: {{", idx).unwrap();
+        if !item.fields.is_empty() {
+            writeln!(
+                &mut to_rust,
+                "        const auto &v = {get}<{item}>(x);",
+                get = variant_get,
+                item = item.name
+            )
+            .unwrap();
+        }
+        let mut from_args = String::new();
+        let mut to_args = String::new();
+        for (i, (field, f_ty)) in item.fields.iter().zip(item_fields.iter()).enumerate() {
+            let name = field.foreign_name(i);
+            writeln!(&mut structs, "    {} {};", f_ty.cpp_ty, name).unwrap();
+            writeln!(
+                &mut from_rust,
+                "        const auto {name} = {c_fn}_{item}_{name}(p);",
+                name = name,
+                c_fn = c_fn,
+                item = item.name,
+            )
+            .unwrap();
+            if i != 0 {
+                from_args.push_str(", ");
+                to_args.push_str(", ");
+            }
+            match f_ty.kind {
+                FieldKind::Plain => {
+                    from_args.push_str(&name);
+                    write!(&mut to_args, "v.{}", name).unwrap();
+                }
+                FieldKind::Bool => {
+                    write!(&mut from_args, "({} != 0)", name).unwrap();
+                    write!(&mut to_args, "v.{} ? 1 : 0", name).unwrap();
+                }
+                FieldKind::Str => {
+                    write!(&mut from_args, "std::string({0}.data, {0}.len)", name).unwrap();
+                    write!(
+                        &mut to_args,
+                        "CRustStrView{{ v.{0}.data(), v.{0}.size() }}",
+                        name
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        writeln!(&mut structs, "}};\n").unwrap();
+        write!(
+            &mut from_rust,
+            r#"        ret = {item}{{{from_args}}};
+        break;
+    }}
+"#,
+            item = item.name,
+            from_args = from_args,
+        )
+        .unwrap();
+        write!(
+            &mut to_rust,
+            r#"        return {c_fn}_new_{item}({to_args});
+    }}
+"#,
+            c_fn = c_fn,
+            item = item.name,
+            to_args = to_args,
+        )
+        .unwrap();
+    }
+
+    let variant_items = enum_info
+        .items
+        .iter()
+        .map(|x| x.name.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let visitor = match cfg.cpp_variant {
+        CppVariant::Std17 => format!(
+            r#"
+/// Combines several lambdas into one visitor for `std::visit` on `{enum_name}`
+template <typename... Fs> struct {enum_name}Visitor : Fs... {{
+    using Fs::operator()...;
+}};
+template <typename... Fs> {enum_name}Visitor(Fs...)->{enum_name}Visitor<Fs...>;
+"#,
+            enum_name = enum_name
+        ),
+        CppVariant::Boost => String::new(),
+    };
+
+    let open_namespace = cpp_code::open_namespace(&cfg.namespace_name, nested_namespace_syntax);
+    let close_namespace = cpp_code::close_namespace(&cfg.namespace_name, nested_namespace_syntax);
+    let inline = if cfg.pimpl { "" } else { "inline " };
+    let conv_funcs = format!(
+        r#"/// Takes ownership of `p`
+{inline}{enum_name} {enum_name}_from_rust({c_type} *p)
+{{
+    {enum_name} ret;
+    switch ({c_fn}_tag(p)) {{
+{from_rust}    default:
+        std::abort();
+    }}
+    {c_fn}_delete(p);
+    return ret;
+}}
+
+{inline}{c_type} *{enum_name}_to_rust(const {enum_name} &x)
+{{
+    switch (x.{variant_index}()) {{
+{to_rust}    default:
+        std::abort();
+    }}
+}}
+"#,
+        inline = inline,
+        enum_name = enum_name,
+        c_type = c_type,
+        c_fn = c_fn,
+        from_rust = from_rust,
+        variant_index = variant_index,
+        to_rust = to_rust,
+    );
+    // in PIMPL mode conversations are implemented in `Shape.cpp`,
+    // so C functions are used only there
+    let (c_decl, header_conv_funcs) = if cfg.pimpl {
+        (
+            format!(
+                "//for (u)intX_t types\n#include <stdint.h>\n\n{}",
+                cpp_code::c_opaque_typedef(&c_type)
+            ),
+            format!(
+                r#"/// Takes ownership of `p`
+{enum_name} {enum_name}_from_rust({c_type} *p);
+{c_type} *{enum_name}_to_rust(const {enum_name} &x);
+"#,
+                enum_name = enum_name,
+                c_type = c_type,
+            ),
+        )
+    } else {
+        (
+            format!(
+                "#include \"{}\"",
+                cpp_code::cpp_header_name_for_enum(enum_info)
+            ),
+            conv_funcs.clone(),
+        )
+    };
+
+    let cpp_path = output_dir.join(cpp_code::cpp_header_name_for_data_enum(enum_info));
+    let mut cpp_file = FileWriteCache::new(&cpp_path);
+    write!(
+        cpp_file,
+        r#"// Automaticaly generated by rust_swig
+#pragma once
+
+//for std::abort
+#include <cstdlib>
+{str_include}#include {variant_include}
+
+{c_decl}
+
+{open_namespace}
+
+{structs}{doc_comments}
+using {enum_name} = {variant_ty}<{variant_items}>;
+{visitor}
+namespace internal {{
+{conv_funcs}}} // namespace internal
+{close_namespace}
+"#,
+        str_include = if has_str { "#include <string>\n" } else { "" },
+        variant_include = variant_include,
+        c_decl = c_decl,
+        open_namespace = open_namespace,
+        close_namespace = close_namespace,
+        structs = structs,
+        doc_comments = cpp_code::doc_comments_to_c_comments(&enum_info.doc_comments, true),
+        enum_name = enum_name,
+        variant_ty = variant_ty,
+        variant_items = variant_items,
+        visitor = visitor,
+        conv_funcs = header_conv_funcs,
+    )
+    .map_err(&map_write_err)?;
+    if !cfg.c_abi_only {
+        cpp_file
+            .update_file_if_necessary()
+            .map_err(&map_write_err)?;
+    }
+    if cfg.pimpl && !cfg.c_abi_only {
+        let cpp_src_path = output_dir.join(cpp_code::cpp_source_name_for_data_enum(enum_info));
+        let mut cpp_src_file = FileWriteCache::new(&cpp_src_path);
+        write!(
+            cpp_src_file,
+            r#"// Automaticaly generated by rust_swig
+#include "{cpp_header}"
+#include "{c_header}"
+
+{open_namespace}
+namespace internal {{
+{conv_funcs}}} // namespace internal
+{close_namespace}
+"#,
+            cpp_header = cpp_code::cpp_header_name_for_data_enum(enum_info),
+            c_header = cpp_code::cpp_header_name_for_enum(enum_info),
+            open_namespace = open_namespace,
+            close_namespace = close_namespace,
+            conv_funcs = conv_funcs,
+        )
+        .map_err(&map_write_err)?;
+        cpp_src_file
+            .update_file_if_necessary()
+            .map_err(&map_write_err)?;
+    }
+    Ok(())
+}
+
+pub(in crate::cpp) fn c_data_enum_type(enum_info: &ForeignEnumInfo) -> String {
+    format!("{}Opaque", enum_info.name)
+}
+
+pub(in crate::cpp) fn generate_rust_code_for_data_enum(
+    cfg: &CppConfig,
+    enum_info: &ForeignEnumInfo,
+    fields: &[Vec<DataEnumField>],
+) -> Vec<TokenStream> {
+    let c_fn = cfg.c_symbol(&enum_info.name.to_string());
+    let rust_enum_name = enum_info.rust_enum_name();
+    let mut code = Vec::new();
+    let mut tag_arms = String::new();
+
+    for (idx, (item, item_fields)) in enum_info.items.iter().zip(fields.iter()).enumerate() {
+        let rust_item = DisplayToTokens(&item.rust_name);
+        tag_arms.push_str(&format!("{} {{ .. }} => {},\n", rust_item, idx));
+        let mut args = Vec::with_capacity(item.fields.len());
+        let mut init = Vec::with_capacity(item.fields.len());
+        for (i, (field, f_ty)) in item.fields.iter().zip(item_fields.iter()).enumerate() {
+            let name = field.foreign_name(i);
+            let (c_ret_ty, ret_conv, c_arg_ty, arg_conv) = match f_ty.kind {
+                FieldKind::Plain => (
+                    f_ty.rust_ty.as_str(),
+                    "*x".to_string(),
+                    f_ty.rust_ty.as_str(),
+                    name.clone(),
+                ),
+                FieldKind::Bool => (
+                    "::std::os::raw::c_char",
+                    "if *x { 1 } else { 0 }".to_string(),
+                    "::std::os::raw::c_char",
+                    format!("{} != 0", name),
+                ),
+                FieldKind::Str => (
+                    "CRustStrView",
+                    "CRustStrView::from_str(x)".to_string(),
+                    "CRustStrView",
+                    format!("{}.as_str().to_string()", name),
+                ),
+            };
+            let func_name = format!("{}_{}_{}", c_fn, item.name, name);
+            code.push(format!(
+                r#"
+#[allow(non_snake_case, unreachable_patterns)]
+#[no_mangle]
+pub extern "C" fn {func_name}(this: *const {rust_enum_name}) -> {c_ret_ty} {{
+    let this: &{rust_enum_name} = unsafe {{ this.as_ref().unwrap() }};
+    match this {{
+        {rust_item} {{ {rust_field}: x, .. }} => {ret_conv},
+        _ => panic!("{func_name}: wrong enum item"),
+    }}
+}}
+"#,
+                func_name = func_name,
+                rust_enum_name = rust_enum_name,
+                c_ret_ty = c_ret_ty,
+                rust_item = rust_item,
+                rust_field = field.rust_name(i),
+                ret_conv = ret_conv,
+            ));
+            args.push(format!("{}: {}", name, c_arg_ty));
+            if field.name.is_some() && f_ty.kind == FieldKind::Plain {
+                init.push(name);
+            } else {
+                init.push(format!("{}: {}", field.rust_name(i), arg_conv));
+            }
+        }
+        code.push(format!(
+            r#"
+#[allow(non_snake_case)]
+#[no_mangle]
+pub extern "C" fn {c_fn}_new_{item}({args}) -> *mut {rust_enum_name} {{
+    Box::into_raw(Box::new({rust_item} {{ {init} }}))
+}}
+"#,
+            c_fn = c_fn,
+            item = item.name,
+            args = args.join(", "),
+            rust_enum_name = rust_enum_name,
+            rust_item = rust_item,
+            init = init.join(", "),
+        ));
+    }
+    code.push(format!(
+        r#"
+#[allow(non_snake_case)]
+#[no_mangle]
+pub extern "C" fn {c_fn}_tag(this: *const {rust_enum_name}) -> u32 {{
+    let this: &{rust_enum_name} = unsafe {{ this.as_ref().unwrap() }};
+    match this {{
+{tag_arms}
+    }}
+}}
+"#,
+        c_fn = c_fn,
+        rust_enum_name = rust_enum_name,
+        tag_arms = tag_arms,
+    ));
+    code.push(format!(
+        r#"
+#[allow(non_snake_case)]
+#[no_mangle]
+pub extern "C" fn {c_fn}_delete(this: *mut {rust_enum_name}) {{
+    let this = unsafe {{ Box::from_raw(this) }};
+    drop(this);
+}}
+"#,
+        c_fn = c_fn,
+        rust_enum_name = rust_enum_name,
+    ));
+
+    code.into_iter()
+        .map(|item| {
+            let item: syn::Item = syn::parse_str(&item)
+                .unwrap_or_else(|err| panic_on_syn_error("data enum item", item, err));
+            item.into_token_stream()
+        })
+        .collect()
+}
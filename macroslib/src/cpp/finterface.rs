@@ -1,4 +1,4 @@
-use std::{io::Write, path::Path};
+use std::io::Write;
 
 use petgraph::Direction;
 use proc_macro2::TokenStream;
@@ -15,9 +15,8 @@ use crate::{
     source_registry::SourceId,
     typemap::{
         ast::{fn_arg_type, DisplayToTokens},
-        ty::RustType,
         utils::rust_to_foreign_convert_method_inputs,
-        ForeignTypeInfo, TypeMap, FROM_VAR_TEMPLATE,
+        ForeignTypeInfo, RustTypeIdx, TypeMap, FROM_VAR_TEMPLATE,
     },
     types::ForeignInterface,
     CppConfig,
@@ -46,7 +45,7 @@ pub struct {struct_with_funcs} {{
         struct_with_funcs = struct_with_funcs,
     );
     for (method, f_method) in interface.items.iter().zip(methods_sign) {
-        let args = rust_generate_args_with_types(f_method).map_err(|err| {
+        let args = rust_generate_args_with_types(conv_map, f_method).map_err(|err| {
             DiagnosticError::new(
                 interface.src_id,
                 interface.span(),
@@ -60,7 +59,7 @@ pub struct {struct_with_funcs} {{
 "#,
             method_name = method.name,
             args = args,
-            ret_type = DisplayToTokens(&f_method.output.base.correspoding_rust_type.ty),
+            ret_type = DisplayToTokens(&conv_map[f_method.output.base.correspoding_rust_type].ty),
         )
         .unwrap();
     }
@@ -154,22 +153,23 @@ impl {trait_name} for {struct_with_funcs} {{
         let (real_output_typename, output_conv) = match method.fn_decl.output {
             syn::ReturnType::Default => ("()".to_string(), String::new()),
             syn::ReturnType::Type(_, ref ret_ty) => {
-                let real_output_type: RustType =
+                let real_output_type: RustTypeIdx =
                     conv_map.find_or_alloc_rust_type(ret_ty, interface.src_id);
+                let real_output_typename = conv_map[real_output_type].normalized_name.clone();
                 let (mut conv_deps, conv_code) = conv_map.convert_rust_types(
-                    f_method.output.base.correspoding_rust_type.to_idx(),
-                    real_output_type.to_idx(),
+                    f_method.output.base.correspoding_rust_type,
+                    real_output_type,
                     "ret",
-                    real_output_type.normalized_name.as_str(),
+                    real_output_typename.as_str(),
                     (interface.src_id, ret_ty.span()),
                 )?;
                 gen_items.append(&mut conv_deps);
-                (real_output_type.normalized_name.to_string(), conv_code)
+                (real_output_typename.to_string(), conv_code)
             }
         };
         let ret_type = format!(
             "{}",
-            DisplayToTokens(&f_method.output.base.correspoding_rust_type.ty)
+            DisplayToTokens(&conv_map[f_method.output.base.correspoding_rust_type].ty)
         );
         write!(
             &mut code,
@@ -243,7 +243,7 @@ pub(in crate::cpp) fn find_suitable_ftypes_for_interace_methods(
             input.push(map_type(
                 conv_map,
                 cpp_cfg,
-                &arg_rust_ty,
+                arg_rust_ty,
                 Direction::Outgoing,
                 (interace.src_id, fn_arg_type(arg).span()),
             )?);
@@ -251,7 +251,7 @@ pub(in crate::cpp) fn find_suitable_ftypes_for_interace_methods(
         let output = match method.fn_decl.output {
             syn::ReturnType::Default => ForeignTypeInfo {
                 name: void_sym.into(),
-                correspoding_rust_type: dummy_rust_ty.clone(),
+                correspoding_rust_type: dummy_rust_ty,
             }
             .into(),
             syn::ReturnType::Type(_, ref ret_ty) => {
@@ -259,44 +259,67 @@ pub(in crate::cpp) fn find_suitable_ftypes_for_interace_methods(
                 map_type(
                     conv_map,
                     cpp_cfg,
-                    &ret_rust_ty,
+                    ret_rust_ty,
                     Direction::Incoming,
                     (interace.src_id, ret_ty.span()),
                 )?
             }
         };
-        f_methods.push(CppForeignMethodSignature { output, input });
+        f_methods.push(CppForeignMethodSignature {
+            output,
+            input,
+            completion: None,
+        });
     }
     Ok(f_methods)
 }
 
 pub(in crate::cpp) fn generate_for_interface(
-    output_dir: &Path,
-    namespace_name: &str,
+    cfg: &CppConfig,
+    conv_map: &TypeMap,
     interface: &ForeignInterface,
     req_includes: &[SmolStr],
     f_methods: &[CppForeignMethodSignature],
 ) -> std::result::Result<(), String> {
     use std::fmt::Write;
 
+    let nested_namespace_syntax = cfg.nested_namespace_syntax();
     let c_interface_struct_header = format!("c_{}.h", interface.name);
-    let c_path = output_dir.join(&c_interface_struct_header);
+    let c_path = cfg.output_dir.join(&c_interface_struct_header);
     let mut file_c = FileWriteCache::new(&c_path);
-    let cpp_path = output_dir.join(format!("{}.hpp", interface.name));
+    let cpp_path = cfg.output_dir.join(format!("{}.hpp", interface.name));
     let mut file_cpp = FileWriteCache::new(&cpp_path);
     let interface_comments = cpp_code::doc_comments_to_c_comments(&interface.doc_comments, true);
 
+    let mut c_includes = String::from("\n//for (u)intX_t types\n#include <stdint.h>\n");
+    if cfg.c_abi_only {
+        let (includes, opaque_types) =
+            cpp_code::c_list_required_includes(conv_map, f_methods, &c_interface_struct_header);
+        for inc in includes {
+            writeln!(&mut c_includes, "#include {}", inc).unwrap();
+        }
+        for opaque_type in opaque_types {
+            writeln!(
+                &mut c_includes,
+                "{}",
+                cpp_code::c_opaque_typedef(&opaque_type)
+            )
+            .unwrap();
+        }
+    }
+
     write!(
         file_c,
         r#"// Automaticaly generated by rust_swig
 #pragma once
-{doc_comments}
+{c_includes}{doc_comments}
 struct C_{interface_name} {{
     void *opaque;
     //! call by Rust side when callback not need anymore
     void (*C_{interface_name}_deref)(void *opaque);
     "#,
         interface_name = interface.name,
+        c_includes = c_includes,
         doc_comments = interface_comments
     )
     .map_err(&map_write_err)?;
@@ -351,7 +374,10 @@ struct C_{interface_name} {{
     virtual {cpp_ret_type} {method_name}({single_args_with_types}) = 0;
 "#,
             method_name = method.name,
-            doc_comments = cpp_code::doc_comments_to_c_comments(&method.doc_comments, false),
+            doc_comments = cpp_code::doc_comments_to_c_comments(
+                &cpp_code::cpp_method_doc_comments(&method.doc_comments, f_method, false),
+                false
+            ),
             single_args_with_types = cpp_code::cpp_generate_args_with_types(f_method)?,
             cpp_ret_type = cpp_ret_type,
         )
@@ -424,7 +450,7 @@ struct C_{interface_name} {{
 {includes}
 #include "{c_interface_struct_header}"
 
-namespace {namespace_name} {{
+{open_namespace}
 {doc_comments}
 class {interface_name} {{
 public:
@@ -442,7 +468,7 @@ public:
 private:
 {static_reroute_methods}
 }};
-}} // namespace {namespace_name}
+{close_namespace}
 "##,
         interface_name = interface.name,
         includes = includes,
@@ -451,14 +477,17 @@ private:
         virtual_methods = cpp_virtual_methods,
         static_reroute_methods = cpp_static_reroute_methods,
         cpp_fill_c_interface_struct = cpp_fill_c_interface_struct,
-        namespace_name = namespace_name,
+        open_namespace = cpp_code::open_namespace(&cfg.namespace_name, nested_namespace_syntax),
+        close_namespace = cpp_code::close_namespace(&cfg.namespace_name, nested_namespace_syntax),
     )
     .map_err(&map_write_err)?;
 
     file_c.update_file_if_necessary().map_err(&map_write_err)?;
-    file_cpp
-        .update_file_if_necessary()
-        .map_err(&map_write_err)?;
+    if !cfg.c_abi_only {
+        file_cpp
+            .update_file_if_necessary()
+            .map_err(&map_write_err)?;
+    }
 
     Ok(())
 }
@@ -0,0 +1,49 @@
+use proc_macro2::TokenStream;
+
+use super::{builtin_c_symbols, write_generated_file};
+use crate::{error::Result, types::ExpandedItem, CppConfig};
+
+/// Generate `<name>.def` (MSVC) and `<name>.map` (GNU ld version script)
+/// in output directory, both list only `#[no_mangle]` functions generated by us
+/// and helper functions from `cpp-include.rs`,
+/// so it is possible to hide everything else if static library
+/// linked into shared one
+pub(in crate::cpp) fn generate_linker_exports(
+    cfg: &CppConfig,
+    name: &str,
+    items: &[ExpandedItem],
+) -> Result<()> {
+    let mut symbols: Vec<String> = builtin_c_symbols().map(|x| cfg.c_symbol(x)).collect();
+    for item in items {
+        for code in &item.code {
+            collect_exported_fns(code, &mut symbols);
+        }
+    }
+    symbols.sort();
+    symbols.dedup();
+
+    let mut def = "; Automaticaly generated by rust_swig\nEXPORTS\n".to_string();
+    let mut map = "/* Automaticaly generated by rust_swig */\n{\n  global:\n".to_string();
+    for sym in &symbols {
+        def.push_str(&format!("    {}\n", sym));
+        map.push_str(&format!("    {};\n", sym));
+    }
+    map.push_str("  local: *;\n};\n");
+
+    write_generated_file(&cfg.output_dir.join(format!("{}.def", name)), &def)?;
+    write_generated_file(&cfg.output_dir.join(format!("{}.map", name)), &map)
+}
+
+fn collect_exported_fns(code: &TokenStream, symbols: &mut Vec<String>) {
+    let file: syn::File = match syn::parse2(code.clone()) {
+        Ok(x) => x,
+        Err(_) => return,
+    };
+    for item in &file.items {
+        if let syn::Item::Fn(ref f) = item {
+            if f.attrs.iter().any(|a| a.path.is_ident("no_mangle")) {
+                symbols.push(f.ident.to_string());
+            }
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use proc_macro2::TokenStream;
+
+use super::{cpp_code, write_generated_file};
+use crate::{error::panic_on_syn_error, error::Result, CppConfig};
+
+/// Header with declaration of `rust_log_init`
+pub(in crate::cpp) const LOG_BRIDGE_HEADER: &str = "rust_log.h";
+
+/// Write `rust_log.h` and return Rust code of `rust_log_init`,
+/// that installs implementation of `log::Log` calling C callback
+pub(in crate::cpp) fn generate_log_bridge(cfg: &CppConfig) -> Result<Vec<TokenStream>> {
+    let func_name = cfg.c_symbol("rust_log_init");
+    let mut header = format!(
+        r#"// Automaticaly generated by rust_swig
+#pragma once
+
+#include <stdint.h>
+{c_api_include}
+#ifdef __cplusplus
+extern "C" {{
+#endif
+
+/**
+ * Receives record of Rust `log` crate, `level` is 1 (error), 2 (warn),
+ * 3 (info), 4 (debug) or 5 (trace), strings are valid only during call
+ */
+typedef void (*RustLogCallback)(void *opaque, uint32_t level, const char *target,
+                                const char *message);
+
+/**
+ * Route records of Rust `log` crate with level up to `max_level` (0 - none) to `callback`,
+ * it is called from any thread with `opaque` as the first argument.
+ * Returns 1 if `callback` is installed, following calls only change maximum level
+ * and return 0
+ */
+{c_api}char {func_name}(RustLogCallback callback, void *opaque, uint32_t max_level);
+
+#ifdef __cplusplus
+}}
+#endif
+"#,
+        c_api_include = cfg.c_api_include(),
+        c_api = cfg.c_api(),
+        func_name = func_name,
+    );
+    if !cfg.c_abi_only {
+        let nested_syntax = cfg.nested_namespace_syntax();
+        header.push_str(&format!(
+            r#"
+#ifdef __cplusplus
+#include <functional>
+#include <utility>
+
+{open_namespace}
+enum class RustLogLevel : uint32_t {{
+    Off = 0,
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}};
+
+/**
+ * Route records of Rust `log` crate with level up to `max_level` to `callback`,
+ * it is called from any thread. Following calls only change maximum level
+ */
+inline void rust_log_init(std::function<void(RustLogLevel, const char *, const char *)> callback,
+                          RustLogLevel max_level = RustLogLevel::Info)
+{{
+    using Callback = std::function<void(RustLogLevel, const char *, const char *)>;
+    // logger is never uninstalled, so installed callback is never released
+    auto cb = new Callback(std::move(callback));
+    if (!::{func_name}(
+            [](void *opaque, uint32_t level, const char *target, const char *message) {{
+                (*static_cast<Callback *>(opaque))(static_cast<RustLogLevel>(level), target,
+                                                   message);
+            }},
+            cb, static_cast<uint32_t>(max_level))) {{
+        delete cb;
+    }}
+}}
+{close_namespace}
+#endif
+"#,
+            open_namespace = cpp_code::open_namespace(&cfg.namespace_name, nested_syntax),
+            close_namespace = cpp_code::close_namespace(&cfg.namespace_name, nested_syntax),
+            func_name = func_name,
+        ));
+    }
+    write_generated_file(&cfg.output_dir.join(LOG_BRIDGE_HEADER), &header)?;
+
+    let code = format!(
+        r#"
+type SwigLogCallback = extern "C" fn(
+    opaque: *mut ::std::os::raw::c_void,
+    level: u32,
+    target: *const ::std::os::raw::c_char,
+    message: *const ::std::os::raw::c_char,
+);
+
+struct SwigCLogger {{
+    callback: SwigLogCallback,
+    opaque: *mut ::std::os::raw::c_void,
+}}
+
+unsafe impl Send for SwigCLogger {{}}
+unsafe impl Sync for SwigCLogger {{}}
+
+/// C string from `s` without interior nul bytes
+fn swig_log_c_string(s: String) -> ::std::ffi::CString {{
+    let mut bytes = s.into_bytes();
+    bytes.retain(|&b| b != 0);
+    unsafe {{ ::std::ffi::CString::from_vec_unchecked(bytes) }}
+}}
+
+impl ::log::Log for SwigCLogger {{
+    fn enabled(&self, metadata: &::log::Metadata) -> bool {{
+        metadata.level() <= ::log::max_level()
+    }}
+
+    fn log(&self, record: &::log::Record) {{
+        if !self.enabled(record.metadata()) {{
+            return;
+        }}
+        let target = swig_log_c_string(record.target().to_string());
+        let message = swig_log_c_string(record.args().to_string());
+        (self.callback)(
+            self.opaque,
+            record.level() as usize as u32,
+            target.as_ptr(),
+            message.as_ptr(),
+        );
+    }}
+
+    fn flush(&self) {{}}
+}}
+
+#[no_mangle]
+pub extern "C" fn {func_name}(
+    callback: Option<SwigLogCallback>,
+    opaque: *mut ::std::os::raw::c_void,
+    max_level: u32,
+) -> ::std::os::raw::c_char {{
+    static INIT: ::std::sync::Once = ::std::sync::Once::new();
+    let mut installed = false;
+    if let Some(callback) = callback {{
+        INIT.call_once(|| {{
+            let logger = Box::new(SwigCLogger {{ callback, opaque }});
+            // if other logger is already installed, it is kept
+            installed = ::log::set_logger(Box::leak(logger)).is_ok();
+        }});
+    }}
+    ::log::set_max_level(match max_level {{
+        0 => ::log::LevelFilter::Off,
+        1 => ::log::LevelFilter::Error,
+        2 => ::log::LevelFilter::Warn,
+        3 => ::log::LevelFilter::Info,
+        4 => ::log::LevelFilter::Debug,
+        _ => ::log::LevelFilter::Trace,
+    }});
+    if installed {{
+        1
+    }} else {{
+        0
+    }}
+}}
+"#,
+        func_name = func_name,
+    );
+    Ok(vec![syn::parse_str(&code).unwrap_or_else(|err| {
+        panic_on_syn_error("cpp internal log bridge", code, err)
+    })])
+}
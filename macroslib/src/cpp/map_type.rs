@@ -9,31 +9,34 @@ use syn::{parse_quote, spanned::Spanned, Type};
 
 use crate::{
     cpp::{
-        cpp_code::{c_class_type, cpp_header_name, cpp_header_name_for_enum},
+        cpp_code::{
+            c_class_type, close_namespace, cpp_header_name, cpp_header_name_for_enum,
+            open_namespace,
+        },
         {CppConverter, CppForeignTypeInfo},
     },
     error::{panic_on_syn_error, DiagnosticError, Result, SourceIdSpan},
     file_cache::FileWriteCache,
     source_registry::SourceId,
     typemap::ast::{
-        if_option_return_some_type, if_result_return_ok_err_types, if_type_slice_return_elem_type,
-        if_vec_return_elem_type,
+        if_box_iterator_return_item_type, if_option_return_some_type,
+        if_result_return_ok_err_types, if_type_slice_return_elem_type, if_vec_return_elem_type,
     },
-    typemap::{ty::RustType, ForeignTypeInfo, FROM_VAR_TEMPLATE, TO_VAR_TEMPLATE},
+    typemap::{ForeignTypeInfo, RustTypeIdx, FROM_VAR_TEMPLATE, TO_VAR_TEMPLATE},
     types::{ForeignEnumInfo, ForeignerClassInfo},
-    CppConfig, CppOptional, CppVariant, TypeMap,
+    CppConfig, CppOptional, CppResult, CppString, CppVariant, TypeMap,
 };
 
 fn special_type(
     conv_map: &mut TypeMap,
     cpp_cfg: &CppConfig,
-    arg_ty: &RustType,
+    arg_ty: RustTypeIdx,
     direction: Direction,
     arg_ty_span: SourceIdSpan,
 ) -> Result<Option<CppForeignTypeInfo>> {
     trace!(
         "special_type: begin arg.ty({}) direction {:?}",
-        arg_ty,
+        conv_map[arg_ty],
         direction
     );
 
@@ -42,11 +45,20 @@ fn special_type(
         return Ok(Some(converter));
     }
 
-    if let Some(elem_ty) = if_vec_return_elem_type(arg_ty) {
+    if cpp_cfg.cpp_string != CppString::Utf8 {
+        if let Some(ret) = map_utf16_string_type(conv_map, cpp_cfg, arg_ty, direction) {
+            return Ok(Some(ret));
+        }
+    }
+
+    if let Some(elem_ty) = if_vec_return_elem_type(&conv_map[arg_ty]) {
         return map_type_vec(conv_map, cpp_cfg, arg_ty, &elem_ty, arg_ty_span, direction);
     }
     if direction == Direction::Outgoing {
-        if let Some((ok_ty, err_ty)) = if_result_return_ok_err_types(arg_ty) {
+        if let Some(item_ty) = if_box_iterator_return_item_type(&conv_map[arg_ty].ty) {
+            return handle_iterator_in_return(conv_map, cpp_cfg, arg_ty, &item_ty, arg_ty_span);
+        }
+        if let Some((ok_ty, err_ty)) = if_result_return_ok_err_types(&conv_map[arg_ty]) {
             trace!(
                 "special_type: return type is Result<{:?}, {:?}>",
                 ok_ty,
@@ -61,35 +73,36 @@ fn special_type(
                 arg_ty_span,
             );
         }
-        if let Some(ty) = if_option_return_some_type(arg_ty) {
+        if let Some(ty) = if_option_return_some_type(&conv_map[arg_ty]) {
             return handle_option_type_in_return(
                 conv_map,
                 cpp_cfg,
                 arg_ty,
-                (&ty, arg_ty.src_id),
+                (&ty, conv_map[arg_ty].src_id),
                 arg_ty_span,
             );
         }
-        if let Some(elem_ty) = if_type_slice_return_elem_type(&arg_ty.ty, false) {
-            return map_return_slice_type(conv_map, arg_ty, &elem_ty, arg_ty_span);
+        if let Some(elem_ty) = if_type_slice_return_elem_type(&conv_map[arg_ty].ty, false).cloned()
+        {
+            return map_return_slice_type(conv_map, cpp_cfg, arg_ty, &elem_ty, arg_ty_span);
         }
     } else {
-        if let Some(ty) = if_option_return_some_type(arg_ty) {
+        if let Some(ty) = if_option_return_some_type(&conv_map[arg_ty]) {
             return handle_option_type_in_input(
                 conv_map,
                 cpp_cfg,
                 arg_ty,
-                (&ty, arg_ty.src_id),
+                (&ty, conv_map[arg_ty].src_id),
                 arg_ty_span,
             );
         }
-        if let Some(elem_ty) = if_type_slice_return_elem_type(&arg_ty.ty, true) {
-            return map_arg_with_slice_type(conv_map, arg_ty, &elem_ty, arg_ty_span);
+        if let Some(elem_ty) = if_type_slice_return_elem_type(&conv_map[arg_ty].ty, true).cloned() {
+            return map_arg_with_slice_type(conv_map, cpp_cfg, arg_ty, &elem_ty, arg_ty_span);
         }
     }
 
     if direction == Direction::Outgoing {
-        if let syn::Type::Tuple(ref tupple) = arg_ty.ty {
+        if let syn::Type::Tuple(ref tupple) = conv_map[arg_ty].ty.clone() {
             if tupple.elems.len() == 2 {
                 let mut ret = map_ordinal_result_type(conv_map, arg_ty, arg_ty_span)?;
                 if let (Some(fc1), Some(fc2)) = (
@@ -114,16 +127,196 @@ fn special_type(
                             CSecondType = c_class_type(fc2)
                         ),
                     });
+                    ret.provides_by_module.extend_from_slice(&[
+                        "<utility>".into(),
+                        format!("\"{}\"", cpp_header_name(fc1)).into(),
+                        format!("\"{}\"", cpp_header_name(fc2)).into(),
+                    ]);
                     return Ok(Some(ret));
                 }
             }
         }
     }
 
-    trace!("special_type: Oridinary type {}", arg_ty);
+    trace!("special_type: Oridinary type {}", conv_map[arg_ty]);
     Ok(None)
 }
 
+/// `&str`, `String` and `Option<String>` as UTF-16 `std::u16string` or `std::wstring`,
+/// Rust code converts UTF-16 from/to UTF-8.
+/// `None` is passed as C struct with `data == NULL`
+fn map_utf16_string_type(
+    conv_map: &mut TypeMap,
+    cpp_cfg: &CppConfig,
+    arg_ty: RustTypeIdx,
+    direction: Direction,
+) -> Option<CppForeignTypeInfo> {
+    let (is_str, is_option) = match conv_map[arg_ty].normalized_name.as_str() {
+        "& str" => (true, false),
+        "String" => (false, false),
+        "Option < String >" => (false, true),
+        _ => return None,
+    };
+    let (char_type, cpp_string) = match cpp_cfg.cpp_string {
+        CppString::Utf8 => return None,
+        CppString::U16String => ("char16_t", "std::u16string"),
+        CppString::WString => ("wchar_t", "std::wstring"),
+    };
+    let (c_name, cpp_type) = match direction {
+        Direction::Incoming => ("struct CRustSliceU16", format!("{}_view", cpp_string)),
+        Direction::Outgoing => ("struct CRustVecU16", cpp_string.to_string()),
+    };
+    let base = conv_map
+        .find_foreign_type_info_by_name(c_name)
+        .unwrap_or_else(|| panic!("Can not find info about {}", c_name));
+    let c_ty = base.correspoding_rust_type;
+    // conversations are done by helpers from built-in types map,
+    // so code of them is not repeated for each method
+    let converter = match direction {
+        Direction::Incoming => {
+            let code = if is_option {
+                format!(
+                    "    let {to_var}: Option<String> = swig_option_string_from_utf16({from_var});\n",
+                    to_var = TO_VAR_TEMPLATE,
+                    from_var = FROM_VAR_TEMPLATE,
+                )
+            } else {
+                let as_str = if is_str {
+                    format!(
+                        "    let {to_var}: &str = {to_var}.as_str();\n",
+                        to_var = TO_VAR_TEMPLATE
+                    )
+                } else {
+                    String::new()
+                };
+                format!(
+                    "    let {to_var}: String = swig_string_from_utf16({from_var});\n{as_str}",
+                    to_var = TO_VAR_TEMPLATE,
+                    from_var = FROM_VAR_TEMPLATE,
+                    as_str = as_str,
+                )
+            };
+            conv_map.add_conversation_rule(c_ty, arg_ty, code.into());
+            if is_option {
+                format!("rust_utf16_option_slice({var})", var = FROM_VAR_TEMPLATE)
+            } else {
+                format!(
+                    "rust_utf16_slice<{CharT}>({var})",
+                    CharT = char_type,
+                    var = FROM_VAR_TEMPLATE
+                )
+            }
+        }
+        Direction::Outgoing => {
+            let helper = if is_option {
+                "swig_option_string_to_utf16"
+            } else {
+                "swig_string_to_utf16"
+            };
+            conv_map.add_conversation_rule(
+                arg_ty,
+                c_ty,
+                format!(
+                    "    let {to_var}: {c_type} = {helper}(&{from_var});\n",
+                    to_var = TO_VAR_TEMPLATE,
+                    from_var = FROM_VAR_TEMPLATE,
+                    c_type = conv_map[c_ty].normalized_name,
+                    helper = helper,
+                )
+                .into(),
+            );
+            if is_option {
+                let opt_ty = match cpp_cfg.cpp_optional {
+                    CppOptional::Std17 => "std::optional",
+                    CppOptional::Boost => "boost::optional",
+                };
+                format!(
+                    "{var}.data != nullptr ? {opt_ty}<{Type}>{{rust_to_utf16_string<{CharT}>({var})}} : {opt_ty}<{Type}>()",
+                    var = FROM_VAR_TEMPLATE,
+                    opt_ty = opt_ty,
+                    Type = cpp_type,
+                    CharT = char_type,
+                )
+            } else {
+                format!(
+                    "rust_to_utf16_string<{CharT}>({var})",
+                    CharT = char_type,
+                    var = FROM_VAR_TEMPLATE
+                )
+            }
+        }
+    };
+    let mut provides_by_module = vec!["\"rust_utf16.h\"".into()];
+    let typename = if is_option {
+        let (typename, opt_inc) = match cpp_cfg.cpp_optional {
+            CppOptional::Std17 => (format!("std::optional<{}>", cpp_type), "<optional>"),
+            CppOptional::Boost => (
+                format!("boost::optional<{}>", cpp_type),
+                "<boost/optional.hpp>",
+            ),
+        };
+        provides_by_module.push(opt_inc.into());
+        typename
+    } else {
+        cpp_type
+    };
+    Some(CppForeignTypeInfo {
+        base,
+        provides_by_module,
+        cpp_converter: Some(CppConverter {
+            typename: typename.into(),
+            converter,
+        }),
+    })
+}
+
+/// `Option<&str>` and `Option<String>` arguments as optional `std::string_view`,
+/// to be consistent with `&str` and `String` arguments
+fn map_option_str_view_input(
+    conv_map: &mut TypeMap,
+    cpp_cfg: &CppConfig,
+    arg_ty: RustTypeIdx,
+    is_str: bool,
+) -> CppForeignTypeInfo {
+    let c_name = "struct CRustOptionStr";
+    let base = conv_map
+        .find_foreign_type_info_by_name(c_name)
+        .unwrap_or_else(|| panic!("Can not find info about {}", c_name));
+    let c_ty = base.correspoding_rust_type;
+    let to_string = if is_str { "" } else { ".map(str::to_string)" };
+    conv_map.add_conversation_rule(
+        c_ty,
+        arg_ty,
+        format!(
+            "    let {to_var}: {arg_ty} = {from_var}.as_option_str(){to_string};\n",
+            to_var = TO_VAR_TEMPLATE,
+            from_var = FROM_VAR_TEMPLATE,
+            arg_ty = conv_map[arg_ty].normalized_name,
+            to_string = to_string,
+        )
+        .into(),
+    );
+    let (typename, opt_inc) = match cpp_cfg.cpp_optional {
+        CppOptional::Std17 => ("std::optional<std::string_view>", "<optional>"),
+        CppOptional::Boost => ("boost::optional<std::string_view>", "<boost/optional.hpp>"),
+    };
+    CppForeignTypeInfo {
+        base,
+        provides_by_module: vec![
+            "\"rust_option.h\"".into(),
+            "<string_view>".into(),
+            opt_inc.into(),
+        ],
+        cpp_converter: Some(CppConverter {
+            typename: typename.into(),
+            converter: format!(
+                "!!{var} ? CRustOptionStr{{CRustStrView{{ {var}->data(), {var}->size() }}, 1}} : c_option_empty<CRustOptionStr>()",
+                var = FROM_VAR_TEMPLATE,
+            ),
+        }),
+    }
+}
+
 fn foreign_class_foreign_name(
     conv_map: &TypeMap,
     foreign_class: &ForeignerClassInfo,
@@ -151,7 +344,7 @@ fn calc_converter_for_enum(
     foreign_enum: &ForeignEnumInfo,
     direction: Direction,
 ) -> CppForeignTypeInfo {
-    let u32_ti: RustType = conv_map.ty_to_rust_type(&parse_type! { u32 });
+    let u32_ti: RustTypeIdx = conv_map.ty_to_rust_type(&parse_type! { u32 });
     let converter = match direction {
         Direction::Outgoing => format!(
             "static_cast<{}>({})",
@@ -176,7 +369,7 @@ fn calc_converter_for_enum(
 pub(in crate::cpp) fn map_type(
     conv_map: &mut TypeMap,
     cpp_cfg: &CppConfig,
-    arg_ty: &RustType,
+    arg_ty: RustTypeIdx,
     direction: Direction,
     arg_ty_span: SourceIdSpan,
 ) -> Result<CppForeignTypeInfo> {
@@ -204,7 +397,7 @@ pub(in crate::cpp) fn map_type(
 
 fn map_ordinal_result_type(
     conv_map: &mut TypeMap,
-    arg_ty: &RustType,
+    arg_ty: RustTypeIdx,
     arg_ty_span: SourceIdSpan,
 ) -> Result<CppForeignTypeInfo> {
     let ftype = conv_map
@@ -220,7 +413,7 @@ fn map_ordinal_result_type(
                 format!(
                     "Do not know conversation from \
                      such rust type '{}' to C++ type",
-                    arg_ty
+                    conv_map[arg_ty]
                 ),
             )
         })?;
@@ -229,7 +422,7 @@ fn map_ordinal_result_type(
 
 fn map_ordinal_input_type(
     conv_map: &mut TypeMap,
-    arg_ty: &RustType,
+    arg_ty: RustTypeIdx,
     arg_ty_span: SourceIdSpan,
 ) -> Result<CppForeignTypeInfo> {
     let ftype = conv_map
@@ -245,7 +438,7 @@ fn map_ordinal_input_type(
                 format!(
                     "Do not know conversation from C++ type \
                      to such rust type '{}'",
-                    arg_ty
+                    conv_map[arg_ty]
                 ),
             )
         })?;
@@ -254,36 +447,85 @@ fn map_ordinal_input_type(
 
 fn map_arg_with_slice_type(
     conv_map: &mut TypeMap,
-    arg_ty: &RustType,
+    cpp_cfg: &CppConfig,
+    arg_ty: RustTypeIdx,
     elem_ty: &Type,
     arg_ty_span: SourceIdSpan,
 ) -> Result<Option<CppForeignTypeInfo>> {
     let mut ftype_info = map_ordinal_input_type(conv_map, arg_ty, arg_ty_span)?;
     let elem_rust_ty = conv_map.find_or_alloc_rust_type(elem_ty, arg_ty_span.0);
     if let Some(foreign_class) =
-        conv_map.find_foreigner_class_with_such_self_type(&elem_rust_ty, false)
+        conv_map.find_foreigner_class_with_such_self_type(elem_rust_ty, false)
     {
         let typename = format!("RustForeignSlice<{}Ref>", foreign_class.name);
         ftype_info.cpp_converter = Some(CppConverter {
             typename: typename.into(),
             converter: FROM_VAR_TEMPLATE.to_string(),
         });
+        ftype_info.provides_by_module.extend_from_slice(&[
+            "\"rust_vec.h\"".into(),
+            format!("\"{}\"", cpp_header_name(foreign_class)).into(),
+        ]);
         return Ok(Some(ftype_info));
-    } else {
-        Ok(None)
+    }
+    if cpp_cfg.std_span {
+        if let Some(cpp_elem_ty) = slice_elem_cpp_type(conv_map, &ftype_info) {
+            let mutable = match conv_map[arg_ty].ty {
+                Type::Reference(ref r) => r.mutability.is_some(),
+                _ => false,
+            };
+            let typename = if mutable {
+                format!("std::span<{}>", cpp_elem_ty)
+            } else {
+                format!("std::span<const {}>", cpp_elem_ty)
+            };
+            let c_slice_ty = conv_map[ftype_info.base.correspoding_rust_type]
+                .normalized_name
+                .clone();
+            ftype_info.cpp_converter = Some(CppConverter {
+                typename: typename.into(),
+                converter: format!(
+                    "{CType}{{ {var}.data(), {var}.size() }}",
+                    CType = c_slice_ty,
+                    var = FROM_VAR_TEMPLATE
+                ),
+            });
+            ftype_info.provides_by_module = vec!["\"rust_vec.h\"".into(), "<span>".into()];
+            return Ok(Some(ftype_info));
+        }
+    }
+    Ok(None)
+}
+
+/// C++ type of element for C struct that represents slice of primitive type
+fn slice_elem_cpp_type(
+    conv_map: &TypeMap,
+    ftype_info: &CppForeignTypeInfo,
+) -> Option<&'static str> {
+    match conv_map[ftype_info.base.correspoding_rust_type]
+        .normalized_name
+        .as_str()
+    {
+        "CRustSliceU8" => Some("uint8_t"),
+        "CRustSliceI32" => Some("int32_t"),
+        "CRustSliceU16" => Some("uint16_t"),
+        "CRustSliceU32" => Some("uint32_t"),
+        "CRustSliceUsize" => Some("uintptr_t"),
+        _ => None,
     }
 }
 
 fn map_return_slice_type(
     conv_map: &mut TypeMap,
-    arg_ty: &RustType,
+    cpp_cfg: &CppConfig,
+    arg_ty: RustTypeIdx,
     elem_ty: &Type,
     arg_ty_span: SourceIdSpan,
 ) -> Result<Option<CppForeignTypeInfo>> {
     let mut ftype_info = map_ordinal_result_type(conv_map, arg_ty, arg_ty_span)?;
     let elem_rust_ty = conv_map.find_or_alloc_rust_type(elem_ty, arg_ty_span.0);
     if let Some(foreign_class) =
-        conv_map.find_foreigner_class_with_such_self_type(&elem_rust_ty, false)
+        conv_map.find_foreigner_class_with_such_self_type(elem_rust_ty, false)
     {
         let typename = format!("RustForeignSlice<{}Ref>", foreign_class.name);
         let converter = format!(
@@ -295,16 +537,35 @@ fn map_return_slice_type(
             typename: typename.into(),
             converter,
         });
+        ftype_info.provides_by_module.extend_from_slice(&[
+            "\"rust_vec.h\"".into(),
+            format!("\"{}\"", cpp_header_name(foreign_class)).into(),
+        ]);
         return Ok(Some(ftype_info));
-    } else {
-        Ok(None)
     }
+    if cpp_cfg.std_span {
+        if let Some(cpp_elem_ty) = slice_elem_cpp_type(conv_map, &ftype_info) {
+            let typename = format!("std::span<const {}>", cpp_elem_ty);
+            let converter = format!(
+                "{cpp_type}{{ {var}.data, {var}.len }}",
+                cpp_type = typename,
+                var = FROM_VAR_TEMPLATE
+            );
+            ftype_info.cpp_converter = Some(CppConverter {
+                typename: typename.into(),
+                converter,
+            });
+            ftype_info.provides_by_module = vec!["\"rust_vec.h\"".into(), "<span>".into()];
+            return Ok(Some(ftype_info));
+        }
+    }
+    Ok(None)
 }
 
 fn map_type_vec(
     conv_map: &mut TypeMap,
     cpp_cfg: &CppConfig,
-    arg_ty: &RustType,
+    arg_ty: RustTypeIdx,
     elem_ty: &Type,
     arg_ty_span: SourceIdSpan,
     direction: Direction,
@@ -312,9 +573,10 @@ fn map_type_vec(
     let mut ftype_info = map_ordinal_result_type(conv_map, arg_ty, arg_ty_span)?;
     let elem_rust_ty = conv_map.find_or_alloc_rust_type(elem_ty, arg_ty_span.0);
     if let Some(foreign_class) =
-        conv_map.find_foreigner_class_with_such_self_type(&elem_rust_ty, false)
+        conv_map.find_foreigner_class_with_such_self_type(elem_rust_ty, false)
     {
         let typename = format!("RustForeignVec{}", foreign_class.name);
+        let class_header = format!("\"{}\"", cpp_header_name(foreign_class));
         let fc_vec_path = cpp_cfg.output_dir.join(format!("{}.h", typename));
         let generate_cpp_part = !cpp_cfg
             .generated_helper_files
@@ -326,33 +588,40 @@ fn map_type_vec(
                 fc_vec_path
             );
             let mut c_vec_f = FileWriteCache::new(&fc_vec_path);
-            let free_mem_func = format!("{}_free", typename);
-            let push_func = format!("{}_push", typename);
-            let remove_func = format!("{}_remove", typename);
+            let free_mem_func = cpp_cfg.c_symbol(&format!("{}_free", typename));
+            let push_func = cpp_cfg.c_symbol(&format!("{}_push", typename));
+            let remove_func = cpp_cfg.c_symbol(&format!("{}_remove", typename));
             write!(
                 c_vec_f,
                 r##"// Automaticaly generated by rust_swig
 #pragma once
 
 #include "rust_vec.h"
-
+{api_include}
 #ifdef __cplusplus
+#include "{class}_fwd.hpp"
+
 extern "C" {{
 #endif
-extern void {free_mem_func}(struct CRustForeignVec);
-extern void {push_func}(struct CRustForeignVec *, void *);
-extern void *{remove_func}(struct CRustForeignVec *, uintptr_t);
+{api}extern void {free_mem_func}(struct CRustForeignVec);
+{api}extern void {push_func}(struct CRustForeignVec *, void *);
+{api}extern void *{remove_func}(struct CRustForeignVec *, uintptr_t);
 #ifdef __cplusplus
 
-namespace {namespace_name} {{
+{open_namespace}
 using {vec_type} = RustForeignVec<{class}Ref, CRustForeignVec,
                                   {free_mem_func}, {push_func}, {remove_func}>;
-}}
+{close_namespace}
 }}
 #endif
 "##,
                 free_mem_func = free_mem_func,
-                namespace_name = cpp_cfg.namespace_name,
+                api_include = cpp_cfg.c_api_include(),
+                api = cpp_cfg.c_api(),
+                open_namespace =
+                    open_namespace(&cpp_cfg.namespace_name, cpp_cfg.nested_namespace_syntax()),
+                close_namespace =
+                    close_namespace(&cpp_cfg.namespace_name, cpp_cfg.nested_namespace_syntax()),
                 vec_type = typename,
                 class = foreign_class.name,
                 push_func = push_func,
@@ -377,11 +646,11 @@ using {vec_type} = RustForeignVec<{class}Ref, CRustForeignVec,
                 .find_or_alloc_rust_type(&foreign_class.self_type_as_ty(), foreign_class.src_id);
 
             let func_id = syn::Ident::new(&free_mem_func, Span::call_site());
-            let self_type_id: Type =
-                syn::parse_str(&self_rust_ty.normalized_name).unwrap_or_else(|err| {
+            let self_type_id: Type = syn::parse_str(&conv_map[self_rust_ty].normalized_name)
+                .unwrap_or_else(|err| {
                     panic_on_syn_error(
                         "c++/internal self_rust_ty",
-                        self_rust_ty.normalized_name.clone().into(),
+                        conv_map[self_rust_ty].normalized_name.clone().into(),
                         err,
                     )
                 });
@@ -437,15 +706,16 @@ using {vec_type} = RustForeignVec<{class}Ref, CRustForeignVec,
             ),
             Direction::Incoming => format!("{var}.release()", var = FROM_VAR_TEMPLATE),
         };
+        ftype_info
+            .provides_by_module
+            .extend_from_slice(&[format!("\"{}.h\"", typename).into(), class_header.into()]);
         ftype_info.cpp_converter = Some(CppConverter {
             typename: typename.into(),
             converter,
         });
         return Ok(Some(ftype_info));
     }
-    let typename = match ftype_info
-        .base
-        .correspoding_rust_type
+    let typename = match conv_map[ftype_info.base.correspoding_rust_type]
         .normalized_name
         .as_str()
     {
@@ -455,6 +725,11 @@ using {vec_type} = RustForeignVec<{class}Ref, CRustForeignVec,
         "CRustVecUsize" => "RustVecUsize",
         "CRustVecF32" => "RustVecF32",
         "CRustVecF64" => "RustVecF64",
+        "CRustVecI8" => "RustVecI8",
+        "CRustVecI16" => "RustVecI16",
+        "CRustVecU16" => "RustVecU16",
+        "CRustVecI64" => "RustVecI64",
+        "CRustVecU64" => "RustVecU64",
         _ => unimplemented!(),
     };
     let converter = match direction {
@@ -475,7 +750,7 @@ using {vec_type} = RustForeignVec<{class}Ref, CRustForeignVec,
 fn handle_result_type_as_return_type(
     conv_map: &mut TypeMap,
     cpp_cfg: &CppConfig,
-    arg_ty: &RustType,
+    arg_ty: RustTypeIdx,
     ok_ty: &Type,
     err_ty: &Type,
     arg_ty_span: SourceIdSpan,
@@ -484,13 +759,12 @@ fn handle_result_type_as_return_type(
     let ok_rust_ty = conv_map.find_or_alloc_rust_type(ok_ty, arg_ty_span.0);
     debug!(
         "handle_result_type_as_return_type: ok_ty: {:?}, err_ty: {}",
-        ok_rust_ty, err_rust_ty
+        ok_rust_ty, conv_map[err_rust_ty]
     );
-    if let Some(foreign_class_this_ty) = conv_map.is_ty_implements(&ok_rust_ty, "SwigForeignClass")
-    {
+    if let Some(foreign_class_this_ty) = conv_map.is_ty_implements(ok_rust_ty, "SwigForeignClass") {
         let foreign_class = conv_map
             .find_foreigner_class_with_such_this_type(
-                &foreign_class_this_ty.ty,
+                &conv_map[foreign_class_this_ty].ty,
                 calc_this_type_for_method,
             )
             .ok_or_else(|| {
@@ -500,28 +774,25 @@ fn handle_result_type_as_return_type(
                 )
             })?;
         let c_class = c_class_type(foreign_class);
-        if err_rust_ty.normalized_name == "String" {
+        if conv_map[err_rust_ty].normalized_name == "String" {
             let foreign_info = conv_map
                 .find_foreign_type_info_by_name("struct CResultObjectString")
                 .expect("Can not find info about struct CResultObjectString");
-            let (typename, var_include) = match cpp_cfg.cpp_variant {
-                CppVariant::Std17 => (
-                    format!("std::variant<{}, RustString>", foreign_class.name),
-                    "<variant>".into(),
-                ),
-                CppVariant::Boost => (
-                    format!("boost::variant<{}, RustString>", foreign_class.name),
-                    "<boost/variant.hpp>".into(),
-                ),
-            };
+            let (typename, var_include) =
+                cpp_result_type(cpp_cfg, &foreign_class.name.to_string(), "RustString");
             let converter = format!(
                 "{var}.is_ok != 0 ?
  {VarType}{{{Type}(static_cast<{C_Type} *>({var}.data.ok))}} :
- {VarType}{{RustString{{{var}.data.err}}}}",
+ {VarType}{{{Err}}}",
                 VarType = typename,
                 Type = foreign_class.name,
                 C_Type = c_class,
                 var = FROM_VAR_TEMPLATE,
+                Err = cpp_result_err(
+                    cpp_cfg,
+                    "RustString",
+                    &format!("RustString{{{}.data.err}}", FROM_VAR_TEMPLATE)
+                ),
             );
             return Ok(Some(CppForeignTypeInfo {
                 base: foreign_info,
@@ -537,32 +808,31 @@ fn handle_result_type_as_return_type(
                 }),
             }));
         } else if let Some(err_class) =
-            conv_map.find_foreigner_class_with_such_self_type(&err_rust_ty, false)
+            conv_map.find_foreigner_class_with_such_self_type(err_rust_ty, false)
         {
             let foreign_info = conv_map
                 .find_foreign_type_info_by_name("struct CResultObjectObject")
                 .expect("Can not find info about struct CResultObjectObject");
             let c_err_class = c_class_type(err_class);
-            let (typename, var_inc) = match cpp_cfg.cpp_variant {
-                CppVariant::Std17 => (
-                    format!("std::variant<{}, {}>", foreign_class.name, err_class.name),
-                    "<variant>".into(),
-                ),
-                CppVariant::Boost => (
-                    format!("boost::variant<{}, {}>", foreign_class.name, err_class.name),
-                    "<boost/variant.hpp>".into(),
-                ),
-            };
+            let err_name = err_class.name.to_string();
+            let (typename, var_inc) =
+                cpp_result_type(cpp_cfg, &foreign_class.name.to_string(), &err_name);
             let converter = format!(
                 "{var}.is_ok != 0 ?
  {VarType} {{ {Type}(static_cast<{C_Type} *>({var}.data.ok))}} :
- {VarType} {{ {ErrType}(static_cast<{C_ErrType} *>({var}.data.err))}}",
+ {VarType} {{ {Err}}}",
                 VarType = typename,
                 Type = foreign_class.name,
                 C_Type = c_class,
                 var = FROM_VAR_TEMPLATE,
-                ErrType = err_class.name,
-                C_ErrType = c_err_class,
+                Err = cpp_result_err(
+                    cpp_cfg,
+                    &err_name,
+                    &format!(
+                        "{}(static_cast<{} *>({}.data.err))",
+                        err_name, c_err_class, FROM_VAR_TEMPLATE
+                    )
+                ),
             );
             return Ok(Some(CppForeignTypeInfo {
                 base: foreign_info,
@@ -577,30 +847,27 @@ fn handle_result_type_as_return_type(
                     converter,
                 }),
             }));
-        } else if let Some(err_enum) = conv_map.is_this_exported_enum(&err_rust_ty) {
+        } else if let Some(err_enum) = conv_map.is_this_exported_enum(err_rust_ty) {
             let foreign_info = conv_map
                 .find_foreign_type_info_by_name("struct CResultObjectEnum")
                 .expect("Can not find info about struct CResultObjectEnum");
 
-            let (typename, var_inc) = match cpp_cfg.cpp_variant {
-                CppVariant::Std17 => (
-                    format!("std::variant<{}, {}>", foreign_class.name, err_enum.name),
-                    "<variant>".into(),
-                ),
-                CppVariant::Boost => (
-                    format!("boost::variant<{}, {}>", foreign_class.name, err_enum.name,),
-                    "<boost/variant.hpp>".into(),
-                ),
-            };
+            let err_name = err_enum.name.to_string();
+            let (typename, var_inc) =
+                cpp_result_type(cpp_cfg, &foreign_class.name.to_string(), &err_name);
             let converter = format!(
                 "{var}.is_ok != 0 ?
  {VarType}{{{Type}(static_cast<{C_Type} *>({var}.data.ok))}} :
- {VarType}{{static_cast<{EnumName}>({var}.data.err)}}",
+ {VarType}{{{Err}}}",
                 VarType = typename,
                 Type = foreign_class.name,
                 C_Type = c_class,
                 var = FROM_VAR_TEMPLATE,
-                EnumName = err_enum.name,
+                Err = cpp_result_err(
+                    cpp_cfg,
+                    &err_name,
+                    &format!("static_cast<{}>({}.data.err)", err_name, FROM_VAR_TEMPLATE)
+                ),
             );
             return Ok(Some(CppForeignTypeInfo {
                 base: foreign_info,
@@ -620,89 +887,91 @@ fn handle_result_type_as_return_type(
         }
     }
 
-    if let Some(elem_ty) = if_vec_return_elem_type(&ok_rust_ty) {
+    if let Some(elem_ty) = if_vec_return_elem_type(&conv_map[ok_rust_ty]) {
         let elem_rust_ty = conv_map.find_or_alloc_rust_type(&elem_ty, arg_ty_span.0);
         trace!(
             "handle_result_type_as_return_type ok_ty is Vec, elem_ty {}",
-            elem_rust_ty
+            conv_map[elem_rust_ty]
         );
         let vec_foreign_info = map_type(
             conv_map,
             cpp_cfg,
-            &ok_rust_ty,
+            ok_rust_ty,
             Direction::Outgoing,
-            (ok_rust_ty.src_id, ok_ty.span()),
+            (conv_map[ok_rust_ty].src_id, ok_ty.span()),
         )?;
         let mut f_type_info = map_ordinal_result_type(conv_map, arg_ty, arg_ty_span)?;
-        if err_rust_ty.normalized_name == "String" {
+        if conv_map[err_rust_ty].normalized_name == "String" {
             let foreign_name = conv_map
-                .find_foreigner_class_with_such_self_type(&elem_rust_ty, false)
+                .find_foreigner_class_with_such_self_type(elem_rust_ty, false)
                 .map(|v| v.name.clone());
             if let Some(foreign_name) = foreign_name {
                 let ok_typename = format!("RustForeignVec{}", foreign_name);
-                let (typename, var_inc) = match cpp_cfg.cpp_variant {
-                    CppVariant::Std17 => (
-                        format!("std::variant<{}, RustString>", ok_typename),
-                        "<variant>".into(),
-                    ),
-                    CppVariant::Boost => (
-                        format!("boost::variant<{}, RustString>", ok_typename),
-                        "<boost/variant.hpp>".into(),
-                    ),
-                };
+                let (typename, var_inc) = cpp_result_type(cpp_cfg, &ok_typename, "RustString");
                 let converter = format!(
                     "{var}.is_ok != 0 ?
  {VarType}{{{Type}{{{var}.data.ok}}}} :
- {VarType}{{RustString{{{var}.data.err}}}}",
+ {VarType}{{{Err}}}",
                     VarType = typename,
                     Type = ok_typename,
                     var = FROM_VAR_TEMPLATE,
+                    Err = cpp_result_err(
+                        cpp_cfg,
+                        "RustString",
+                        &format!("RustString{{{}.data.err}}", FROM_VAR_TEMPLATE)
+                    ),
                 );
                 f_type_info.cpp_converter = Some(CppConverter {
                     typename: typename.into(),
                     converter,
                 });
                 f_type_info.provides_by_module = vec!["\"rust_str.h\"".into(), var_inc];
+                f_type_info
+                    .provides_by_module
+                    .extend_from_slice(&vec_foreign_info.provides_by_module);
                 return Ok(Some(f_type_info));
             } else {
                 return Ok(None);
             }
         } else if let Some(err_class) =
-            conv_map.find_foreigner_class_with_such_self_type(&err_rust_ty, false)
+            conv_map.find_foreigner_class_with_such_self_type(err_rust_ty, false)
         {
             // Result<Vec<T>, Err>
             let foreign_name = conv_map
-                .find_foreigner_class_with_such_self_type(&elem_rust_ty, false)
+                .find_foreigner_class_with_such_self_type(elem_rust_ty, false)
                 .map(|v| v.name.clone());
             if let Some(foreign_name) = foreign_name {
                 let ok_typename = format!("RustForeignVec{}", foreign_name);
                 let c_err_class = c_class_type(err_class);
-                let (typename, var_inc) = match cpp_cfg.cpp_variant {
-                    CppVariant::Std17 => (
-                        format!("std::variant<{}, {}>", ok_typename, err_class.name),
-                        "<variant>".into(),
-                    ),
-
-                    CppVariant::Boost => (
-                        format!("boost::variant<{}, {}>", ok_typename, err_class.name),
-                        "<boost/variant.hpp>".into(),
-                    ),
-                };
+                let err_name = err_class.name.to_string();
+                let (typename, var_inc) = cpp_result_type(cpp_cfg, &ok_typename, &err_name);
                 let converter = format!(
                     "{var}.is_ok != 0 ?
  {VarType} {{ {Type}{{{var}.data.ok}} }} :
- {VarType} {{ {ErrType}(static_cast<{C_ErrType} *>({var}.data.err)) }}",
+ {VarType} {{ {Err} }}",
                     VarType = typename,
                     Type = ok_typename,
                     var = FROM_VAR_TEMPLATE,
-                    ErrType = err_class.name,
-                    C_ErrType = c_err_class,
+                    Err = cpp_result_err(
+                        cpp_cfg,
+                        &err_name,
+                        &format!(
+                            "{}(static_cast<{} *>({}.data.err))",
+                            err_name, c_err_class, FROM_VAR_TEMPLATE
+                        )
+                    ),
                 );
                 f_type_info.cpp_converter = Some(CppConverter {
                     typename: typename.into(),
                     converter,
                 });
-                f_type_info.provides_by_module = vec![var_inc];
+                f_type_info.provides_by_module = vec![
+                    var_inc,
+                    format!("\"{}\"", cpp_header_name(err_class)).into(),
+                ];
+                f_type_info
+                    .provides_by_module
+                    .extend_from_slice(&vec_foreign_info.provides_by_module);
                 return Ok(Some(f_type_info));
             } else {
                 if let Some(cpp_conv) = vec_foreign_info.cpp_converter.as_ref() {
@@ -711,28 +980,35 @@ fn handle_result_type_as_return_type(
                     );
                     let ok_typename = &cpp_conv.typename;
                     let c_err_class = c_class_type(err_class);
-                    let typename = match cpp_cfg.cpp_variant {
-                        CppVariant::Std17 => {
-                            format!("std::variant<{}, {}>", ok_typename, err_class.name)
-                        }
-                        CppVariant::Boost => {
-                            format!("boost::variant<{}, {}>", ok_typename, err_class.name)
-                        }
-                    };
+                    let err_name = err_class.name.to_string();
+                    let (typename, var_inc) = cpp_result_type(cpp_cfg, ok_typename, &err_name);
                     let converter = format!(
                         "{var}.is_ok != 0 ?
  {VarType} {{ {Type}{{{var}.data.ok}} }} :
- {VarType} {{ {ErrType}(static_cast<{C_ErrType} *>({var}.data.err)) }}",
+ {VarType} {{ {Err} }}",
                         VarType = typename,
                         Type = ok_typename,
                         var = FROM_VAR_TEMPLATE,
-                        ErrType = err_class.name,
-                        C_ErrType = c_err_class,
+                        Err = cpp_result_err(
+                            cpp_cfg,
+                            &err_name,
+                            &format!(
+                                "{}(static_cast<{} *>({}.data.err))",
+                                err_name, c_err_class, FROM_VAR_TEMPLATE
+                            )
+                        ),
                     );
                     f_type_info.cpp_converter = Some(CppConverter {
                         typename: typename.into(),
                         converter,
                     });
+                    f_type_info.provides_by_module.extend_from_slice(&[
+                        var_inc,
+                        format!("\"{}\"", cpp_header_name(err_class)).into(),
+                    ]);
+                    f_type_info
+                        .provides_by_module
+                        .extend_from_slice(&vec_foreign_info.provides_by_module);
                     return Ok(Some(f_type_info));
                 }
                 return Ok(None);
@@ -760,16 +1036,16 @@ fn handle_result_type_as_return_type(
 fn handle_option_type_in_input(
     conv_map: &mut TypeMap,
     cpp_cfg: &CppConfig,
-    arg_ty: &RustType,
+    arg_ty: RustTypeIdx,
     (opt_ty, opt_src_id): (&Type, SourceId),
     arg_ty_span: SourceIdSpan,
 ) -> Result<Option<CppForeignTypeInfo>> {
     let opt_rust_ty = conv_map.find_or_alloc_rust_type(opt_ty, arg_ty_span.0);
-    if let Some(fclass) = conv_map.find_foreigner_class_with_such_self_type(&opt_rust_ty, false) {
+    if let Some(fclass) = conv_map.find_foreigner_class_with_such_self_type(opt_rust_ty, false) {
         let foreign_info = foreign_class_foreign_name(
             conv_map,
             fclass,
-            (opt_rust_ty.src_id, opt_ty.span()),
+            (conv_map[opt_rust_ty].src_id, opt_ty.span()),
             false,
         )?;
         let (typename, converter, opt_inc) = match cpp_cfg.cpp_optional {
@@ -806,51 +1082,58 @@ fn handle_option_type_in_input(
 
     let opt_rust_ty = conv_map.find_or_alloc_rust_type(opt_ty, opt_src_id);
 
-    if let Type::Reference(syn::TypeReference {
-        elem: ref ref_ty,
-
-        mutability: None,
-        ..
-    }) = opt_ty
+    if conv_map[opt_rust_ty].normalized_name == "& str"
+        || conv_map[opt_rust_ty].normalized_name == "String"
     {
-        if let Type::Path(syn::TypePath { ref path, .. }) = **ref_ty {
-            if path.segments.len() == 1 && path.segments[0].ident == "str" {
-                trace!("Catch Option<&str>");
-                let mut cpp_info_opt = map_ordinal_input_type(conv_map, arg_ty, arg_ty_span)?;
-                let cpp_info_ty = map_ordinal_input_type(
-                    conv_map,
-                    &opt_rust_ty,
-                    (opt_rust_ty.src_id, opt_ty.span()),
-                )?;
-                let f_opt_ty = cpp_info_ty.base.name;
-                let (typename, converter) = match cpp_cfg.cpp_optional {
-                    CppOptional::Std17 => (
-                        format!("std::optional<{}>", f_opt_ty),
-                        format!("!!{var} ? *{var} : nullptr", var = FROM_VAR_TEMPLATE,),
-                    ),
-                    CppOptional::Boost => (
-                        format!("boost::optional<{}>", f_opt_ty),
-                        format!("!!{var} ? *{var} : nullptr", var = FROM_VAR_TEMPLATE,),
-                    ),
-                };
-                cpp_info_opt.cpp_converter = Some(CppConverter {
-                    typename: typename.into(),
-                    converter,
-                });
-                return Ok(Some(cpp_info_opt));
-            }
+        trace!("Catch Option<&str> or Option<String>");
+        if cpp_cfg.string_view_args() {
+            return Ok(Some(map_option_str_view_input(
+                conv_map,
+                cpp_cfg,
+                arg_ty,
+                conv_map[opt_rust_ty].normalized_name == "& str",
+            )));
         }
+        let mut cpp_info_opt = map_ordinal_input_type(conv_map, arg_ty, arg_ty_span)?;
+        let f_opt_ty = "const char *";
+        let (typename, converter) = match cpp_cfg.cpp_optional {
+            CppOptional::Std17 => (
+                format!("std::optional<{}>", f_opt_ty),
+                format!("!!{var} ? *{var} : nullptr", var = FROM_VAR_TEMPLATE,),
+            ),
+            CppOptional::Boost => (
+                format!("boost::optional<{}>", f_opt_ty),
+                format!("!!{var} ? *{var} : nullptr", var = FROM_VAR_TEMPLATE,),
+            ),
+        };
+        cpp_info_opt.cpp_converter = Some(CppConverter {
+            typename: typename.into(),
+            converter,
+        });
+        return Ok(Some(cpp_info_opt));
     }
     trace!("handle_option_type_in_input arg_ty {:?}", arg_ty);
     let mut cpp_info_opt = map_ordinal_input_type(conv_map, arg_ty, arg_ty_span)?;
-    let cpp_info_ty =
-        map_ordinal_input_type(conv_map, &opt_rust_ty, (opt_rust_ty.src_id, opt_ty.span()))?;
+    let cpp_info_ty = map_ordinal_input_type(
+        conv_map,
+        opt_rust_ty,
+        (conv_map[opt_rust_ty].src_id, opt_ty.span()),
+    )?;
     let f_opt_ty = cpp_info_ty.base.name;
     let mut c_option_name: &str = &cpp_info_opt.base.name;
     if c_option_name.starts_with("struct ") {
         c_option_name = &c_option_name[7..];
     }
-    let conv: &'static str = if conv_map.is_this_exported_enum(&opt_rust_ty).is_some() {
+    if !c_option_name.starts_with("CRustOption") {
+        return Err(DiagnosticError::new2(
+            arg_ty_span,
+            format!(
+                "Do not know how to pass {} as input of type {}",
+                conv_map[arg_ty].normalized_name, cpp_info_opt.base.name
+            ),
+        ));
+    }
+    let conv: &'static str = if conv_map.is_this_exported_enum(opt_rust_ty).is_some() {
         "static_cast<uint32_t>"
     } else {
         ""
@@ -875,6 +1158,14 @@ fn handle_option_type_in_input(
             ),
         ),
     };
+    cpp_info_opt
+        .provides_by_module
+        .extend(cpp_info_ty.provides_by_module);
+    if let Some(foreign_enum) = conv_map.is_this_exported_enum(opt_rust_ty) {
+        cpp_info_opt
+            .provides_by_module
+            .push(format!("\"{}\"", cpp_header_name_for_enum(foreign_enum)).into());
+    }
     cpp_info_opt.cpp_converter = Some(CppConverter {
         typename: typename.into(),
         converter,
@@ -882,19 +1173,147 @@ fn handle_option_type_in_input(
     Ok(Some(cpp_info_opt))
 }
 
+/// `Box<dyn Iterator<Item = T>>` is collected into `Vec<T>`,
+/// so C++ code gets the same view over Rust memory as for `Vec<T>`
+fn handle_iterator_in_return(
+    conv_map: &mut TypeMap,
+    cpp_cfg: &CppConfig,
+    arg_ty: RustTypeIdx,
+    item_ty: &Type,
+    arg_ty_span: SourceIdSpan,
+) -> Result<Option<CppForeignTypeInfo>> {
+    let vec_ty = conv_map.find_or_alloc_rust_type(&parse_type! { Vec<#item_ty> }, arg_ty_span.0);
+    let cpp_info = match map_type_vec(
+        conv_map,
+        cpp_cfg,
+        vec_ty,
+        item_ty,
+        arg_ty_span,
+        Direction::Outgoing,
+    )? {
+        Some(x) => x,
+        None => return Ok(None),
+    };
+    let c_vec_ty = cpp_info.base.correspoding_rust_type;
+    let (mut deps, conv_code) =
+        conv_map.convert_rust_types(vec_ty, c_vec_ty, "x", "", arg_ty_span)?;
+    cpp_cfg.to_generate.borrow_mut().append(&mut deps);
+    conv_map.add_conversation_rule(
+        arg_ty,
+        c_vec_ty,
+        format!(
+            r#"
+    let {to_var}: {c_vec_type} = {{
+        let x: {vec_type} = {from_var}.collect();
+{conv_code}
+        x
+    }};
+"#,
+            to_var = TO_VAR_TEMPLATE,
+            from_var = FROM_VAR_TEMPLATE,
+            c_vec_type = conv_map[c_vec_ty].normalized_name,
+            vec_type = conv_map[vec_ty].normalized_name,
+            conv_code = conv_code,
+        )
+        .into(),
+    );
+    Ok(Some(cpp_info))
+}
+
+/// `Option<Vec<T>>` is returned as C struct for `Vec<T>` with `data == NULL` for `None`
+fn handle_option_vec_in_return(
+    conv_map: &mut TypeMap,
+    cpp_cfg: &CppConfig,
+    arg_ty: RustTypeIdx,
+    vec_ty: RustTypeIdx,
+    elem_ty: &Type,
+    arg_ty_span: SourceIdSpan,
+) -> Result<Option<CppForeignTypeInfo>> {
+    let mut cpp_info = match map_type_vec(
+        conv_map,
+        cpp_cfg,
+        vec_ty,
+        elem_ty,
+        arg_ty_span,
+        Direction::Outgoing,
+    )? {
+        Some(x) => x,
+        None => return Ok(None),
+    };
+    let c_vec_ty = cpp_info.base.correspoding_rust_type;
+    let (mut deps, conv_code) =
+        conv_map.convert_rust_types(vec_ty, c_vec_ty, "x", "", arg_ty_span)?;
+    cpp_cfg.to_generate.borrow_mut().append(&mut deps);
+    conv_map.add_conversation_rule(
+        arg_ty,
+        c_vec_ty,
+        format!(
+            r#"
+    let {to_var}: {c_vec_type} = match {from_var} {{
+        Some(x) => {{
+{conv_code}
+            x
+        }}
+        None => unsafe {{ ::std::mem::zeroed() }},
+    }};
+"#,
+            to_var = TO_VAR_TEMPLATE,
+            from_var = FROM_VAR_TEMPLATE,
+            c_vec_type = conv_map[c_vec_ty].normalized_name,
+            conv_code = conv_code,
+        )
+        .into(),
+    );
+    let cpp_vec_ty = cpp_info
+        .cpp_converter
+        .take()
+        .expect("C++ converter for Vec")
+        .typename;
+    let (typename, converter, opt_inc) = match cpp_cfg.cpp_optional {
+        CppOptional::Std17 => (
+            format!("std::optional<{}>", cpp_vec_ty),
+            format!(
+                "{var}.data != nullptr ? {Type}{{{var}}} : std::optional<{Type}>()",
+                Type = cpp_vec_ty,
+                var = FROM_VAR_TEMPLATE,
+            ),
+            "<optional>".into(),
+        ),
+        CppOptional::Boost => (
+            format!("boost::optional<{}>", cpp_vec_ty),
+            format!(
+                "{var}.data != nullptr ? {Type}{{{var}}} : boost::optional<{Type}>()",
+                Type = cpp_vec_ty,
+                var = FROM_VAR_TEMPLATE,
+            ),
+            "<boost/optional.hpp>".into(),
+        ),
+    };
+    cpp_info.provides_by_module.push("\"rust_option.h\"".into());
+    cpp_info.provides_by_module.push(opt_inc);
+    cpp_info.cpp_converter = Some(CppConverter {
+        typename: typename.into(),
+        converter,
+    });
+    Ok(Some(cpp_info))
+}
+
 fn handle_option_type_in_return(
     conv_map: &mut TypeMap,
     cpp_cfg: &CppConfig,
-    arg_ty: &RustType,
+    arg_ty: RustTypeIdx,
     (opt_ty, opt_ty_src_id): (&Type, SourceId),
     arg_ty_span: SourceIdSpan,
 ) -> Result<Option<CppForeignTypeInfo>> {
     let opt_rust_ty = conv_map.find_or_alloc_rust_type(opt_ty, opt_ty_src_id);
-    if opt_rust_ty.implements.contains("SwigForeignClass") {
-        let foreign_class_this_ty = &opt_rust_ty;
+    if conv_map[opt_rust_ty]
+        .implements
+        .contains("SwigForeignClass")
+    {
+        let foreign_class_this_ty = opt_rust_ty;
         let foreign_class = conv_map
             .find_foreigner_class_with_such_this_type(
-                &foreign_class_this_ty.ty,
+                &conv_map[foreign_class_this_ty].ty,
                 calc_this_type_for_method,
             )
             .ok_or_else(|| {
@@ -940,6 +1359,17 @@ fn handle_option_type_in_return(
         }));
     }
 
+    if let Some(elem_ty) = if_vec_return_elem_type(&conv_map[opt_rust_ty]) {
+        return handle_option_vec_in_return(
+            conv_map,
+            cpp_cfg,
+            arg_ty,
+            opt_rust_ty,
+            &elem_ty,
+            arg_ty_span,
+        );
+    }
+
     //handle Option<&ForeignClass> case
     if let Type::Reference(syn::TypeReference {
         elem: ref under_ref_ty,
@@ -949,7 +1379,7 @@ fn handle_option_type_in_return(
     {
         let under_ref_rust_ty = conv_map.find_or_alloc_rust_type(under_ref_ty, arg_ty_span.0);
         if let Some(fclass) = conv_map
-            .find_foreigner_class_with_such_self_type(&under_ref_rust_ty, false)
+            .find_foreigner_class_with_such_self_type(under_ref_rust_ty, false)
             .cloned()
         {
             let foreign_info = foreign_class_foreign_name(
@@ -972,16 +1402,17 @@ fn handle_option_type_in_return(
                     ),
                 )
             })?;
-            let this_type: RustType = conv_map.ty_to_rust_type(this_type_for_method);
+            let this_type: RustTypeIdx = conv_map.ty_to_rust_type(this_type_for_method);
             let void_ptr_ty = parse_type! { *mut ::std::os::raw::c_void };
+            let this_type_name = conv_map[this_type].normalized_name.clone();
             let my_void_ptr_ti = conv_map.find_or_alloc_rust_type_with_suffix(
                 &void_ptr_ty,
-                &this_type.normalized_name,
+                &this_type_name,
                 SourceId::none(),
             );
             conv_map.add_conversation_rule(
-                arg_ty.to_idx(),
-                my_void_ptr_ti.to_idx(),
+                arg_ty,
+                my_void_ptr_ti,
                 format!(
                     r#"
     let {to_var}: *mut ::std::os::raw::c_void = match {from_var} {{
@@ -991,7 +1422,7 @@ fn handle_option_type_in_return(
 "#,
                     to_var = TO_VAR_TEMPLATE,
                     from_var = FROM_VAR_TEMPLATE,
-                    self_type = this_type.normalized_name,
+                    self_type = conv_map[this_type].normalized_name,
                 )
                 .into(),
             );
@@ -1031,7 +1462,7 @@ fn handle_option_type_in_return(
         }
     }
     let mut cpp_info_opt = map_ordinal_result_type(conv_map, arg_ty, arg_ty_span)?;
-    let cpp_info_ty = map_ordinal_result_type(conv_map, &opt_rust_ty, arg_ty_span)?;
+    let cpp_info_ty = map_ordinal_result_type(conv_map, opt_rust_ty, arg_ty_span)?;
 
     let f_opt_ty = if *opt_ty != parse_type! {bool} {
         cpp_info_ty.base.name
@@ -1040,7 +1471,7 @@ fn handle_option_type_in_return(
     };
     debug!("is_this_exported_enum {:?}", opt_ty);
     let (typename, converter) =
-        if let Some(foreign_enum) = conv_map.is_this_exported_enum(&opt_rust_ty) {
+        if let Some(foreign_enum) = conv_map.is_this_exported_enum(opt_rust_ty) {
             match cpp_cfg.cpp_optional {
                 CppOptional::Std17 => (
                     format!("std::optional<{}>", f_opt_ty),
@@ -1086,7 +1517,7 @@ fn handle_option_type_in_return(
     if let Type::Path(syn::TypePath { ref path, .. }) = opt_ty {
         if path.segments.len() == 1 && path.segments[0].ident == "String" {
             trace!("Catch return of Option<String>");
-            let cpp_info_ty = map_ordinal_result_type(conv_map, &opt_rust_ty, arg_ty_span)?;
+            let cpp_info_ty = map_ordinal_result_type(conv_map, opt_rust_ty, arg_ty_span)?;
             let cpp_typename = cpp_info_ty
                 .cpp_converter
                 .expect("C++ converter from C struct")
@@ -1121,9 +1552,9 @@ fn handle_option_type_in_return(
         }
     }
 
-    if opt_rust_ty.normalized_name == "& str" {
+    if conv_map[opt_rust_ty].normalized_name == "& str" {
         trace!("Catch return of Option<&str>");
-        let cpp_info_ty = map_ordinal_result_type(conv_map, &opt_rust_ty, arg_ty_span)?;
+        let cpp_info_ty = map_ordinal_result_type(conv_map, opt_rust_ty, arg_ty_span)?;
         let cpp_typename = cpp_info_ty
             .cpp_converter
             .expect("C++ converter from C struct")
@@ -1157,6 +1588,14 @@ fn handle_option_type_in_return(
         return Ok(Some(cpp_info_opt));
     }
 
+    cpp_info_opt
+        .provides_by_module
+        .extend(cpp_info_ty.provides_by_module);
+    if let Some(foreign_enum) = conv_map.is_this_exported_enum(opt_rust_ty) {
+        cpp_info_opt
+            .provides_by_module
+            .push(format!("\"{}\"", cpp_header_name_for_enum(foreign_enum)).into());
+    }
     cpp_info_opt.cpp_converter = Some(CppConverter {
         typename: typename.into(),
         converter,
@@ -1167,7 +1606,7 @@ fn handle_option_type_in_return(
 fn handle_result_with_primitive_type_as_ok_ty(
     conv_map: &mut TypeMap,
     cpp_cfg: &CppConfig,
-    arg_ty: &RustType,
+    arg_ty: RustTypeIdx,
     ok_ty: &Type,
     err_ty: &Type,
     arg_ty_span: SourceIdSpan,
@@ -1177,32 +1616,44 @@ fn handle_result_with_primitive_type_as_ok_ty(
     let err_rust_ty = conv_map.find_or_alloc_rust_type(err_ty, arg_ty_span.0);
 
     let c_ok_type_name: SmolStr = if empty_ok_ty {
-        "void *".into()
+        match cpp_cfg.cpp_result {
+            CppResult::Variant => "void *".into(),
+            CppResult::Std23Expected | CppResult::RustExpected => "void".into(),
+        }
     } else {
         let ok_rust_ty = conv_map.find_or_alloc_rust_type(ok_ty, arg_ty_span.0);
-        map_ordinal_result_type(conv_map, &ok_rust_ty, arg_ty_span)?
+        map_ordinal_result_type(conv_map, ok_rust_ty, arg_ty_span)?
             .base
             .name
     };
 
-    if err_rust_ty.normalized_name == "String" {
-        let typename = match cpp_cfg.cpp_variant {
-            CppVariant::Std17 => format!("std::variant<{}, RustString>", c_ok_type_name),
-            CppVariant::Boost => format!("boost::variant<{}, RustString>", c_ok_type_name),
-        };
+    let ok_value: String = if c_ok_type_name == "void" {
+        String::new()
+    } else {
+        format!("{}.data.ok", FROM_VAR_TEMPLATE)
+    };
+
+    if conv_map[err_rust_ty].normalized_name == "String" {
+        let (typename, var_inc) = cpp_result_type(cpp_cfg, &c_ok_type_name, "RustString");
         let converter = format!(
             "{var}.is_ok != 0 ?
- {VarType}{{{var}.data.ok}} :
- {VarType}{{RustString{{{var}.data.err}}}}",
+ {VarType}{{{Ok}}} :
+ {VarType}{{{Err}}}",
             VarType = typename,
-            var = FROM_VAR_TEMPLATE
+            var = FROM_VAR_TEMPLATE,
+            Ok = ok_value,
+            Err = cpp_result_err(
+                cpp_cfg,
+                "RustString",
+                &format!("RustString{{{}.data.err}}", FROM_VAR_TEMPLATE)
+            ),
         );
         let foreign_info = map_ordinal_result_type(conv_map, arg_ty, arg_ty_span)?;
         if empty_ok_ty {
             assert_eq!(foreign_info.base.name, "struct CResultObjectString");
         }
         Ok(Some(CppForeignTypeInfo {
-            provides_by_module: vec!["\"rust_result.h\"".into(), "\"rust_str.h\"".into()],
+            provides_by_module: vec!["\"rust_result.h\"".into(), "\"rust_str.h\"".into(), var_inc],
             base: foreign_info.base,
             cpp_converter: Some(CppConverter {
                 typename: typename.into(),
@@ -1210,21 +1661,26 @@ fn handle_result_with_primitive_type_as_ok_ty(
             }),
         }))
     } else if let Some(err_class) =
-        conv_map.find_foreigner_class_with_such_self_type(&err_rust_ty, false)
+        conv_map.find_foreigner_class_with_such_self_type(err_rust_ty, false)
     {
         let c_err_class = c_class_type(err_class);
-        let typename = match cpp_cfg.cpp_variant {
-            CppVariant::Std17 => format!("std::variant<{}, {}>", c_ok_type_name, err_class.name),
-            CppVariant::Boost => format!("boost::variant<{}, {}>", c_ok_type_name, err_class.name),
-        };
+        let err_name = err_class.name.to_string();
+        let (typename, var_inc) = cpp_result_type(cpp_cfg, &c_ok_type_name, &err_name);
         let converter = format!(
             "{var}.is_ok != 0 ?
- {VarType} {{ {var}.data.ok }} :
- {VarType} {{ {ErrType}(static_cast<{C_ErrType} *>({var}.data.err)) }}",
+ {VarType} {{ {Ok} }} :
+ {VarType} {{ {Err} }}",
             VarType = typename,
             var = FROM_VAR_TEMPLATE,
-            ErrType = err_class.name,
-            C_ErrType = c_err_class,
+            Ok = ok_value,
+            Err = cpp_result_err(
+                cpp_cfg,
+                &err_name,
+                &format!(
+                    "{}(static_cast<{} *>({}.data.err))",
+                    err_name, c_err_class, FROM_VAR_TEMPLATE
+                )
+            ),
         );
         let err_cpp_header: SmolStr = format!("\"{}\"", cpp_header_name(err_class)).into();
         let foreign_info = map_ordinal_result_type(conv_map, arg_ty, arg_ty_span)?;
@@ -1233,7 +1689,7 @@ fn handle_result_with_primitive_type_as_ok_ty(
         }
         Ok(Some(CppForeignTypeInfo {
             base: foreign_info.base,
-            provides_by_module: vec!["\"rust_result.h\"".into(), err_cpp_header],
+            provides_by_module: vec!["\"rust_result.h\"".into(), err_cpp_header, var_inc],
             cpp_converter: Some(CppConverter {
                 typename: typename.into(),
                 converter,
@@ -1244,6 +1700,39 @@ fn handle_result_with_primitive_type_as_ok_ty(
     }
 }
 
+/// C++ type for `Result<ok_ty, err_ty>` and header that provides it
+fn cpp_result_type(cpp_cfg: &CppConfig, ok_ty: &str, err_ty: &str) -> (String, SmolStr) {
+    match cpp_cfg.cpp_result {
+        CppResult::Variant => match cpp_cfg.cpp_variant {
+            CppVariant::Std17 => (
+                format!("std::variant<{}, {}>", ok_ty, err_ty),
+                "<variant>".into(),
+            ),
+            CppVariant::Boost => (
+                format!("boost::variant<{}, {}>", ok_ty, err_ty),
+                "<boost/variant.hpp>".into(),
+            ),
+        },
+        CppResult::Std23Expected => (
+            format!("std::expected<{}, {}>", ok_ty, err_ty),
+            "<expected>".into(),
+        ),
+        CppResult::RustExpected => (
+            format!("RustExpected<{}, {}>", ok_ty, err_ty),
+            "\"rust_expected.h\"".into(),
+        ),
+    }
+}
+
+/// Mark C++ expression as error value of `Result`
+fn cpp_result_err(cpp_cfg: &CppConfig, err_ty: &str, expr: &str) -> String {
+    match cpp_cfg.cpp_result {
+        CppResult::Variant => expr.to_string(),
+        CppResult::Std23Expected => format!("std::unexpected<{}>{{{}}}", err_ty, expr),
+        CppResult::RustExpected => format!("RustUnexpected<{}>{{{}}}", err_ty, expr),
+    }
+}
+
 pub(in crate::cpp) fn calc_this_type_for_method(
     _: &TypeMap,
     class: &ForeignerClassInfo,
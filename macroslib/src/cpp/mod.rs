@@ -1,10 +1,17 @@
+mod awaitable;
+mod cmake_package;
 mod cpp_code;
+mod cpp_standard;
+mod dll_api;
 mod fclass;
 mod fenum;
 mod finterface;
+mod linker_exports;
+mod log_bridge;
 mod map_type;
+mod panics;
 
-use std::{fmt, io::Write, mem};
+use std::{fmt, io::Write, mem, path::Path};
 
 use log::{debug, trace};
 use petgraph::Direction;
@@ -15,15 +22,19 @@ use strum::IntoEnumIterator;
 use syn::{parse_quote, spanned::Spanned, Type};
 
 use crate::{
+    api_manifest::{ApiManifest, ForeignFnTypes},
+    catch_panics,
     cpp::map_type::map_type,
-    error::{DiagnosticError, Result},
+    doc_links,
+    error::{collect_error, DiagnosticError, Result},
     file_cache::FileWriteCache,
+    profiler::{self, Stage},
     source_registry::SourceId,
     typemap::{
         ast::{parse_ty_with_given_span, parse_ty_with_given_span_checked, TypeName},
         ty::{
             FTypeConvCode, ForeignConversationIntermediate, ForeignConversationRule, ForeignType,
-            ForeignTypeS, RustType,
+            ForeignTypeS,
         },
         utils::{
             boxed_type, unpack_from_heap_pointer, validate_cfg_options, ForeignMethodSignature,
@@ -32,10 +43,11 @@ use crate::{
         CType, CTypes, ForeignTypeInfo, RustTypeIdx, FROM_VAR_TEMPLATE, TO_VAR_TEMPLATE,
     },
     types::{
-        ForeignEnumInfo, ForeignInterface, ForeignerClassInfo, ForeignerMethod, ItemToExpand,
-        MethodAccess, MethodVariant, SelfTypeDesc,
+        ExpandedItem, ForeignEnumInfo, ForeignInterface, ForeignerClassInfo, ForeignerMethod,
+        ItemToExpand, MethodAccess, MethodVariant, SelfTypeDesc,
     },
-    CppConfig, CppOptional, CppStrView, CppVariant, LanguageGenerator, SourceCode, TypeMap,
+    CppConfig, CppOptional, CppStandard, CppStrView, CppVariant, LanguageGenerator, PanicAction,
+    SourceCode, TypeMap,
 };
 
 #[derive(Debug)]
@@ -55,8 +67,8 @@ impl ForeignTypeInfoT for CppForeignTypeInfo {
     fn name(&self) -> &str {
         self.base.name.as_str()
     }
-    fn correspoding_rust_type(&self) -> &RustType {
-        &self.base.correspoding_rust_type
+    fn correspoding_rust_type(&self) -> RustTypeIdx {
+        self.base.correspoding_rust_type
     }
 }
 
@@ -107,7 +119,7 @@ impl CppForeignTypeInfo {
         Ok(CppForeignTypeInfo {
             base: ForeignTypeInfo {
                 name: base_ft_name,
-                correspoding_rust_type: tmap[base_rt].clone(),
+                correspoding_rust_type: base_rt,
             },
             provides_by_module,
             cpp_converter,
@@ -124,6 +136,8 @@ impl AsRef<ForeignTypeInfo> for CppForeignTypeInfo {
 struct CppForeignMethodSignature {
     output: CppForeignTypeInfo,
     input: Vec<CppForeignTypeInfo>,
+    /// Value passed to completion callback of `#[awaitable]` method
+    completion: Option<CppForeignTypeInfo>,
 }
 
 impl From<ForeignTypeInfo> for CppForeignTypeInfo {
@@ -157,9 +171,55 @@ struct MethodContext<'a> {
     decl_func_args: &'a str,
     args_names: &'a str,
     real_output_typename: &'a str,
+    /// Creation of completion callback for `#[awaitable]` method
+    completion_code: &'a str,
+    /// Wrap body into `swig_catch_panic`
+    catch_panics: bool,
+    /// Enter span of `tracing` crate in body
+    tracing_spans: bool,
 }
 
 impl CppConfig {
+    /// Pass `&str` and `String` arguments as `std::string_view`,
+    /// only if C++17 or later is requested explicitly
+    pub(in crate::cpp) fn string_view_args(&self) -> bool {
+        self.cpp_standard
+            .map(|x| x >= CppStandard::Cpp17)
+            .unwrap_or(false)
+    }
+
+    /// Use C++17 nested namespace definition, if C++17 is not avoided via boost
+    fn nested_namespace_syntax(&self) -> bool {
+        self.cpp_standard_at_least(CppStandard::Cpp17)
+            && self.cpp_optional == CppOptional::Std17
+            && self.cpp_variant == CppVariant::Std17
+            && self.cpp_str_view == CppStrView::Std17
+    }
+
+    /// Name of exported C function with `symbol_prefix` applied
+    pub(in crate::cpp) fn c_symbol(&self, name: &str) -> String {
+        match self.symbol_prefix {
+            Some(ref prefix) => format!("{}{}", prefix, name),
+            None => name.to_string(),
+        }
+    }
+
+    /// `<NAME>_API ` for declaration of exported C function, if `dll_api` is used
+    pub(in crate::cpp) fn c_api(&self) -> String {
+        match self.dll_api {
+            Some(ref name) => format!("{}_API ", name),
+            None => String::new(),
+        }
+    }
+
+    /// Include of header with `<NAME>_API` macro, if `dll_api` is used
+    pub(in crate::cpp) fn c_api_include(&self) -> String {
+        match self.dll_api {
+            Some(ref name) => format!("#include \"{}\"\n", dll_api::dll_api_header_name(name)),
+            None => String::new(),
+        }
+    }
+
     fn register_class(&self, conv_map: &mut TypeMap, class: &ForeignerClassInfo) -> Result<()> {
         class
             .validate_class()
@@ -173,7 +233,7 @@ impl CppConfig {
                 class.src_id,
             );
 
-            register_typemap_for_self_type(conv_map, class, this_type, self_desc)?;
+            register_typemap_for_self_type(conv_map, class, this_type, self_desc, self.shared_ptr)?;
         }
         conv_map.find_or_alloc_rust_type(&class.self_type_as_ty(), class.src_id);
         Ok(())
@@ -184,6 +244,7 @@ impl CppConfig {
         conv_map: &mut TypeMap,
         target_pointer_width: usize,
         class: &ForeignerClassInfo,
+        manifest: &mut ApiManifest,
     ) -> Result<Vec<TokenStream>> {
         debug!(
             "generate: begin for {}, this_type_for_method {:?}",
@@ -211,6 +272,7 @@ May be you need to use `private constructor = empty;` syntax?",
         }
 
         let mut m_sigs = fclass::find_suitable_foreign_types_for_methods(conv_map, class, self)?;
+        manifest.add_class(class, &manifest_fn_types(&m_sigs));
         let req_includes = cpp_code::cpp_list_required_includes(&mut m_sigs);
         let mut code_items = fclass::generate(
             conv_map,
@@ -229,6 +291,7 @@ May be you need to use `private constructor = empty;` syntax?",
         conv_map: &mut TypeMap,
         pointer_target_width: usize,
         enum_info: &ForeignEnumInfo,
+        manifest: &mut ApiManifest,
     ) -> Result<Vec<TokenStream>> {
         if (enum_info.items.len() as u64) >= u64::from(u32::max_value()) {
             return Err(DiagnosticError::new(
@@ -238,6 +301,17 @@ May be you need to use `private constructor = empty;` syntax?",
             ));
         }
 
+        if enum_info.is_data_enum() {
+            let fields = fenum::data_enum_fields(enum_info)?;
+            manifest.add_enum(enum_info);
+            fenum::generate_code_for_data_enum(self, enum_info, &fields)
+                .map_err(|err| DiagnosticError::new(enum_info.src_id, enum_info.span(), err))?;
+            register_typemap_for_data_enum(conv_map, enum_info)?;
+            return Ok(fenum::generate_rust_code_for_data_enum(
+                self, enum_info, &fields,
+            ));
+        }
+
         trace!("enum_ti: {}", enum_info.name);
         let enum_name = &enum_info.name;
         let enum_ti: Type = parse_ty_with_given_span(&enum_name.to_string(), enum_info.name.span())
@@ -248,6 +322,7 @@ May be you need to use `private constructor = empty;` syntax?",
             enum_info.src_id,
         );
 
+        manifest.add_enum(enum_info);
         fenum::generate_code_for_enum(&self.output_dir, enum_info)
             .map_err(|err| DiagnosticError::new(enum_info.src_id, enum_info.span(), err))?;
         let code = fenum::generate_rust_code_for_enum(conv_map, pointer_target_width, enum_info)?;
@@ -259,18 +334,14 @@ May be you need to use `private constructor = empty;` syntax?",
         conv_map: &mut TypeMap,
         pointer_target_width: usize,
         interface: &ForeignInterface,
+        manifest: &mut ApiManifest,
     ) -> Result<Vec<TokenStream>> {
         let mut f_methods =
             finterface::find_suitable_ftypes_for_interace_methods(conv_map, interface, self)?;
+        manifest.add_interface(interface, &manifest_fn_types(&f_methods));
         let req_includes = cpp_code::cpp_list_required_includes(&mut f_methods);
-        finterface::generate_for_interface(
-            &self.output_dir,
-            &self.namespace_name,
-            interface,
-            &req_includes,
-            &f_methods,
-        )
-        .map_err(|err| DiagnosticError::new(interface.src_id, interface.span(), err))?;
+        finterface::generate_for_interface(self, conv_map, interface, &req_includes, &f_methods)
+            .map_err(|err| DiagnosticError::new(interface.src_id, interface.span(), err))?;
 
         let items = finterface::rust_code_generate_interface(
             conv_map,
@@ -287,14 +358,24 @@ May be you need to use `private constructor = empty;` syntax?",
 
         let rust_ty = conv_map.find_or_alloc_rust_type_no_src_id(&rust_ty);
 
-        conv_map.add_foreign(
+        let c_struct_ftype = conv_map.add_foreign(
             rust_ty,
             TypeName::new(c_struct_pointer, interface.src_id_span()),
         )?;
+        conv_map
+            .add_foreign_type_module(c_struct_ftype, format!("\"c_{}.h\"", interface.name).into());
 
         Ok(items)
     }
 
+    fn generate_umbrella_header(&self, name: &str, headers: &[String]) -> Result<()> {
+        let mut cnt = "// Automaticaly generated by rust_swig\n#pragma once\n\n".to_string();
+        for header in headers {
+            cnt.push_str(&format!("#include \"{}\"\n", header));
+        }
+        write_generated_file(&self.output_dir.join(name), &cnt)
+    }
+
     fn init(
         &self,
         conv_map: &mut TypeMap,
@@ -310,9 +391,13 @@ May be you need to use `private constructor = empty;` syntax?",
             let mut src_file = FileWriteCache::new(&src_path);
             src_file
                 .write_all(
-                    cu.code
-                        .replace("RUST_SWIG_USER_NAMESPACE", &self.namespace_name)
-                        .as_bytes(),
+                    cpp_code::expand_namespace_placeholder(
+                        &cu.code,
+                        "RUST_SWIG_USER_NAMESPACE",
+                        &self.namespace_name,
+                        self.nested_namespace_syntax(),
+                    )
+                    .as_bytes(),
                 )
                 .map_err(|err| {
                     map_any_err_to_our_err(format!(
@@ -324,6 +409,7 @@ May be you need to use `private constructor = empty;` syntax?",
             src_file.update_file_if_necessary().map_err(|err| {
                 map_any_err_to_our_err(format!("update of {} failed: {}", src_path.display(), err))
             })?;
+            register_c_structs_module(conv_map, &cu.id_of_code, &cu.code);
         }
 
         let c_module_path = |module_name: &str| self.output_dir.join(module_name);
@@ -340,12 +426,13 @@ May be you need to use `private constructor = empty;` syntax?",
 
 //for (u)intX_t types
 #include <stdint.h>
-
+{api_include}
 #ifdef __cplusplus
 static_assert(sizeof(uintptr_t) == sizeof(uint8_t) * {sizeof_usize},
    "our conversation usize <-> uintptr_t is wrong");
 #endif
             "##,
+                        api_include = self.c_api_include(),
                         sizeof_usize = target_pointer_width / 8,
                     )
                     .expect("write to memory failed, no free mem?");
@@ -359,6 +446,13 @@ static_assert(sizeof(uintptr_t) == sizeof(uint8_t) * {sizeof_usize},
             opts.insert(self.cpp_variant.into());
             opts.insert(self.cpp_optional.into());
             opts.insert(self.cpp_str_view.into());
+            if let Some(cpp_standard) = self.cpp_standard {
+                opts.extend(
+                    CppStandard::iter()
+                        .filter(|x| *x <= cpp_standard)
+                        .map(|x| -> &'static str { x.into() }),
+                );
+            }
             opts
         };
 
@@ -367,6 +461,7 @@ static_assert(sizeof(uintptr_t) == sizeof(uint8_t) * {sizeof_usize},
             opts.extend(CppOptional::iter().map(|x| -> &'static str { x.into() }));
             opts.extend(CppVariant::iter().map(|x| -> &'static str { x.into() }));
             opts.extend(CppStrView::iter().map(|x| -> &'static str { x.into() }));
+            opts.extend(CppStandard::iter().map(|x| -> &'static str { x.into() }));
             opts
         };
 
@@ -416,10 +511,13 @@ extern "C" {
                 if use_fcode {
                     c_header_f
                         .write_all(
-                            fcode
-                                .code
-                                .replace("$RUST_SWIG_USER_NAMESPACE", &self.namespace_name)
-                                .as_bytes(),
+                            cpp_code::expand_namespace_placeholder(
+                                &fcode.code,
+                                "$RUST_SWIG_USER_NAMESPACE",
+                                &self.namespace_name,
+                                self.nested_namespace_syntax(),
+                            )
+                            .as_bytes(),
                         )
                         .map_err(map_any_err_to_our_err)?;
                 }
@@ -459,11 +557,22 @@ extern "C" {
             }
 
             let ftype_left_to_right = &mut rule.ftype_left_to_right;
+            let has_ftype_output = !ftype_left_to_right.is_empty();
             configure_ftype_rule!(ftype_left_to_right, =>);
 
             let ftype_right_to_left = &mut rule.ftype_right_to_left;
+            let has_ftype_input = !ftype_right_to_left.is_empty();
             configure_ftype_rule!(ftype_right_to_left, <=);
 
+            // all f_type rules disabled by options, so r_type rule
+            // would expose raw C type
+            if has_ftype_output && rule.ftype_left_to_right.is_empty() {
+                rule.rtype_left_to_right = None;
+            }
+            if has_ftype_input && rule.ftype_right_to_left.is_empty() {
+                rule.rtype_right_to_left = None;
+            }
+
             conv_map.merge_conv_rule(rule.src_id, rule)?;
         }
 
@@ -488,36 +597,149 @@ impl LanguageGenerator for CppConfig {
         conv_map: &mut TypeMap,
         pointer_target_width: usize,
         code: &[SourceCode],
-        items: Vec<ItemToExpand>,
-    ) -> Result<Vec<TokenStream>> {
-        let mut ret = Vec::with_capacity(items.len());
-        ret.append(&mut self.init(conv_map, pointer_target_width, code)?);
+        mut items: Vec<ItemToExpand>,
+        manifest: &mut ApiManifest,
+    ) -> Result<Vec<ExpandedItem>> {
+        cpp_standard::check_cpp_standard(self, &items)?;
+        cpp_code::check_name_collisions(&items)?;
+        doc_links::rewrite_doc_links(&mut items, cpp_code::doxygen_link);
+        let mut ret = Vec::with_capacity(items.len() + 1);
+        ret.push(ExpandedItem {
+            descr: "common helpers".into(),
+            code: self.init(conv_map, pointer_target_width, code)?,
+        });
+        // classes are shared with `TypeMap`, so after this point they are not modified
+        for item in &items {
+            if let ItemToExpand::Class(ref fclass) = item {
+                conv_map.register_foreigner_class(fclass);
+            }
+        }
         for item in &items {
             if let ItemToExpand::Class(ref fclass) = item {
                 self.register_class(conv_map, fclass)?;
             }
         }
-        for item in items {
+        let panic_action = catch_panics::panic_action(
+            items.iter().filter_map(|item| match item {
+                ItemToExpand::Class(ref fclass) => Some(&**fclass),
+                _ => None,
+            }),
+            self.catch_panics.as_ref(),
+            panics::DEFAULT_PANIC_EXCEPTION,
+        );
+        let mut headers = Vec::with_capacity(items.len());
+        let mut sources = Vec::new();
+        for item in &items {
             match item {
+                ItemToExpand::Class(fclass) if self.c_abi_only => {
+                    headers.push(cpp_code::c_header_name(fclass))
+                }
                 ItemToExpand::Class(fclass) => {
-                    ret.append(&mut self.generate(conv_map, pointer_target_width, &fclass)?)
+                    headers.push(cpp_code::cpp_header_name(fclass));
+                    if self.pimpl {
+                        sources.push(cpp_code::cpp_source_name(fclass));
+                    } else if self.separate_impl_headers {
+                        headers.push(format!("{}_impl.hpp", fclass.name));
+                    }
                 }
                 ItemToExpand::Enum(fenum) => {
-                    ret.append(&mut self.generate_enum(conv_map, pointer_target_width, &fenum)?)
+                    if fenum.is_data_enum() && !self.c_abi_only {
+                        headers.push(cpp_code::cpp_header_name_for_data_enum(fenum));
+                        if self.pimpl {
+                            sources.push(cpp_code::cpp_source_name_for_data_enum(fenum));
+                        }
+                    } else {
+                        headers.push(cpp_code::cpp_header_name_for_enum(fenum))
+                    }
                 }
-                ItemToExpand::Interface(finterface) => ret.append(&mut self.generate_interface(
-                    conv_map,
-                    pointer_target_width,
-                    &finterface,
-                )?),
+                ItemToExpand::Interface(finterface) => headers.push(if self.c_abi_only {
+                    format!("c_{}.h", finterface.name)
+                } else {
+                    format!("{}.hpp", finterface.name)
+                }),
+            }
+        }
+        // errors of all items are reported together
+        let mut items_err = None;
+        let fallible_conversations = conv_map.fallible_conversations();
+        for item in items {
+            let descr = item.descr();
+            let _timer = profiler::timer(Stage::ItemExpansion, || descr.clone());
+            let code = match item {
+                ItemToExpand::Class(fclass) => {
+                    conv_map.push_class_scope(&fclass)?;
+                    let code = self.generate(conv_map, pointer_target_width, &fclass, manifest);
+                    conv_map.pop_class_scope();
+                    code
+                }
+                ItemToExpand::Enum(fenum) => {
+                    self.generate_enum(conv_map, pointer_target_width, &fenum, manifest)
+                }
+                ItemToExpand::Interface(finterface) => {
+                    self.generate_interface(conv_map, pointer_target_width, &finterface, manifest)
+                }
+            };
+            match code {
+                Ok(code) => ret.push(ExpandedItem { descr, code }),
+                Err(err) => collect_error(&mut items_err, err),
+            }
+        }
+        if let Some(err) = items_err {
+            return Err(err);
+        }
+        let fallible_rules = conv_map.fallible_conversations() != fallible_conversations;
+        if panic_action.is_some() || fallible_rules {
+            if fallible_rules || panic_action != Some(PanicAction::Abort) {
+                headers.push(panics::PANIC_HEADER.into());
             }
+            ret.push(ExpandedItem {
+                descr: "panic helpers".into(),
+                code: panics::generate_panic_helpers(self, panic_action.as_ref(), fallible_rules)?,
+            });
+        }
+        if self.log_bridge {
+            headers.push(log_bridge::LOG_BRIDGE_HEADER.into());
+            ret.push(ExpandedItem {
+                descr: "log bridge".into(),
+                code: log_bridge::generate_log_bridge(self)?,
+            });
+        }
+        if let Some(umbrella_header) = self.umbrella_header.as_ref() {
+            self.generate_umbrella_header(umbrella_header, &headers)?;
+        }
+        if let Some((package, rust_library)) = self.cmake_package.as_ref() {
+            cmake_package::generate_cmake_package(self, package, rust_library, &sources)?;
+        }
+        if let Some(name) = self.linker_exports.as_ref() {
+            linker_exports::generate_linker_exports(self, name, &ret)?;
+        }
+        if let Some(name) = self.dll_api.as_ref() {
+            dll_api::generate_dll_api_header(self, name)?;
         }
         Ok(ret)
     }
 }
 
-fn c_func_name(class: &ForeignerClassInfo, method: &ForeignerMethod) -> String {
-    format!(
+/// C++ types visible to user, not C types used to call Rust
+fn manifest_fn_types(f_methods: &[CppForeignMethodSignature]) -> Vec<ForeignFnTypes> {
+    let cpp_name = |fti: &CppForeignTypeInfo| match fti.cpp_converter {
+        Some(ref conv) => conv.typename.to_string(),
+        None => fti.base.name.to_string(),
+    };
+    f_methods
+        .iter()
+        .map(|f_method| ForeignFnTypes {
+            input: f_method.input.iter().map(cpp_name).collect(),
+            output: match f_method.completion {
+                Some(ref completion) => awaitable::cpp_awaitable_type(completion),
+                None => cpp_name(&f_method.output),
+            },
+        })
+        .collect()
+}
+
+fn c_func_name(cfg: &CppConfig, class: &ForeignerClassInfo, method: &ForeignerMethod) -> String {
+    cfg.c_symbol(&format!(
         "{access}{class_name}_{func}",
         access = match method.access {
             MethodAccess::Private => "private_",
@@ -526,10 +748,11 @@ fn c_func_name(class: &ForeignerClassInfo, method: &ForeignerMethod) -> String {
         },
         class_name = class.name,
         func = method.short_name(),
-    )
+    ))
 }
 
 fn rust_generate_args_with_types(
+    conv_map: &TypeMap,
     f_method: &CppForeignMethodSignature,
 ) -> std::result::Result<String, String> {
     use std::fmt::Write;
@@ -540,7 +763,7 @@ fn rust_generate_args_with_types(
             &mut buf,
             "a_{}: {}, ",
             i,
-            f_type_info.as_ref().correspoding_rust_type.typename(),
+            conv_map[f_type_info.as_ref().correspoding_rust_type].typename(),
         )
         .map_err(fmt_write_err_map)?;
     }
@@ -559,6 +782,103 @@ fn map_any_err_to_our_err<E: fmt::Display>(err: E) -> DiagnosticError {
     DiagnosticError::new_without_src_info(err)
 }
 
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Names of `extern "C"` functions defined in `cpp-include.rs`
+fn builtin_c_symbols() -> impl Iterator<Item = &'static str> {
+    const EXTERN_FN: &str = "extern \"C\" fn ";
+    let builtin = include_str!("cpp-include.rs");
+    builtin
+        .match_indices(EXTERN_FN)
+        .map(move |(pos, _)| {
+            let name = &builtin[pos + EXTERN_FN.len()..];
+            &name[..name.find(|c| !is_ident_char(c)).unwrap_or(name.len())]
+        })
+        .filter(|name| !name.is_empty())
+}
+
+/// Add `symbol_prefix` to names of `extern "C"` functions from `cpp-include.rs`,
+/// `code` is `cpp-include.rs` itself or one of helper headers
+pub(crate) fn prefix_builtin_c_symbols(cfg: &CppConfig, code: &str) -> String {
+    let prefix = match cfg.symbol_prefix {
+        Some(ref prefix) => prefix,
+        None => return code.to_string(),
+    };
+    let symbols: FxHashSet<&str> = builtin_c_symbols().collect();
+
+    let mut ret = String::with_capacity(code.len());
+    let mut rest = code;
+    while let Some(start) = rest.find(is_ident_char) {
+        ret.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest.find(|c| !is_ident_char(c)).unwrap_or(rest.len());
+        let ident = &rest[..end];
+        if symbols.contains(ident) {
+            ret.push_str(prefix);
+        }
+        ret.push_str(ident);
+        rest = &rest[end..];
+    }
+    ret.push_str(rest);
+    ret
+}
+
+/// Add `<NAME>_API` to C declarations of functions from `cpp-include.rs`,
+/// `code` is `cpp-include.rs` itself or one of helper headers,
+/// must be called after `prefix_builtin_c_symbols`
+pub(crate) fn add_dll_api_to_builtin_decls(cfg: &CppConfig, code: &str) -> String {
+    if cfg.dll_api.is_none() {
+        return code.to_string();
+    }
+    let symbols: FxHashSet<String> = builtin_c_symbols().map(|x| cfg.c_symbol(x)).collect();
+    let is_decl = |line: &str| {
+        if line.starts_with(char::is_whitespace) || !line.trim_end().ends_with(");") {
+            return false;
+        }
+        let name = match line.find('(') {
+            Some(pos) => &line[..pos],
+            None => return false,
+        };
+        let name = &name[name
+            .rfind(|c| !is_ident_char(c))
+            .map(|x| x + 1)
+            .unwrap_or(0)..];
+        symbols.contains(name)
+    };
+
+    let api = cfg.c_api();
+    let mut has_decls = false;
+    let mut ret = String::with_capacity(code.len());
+    for (i, line) in code.split('\n').enumerate() {
+        if i != 0 {
+            ret.push('\n');
+        }
+        if is_decl(line) {
+            has_decls = true;
+            ret.push_str(&api);
+        }
+        ret.push_str(line);
+    }
+    // helper header, declarations in `cpp-include.rs` go to headers
+    // that include it anyway
+    const PRAGMA_ONCE: &str = "#pragma once\n";
+    if has_decls && ret.starts_with(PRAGMA_ONCE) {
+        ret.insert_str(PRAGMA_ONCE.len(), &format!("\n{}", cfg.c_api_include()));
+    }
+    ret
+}
+
+fn write_generated_file(path: &Path, cnt: &str) -> Result<()> {
+    let mut file = FileWriteCache::new(path);
+    file.write_all(cnt.as_bytes())
+        .and_then(|_| file.update_file_if_necessary())
+        .map_err(|err| {
+            map_any_err_to_our_err(format!("write to {} failed: {}", path.display(), err))
+        })
+}
+
 fn n_arguments_list(n: usize) -> String {
     (0..n)
         .map(|v| format!("a_{}", v))
@@ -572,14 +892,15 @@ fn n_arguments_list(n: usize) -> String {
 }
 
 fn convert_rt_to_ft(tmap: &mut TypeMap, rt: RustTypeIdx) -> Result<ForeignType> {
-    let rtype = tmap[rt].clone();
+    let src_id_span = tmap[rt].src_id_span();
     tmap.map_through_conversation_to_foreign(
-        &rtype,
+        rt,
         Direction::Outgoing,
-        rtype.src_id_span(),
+        src_id_span,
         self::map_type::calc_this_type_for_method,
     )
     .ok_or_else(|| {
+        let rtype = &tmap[rt];
         DiagnosticError::new(
             rtype.src_id,
             rtype.ty.span(),
@@ -603,76 +924,198 @@ fn register_c_type(tmap: &mut TypeMap, c_types: &CTypes) -> Result<()> {
             .map_err(|err| DiagnosticError::from_syn_err(c_types.src_id, err))?;
         let rust_ty = tmap.find_or_alloc_rust_type(&rust_ty, c_types.src_id);
         let f_type = format!("struct {}", struct_name);
-        debug!("init::c_types add {} / {}", rust_ty, f_type);
-        tmap.add_foreign(
+        debug!("init::c_types add {} / {}", tmap[rust_ty], f_type);
+        let rule = ForeignConversationRule {
             rust_ty,
-            TypeName::new(f_type, (c_types.src_id, f_ident.span())),
-        )?;
+            intermediate: None,
+        };
+        tmap.alloc_foreign_type(ForeignTypeS {
+            name: TypeName::new(f_type, (c_types.src_id, f_ident.span())),
+            provides_by_module: vec![format!("\"{}\"", c_types.header_name).into()],
+            into_from_rust: Some(rule.clone()),
+            from_into_rust: Some(rule),
+            name_prefix: None,
+        })?;
     }
     Ok(())
 }
 
+/// C structs defined in helper header, like `struct CRustVecU8 {`,
+/// are provided by this header
+fn register_c_structs_module(tmap: &mut TypeMap, header: &str, code: &str) {
+    for line in code.lines() {
+        let line = line.trim();
+        if !line.starts_with("struct ") || !line.ends_with('{') {
+            continue;
+        }
+        let name = line[..line.len() - 1].trim();
+        if let Some(ftype) = tmap.find_foreign_type_by_name(name) {
+            tmap.add_foreign_type_module(ftype, format!("\"{}\"", header).into());
+        }
+    }
+}
+
 fn register_typemap_for_self_type(
     conv_map: &mut TypeMap,
     class: &ForeignerClassInfo,
-    this_type: RustType,
+    this_type: RustTypeIdx,
     self_desc: &SelfTypeDesc,
+    shared_ptr: bool,
 ) -> Result<()> {
+    let this_type_span = conv_map[this_type].ty.span();
+    let this_type_name = conv_map[this_type].normalized_name.clone();
     let void_ptr_ty =
-        parse_ty_with_given_span_checked("*mut ::std::os::raw::c_void", this_type.ty.span());
+        parse_ty_with_given_span_checked("*mut ::std::os::raw::c_void", this_type_span);
     let void_ptr_rust_ty = conv_map.find_or_alloc_rust_type_with_suffix(
         &void_ptr_ty,
-        &this_type.normalized_name,
+        &this_type_name,
         SourceId::none(),
     );
 
     let const_void_ptr_ty =
-        parse_ty_with_given_span_checked("*const ::std::os::raw::c_void", this_type.ty.span());
+        parse_ty_with_given_span_checked("*const ::std::os::raw::c_void", this_type_span);
     let const_void_ptr_rust_ty = conv_map.find_or_alloc_rust_type_with_suffix(
         &const_void_ptr_ty,
-        &this_type.normalized_name,
+        &this_type_name,
         SourceId::none(),
     );
 
-    let this_type_inner = boxed_type(conv_map, &this_type);
+    let this_type_inner = boxed_type(conv_map, this_type);
+    let this_type_inner_span = conv_map[this_type_inner].ty.span();
 
-    let code = format!("& {}", this_type_inner);
-    let gen_ty = parse_ty_with_given_span_checked(&code, this_type_inner.ty.span());
+    let code = format!("& {}", conv_map[this_type_inner]);
+    let gen_ty = parse_ty_with_given_span_checked(&code, this_type_inner_span);
     let this_type_ref = conv_map.find_or_alloc_rust_type(&gen_ty, class.src_id);
 
-    let code = format!("&mut {}", this_type_inner);
-    let gen_ty = parse_ty_with_given_span_checked(&code, this_type_inner.ty.span());
+    let code = format!("&mut {}", conv_map[this_type_inner]);
+    let gen_ty = parse_ty_with_given_span_checked(&code, this_type_inner_span);
     let this_type_mut_ref = conv_map.find_or_alloc_rust_type(&gen_ty, class.src_id);
 
-    register_intermidiate_pointer_types(
-        conv_map,
-        class,
-        void_ptr_rust_ty.to_idx(),
-        const_void_ptr_rust_ty.to_idx(),
-    )?;
-    register_rust_ty_conversation_rules(
-        conv_map,
-        class,
-        this_type.clone(),
-        this_type_inner.to_idx(),
-        void_ptr_rust_ty.to_idx(),
-        const_void_ptr_rust_ty.to_idx(),
-        this_type_ref.to_idx(),
-        this_type_mut_ref.to_idx(),
-    )?;
+    register_intermidiate_pointer_types(conv_map, class, void_ptr_rust_ty, const_void_ptr_rust_ty)?;
+    let ptr_types = ClassPtrTypes {
+        void_ptr_rust_ty,
+        const_void_ptr_rust_ty,
+        this_type_ref,
+        this_type_mut_ref,
+    };
+    register_rust_ty_conversation_rules(conv_map, class, this_type, this_type_inner, ptr_types)?;
 
     let self_type = conv_map.find_or_alloc_rust_type(&self_desc.self_type, class.src_id);
 
-    register_main_foreign_types(
-        conv_map,
-        class,
-        this_type.to_idx(),
-        self_type.to_idx(),
-        void_ptr_rust_ty.to_idx(),
-        const_void_ptr_rust_ty.to_idx(),
-        this_type_ref.to_idx(),
-        this_type_mut_ref.to_idx(),
-    )?;
+    register_main_foreign_types(conv_map, class, this_type, self_type, ptr_types, shared_ptr)?;
+    Ok(())
+}
+
+/// Types via which object of class is passed through C API
+#[derive(Clone, Copy)]
+struct ClassPtrTypes {
+    void_ptr_rust_ty: RustTypeIdx,
+    const_void_ptr_rust_ty: RustTypeIdx,
+    this_type_ref: RustTypeIdx,
+    this_type_mut_ref: RustTypeIdx,
+}
+
+/// Enum with payload is passed as boxed Rust object,
+/// and converted to/from `std::variant` on C++ side
+fn register_typemap_for_data_enum(
+    conv_map: &mut TypeMap,
+    enum_info: &ForeignEnumInfo,
+) -> Result<()> {
+    let enum_ty = parse_ty_with_given_span(&enum_info.rust_enum_name(), enum_info.span())
+        .map_err(|err| DiagnosticError::from_syn_err(enum_info.src_id, err))?;
+    let enum_rty = conv_map.find_or_alloc_rust_type(&enum_ty, enum_info.src_id);
+    let void_ptr_ty =
+        parse_ty_with_given_span_checked("*mut ::std::os::raw::c_void", enum_info.span());
+    let enum_rty_name = conv_map[enum_rty].normalized_name.clone();
+    let void_ptr_rty = conv_map.find_or_alloc_rust_type_with_suffix(
+        &void_ptr_ty,
+        &enum_rty_name,
+        SourceId::none(),
+    );
+
+    conv_map.add_conversation_rule(
+        enum_rty,
+        void_ptr_rty,
+        format!(
+            "let {to_var}: {ptr_type} = Box::into_raw(Box::new({from_var})) as {ptr_type};",
+            to_var = TO_VAR_TEMPLATE,
+            ptr_type = conv_map[void_ptr_rty].typename(),
+            from_var = FROM_VAR_TEMPLATE,
+        )
+        .into(),
+    );
+    conv_map.add_conversation_rule(
+        void_ptr_rty,
+        enum_rty,
+        format!(
+            r#"
+    assert!(!{from_var}.is_null());
+    let {to_var}: {enum_type} = *unsafe {{ Box::from_raw({from_var} as *mut {enum_type}) }};
+"#,
+            to_var = TO_VAR_TEMPLATE,
+            from_var = FROM_VAR_TEMPLATE,
+            enum_type = conv_map[enum_rty],
+        )
+        .into(),
+    );
+
+    let src_id_span = (enum_info.src_id, enum_info.name.span());
+    let c_ftype = ForeignTypeS {
+        name: TypeName::new(
+            format!("{} *", fenum::c_data_enum_type(enum_info)),
+            src_id_span,
+        ),
+        provides_by_module: vec![
+            format!("\"{}\"", cpp_code::cpp_header_name_for_enum(enum_info)).into(),
+        ],
+        into_from_rust: Some(ForeignConversationRule {
+            rust_ty: void_ptr_rty,
+            intermediate: None,
+        }),
+        from_into_rust: Some(ForeignConversationRule {
+            rust_ty: void_ptr_rty,
+            intermediate: None,
+        }),
+        name_prefix: None,
+    };
+    conv_map.alloc_foreign_type(c_ftype)?;
+
+    let enum_ftype = ForeignTypeS {
+        name: TypeName::new(enum_info.name.to_string(), src_id_span),
+        provides_by_module: vec![format!(
+            "\"{}\"",
+            cpp_code::cpp_header_name_for_data_enum(enum_info)
+        )
+        .into()],
+        into_from_rust: Some(ForeignConversationRule {
+            rust_ty: enum_rty,
+            intermediate: Some(ForeignConversationIntermediate {
+                intermediate_ty: void_ptr_rty,
+                conv_code: FTypeConvCode::new(
+                    format!(
+                        "internal::{}_from_rust({})",
+                        enum_info.name, FROM_VAR_TEMPLATE
+                    ),
+                    Span::call_site(),
+                ),
+            }),
+        }),
+        from_into_rust: Some(ForeignConversationRule {
+            rust_ty: enum_rty,
+            intermediate: Some(ForeignConversationIntermediate {
+                intermediate_ty: void_ptr_rty,
+                conv_code: FTypeConvCode::new(
+                    format!(
+                        "internal::{}_to_rust({})",
+                        enum_info.name, FROM_VAR_TEMPLATE
+                    ),
+                    Span::call_site(),
+                ),
+            }),
+        }),
+        name_prefix: None,
+    };
+    conv_map.alloc_foreign_type(enum_ftype)?;
     Ok(())
 }
 
@@ -723,13 +1166,16 @@ fn register_intermidiate_pointer_types(
 fn register_rust_ty_conversation_rules(
     conv_map: &mut TypeMap,
     class: &ForeignerClassInfo,
-    this_type: RustType,
+    this_type: RustTypeIdx,
     this_type_inner: RustTypeIdx,
-    void_ptr_rust_ty: RustTypeIdx,
-    const_void_ptr_rust_ty: RustTypeIdx,
-    this_type_ref: RustTypeIdx,
-    this_type_mut_ref: RustTypeIdx,
+    ptr_types: ClassPtrTypes,
 ) -> Result<()> {
+    let ClassPtrTypes {
+        void_ptr_rust_ty,
+        const_void_ptr_rust_ty,
+        this_type_ref,
+        this_type_mut_ref,
+    } = ptr_types;
     // *const c_void -> &"class"
     conv_map.add_conversation_rule(
         const_void_ptr_rust_ty,
@@ -772,7 +1218,7 @@ fn register_rust_ty_conversation_rules(
 
     conv_map.add_conversation_rule(
         void_ptr_rust_ty,
-        this_type_mut_ptr.to_idx(),
+        this_type_mut_ptr,
         format!(
             r#"
             assert!(!{from_var}.is_null());
@@ -780,27 +1226,27 @@ fn register_rust_ty_conversation_rules(
         "#,
             to_var = TO_VAR_TEMPLATE,
             from_var = FROM_VAR_TEMPLATE,
-            this_type_mut_ptr = this_type_mut_ptr,
+            this_type_mut_ptr = conv_map[this_type_mut_ptr],
         )
         .into(),
     );
 
-    let unpack_code = unpack_from_heap_pointer(&this_type, TO_VAR_TEMPLATE, true);
+    let unpack_code = unpack_from_heap_pointer(&conv_map[this_type], TO_VAR_TEMPLATE, true);
     conv_map.add_conversation_rule(
-        this_type_mut_ptr.to_idx(),
-        this_type.to_idx(),
+        this_type_mut_ptr,
+        this_type,
         format!("\n{}\n", unpack_code,).into(),
     );
 
     //"class" -> *mut void
     conv_map.add_conversation_rule(
-        this_type.to_idx(),
+        this_type,
         void_ptr_rust_ty,
         format!(
             "let {to_var}: {ptr_type} = <{this_type}>::box_object({from_var});",
             to_var = TO_VAR_TEMPLATE,
             ptr_type = conv_map[void_ptr_rust_ty].typename(),
-            this_type = this_type,
+            this_type = conv_map[this_type],
             from_var = FROM_VAR_TEMPLATE
         )
         .into(),
@@ -828,24 +1274,51 @@ fn register_main_foreign_types(
     class: &ForeignerClassInfo,
     this_type: RustTypeIdx,
     self_type: RustTypeIdx,
-    void_ptr_rust_ty: RustTypeIdx,
-    const_void_ptr_rust_ty: RustTypeIdx,
-    this_type_ref: RustTypeIdx,
-    this_type_mut_ref: RustTypeIdx,
+    ptr_types: ClassPtrTypes,
+    shared_ptr: bool,
 ) -> Result<()> {
-    let class_ftype = ForeignTypeS {
-        name: TypeName::new(class.name.to_string(), (class.src_id, class.name.span())),
-        provides_by_module: vec![format!("\"{}\"", cpp_code::cpp_header_name(class)).into()],
-        into_from_rust: Some(ForeignConversationRule {
+    let ClassPtrTypes {
+        void_ptr_rust_ty,
+        const_void_ptr_rust_ty,
+        this_type_ref,
+        this_type_mut_ref,
+    } = ptr_types;
+    let into_from_rust = |conv_code: String| {
+        Some(ForeignConversationRule {
             rust_ty: this_type,
             intermediate: Some(ForeignConversationIntermediate {
                 intermediate_ty: void_ptr_rust_ty,
-                conv_code: FTypeConvCode::new(
-                    format!("{}({})", class.name, FROM_VAR_TEMPLATE),
-                    Span::call_site(),
-                ),
+                conv_code: FTypeConvCode::new(conv_code, Span::call_site()),
             }),
-        }),
+        })
+    };
+    if shared_ptr {
+        let class_ftype_shared_ptr = ForeignTypeS {
+            name: TypeName::new(
+                format!("std::shared_ptr<{}>", class.name),
+                (class.src_id, class.name.span()),
+            ),
+            provides_by_module: vec![
+                "<memory>".into(),
+                format!("\"{}\"", cpp_code::cpp_header_name(class)).into(),
+            ],
+            into_from_rust: into_from_rust(format!(
+                "std::make_shared<{}>({})",
+                class.name, FROM_VAR_TEMPLATE
+            )),
+            from_into_rust: None,
+            name_prefix: None,
+        };
+        conv_map.alloc_foreign_type(class_ftype_shared_ptr)?;
+    }
+    let class_ftype = ForeignTypeS {
+        name: TypeName::new(class.name.to_string(), (class.src_id, class.name.span())),
+        provides_by_module: vec![format!("\"{}\"", cpp_code::cpp_header_name(class)).into()],
+        into_from_rust: if shared_ptr {
+            None
+        } else {
+            into_from_rust(format!("{}({})", class.name, FROM_VAR_TEMPLATE))
+        },
         from_into_rust: Some(ForeignConversationRule {
             rust_ty: this_type,
             intermediate: Some(ForeignConversationIntermediate {
@@ -945,7 +1418,7 @@ fn register_main_foreign_types(
                 ),
                 provides_by_module: vec![format!("\"{}\"", cpp_code::cpp_header_name(class)).into()],
                 from_into_rust: Some(ForeignConversationRule {
-                    rust_ty: self_type_mut_ref.to_idx(),
+                    rust_ty: self_type_mut_ref,
                     intermediate: Some(ForeignConversationIntermediate {
                         intermediate_ty: void_ptr_rust_ty,
                         conv_code: FTypeConvCode::new(
@@ -975,7 +1448,7 @@ fn register_main_foreign_types(
                 ),
                 provides_by_module: vec![format!("\"{}\"", cpp_code::cpp_header_name(class)).into()],
                 from_into_rust: Some(ForeignConversationRule {
-                    rust_ty: self_type_ref.to_idx(),
+                    rust_ty: self_type_ref,
                     intermediate: Some(ForeignConversationIntermediate {
                         intermediate_ty: const_void_ptr_rust_ty,
                         conv_code: FTypeConvCode::new(
@@ -0,0 +1,193 @@
+use proc_macro2::TokenStream;
+
+use super::{cpp_code, write_generated_file};
+use crate::{
+    catch_panics::{init_panic_hook_call, panic_message_code},
+    error::{panic_on_syn_error, Result},
+    CppConfig, PanicAction,
+};
+
+/// Exception thrown if class is marked with `#[catch_panics]`,
+/// but `CppConfig::catch_panics` is not set
+pub(in crate::cpp) const DEFAULT_PANIC_EXCEPTION: &str = "std::runtime_error";
+
+/// Header with declaration of `rust_panic_message`, that is also used
+/// to report errors of fallible conversation rules
+pub(in crate::cpp) const PANIC_HEADER: &str = "rust_panic.h";
+
+/// Write `rust_panic.h` and return Rust code of `swig_report_error`, that saves
+/// message of error for C++ wrapper, if panics are converted to exceptions
+/// or there are fallible conversation rules. If panics are caught there is also
+/// `swig_catch_panic`, that calls closure and reports its panic or aborts according to `action`
+pub(in crate::cpp) fn generate_panic_helpers(
+    cfg: &CppConfig,
+    action: Option<&PanicAction>,
+    fallible_rules: bool,
+) -> Result<Vec<TokenStream>> {
+    let mut code = String::new();
+    let exception = match action {
+        Some(PanicAction::Throw(exception)) => Some(exception.as_str()),
+        Some(PanicAction::Abort) | None if fallible_rules => Some(DEFAULT_PANIC_EXCEPTION),
+        Some(PanicAction::Abort) | None => None,
+    };
+    if let Some(exception) = exception {
+        write_panic_header(cfg, exception)?;
+        code.push_str(&format!(
+            r#"
+thread_local! {{
+    static SWIG_PANIC_MESSAGE: ::std::cell::RefCell<Option<::std::ffi::CString>> =
+        ::std::cell::RefCell::new(None);
+}}
+
+#[allow(dead_code)]
+fn swig_report_error(mut msg: String) {{
+    // C string can not contain nul byte
+    msg.retain(|ch| ch != '\0');
+    let msg = ::std::ffi::CString::new(msg).unwrap_or_default();
+    SWIG_PANIC_MESSAGE.with(|x| *x.borrow_mut() = Some(msg));
+}}
+
+#[no_mangle]
+pub extern "C" fn {message_func}() -> *const ::std::os::raw::c_char {{
+    SWIG_PANIC_MESSAGE.with(|x| match *x.borrow() {{
+        Some(ref msg) => msg.as_ptr(),
+        None => ::std::ptr::null(),
+    }})
+}}
+
+#[no_mangle]
+pub extern "C" fn {clear_func}() {{
+    SWIG_PANIC_MESSAGE.with(|x| *x.borrow_mut() = None);
+}}
+"#,
+            message_func = cfg.c_symbol("rust_panic_message"),
+            clear_func = cfg.c_symbol("rust_panic_clear"),
+        ));
+    }
+    if let Some(action) = action {
+        code.push_str(&panic_message_code(cfg.panic_backtrace));
+        let init_hook = init_panic_hook_call(cfg.panic_backtrace);
+        match action {
+            PanicAction::Throw(_) => {
+                code.push_str(&format!(
+                    r#"
+#[allow(dead_code)]
+fn swig_catch_panic<T, F: FnOnce() -> T>(f: F) -> T {{
+{init_hook}    SWIG_PANIC_MESSAGE.with(|x| *x.borrow_mut() = None);
+    match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(f)) {{
+        Ok(ret) => ret,
+        Err(payload) => {{
+            swig_report_error(swig_panic_message(&*payload));
+            // C types are plain data, so zeroed value is valid
+            unsafe {{ ::std::mem::zeroed() }}
+        }}
+    }}
+}}
+"#,
+                    init_hook = init_hook,
+                ));
+            }
+            PanicAction::Abort => {
+                code.push_str(&format!(
+                    r#"
+#[allow(dead_code)]
+fn swig_catch_panic<T, F: FnOnce() -> T>(f: F) -> T {{
+{init_hook}    match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(f)) {{
+        Ok(ret) => ret,
+        Err(payload) => {{
+            eprintln!("panic in exported function: {{}}", swig_panic_message(&*payload));
+            ::std::process::abort();
+        }}
+    }}
+}}
+"#,
+                    init_hook = init_hook,
+                ));
+            }
+        }
+    }
+    Ok(vec![syn::parse_str(&code).unwrap_or_else(|err| {
+        panic_on_syn_error("cpp internal panic helpers", code, err)
+    })])
+}
+
+fn write_panic_header(cfg: &CppConfig, exception: &str) -> Result<()> {
+    let message_func = cfg.c_symbol("rust_panic_message");
+    let clear_func = cfg.c_symbol("rust_panic_clear");
+    let mut header = format!(
+        r#"// Automaticaly generated by rust_swig
+#pragma once
+
+{c_api_include}
+#ifdef __cplusplus
+extern "C" {{
+#endif
+
+/**
+ * Message of panic caught or error of conversation during the last call
+ * of Rust function in this thread, or NULL if there was no error.
+ * It is valid until the next call of Rust function
+ */
+{c_api}const char *{message_func}(void);
+
+/**
+ * Forget message of the last error
+ */
+{c_api}void {clear_func}(void);
+
+#ifdef __cplusplus
+}}
+#endif
+"#,
+        c_api_include = cfg.c_api_include(),
+        c_api = cfg.c_api(),
+        message_func = message_func,
+        clear_func = clear_func,
+    );
+    if !cfg.c_abi_only {
+        let nested_syntax = cfg.nested_namespace_syntax();
+        header.push_str(&format!(
+            r#"
+#ifdef __cplusplus
+#include <stdexcept>
+#include <string>
+
+{open_namespace}
+/**
+ * Throw {exception} if the last called Rust function panicked or reported error
+ */
+inline void rust_check_panic()
+{{
+    const char *msg = ::{message_func}();
+    if (msg != nullptr) {{
+        std::string what(msg);
+        ::{clear_func}();
+        throw {exception}(what);
+    }}
+}}
+{close_namespace}
+#endif
+"#,
+            open_namespace = cpp_code::open_namespace(&cfg.namespace_name, nested_syntax),
+            close_namespace = cpp_code::close_namespace(&cfg.namespace_name, nested_syntax),
+            message_func = message_func,
+            clear_func = clear_func,
+            exception = exception,
+        ));
+    }
+    write_generated_file(&cfg.output_dir.join(PANIC_HEADER), &header)
+}
+
+/// Wrap `body` of C function, that returns `ret_type`, into `swig_catch_panic`
+pub(in crate::cpp) fn catch_panic_code(catch_panics: bool, ret_type: &str, body: String) -> String {
+    if !catch_panics {
+        return body;
+    }
+    format!(
+        r#"    swig_catch_panic(move || -> {ret_type} {{
+{body}
+    }})"#,
+        ret_type = ret_type,
+        body = body,
+    )
+}
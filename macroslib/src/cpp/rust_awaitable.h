@@ -0,0 +1,89 @@
+#pragma once
+
+#ifdef __cplusplus
+#include <atomic>
+#include <coroutine>
+#include <optional>
+#include <type_traits>
+#include <utility>
+
+namespace RUST_SWIG_USER_NAMESPACE {
+
+/// Result of `#[awaitable]` method, `co_await` returns value passed to completion callback.
+/// Rust method is called before `co_await`, callback may be called from any thread,
+/// in this case coroutine is resumed in that thread
+template <typename T> class RustAwaitable final {
+    struct Void {
+    };
+    using Value = std::conditional_t<std::is_void_v<T>, Void, T>;
+    struct State {
+        // shared by `RustAwaitable` and completion callback
+        std::atomic<int> refs{ 2 };
+        // set by second of completion and `await_suspend`, the last one resumes coroutine
+        std::atomic<bool> ready{ false };
+        std::optional<Value> value;
+        std::coroutine_handle<> handle;
+    };
+
+public:
+    RustAwaitable()
+        : state_(new State)
+    {
+    }
+    RustAwaitable(const RustAwaitable &) = delete;
+    RustAwaitable &operator=(const RustAwaitable &) = delete;
+    RustAwaitable(RustAwaitable &&o) noexcept
+        : state_(o.state_)
+    {
+        o.state_ = nullptr;
+    }
+    RustAwaitable &operator=(RustAwaitable &&o) noexcept
+    {
+        std::swap(state_, o.state_);
+        return *this;
+    }
+    ~RustAwaitable() noexcept
+    {
+        if (state_ != nullptr) {
+            release(state_);
+        }
+    }
+
+    bool await_ready() const noexcept { return state_->ready.load(std::memory_order_acquire); }
+    bool await_suspend(std::coroutine_handle<> handle) noexcept
+    {
+        state_->handle = handle;
+        return !state_->ready.exchange(true, std::memory_order_acq_rel);
+    }
+    T await_resume() noexcept
+    {
+        if constexpr (!std::is_void_v<T>) {
+            return std::move(*state_->value);
+        }
+    }
+
+    /// Argument for completion callback, used by generated code
+    void *opaque() noexcept { return state_; }
+    /// Completion callback, used by generated code
+    template <typename... Args> static void complete(void *opaque, Args &&... args) noexcept
+    {
+        auto state = static_cast<State *>(opaque);
+        state->value.emplace(std::forward<Args>(args)...);
+        if (state->ready.exchange(true, std::memory_order_acq_rel)) {
+            state->handle.resume();
+        }
+        release(state);
+    }
+
+private:
+    State *state_;
+
+    static void release(State *state) noexcept
+    {
+        if (state->refs.fetch_sub(1, std::memory_order_acq_rel) == 1) {
+            delete state;
+        }
+    }
+};
+} // namespace RUST_SWIG_USER_NAMESPACE
+#endif // __cplusplus
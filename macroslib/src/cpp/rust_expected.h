@@ -0,0 +1,171 @@
+#pragma once
+
+#ifdef __cplusplus
+#include <cassert>
+#include <new>
+#include <utility>
+
+namespace RUST_SWIG_USER_NAMESPACE {
+
+/// Error value for `RustExpected`, like `std::unexpected`
+template <typename E> class RustUnexpected final {
+public:
+    explicit RustUnexpected(E e) noexcept
+        : err_(std::move(e))
+    {
+    }
+    E &error() noexcept { return err_; }
+    const E &error() const noexcept { return err_; }
+
+private:
+    E err_;
+};
+
+/// Subset of `std::expected` from C++23, that not use exceptions:
+/// access to absent value or error is checked only with `assert`
+template <typename T, typename E> class RustExpected final {
+public:
+    RustExpected(T val) noexcept
+        : has_value_(true)
+    {
+        new (&val_) T(std::move(val));
+    }
+    RustExpected(RustUnexpected<E> err) noexcept
+        : has_value_(false)
+    {
+        new (&err_) E(std::move(err.error()));
+    }
+    RustExpected(const RustExpected &) = delete;
+    RustExpected &operator=(const RustExpected &) = delete;
+    RustExpected(RustExpected &&o) noexcept
+        : has_value_(o.has_value_)
+    {
+        if (has_value_) {
+            new (&val_) T(std::move(o.val_));
+        } else {
+            new (&err_) E(std::move(o.err_));
+        }
+    }
+    RustExpected &operator=(RustExpected &&o) noexcept
+    {
+        if (this != &o) {
+            destroy();
+            has_value_ = o.has_value_;
+            if (has_value_) {
+                new (&val_) T(std::move(o.val_));
+            } else {
+                new (&err_) E(std::move(o.err_));
+            }
+        }
+        return *this;
+    }
+    ~RustExpected() noexcept { destroy(); }
+
+    bool has_value() const noexcept { return has_value_; }
+    explicit operator bool() const noexcept { return has_value_; }
+    T &value() noexcept
+    {
+        assert(has_value_);
+        return val_;
+    }
+    const T &value() const noexcept
+    {
+        assert(has_value_);
+        return val_;
+    }
+    T &operator*() noexcept { return value(); }
+    const T &operator*() const noexcept { return value(); }
+    T *operator->() noexcept { return &value(); }
+    const T *operator->() const noexcept { return &value(); }
+    E &error() noexcept
+    {
+        assert(!has_value_);
+        return err_;
+    }
+    const E &error() const noexcept
+    {
+        assert(!has_value_);
+        return err_;
+    }
+
+private:
+    void destroy() noexcept
+    {
+        if (has_value_) {
+            val_.~T();
+        } else {
+            err_.~E();
+        }
+    }
+
+    bool has_value_;
+    union {
+        T val_;
+        E err_;
+    };
+};
+
+/// `RustExpected` for `Result<(), E>`
+template <typename E> class RustExpected<void, E> final {
+public:
+    RustExpected() noexcept
+        : has_value_(true)
+    {
+    }
+    RustExpected(RustUnexpected<E> err) noexcept
+        : has_value_(false)
+    {
+        new (&err_) E(std::move(err.error()));
+    }
+    RustExpected(const RustExpected &) = delete;
+    RustExpected &operator=(const RustExpected &) = delete;
+    RustExpected(RustExpected &&o) noexcept
+        : has_value_(o.has_value_)
+    {
+        if (!has_value_) {
+            new (&err_) E(std::move(o.err_));
+        }
+    }
+    RustExpected &operator=(RustExpected &&o) noexcept
+    {
+        if (this != &o) {
+            destroy();
+            has_value_ = o.has_value_;
+            if (!has_value_) {
+                new (&err_) E(std::move(o.err_));
+            }
+        }
+        return *this;
+    }
+    ~RustExpected() noexcept { destroy(); }
+
+    bool has_value() const noexcept { return has_value_; }
+    explicit operator bool() const noexcept { return has_value_; }
+    void value() const noexcept { assert(has_value_); }
+    E &error() noexcept
+    {
+        assert(!has_value_);
+        return err_;
+    }
+    const E &error() const noexcept
+    {
+        assert(!has_value_);
+        return err_;
+    }
+
+private:
+    void destroy() noexcept
+    {
+        if (!has_value_) {
+            err_.~E();
+        }
+    }
+
+    bool has_value_;
+    union {
+        char dummy_;
+        E err_;
+    };
+};
+} // namespace RUST_SWIG_USER_NAMESPACE
+#endif // __cplusplus
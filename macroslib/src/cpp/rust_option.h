@@ -19,6 +19,26 @@ struct CRustOptionF64 {
     uint8_t is_some;
 };
 
+struct CRustOptionI8 {
+    int8_t val;
+    uint8_t is_some;
+};
+
+struct CRustOptionU8 {
+    uint8_t val;
+    uint8_t is_some;
+};
+
+struct CRustOptionI16 {
+    int16_t val;
+    uint8_t is_some;
+};
+
+struct CRustOptionU16 {
+    uint16_t val;
+    uint8_t is_some;
+};
+
 struct CRustOptionI32 {
     int32_t val;
     uint8_t is_some;
@@ -50,7 +70,7 @@ struct CRustOptionStr {
 };
 
 struct CRustOptionString {
-    CRustString val;
+    struct CRustString val;
     uint8_t is_some;
 };
 
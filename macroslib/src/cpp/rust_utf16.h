@@ -0,0 +1,45 @@
+#pragma once
+
+#ifdef __cplusplus
+#include <string>
+#include <string_view>
+
+#include "rust_vec.h"
+
+namespace RUST_SWIG_USER_NAMESPACE {
+
+/// Copy UTF-16 string returned from Rust into `std::u16string` or `std::wstring`,
+/// Rust memory is freed
+template <typename CharT> std::basic_string<CharT> rust_to_utf16_string(CRustVecU16 s)
+{
+    static_assert(sizeof(CharT) == sizeof(uint16_t),
+                  "UTF-16 strings require 16 bit character type, like wchar_t on Windows");
+    RustVecU16 guard{ s };
+    return std::basic_string<CharT>(reinterpret_cast<const CharT *>(guard.begin()),
+                                    guard.size());
+}
+
+/// UTF-16 string passed to Rust without copy
+template <typename CharT> CRustSliceU16 rust_utf16_slice(std::basic_string_view<CharT> s) noexcept
+{
+    static_assert(sizeof(CharT) == sizeof(uint16_t),
+                  "UTF-16 strings require 16 bit character type, like wchar_t on Windows");
+    return CRustSliceU16{ reinterpret_cast<const uint16_t *>(s.data()), s.size() };
+}
+
+/// Optional UTF-16 string passed to Rust without copy, empty optional is passed
+/// as `data == nullptr`, so empty string gets not null pointer
+template <typename OptionalT> CRustSliceU16 rust_utf16_option_slice(const OptionalT &s) noexcept
+{
+    static const uint16_t empty = 0;
+    if (!s) {
+        return CRustSliceU16{ nullptr, 0 };
+    }
+    CRustSliceU16 ret = rust_utf16_slice(*s);
+    if (ret.data == nullptr) {
+        ret.data = &empty;
+    }
+    return ret;
+}
+} // namespace RUST_SWIG_USER_NAMESPACE
+#endif // __cplusplus
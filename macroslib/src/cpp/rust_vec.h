@@ -56,6 +56,46 @@ struct CRustVecF64 {
 
 void CRustVecF64_free(struct CRustVecF64 vec);
 
+struct CRustVecI8 {
+    const int8_t *data;
+    uintptr_t len;
+    uintptr_t capacity;
+};
+
+void CRustVecI8_free(struct CRustVecI8 vec);
+
+struct CRustVecI16 {
+    const int16_t *data;
+    uintptr_t len;
+    uintptr_t capacity;
+};
+
+void CRustVecI16_free(struct CRustVecI16 vec);
+
+struct CRustVecU16 {
+    const uint16_t *data;
+    uintptr_t len;
+    uintptr_t capacity;
+};
+
+void CRustVecU16_free(struct CRustVecU16 vec);
+
+struct CRustVecI64 {
+    const int64_t *data;
+    uintptr_t len;
+    uintptr_t capacity;
+};
+
+void CRustVecI64_free(struct CRustVecI64 vec);
+
+struct CRustVecU64 {
+    const uint64_t *data;
+    uintptr_t len;
+    uintptr_t capacity;
+};
+
+void CRustVecU64_free(struct CRustVecU64 vec);
+
 struct CRustForeignVec {
     const void *data;
     uintptr_t len;
@@ -78,6 +118,11 @@ struct CRustSliceU32 {
     uintptr_t len;
 };
 
+struct CRustSliceU16 {
+    const uint16_t *data;
+    uintptr_t len;
+};
+
 struct CRustSliceUsize {
     const uintptr_t *data;
     uintptr_t len;
@@ -154,7 +199,7 @@ class RustVec final : private CContainerType {
 public:
     using value_type = typename std::remove_const<typename std::remove_reference<decltype(
         *internal::field_type(&CContainerType::data))>::type>::type;
-    using iterator = value_type *;
+    using iterator = const value_type *;
     using const_iterator = const value_type *;
 
     explicit RustVec(const CContainerType &o) noexcept
@@ -176,12 +221,14 @@ public:
     }
     RustVec &operator=(RustVec &&o) noexcept
     {
-        free_mem();
-        this->data = o.data;
-        this->len = o.len;
-        this->capacity = o.capacity;
+        if (this != &o) {
+            free_mem();
+            this->data = o.data;
+            this->len = o.len;
+            this->capacity = o.capacity;
 
-        reset(o);
+            reset(o);
+        }
         return *this;
     }
     ~RustVec() noexcept { free_mem(); }
@@ -195,7 +242,7 @@ public:
     void clear() noexcept { free_mem(); }
     CContainerType release() noexcept
     {
-        CContainerType ret{ this->data, this->len, this->capacity, this->step };
+        CContainerType ret{ this->data, this->len, this->capacity };
         reset(*this);
         return ret;
     }
@@ -222,6 +269,11 @@ using RustVecU32 = RustVec<CRustVecU32, CRustVecU32_free>;
 using RustVecUsize = RustVec<CRustVecUsize, CRustVecUsize_free>;
 using RustVecF32 = RustVec<CRustVecF32, CRustVecF32_free>;
 using RustVecF64 = RustVec<CRustVecF64, CRustVecF64_free>;
+using RustVecI8 = RustVec<CRustVecI8, CRustVecI8_free>;
+using RustVecI16 = RustVec<CRustVecI16, CRustVecI16_free>;
+using RustVecU16 = RustVec<CRustVecU16, CRustVecU16_free>;
+using RustVecI64 = RustVec<CRustVecI64, CRustVecI64_free>;
+using RustVecU64 = RustVec<CRustVecU64, CRustVecU64_free>;
 
 template <typename T>
 class RustForeignVecIterator final
@@ -449,13 +501,15 @@ public:
     }
     RustForeignVec &operator=(RustForeignVec &&o) noexcept
     {
-        free_mem();
-        this->data = o.data;
-        this->len = o.len;
-        this->capacity = o.capacity;
-        assert(this->step == o.step || this->step == 0 || o.step == 0);
-        this->step = o.step;
-        reset(o);
+        if (this != &o) {
+            free_mem();
+            this->data = o.data;
+            this->len = o.len;
+            this->capacity = o.capacity;
+            assert(this->step == o.step || this->step == 0 || o.step == 0);
+            this->step = o.step;
+            reset(o);
+        }
         return *this;
     }
     ~RustForeignVec() noexcept { free_mem(); }
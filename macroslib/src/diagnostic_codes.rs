@@ -0,0 +1,263 @@
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+use rustc_hash::FxHashSet;
+
+/// Stable identifiers of classes of errors and warnings,
+/// `SWIG0xxx` for errors and `SWIG1xxx` for warnings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum DiagnosticCode {
+    NameCollision,
+    UnsupportedType,
+    ConflictingForeignTypeRules,
+    DuplicateForeignType,
+    CppStandardTooOld,
+    SelfByValue,
+    LossyConversion,
+    OverriddenConvRule,
+    NoForeignClass,
+    NoConvPathToIntermediate,
+    UnusedRule,
+    ConvGraphProblem,
+}
+
+impl DiagnosticCode {
+    pub(crate) const ALL: [DiagnosticCode; 12] = [
+        DiagnosticCode::NameCollision,
+        DiagnosticCode::UnsupportedType,
+        DiagnosticCode::ConflictingForeignTypeRules,
+        DiagnosticCode::DuplicateForeignType,
+        DiagnosticCode::CppStandardTooOld,
+        DiagnosticCode::SelfByValue,
+        DiagnosticCode::LossyConversion,
+        DiagnosticCode::OverriddenConvRule,
+        DiagnosticCode::NoForeignClass,
+        DiagnosticCode::NoConvPathToIntermediate,
+        DiagnosticCode::UnusedRule,
+        DiagnosticCode::ConvGraphProblem,
+    ];
+
+    pub(crate) fn id(self) -> &'static str {
+        use DiagnosticCode::*;
+        match self {
+            NameCollision => "SWIG0001",
+            UnsupportedType => "SWIG0002",
+            ConflictingForeignTypeRules => "SWIG0003",
+            DuplicateForeignType => "SWIG0004",
+            CppStandardTooOld => "SWIG0005",
+            SelfByValue => "SWIG0006",
+            LossyConversion => "SWIG0007",
+            OverriddenConvRule => "SWIG1001",
+            NoForeignClass => "SWIG1002",
+            NoConvPathToIntermediate => "SWIG1003",
+            UnusedRule => "SWIG1004",
+            ConvGraphProblem => "SWIG1005",
+        }
+    }
+
+    pub(crate) fn from_id(id: &str) -> Option<DiagnosticCode> {
+        DiagnosticCode::ALL
+            .iter()
+            .find(|x| x.id().eq_ignore_ascii_case(id))
+            .cloned()
+    }
+
+    pub(crate) fn is_warning(self) -> bool {
+        self.id().starts_with("SWIG1")
+    }
+
+    pub(crate) fn explanation(self) -> &'static str {
+        use DiagnosticCode::*;
+        match self {
+            NameCollision => {
+                r#"Two items would get the same name in generated code.
+
+Classes, enums and interfaces share one namespace in foreign language,
+and rust_swig also generates helpers with fixed names, like `RustString`,
+or derived from names of classes, like `FooRef` or `FooOpaque` for C++.
+For Java two methods of class with the same name and arguments' types
+would get the same JNI function.
+
+    foreigner_class!(class Foo { ... });
+    foreign_enum!(enum Foo { ... }); // error: name `Foo` is already used
+
+Rename one of the items, or use `alias` for method."#
+            }
+            UnsupportedType => {
+                r#"Type of argument or result of method can not be converted
+to or from foreign language.
+
+    foreigner_class!(class Foo {
+        self_type Foo;
+        constructor Foo::new() -> Foo;
+        method Foo::f(&self, _: &Bar); // error: `Bar` is unknown
+    });
+
+Export the type via foreigner_class! (or foreign_enum! for enum),
+or add foreign_typemap! rule that converts it."#
+            }
+            ConflictingForeignTypeRules => {
+                r#"Two type map rules bind the same foreign type to different Rust types,
+so the later one would silently break usage of the earlier one.
+
+    foreign_typemap!(
+        ($p:r_type) u32 => jint { $out = $p as jint; };
+        ($p:f_type) => "int" "$out = $p;"; // error: "int" is already used for `i32`
+    );
+
+Use another name of foreign type, or remove one of the rules."#
+            }
+            DuplicateForeignType => {
+                r#"Foreign type is defined twice in `swig_foreign_types_map`
+or via simple foreign_typemap! rule.
+
+    foreign_typemap!(($p:r_type) jint; ($p:f_type) "int";);
+    foreign_typemap!(($p:r_type) jshort; ($p:f_type) "int";); // error
+
+Remove one of the definitions."#
+            }
+            CppStandardTooOld => {
+                r#"Feature used by generated C++ code requires newer C++ standard,
+than one set via `CppConfig::cpp_standard`.
+
+    CppConfig::new(output_dir, "ns".into())
+        .cpp_standard(CppStandard::Cpp14)
+        .cpp_optional(CppOptional::Std17) // error: needs C++17
+
+Raise the standard or use another variant of the feature."#
+            }
+            SelfByValue => {
+                r#"Method of foreigner_class! takes `self` by value.
+
+Foreign object keeps handle to Rust object after call of such method,
+so calling of other method via it would use consumed Rust object.
+
+    foreigner_class!(class Foo {
+        self_type Foo;
+        constructor Foo::new() -> Foo;
+        method Foo::into_bar(self) -> Bar; // error
+    });
+
+Take `&self` or `&mut self`, for example clone object inside of method."#
+            }
+            LossyConversion => {
+                r#"Conversation may silently truncate value or change its sign,
+reported if `Generator::strict_conversions(true)`.
+
+For example Java has no unsigned 64-bit type, so `u64` returned to Java
+as `long` is saturated to `i64::MAX`:
+
+    method Foo::size(&self) -> u64; // error in strict mode
+
+Add explicit rule for such conversation, for example range-checked fallible rule:
+
+    foreign_typemap!(
+        ($p:r_type, fallible, force_override) u64 => jlong {
+            $out = if $p <= i64::max_value() as u64 {
+                Ok($p as jlong)
+            } else {
+                Err(format!("{} is too big for long", $p))
+            }
+        };
+    );"#
+            }
+            OverriddenConvRule => {
+                r#"Conversation rule from merged type map replaces rule
+for the same pair of types with higher precedence.
+
+    foreign_typemap!(($p:r_type) i32 => i64 { $out = $p as i64 + 1 };);
+
+If replacing is intended, mark the rule with `force_override`:
+
+    foreign_typemap!(($p:r_type, force_override) i32 => i64 { ... };);"#
+            }
+            NoForeignClass => {
+                r#"Generic rule with `swig_to_foreigner_hint` is applicable to type,
+but there is no foreigner_class! for it, so the rule is skipped.
+
+Export the type via foreigner_class!, or allow this warning."#
+            }
+            NoConvPathToIntermediate => {
+                r#"Foreign type has conversation rule with intermediate Rust type,
+but there is no path of conversations between the Rust type
+and the intermediate one, so the rule can not be used.
+
+Add rule that converts between these Rust types."#
+            }
+            UnusedRule => {
+                r#"Rule from merged type map was not used to generate code,
+reported if `Generator::warn_unused_rules(true)`.
+
+Remove the rule, or allow this warning."#
+            }
+            ConvGraphProblem => {
+                r#"Rules of conversation graph are ambiguous or form cycle,
+reported if `Generator::validate_conversation_graph(true)`.
+
+Remove one of the reported rules, or change its cost."#
+            }
+        }
+    }
+}
+
+impl fmt::Display for DiagnosticCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+/// Warnings, that can be turned off per project
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Warnings {
+    allowed: FxHashSet<DiagnosticCode>,
+}
+
+/// Hash does not depend on order of allowing
+impl Hash for Warnings {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for code in &DiagnosticCode::ALL {
+            self.allowed.contains(code).hash(state);
+        }
+    }
+}
+
+impl Warnings {
+    pub(crate) fn allow(&mut self, code: DiagnosticCode) {
+        self.allowed.insert(code);
+    }
+
+    /// Print warning as build script does, if it is not allowed
+    pub(crate) fn print<T: fmt::Display>(&self, code: DiagnosticCode, msg: T) {
+        if !self.allowed.contains(&code) {
+            println!("warning=[{}] {}", code, msg);
+        }
+    }
+
+    pub(crate) fn is_allowed(&self, code: DiagnosticCode) -> bool {
+        self.allowed.contains(&code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnostic_codes() {
+        let mut ids = FxHashSet::default();
+        for code in &DiagnosticCode::ALL {
+            assert!(ids.insert(code.id()));
+            assert_eq!(Some(*code), DiagnosticCode::from_id(code.id()));
+            assert!(!code.explanation().is_empty());
+        }
+        assert_eq!(
+            Some(DiagnosticCode::UnusedRule),
+            DiagnosticCode::from_id("swig1004")
+        );
+        assert!(DiagnosticCode::UnusedRule.is_warning());
+        assert!(!DiagnosticCode::NameCollision.is_warning());
+        assert_eq!(None, DiagnosticCode::from_id("SWIG9999"));
+    }
+}
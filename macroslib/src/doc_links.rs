@@ -0,0 +1,197 @@
+//! Rewriting of rustdoc intra-doc links like [`Foo`] or [`Foo::bar`]
+//! to exported items into links of foreign language documentation
+
+use rustc_hash::FxHashMap;
+use std::rc::Rc;
+
+use crate::types::{ItemToExpand, MethodAccess, MethodVariant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum DocLinkKind {
+    Class,
+    Enum,
+    Interface,
+}
+
+/// Link to exported item or to its method/enum item
+#[derive(Debug, PartialEq)]
+pub(crate) struct DocLink<'a> {
+    pub(crate) kind: DocLinkKind,
+    pub(crate) item: &'a str,
+    pub(crate) member: Option<&'a str>,
+}
+
+/// Replace links to exported items inside of doc comments of `items`
+/// with result of `link`, links to unknown items are left as is
+pub(crate) fn rewrite_doc_links<F>(items: &mut [ItemToExpand], link: F)
+where
+    F: Fn(&DocLink) -> String,
+{
+    let known = known_items(items);
+    for item in items.iter_mut() {
+        let (self_name, doc_comments) = item_doc_comments(item);
+        for comments in doc_comments {
+            for comment in comments.iter_mut() {
+                if comment.contains('[') {
+                    *comment = rewrite_comment(comment, &self_name, &known, &link);
+                }
+            }
+        }
+    }
+}
+
+type KnownItems = FxHashMap<String, (DocLinkKind, Vec<String>)>;
+
+fn known_items(items: &[ItemToExpand]) -> KnownItems {
+    let mut known = KnownItems::default();
+    for item in items {
+        let (name, kind, members) = match item {
+            ItemToExpand::Class(fclass) => (
+                &fclass.name,
+                DocLinkKind::Class,
+                fclass
+                    .methods
+                    .iter()
+                    .filter(|m| {
+                        m.variant != MethodVariant::Constructor && m.access != MethodAccess::Private
+                    })
+                    .map(|m| m.short_name())
+                    .collect(),
+            ),
+            ItemToExpand::Enum(fenum) => (
+                &fenum.name,
+                DocLinkKind::Enum,
+                fenum.items.iter().map(|x| x.name.to_string()).collect(),
+            ),
+            ItemToExpand::Interface(finterface) => (
+                &finterface.name,
+                DocLinkKind::Interface,
+                finterface
+                    .items
+                    .iter()
+                    .map(|x| x.name.to_string())
+                    .collect(),
+            ),
+        };
+        known.insert(name.to_string(), (kind, members));
+    }
+    known
+}
+
+fn item_doc_comments(item: &mut ItemToExpand) -> (String, Vec<&mut Vec<String>>) {
+    match item {
+        ItemToExpand::Class(fclass) => {
+            let fclass = Rc::make_mut(fclass);
+            let mut ret = vec![&mut fclass.doc_comments];
+            ret.extend(fclass.methods.iter_mut().map(|x| &mut x.doc_comments));
+            (fclass.name.to_string(), ret)
+        }
+        ItemToExpand::Enum(fenum) => {
+            let mut ret = vec![&mut fenum.doc_comments];
+            ret.extend(fenum.items.iter_mut().map(|x| &mut x.doc_comments));
+            (fenum.name.to_string(), ret)
+        }
+        ItemToExpand::Interface(finterface) => {
+            let mut ret = vec![&mut finterface.doc_comments];
+            ret.extend(finterface.items.iter_mut().map(|x| &mut x.doc_comments));
+            (finterface.name.to_string(), ret)
+        }
+    }
+}
+
+fn rewrite_comment<F>(comment: &str, self_name: &str, known: &KnownItems, link: &F) -> String
+where
+    F: Fn(&DocLink) -> String,
+{
+    let mut ret = String::with_capacity(comment.len());
+    let mut rest = comment;
+    while let Some(start) = rest.find('[') {
+        ret.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = match rest.find(']') {
+            Some(end) => end,
+            None => break,
+        };
+        // `[text](url)` and `[text][ref]` are usual markdown links
+        let resolved = match rest[end + 1..].chars().next() {
+            Some('(') | Some('[') => None,
+            _ => resolve_link(&rest[1..end], self_name, known, link),
+        };
+        match resolved {
+            Some(resolved) => {
+                ret.push_str(&resolved);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                ret.push('[');
+                rest = &rest[1..];
+            }
+        }
+    }
+    ret.push_str(rest);
+    ret
+}
+
+fn resolve_link<F>(target: &str, self_name: &str, known: &KnownItems, link: &F) -> Option<String>
+where
+    F: Fn(&DocLink) -> String,
+{
+    let target = target.trim_matches('`').trim_end_matches("()");
+    if !target
+        .chars()
+        .all(|ch| ch.is_alphanumeric() || ch == '_' || ch == ':')
+    {
+        return None;
+    }
+    let mut parts = target.split("::");
+    let item = match parts.next()? {
+        "Self" => self_name,
+        item => item,
+    };
+    let member = parts.next();
+    if parts.next().is_some() {
+        return None;
+    }
+    let (item, (kind, members)) = known.get_key_value(item)?;
+    if let Some(member) = member {
+        if !members.iter().any(|x| x == member) {
+            return None;
+        }
+    }
+    Some(link(&DocLink {
+        kind: *kind,
+        item,
+        member,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rewrite_comment() {
+        let mut known = KnownItems::default();
+        known.insert(
+            "Foo".into(),
+            (DocLinkKind::Class, vec!["bar".into(), "baz".into()]),
+        );
+        known.insert("Color".into(), (DocLinkKind::Enum, vec!["RED".into()]));
+        let link = |l: &DocLink| match l.member {
+            Some(member) => format!("{{@link {}#{}}}", l.item, member),
+            None => format!("{{@link {}}}", l.item),
+        };
+        let rewrite = |comment: &str| rewrite_comment(comment, "Foo", &known, &link);
+        assert_eq!(" see {@link Foo}", rewrite(" see [`Foo`]"));
+        assert_eq!(" see {@link Foo}", rewrite(" see [Foo]"));
+        assert_eq!(
+            " {@link Foo#bar} and {@link Foo#baz}",
+            rewrite(" [`Foo::bar`] and [`Self::baz()`]")
+        );
+        assert_eq!(" {@link Color#RED}", rewrite(" [`Color::RED`]"));
+        assert_eq!(" [`Boo`] [`Foo::new`]", rewrite(" [`Boo`] [`Foo::new`]"));
+        assert_eq!(" [Foo](https://x.org)", rewrite(" [Foo](https://x.org)"));
+        assert_eq!(" a[0] [`Foo`", rewrite(" a[0] [`Foo`"));
+        assert_eq!(" [[{@link Foo}]", rewrite(" [[[`Foo`]]"));
+    }
+}
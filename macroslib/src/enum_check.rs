@@ -0,0 +1,24 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use crate::types::ForeignEnumInfo;
+
+/// Function with `match` over all items of `enum_info`, so rustc reports
+/// variant of Rust enum, that is not listed in `foreign_enum!`,
+/// instead of panic when such value is passed to foreign code
+pub(crate) fn generate_exhaustive_match(enum_info: &ForeignEnumInfo) -> TokenStream {
+    let enum_name = &enum_info.name;
+    let func_name = syn::Ident::new(
+        &format!("swig_foreign_enum_{}_lists_all_variants", enum_name),
+        proc_macro2::Span::call_site(),
+    );
+    let items = enum_info.items.iter().map(|x| &x.rust_name);
+    quote! {
+        #[allow(dead_code, non_snake_case)]
+        fn #func_name(x: &#enum_name) {
+            match *x {
+                #(#items { .. } => {})*
+            }
+        }
+    }
+}
@@ -1,10 +1,12 @@
 use std::fmt::{Display, Write};
 
 use crate::{
+    diagnostic_codes::DiagnosticCode,
     source_registry::{SourceId, SourceRegistry},
     SourceCode,
 };
-use proc_macro2::Span;
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
 
 pub(crate) type SourceIdSpan = (SourceId, Span);
 
@@ -14,35 +16,51 @@ pub(crate) fn invalid_src_id_span() -> SourceIdSpan {
 
 #[derive(Debug)]
 pub(crate) struct DiagnosticError {
-    data: Vec<(SourceId, syn::Error)>,
+    /// messages with code of class of error, notes have no code
+    data: Vec<(SourceId, syn::Error, Option<DiagnosticCode>)>,
 }
 
 impl DiagnosticError {
     pub fn from_syn_err(src_id: SourceId, err: syn::Error) -> Self {
         DiagnosticError {
-            data: vec![(src_id, err)],
+            data: vec![(src_id, err, None)],
         }
     }
     pub fn new<T: Display>(src_id: SourceId, sp: Span, err: T) -> Self {
         DiagnosticError {
-            data: vec![(src_id, syn::Error::new(sp, err))],
+            data: vec![(src_id, syn::Error::new(sp, err), None)],
         }
     }
     pub fn new2<T: Display>((src_id, sp): SourceIdSpan, err: T) -> Self {
         DiagnosticError {
-            data: vec![(src_id, syn::Error::new(sp, err))],
+            data: vec![(src_id, syn::Error::new(sp, err), None)],
         }
     }
     pub fn span_note<T: Display>(&mut self, sp: SourceIdSpan, err: T) {
-        self.data.push((sp.0, syn::Error::new(sp.1, err)));
+        self.data.push((sp.0, syn::Error::new(sp.1, err), None));
     }
     pub fn add_span_note<T: Display>(mut self, sp: SourceIdSpan, err: T) -> Self {
         self.span_note(sp, err);
         self
     }
+    /// Set code of class of error, see `explain_diagnostic`
+    pub fn with_code(mut self, code: DiagnosticCode) -> Self {
+        if let Some(first) = self.data.first_mut() {
+            first.2 = Some(code);
+        }
+        self
+    }
+    /// Append errors from `other`
+    pub fn merge(&mut self, other: DiagnosticError) {
+        self.data.extend(other.data);
+    }
     pub fn new_without_src_info<T: Display>(err: T) -> Self {
         DiagnosticError {
-            data: vec![(SourceId::none(), syn::Error::new(Span::call_site(), err))],
+            data: vec![(
+                SourceId::none(),
+                syn::Error::new(Span::call_site(), err),
+                None,
+            )],
         }
     }
 }
@@ -50,6 +68,9 @@ impl DiagnosticError {
 impl Display for DiagnosticError {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::result::Result<(), core::fmt::Error> {
         for x in &self.data {
+            if let Some(code) = x.2 {
+                write!(f, "[{}] ", code)?;
+            }
             write!(f, "{}", x.1)?;
         }
         Ok(())
@@ -58,6 +79,14 @@ impl Display for DiagnosticError {
 
 pub(crate) type Result<T> = std::result::Result<T, DiagnosticError>;
 
+/// Add `err` to errors collected in `all`, to report all of them at once
+pub(crate) fn collect_error(all: &mut Option<DiagnosticError>, err: DiagnosticError) {
+    match all {
+        Some(all) => all.merge(err),
+        None => *all = Some(err),
+    }
+}
+
 pub(crate) fn panic_on_syn_error(id_of_code: &str, code: String, err: syn::Error) -> ! {
     let mut src_reg = SourceRegistry::default();
     let src_id = src_reg.register(SourceCode {
@@ -70,9 +99,13 @@ pub(crate) fn panic_on_syn_error(id_of_code: &str, code: String, err: syn::Error
 pub(crate) fn panic_on_parse_error(src_reg: &SourceRegistry, main_err: &DiagnosticError) -> ! {
     let mut prev_err_src_id = None;
 
-    for (src_id, err) in &main_err.data {
+    for (src_id, err, code) in &main_err.data {
         if src_id.is_none() {
-            eprintln!("Error (without location information): {}", err);
+            eprintln!(
+                "Error (without location information): {}{}",
+                code_prefix(*code),
+                err
+            );
             continue;
         }
         let src = &src_reg.src_with_id(*src_id);
@@ -80,12 +113,44 @@ pub(crate) fn panic_on_parse_error(src_reg: &SourceRegistry, main_err: &Diagnost
             eprintln!("error in {}", src.id_of_code);
         }
         prev_err_src_id = Some(*src_id);
-        eprint_error_location(err, src);
+        eprintln!("{}", error_location_descr(err, *code, src));
     }
     panic!();
 }
 
-fn eprint_error_location(err: &syn::Error, src: &SourceCode) {
+/// Convert error to `compile_error!` invocations, so it will be reported
+/// by rustc during compilation of file with generated code
+pub(crate) fn parse_error_to_compile_error(
+    src_reg: &SourceRegistry,
+    main_err: &DiagnosticError,
+) -> TokenStream {
+    let mut ret = TokenStream::new();
+    for (src_id, err, code) in &main_err.data {
+        let msg = if src_id.is_none() {
+            format!("rust_swig: {}{}", code_prefix(*code), err)
+        } else {
+            format!(
+                "rust_swig: {}",
+                error_location_descr(err, *code, src_reg.src_with_id(*src_id))
+            )
+        };
+        ret.extend(quote! { compile_error!(#msg); });
+    }
+    ret
+}
+
+fn code_prefix(code: Option<DiagnosticCode>) -> String {
+    match code {
+        Some(code) => format!("[{}] ", code),
+        None => String::new(),
+    }
+}
+
+fn error_location_descr(
+    err: &syn::Error,
+    code: Option<DiagnosticCode>,
+    src: &SourceCode,
+) -> String {
     let span = err.span();
     let start = span.start();
     let end = span.end();
@@ -122,12 +187,13 @@ fn eprint_error_location(err: &syn::Error, src: &SourceCode) {
         }
     }
 
-    eprintln!(
-        "parsing of {name} failed\nerror: {err}\n{code_problem}\nAt {name}:{line_s}:{col_s}",
+    format!(
+        "parsing of {name} failed\nerror{code}: {err}\n{code_problem}\nAt {name}:{line_s}:{col_s}",
         name = src.id_of_code,
+        code = code.map(|x| format!("[{}]", x)).unwrap_or_default(),
         err = err,
         code_problem = code_problem,
         line_s = start.line,
         col_s = start.column,
-    );
+    )
 }
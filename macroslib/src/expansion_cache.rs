@@ -0,0 +1,228 @@
+//! Cache of `Generator::expand_many` results between builds.
+//! Build script is rerun on each change of crate, so if interface
+//! definitions, types maps and configuration are the same as during
+//! previous build, and generated files are not modified since then,
+//! expansion is skipped.
+//! The cache is just list of generated files with hashes of their content,
+//! saved together with hash of all inputs of expansion.
+//! Hashes are written to disk, so they are calculated with `StableHasher`,
+//! its result does not depend on version of compiler and std.
+
+use std::{
+    fs,
+    hash::{Hash, Hasher},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    file_cache::{self, FileWriteCache},
+    source_registry::SourceId,
+    Generator,
+};
+use log::{debug, warn};
+
+static CACHE_HEADER: &str = "rust_swig expansion cache v2";
+
+pub(crate) struct ExpansionCache {
+    path: PathBuf,
+    key: u64,
+}
+
+impl ExpansionCache {
+    /// Cache for expansion of `src_ids` by `gen` into `dst`
+    pub(crate) fn new(
+        cache_dir: &Path,
+        gen: &Generator,
+        src_ids: &[SourceId],
+        dst: &Path,
+    ) -> ExpansionCache {
+        let mut hasher = StableHasher::default();
+        dst.hash(&mut hasher);
+        ExpansionCache {
+            path: cache_dir.join(format!("expansion-{:016x}.txt", hasher.finish())),
+            key: expansion_key(gen, src_ids, dst),
+        }
+    }
+
+    /// `true` if the previous expansion had the same inputs,
+    /// and all generated files are the same as after it
+    pub(crate) fn is_up_to_date(&self) -> bool {
+        let cnt = match fs::read_to_string(&self.path) {
+            Ok(x) => x,
+            Err(_) => return false,
+        };
+        let mut lines = cnt.lines();
+        if lines.next() != Some(CACHE_HEADER) || lines.next() != Some(&format!("{:016x}", self.key))
+        {
+            debug!("expansion cache {}: inputs changed", self.path.display());
+            return false;
+        }
+        for line in lines {
+            let mut parts = line.splitn(2, ' ');
+            let (hash, path) = match (parts.next(), parts.next()) {
+                (Some(hash), Some(path)) => (hash, Path::new(path)),
+                _ => return false,
+            };
+            match content_hash(path) {
+                Ok(cur_hash) if format!("{:016x}", cur_hash) == hash => {}
+                _ => {
+                    debug!("expansion cache: {} changed", path.display());
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// Remember `files` generated by expansion
+    pub(crate) fn save(&self, files: &[PathBuf]) {
+        let mut files: Vec<&PathBuf> = files.iter().collect();
+        files.sort();
+        files.dedup();
+        let res = self.write(&files);
+        if let Err(err) = res {
+            warn!(
+                "Can not write expansion cache {}: {}",
+                self.path.display(),
+                err
+            );
+        }
+    }
+
+    fn write(&self, files: &[&PathBuf]) -> io::Result<()> {
+        let mut cnt = format!("{}\n{:016x}\n", CACHE_HEADER, self.key);
+        for path in files {
+            cnt.push_str(&format!(
+                "{:016x} {}\n",
+                content_hash(path)?,
+                path.display()
+            ));
+        }
+        if let Some(dir) = self.path.parent() {
+            file_cache::create_dir_all(dir)?;
+        }
+        let mut file = FileWriteCache::new(&self.path);
+        file.write_all(cnt.as_bytes())?;
+        file.update_file_if_necessary()
+    }
+}
+
+fn content_hash(path: &Path) -> io::Result<u64> {
+    let cnt = fs::read(path)?;
+    let mut hasher = StableHasher::default();
+    cnt.hash(&mut hasher);
+    Ok(hasher.finish())
+}
+
+/// Hash of everything that affects result of expansion,
+/// except closures passed to `Generator::with_generated_file_hook`
+fn expansion_key(gen: &Generator, src_ids: &[SourceId], dst: &Path) -> u64 {
+    let mut hasher = StableHasher::default();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    dst.hash(&mut hasher);
+    for src_id in src_ids.iter().chain(&gen.conv_map_source) {
+        let src = gen.src_reg.src_with_id(*src_id);
+        src.id_of_code.hash(&mut hasher);
+        src.code.hash(&mut hasher);
+    }
+    gen.pointer_target_width.hash(&mut hasher);
+    gen.target_cfg.hash(&mut hasher);
+    gen.config.hash(&mut hasher);
+    gen.conv_map.warnings().hash(&mut hasher);
+    gen.conv_map.strict_conversions().hash(&mut hasher);
+    gen.conv_graph_dump_path.hash(&mut hasher);
+    gen.expanded_code_dump_path.hash(&mut hasher);
+    gen.api_manifest_path.hash(&mut hasher);
+    gen.warn_unused_rules.hash(&mut hasher);
+    gen.validate_conv_graph.hash(&mut hasher);
+    gen.removed_conversions.hash(&mut hasher);
+    gen.formatters.hash(&mut hasher);
+    gen.errors_as_compile_error.hash(&mut hasher);
+    gen.additional_targets.hash(&mut hasher);
+    gen.api_version.hash(&mut hasher);
+    gen.source_location_comments.hash(&mut hasher);
+    gen.check_rust_signatures.hash(&mut hasher);
+    gen.check_send.hash(&mut hasher);
+    gen.generated_file_hooks.borrow().len().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// FNV-1a, integers are written as little endian bytes of fixed size,
+/// so result is the same on all hosts
+struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        StableHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= u64::from(*b);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+    fn write_isize(&mut self, i: isize) {
+        self.write_u64(i as u64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{JavaConfig, LanguageConfig};
+
+    #[test]
+    fn test_stable_hasher() {
+        let mut hasher = StableHasher::default();
+        hasher.write(b"a");
+        assert_eq!(0xaf63_dc4c_8601_ec8c, hasher.finish());
+
+        let mut h1 = StableHasher::default();
+        h1.write_usize(5);
+        let mut h2 = StableHasher::default();
+        h2.write_u64(5);
+        assert_eq!(h1.finish(), h2.finish());
+    }
+
+    #[test]
+    fn test_expansion_key() {
+        let java_gen = || {
+            Generator::new(LanguageConfig::JavaConfig(JavaConfig::new(
+                "java".into(),
+                "com.example".into(),
+            )))
+        };
+        let dst = Path::new("lib.rs");
+        let key = expansion_key(&java_gen(), &[], dst);
+        assert_eq!(key, expansion_key(&java_gen(), &[], dst));
+        assert_ne!(key, expansion_key(&java_gen(), &[], Path::new("lib2.rs")));
+        assert_ne!(
+            key,
+            expansion_key(&java_gen().warn_unused_rules(true), &[], dst)
+        );
+        let other_pkg = Generator::new(LanguageConfig::JavaConfig(JavaConfig::new(
+            "java".into(),
+            "com.example2".into(),
+        )));
+        assert_ne!(key, expansion_key(&other_pkg, &[], dst));
+    }
+}
@@ -1,45 +1,496 @@
 /// To prevent modification time changing
 use std::{
-    fs::File,
-    io,
-    io::{Read, Write},
-    path::PathBuf,
+    cell::RefCell,
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    mem,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
-/// Implement write cache in memory, and update file only if necessary
+/// Transformation of file content before comparing it with file on disk,
+/// for example formatting of generated code
+pub(crate) type ContentFilter = Box<dyn Fn(&Path, Vec<u8>) -> Result<Vec<u8>, io::Error>>;
+
+thread_local! {
+    static CONTENT_FILTER: RefCell<Option<ContentFilter>> = RefCell::new(None);
+    static CHANGED_FILES: RefCell<Option<Vec<PathBuf>>> = const { RefCell::new(None) };
+    static WRITE_TIMES: RefCell<Option<Vec<(PathBuf, Duration)>>> = const { RefCell::new(None) };
+    static WRITTEN_FILES: RefCell<Option<Vec<PathBuf>>> = const { RefCell::new(None) };
+    static DRY_RUN_FILES: RefCell<Option<HashMap<PathBuf, Vec<u8>>>> = const { RefCell::new(None) };
+}
+
+/// Until returned guard is dropped, `filter` is applied to content
+/// of all files written on the current thread via `FileWriteCache`
+pub(crate) fn set_content_filter(filter: ContentFilter) -> ContentFilterGuard {
+    let prev = CONTENT_FILTER.with(|cur| cur.replace(Some(filter)));
+    ContentFilterGuard { prev }
+}
+
+pub(crate) struct ContentFilterGuard {
+    prev: Option<ContentFilter>,
+}
+
+impl Drop for ContentFilterGuard {
+    fn drop(&mut self) {
+        let prev = self.prev.take();
+        CONTENT_FILTER.with(|cur| *cur.borrow_mut() = prev);
+    }
+}
+
+/// Call `f` without modification of files: files written via `FileWriteCache`
+/// on the current thread are only compared with content on disk,
+/// and paths of files with different content are returned
+pub(crate) fn collect_changed_files<R, F: FnOnce() -> R>(f: F) -> (R, Vec<PathBuf>) {
+    struct Guard(Option<Vec<PathBuf>>);
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            let prev = self.0.take();
+            CHANGED_FILES.with(|cur| *cur.borrow_mut() = prev);
+        }
+    }
+    let _guard = Guard(CHANGED_FILES.with(|cur| cur.replace(Some(vec![]))));
+    let ret = f();
+    let changed = CHANGED_FILES.with(|cur| cur.borrow_mut().take().unwrap_or_default());
+    (ret, changed)
+}
+
+/// Call `f` without modification of file system: content of files
+/// written via `FileWriteCache` on the current thread is kept in memory,
+/// and is used instead of content on disk by following writes of the same files
+pub(crate) fn dry_run<R, F: FnOnce() -> R>(f: F) -> R {
+    struct Guard(Option<HashMap<PathBuf, Vec<u8>>>);
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            let prev = self.0.take();
+            DRY_RUN_FILES.with(|cur| *cur.borrow_mut() = prev);
+        }
+    }
+    let _guard = Guard(DRY_RUN_FILES.with(|cur| cur.replace(Some(HashMap::new()))));
+    f()
+}
+
+/// `fs::create_dir_all`, that does nothing inside `dry_run`
+/// or `collect_changed_files`
+pub(crate) fn create_dir_all(path: &Path) -> Result<(), io::Error> {
+    let only_memory = DRY_RUN_FILES.with(|files| files.borrow().is_some())
+        || CHANGED_FILES.with(|changed| changed.borrow().is_some());
+    if only_memory {
+        Ok(())
+    } else {
+        fs::create_dir_all(path)
+    }
+}
+
+/// Call `f` and collect time spent in `FileWriteCache::update_file_if_necessary`
+/// on the current thread, for each written file
+pub(crate) fn collect_write_times<R, F: FnOnce() -> R>(f: F) -> (R, Vec<(PathBuf, Duration)>) {
+    struct Guard(Option<Vec<(PathBuf, Duration)>>);
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            let prev = self.0.take();
+            WRITE_TIMES.with(|cur| *cur.borrow_mut() = prev);
+        }
+    }
+    let _guard = Guard(WRITE_TIMES.with(|cur| cur.replace(Some(vec![]))));
+    let ret = f();
+    let times = WRITE_TIMES.with(|cur| cur.borrow_mut().take().unwrap_or_default());
+    (ret, times)
+}
+
+/// Call `f` and collect paths of all files successfully written
+/// via `FileWriteCache` on the current thread, even if they were not modified
+pub(crate) fn collect_written_files<R, F: FnOnce() -> R>(f: F) -> (R, Vec<PathBuf>) {
+    struct Guard(Option<Vec<PathBuf>>);
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            let prev = self.0.take();
+            WRITTEN_FILES.with(|cur| *cur.borrow_mut() = prev);
+        }
+    }
+    let _guard = Guard(WRITTEN_FILES.with(|cur| cur.replace(Some(vec![]))));
+    let ret = f();
+    let written = WRITTEN_FILES.with(|cur| cur.borrow_mut().take().unwrap_or_default());
+    (ret, written)
+}
+
+/// Writer of generated file, that updates file only if necessary.
+/// Content is compared with file on disk while it is written,
+/// and since first difference it is streamed to temporary file,
+/// so whole content is kept in memory only if it should be transformed
+/// by content filter or if file is written inside of `dry_run`
 pub struct FileWriteCache {
-    cnt: Vec<u8>,
     path: PathBuf,
+    state: WriteState,
+}
+
+enum WriteState {
+    /// nothing was written yet
+    Empty,
+    /// content is collected in memory
+    InMemory(Vec<u8>),
+    /// content written so far is the same as beginning of file on disk
+    SameAsOnDisk {
+        old: BufReader<File>,
+        old_len: u64,
+        len: u64,
+        buf: Vec<u8>,
+    },
+    /// content differs from file on disk and is written to `tmp_path`
+    Changed {
+        tmp: BufWriter<File>,
+        tmp_path: PathBuf,
+    },
+    /// content differs from file on disk, but only fact of change
+    /// is interesting, see `collect_changed_files`
+    ChangedNotWritten,
+    /// error of I/O during write, it is reported by `update_file_if_necessary`
+    Failed(io::Error),
 }
 
 impl FileWriteCache {
     pub fn new<P: Into<PathBuf>>(p: P) -> FileWriteCache {
         FileWriteCache {
-            cnt: vec![],
             path: p.into(),
+            state: WriteState::Empty,
         }
     }
 
+    /// Write content to file, if file content is not the same,
+    /// so build systems do not see modification of file
+    /// and do not rebuild things that depend on it
     pub fn update_file_if_necessary(self) -> Result<(), io::Error> {
-        if let Ok(mut f) = File::open(&self.path) {
-            let mut cur_cnt = vec![];
-            f.read_to_end(&mut cur_cnt)?;
-            if cur_cnt == self.cnt {
+        let path = self.path.clone();
+        let ret = if WRITE_TIMES.with(|times| times.borrow().is_none()) {
+            self.update_file()
+        } else {
+            let start = Instant::now();
+            let ret = self.update_file();
+            let time = start.elapsed();
+            WRITE_TIMES.with(|times| {
+                if let Some(ref mut times) = *times.borrow_mut() {
+                    times.push((path.clone(), time));
+                }
+            });
+            ret
+        };
+        if ret.is_ok() {
+            WRITTEN_FILES.with(|written| {
+                if let Some(ref mut written) = *written.borrow_mut() {
+                    written.push(path);
+                }
+            });
+        }
+        ret
+    }
+
+    fn update_file(mut self) -> Result<(), io::Error> {
+        match mem::replace(&mut self.state, WriteState::Empty) {
+            WriteState::Empty => self.update_file_from_memory(vec![]),
+            WriteState::InMemory(cnt) => self.update_file_from_memory(cnt),
+            WriteState::SameAsOnDisk { old_len, len, .. } => {
+                if len == old_len {
+                    return Ok(());
+                }
+                if self.only_collect_changed() {
+                    return Ok(());
+                }
+                // new content is beginning of old one
+                let f = OpenOptions::new().write(true).open(&self.path)?;
+                f.set_len(len)
+            }
+            WriteState::Changed { tmp, tmp_path } => {
+                let ret = tmp
+                    .into_inner()
+                    .map_err(|err| err.into_error())
+                    .and_then(|_| fs::rename(&tmp_path, &self.path));
+                if ret.is_err() {
+                    let _ = fs::remove_file(&tmp_path);
+                }
+                ret
+            }
+            WriteState::ChangedNotWritten => {
+                self.only_collect_changed();
+                Ok(())
+            }
+            WriteState::Failed(err) => Err(err),
+        }
+    }
+
+    fn update_file_from_memory(&mut self, cnt: Vec<u8>) -> Result<(), io::Error> {
+        let cnt = CONTENT_FILTER.with(|filter| match *filter.borrow() {
+            Some(ref filter) => filter(&self.path, cnt),
+            None => Ok(cnt),
+        })?;
+        if self.is_file_up_to_date(&cnt)? {
+            return Ok(());
+        }
+        if self.only_collect_changed() {
+            return Ok(());
+        }
+        let path = &self.path;
+        let mut cnt = Some(cnt);
+        DRY_RUN_FILES.with(|files| {
+            if let Some(ref mut files) = *files.borrow_mut() {
+                files.insert(path.clone(), cnt.take().unwrap_or_default());
+            }
+        });
+        match cnt {
+            Some(cnt) => {
+                let mut f = File::create(path)?;
+                f.write_all(&cnt)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Inside `collect_changed_files` remember that file was changed,
+    /// returns `true` if file should not be modified
+    fn only_collect_changed(&self) -> bool {
+        CHANGED_FILES.with(|changed| match *changed.borrow_mut() {
+            Some(ref mut changed) => {
+                changed.push(self.path.clone());
+                true
+            }
+            None => false,
+        })
+    }
+
+    fn is_file_up_to_date(&self, cnt: &[u8]) -> Result<bool, io::Error> {
+        let in_memory = DRY_RUN_FILES.with(|files| {
+            files
+                .borrow()
+                .as_ref()
+                .and_then(|files| files.get(&self.path).map(|x| *x == cnt))
+        });
+        if let Some(up_to_date) = in_memory {
+            return Ok(up_to_date);
+        }
+        let mut f = match File::open(&self.path) {
+            Ok(f) => f,
+            Err(_) => return Ok(false),
+        };
+        // no need to read file with different size
+        if f.metadata()?.len() != cnt.len() as u64 {
+            return Ok(false);
+        }
+        let mut cur_cnt = Vec::with_capacity(cnt.len());
+        f.read_to_end(&mut cur_cnt)?;
+        Ok(cur_cnt == cnt)
+    }
+
+    /// Choose how to handle content, when the first data are written
+    fn start_write(&self) -> Result<WriteState, io::Error> {
+        let need_whole_content = CONTENT_FILTER.with(|filter| filter.borrow().is_some())
+            || DRY_RUN_FILES.with(|files| files.borrow().is_some());
+        if need_whole_content {
+            return Ok(WriteState::InMemory(vec![]));
+        }
+        match File::open(&self.path) {
+            Ok(f) => Ok(WriteState::SameAsOnDisk {
+                old_len: f.metadata()?.len(),
+                old: BufReader::new(f),
+                len: 0,
+                buf: vec![],
+            }),
+            Err(_) => Self::start_changed(&self.path, None),
+        }
+    }
+
+    /// Content differs from file on disk since `same_prefix`
+    /// bytes of `old` file
+    fn start_changed(
+        path: &Path,
+        same_prefix: Option<(&mut BufReader<File>, u64)>,
+    ) -> Result<WriteState, io::Error> {
+        if CHANGED_FILES.with(|changed| changed.borrow().is_some()) {
+            return Ok(WriteState::ChangedNotWritten);
+        }
+        let mut tmp_name = path
+            .file_name()
+            .map(|x| x.to_os_string())
+            .unwrap_or_default();
+        tmp_name.push(".rust_swig_tmp");
+        let tmp_path = path.with_file_name(tmp_name);
+        let mut tmp = BufWriter::new(File::create(&tmp_path)?);
+        if let Some((old, len)) = same_prefix {
+            old.seek(SeekFrom::Start(0))?;
+            let copied = io::copy(&mut old.take(len), &mut tmp)?;
+            if copied != len {
+                let _ = fs::remove_file(&tmp_path);
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("{} was truncated during write", path.display()),
+                ));
+            }
+        }
+        Ok(WriteState::Changed { tmp, tmp_path })
+    }
+
+    fn write_data(&mut self, data: &[u8]) -> Result<(), io::Error> {
+        if let WriteState::Empty = self.state {
+            self.state = self.start_write()?;
+        }
+        let changed_since = match self.state {
+            WriteState::Empty | WriteState::Failed(_) | WriteState::ChangedNotWritten => {
+                return Ok(())
+            }
+            WriteState::InMemory(ref mut cnt) => {
+                cnt.extend_from_slice(data);
                 return Ok(());
             }
+            WriteState::Changed { ref mut tmp, .. } => return tmp.write_all(data),
+            WriteState::SameAsOnDisk {
+                ref mut old,
+                old_len,
+                ref mut len,
+                ref mut buf,
+            } => {
+                let new_len = *len + data.len() as u64;
+                if new_len <= old_len {
+                    buf.resize(data.len(), 0);
+                    old.read_exact(buf)?;
+                    if &buf[..] == data {
+                        *len = new_len;
+                        return Ok(());
+                    }
+                }
+                *len
+            }
+        };
+        let state = match self.state {
+            WriteState::SameAsOnDisk { ref mut old, .. } => {
+                Self::start_changed(&self.path, Some((old, changed_since)))?
+            }
+            _ => unreachable!(),
+        };
+        self.state = state;
+        match self.state {
+            WriteState::Changed { ref mut tmp, .. } => tmp.write_all(data),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Drop for FileWriteCache {
+    fn drop(&mut self) {
+        if let WriteState::Changed { ref tmp_path, .. } = self.state {
+            let _ = fs::remove_file(tmp_path);
         }
-        let mut f = File::create(&self.path)?;
-        f.write_all(&self.cnt)?;
-        Ok(())
     }
 }
 
 impl io::Write for FileWriteCache {
+    /// Errors are reported by `update_file_if_necessary`,
+    /// so code generators can write to `FileWriteCache` like to memory
     fn write(&mut self, data: &[u8]) -> Result<usize, io::Error> {
-        self.cnt.extend_from_slice(data);
+        if let Err(err) = self.write_data(data) {
+            if let WriteState::Changed { ref tmp_path, .. } = self.state {
+                let _ = fs::remove_file(tmp_path);
+            }
+            self.state = WriteState::Failed(err);
+        }
         Ok(data.len())
     }
     fn flush(&mut self) -> Result<(), io::Error> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, thread, time::Duration};
+
+    #[test]
+    fn test_update_file_if_necessary() {
+        let tmp_dir = tempfile::tempdir().expect("Can not create tmp directory");
+        let path = tmp_dir.path().join("a.txt");
+        let write = |data: &str| {
+            let mut file = FileWriteCache::new(&path);
+            file.write_all(data.as_bytes()).unwrap();
+            file.update_file_if_necessary().unwrap();
+            fs::metadata(&path).unwrap().modified().unwrap()
+        };
+        let mtime = write("abc");
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(mtime, write("abc"));
+        assert_eq!("abc", fs::read_to_string(&path).unwrap());
+        write("abd");
+        assert_eq!("abd", fs::read_to_string(&path).unwrap());
+        write("ab");
+        assert_eq!("ab", fs::read_to_string(&path).unwrap());
+
+        {
+            let _guard = set_content_filter(Box::new(|_, mut cnt| {
+                cnt.make_ascii_uppercase();
+                Ok(cnt)
+            }));
+            write("abc");
+        }
+        assert_eq!("ABC", fs::read_to_string(&path).unwrap());
+        write("abc");
+        assert_eq!("abc", fs::read_to_string(&path).unwrap());
+
+        let (_, changed) = collect_changed_files(|| {
+            write("abc");
+            write("abcd");
+        });
+        assert_eq!(vec![path.clone()], changed);
+        assert_eq!("abc", fs::read_to_string(&path).unwrap());
+
+        let new_dir = tmp_dir.path().join("new");
+        let new_path = new_dir.join("b.txt");
+        dry_run(|| {
+            create_dir_all(&new_dir).unwrap();
+            write("abcd");
+            let mut file = FileWriteCache::new(&new_path);
+            file.write_all(b"abc").unwrap();
+            file.update_file_if_necessary().unwrap();
+            let (_, changed) = collect_changed_files(|| {
+                write("abcd");
+                write("abc");
+            });
+            assert_eq!(vec![path.clone()], changed);
+        });
+        assert_eq!("abc", fs::read_to_string(&path).unwrap());
+        assert!(!new_dir.exists());
+    }
+
+    #[test]
+    fn test_streaming_write() {
+        let tmp_dir = tempfile::tempdir().expect("Can not create tmp directory");
+        let path = tmp_dir.path().join("a.txt");
+        let write = |chunks: &[&[u8]]| {
+            let mut file = FileWriteCache::new(&path);
+            for chunk in chunks {
+                file.write_all(chunk).unwrap();
+            }
+            file.update_file_if_necessary().unwrap();
+            fs::metadata(&path).unwrap().modified().unwrap()
+        };
+        let big: Vec<u8> = (0..100_000).map(|x| (x % 251) as u8).collect();
+        let (first, second) = big.split_at(70_000);
+        let mtime = write(&[first, second]);
+        assert_eq!(big, fs::read(&path).unwrap());
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(mtime, write(&[&big[..10], &big[10..]]));
+
+        let mut changed = big.clone();
+        changed[80_000] = 0xFF;
+        write(&[first, &changed[70_000..]]);
+        assert_eq!(changed, fs::read(&path).unwrap());
+
+        write(&[first, b"end"]);
+        assert_eq!([first, b"end"].concat(), fs::read(&path).unwrap());
+
+        write(&[&first[..1000]]);
+        assert_eq!(&first[..1000], &fs::read(&path).unwrap()[..]);
+
+        let (_, changed) = collect_changed_files(|| write(&[&first[..1000], b"x"]));
+        assert_eq!(vec![path.clone()], changed);
+        assert_eq!(&first[..1000], &fs::read(&path).unwrap()[..]);
+        assert_eq!(1, fs::read_dir(tmp_dir.path()).unwrap().count());
+    }
+}
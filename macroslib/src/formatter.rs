@@ -0,0 +1,101 @@
+//! Run external formatters, like `rustfmt`, `clang-format`
+//! or `google-java-format`, on generated files before they are written,
+//! so generated code that is stored in VCS has stable and readable diffs.
+
+use std::{
+    ffi::OsStr,
+    io::{self, Write},
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+};
+
+/// External program that reads source code from stdin
+/// and prints formatted code to stdout
+#[derive(Clone, Debug, Hash)]
+pub(crate) struct Formatter {
+    pub(crate) extensions: Vec<String>,
+    pub(crate) command: String,
+    pub(crate) args: Vec<String>,
+}
+
+impl Formatter {
+    pub(crate) fn is_applicable(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(OsStr::to_str)
+            .is_some_and(|ext| self.extensions.iter().any(|x| x == ext))
+    }
+
+    pub(crate) fn format(&self, path: &Path, cnt: Vec<u8>) -> Result<Vec<u8>, io::Error> {
+        let err_context = |err: &dyn std::fmt::Display| {
+            io::Error::other(format!(
+                "formatter `{}` failed on {}: {}",
+                self.command,
+                path.display(),
+                err
+            ))
+        };
+        let mut child = Command::new(&self.command)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| err_context(&err))?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        // write in separate thread, to not deadlock if formatter
+        // prints output before reading all input
+        let writer = thread::spawn(move || stdin.write_all(&cnt));
+        let output = child.wait_with_output().map_err(|err| err_context(&err))?;
+        writer
+            .join()
+            .expect("formatter writer thread panicked")
+            .map_err(|err| err_context(&err))?;
+        if !output.status.success() {
+            return Err(err_context(&format!(
+                "{}\n{}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+        Ok(output.stdout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formatter_is_applicable() {
+        let fmt = Formatter {
+            extensions: vec!["hpp".into(), "h".into()],
+            command: "clang-format".into(),
+            args: vec![],
+        };
+        assert!(fmt.is_applicable(Path::new("/tmp/Foo.hpp")));
+        assert!(fmt.is_applicable(Path::new("c_Foo.h")));
+        assert!(!fmt.is_applicable(Path::new("Foo.java")));
+        assert!(!fmt.is_applicable(Path::new("hpp")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_formatter_format() {
+        let fmt = Formatter {
+            extensions: vec!["txt".into()],
+            command: "tr".into(),
+            args: vec!["a-z".into(), "A-Z".into()],
+        };
+        assert_eq!(
+            b"ABC\n".to_vec(),
+            fmt.format(Path::new("a.txt"), b"abc\n".to_vec()).unwrap()
+        );
+        let fmt = Formatter {
+            extensions: vec!["txt".into()],
+            command: "false".into(),
+            args: vec![],
+        };
+        assert!(fmt.format(Path::new("a.txt"), b"abc\n".to_vec()).is_err());
+    }
+}
@@ -0,0 +1,85 @@
+use std::{io::Write, path::Path};
+
+use crate::{
+    error::{DiagnosticError, Result},
+    file_cache::{self, FileWriteCache},
+    java_jni::proguard::PROGUARD_RULES_FILE,
+    JavaConfig,
+};
+
+/// ABIs supported by Android NDK, for each of them we create directory
+/// inside `src/main/jniLibs`, where `lib<crate>.so` should be placed
+const ANDROID_ABIS: [&str; 4] = ["armeabi-v7a", "arm64-v8a", "x86", "x86_64"];
+
+/// Create layout of Android library module:
+/// `build.gradle`, `src/main/AndroidManifest.xml`, `src/main/java/<package>`
+/// and `src/main/jniLibs/<abi>`, so `gradle assembleRelease` produces AAR
+pub(in crate::java_jni) fn generate_android_module(
+    cfg: &JavaConfig,
+    module_dir: &Path,
+) -> Result<()> {
+    let main_dir = module_dir.join("src").join("main");
+    let mut dirs = vec![cfg.output_dir.clone()];
+    dirs.extend(
+        ANDROID_ABIS
+            .iter()
+            .map(|abi| main_dir.join("jniLibs").join(abi)),
+    );
+    for dir in &dirs {
+        file_cache::create_dir_all(dir).map_err(|err| {
+            DiagnosticError::new_without_src_info(format!(
+                "Can not create directory {}: {}",
+                dir.display(),
+                err
+            ))
+        })?;
+    }
+
+    write_file(
+        &module_dir.join("build.gradle"),
+        &format!(
+            r#"// Automaticaly generated by rust_swig
+apply plugin: 'com.android.library'
+
+android {{
+    compileSdkVersion 28
+    defaultConfig {{
+        minSdkVersion 16
+        targetSdkVersion 28
+        consumerProguardFiles '{proguard_rules}'
+    }}
+    sourceSets {{
+        main {{
+            jniLibs.srcDirs = ['src/main/jniLibs']
+        }}
+    }}
+}}
+"#,
+            proguard_rules = PROGUARD_RULES_FILE
+        ),
+    )?;
+    write_file(
+        &main_dir.join("AndroidManifest.xml"),
+        &format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<!-- Automaticaly generated by rust_swig -->
+<manifest xmlns:android="http://schemas.android.com/apk/res/android"
+    package="{package}" />
+"#,
+            package = cfg.package_name
+        ),
+    )
+}
+
+fn write_file(path: &Path, cnt: &str) -> Result<()> {
+    let mut file = FileWriteCache::new(path);
+    file.write_all(cnt.as_bytes())
+        .and_then(|_| file.update_file_if_necessary())
+        .map_err(|err| {
+            DiagnosticError::new_without_src_info(format!(
+                "write to {} failed: {}",
+                path.display(),
+                err
+            ))
+        })
+}
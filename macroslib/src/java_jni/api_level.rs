@@ -0,0 +1,62 @@
+use crate::{
+    error::{DiagnosticError, Result},
+    types::ItemToExpand,
+    JavaConfig,
+};
+
+/// `java.util.Optional`, `java.util.stream`, `java.util.Spliterator`
+/// and `java.util.concurrent.CompletableFuture` are available since this level
+pub(in crate::java_jni) const JAVA8_API_LEVEL: u32 = 24;
+/// `java.lang.Record` is available since this level
+const RECORDS_API_LEVEL: u32 = 34;
+
+impl JavaConfig {
+    /// Is API of Android `level` available, always true if `min_api_level` is not set
+    pub(in crate::java_jni) fn api_level_at_least(&self, level: u32) -> bool {
+        match self.min_api_level {
+            Some(min_api_level) => min_api_level >= level,
+            None => true,
+        }
+    }
+}
+
+/// Report usage of features, that are not available on `JavaConfig::min_api_level`
+pub(in crate::java_jni) fn check_api_level(cfg: &JavaConfig, items: &[ItemToExpand]) -> Result<()> {
+    let min_api_level = match cfg.min_api_level {
+        Some(x) => x,
+        None => return Ok(()),
+    };
+    let unsupported = |feature: &str, level: u32| {
+        format!(
+            "{} requires Android API level {}, but min_api_level is {}",
+            feature, level, min_api_level
+        )
+    };
+    if cfg.java_streams && !cfg.api_level_at_least(JAVA8_API_LEVEL) {
+        return Err(DiagnosticError::new_without_src_info(unsupported(
+            "java.util.stream",
+            JAVA8_API_LEVEL,
+        )));
+    }
+    if cfg.records && !cfg.api_level_at_least(RECORDS_API_LEVEL) {
+        return Err(DiagnosticError::new_without_src_info(unsupported(
+            "Java records",
+            RECORDS_API_LEVEL,
+        )));
+    }
+    if cfg.api_level_at_least(JAVA8_API_LEVEL) {
+        return Ok(());
+    }
+    for item in items {
+        if let ItemToExpand::Class(fclass) = item {
+            if let Some(method) = fclass.methods.iter().find(|m| m.completable_future) {
+                return Err(DiagnosticError::new(
+                    fclass.src_id,
+                    method.span(),
+                    unsupported("#[completable_future]", JAVA8_API_LEVEL),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,332 @@
+use std::io::Write;
+
+use proc_macro2::TokenStream;
+use syn::Type;
+
+use crate::{
+    error::{panic_on_syn_error, DiagnosticError, Result},
+    file_cache::FileWriteCache,
+    java_jni::{java_class_full_name, java_class_name_to_jni},
+    typemap::{
+        ast::{if_result_return_ok_err_types, normalize_ty_lifetimes},
+        FROM_VAR_TEMPLATE, FUNCTION_RETURN_TYPE_TEMPLATE, TO_VAR_TEMPLATE, TO_VAR_TYPE_TEMPLATE,
+    },
+    types::{ForeignerClassInfo, ItemToExpand},
+    JavaConfig, TypeMap,
+};
+
+/// Java exception, that is thrown for `Err` of Rust error type
+pub(in crate::java_jni) struct JavaException {
+    /// `None` for any error type without own exception
+    rust_ty: Option<String>,
+    /// full name, like `java.io.IOException`
+    pub(in crate::java_jni) class_name: String,
+    /// full name of Java class for error, if error is exported
+    /// via `foreigner_class!` and exception carries it
+    error_class: Option<String>,
+    /// should be declared in `throws` of methods
+    pub(in crate::java_jni) checked: bool,
+    /// name of Rust function, that throws this exception,
+    /// shared by all methods with such error type
+    throw_func: String,
+}
+
+/// Find exception for error type of `Result`
+pub(in crate::java_jni) fn find_exception<'a>(
+    exceptions: &'a [JavaException],
+    err_ty: &Type,
+) -> Option<&'a JavaException> {
+    let err_ty = normalize_ty_lifetimes(err_ty);
+    exceptions
+        .iter()
+        .find(|x| match x.rust_ty {
+            Some(ref ty) => ty == err_ty,
+            None => false,
+        })
+        .or_else(|| exceptions.iter().find(|x| x.rust_ty.is_none()))
+}
+
+/// Check mapping of errors to exceptions and write Java code
+/// of exceptions, that should be generated
+pub(in crate::java_jni) fn generate_exceptions(
+    cfg: &JavaConfig,
+    items: &[ItemToExpand],
+) -> Result<Vec<JavaException>> {
+    let mut ret = Vec::with_capacity(cfg.exceptions.len());
+    for (rust_ty, exception_class) in &cfg.exceptions {
+        let ty: Type = syn::parse_str(rust_ty).map_err(|err| {
+            DiagnosticError::new_without_src_info(format!(
+                "Invalid Rust error type `{}` for exception {}: {}",
+                rust_ty, exception_class, err
+            ))
+        })?;
+        let rust_ty = normalize_ty_lifetimes(&ty).to_string();
+        if ret
+            .iter()
+            .any(|x: &JavaException| x.rust_ty.as_ref() == Some(&rust_ty))
+        {
+            return Err(DiagnosticError::new_without_src_info(format!(
+                "Exception for Rust error type `{}` is set twice",
+                rust_ty
+            )));
+        }
+        let generated = !exception_class.contains('.');
+        let error_class = if generated {
+            error_class(cfg, items, &rust_ty)
+        } else {
+            None
+        };
+        let exception = JavaException {
+            rust_ty: Some(rust_ty),
+            class_name: if generated {
+                java_class_full_name(&cfg.package_name, exception_class)
+            } else {
+                exception_class.clone()
+            },
+            error_class,
+            checked: !cfg.unchecked_exceptions,
+            throw_func: format!("swig_jni_throw_{}", ret.len()),
+        };
+        if generated {
+            generate_java_code_for_exception(cfg, exception_class, &exception)?;
+        }
+        ret.push(exception);
+    }
+    if cfg.unchecked_exceptions {
+        ret.push(JavaException {
+            rust_ty: None,
+            class_name: "java.lang.RuntimeException".into(),
+            error_class: None,
+            checked: false,
+            throw_func: format!("swig_jni_throw_{}", ret.len()),
+        });
+    }
+    Ok(ret)
+}
+
+/// Rust functions that throw exceptions, generated once and called
+/// from conversations of `Result` of all methods, instead of inlining
+/// creation of exception into each of them
+pub(in crate::java_jni) fn generate_rust_code_for_exceptions(
+    exceptions: &[JavaException],
+) -> Vec<TokenStream> {
+    exceptions
+        .iter()
+        .map(|exception| {
+            let class_name = java_class_name_to_jni(&exception.class_name);
+            let code = match (&exception.rust_ty, &exception.error_class) {
+                (Some(ref rust_ty), Some(ref error_class)) => format!(
+                    r#"
+#[allow(dead_code)]
+#[inline(never)]
+fn {func}(env: *mut JNIEnv, err: {rust_ty}) {{
+    let msg = err.to_string();
+    jni_throw_with_error(env, swig_c_str!("{class_name}"),
+                         swig_c_str!("(Ljava/lang/String;L{error_class};)V"),
+                         &msg, object_to_jobject(err, env));
+}}
+"#,
+                    func = exception.throw_func,
+                    rust_ty = rust_ty,
+                    class_name = class_name,
+                    error_class = java_class_name_to_jni(error_class),
+                ),
+                (Some(ref rust_ty), None) => format!(
+                    r#"
+#[allow(dead_code)]
+#[inline(never)]
+fn {func}(env: *mut JNIEnv, err: {rust_ty}) {{
+    jni_throw(env, swig_c_str!("{class_name}"), &err.to_string());
+}}
+"#,
+                    func = exception.throw_func,
+                    rust_ty = rust_ty,
+                    class_name = class_name,
+                ),
+                (None, _) => format!(
+                    r#"
+#[allow(dead_code)]
+#[inline(never)]
+fn {func}(env: *mut JNIEnv, err: &dyn ToString) {{
+    jni_throw(env, swig_c_str!("{class_name}"), &err.to_string());
+}}
+"#,
+                    func = exception.throw_func,
+                    class_name = class_name,
+                ),
+            };
+            syn::parse_str(&code)
+                .unwrap_or_else(|err| panic_on_syn_error("java/jni internal exception", code, err))
+        })
+        .collect()
+}
+
+/// Add conversations of `Result<T, E>` to `T` for methods of class,
+/// where `E` is mapped to exception
+pub(in crate::java_jni) fn register_exception_conversions(
+    conv_map: &mut TypeMap,
+    exceptions: &[JavaException],
+    class: &ForeignerClassInfo,
+) {
+    if exceptions.is_empty() {
+        return;
+    }
+    for method in &class.methods {
+        let ret_ty = match method.fn_decl.output {
+            syn::ReturnType::Default => continue,
+            syn::ReturnType::Type(_, ref ty) => ty,
+        };
+        let ret_ty = conv_map.find_or_alloc_rust_type(ret_ty, class.src_id);
+        let (ok_ty, err_ty) = match if_result_return_ok_err_types(&conv_map[ret_ty]) {
+            Some(x) => x,
+            None => continue,
+        };
+        let exception = match find_exception(exceptions, &err_ty) {
+            Some(x) => x,
+            None => continue,
+        };
+        let ok_ty = conv_map.find_or_alloc_rust_type(&ok_ty, class.src_id);
+        conv_map.add_conversation_rule(
+            ret_ty,
+            ok_ty,
+            format!(
+                r#"
+    let mut {to_var}: {to_var_type} = match {from_var} {{
+        Ok(x) => x,
+        Err(err) => {{
+            {throw_code}
+            return <{function_ret_type}>::invalid_value();
+        }}
+    }};
+"#,
+                to_var = TO_VAR_TEMPLATE,
+                to_var_type = TO_VAR_TYPE_TEMPLATE,
+                from_var = FROM_VAR_TEMPLATE,
+                function_ret_type = FUNCTION_RETURN_TYPE_TEMPLATE,
+                throw_code = throw_code(exception),
+            )
+            .into(),
+        );
+    }
+}
+
+fn throw_code(exception: &JavaException) -> String {
+    match exception.rust_ty {
+        Some(_) => format!("{}(env, err);", exception.throw_func),
+        // any error type, so it is passed as `&dyn ToString`
+        None => format!("{}(env, &err);", exception.throw_func),
+    }
+}
+
+/// JNI name of exception class, signature of its constructor and code that
+/// converts `err` into argument of constructor, to complete future exceptionally
+pub(in crate::java_jni) fn exception_constructor(
+    exception: Option<&JavaException>,
+) -> (String, String, &'static str) {
+    match exception {
+        Some(JavaException {
+            ref class_name,
+            error_class: Some(ref error_class),
+            ..
+        }) => (
+            java_class_name_to_jni(class_name),
+            format!(
+                "(Ljava/lang/String;L{};)V",
+                java_class_name_to_jni(error_class)
+            ),
+            "object_to_jobject(err, env)",
+        ),
+        Some(JavaException { ref class_name, .. }) => (
+            java_class_name_to_jni(class_name),
+            "(Ljava/lang/String;)V".into(),
+            "::std::ptr::null_mut()",
+        ),
+        None => (
+            "java/lang/Exception".into(),
+            "(Ljava/lang/String;)V".into(),
+            "::std::ptr::null_mut()",
+        ),
+    }
+}
+
+fn error_class(cfg: &JavaConfig, items: &[ItemToExpand], rust_ty: &str) -> Option<String> {
+    items.iter().find_map(|item| match item {
+        ItemToExpand::Class(fclass) if !cfg.is_record(fclass) => {
+            let self_desc = fclass.self_desc.as_ref()?;
+            if normalize_ty_lifetimes(&self_desc.self_type) == rust_ty {
+                Some(java_class_full_name(
+                    &cfg.package_name,
+                    &fclass.name.to_string(),
+                ))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    })
+}
+
+fn generate_java_code_for_exception(
+    cfg: &JavaConfig,
+    name: &str,
+    exception: &JavaException,
+) -> Result<()> {
+    let path = cfg.output_dir.join(format!("{}.java", name));
+    let mut file = FileWriteCache::new(&path);
+    let body = match exception.error_class {
+        Some(ref error_class) => format!(
+            r#"    private final transient {error_class} error;
+
+    {name}(String message, {error_class} error) {{
+        super(message);
+        this.error = error;
+    }}
+
+    /**
+     * Rust error, that caused this exception
+     */
+    public {error_class} getError() {{
+        return error;
+    }}
+"#,
+            name = name,
+            error_class = error_class,
+        ),
+        None => format!(
+            r#"    {name}(String message) {{
+        super(message);
+    }}
+"#,
+            name = name
+        ),
+    };
+    write!(
+        file,
+        r#"// Automaticaly generated by rust_swig
+package {package_name};
+
+/**
+ * Thrown for Rust error `{rust_ty}`
+ */
+public final class {name} extends {base_class} {{
+{body}}}
+"#,
+        package_name = cfg.package_name,
+        rust_ty = exception.rust_ty.as_ref().map_or("", String::as_str),
+        name = name,
+        base_class = if exception.checked {
+            "Exception"
+        } else {
+            "RuntimeException"
+        },
+        body = body,
+    )
+    .and_then(|_| file.update_file_if_necessary())
+    .map_err(|err| {
+        DiagnosticError::new_without_src_info(format!(
+            "write to {} failed: {}",
+            path.display(),
+            err
+        ))
+    })
+}
@@ -0,0 +1,206 @@
+use syn::{parse_quote, spanned::Spanned, Type};
+
+use crate::{
+    error::{DiagnosticError, Result},
+    java_jni::{
+        exceptions::{exception_constructor, find_exception, JavaException},
+        map_type::jobject_conv_code,
+        JavaForeignTypeInfo, NullAnnotation,
+    },
+    typemap::{
+        ast::{
+            fn_arg_type, if_box_fn_once_return_args, if_result_return_ok_err_types,
+            normalize_ty_lifetimes,
+        },
+        ForeignTypeInfo,
+    },
+    types::{ForeignerClassInfo, ForeignerMethod, MethodVariant},
+    TypeMap,
+};
+
+/// Name of generated Rust variable, that holds completion callback
+pub(in crate::java_jni) const COMPLETION_VAR: &str = "swig_completion";
+
+/// Argument of completion callback `Box<dyn FnOnce(..) + Send>`
+struct CompletionArg {
+    /// `T` for `FnOnce(T)` and `FnOnce(Result<T, E>)`, `()` for `FnOnce()`
+    ok_ty: Type,
+    /// `E` for `FnOnce(Result<T, E>)`
+    err_ty: Option<Type>,
+    /// `false` for `FnOnce()`
+    has_arg: bool,
+}
+
+fn completion_arg(
+    conv_map: &mut TypeMap,
+    class: &ForeignerClassInfo,
+    method: &ForeignerMethod,
+) -> Result<CompletionArg> {
+    let invalid_method = |msg: &str| DiagnosticError::new(class.src_id, method.span(), msg);
+    if method.variant == MethodVariant::Constructor {
+        return Err(invalid_method(
+            "#[completable_future] can not be used with constructor",
+        ));
+    }
+    if let syn::ReturnType::Type(_, ref ty) = method.fn_decl.output {
+        return Err(DiagnosticError::new(
+            class.src_id,
+            ty.span(),
+            "#[completable_future] method should not return value",
+        ));
+    }
+    let last_arg_ty = method
+        .fn_decl
+        .inputs
+        .iter()
+        .last()
+        .map(fn_arg_type)
+        .and_then(if_box_fn_once_return_args);
+    let mut args = match last_arg_ty {
+        Some(ref args) if args.len() <= 1 => args.clone(),
+        _ => {
+            return Err(invalid_method(
+                "#[completable_future] method should have Box<dyn FnOnce(Result<T, E>) + Send> as last argument",
+            ));
+        }
+    };
+    let arg_ty = match args.pop() {
+        Some(ty) => ty,
+        None => {
+            return Ok(CompletionArg {
+                ok_ty: parse_type! { () },
+                err_ty: None,
+                has_arg: false,
+            });
+        }
+    };
+    let arg_rust_ty = conv_map.find_or_alloc_rust_type(&arg_ty, class.src_id);
+    match if_result_return_ok_err_types(&conv_map[arg_rust_ty]) {
+        Some((ok_ty, err_ty)) => Ok(CompletionArg {
+            ok_ty,
+            err_ty: Some(err_ty),
+            has_arg: true,
+        }),
+        None => Ok(CompletionArg {
+            ok_ty: arg_ty,
+            err_ty: None,
+            has_arg: true,
+        }),
+    }
+}
+
+/// Java class and code that converts `x` of type `ok_ty` into `jobject`
+fn ok_conv_code(
+    conv_map: &mut TypeMap,
+    class: &ForeignerClassInfo,
+    ok_ty: &Type,
+) -> Result<(String, String)> {
+    if let Type::Tuple(ref tuple) = *ok_ty {
+        if tuple.elems.is_empty() {
+            return Ok(("Void".into(), "::std::ptr::null_mut()".into()));
+        }
+    }
+    let ok_rust_ty = conv_map.find_or_alloc_rust_type(ok_ty, class.src_id);
+    jobject_conv_code(conv_map, ok_rust_ty).ok_or_else(|| {
+        DiagnosticError::new(
+            class.src_id,
+            ok_ty.span(),
+            format!(
+                "#[completable_future]: result should be (), exported class, String, i32, i64 or f64, got {}",
+                conv_map[ok_rust_ty]
+            ),
+        )
+    })
+}
+
+/// `java.util.concurrent.CompletableFuture<T>` for `#[completable_future]` method
+pub(in crate::java_jni) fn completable_future_type(
+    conv_map: &mut TypeMap,
+    class: &ForeignerClassInfo,
+    method: &ForeignerMethod,
+) -> Result<JavaForeignTypeInfo> {
+    let ok_ty = completion_arg(conv_map, class, method)?.ok_ty;
+    let (ok_java_name, _) = ok_conv_code(conv_map, class, &ok_ty)?;
+    let java_name = format!("java.util.concurrent.CompletableFuture<{}>", ok_java_name);
+    let future_ti = conv_map.find_or_alloc_rust_type_with_suffix(
+        &parse_type! { jobject },
+        &java_name,
+        class.src_id,
+    );
+    Ok(JavaForeignTypeInfo {
+        base: ForeignTypeInfo {
+            name: java_name.into(),
+            correspoding_rust_type: future_ti,
+        },
+        java_converter: None,
+        annotation: Some(NullAnnotation::NonNull),
+        provides_by_module: Vec::new(),
+    })
+}
+
+/// Code that creates `CompletableFuture` as `swig_future` and completion
+/// callback for last argument of `#[completable_future]` method
+pub(in crate::java_jni) fn completion_code(
+    conv_map: &mut TypeMap,
+    class: &ForeignerClassInfo,
+    method: &ForeignerMethod,
+    exceptions: &[JavaException],
+) -> Result<String> {
+    let arg = completion_arg(conv_map, class, method)?;
+    let (_, conv_ok) = ok_conv_code(conv_map, class, &arg.ok_ty)?;
+    let ok_ty = normalize_ty_lifetimes(&arg.ok_ty);
+    let (closure_arg, result, exception, err_ty) = match arg.err_ty {
+        Some(ref err_ty) => (
+            "r",
+            "r".to_string(),
+            find_exception(exceptions, err_ty),
+            normalize_ty_lifetimes(err_ty),
+        ),
+        None if arg.has_arg => (
+            "x",
+            format!("Ok::<{}, String>(x)", ok_ty),
+            None,
+            "String".into(),
+        ),
+        None => (
+            "",
+            "Ok::<(), String>(())".to_string(),
+            None,
+            "String".into(),
+        ),
+    };
+    let (ex_class, ex_constructor_sig, conv_err) = exception_constructor(exception);
+    let completion_ty = method
+        .fn_decl
+        .inputs
+        .iter()
+        .last()
+        .map(|arg| normalize_ty_lifetimes(fn_arg_type(arg)))
+        .expect("completion argument was checked");
+    Ok(format!(
+        r#"
+    let (swig_future, {completion}) = swig_jni_completable_future(
+        env,
+        swig_c_str!("{ex_class}"),
+        swig_c_str!("{ex_constructor_sig}"),
+    );
+    let {completion}: {completion_ty} = Box::new(move |{closure_arg}| {{
+        {completion}.complete(
+            {result},
+            |x: {ok_ty}, env: *mut JNIEnv| {conv_ok},
+            |err: {err_ty}, env: *mut JNIEnv| {conv_err},
+        )
+    }});
+"#,
+        completion = COMPLETION_VAR,
+        ex_class = ex_class,
+        ex_constructor_sig = ex_constructor_sig,
+        completion_ty = completion_ty,
+        closure_arg = closure_arg,
+        result = result,
+        ok_ty = ok_ty,
+        conv_ok = conv_ok,
+        err_ty = err_ty,
+        conv_err = conv_err,
+    ))
+}
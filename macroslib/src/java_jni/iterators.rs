@@ -0,0 +1,237 @@
+use std::io::Write;
+
+use syn::{parse_quote, Type};
+
+use crate::{
+    error::{DiagnosticError, Result},
+    file_cache::FileWriteCache,
+    java_jni::{
+        api_level, java_class_full_name, java_class_name_to_jni, map_type::jobject_conv_code,
+        JavaForeignTypeInfo, NullAnnotation,
+    },
+    source_registry::SourceId,
+    typemap::{
+        ast::if_box_iterator_return_item_type, ForeignTypeInfo, RustTypeIdx, FROM_VAR_TEMPLATE,
+        TO_VAR_TEMPLATE,
+    },
+    types::ItemToExpand,
+    JavaConfig, TypeMap,
+};
+
+/// Generated Java class, that pulls elements from Rust iterator
+pub(in crate::java_jni) const ITERATOR_CLASS: &str = "RustIterator";
+
+/// Is there any method that returns `Box<dyn Iterator<Item = T>>`
+pub(in crate::java_jni) fn uses_iterators(cfg: &JavaConfig, items: &[ItemToExpand]) -> bool {
+    items.iter().any(|item| match item {
+        ItemToExpand::Class(fclass) if !cfg.is_record(fclass) => {
+            fclass
+                .methods
+                .iter()
+                .any(|method| match method.fn_decl.output {
+                    syn::ReturnType::Type(_, ref ty) => {
+                        if_box_iterator_return_item_type(ty).is_some()
+                    }
+                    syn::ReturnType::Default => false,
+                })
+        }
+        _ => false,
+    })
+}
+
+/// `java.util.Iterator<T>` or `java.util.stream.Stream<T>` for `Box<dyn Iterator<Item = T>>`,
+/// if `T` is exported class, `String`, `i32`, `i64` or `f64`
+pub(in crate::java_jni) fn java_iterator_type(
+    conv_map: &mut TypeMap,
+    ret_ty: RustTypeIdx,
+    package_name: &str,
+    java_streams: bool,
+) -> Option<JavaForeignTypeInfo> {
+    let item_ty = if_box_iterator_return_item_type(&conv_map[ret_ty].ty)?;
+    let item_ty = conv_map.find_or_alloc_rust_type_no_src_id(&item_ty);
+    let (item_java_name, conv_item) = jobject_conv_code(conv_map, item_ty)?;
+    let java_name = if java_streams {
+        format!("java.util.stream.Stream<{}>", item_java_name)
+    } else {
+        format!("java.util.Iterator<{}>", item_java_name)
+    };
+    let iter_ti = conv_map.find_or_alloc_rust_type_with_suffix(
+        &parse_type! { jobject },
+        &java_name,
+        SourceId::none(),
+    );
+    conv_map.add_conversation_rule(
+        ret_ty,
+        iter_ti,
+        format!(
+            r#"
+        let mut iter = {from_var};
+        let {to_var}: jobject = swig_jni_iterator_to_java(
+            swig_jni_find_class!(env, "{iter_class}"),
+            Box::new(move |env: *mut JNIEnv| match iter.next() {{
+                Some(x) => {conv_item},
+                None => ::std::ptr::null_mut(),
+            }}),
+            {java_streams},
+            env,
+        );
+"#,
+            to_var = TO_VAR_TEMPLATE,
+            from_var = FROM_VAR_TEMPLATE,
+            iter_class =
+                java_class_name_to_jni(&java_class_full_name(package_name, ITERATOR_CLASS)),
+            conv_item = conv_item,
+            java_streams = java_streams,
+        )
+        .into(),
+    );
+    Some(JavaForeignTypeInfo {
+        base: ForeignTypeInfo {
+            name: java_name.into(),
+            correspoding_rust_type: iter_ti,
+        },
+        java_converter: None,
+        annotation: Some(NullAnnotation::NonNull),
+        provides_by_module: Vec::new(),
+    })
+}
+
+/// Methods of `java.util.Spliterator` and `stream()` for `RustIterator`
+const SPLITERATOR_CODE: &str = r#"
+    @Override
+    public boolean tryAdvance(java.util.function.Consumer<? super T> action) {
+        T item = take();
+        if (item == null) {
+            return false;
+        }
+        action.accept(item);
+        return true;
+    }
+
+    @Override
+    public void forEachRemaining(java.util.function.Consumer<? super T> action) {
+        while (tryAdvance(action)) {
+        }
+    }
+
+    @Override
+    public java.util.Spliterator<T> trySplit() {
+        return null;
+    }
+
+    @Override
+    public long estimateSize() {
+        return Long.MAX_VALUE;
+    }
+
+    @Override
+    public int characteristics() {
+        return ORDERED | NONNULL;
+    }
+
+    /**
+     * Sequential stream, that releases native iterator on close
+     */
+    public java.util.stream.Stream<T> stream() {
+        return java.util.stream.StreamSupport.stream(this, false).onClose(this::close);
+    }
+"#;
+
+/// Write Java code of `RustIterator`, without `java.util.Spliterator`
+/// if it is not available on `min_api_level`
+pub(in crate::java_jni) fn generate_java_code_for_iterator(cfg: &JavaConfig) -> Result<()> {
+    let java8 = cfg.api_level_at_least(api_level::JAVA8_API_LEVEL);
+    let path = cfg.output_dir.join(format!("{}.java", ITERATOR_CLASS));
+    let mut file = FileWriteCache::new(&path);
+    write!(
+        file,
+        r#"// Automaticaly generated by rust_swig
+package {package_name};
+
+/**
+ * Elements of Rust iterator, pulled lazily one by one.
+ * Native iterator is released after the last element, by {{@link #close()}}{stream_doc}
+ */
+public final class {class_name}<T> implements java.util.Iterator<T>,{spliterator} AutoCloseable {{
+    private long mNativeObj;
+    private T nextItem;
+
+    {class_name}(long nativeObj) {{
+        mNativeObj = nativeObj;
+    }}
+
+    @SuppressWarnings("unchecked")
+    private synchronized T peek() {{
+        if (nextItem == null && mNativeObj != 0) {{
+            nextItem = (T) do_next(mNativeObj);
+            if (nextItem == null) {{
+                close();
+            }}
+        }}
+        return nextItem;
+    }}
+
+    private synchronized T take() {{
+        T ret = peek();
+        nextItem = null;
+        return ret;
+    }}
+
+    @Override
+    public boolean hasNext() {{
+        return peek() != null;
+    }}
+
+    @Override
+    public T next() {{
+        T ret = take();
+        if (ret == null) {{
+            throw new java.util.NoSuchElementException();
+        }}
+        return ret;
+    }}
+{spliterator_code}
+    @Override
+    public synchronized void close() {{
+        if (mNativeObj != 0) {{
+            do_delete(mNativeObj);
+            mNativeObj = 0;
+        }}
+    }}
+
+    @Override
+    protected void finalize() throws Throwable {{
+        try {{
+            close();
+        }} finally {{
+            super.finalize();
+        }}
+    }}
+
+    private static native Object do_next(long me);
+    private static native void do_delete(long me);
+}}
+"#,
+        package_name = cfg.package_name,
+        class_name = ITERATOR_CLASS,
+        stream_doc = if java8 {
+            "\n * or by close of stream created by {@link #stream()}"
+        } else {
+            ""
+        },
+        spliterator = if java8 {
+            " java.util.Spliterator<T>,"
+        } else {
+            ""
+        },
+        spliterator_code = if java8 { SPLITERATOR_CODE } else { "" },
+    )
+    .and_then(|_| file.update_file_if_necessary())
+    .map_err(|err| {
+        DiagnosticError::new_without_src_info(format!(
+            "write to {} failed: {}",
+            path.display(),
+            err
+        ))
+    })
+}
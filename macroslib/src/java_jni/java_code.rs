@@ -1,13 +1,20 @@
 use std::{fmt, io::Write, path::Path};
 
 use bitflags::bitflags;
+use rustc_hash::FxHashSet;
+use smol_str::SmolStr;
 
 use crate::{
+    doc_links::DocLink,
     file_cache::FileWriteCache,
-    java_jni::{fmt_write_err_map, method_name, JniForeignMethodSignature, NullAnnotation},
+    java_jni::{
+        exceptions::{find_exception, JavaException},
+        fmt_write_err_map, method_name, JniForeignMethodSignature, NullAnnotation, RecordComponent,
+    },
     typemap::ast::if_result_return_ok_err_types,
     typemap::TypeMap,
     types::{ForeignEnumInfo, ForeignInterface, ForeignerClassInfo, MethodAccess, MethodVariant},
+    JavaConfig,
 };
 
 bitflags! {
@@ -85,7 +92,12 @@ pub(in crate::java_jni) fn generate_java_code_for_interface(
 ) -> Result<(), String> {
     let path = output_dir.join(format!("{}.java", interface.name));
     let mut file = FileWriteCache::new(&path);
-    let imports = get_null_annotation_imports(use_null_annotation, methods_sign);
+    let mut imports = get_required_imports(methods_sign);
+    imports.push_str(&get_null_annotation_imports(
+        use_null_annotation,
+        methods_sign,
+        false,
+    ));
     let interface_comments = doc_comments_to_java_comments(&interface.doc_comments, true);
     write!(
         file,
@@ -131,18 +143,49 @@ public interface {interface_name} {{
     Ok(())
 }
 
-pub(in crate::java_jni) fn generate_java_code(
-    conv_map: &mut TypeMap,
+pub(in crate::java_jni) fn generate_java_code_for_record(
     output_dir: &Path,
     package_name: &str,
     class: &ForeignerClassInfo,
-    methods_sign: &[JniForeignMethodSignature],
+    components: &[RecordComponent],
     null_annotation_package: Option<&str>,
 ) -> Result<(), String> {
+    use std::fmt::Write as FmtWrite;
+
     let path = output_dir.join(format!("{}.java", class.name));
     let mut file = FileWriteCache::new(&path);
 
-    let imports = get_null_annotation_imports(null_annotation_package, methods_sign);
+    let mut imports = FxHashSet::<String>::default();
+    let mut args = String::new();
+    for (i, comp) in components.iter().enumerate() {
+        let output = &comp.f_method.output;
+        imports.extend(output.provides_by_module.iter().map(SmolStr::to_string));
+        let annotation = match (null_annotation_package, output.annotation) {
+            (Some(package), Some(NullAnnotation::NonNull)) => {
+                imports.insert(format!("{}.NonNull", package));
+                "@NonNull "
+            }
+            (Some(package), Some(NullAnnotation::Nullable)) => {
+                imports.insert(format!("{}.Nullable", package));
+                "@Nullable "
+            }
+            _ => "",
+        };
+        if i != 0 {
+            args.push_str(", ");
+        }
+        write!(
+            &mut args,
+            "{}{} {}",
+            annotation,
+            output.base.name.as_str(),
+            comp.name
+        )
+        .map_err(fmt_write_err_map)?;
+    }
+    let mut imports: Vec<_> = imports.into_iter().collect();
+    imports.sort();
+    let imports: String = imports.iter().map(|x| format!("import {};\n", x)).collect();
 
     let class_doc_comments = doc_comments_to_java_comments(&class.doc_comments, true);
     write!(
@@ -151,12 +194,66 @@ pub(in crate::java_jni) fn generate_java_code(
 package {package_name};
 {imports}
 {doc_comments}
-public final class {class_name} {{
+public record {class_name}({args}) {{
 "#,
         package_name = package_name,
         imports = imports,
         class_name = class.name,
         doc_comments = class_doc_comments,
+        args = args,
+    )
+    .map_err(&map_write_err)?;
+    file.write_all(class.foreigner_code.as_bytes())
+        .map_err(&map_write_err)?;
+    write!(file, "}}").map_err(&map_write_err)?;
+
+    file.update_file_if_necessary().map_err(&map_write_err)?;
+    Ok(())
+}
+
+pub(in crate::java_jni) fn generate_java_code(
+    conv_map: &mut TypeMap,
+    cfg: &JavaConfig,
+    class: &ForeignerClassInfo,
+    methods_sign: &[JniForeignMethodSignature],
+    exceptions: &[JavaException],
+) -> Result<(), String> {
+    let package_name = cfg.package_name.as_str();
+    let null_annotation_package = cfg.null_annotation_package();
+    let path = cfg.output_dir.join(format!("{}.java", class.name));
+    let mut file = FileWriteCache::new(&path);
+
+    let mut imports = get_required_imports(methods_sign);
+    imports.push_str(&get_null_annotation_imports(
+        null_annotation_package,
+        methods_sign,
+        cfg.kotlin_friendly,
+    ));
+
+    // Kotlin's `use` works with `AutoCloseable`, so `finalize` is not needed
+    let auto_closeable = cfg.kotlin_friendly
+        && class
+            .methods
+            .iter()
+            .any(|m| m.variant == MethodVariant::Constructor);
+    let class_doc_comments = doc_comments_to_java_comments(&class.doc_comments, true);
+    write!(
+        file,
+        r#"// Automaticaly generated by rust_swig
+package {package_name};
+{imports}
+{doc_comments}
+public final class {class_name}{implements} {{
+"#,
+        package_name = package_name,
+        imports = imports,
+        class_name = class.name,
+        doc_comments = class_doc_comments,
+        implements = if auto_closeable {
+            " implements AutoCloseable"
+        } else {
+            ""
+        },
     )
     .map_err(&map_write_err)?;
 
@@ -164,26 +261,39 @@ public final class {class_name} {{
     let mut have_constructor = false;
 
     for (method, f_method) in class.methods.iter().zip(methods_sign) {
-        write!(
-            &mut file,
-            "{doc_comments}",
-            doc_comments = doc_comments_to_java_comments(&method.doc_comments, false)
-        )
-        .map_err(&map_write_err)?;
-
         let may_return_error = match method.fn_decl.output {
-            syn::ReturnType::Default => false,
+            syn::ReturnType::Default => None,
             syn::ReturnType::Type(_, ref ptype) => {
                 let ret_rust_ty = conv_map.find_or_alloc_rust_type(ptype, class.src_id);
-                if_result_return_ok_err_types(&ret_rust_ty).is_some()
+                if_result_return_ok_err_types(&conv_map[ret_rust_ty]).map(|(_, err_ty)| err_ty)
             }
         };
 
-        let exception_spec = if may_return_error {
-            "throws Exception"
-        } else {
-            ""
+        let mut doc_comments = method.doc_comments.clone();
+        let exception_spec = match may_return_error {
+            Some(err_ty) => {
+                let (exception_class, checked) = match find_exception(exceptions, &err_ty) {
+                    Some(exception) => (exception.class_name.as_str(), exception.checked),
+                    None => ("Exception", true),
+                };
+                doc_comments.push(format!(
+                    "@throws {} if Rust function returns error",
+                    exception_class
+                ));
+                if checked {
+                    format!("throws {}", exception_class)
+                } else {
+                    String::new()
+                }
+            }
+            None => String::new(),
         };
+        write!(
+            &mut file,
+            "{doc_comments}",
+            doc_comments = doc_comments_to_java_comments(&doc_comments, false)
+        )
+        .map_err(&map_write_err)?;
 
         let method_access = match method.access {
             MethodAccess::Private => "private",
@@ -193,16 +303,29 @@ public final class {class_name} {{
 
         let convert_code = convert_code_for_method(f_method);
         let func_name = method_name(method, f_method);
+        let ret_annotation = match f_method.output.annotation {
+            Some(NullAnnotation::NonNull) if cfg.kotlin_friendly => "@NonNull\n    ",
+            Some(NullAnnotation::Nullable) if cfg.kotlin_friendly => "@Nullable\n    ",
+            _ => "",
+        };
+        let native_annotation = if method.critical_native {
+            "@dalvik.annotation.optimization.CriticalNative\n    "
+        } else if method.fast_native {
+            "@dalvik.annotation.optimization.FastNative\n    "
+        } else {
+            ""
+        };
         match method.variant {
             MethodVariant::StaticMethod => {
-                let ret_type = &f_method.output.name;
+                let ret_type = &f_method.output.base.name;
 
                 if convert_code.is_empty() {
                     write!(
                         file,
                         r#"
-    {method_access} static native {ret_type} {func_name}({args_with_types}) {exception_spec};
+    {ret_annotation}{native_annotation}{method_access} static native {ret_type} {func_name}({args_with_types}) {exception_spec};
 "#,
+                        ret_annotation = ret_annotation,
                         method_access = method_access,
                         ret_type = ret_type,
                         func_name = func_name,
@@ -212,18 +335,20 @@ public final class {class_name} {{
                             null_annotation_package.is_some()
                         )?,
                         exception_spec = exception_spec,
+                        native_annotation = native_annotation,
                     )
                     .map_err(&map_write_err)?;
                 } else {
                     write!(
                         file,
                         r#"
-    {method_access} static {ret_type} {method_name}({single_args_with_types}) {exception_spec} {{
+    {ret_annotation}{method_access} static {ret_type} {method_name}({single_args_with_types}) {exception_spec} {{
 {convert_code}
          {return_code}{func_name}({args});
     }}
-    private static native {ret_type} {func_name}({args_with_types}) {exception_spec};
+    {native_annotation}private static native {ret_type} {func_name}({args_with_types}) {exception_spec};
 "#,
+                        ret_annotation = ret_annotation,
                         method_name = method.short_name(),
                         method_access = method_access,
                         ret_type = ret_type,
@@ -235,6 +360,7 @@ public final class {class_name} {{
                             null_annotation_package.is_some()
                         )?,
                         exception_spec = exception_spec,
+                        native_annotation = native_annotation,
                         single_args_with_types = args_with_java_types(
                             f_method,
                             ArgsFormatFlags::EXTERNAL,
@@ -248,20 +374,22 @@ public final class {class_name} {{
             }
             MethodVariant::Method(_) => {
                 have_methods = true;
-                let ret_type = &f_method.output.name;
+                let ret_type = &f_method.output.base.name;
                 write!(
                     file,
                     r#"
-    {method_access} final {ret_type} {method_name}({single_args_with_types}) {exception_spec} {{
+    {ret_annotation}{method_access} final {ret_type} {method_name}({single_args_with_types}) {exception_spec} {{
 {convert_code}
         {return_code}{func_name}(mNativeObj{args});
     }}
-    private static native {ret_type} {func_name}(long me{args_with_types}) {exception_spec};
+    {native_annotation}private static native {ret_type} {func_name}(long me{args_with_types}) {exception_spec};
 "#,
+                    ret_annotation = ret_annotation,
                     method_access = method_access,
                     ret_type = ret_type,
                     method_name = method.short_name(),
                     exception_spec = exception_spec,
+                        native_annotation = native_annotation,
                     return_code = if ret_type != "void" { "return " } else { "" },
                     func_name = func_name,
                     convert_code = convert_code,
@@ -303,11 +431,12 @@ public final class {class_name} {{
 {convert_code}
         mNativeObj = init({args});
     }}
-    private static native long {func_name}({args_with_types}) {exception_spec};
+    {native_annotation}private static native long {func_name}({args_with_types}) {exception_spec};
 ",
                         method_access = method_access,
                         class_name = class.name,
                         exception_spec = exception_spec,
+                        native_annotation = native_annotation,
                         func_name = func_name,
                         ext_args_with_types = args_with_java_types(
                             f_method,
@@ -335,7 +464,26 @@ May be you need to use `private constructor = empty;` syntax?",
             package_name, class.name
         ));
     }
-    if have_constructor {
+    if auto_closeable {
+        write!(
+            file,
+            "
+    public synchronized void delete() {{
+        if (mNativeObj != 0) {{
+            do_delete(mNativeObj);
+            mNativeObj = 0;
+       }}
+    }}
+    @Override
+    public void close() {{
+        delete();
+    }}
+    private static native void do_delete(long me);
+    /*package*/ long mNativeObj;
+"
+        )
+        .map_err(&map_write_err)?;
+    } else if have_constructor {
         write!(
             file,
             "
@@ -495,16 +643,55 @@ fn doc_comments_to_java_comments(doc_comments: &[String], class_comments: bool)
     comments
 }
 
+/// `{@link Foo}` or `{@link Foo#bar}` for rustdoc link
+pub(in crate::java_jni) fn javadoc_link(link: &DocLink) -> String {
+    match link.member {
+        Some(member) => format!("{{@link {}#{}}}", link.item, member),
+        None => format!("{{@link {}}}", link.item),
+    }
+}
+
+fn get_required_imports(methods_sign: &[JniForeignMethodSignature]) -> String {
+    use std::fmt::Write;
+
+    let mut imports = FxHashSet::<&str>::default();
+    for f_method in methods_sign {
+        for arg in &f_method.input {
+            imports.extend(arg.provides_by_module.iter().map(SmolStr::as_str));
+        }
+        imports.extend(
+            f_method
+                .output
+                .provides_by_module
+                .iter()
+                .map(SmolStr::as_str),
+        );
+    }
+    let mut imports: Vec<_> = imports.into_iter().collect();
+    imports.sort();
+    let mut ret = String::new();
+    for import in imports {
+        writeln!(&mut ret, "import {};", import).unwrap();
+    }
+    ret
+}
+
 fn get_null_annotation_imports(
     null_annotation_package: Option<&str>,
     methods_sign: &[JniForeignMethodSignature],
+    annotate_output: bool,
 ) -> String {
     if let Some(null_annotation_package) = null_annotation_package {
         let mut has_non_null = false;
         let mut has_nullable = false;
 
         for f_method in methods_sign {
-            for arg in &f_method.input {
+            let output = if annotate_output {
+                Some(&f_method.output)
+            } else {
+                None
+            };
+            for arg in f_method.input.iter().chain(output) {
                 match arg.annotation {
                     Some(NullAnnotation::NonNull) => has_non_null = true,
                     Some(NullAnnotation::Nullable) => has_nullable = true,
@@ -37,6 +37,8 @@ mod swig_foreign_types_map {
     #![swig_rust_type_not_unique = "jobjectArray"]
     #![swig_foreigner_type = "java.lang.String []"]
     #![swig_rust_type_not_unique = "jobjectArray"]
+    #![swig_foreigner_type = "java.util.OptionalInt"]
+    #![swig_rust_type_not_unique = "jobject"]
     #![swig_foreigner_type = "java.util.OptionalLong"]
     #![swig_rust_type_not_unique = "jobject"]
     #![swig_foreigner_type = "Long"]
@@ -75,9 +77,31 @@ trait SwigDerefMut {
     fn swig_deref_mut(&mut self) -> &mut Self::Target;
 }
 
+// how to handle result of `fallible` rule from `foreign_typemap!`
+#[allow(dead_code)]
+#[swig_code = "let mut {to_var}: {to_var_type} = jni_unwrap_or_throw!({from_var}, {function_ret_type}, env);"]
+trait SwigFallible {}
+
+#[allow(unused_macros)]
+macro_rules! jni_unwrap_or_throw {
+    ($result_value:expr, $func_ret_type:ty, $env:ident) => {{
+        match $result_value {
+            Ok(x) => x,
+            Err(msg) => {
+                jni_throw_exception($env, &msg);
+                return <$func_ret_type>::invalid_value();
+            }
+        }
+    }};
+}
+
 #[allow(dead_code)]
 trait SwigForeignClass {
     fn jni_class_name() -> *const ::std::os::raw::c_char;
+    /// Global reference to Java class, `FindClass` is called only once
+    fn jni_class(env: *mut JNIEnv) -> jclass;
+    /// Id of `mNativeObj` field, `GetFieldID` is called only once
+    fn jni_native_ptr_field(env: *mut JNIEnv) -> jfieldID;
     fn box_object(x: Self) -> jlong;
     fn unbox_object(x: jlong) -> Self;
 }
@@ -89,6 +113,79 @@ macro_rules! swig_c_str {
     };
 }
 
+/// Find class via `FindClass` only once, the global reference to class
+/// is stored in `cache` and never released, so class is never unloaded
+#[allow(dead_code)]
+fn swig_jni_find_class_cached(
+    env: *mut JNIEnv,
+    cache: &::std::sync::atomic::AtomicUsize,
+    class_name: *const ::std::os::raw::c_char,
+) -> jclass {
+    use std::sync::atomic::Ordering;
+
+    let class = cache.load(Ordering::Acquire) as jclass;
+    if !class.is_null() {
+        return class;
+    }
+    let local_class: jclass = unsafe { (**env).FindClass.unwrap()(env, class_name) };
+    assert!(!local_class.is_null(), "FindClass {:?} failed", unsafe {
+        ::std::ffi::CStr::from_ptr(class_name)
+    });
+    let class = unsafe { (**env).NewGlobalRef.unwrap()(env, local_class) } as jclass;
+    assert!(!class.is_null(), "NewGlobalRef for class failed");
+    unsafe { (**env).DeleteLocalRef.unwrap()(env, local_class) };
+    match cache.compare_exchange(0, class as usize, Ordering::AcqRel, Ordering::Acquire) {
+        Ok(_) => class,
+        Err(prev) => {
+            // other thread was faster
+            unsafe { (**env).DeleteGlobalRef.unwrap()(env, class) };
+            prev as jclass
+        }
+    }
+}
+
+/// Get `jmethodID` or `jfieldID` via `get_id` only once, ids are valid
+/// while class is not unloaded, see `swig_jni_find_class_cached`
+#[allow(dead_code)]
+fn swig_jni_cached_id<T, F: FnOnce() -> *mut T>(
+    cache: &::std::sync::atomic::AtomicUsize,
+    get_id: F,
+) -> *mut T {
+    use std::sync::atomic::Ordering;
+
+    let id = cache.load(Ordering::Acquire) as *mut T;
+    if !id.is_null() {
+        return id;
+    }
+    let id = get_id();
+    if !id.is_null() {
+        cache.store(id as usize, Ordering::Release);
+    }
+    id
+}
+
+/// `swig_jni_find_class!(env, "java/lang/String")`, with cache per usage
+#[allow(unused_macros)]
+macro_rules! swig_jni_find_class {
+    ($env:expr, $class_name:expr) => {{
+        static CACHE: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
+        swig_jni_find_class_cached($env, &CACHE, swig_c_str!($class_name))
+    }};
+}
+
+/// `swig_jni_get_id!(env, class, GetMethodID, "name", "()V")`, with cache per usage,
+/// works also with `GetStaticMethodID`, `GetFieldID` and `GetStaticFieldID`
+#[allow(unused_macros)]
+macro_rules! swig_jni_get_id {
+    ($env:expr, $class:expr, $get_id:ident, $name:expr, $sig:expr) => {{
+        static CACHE: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
+        let env: *mut JNIEnv = $env;
+        swig_jni_cached_id(&CACHE, || unsafe {
+            (**env).$get_id.unwrap()(env, $class, swig_c_str!($name), swig_c_str!($sig))
+        })
+    }};
+}
+
 #[allow(unused_macros)]
 macro_rules! swig_assert_eq_size {
     ($x:ty, $($xs:ty),+ $(,)*) => {
@@ -110,48 +207,68 @@ pub unsafe fn jlong_to_pointer<T>(val: jlong) -> *mut T {
     val as *mut T
 }
 
+/// Strings with modified UTF-8 representation shorter than this
+/// are converted without heap allocation
+const SWIG_JAVA_STRING_INLINE_CAPACITY: usize = 128;
+
+#[allow(dead_code)]
+enum JavaStringBuf {
+    Inline([u8; SWIG_JAVA_STRING_INLINE_CAPACITY]),
+    Heap(Vec<u8>),
+}
+
+/// Copy of Java string, made by `GetStringUTFRegion`
 #[allow(dead_code)]
 pub struct JavaString {
-    string: jstring,
-    chars: *const ::std::os::raw::c_char,
-    env: *mut JNIEnv,
+    buf: JavaStringBuf,
+    len: usize,
 }
 #[allow(dead_code)]
 impl JavaString {
     pub fn new(env: *mut JNIEnv, js: jstring) -> JavaString {
-        let chars = if !js.is_null() {
-            unsafe { (**env).GetStringUTFChars.unwrap()(env, js, ::std::ptr::null_mut()) }
-        } else {
-            ::std::ptr::null_mut()
-        };
-        JavaString {
-            string: js,
-            chars: chars,
-            env: env,
+        if js.is_null() {
+            return JavaString {
+                buf: JavaStringBuf::Inline([0; SWIG_JAVA_STRING_INLINE_CAPACITY]),
+                len: 0,
+            };
         }
-    }
-    pub fn to_str(&self) -> &str {
-        if !self.chars.is_null() {
-            let s = unsafe { ::std::ffi::CStr::from_ptr(self.chars) };
-            s.to_str().unwrap()
+        let (len, n_chars) = unsafe {
+            (
+                (**env).GetStringUTFLength.unwrap()(env, js),
+                (**env).GetStringLength.unwrap()(env, js),
+            )
+        };
+        assert!(len >= 0 && n_chars >= 0);
+        let len = len as usize;
+        // place for terminating zero, that JVM may write
+        let mut buf = if len < SWIG_JAVA_STRING_INLINE_CAPACITY {
+            JavaStringBuf::Inline([0; SWIG_JAVA_STRING_INLINE_CAPACITY])
         } else {
-            ""
-        }
-    }
-}
-
-#[allow(dead_code)]
-impl Drop for JavaString {
-    fn drop(&mut self) {
-        assert!(!self.env.is_null());
-        if !self.string.is_null() {
-            assert!(!self.chars.is_null());
+            JavaStringBuf::Heap(vec![0; len + 1])
+        };
+        {
+            let data: &mut [u8] = match buf {
+                JavaStringBuf::Inline(ref mut x) => &mut x[..],
+                JavaStringBuf::Heap(ref mut x) => &mut x[..],
+            };
             unsafe {
-                (**self.env).ReleaseStringUTFChars.unwrap()(self.env, self.string, self.chars)
+                (**env).GetStringUTFRegion.unwrap()(
+                    env,
+                    js,
+                    0,
+                    n_chars,
+                    data.as_mut_ptr() as *mut ::std::os::raw::c_char,
+                )
             };
-            self.env = ::std::ptr::null_mut();
-            self.chars = ::std::ptr::null_mut();
         }
+        JavaString { buf, len }
+    }
+    pub fn to_str(&self) -> &str {
+        let data: &[u8] = match self.buf {
+            JavaStringBuf::Inline(ref x) => &x[..],
+            JavaStringBuf::Heap(ref x) => &x[..],
+        };
+        ::std::str::from_utf8(&data[..self.len]).unwrap()
     }
 }
 
@@ -180,6 +297,12 @@ struct JniEnvHolder<'a> {
 impl<'a> Drop for JniEnvHolder<'a> {
     fn drop(&mut self) {
         if self.need_detach {
+            #[cfg(debug_assertions)]
+            assert_eq!(
+                0,
+                SWIG_JNI_LOCAL_FRAMES.with(|n| n.get()),
+                "JniEnvHolder: thread detached with not popped JNI local frames"
+            );
             let res = unsafe {
                 (**self.callback.java_vm).DetachCurrentThread.unwrap()(self.callback.java_vm)
             };
@@ -190,6 +313,52 @@ impl<'a> Drop for JniEnvHolder<'a> {
     }
 }
 
+#[cfg(debug_assertions)]
+thread_local! {
+    static SWIG_JNI_LOCAL_FRAMES: ::std::cell::Cell<usize> = ::std::cell::Cell::new(0);
+}
+
+/// Frame for local references created by Rust code called not from Java,
+/// all of them are deleted when frame is dropped
+#[allow(dead_code)]
+struct JniLocalFrame {
+    env: *mut JNIEnv,
+    #[cfg(debug_assertions)]
+    depth: usize,
+}
+
+#[allow(dead_code)]
+impl JniLocalFrame {
+    fn new(env: *mut JNIEnv, capacity: jint) -> JniLocalFrame {
+        let res = unsafe { (**env).PushLocalFrame.unwrap()(env, capacity) };
+        assert_eq!(0, res, "PushLocalFrame({}) failed", capacity);
+        JniLocalFrame {
+            env,
+            #[cfg(debug_assertions)]
+            depth: SWIG_JNI_LOCAL_FRAMES.with(|n| {
+                n.set(n.get() + 1);
+                n.get()
+            }),
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Drop for JniLocalFrame {
+    fn drop(&mut self) {
+        #[cfg(debug_assertions)]
+        SWIG_JNI_LOCAL_FRAMES.with(|n| {
+            assert_eq!(
+                self.depth,
+                n.get(),
+                "JniLocalFrame: JNI local frames popped not in reverse order"
+            );
+            n.set(n.get() - 1);
+        });
+        unsafe { (**self.env).PopLocalFrame.unwrap()(self.env, ::std::ptr::null_mut()) };
+    }
+}
+
 #[allow(dead_code)]
 impl JavaCallback {
     fn new(obj: jobject, env: *mut JNIEnv) -> JavaCallback {
@@ -207,6 +376,7 @@ impl JavaCallback {
 
     fn get_jni_env(&self) -> JniEnvHolder {
         assert!(!self.java_vm.is_null());
+        swig_jni_check_no_critical_sections();
         let mut env: *mut JNIEnv = ::std::ptr::null_mut();
 
         #[cfg(target_os = "android")]
@@ -298,26 +468,74 @@ fn jni_throw_exception(env: *mut JNIEnv, message: &str) {
     jni_throw(env, swig_c_str!("java/lang/Exception"), message)
 }
 
+/// Throw exception, created by constructor `(String message, error)`
+#[allow(dead_code)]
+fn jni_throw_with_error(
+    env: *mut JNIEnv,
+    class_name: *const ::std::os::raw::c_char,
+    constructor_sig: *const ::std::os::raw::c_char,
+    message: &str,
+    error: jobject,
+) {
+    let ex_class = unsafe { (**env).FindClass.unwrap()(env, class_name) };
+    if ex_class.is_null() {
+        error!(
+            "throw_exception: can not find exp class {:?}, msg {}",
+            unsafe { ::std::ffi::CStr::from_ptr(class_name) },
+            message
+        );
+        return;
+    }
+    let constructor = unsafe {
+        (**env).GetMethodID.unwrap()(env, ex_class, swig_c_str!("<init>"), constructor_sig)
+    };
+    assert!(
+        !constructor.is_null(),
+        "Can not find constructor of exception {:?}",
+        unsafe { ::std::ffi::CStr::from_ptr(class_name) }
+    );
+    let ex = swig_jni_new_exception(env, ex_class, constructor, message, error);
+    if ex.is_null() {
+        error!("NewObject for exception {} failed", message);
+        return;
+    }
+    let res = unsafe { (**env).Throw.unwrap()(env, ex as jthrowable) };
+    if res != 0 {
+        error!("Throw({}) failed", message);
+    }
+}
+
+/// Create exception via `constructor`, that has signature `(String message)`
+/// or `(String message, error)`
+#[allow(dead_code)]
+fn swig_jni_new_exception(
+    env: *mut JNIEnv,
+    ex_class: jclass,
+    constructor: jmethodID,
+    message: &str,
+    error: jobject,
+) -> jobject {
+    let c_message = ::std::ffi::CString::new(message).unwrap();
+    let jmessage = unsafe { (**env).NewStringUTF.unwrap()(env, c_message.as_ptr()) };
+    let args = [jvalue { l: jmessage }, jvalue { l: error }];
+    let ex = unsafe { (**env).NewObjectA.unwrap()(env, ex_class, constructor, args.as_ptr()) };
+    unsafe { (**env).DeleteLocalRef.unwrap()(env, jmessage) };
+    ex
+}
+
 #[swig_to_foreigner_hint = "T"]
 impl<T: SwigForeignClass> SwigFrom<T> for jobject {
     fn swig_from(x: T, env: *mut JNIEnv) -> Self {
-        object_to_jobject(x, <T>::jni_class_name(), env)
+        object_to_jobject(x, env)
     }
 }
 
 #[allow(dead_code)]
-fn object_to_jobject<T: SwigForeignClass>(
-    obj: T,
-    class_id: *const ::std::os::raw::c_char,
-    env: *mut JNIEnv,
-) -> jobject {
-    let jcls: jclass = unsafe { (**env).FindClass.unwrap()(env, class_id) };
-    assert!(!jcls.is_null(), "object_to_jobject: FindClass failed");
+fn object_to_jobject<T: SwigForeignClass>(obj: T, env: *mut JNIEnv) -> jobject {
+    let jcls: jclass = <T>::jni_class(env);
     let jobj: jobject = unsafe { (**env).AllocObject.unwrap()(env, jcls) };
     assert!(!jobj.is_null(), "object_to_jobject: AllocObject failed");
-    let field_id: jfieldID = unsafe {
-        (**env).GetFieldID.unwrap()(env, jcls, swig_c_str!("mNativeObj"), swig_c_str!("J"))
-    };
+    let field_id: jfieldID = <T>::jni_native_ptr_field(env);
     assert!(
         !field_id.is_null(),
         "object_to_jobject: GetFieldID(mNativeObj) failed"
@@ -335,19 +553,14 @@ fn object_to_jobject<T: SwigForeignClass>(
 #[swig_to_foreigner_hint = "T []"]
 impl<T: SwigForeignClass> SwigFrom<Vec<T>> for jobjectArray {
     fn swig_from(x: Vec<T>, env: *mut JNIEnv) -> Self {
-        vec_of_objects_to_jobject_array(x, <T>::jni_class_name(), env)
+        vec_of_objects_to_jobject_array(x, env)
     }
 }
 
 #[swig_from_foreigner_hint = "T []"]
 impl<T: SwigForeignClass + Clone> SwigInto<Vec<T>> for jobjectArray {
     fn swig_into(self, env: *mut JNIEnv) -> Vec<T> {
-        let class_id = <T>::jni_class_name();
-        let jcls: jclass = unsafe { (**env).FindClass.unwrap()(env, class_id) };
-        let field_id = swig_c_str!("mNativeObj");
-        let type_id = swig_c_str!("J");
-        let field_id: jfieldID =
-            unsafe { (**env).GetFieldID.unwrap()(env, jcls, field_id, type_id) };
+        let field_id: jfieldID = <T>::jni_native_ptr_field(env);
         assert!(!field_id.is_null());
 
         let length = unsafe { (**env).GetArrayLength.unwrap()(env, self) };
@@ -375,20 +588,16 @@ impl<T: SwigForeignClass + Clone> SwigInto<Vec<T>> for jobjectArray {
 #[allow(dead_code)]
 fn vec_of_objects_to_jobject_array<T: SwigForeignClass>(
     mut arr: Vec<T>,
-    class_id: *const ::std::os::raw::c_char,
     env: *mut JNIEnv,
 ) -> jobjectArray {
-    let jcls: jclass = unsafe { (**env).FindClass.unwrap()(env, class_id) };
-    assert!(!jcls.is_null());
+    let jcls: jclass = <T>::jni_class(env);
     //TODO: check for arr.len() -> jsize overflow
     let obj_arr: jobjectArray = unsafe {
         (**env).NewObjectArray.unwrap()(env, arr.len() as jsize, jcls, ::std::ptr::null_mut())
     };
     assert!(!obj_arr.is_null());
 
-    let field_id = swig_c_str!("mNativeObj");
-    let type_id = swig_c_str!("J");
-    let field_id: jfieldID = unsafe { (**env).GetFieldID.unwrap()(env, jcls, field_id, type_id) };
+    let field_id: jfieldID = <T>::jni_native_ptr_field(env);
     assert!(!field_id.is_null());
 
     for (i, r_obj) in arr.drain(..).enumerate() {
@@ -443,7 +652,7 @@ macro_rules! impl_jni_invalid_value {
 }
 
 impl_jni_invalid_value! {
-    jbyte jshort jint jlong jfloat jdouble
+    jboolean jchar jbyte jshort jint jlong jfloat jdouble
 }
 
 #[swig_generic_arg = "T"]
@@ -592,6 +801,7 @@ pub fn u64_to_jlong_checked(x: u64) -> jlong {
     }
 }
 
+#[swig_lossy = "values greater than i64::MAX are saturated"]
 impl SwigFrom<u64> for jlong {
     fn swig_from(x: u64, _: *mut JNIEnv) -> Self {
         u64_to_jlong_checked(x)
@@ -657,20 +867,8 @@ impl SwigFrom<SystemTime> for jobject {
         let mills: jlong = (since_unix_epoch.as_secs() * 1_000
             + (since_unix_epoch.subsec_nanos() / 1_000_000) as u64)
             as jlong;
-        let date_class: jclass =
-            unsafe { (**env).FindClass.unwrap()(env, swig_c_str!("java/util/Date")) };
-        assert!(
-            !date_class.is_null(),
-            "FindClass for `java/util/Date` failed"
-        );
-        let init: jmethodID = unsafe {
-            (**env).GetMethodID.unwrap()(
-                env,
-                date_class,
-                swig_c_str!("<init>"),
-                swig_c_str!("(J)V"),
-            )
-        };
+        let date_class: jclass = swig_jni_find_class!(env, "java/util/Date");
+        let init: jmethodID = swig_jni_get_id!(env, date_class, GetMethodID, "<init>", "(J)V");
         assert!(
             !init.is_null(),
             "java/util/Date GetMethodID for init failed"
@@ -709,9 +907,7 @@ impl<'a> SwigInto<&'a Path> for &'a str {
 #[swig_to_foreigner_hint = "java.lang.String []"]
 impl SwigInto<jobjectArray> for Vec<String> {
     fn swig_into(mut self, env: *mut JNIEnv) -> jobjectArray {
-        let class_id = swig_c_str!("java/lang/String");
-        let jcls: jclass = unsafe { (**env).FindClass.unwrap()(env, class_id) };
-        assert!(!jcls.is_null());
+        let jcls: jclass = swig_jni_find_class!(env, "java/lang/String");
         let obj_arr: jobjectArray = unsafe {
             (**env).NewObjectArray.unwrap()(env, self.len() as jsize, jcls, ::std::ptr::null_mut())
         };
@@ -732,6 +928,34 @@ impl SwigInto<jobjectArray> for Vec<String> {
     }
 }
 
+/// `java.util.ArrayList` with elements of array
+#[allow(dead_code)]
+fn swig_jni_array_to_list(arr: jobjectArray, env: *mut JNIEnv) -> jobject {
+    let list_class: jclass = swig_jni_find_class!(env, "java/util/ArrayList");
+    let constructor: jmethodID = swig_jni_get_id!(env, list_class, GetMethodID, "<init>", "(I)V");
+    assert!(!constructor.is_null(), "Can not find ArrayList constructor");
+    let add: jmethodID =
+        swig_jni_get_id!(env, list_class, GetMethodID, "add", "(Ljava/lang/Object;)Z");
+    assert!(!add.is_null(), "Can not find ArrayList.add");
+    let len = unsafe { (**env).GetArrayLength.unwrap()(env, arr) };
+    let args = [jvalue { i: len }];
+    let list = unsafe { (**env).NewObjectA.unwrap()(env, list_class, constructor, args.as_ptr()) };
+    assert!(!list.is_null(), "Can not create ArrayList");
+    for i in 0..len {
+        unsafe {
+            let x = (**env).GetObjectArrayElement.unwrap()(env, arr, i);
+            let args = [jvalue { l: x }];
+            (**env).CallBooleanMethodA.unwrap()(env, list, add, args.as_ptr());
+            if (**env).ExceptionCheck.unwrap()(env) != 0 {
+                panic!("ArrayList.add({}) failed", i);
+            }
+            (**env).DeleteLocalRef.unwrap()(env, x);
+        }
+    }
+    unsafe { (**env).DeleteLocalRef.unwrap()(env, arr) };
+    list
+}
+
 macro_rules! define_array_handling_code {
     ($([jni_arr_type = $jni_arr_type:ident,
         rust_arr_wrapper = $rust_arr_wrapper:ident,
@@ -966,6 +1190,94 @@ impl<'a> SwigInto<jbyteArray> for &'a [i8] {
     }
 }
 
+/// Memory of direct `java.nio.ByteBuffer`, without copying,
+/// valid while Java code keeps reference to buffer
+#[allow(dead_code)]
+unsafe fn swig_jni_direct_buffer<'a>(buf: jobject, env: *mut JNIEnv) -> &'a mut [u8] {
+    assert!(!buf.is_null(), "ByteBuffer is null");
+    let data = (**env).GetDirectBufferAddress.unwrap()(env, buf) as *mut u8;
+    assert!(
+        !data.is_null(),
+        "GetDirectBufferAddress failed, ByteBuffer is not direct"
+    );
+    let len = (**env).GetDirectBufferCapacity.unwrap()(env, buf);
+    assert!(len >= 0, "GetDirectBufferCapacity failed");
+    ::std::slice::from_raw_parts_mut(data, len as usize)
+}
+
+thread_local! {
+    static SWIG_JNI_CRITICAL_SECTIONS: ::std::cell::Cell<usize> = ::std::cell::Cell::new(0);
+}
+
+/// Called before JNI usage from Rust code,
+/// JNI calls are not allowed inside of `GetPrimitiveArrayCritical` section
+#[allow(dead_code)]
+fn swig_jni_check_no_critical_sections() {
+    assert_eq!(
+        0,
+        SWIG_JNI_CRITICAL_SECTIONS.with(|n| n.get()),
+        "JNI call inside of critical section of #[critical_arrays] method"
+    );
+}
+
+/// Primitive Java array for `#[critical_arrays]` method,
+/// `new` queries only length, so arrays should be acquired after
+/// all other JNI calls, critical section is finished on drop
+#[allow(dead_code)]
+struct JavaCriticalArray<T> {
+    env: *mut JNIEnv,
+    array: jarray,
+    len: jsize,
+    data: *mut T,
+    mode: jint,
+}
+
+#[allow(dead_code)]
+impl<T> JavaCriticalArray<T> {
+    fn new(env: *mut JNIEnv, array: jarray, mode: jint) -> JavaCriticalArray<T> {
+        assert!(!array.is_null());
+        let len = unsafe { (**env).GetArrayLength.unwrap()(env, array) };
+        assert!((len as u64) <= (usize::max_value() as u64));
+        JavaCriticalArray {
+            env,
+            array,
+            len,
+            data: ::std::ptr::null_mut(),
+            mode,
+        }
+    }
+
+    unsafe fn acquire(&mut self) -> &mut [T] {
+        assert!(self.data.is_null());
+        self.data = (**self.env).GetPrimitiveArrayCritical.unwrap()(
+            self.env,
+            self.array,
+            ::std::ptr::null_mut(),
+        ) as *mut T;
+        assert!(!self.data.is_null(), "GetPrimitiveArrayCritical failed");
+        SWIG_JNI_CRITICAL_SECTIONS.with(|n| n.set(n.get() + 1));
+        ::std::slice::from_raw_parts_mut(self.data, self.len as usize)
+    }
+}
+
+#[allow(dead_code)]
+impl<T> Drop for JavaCriticalArray<T> {
+    fn drop(&mut self) {
+        if self.data.is_null() {
+            return;
+        }
+        unsafe {
+            (**self.env).ReleasePrimitiveArrayCritical.unwrap()(
+                self.env,
+                self.array,
+                self.data as *mut ::std::os::raw::c_void,
+                self.mode,
+            )
+        };
+        SWIG_JNI_CRITICAL_SECTIONS.with(|n| n.set(n.get() - 1));
+    }
+}
+
 impl SwigDeref for JavaShortArray {
     type Target = [i16];
     fn swig_deref(&self) -> &Self::Target {
@@ -1095,6 +1407,7 @@ impl SwigFrom<usize> for jlong {
 }
 
 #[cfg(target_pointer_width = "64")]
+#[swig_lossy = "values greater than i64::MAX are saturated"]
 impl SwigFrom<usize> for jlong {
     fn swig_from(x: usize, _: *mut JNIEnv) -> Self {
         let x = x as u64;
@@ -1111,22 +1424,16 @@ impl<'a> SwigInto<String> for &'a str {
 #[swig_to_foreigner_hint = "java.util.OptionalDouble"]
 impl SwigFrom<Option<f64>> for jobject {
     fn swig_from(x: Option<f64>, env: *mut JNIEnv) -> Self {
-        let class: jclass =
-            unsafe { (**env).FindClass.unwrap()(env, swig_c_str!("java/util/OptionalDouble")) };
-        assert!(
-            !class.is_null(),
-            "FindClass for `java/util/OptionalDouble` failed"
-        );
+        let class: jclass = swig_jni_find_class!(env, "java/util/OptionalDouble");
         match x {
             Some(val) => {
-                let of_m: jmethodID = unsafe {
-                    (**env).GetStaticMethodID.unwrap()(
-                        env,
-                        class,
-                        swig_c_str!("of"),
-                        swig_c_str!("(D)Ljava/util/OptionalDouble;"),
-                    )
-                };
+                let of_m: jmethodID = swig_jni_get_id!(
+                    env,
+                    class,
+                    GetStaticMethodID,
+                    "of",
+                    "(D)Ljava/util/OptionalDouble;"
+                );
                 assert!(
                     !of_m.is_null(),
                     "java/util/OptionalDouble GetStaticMethodID for `of` failed"
@@ -1143,14 +1450,13 @@ impl SwigFrom<Option<f64>> for jobject {
                 ret
             }
             None => {
-                let empty_m: jmethodID = unsafe {
-                    (**env).GetStaticMethodID.unwrap()(
-                        env,
-                        class,
-                        swig_c_str!("empty"),
-                        swig_c_str!("()Ljava/util/OptionalDouble;"),
-                    )
-                };
+                let empty_m: jmethodID = swig_jni_get_id!(
+                    env,
+                    class,
+                    GetStaticMethodID,
+                    "empty",
+                    "()Ljava/util/OptionalDouble;"
+                );
                 assert!(
                     !empty_m.is_null(),
                     "java/util/OptionalDouble GetStaticMethodID for `empty` failed"
@@ -1179,18 +1485,10 @@ impl SwigFrom<jobject> for Option<f64> {
             if x.is_null() {
                 None
             } else {
-                let class: jclass =
-                    unsafe { (**env).FindClass.unwrap()(env, swig_c_str!("java/lang/Double")) };
-                assert!(!class.is_null(), "FindClass for `java/lang/Double` failed");
+                let class: jclass = swig_jni_find_class!(env, "java/lang/Double");
 
-                let double_value_m: jmethodID = unsafe {
-                    (**env).GetMethodID.unwrap()(
-                        env,
-                        class,
-                        swig_c_str!("doubleValue"),
-                        swig_c_str!("()D"),
-                    )
-                };
+                let double_value_m: jmethodID =
+                    swig_jni_get_id!(env, class, GetMethodID, "doubleValue", "()D");
                 assert!(
                     !double_value_m.is_null(),
                     "java/lang/Double GetMethodID for doubleValue failed"
@@ -1209,25 +1507,73 @@ impl SwigFrom<jobject> for Option<f64> {
     }
 }
 
+#[swig_to_foreigner_hint = "java.util.OptionalInt"]
+impl SwigFrom<Option<i32>> for jobject {
+    fn swig_from(x: Option<i32>, env: *mut JNIEnv) -> Self {
+        let class: jclass = swig_jni_find_class!(env, "java/util/OptionalInt");
+        match x {
+            Some(val) => {
+                let of_m: jmethodID = swig_jni_get_id!(
+                    env,
+                    class,
+                    GetStaticMethodID,
+                    "of",
+                    "(I)Ljava/util/OptionalInt;"
+                );
+                assert!(
+                    !of_m.is_null(),
+                    "java/util/OptionalInt GetStaticMethodID for `of` failed"
+                );
+                let ret = unsafe {
+                    let ret = (**env).CallStaticObjectMethod.unwrap()(env, class, of_m, val);
+                    if (**env).ExceptionCheck.unwrap()(env) != 0 {
+                        panic!("OptionalInt.of failed: catch exception");
+                    }
+                    ret
+                };
+
+                assert!(!ret.is_null());
+                ret
+            }
+            None => {
+                let empty_m: jmethodID = swig_jni_get_id!(
+                    env,
+                    class,
+                    GetStaticMethodID,
+                    "empty",
+                    "()Ljava/util/OptionalInt;"
+                );
+                assert!(
+                    !empty_m.is_null(),
+                    "java/util/OptionalInt GetStaticMethodID for `empty` failed"
+                );
+                let ret = unsafe {
+                    let ret = (**env).CallStaticObjectMethod.unwrap()(env, class, empty_m);
+                    if (**env).ExceptionCheck.unwrap()(env) != 0 {
+                        panic!("OptionalInt.empty failed: catch exception");
+                    }
+                    ret
+                };
+                assert!(!ret.is_null());
+                ret
+            }
+        }
+    }
+}
+
 #[swig_to_foreigner_hint = "java.util.OptionalLong"]
 impl SwigFrom<Option<i64>> for jobject {
     fn swig_from(x: Option<i64>, env: *mut JNIEnv) -> Self {
-        let class: jclass =
-            unsafe { (**env).FindClass.unwrap()(env, swig_c_str!("java/util/OptionalLong")) };
-        assert!(
-            !class.is_null(),
-            "FindClass for `java/util/OptionalLong` failed"
-        );
+        let class: jclass = swig_jni_find_class!(env, "java/util/OptionalLong");
         match x {
             Some(val) => {
-                let of_m: jmethodID = unsafe {
-                    (**env).GetStaticMethodID.unwrap()(
-                        env,
-                        class,
-                        swig_c_str!("of"),
-                        swig_c_str!("(J)Ljava/util/OptionalLong;"),
-                    )
-                };
+                let of_m: jmethodID = swig_jni_get_id!(
+                    env,
+                    class,
+                    GetStaticMethodID,
+                    "of",
+                    "(J)Ljava/util/OptionalLong;"
+                );
                 assert!(
                     !of_m.is_null(),
                     "java/util/OptionalLong GetStaticMethodID for `of` failed"
@@ -1244,14 +1590,13 @@ impl SwigFrom<Option<i64>> for jobject {
                 ret
             }
             None => {
-                let empty_m: jmethodID = unsafe {
-                    (**env).GetStaticMethodID.unwrap()(
-                        env,
-                        class,
-                        swig_c_str!("empty"),
-                        swig_c_str!("()Ljava/util/OptionalLong;"),
-                    )
-                };
+                let empty_m: jmethodID = swig_jni_get_id!(
+                    env,
+                    class,
+                    GetStaticMethodID,
+                    "empty",
+                    "()Ljava/util/OptionalLong;"
+                );
                 assert!(
                     !empty_m.is_null(),
                     "java/util/OptionalLong GetStaticMethodID for `empty` failed"
@@ -1280,18 +1625,10 @@ impl SwigFrom<jobject> for Option<i64> {
             if x.is_null() {
                 None
             } else {
-                let class: jclass =
-                    unsafe { (**env).FindClass.unwrap()(env, swig_c_str!("java/lang/Long")) };
-                assert!(!class.is_null(), "FindClass for `java/lang/Long` failed");
+                let class: jclass = swig_jni_find_class!(env, "java/lang/Long");
 
-                let long_value_m: jmethodID = unsafe {
-                    (**env).GetMethodID.unwrap()(
-                        env,
-                        class,
-                        swig_c_str!("longValue"),
-                        swig_c_str!("()J"),
-                    )
-                };
+                let long_value_m: jmethodID =
+                    swig_jni_get_id!(env, class, GetMethodID, "longValue", "()J");
                 assert!(
                     !long_value_m.is_null(),
                     "java/lang/Long GetMethodID for longValue failed"
@@ -1312,22 +1649,16 @@ impl SwigFrom<jobject> for Option<i64> {
 
 #[allow(dead_code)]
 fn opt_jobject_to_optional_class(x: Option<jobject>, env: *mut JNIEnv) -> jobject {
-    let class: jclass =
-        unsafe { (**env).FindClass.unwrap()(env, swig_c_str!("java/util/Optional")) };
-    assert!(
-        !class.is_null(),
-        "FindClass for `java/util/Optional` failed"
-    );
+    let class: jclass = swig_jni_find_class!(env, "java/util/Optional");
     match x {
         Some(obj) => {
-            let of_m: jmethodID = unsafe {
-                (**env).GetStaticMethodID.unwrap()(
-                    env,
-                    class,
-                    swig_c_str!("of"),
-                    swig_c_str!("(Ljava/lang/Object;)Ljava/util/Optional;"),
-                )
-            };
+            let of_m: jmethodID = swig_jni_get_id!(
+                env,
+                class,
+                GetStaticMethodID,
+                "of",
+                "(Ljava/lang/Object;)Ljava/util/Optional;"
+            );
             assert!(
                 !of_m.is_null(),
                 "java/util/Optional GetStaticMethodID for `of` failed"
@@ -1345,14 +1676,13 @@ fn opt_jobject_to_optional_class(x: Option<jobject>, env: *mut JNIEnv) -> jobjec
             ret
         }
         None => {
-            let empty_m: jmethodID = unsafe {
-                (**env).GetStaticMethodID.unwrap()(
-                    env,
-                    class,
-                    swig_c_str!("empty"),
-                    swig_c_str!("()Ljava/util/Optional;"),
-                )
-            };
+            let empty_m: jmethodID = swig_jni_get_id!(
+                env,
+                class,
+                GetStaticMethodID,
+                "empty",
+                "()Ljava/util/Optional;"
+            );
             assert!(
                 !empty_m.is_null(),
                 "java/util/Optional GetStaticMethodID for `empty` failed"
@@ -1373,7 +1703,7 @@ fn opt_jobject_to_optional_class(x: Option<jobject>, env: *mut JNIEnv) -> jobjec
 #[swig_to_foreigner_hint = "java.util.Optional<T>"]
 impl<T: SwigForeignClass> SwigFrom<Option<T>> for jobject {
     fn swig_from(x: Option<T>, env: *mut JNIEnv) -> Self {
-        let opt_jobject = x.map(|obj| object_to_jobject(obj, <T>::jni_class_name(), env));
+        let opt_jobject = x.map(|obj| object_to_jobject(obj, env));
         opt_jobject_to_optional_class(opt_jobject, env)
     }
 }
@@ -1396,3 +1726,293 @@ impl SwigInto<jobject> for Option<String> {
         opt_jobject_to_optional_class(opt_jobject, env)
     }
 }
+
+// `valueOf` of Java wrapper class, used to return `Option<T>` as nullable reference
+macro_rules! define_boxing_code {
+    ($([func = $func:ident, rust_type = $rust_type:ty, jvalue_field = $jvalue_field:ident,
+        class_name = $class_name:expr, value_of_sig = $value_of_sig:expr]),*) => {
+        $(
+            #[allow(dead_code)]
+            fn $func(x: $rust_type, env: *mut JNIEnv) -> jobject {
+                let class: jclass = swig_jni_find_class!(env, $class_name);
+                let value_of: jmethodID =
+                    swig_jni_get_id!(env, class, GetStaticMethodID, "valueOf", $value_of_sig);
+                assert!(
+                    !value_of.is_null(),
+                    concat!($class_name, " GetStaticMethodID for `valueOf` failed")
+                );
+                let args = [jvalue { $jvalue_field: x }];
+                let ret = unsafe {
+                    let ret =
+                        (**env).CallStaticObjectMethodA.unwrap()(env, class, value_of, args.as_ptr());
+                    if (**env).ExceptionCheck.unwrap()(env) != 0 {
+                        panic!(concat!($class_name, ".valueOf failed: catch exception"));
+                    }
+                    ret
+                };
+                assert!(!ret.is_null());
+                ret
+            }
+        )*
+    }
+}
+
+define_boxing_code!(
+    [
+        func = swig_jni_box_int,
+        rust_type = i32,
+        jvalue_field = i,
+        class_name = "java/lang/Integer",
+        value_of_sig = "(I)Ljava/lang/Integer;"
+    ],
+    [
+        func = swig_jni_box_long,
+        rust_type = i64,
+        jvalue_field = j,
+        class_name = "java/lang/Long",
+        value_of_sig = "(J)Ljava/lang/Long;"
+    ],
+    [
+        func = swig_jni_box_double,
+        rust_type = f64,
+        jvalue_field = d,
+        class_name = "java/lang/Double",
+        value_of_sig = "(D)Ljava/lang/Double;"
+    ]
+);
+
+/// Create Java `RustIterator` (`iter_class`) for Rust iterator,
+/// `next` returns next element converted to `jobject` or null after the end.
+/// If `stream` is true, then `java.util.stream.Stream` created by `RustIterator.stream`
+/// is returned
+#[allow(dead_code)]
+fn swig_jni_iterator_to_java(
+    iter_class: jclass,
+    next: Box<dyn FnMut(*mut JNIEnv) -> jobject>,
+    stream: bool,
+    env: *mut JNIEnv,
+) -> jobject {
+    let constructor: jmethodID = swig_jni_get_id!(env, iter_class, GetMethodID, "<init>", "(J)V");
+    assert!(
+        !constructor.is_null(),
+        "Can not find constructor of RustIterator"
+    );
+    let next: *mut Box<dyn FnMut(*mut JNIEnv) -> jobject> = Box::into_raw(Box::new(next));
+    let args = [jvalue { j: next as jlong }];
+    let iter = unsafe { (**env).NewObjectA.unwrap()(env, iter_class, constructor, args.as_ptr()) };
+    assert!(!iter.is_null(), "Can not create RustIterator");
+    if !stream {
+        return iter;
+    }
+    let stream_m: jmethodID = swig_jni_get_id!(
+        env,
+        iter_class,
+        GetMethodID,
+        "stream",
+        "()Ljava/util/stream/Stream;"
+    );
+    assert!(!stream_m.is_null(), "Can not find RustIterator.stream");
+    unsafe {
+        let ret = (**env).CallObjectMethodA.unwrap()(env, iter, stream_m, ::std::ptr::null());
+        if (**env).ExceptionCheck.unwrap()(env) != 0 {
+            panic!("RustIterator.stream failed: catch exception");
+        }
+        (**env).DeleteLocalRef.unwrap()(env, iter);
+        ret
+    }
+}
+
+/// `RustIterator.do_next`
+#[allow(dead_code)]
+fn swig_jni_iterator_next(iter: jlong, env: *mut JNIEnv) -> jobject {
+    let next: &mut Box<dyn FnMut(*mut JNIEnv) -> jobject> = unsafe {
+        jlong_to_pointer::<Box<dyn FnMut(*mut JNIEnv) -> jobject>>(iter)
+            .as_mut()
+            .unwrap()
+    };
+    next(env)
+}
+
+/// `RustIterator.do_delete`
+#[allow(dead_code)]
+fn swig_jni_iterator_delete(iter: jlong) {
+    let next: *mut Box<dyn FnMut(*mut JNIEnv) -> jobject> =
+        unsafe { jlong_to_pointer::<Box<dyn FnMut(*mut JNIEnv) -> jobject>>(iter) };
+    assert!(!next.is_null());
+    drop(unsafe { Box::from_raw(next) });
+}
+
+/// Java `CompletableFuture` of `#[completable_future]` method,
+/// can be completed from any thread. If it is dropped without result
+/// (for example because of panic), then future is completed exceptionally
+#[allow(dead_code)]
+struct JavaFutureCompletion {
+    future: JavaCallback,
+    exception_class: JavaCallback,
+    exception_constructor: jmethodID,
+    completed: bool,
+}
+
+unsafe impl Send for JavaFutureCompletion {}
+
+/// Create `CompletableFuture` and its completion, class of exception for `Err`
+/// is resolved here, because `FindClass` for not system classes may fail
+/// on thread attached by Rust code
+#[allow(dead_code)]
+fn swig_jni_completable_future(
+    env: *mut JNIEnv,
+    exception_class_name: *const ::std::os::raw::c_char,
+    exception_constructor_sig: *const ::std::os::raw::c_char,
+) -> (jobject, JavaFutureCompletion) {
+    let class: jclass = swig_jni_find_class!(env, "java/util/concurrent/CompletableFuture");
+    let constructor: jmethodID = swig_jni_get_id!(env, class, GetMethodID, "<init>", "()V");
+    assert!(
+        !constructor.is_null(),
+        "Can not find constructor of CompletableFuture"
+    );
+    let future =
+        unsafe { (**env).NewObjectA.unwrap()(env, class, constructor, ::std::ptr::null()) };
+    assert!(!future.is_null(), "Can not create CompletableFuture");
+    let ex_class = unsafe { (**env).FindClass.unwrap()(env, exception_class_name) };
+    assert!(
+        !ex_class.is_null(),
+        "Can not find exception class {:?}",
+        unsafe { ::std::ffi::CStr::from_ptr(exception_class_name) }
+    );
+    let exception_constructor = unsafe {
+        (**env).GetMethodID.unwrap()(
+            env,
+            ex_class,
+            swig_c_str!("<init>"),
+            exception_constructor_sig,
+        )
+    };
+    assert!(
+        !exception_constructor.is_null(),
+        "Can not find constructor of exception {:?}",
+        unsafe { ::std::ffi::CStr::from_ptr(exception_class_name) }
+    );
+    let completion = JavaFutureCompletion {
+        future: JavaCallback::new(future, env),
+        exception_class: JavaCallback::new(ex_class, env),
+        exception_constructor,
+        completed: false,
+    };
+    unsafe { (**env).DeleteLocalRef.unwrap()(env, ex_class) };
+    (future, completion)
+}
+
+#[allow(dead_code)]
+impl JavaFutureCompletion {
+    /// Complete future with `Ok` converted by `ok_to_jobject`, or exceptionally
+    /// with message of `Err` and error object created by `err_to_jobject`
+    fn complete<T, E, OkConv, ErrConv>(
+        mut self,
+        result: Result<T, E>,
+        ok_to_jobject: OkConv,
+        err_to_jobject: ErrConv,
+    ) where
+        E: ToString,
+        OkConv: FnOnce(T, *mut JNIEnv) -> jobject,
+        ErrConv: FnOnce(E, *mut JNIEnv) -> jobject,
+    {
+        self.completed = true;
+        let env = self.future.get_jni_env();
+        let env = match env.env {
+            Some(env) => env,
+            None => {
+                error!("JavaFutureCompletion::complete failed, can not get JNIEnv");
+                return;
+            }
+        };
+        let _frame = JniLocalFrame::new(env, 16);
+        match result {
+            Ok(x) => {
+                let class: jclass =
+                    swig_jni_find_class!(env, "java/util/concurrent/CompletableFuture");
+                let complete_m: jmethodID =
+                    swig_jni_get_id!(env, class, GetMethodID, "complete", "(Ljava/lang/Object;)Z");
+                assert!(
+                    !complete_m.is_null(),
+                    "Can not find CompletableFuture.complete"
+                );
+                let obj = ok_to_jobject(x, env);
+                let args = [jvalue { l: obj }];
+                unsafe {
+                    (**env).CallBooleanMethodA.unwrap()(
+                        env,
+                        self.future.this,
+                        complete_m,
+                        args.as_ptr(),
+                    );
+                    if (**env).ExceptionCheck.unwrap()(env) != 0 {
+                        error!("CompletableFuture.complete failed: catch exception");
+                        (**env).ExceptionClear.unwrap()(env);
+                    }
+                    if !obj.is_null() {
+                        (**env).DeleteLocalRef.unwrap()(env, obj);
+                    }
+                }
+            }
+            Err(err) => {
+                let msg = err.to_string();
+                let error = err_to_jobject(err, env);
+                self.complete_exceptionally(env, &msg, error);
+            }
+        }
+    }
+
+    fn complete_exceptionally(&self, env: *mut JNIEnv, message: &str, error: jobject) {
+        let ex = swig_jni_new_exception(
+            env,
+            self.exception_class.this as jclass,
+            self.exception_constructor,
+            message,
+            error,
+        );
+        assert!(!ex.is_null(), "Can not create exception {}", message);
+        let class: jclass = swig_jni_find_class!(env, "java/util/concurrent/CompletableFuture");
+        let complete_m: jmethodID = swig_jni_get_id!(
+            env,
+            class,
+            GetMethodID,
+            "completeExceptionally",
+            "(Ljava/lang/Throwable;)Z"
+        );
+        assert!(
+            !complete_m.is_null(),
+            "Can not find CompletableFuture.completeExceptionally"
+        );
+        let args = [jvalue { l: ex }];
+        unsafe {
+            (**env).CallBooleanMethodA.unwrap()(env, self.future.this, complete_m, args.as_ptr());
+            if (**env).ExceptionCheck.unwrap()(env) != 0 {
+                error!("CompletableFuture.completeExceptionally failed: catch exception");
+                (**env).ExceptionClear.unwrap()(env);
+            }
+            (**env).DeleteLocalRef.unwrap()(env, ex);
+            if !error.is_null() {
+                (**env).DeleteLocalRef.unwrap()(env, error);
+            }
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Drop for JavaFutureCompletion {
+    fn drop(&mut self) {
+        if self.completed {
+            return;
+        }
+        let env = self.future.get_jni_env();
+        if let Some(env) = env.env {
+            self.complete_exceptionally(
+                env,
+                "Rust code dropped completion of future without result",
+                ::std::ptr::null_mut(),
+            );
+        } else {
+            error!("JavaFutureCompletion::drop failed, can not get JNIEnv");
+        }
+    }
+}
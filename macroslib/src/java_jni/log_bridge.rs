@@ -0,0 +1,155 @@
+use std::io::Write;
+
+use crate::{
+    error::{DiagnosticError, Result},
+    file_cache::FileWriteCache,
+    JavaConfig, JavaLogBackend,
+};
+
+/// Generated Java class, that receives records of Rust `log` crate
+pub(in crate::java_jni) const LOG_CLASS: &str = "RustLog";
+
+/// Body of `RustLog.log(int level, String target, String message)`,
+/// levels are the same as values of `log::Level`
+fn log_method_code(backend: JavaLogBackend) -> &'static str {
+    match backend {
+        JavaLogBackend::AndroidLog => {
+            r#"        int priority;
+        switch (level) {
+        case ERROR:
+            priority = android.util.Log.ERROR;
+            break;
+        case WARN:
+            priority = android.util.Log.WARN;
+            break;
+        case INFO:
+            priority = android.util.Log.INFO;
+            break;
+        case DEBUG:
+            priority = android.util.Log.DEBUG;
+            break;
+        default:
+            priority = android.util.Log.VERBOSE;
+            break;
+        }
+        android.util.Log.println(priority, target, message);
+"#
+        }
+        JavaLogBackend::JavaUtilLogging => {
+            r#"        java.util.logging.Level julLevel;
+        switch (level) {
+        case ERROR:
+            julLevel = java.util.logging.Level.SEVERE;
+            break;
+        case WARN:
+            julLevel = java.util.logging.Level.WARNING;
+            break;
+        case INFO:
+            julLevel = java.util.logging.Level.INFO;
+            break;
+        case DEBUG:
+            julLevel = java.util.logging.Level.FINE;
+            break;
+        default:
+            julLevel = java.util.logging.Level.FINEST;
+            break;
+        }
+        java.util.logging.Logger.getLogger(target.replace("::", ".")).log(julLevel, message);
+"#
+        }
+        JavaLogBackend::Slf4j => {
+            r#"        org.slf4j.Logger logger = org.slf4j.LoggerFactory.getLogger(target.replace("::", "."));
+        switch (level) {
+        case ERROR:
+            logger.error(message);
+            break;
+        case WARN:
+            logger.warn(message);
+            break;
+        case INFO:
+            logger.info(message);
+            break;
+        case DEBUG:
+            logger.debug(message);
+            break;
+        default:
+            logger.trace(message);
+            break;
+        }
+"#
+        }
+    }
+}
+
+fn backend_name(backend: JavaLogBackend) -> &'static str {
+    match backend {
+        JavaLogBackend::AndroidLog => "android.util.Log",
+        JavaLogBackend::JavaUtilLogging => "java.util.logging",
+        JavaLogBackend::Slf4j => "SLF4J",
+    }
+}
+
+/// Write Java code of `RustLog`, `log` is called by Rust code via JNI
+pub(in crate::java_jni) fn generate_java_code_for_log_bridge(
+    cfg: &JavaConfig,
+    backend: JavaLogBackend,
+) -> Result<()> {
+    let path = cfg.output_dir.join(format!("{}.java", LOG_CLASS));
+    let mut file = FileWriteCache::new(&path);
+    write!(
+        file,
+        r#"// Automaticaly generated by rust_swig
+package {package_name};
+
+/**
+ * Routes records of Rust `log` crate to {backend_name}.
+ * {{@link #init()}} should be called once, after loading of native library
+ */
+public final class {class_name} {{
+    public static final int OFF = 0;
+    public static final int ERROR = 1;
+    public static final int WARN = 2;
+    public static final int INFO = 3;
+    public static final int DEBUG = 4;
+    public static final int TRACE = 5;
+
+    private {class_name}() {{
+    }}
+
+    /**
+     * Route records with level {{@link #INFO}} and above
+     */
+    public static void init() {{
+        init(INFO);
+    }}
+
+    /**
+     * Route records with level up to {{@code maxLevel}},
+     * following calls only change maximum level
+     * @param maxLevel one of {{@link #OFF}}, {{@link #ERROR}}, {{@link #WARN}},
+     *                 {{@link #INFO}}, {{@link #DEBUG}} or {{@link #TRACE}}
+     */
+    public static void init(int maxLevel) {{
+        do_init(maxLevel);
+    }}
+
+    private static void log(int level, String target, String message) {{
+{log_code}    }}
+
+    private static native void do_init(int maxLevel);
+}}
+"#,
+        package_name = cfg.package_name,
+        backend_name = backend_name(backend),
+        class_name = LOG_CLASS,
+        log_code = log_method_code(backend),
+    )
+    .and_then(|_| file.update_file_if_necessary())
+    .map_err(|err| {
+        DiagnosticError::new_without_src_info(format!(
+            "write to {} failed: {}",
+            path.display(),
+            err
+        ))
+    })
+}
@@ -8,8 +8,7 @@ use crate::{
     source_registry::SourceId,
     typemap::{
         ast::{if_option_return_some_type, normalize_ty_lifetimes},
-        ty::RustType,
-        ForeignTypeInfo, FROM_VAR_TEMPLATE, TO_VAR_TEMPLATE,
+        ForeignTypeInfo, RustTypeIdx, FROM_VAR_TEMPLATE, TO_VAR_TEMPLATE,
     },
     types::{ForeignEnumInfo, ForeignerClassInfo},
     TypeMap,
@@ -17,20 +16,20 @@ use crate::{
 
 pub(in crate::java_jni) fn map_type(
     conv_map: &mut TypeMap,
-    arg_ty: &RustType,
+    arg_ty: RustTypeIdx,
     direction: Direction,
     arg_ty_span: SourceIdSpan,
 ) -> Result<JavaForeignTypeInfo> {
     if direction == Direction::Incoming {
-        if let Some(fti) = special_type(conv_map, &arg_ty, arg_ty_span)? {
+        if let Some(fti) = special_type(conv_map, arg_ty, arg_ty_span)? {
             return Ok(fti);
         }
     }
 
-    let fti = {
+    let mut fti = {
         let fti = conv_map
             .map_through_conversation_to_foreign(
-                &arg_ty,
+                arg_ty,
                 direction,
                 arg_ty_span,
                 calc_this_type_for_method,
@@ -45,7 +44,7 @@ pub(in crate::java_jni) fn map_type(
                             Direction::Outgoing => "=>",
                             Direction::Incoming => "<=",
                         },
-                        arg_ty,
+                        conv_map[arg_ty],
                     ),
                 )
             })?;
@@ -66,14 +65,17 @@ pub(in crate::java_jni) fn map_type(
                     .rust_ty
             }
         };
-        ForeignTypeInfo {
+        let provides_by_module = ftype.provides_by_module.clone();
+        let mut fti: JavaForeignTypeInfo = ForeignTypeInfo {
             name: ftype.name.typename.clone(),
-            correspoding_rust_type: conv_map[rtype_idx].clone(),
+            correspoding_rust_type: rtype_idx,
         }
+        .into();
+        fti.provides_by_module = provides_by_module;
+        fti
     };
-    let mut fti: JavaForeignTypeInfo = fti.into();
     if !is_primitive_type(&fti.base.name) {
-        fti.annotation = Some(if if_option_return_some_type(arg_ty).is_none() {
+        fti.annotation = Some(if if_option_return_some_type(&conv_map[arg_ty]).is_none() {
             NullAnnotation::NonNull
         } else {
             NullAnnotation::Nullable
@@ -84,27 +86,27 @@ pub(in crate::java_jni) fn map_type(
 
 pub(in crate::java_jni) fn special_type(
     conv_map: &mut TypeMap,
-    arg_ty: &RustType,
+    arg_ty: RustTypeIdx,
     arg_ty_span: SourceIdSpan,
 ) -> Result<Option<JavaForeignTypeInfo>> {
     let foreign_class_trait = "SwigForeignClass";
 
     trace!(
         "special_type: check is arg.ty({}) implements foreign_class_trait",
-        arg_ty
+        conv_map[arg_ty]
     );
 
     if let Some(foreign_class_this_ty) = conv_map.is_ty_implements(arg_ty, foreign_class_trait) {
         let foreigner_class = conv_map
             .find_foreigner_class_with_such_this_type(
-                &foreign_class_this_ty.ty,
+                &conv_map[foreign_class_this_ty].ty,
                 calc_this_type_for_method,
             )
             .ok_or_else(|| {
                 DiagnosticError::new(
                     arg_ty_span.0,
                     arg_ty_span.1,
-                    format!("Can not find foreigner_class for '{}'", arg_ty),
+                    format!("Can not find foreigner_class for '{}'", conv_map[arg_ty]),
                 )
             })?;
         let converter = calc_converter_for_foreign_class_arg(conv_map, foreigner_class, arg_ty);
@@ -112,16 +114,37 @@ pub(in crate::java_jni) fn special_type(
     }
     trace!(
         "special_type: check is arg.ty({}) implements exported enum",
-        arg_ty
+        conv_map[arg_ty]
     );
     if let Some(foreign_enum) = conv_map.is_this_exported_enum(arg_ty) {
         let converter = calc_converter_for_enum(conv_map, foreign_enum);
         return Ok(Some(converter));
     }
 
+    trace!(
+        "special_type: check is arg.ty({}) Java record",
+        conv_map[arg_ty]
+    );
+    if conv_map
+        .is_ty_implements(arg_ty, "SwigJavaRecord")
+        .is_some()
+    {
+        if let syn::Type::Reference(_) = conv_map[arg_ty].ty {
+            return Err(DiagnosticError::new2(
+                arg_ty_span,
+                format!(
+                    "Java record '{}' can be passed only by value",
+                    conv_map[arg_ty]
+                ),
+            ));
+        }
+        // converted via types map
+        return Ok(None);
+    }
+
     trace!(
         "special_type: check is arg.ty({}) self type of foreign class",
-        arg_ty
+        conv_map[arg_ty]
     );
     if let Some(foreign_class) = conv_map.find_foreigner_class_with_such_self_type(arg_ty, true) {
         let jlong_ti = conv_map.ty_to_rust_type(&parse_type! { jlong });
@@ -139,27 +162,253 @@ pub(in crate::java_jni) fn special_type(
                 java_transition_type: "long".into(),
             }),
             annotation: Some(NullAnnotation::NonNull),
+            provides_by_module: Vec::new(),
         };
         return Ok(Some(converter));
     }
 
-    if let Some(ty) = if_option_return_some_type(arg_ty) {
+    if let Some(ty) = if_option_return_some_type(&conv_map[arg_ty]) {
         return handle_option_type_in_input(conv_map, &ty, arg_ty_span.0);
     }
 
-    trace!("special_type: oridinary type {}", arg_ty);
+    trace!("special_type: oridinary type {}", conv_map[arg_ty]);
     Ok(None)
 }
 
+/// `&[u8]` or `&mut [u8]` argument of `#[direct_buffer]` method,
+/// memory of `java.nio.ByteBuffer` is used without copying
+pub(in crate::java_jni) fn direct_buffer_type(
+    conv_map: &mut TypeMap,
+    arg_ty: RustTypeIdx,
+) -> Option<JavaForeignTypeInfo> {
+    match conv_map[arg_ty].ty {
+        syn::Type::Reference(syn::TypeReference { ref elem, .. })
+            if normalize_ty_lifetimes(elem) == normalize_ty_lifetimes(&parse_type! { [u8] }) => {}
+        _ => return None,
+    }
+    let buffer_ti = conv_map.find_or_alloc_rust_type_with_suffix(
+        &parse_type! { jobject },
+        "java.nio.ByteBuffer",
+        SourceId::none(),
+    );
+    let code = format!(
+        r#"
+        let {to_var}: {arg_ty} = unsafe {{ swig_jni_direct_buffer({from_var}, env) }};
+"#,
+        to_var = TO_VAR_TEMPLATE,
+        from_var = FROM_VAR_TEMPLATE,
+        arg_ty = conv_map[arg_ty].normalized_name,
+    );
+    conv_map.add_conversation_rule(buffer_ti, arg_ty, code.into());
+    Some(JavaForeignTypeInfo {
+        base: ForeignTypeInfo {
+            name: "java.nio.ByteBuffer".into(),
+            correspoding_rust_type: buffer_ti,
+        },
+        java_converter: None,
+        annotation: Some(NullAnnotation::NonNull),
+        provides_by_module: Vec::new(),
+    })
+}
+
+/// `java.util.List<T>` instead of array `T []` of objects, returned from Rust
+pub(in crate::java_jni) fn java_list_type(
+    conv_map: &mut TypeMap,
+    array_fti: JavaForeignTypeInfo,
+) -> JavaForeignTypeInfo {
+    let elem_name = {
+        let name = array_fti.base.name.as_str();
+        if conv_map[array_fti.base.correspoding_rust_type].typename() != "jobjectArray"
+            || !name.ends_with("[]")
+        {
+            return array_fti;
+        }
+        name[..name.len() - 2].trim().to_string()
+    };
+    let list_name = format!("java.util.List<{}>", elem_name);
+    let list_ti = conv_map.find_or_alloc_rust_type_with_suffix(
+        &parse_type! { jobject },
+        &list_name,
+        SourceId::none(),
+    );
+    conv_map.add_conversation_rule(
+        array_fti.base.correspoding_rust_type,
+        list_ti,
+        format!(
+            r#"
+        let {to_var}: jobject = swig_jni_array_to_list({from_var}, env);
+"#,
+            to_var = TO_VAR_TEMPLATE,
+            from_var = FROM_VAR_TEMPLATE,
+        )
+        .into(),
+    );
+    JavaForeignTypeInfo {
+        base: ForeignTypeInfo {
+            name: list_name.into(),
+            correspoding_rust_type: list_ti,
+        },
+        java_converter: None,
+        annotation: array_fti.annotation,
+        provides_by_module: array_fti.provides_by_module,
+    }
+}
+
+/// Rust type, Java wrapper class and function from `jni-include.rs`
+/// to box value, for `Option<T>` returned as nullable reference and iterators
+const BOXED_PRIMITIVE_TYPES: [(&str, &str, &str); 3] = [
+    ("i32", "Integer", "swig_jni_box_int"),
+    ("i64", "Long", "swig_jni_box_long"),
+    ("f64", "Double", "swig_jni_box_double"),
+];
+
+/// Java class and code that converts `x` of Rust type `ty` into `jobject`,
+/// if `ty` is exported class, `String`, `i32`, `i64` or `f64`
+pub(in crate::java_jni) fn jobject_conv_code(
+    conv_map: &TypeMap,
+    ty: RustTypeIdx,
+) -> Option<(String, String)> {
+    let ty_name = &conv_map[ty].normalized_name;
+    if ty_name == "String" {
+        Some((
+            "String".to_string(),
+            "from_std_string_jstring(x, env)".to_string(),
+        ))
+    } else if let Some(&(_, java_name, box_func)) =
+        BOXED_PRIMITIVE_TYPES.iter().find(|x| x.0 == ty_name)
+    {
+        Some((java_name.to_string(), format!("{}(x, env)", box_func)))
+    } else if conv_map.is_ty_implements(ty, "SwigForeignClass").is_some() {
+        let class = conv_map.find_foreigner_class_with_such_self_type(ty, false)?;
+        Some((
+            class.name.to_string(),
+            "object_to_jobject(x, env)".to_string(),
+        ))
+    } else {
+        None
+    }
+}
+
+/// `Option<T>` as nullable reference instead of `java.util.Optional`,
+/// if `T` is exported class, `String`, `i32`, `i64` or `f64`
+pub(in crate::java_jni) fn java_nullable_type(
+    conv_map: &mut TypeMap,
+    ret_ty: RustTypeIdx,
+) -> Option<JavaForeignTypeInfo> {
+    let some_ty = if_option_return_some_type(&conv_map[ret_ty])?;
+    let some_ty = conv_map.find_or_alloc_rust_type_no_src_id(&some_ty);
+    let (java_name, conv_some) = jobject_conv_code(conv_map, some_ty)?;
+    let nullable_ti = conv_map.find_or_alloc_rust_type_with_suffix(
+        &parse_type! { jobject },
+        &format!("nullable {}", java_name),
+        SourceId::none(),
+    );
+    conv_map.add_conversation_rule(
+        ret_ty,
+        nullable_ti,
+        format!(
+            r#"
+        let {to_var}: jobject = match {from_var} {{
+            Some(x) => {conv_some},
+            None => ::std::ptr::null_mut(),
+        }};
+"#,
+            to_var = TO_VAR_TEMPLATE,
+            from_var = FROM_VAR_TEMPLATE,
+            conv_some = conv_some,
+        )
+        .into(),
+    );
+    Some(JavaForeignTypeInfo {
+        base: ForeignTypeInfo {
+            name: java_name.into(),
+            correspoding_rust_type: nullable_ti,
+        },
+        java_converter: None,
+        annotation: Some(NullAnnotation::Nullable),
+        provides_by_module: Vec::new(),
+    })
+}
+
+/// Rust element type, JNI array type and Java type of arrays
+/// that can be used by `#[critical_arrays]` method
+const CRITICAL_ARRAY_TYPES: [(&str, &str, &str); 6] = [
+    ("i8", "jbyteArray", "byte []"),
+    ("i16", "jshortArray", "short []"),
+    ("i32", "jintArray", "int []"),
+    ("i64", "jlongArray", "long []"),
+    ("f32", "jfloatArray", "float []"),
+    ("f64", "jdoubleArray", "double []"),
+];
+
+/// Index in `CRITICAL_ARRAY_TYPES` and mutability of `&[T]` or `&mut [T]`
+pub(in crate::java_jni) fn critical_array_elem(ty: &Type) -> Option<(usize, bool)> {
+    let (elem, mutable) = match *ty {
+        syn::Type::Reference(syn::TypeReference {
+            ref elem,
+            ref mutability,
+            ..
+        }) => match **elem {
+            syn::Type::Slice(syn::TypeSlice { ref elem, .. }) => (elem, mutability.is_some()),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let elem = normalize_ty_lifetimes(elem);
+    CRITICAL_ARRAY_TYPES
+        .iter()
+        .position(|x| x.0 == elem)
+        .map(|idx| (idx, mutable))
+}
+
+/// `&[T]` or `&mut [T]` argument of `#[critical_arrays]` method,
+/// only length of array is queried during conversation, elements are
+/// acquired with `GetPrimitiveArrayCritical` just before call
+pub(in crate::java_jni) fn critical_array_type(
+    conv_map: &mut TypeMap,
+    arg_ty: RustTypeIdx,
+) -> Option<JavaForeignTypeInfo> {
+    let (idx, mutable) = critical_array_elem(&conv_map[arg_ty].ty)?;
+    let (elem, jni_ty, java_ty) = CRITICAL_ARRAY_TYPES[idx];
+    let array_ti = conv_map.find_or_alloc_rust_type_with_suffix(
+        &syn::parse_str(jni_ty).expect("jni array type should be valid Rust type"),
+        "critical",
+        SourceId::none(),
+    );
+    conv_map.add_conversation_rule(
+        array_ti,
+        arg_ty,
+        format!(
+            r#"
+        let mut {to_var}: JavaCriticalArray<{elem}> = JavaCriticalArray::new(env, {from_var}, {mode});
+"#,
+            to_var = TO_VAR_TEMPLATE,
+            from_var = FROM_VAR_TEMPLATE,
+            elem = elem,
+            mode = if mutable { "0" } else { "JNI_ABORT as jint" },
+        )
+        .into(),
+    );
+    Some(JavaForeignTypeInfo {
+        base: ForeignTypeInfo {
+            name: java_ty.into(),
+            correspoding_rust_type: array_ti,
+        },
+        java_converter: None,
+        annotation: Some(NullAnnotation::NonNull),
+        provides_by_module: Vec::new(),
+    })
+}
+
 fn calc_converter_for_foreign_class_arg(
     conv_map: &TypeMap,
     foreigner_class: &ForeignerClassInfo,
-    arg_ty: &RustType,
+    arg_ty: RustTypeIdx,
 ) -> JavaForeignTypeInfo {
     let this_ty = calc_this_type_for_method(conv_map, foreigner_class).unwrap();
     let this_ty = conv_map.ty_to_rust_type(&this_ty);
 
-    let converter = if this_ty.normalized_name == arg_ty.normalized_name {
+    let converter = if this_ty == arg_ty {
         format!(
             r#"
         long {to_var} = {from_var}.mNativeObj;
@@ -168,8 +417,11 @@ fn calc_converter_for_foreign_class_arg(
             to_var = TO_VAR_TEMPLATE,
             from_var = FROM_VAR_TEMPLATE
         )
-    } else if let syn::Type::Reference(syn::TypeReference { ref elem, .. }) = arg_ty.ty {
-        assert_eq!(normalize_ty_lifetimes(elem), this_ty.normalized_name);
+    } else if let syn::Type::Reference(syn::TypeReference { ref elem, .. }) = conv_map[arg_ty].ty {
+        assert_eq!(
+            normalize_ty_lifetimes(elem),
+            conv_map[this_ty].normalized_name
+        );
         format!(
             r#"
         long {to_var} = {from_var}.mNativeObj;
@@ -191,6 +443,7 @@ fn calc_converter_for_foreign_class_arg(
             converter,
         }),
         annotation: Some(NullAnnotation::NonNull),
+        provides_by_module: Vec::new(),
     }
 }
 
@@ -216,6 +469,7 @@ fn calc_converter_for_enum(
             converter,
         }),
         annotation: Some(NullAnnotation::NonNull),
+        provides_by_module: Vec::new(),
     }
 }
 
@@ -225,8 +479,14 @@ fn handle_option_type_in_input(
     arg_src_id: SourceId,
 ) -> Result<Option<JavaForeignTypeInfo>> {
     let opt_inside_rust_ty = conv_map.find_or_alloc_rust_type(opt_inside_ty, arg_src_id);
+    if conv_map
+        .is_ty_implements(opt_inside_rust_ty, "SwigJavaRecord")
+        .is_some()
+    {
+        return Ok(None);
+    }
     if let Some(fclass) =
-        conv_map.find_foreigner_class_with_such_self_type(&opt_inside_rust_ty, false)
+        conv_map.find_foreigner_class_with_such_self_type(opt_inside_rust_ty, false)
     {
         let jlong_ti = conv_map.ty_to_rust_type(&parse_type! { jlong });
         Ok(Some(JavaForeignTypeInfo {
@@ -249,13 +509,14 @@ fn handle_option_type_in_input(
                 java_transition_type: "long".into(),
             }),
             annotation: Some(NullAnnotation::Nullable),
+            provides_by_module: Vec::new(),
         }))
     } else {
         Ok(None)
     }
 }
 
-fn is_primitive_type(type_name: &str) -> bool {
+pub(in crate::java_jni) fn is_primitive_type(type_name: &str) -> bool {
     match type_name {
         "void" | "boolean" | "byte" | "short" | "int" | "long" | "float" | "double" => true,
         _ => false,
@@ -1,5 +1,15 @@
+mod android_module;
+mod api_level;
+mod exceptions;
+mod futures;
+mod iterators;
 mod java_code;
+mod log_bridge;
 mod map_type;
+mod module_info;
+mod native_library;
+mod panics;
+mod proguard;
 mod rust_code;
 
 use std::fmt;
@@ -7,29 +17,35 @@ use std::fmt;
 use log::debug;
 use petgraph::Direction;
 use proc_macro2::TokenStream;
+use rustc_hash::FxHashMap;
 use smol_str::SmolStr;
 use syn::{parse_quote, spanned::Spanned, Type};
 
-use self::map_type::map_type;
+use self::{exceptions::JavaException, map_type::map_type};
 use crate::{
-    error::{DiagnosticError, Result},
+    api_manifest::{ApiManifest, ForeignFnTypes},
+    catch_panics, doc_links,
+    error::{collect_error, DiagnosticError, Result},
+    name_collisions::NameRegistry,
+    profiler::{self, Stage},
     source_registry::SourceId,
     typemap::ast::{
         fn_arg_type, if_result_return_ok_err_types, if_ty_result_return_ok_type,
-        parse_ty_with_given_span_checked, DisplayToTokens, TypeName,
+        if_vec_return_elem_type, normalize_ty_lifetimes, parse_ty_with_given_span_checked,
+        DisplayToTokens, TypeName,
     },
     typemap::{
-        ty::RustType,
         utils::{
             convert_to_heap_pointer, unpack_from_heap_pointer, ForeignMethodSignature,
             ForeignTypeInfoT,
         },
-        ForeignTypeInfo, FROM_VAR_TEMPLATE, TO_VAR_TEMPLATE,
+        ForeignTypeInfo, RustTypeIdx, FROM_VAR_TEMPLATE, TO_VAR_TEMPLATE,
     },
     types::{
-        ForeignEnumInfo, ForeignInterface, ForeignerClassInfo, ForeignerMethod, ItemToExpand,
-        MethodVariant,
+        ExpandedItem, ForeignEnumInfo, ForeignInterface, ForeignerClassInfo, ForeignerMethod,
+        ItemToExpand, MethodVariant, SelfTypeVariant,
     },
+    unsupported_types::UnsupportedTypes,
     JavaConfig, LanguageGenerator, SourceCode, TypeMap,
 };
 
@@ -43,14 +59,16 @@ struct JavaForeignTypeInfo {
     pub base: ForeignTypeInfo,
     pub java_converter: Option<JavaConverter>,
     annotation: Option<NullAnnotation>,
+    /// classes that should be imported to use this type
+    provides_by_module: Vec<SmolStr>,
 }
 
 impl ForeignTypeInfoT for JavaForeignTypeInfo {
     fn name(&self) -> &str {
         self.base.name.as_str()
     }
-    fn correspoding_rust_type(&self) -> &RustType {
-        &self.base.correspoding_rust_type
+    fn correspoding_rust_type(&self) -> RustTypeIdx {
+        self.base.correspoding_rust_type
     }
 }
 
@@ -91,12 +109,13 @@ impl From<ForeignTypeInfo> for JavaForeignTypeInfo {
             },
             java_converter: None,
             annotation: None,
+            provides_by_module: Vec::new(),
         }
     }
 }
 
 struct JniForeignMethodSignature {
-    output: ForeignTypeInfo,
+    output: JavaForeignTypeInfo,
     input: Vec<JavaForeignTypeInfo>,
 }
 
@@ -110,7 +129,42 @@ impl ForeignMethodSignature for JniForeignMethodSignature {
     }
 }
 
+/// Java types for returned `Vec<T>`, `Option<T>` and iterators
+struct ReturnMapping<'a> {
+    package_name: &'a str,
+    java_lists: bool,
+    java_optional: bool,
+    java_streams: bool,
+}
+
+/// Getter of value-like class, becomes component of Java record
+struct RecordComponent<'a> {
+    name: String,
+    method: &'a ForeignerMethod,
+    f_method: &'a JniForeignMethodSignature,
+}
+
 impl JavaConfig {
+    fn is_record(&self, class: &ForeignerClassInfo) -> bool {
+        self.records && is_record_class(class)
+    }
+    fn return_mapping(&self) -> ReturnMapping<'_> {
+        ReturnMapping {
+            package_name: &self.package_name,
+            java_lists: self.java_lists || self.kotlin_friendly,
+            java_optional: self.java_optional
+                && (self.optional_package != "java.util"
+                    || self.api_level_at_least(api_level::JAVA8_API_LEVEL)),
+            java_streams: self.java_streams,
+        }
+    }
+    fn null_annotation_package(&self) -> Option<&str> {
+        match self.null_annotation_package {
+            Some(ref package) => Some(package.as_str()),
+            None if self.kotlin_friendly => Some("androidx.annotation"),
+            None => None,
+        }
+    }
     fn init(&self, conv_map: &mut TypeMap, _code: &[SourceCode]) {
         conv_map.find_or_alloc_rust_type_no_src_id(&parse_type! { jint });
         conv_map.find_or_alloc_rust_type_no_src_id(&parse_type! { jlong });
@@ -125,24 +179,24 @@ impl JavaConfig {
             let this_type_for_method = if_ty_result_return_ok_type(constructor_ret_type)
                 .unwrap_or_else(|| constructor_ret_type.clone());
 
-            let this_type: RustType = conv_map.find_or_alloc_rust_type_that_implements(
+            let this_type: RustTypeIdx = conv_map.find_or_alloc_rust_type_that_implements(
                 &this_type_for_method,
                 "SwigForeignClass",
                 class.src_id,
             );
             debug!(
                 "register_class: add implements SwigForeignClass for {}",
-                this_type
+                conv_map[this_type]
             );
 
             let my_jobj_ti = conv_map.find_or_alloc_rust_type_with_suffix(
                 &parse_type! { jobject },
-                &this_type.normalized_name,
+                &conv_map[this_type].normalized_name.clone(),
                 SourceId::none(),
             );
 
             conv_map.cache_rust_to_foreign_conv(
-                &this_type,
+                this_type,
                 ForeignTypeInfo {
                     correspoding_rust_type: my_jobj_ti,
                     name: class.name.to_string().into(),
@@ -152,19 +206,21 @@ impl JavaConfig {
             conv_map.find_or_alloc_rust_type(constructor_ret_type, class.src_id);
 
             let (this_type_for_method, _code_box_this) =
-                convert_to_heap_pointer(conv_map, &this_type, "this");
+                convert_to_heap_pointer(conv_map, this_type, "this");
 
-            let jlong_ti: RustType =
+            let jlong_ti: RustTypeIdx =
                 conv_map.find_or_alloc_rust_type_no_src_id(&parse_type! { jlong });
-            let this_type_for_method_ty = &this_type_for_method.ty;
-            let code = format!("& {}", DisplayToTokens(this_type_for_method_ty));
+            let this_type_for_method_ty = conv_map[this_type_for_method].ty.clone();
+            let this_type_for_method_name = conv_map[this_type_for_method].normalized_name.clone();
+            let this_type_for_method_src_id = conv_map[this_type_for_method].src_id;
+            let code = format!("& {}", DisplayToTokens(&this_type_for_method_ty));
             let gen_ty = parse_ty_with_given_span_checked(&code, this_type_for_method_ty.span());
             let this_type_ref =
-                conv_map.find_or_alloc_rust_type(&gen_ty, this_type_for_method.src_id);
+                conv_map.find_or_alloc_rust_type(&gen_ty, this_type_for_method_src_id);
             //handle foreigner_class as input arg
             conv_map.add_conversation_rule(
-                jlong_ti.to_idx(),
-                this_type_ref.to_idx(),
+                jlong_ti,
+                this_type_ref,
                 format!(
                     r#"
         let {to_var}: &{this_type} = unsafe {{
@@ -173,18 +229,18 @@ impl JavaConfig {
     "#,
                     to_var = TO_VAR_TEMPLATE,
                     from_var = FROM_VAR_TEMPLATE,
-                    this_type = this_type_for_method.normalized_name,
+                    this_type = this_type_for_method_name,
                 )
                 .into(),
             );
-            let code = format!("&mut {}", DisplayToTokens(this_type_for_method_ty));
+            let code = format!("&mut {}", DisplayToTokens(&this_type_for_method_ty));
             let gen_ty = parse_ty_with_given_span_checked(&code, this_type_for_method_ty.span());
             let this_type_mut_ref =
-                conv_map.find_or_alloc_rust_type(&gen_ty, this_type_for_method.src_id);
+                conv_map.find_or_alloc_rust_type(&gen_ty, this_type_for_method_src_id);
             //handle foreigner_class as input arg
             conv_map.add_conversation_rule(
-                jlong_ti.to_idx(),
-                this_type_mut_ref.to_idx(),
+                jlong_ti,
+                this_type_mut_ref,
                 format!(
                     r#"
         let {to_var}: &mut {this_type} = unsafe {{
@@ -193,16 +249,16 @@ impl JavaConfig {
     "#,
                     to_var = TO_VAR_TEMPLATE,
                     from_var = FROM_VAR_TEMPLATE,
-                    this_type = this_type_for_method.normalized_name,
+                    this_type = this_type_for_method_name,
                 )
                 .into(),
             );
 
             let unpack_code =
-                unpack_from_heap_pointer(&this_type_for_method, TO_VAR_TEMPLATE, true);
+                unpack_from_heap_pointer(&conv_map[this_type_for_method], TO_VAR_TEMPLATE, true);
             conv_map.add_conversation_rule(
-                jlong_ti.to_idx(),
-                this_type.to_idx(),
+                jlong_ti,
+                this_type,
                 format!(
                     r#"
         let {to_var}: *mut {this_type} = unsafe {{
@@ -212,7 +268,7 @@ impl JavaConfig {
     "#,
                     to_var = TO_VAR_TEMPLATE,
                     from_var = FROM_VAR_TEMPLATE,
-                    this_type = this_type_for_method.normalized_name,
+                    this_type = this_type_for_method_name,
                     unpack_code = unpack_code,
                 )
                 .into(),
@@ -224,31 +280,136 @@ impl JavaConfig {
         Ok(())
     }
 
+    fn register_record(&self, conv_map: &mut TypeMap, class: &ForeignerClassInfo) -> Result<()> {
+        class
+            .validate_class()
+            .map_err(|err| DiagnosticError::new(class.src_id, class.span(), &err))?;
+        let self_type = class.self_type_as_ty();
+        let this_type = conv_map.find_or_alloc_rust_type_that_implements(
+            &self_type,
+            "SwigJavaRecord",
+            class.src_id,
+        );
+        debug!(
+            "register_record: add implements SwigJavaRecord for {}",
+            conv_map[this_type]
+        );
+        Ok(())
+    }
+
     fn generate(
         &self,
         conv_map: &mut TypeMap,
         class: &ForeignerClassInfo,
+        exceptions: &[JavaException],
+        manifest: &mut ApiManifest,
     ) -> Result<Vec<TokenStream>> {
         debug!(
             "generate: begin for {}, this_type_for_method {:?}",
             class.name, class.self_desc
         );
 
-        let f_methods_sign = find_suitable_foreign_types_for_methods(conv_map, class)?;
-        java_code::generate_java_code(
+        exceptions::register_exception_conversions(conv_map, exceptions, class);
+        let f_methods_sign =
+            find_suitable_foreign_types_for_methods(conv_map, class, &self.return_mapping())?;
+        manifest.add_class(class, &manifest_fn_types(&f_methods_sign));
+        java_code::generate_java_code(conv_map, self, class, &f_methods_sign, exceptions)
+            .map_err(|err| DiagnosticError::new(class.src_id, class.span(), err))?;
+        debug!("generate: java code done");
+        let ast_items = rust_code::generate_rust_code(
+            conv_map,
+            self,
+            class,
+            &f_methods_sign,
+            exceptions,
+            catch_panics::class_panic_action(
+                class,
+                self.catch_panics.as_ref(),
+                panics::DEFAULT_PANIC_EXCEPTION,
+            )
+            .is_some(),
+        )?;
+
+        Ok(ast_items)
+    }
+
+    fn generate_record(
+        &self,
+        conv_map: &mut TypeMap,
+        pointer_target_width: usize,
+        class: &ForeignerClassInfo,
+        manifest: &mut ApiManifest,
+    ) -> Result<Vec<TokenStream>> {
+        // `clone` is not needed, records are passed by value
+        let mut class = class.clone();
+        class.methods.retain(|m| !is_clone_method(m));
+        let class = &class;
+
+        let f_methods_sign = find_suitable_foreign_types_for_methods(
             conv_map,
+            class,
+            &ReturnMapping {
+                package_name: &self.package_name,
+                java_lists: false,
+                java_optional: true,
+                java_streams: false,
+            },
+        )?;
+        let mut components = Vec::with_capacity(class.methods.len());
+        let mut constructor = None;
+        for (method, f_method) in class.methods.iter().zip(&f_methods_sign) {
+            match method.variant {
+                MethodVariant::Constructor if !method.is_dummy_constructor() => {
+                    constructor = Some((method, f_method))
+                }
+                MethodVariant::Method(_) => components.push(RecordComponent {
+                    name: method.short_name(),
+                    method,
+                    f_method,
+                }),
+                _ => {}
+            }
+        }
+        if let Some((method, f_method)) = constructor {
+            let same_types = f_method.input.len() == components.len()
+                && f_method.input.iter().zip(&components).all(|(arg, comp)| {
+                    arg.java_converter.is_none() && arg.base.name == comp.f_method.output.base.name
+                });
+            if !same_types {
+                let expected: Vec<&str> = components
+                    .iter()
+                    .map(|x| x.f_method.output.base.name.as_str())
+                    .collect();
+                return Err(DiagnosticError::new(
+                    class.src_id,
+                    method.span(),
+                    format!(
+                        "constructor of record {} should have arguments of the same types \
+                         as getters: ({})",
+                        class.name,
+                        expected.join(", ")
+                    ),
+                ));
+            }
+        }
+
+        manifest.add_class(class, &manifest_fn_types(&f_methods_sign));
+        java_code::generate_java_code_for_record(
             &self.output_dir,
             &self.package_name,
             class,
-            &f_methods_sign,
-            self.null_annotation_package.as_ref().map(String::as_str),
+            &components,
+            self.null_annotation_package(),
         )
         .map_err(|err| DiagnosticError::new(class.src_id, class.span(), err))?;
-        debug!("generate: java code done");
-        let ast_items =
-            rust_code::generate_rust_code(conv_map, &self.package_name, class, &f_methods_sign)?;
-
-        Ok(ast_items)
+        rust_code::generate_rust_code_for_record(
+            &self.package_name,
+            conv_map,
+            pointer_target_width,
+            class,
+            &components,
+            constructor,
+        )
     }
 
     fn generate_enum(
@@ -256,6 +417,7 @@ impl JavaConfig {
         conv_map: &mut TypeMap,
         pointer_target_width: usize,
         enum_info: &ForeignEnumInfo,
+        manifest: &mut ApiManifest,
     ) -> Result<Vec<TokenStream>> {
         if (enum_info.items.len() as u64) >= (i32::max_value() as u64) {
             return Err(DiagnosticError::new(
@@ -264,7 +426,15 @@ impl JavaConfig {
                 "Too many items in enum",
             ));
         }
+        if enum_info.is_data_enum() {
+            return Err(DiagnosticError::new(
+                enum_info.src_id,
+                enum_info.span(),
+                "Enum items with payload are supported only for C++",
+            ));
+        }
 
+        manifest.add_enum(enum_info);
         java_code::generate_java_code_for_enum(&self.output_dir, &self.package_name, enum_info)
             .map_err(|err| DiagnosticError::new(enum_info.src_id, enum_info.span(), &err))?;
 
@@ -281,14 +451,16 @@ impl JavaConfig {
         conv_map: &mut TypeMap,
         pointer_target_width: usize,
         interface: &ForeignInterface,
+        manifest: &mut ApiManifest,
     ) -> Result<Vec<TokenStream>> {
         let f_methods = find_suitable_ftypes_for_interace_methods(conv_map, interface)?;
+        manifest.add_interface(interface, &manifest_fn_types(&f_methods));
         java_code::generate_java_code_for_interface(
             &self.output_dir,
             &self.package_name,
             interface,
             &f_methods,
-            self.null_annotation_package.as_ref().map(String::as_str),
+            self.null_annotation_package(),
         )
         .map_err(|err| DiagnosticError::new(interface.src_id, interface.span(), err))?;
         let items = rust_code::generate_interface(
@@ -318,32 +490,175 @@ impl LanguageGenerator for JavaConfig {
         conv_map: &mut TypeMap,
         pointer_target_width: usize,
         code: &[SourceCode],
-        items: Vec<ItemToExpand>,
-    ) -> Result<Vec<TokenStream>> {
+        mut items: Vec<ItemToExpand>,
+        manifest: &mut ApiManifest,
+    ) -> Result<Vec<ExpandedItem>> {
+        check_name_collisions(self, &items)?;
+        if let Some(module_dir) = self.android_module.as_ref() {
+            android_module::generate_android_module(self, module_dir)?;
+        }
+        if let Some((ref module_name, ref native_library)) = self.module_info {
+            module_info::generate_module_info(self, module_name, native_library)?;
+        }
+        api_level::check_api_level(self, &items)?;
+        native_library::add_static_initializer(self, &mut items)?;
+        doc_links::rewrite_doc_links(&mut items, java_code::javadoc_link);
+        let exceptions = exceptions::generate_exceptions(self, &items)?;
+        let uses_iterators = iterators::uses_iterators(self, &items);
+        if uses_iterators {
+            iterators::generate_java_code_for_iterator(self)?;
+        }
+        if let Some(backend) = self.log_bridge {
+            log_bridge::generate_java_code_for_log_bridge(self, backend)?;
+        }
+        proguard::generate_proguard_rules(self, &items, &exceptions, uses_iterators)?;
         self.init(conv_map, code);
+        // classes are shared with `TypeMap`, so after this point they are not modified
+        for item in &items {
+            if let ItemToExpand::Class(ref fclass) = item {
+                conv_map.register_foreigner_class(fclass);
+            }
+        }
         for item in &items {
             if let ItemToExpand::Class(ref fclass) = item {
-                self.register_class(conv_map, fclass)?;
+                if self.is_record(fclass) {
+                    self.register_record(conv_map, fclass)?;
+                } else {
+                    self.register_class(conv_map, fclass)?;
+                }
+            }
+        }
+        // records are converted by value, so conversations should be known
+        // before generation of any class that uses them
+        let mut records_code = FxHashMap::<String, Vec<TokenStream>>::default();
+        for item in &items {
+            if let ItemToExpand::Class(ref fclass) = item {
+                if self.is_record(fclass) {
+                    let _timer = profiler::timer(Stage::ItemExpansion, || item.descr());
+                    conv_map.push_class_scope(fclass)?;
+                    let code =
+                        self.generate_record(conv_map, pointer_target_width, fclass, manifest)?;
+                    conv_map.pop_class_scope();
+                    records_code.insert(fclass.name.to_string(), code);
+                }
             }
         }
-        let mut ret = Vec::with_capacity(items.len());
+        let mut register_funcs: Vec<String> = if self.register_natives {
+            items
+                .iter()
+                .filter_map(|item| match item {
+                    ItemToExpand::Class(ref fclass)
+                        if !fclass.methods.is_empty() && !self.is_record(fclass) =>
+                    {
+                        Some(rust_code::register_natives_func_name(fclass))
+                    }
+                    _ => None,
+                })
+                .collect()
+        } else {
+            vec![]
+        };
+        if self.register_natives && uses_iterators {
+            register_funcs.push(rust_code::register_natives_func_name_for_iterator());
+        }
+        if self.register_natives && self.log_bridge.is_some() {
+            register_funcs.push(rust_code::register_natives_func_name_for_log_bridge());
+        }
+        let panic_action = catch_panics::panic_action(
+            items.iter().filter_map(|item| match item {
+                ItemToExpand::Class(ref fclass) if !self.is_record(fclass) => Some(&**fclass),
+                _ => None,
+            }),
+            self.catch_panics.as_ref(),
+            panics::DEFAULT_PANIC_EXCEPTION,
+        );
+        let mut ret = Vec::with_capacity(items.len() + 3);
+        if !exceptions.is_empty() {
+            ret.push(ExpandedItem {
+                descr: "exception helpers".into(),
+                code: exceptions::generate_rust_code_for_exceptions(&exceptions),
+            });
+        }
+        // errors of all items are reported together
+        let mut items_err = None;
         for item in items {
-            match item {
-                ItemToExpand::Class(fclass) => ret.append(&mut self.generate(conv_map, &fclass)?),
+            let descr = item.descr();
+            let _timer = profiler::timer(Stage::ItemExpansion, || descr.clone());
+            let code = match item {
+                ItemToExpand::Class(fclass) => {
+                    match records_code.remove(&fclass.name.to_string()) {
+                        Some(code) => Ok(code),
+                        None => {
+                            conv_map.push_class_scope(&fclass)?;
+                            let code = self.generate(conv_map, &fclass, &exceptions, manifest);
+                            conv_map.pop_class_scope();
+                            code
+                        }
+                    }
+                }
                 ItemToExpand::Enum(fenum) => {
-                    ret.append(&mut self.generate_enum(conv_map, pointer_target_width, &fenum)?)
+                    self.generate_enum(conv_map, pointer_target_width, &fenum, manifest)
                 }
-                ItemToExpand::Interface(finterface) => ret.append(&mut self.generate_interface(
-                    conv_map,
-                    pointer_target_width,
-                    &finterface,
-                )?),
+                ItemToExpand::Interface(finterface) => {
+                    self.generate_interface(conv_map, pointer_target_width, &finterface, manifest)
+                }
+            };
+            match code {
+                Ok(code) => ret.push(ExpandedItem { descr, code }),
+                Err(err) => collect_error(&mut items_err, err),
             }
         }
+        if let Some(err) = items_err {
+            return Err(err);
+        }
+        if let Some(action) = panic_action {
+            ret.push(ExpandedItem {
+                descr: "panic helpers".into(),
+                code: panics::generate_rust_code_for_panics(&action, self.panic_backtrace),
+            });
+        }
+        if uses_iterators {
+            ret.push(ExpandedItem {
+                descr: iterators::ITERATOR_CLASS.into(),
+                code: rust_code::generate_rust_code_for_iterator(
+                    &self.package_name,
+                    self.register_natives,
+                ),
+            });
+        }
+        if self.log_bridge.is_some() {
+            ret.push(ExpandedItem {
+                descr: log_bridge::LOG_CLASS.into(),
+                code: rust_code::generate_rust_code_for_log_bridge(
+                    &self.package_name,
+                    self.register_natives,
+                ),
+            });
+        }
+        if self.register_natives {
+            ret.push(ExpandedItem {
+                descr: "JNI_OnLoad".into(),
+                code: vec![rust_code::generate_jni_onload(&register_funcs)],
+            });
+        }
         Ok(ret)
     }
 }
 
+fn manifest_fn_types(f_methods: &[JniForeignMethodSignature]) -> Vec<ForeignFnTypes> {
+    f_methods
+        .iter()
+        .map(|f_method| ForeignFnTypes {
+            input: f_method
+                .input
+                .iter()
+                .map(|x| x.base.name.to_string())
+                .collect(),
+            output: f_method.output.base.name.to_string(),
+        })
+        .collect()
+}
+
 fn method_name(method: &ForeignerMethod, f_method: &JniForeignMethodSignature) -> String {
     let need_conv = f_method.input.iter().any(|v| v.java_converter.is_some());
     match method.variant {
@@ -355,6 +670,83 @@ fn method_name(method: &ForeignerMethod, f_method: &JniForeignMethodSignature) -
     }
 }
 
+/// Report all collisions of names of classes, enums, interfaces,
+/// generated Java classes and native methods, that become the same JNI function
+fn check_name_collisions(cfg: &JavaConfig, items: &[ItemToExpand]) -> Result<()> {
+    let mut names = NameRegistry::default();
+    names.reserve(iterators::ITERATOR_CLASS.into(), "Java helper");
+    for (_, exception_class) in &cfg.exceptions {
+        if !exception_class.contains('.') {
+            names.reserve(exception_class.clone(), "generated exception");
+        }
+    }
+    names.add_items(items);
+    for item in items {
+        let class = match item {
+            ItemToExpand::Class(fclass) => fclass,
+            _ => continue,
+        };
+        // methods of the same kind with the same name and arguments' types
+        // have the same Java signature, so the same JNI function,
+        // name of static method depends on conversations of arguments,
+        // so it is compared only with other static methods
+        let mut do_delete_methods = Vec::new();
+        for method in &class.methods {
+            let (kind, java_name, skip_args) = match method.variant {
+                MethodVariant::Constructor => ("constructor", "init".to_string(), 0),
+                MethodVariant::StaticMethod => ("static method", method.short_name(), 0),
+                MethodVariant::Method(_) => ("method", format!("do_{}", method.short_name()), 1),
+            };
+            if java_name == "do_delete" {
+                do_delete_methods.push(method);
+            }
+            let args = method
+                .fn_decl
+                .inputs
+                .iter()
+                .skip(skip_args)
+                .map(|arg| normalize_ty_lifetimes(fn_arg_type(arg)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let static_prefix = if method.variant == MethodVariant::StaticMethod {
+                "static "
+            } else {
+                ""
+            };
+            names.add(
+                format!("{}{}.{}({})", static_prefix, class.name, java_name, args),
+                (class.src_id, method.span()),
+                format_args!(
+                    "JNI function for {} {}",
+                    kind,
+                    DisplayToTokens(&method.rust_id)
+                ),
+            );
+        }
+        // not overloaded `do_delete` gets the same JNI function as destructor
+        let have_constructor = class
+            .methods
+            .iter()
+            .any(|m| m.variant == MethodVariant::Constructor);
+        if have_constructor && do_delete_methods.len() == 1 {
+            let method = do_delete_methods[0];
+            names.reserve(
+                format!("{}.do_delete", class.name),
+                format_args!("JNI function for destructor of {}", class.name),
+            );
+            names.add(
+                format!("{}.do_delete", class.name),
+                (class.src_id, method.span()),
+                format_args!(
+                    "JNI function for method {}",
+                    DisplayToTokens(&method.rust_id)
+                ),
+            );
+        }
+    }
+    names.into_result()
+}
+
 fn find_suitable_ftypes_for_interace_methods(
     conv_map: &mut TypeMap,
     interace: &ForeignInterface,
@@ -370,7 +762,7 @@ fn find_suitable_ftypes_for_interace_methods(
             let arg_rust_ty = conv_map.find_or_alloc_rust_type(fn_arg_type(arg), interace.src_id);
             let f_arg_type = map_type(
                 conv_map,
-                &arg_rust_ty,
+                arg_rust_ty,
                 Direction::Outgoing,
                 (interace.src_id, fn_arg_type(arg).span()),
             )?;
@@ -380,8 +772,9 @@ fn find_suitable_ftypes_for_interace_methods(
         let output = match method.fn_decl.output {
             syn::ReturnType::Default => ForeignTypeInfo {
                 name: void_sym.into(),
-                correspoding_rust_type: dummy_rust_ty.clone(),
-            },
+                correspoding_rust_type: dummy_rust_ty,
+            }
+            .into(),
             _ => unimplemented!(),
         };
         f_methods.push(JniForeignMethodSignature { output, input });
@@ -392,13 +785,18 @@ fn find_suitable_ftypes_for_interace_methods(
 fn find_suitable_foreign_types_for_methods(
     conv_map: &mut TypeMap,
     class: &ForeignerClassInfo,
+    ret_mapping: &ReturnMapping,
 ) -> Result<Vec<JniForeignMethodSignature>> {
     let mut ret = Vec::<JniForeignMethodSignature>::with_capacity(class.methods.len());
     let empty_symbol = "";
     let dummy_ty = parse_type! { () };
     let dummy_rust_ty = conv_map.find_or_alloc_rust_type_no_src_id(&dummy_ty);
+    let mut unsupported = UnsupportedTypes::default();
 
     for method in &class.methods {
+        let _timer = profiler::timer(Stage::MethodConversions, || {
+            format!("{}::{}", class.name, method.short_name())
+        });
         //skip self argument
         let skip_n = match method.variant {
             MethodVariant::Method(_) => 1,
@@ -407,44 +805,216 @@ fn find_suitable_foreign_types_for_methods(
         assert!(method.fn_decl.inputs.len() >= skip_n);
         let mut input =
             Vec::<JavaForeignTypeInfo>::with_capacity(method.fn_decl.inputs.len() - skip_n);
-        for arg in method.fn_decl.inputs.iter().skip(skip_n) {
+        let mut all_inputs_supported = true;
+        let mut has_direct_buffers = false;
+        let mut has_critical_arrays = false;
+        // completion callback of future is created by generated code
+        let n_inputs = if method.completable_future {
+            method.fn_decl.inputs.len().saturating_sub(1)
+        } else {
+            method.fn_decl.inputs.len()
+        };
+        for (i, arg) in method
+            .fn_decl
+            .inputs
+            .iter()
+            .take(n_inputs)
+            .skip(skip_n)
+            .enumerate()
+        {
             let arg_rust_ty = conv_map.find_or_alloc_rust_type(fn_arg_type(arg), class.src_id);
 
-            let fti = map_type(
+            if method.direct_buffer {
+                if let Some(fti) = map_type::direct_buffer_type(conv_map, arg_rust_ty) {
+                    has_direct_buffers = true;
+                    input.push(fti);
+                    continue;
+                }
+            }
+            if method.critical_arrays {
+                if let Some(fti) = map_type::critical_array_type(conv_map, arg_rust_ty) {
+                    has_critical_arrays = true;
+                    input.push(fti);
+                    continue;
+                }
+            }
+            match map_type(
                 conv_map,
-                &arg_rust_ty,
+                arg_rust_ty,
                 Direction::Incoming,
                 (class.src_id, fn_arg_type(arg).span()),
-            )?;
-            input.push(fti);
+            ) {
+                Ok(fti) => input.push(fti),
+                Err(err) => {
+                    unsupported.add(class, method, Some((i + 1, arg)), fn_arg_type(arg), err);
+                    all_inputs_supported = false;
+                }
+            }
+        }
+        if method.direct_buffer && !has_direct_buffers {
+            return Err(DiagnosticError::new(
+                class.src_id,
+                method.span(),
+                "#[direct_buffer] method should have &[u8] or &mut [u8] arguments",
+            ));
+        }
+        if method.critical_arrays && !has_critical_arrays {
+            return Err(DiagnosticError::new(
+                class.src_id,
+                method.span(),
+                "#[critical_arrays] method should have slices of i8, i16, i32, i64, f32 or f64 as arguments",
+            ));
         }
         let output = match method.variant {
+            _ if method.completable_future => {
+                futures::completable_future_type(conv_map, class, method)?
+            }
             MethodVariant::Constructor => ForeignTypeInfo {
                 name: empty_symbol.into(),
-                correspoding_rust_type: dummy_rust_ty.clone(),
-            },
+                correspoding_rust_type: dummy_rust_ty,
+            }
+            .into(),
             _ => match method.fn_decl.output {
                 syn::ReturnType::Default => ForeignTypeInfo {
                     name: "void".into(),
-                    correspoding_rust_type: dummy_rust_ty.clone(),
-                },
+                    correspoding_rust_type: dummy_rust_ty,
+                }
+                .into(),
                 syn::ReturnType::Type(_, ref rt) => {
                     let ret_rust_ty = conv_map.find_or_alloc_rust_type(rt, class.src_id);
-                    let fti = map_type(
+                    let special_fti = iterators::java_iterator_type(
                         conv_map,
-                        &ret_rust_ty,
-                        Direction::Outgoing,
-                        (class.src_id, rt.span()),
-                    )?;
-                    fti.base
+                        ret_rust_ty,
+                        ret_mapping.package_name,
+                        ret_mapping.java_streams,
+                    )
+                    .or_else(|| {
+                        if ret_mapping.java_optional {
+                            None
+                        } else {
+                            map_type::java_nullable_type(conv_map, ret_rust_ty)
+                        }
+                    });
+                    match special_fti {
+                        Some(fti) => fti,
+                        None => {
+                            let fti = match map_type(
+                                conv_map,
+                                ret_rust_ty,
+                                Direction::Outgoing,
+                                (class.src_id, rt.span()),
+                            ) {
+                                Ok(fti) => fti,
+                                Err(err) => {
+                                    unsupported.add(class, method, None, rt, err);
+                                    continue;
+                                }
+                            };
+                            if ret_mapping.java_lists
+                                && if_vec_return_elem_type(&conv_map[ret_rust_ty]).is_some()
+                            {
+                                map_type::java_list_type(conv_map, fti)
+                            } else {
+                                fti
+                            }
+                        }
+                    }
                 }
             },
         };
+        if !all_inputs_supported {
+            continue;
+        }
+        check_native_annotations(class, method, &input, &output)?;
         ret.push(JniForeignMethodSignature { output, input });
     }
+    unsupported.into_result()?;
     Ok(ret)
 }
 
+/// `@CriticalNative` method should be static (or method that is called via
+/// static native method), and should use only primitive types
+fn check_native_annotations(
+    class: &ForeignerClassInfo,
+    method: &ForeignerMethod,
+    input: &[JavaForeignTypeInfo],
+    output: &JavaForeignTypeInfo,
+) -> Result<()> {
+    if method.fast_native && method.critical_native {
+        return Err(DiagnosticError::new(
+            class.src_id,
+            method.span(),
+            "#[fast_native] and #[critical_native] can not be used together",
+        ));
+    }
+    if !method.critical_native {
+        return Ok(());
+    }
+    if method.variant == MethodVariant::Constructor {
+        return Err(DiagnosticError::new(
+            class.src_id,
+            method.span(),
+            "#[critical_native] can not be used with constructor",
+        ));
+    }
+    let returns_result = match method.fn_decl.output {
+        syn::ReturnType::Default => false,
+        syn::ReturnType::Type(_, ref rt) => if_ty_result_return_ok_type(rt).is_some(),
+    };
+    let only_primitives = input
+        .iter()
+        .chain(std::iter::once(output))
+        .all(|fti| fti.java_converter.is_none() && map_type::is_primitive_type(&fti.base.name));
+    if returns_result || !only_primitives {
+        return Err(DiagnosticError::new(
+            class.src_id,
+            method.span(),
+            "#[critical_native] method should have only arguments and result of primitive types, \
+             and can not return Result",
+        ));
+    }
+    Ok(())
+}
+
+fn is_clone_method(method: &ForeignerMethod) -> bool {
+    method
+        .rust_id
+        .segments
+        .last()
+        .map(|seg| seg.into_value().ident == "clone")
+        .unwrap_or(false)
+}
+
+/// `#[derive(Copy)]` class with only getters, plus constructor and `clone`,
+/// can be represented as Java record
+fn is_record_class(class: &ForeignerClassInfo) -> bool {
+    let self_desc = match class.self_desc {
+        Some(ref x) => x,
+        None => return false,
+    };
+    if !class.copy_derived
+        || DisplayToTokens(&self_desc.self_type).to_string()
+            != DisplayToTokens(&self_desc.constructor_ret_type).to_string()
+    {
+        return false;
+    }
+    let mut has_getters = false;
+    for method in &class.methods {
+        match method.variant {
+            MethodVariant::Constructor => {}
+            MethodVariant::Method(_) if is_clone_method(method) => {}
+            MethodVariant::Method(SelfTypeVariant::Rptr)
+                if method.fn_decl.inputs.len() == 1
+                    && method.fn_decl.output != syn::ReturnType::Default =>
+            {
+                has_getters = true
+            }
+            MethodVariant::Method(_) | MethodVariant::StaticMethod => return false,
+        }
+    }
+    has_getters
+}
+
 fn fmt_write_err_map(err: fmt::Error) -> String {
     format!("fmt write error: {}", err)
 }
@@ -464,14 +1034,15 @@ fn calc_this_type_for_method(tm: &TypeMap, class: &ForeignerClassInfo) -> Option
     if let Some(constructor_ret_type) = class.self_desc.as_ref().map(|x| &x.constructor_ret_type) {
         Some(
             if_result_return_ok_err_types(
-                &tm.ty_to_rust_type_checked(constructor_ret_type)
+                &tm[tm
+                    .ty_to_rust_type_checked(constructor_ret_type)
                     .unwrap_or_else(|| {
                         panic!(
                             "Internal error: constructor type {} for class {} unknown",
                             DisplayToTokens(constructor_ret_type),
                             class.name
                         );
-                    }),
+                    })],
             )
             .map(|(ok_ty, _err_ty)| ok_ty)
             .unwrap_or_else(|| constructor_ret_type.clone()),
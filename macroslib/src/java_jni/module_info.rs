@@ -0,0 +1,86 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{DiagnosticError, Result},
+    file_cache::FileWriteCache,
+    JavaConfig,
+};
+
+/// Create `module-info.java` in root of Java sources,
+/// root is calculated from `output_dir` by removing package directories
+pub(in crate::java_jni) fn generate_module_info(
+    cfg: &JavaConfig,
+    module_name: &str,
+    native_library: &str,
+) -> Result<()> {
+    let root = java_sources_root(&cfg.output_dir, &cfg.package_name).ok_or_else(|| {
+        DiagnosticError::new_without_src_info(format!(
+            "Can not generate module-info.java: output directory {} \
+             does not match package {}",
+            cfg.output_dir.display(),
+            cfg.package_name
+        ))
+    })?;
+    let path = root.join("module-info.java");
+    let mut file = FileWriteCache::new(&path);
+    write!(
+        file,
+        r#"// Automaticaly generated by rust_swig
+/**
+ * Bindings to native library {native_library},
+ * it should be loaded via System.loadLibrary("{native_library}")
+ * before usage of classes from this module
+ */
+module {module_name} {{
+    exports {package};
+}}
+"#,
+        native_library = native_library,
+        module_name = module_name,
+        package = cfg.package_name
+    )
+    .and_then(|_| file.update_file_if_necessary())
+    .map_err(|err| {
+        DiagnosticError::new_without_src_info(format!(
+            "write to {} failed: {}",
+            path.display(),
+            err
+        ))
+    })
+}
+
+fn java_sources_root(output_dir: &Path, package_name: &str) -> Option<PathBuf> {
+    let mut root = output_dir.to_path_buf();
+    for part in package_name.rsplit('.') {
+        if root.file_name()? != part {
+            return None;
+        }
+        root.pop();
+    }
+    Some(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_java_sources_root() {
+        assert_eq!(
+            Some(PathBuf::from("/src/main/java")),
+            java_sources_root(Path::new("/src/main/java/com/example"), "com.example")
+        );
+        assert_eq!(
+            Some(PathBuf::from("java")),
+            java_sources_root(Path::new("java/com/example/"), "com.example")
+        );
+        assert_eq!(
+            None,
+            java_sources_root(Path::new("/src/main/java/com/other"), "com.example")
+        );
+        assert_eq!(None, java_sources_root(Path::new("example"), "com.example"));
+    }
+}
@@ -0,0 +1,78 @@
+use std::{env, rc::Rc};
+
+use crate::{
+    error::{DiagnosticError, Result},
+    types::ItemToExpand,
+    JavaConfig,
+};
+
+/// Add static initializer, that loads native library, to Java code
+/// of class chosen via `JavaConfig::load_native_library`
+pub(in crate::java_jni) fn add_static_initializer(
+    cfg: &JavaConfig,
+    items: &mut [ItemToExpand],
+) -> Result<()> {
+    let (class_name, library_name) = match cfg.load_native_library {
+        Some((ref class_name, ref library_name)) => (class_name, library_name),
+        None => return Ok(()),
+    };
+    let fclass = items
+        .iter_mut()
+        .find_map(|item| match item {
+            ItemToExpand::Class(ref mut fclass) if fclass.name == class_name => Some(fclass),
+            _ => None,
+        })
+        .ok_or_else(|| {
+            DiagnosticError::new_without_src_info(format!(
+                "Can not find class {} to load native library {}",
+                class_name, library_name
+            ))
+        })?;
+    let code = static_initializer(cfg, &env::var("TARGET").unwrap_or_default());
+    Rc::make_mut(fclass).foreigner_code.insert_str(0, &code);
+    Ok(())
+}
+
+fn static_initializer(cfg: &JavaConfig, target: &str) -> String {
+    let library_name = cfg
+        .native_library_targets
+        .iter()
+        .find(|x| x.0 == target)
+        .or(cfg.load_native_library.as_ref())
+        .map(|x| x.1.as_str())
+        .unwrap_or_default();
+    let loader = match cfg.native_library_loader {
+        Some(ref loader) => loader.as_str(),
+        None => "System",
+    };
+    format!(
+        r#"
+    static {{
+        {loader}.loadLibrary("{library_name}");
+    }}
+"#,
+        loader = loader,
+        library_name = library_name,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_static_initializer() {
+        let cfg = JavaConfig::new(PathBuf::from("java"), "com.example".into())
+            .load_native_library("Api".into(), "example".into())
+            .native_library_for_target("x86_64-pc-windows-msvc".into(), "example_win".into());
+        assert!(static_initializer(&cfg, "x86_64-unknown-linux-gnu")
+            .contains("System.loadLibrary(\"example\");"));
+        assert!(static_initializer(&cfg, "").contains("System.loadLibrary(\"example\");"));
+        assert!(static_initializer(&cfg, "x86_64-pc-windows-msvc")
+            .contains("System.loadLibrary(\"example_win\");"));
+        let cfg = cfg.native_library_loader("com.example.NativeLoader".into());
+        assert!(static_initializer(&cfg, "")
+            .contains("com.example.NativeLoader.loadLibrary(\"example\");"));
+    }
+}
@@ -0,0 +1,78 @@
+use proc_macro2::TokenStream;
+
+use crate::{
+    catch_panics::{init_panic_hook_call, panic_message_code},
+    error::panic_on_syn_error,
+    java_jni::java_class_name_to_jni,
+    PanicAction,
+};
+
+/// Exception thrown if class is marked with `#[catch_panics]`,
+/// but `JavaConfig::catch_panics` is not set
+pub(in crate::java_jni) const DEFAULT_PANIC_EXCEPTION: &str = "java.lang.RuntimeException";
+
+/// Rust code of `swig_jni_catch_panic`, that calls closure and converts
+/// its panic to Java exception or abort according to `action`
+pub(in crate::java_jni) fn generate_rust_code_for_panics(
+    action: &PanicAction,
+    backtrace: bool,
+) -> Vec<TokenStream> {
+    let on_panic = match action {
+        PanicAction::Throw(exception) => format!(
+            r#"            if env.is_null() {{
+                // `#[critical_native]` method can not throw
+                eprintln!("panic in native method: {{}}", msg);
+                ::std::process::abort();
+            }}
+            unsafe {{
+                if (**env).ExceptionCheck.unwrap()(env) != 0 {{
+                    (**env).ExceptionClear.unwrap()(env);
+                }}
+            }}
+            jni_throw(env, swig_c_str!("{exception}"), &msg);
+            <T>::invalid_value()
+"#,
+            exception = java_class_name_to_jni(exception),
+        ),
+        PanicAction::Abort => r#"            eprintln!("panic in native method: {}", msg);
+            ::std::process::abort();
+"#
+        .to_string(),
+    };
+    let mut code = panic_message_code(backtrace);
+    code.push_str(&format!(
+        r#"
+#[allow(dead_code)]
+fn swig_jni_catch_panic<T: JniInvalidValue<T>, F: FnOnce() -> T>(env: *mut JNIEnv, f: F) -> T {{
+{init_hook}    match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(f)) {{
+        Ok(ret) => ret,
+        Err(payload) => {{
+            let msg = swig_panic_message(&*payload);
+{on_panic}        }}
+    }}
+}}
+"#,
+        init_hook = init_panic_hook_call(backtrace),
+        on_panic = on_panic,
+    ));
+    vec![syn::parse_str(&code)
+        .unwrap_or_else(|err| panic_on_syn_error("java/jni internal panic helpers", code, err))]
+}
+
+/// Wrap `body` of JNI function, that returns `ret_type`, into `swig_jni_catch_panic`
+pub(in crate::java_jni) fn catch_panic_code(
+    catch_panics: bool,
+    ret_type: &str,
+    body: String,
+) -> String {
+    if !catch_panics {
+        return body;
+    }
+    format!(
+        r#"    swig_jni_catch_panic(env, move || -> {ret_type} {{
+{body}
+    }})"#,
+        ret_type = ret_type,
+        body = body,
+    )
+}
@@ -0,0 +1,108 @@
+use std::{fmt::Write as FmtWrite, io::Write};
+
+use crate::{
+    error::{DiagnosticError, Result},
+    file_cache::FileWriteCache,
+    java_jni::{
+        exceptions::JavaException, iterators::ITERATOR_CLASS, java_class_full_name,
+        log_bridge::LOG_CLASS,
+    },
+    types::{ItemToExpand, MethodVariant},
+    JavaConfig,
+};
+
+pub(in crate::java_jni) const PROGUARD_RULES_FILE: &str = "proguard-rules.pro";
+
+/// Write ProGuard/R8 rules that keep names used by Rust code via JNI:
+/// generated classes, their native methods and `mNativeObj`,
+/// enums items, methods of callback interfaces, constructors of exceptions
+/// `RustIterator` and `RustLog`
+pub(in crate::java_jni) fn generate_proguard_rules(
+    cfg: &JavaConfig,
+    items: &[ItemToExpand],
+    exceptions: &[JavaException],
+    uses_iterators: bool,
+) -> Result<()> {
+    let mut rules = "# Automaticaly generated by rust_swig\n".to_string();
+    for item in items {
+        match item {
+            ItemToExpand::Class(fclass) => {
+                let full_name = java_class_full_name(&cfg.package_name, &fclass.name.to_string());
+                if cfg.is_record(fclass) {
+                    // Rust code calls constructor and reads fields
+                    writeln!(&mut rules, "-keep class {} {{ *; }}", full_name)
+                } else if fclass
+                    .methods
+                    .iter()
+                    .any(|x| x.variant == MethodVariant::Constructor)
+                {
+                    writeln!(
+                        &mut rules,
+                        "-keep class {} {{\n    native <methods>;\n    long mNativeObj;\n}}",
+                        full_name
+                    )
+                } else {
+                    writeln!(
+                        &mut rules,
+                        "-keep class {} {{\n    native <methods>;\n}}",
+                        full_name
+                    )
+                }
+            }
+            ItemToExpand::Enum(fenum) => writeln!(
+                &mut rules,
+                "-keep class {} {{ *; }}",
+                java_class_full_name(&cfg.package_name, &fenum.name.to_string())
+            ),
+            ItemToExpand::Interface(finterface) => writeln!(
+                &mut rules,
+                "-keep interface {} {{ <methods>; }}",
+                java_class_full_name(&cfg.package_name, &finterface.name.to_string())
+            ),
+        }
+        .expect("write to String failed");
+    }
+    for exception in exceptions {
+        if exception.class_name.starts_with("java.") {
+            continue;
+        }
+        writeln!(
+            &mut rules,
+            "-keep class {} {{ <init>(...); }}",
+            exception.class_name
+        )
+        .expect("write to String failed");
+    }
+    if uses_iterators {
+        writeln!(
+            &mut rules,
+            "-keep class {} {{\n    native <methods>;\n    <init>(long);\n    java.util.stream.Stream stream();\n}}",
+            java_class_full_name(&cfg.package_name, ITERATOR_CLASS)
+        )
+        .expect("write to String failed");
+    }
+    if cfg.log_bridge.is_some() {
+        writeln!(
+            &mut rules,
+            "-keep class {} {{\n    native <methods>;\n    static void log(int, java.lang.String, java.lang.String);\n}}",
+            java_class_full_name(&cfg.package_name, LOG_CLASS)
+        )
+        .expect("write to String failed");
+    }
+
+    let path = cfg
+        .android_module
+        .as_ref()
+        .unwrap_or(&cfg.output_dir)
+        .join(PROGUARD_RULES_FILE);
+    let mut file = FileWriteCache::new(&path);
+    file.write_all(rules.as_bytes())
+        .and_then(|_| file.update_file_if_necessary())
+        .map_err(|err| {
+            DiagnosticError::new_without_src_info(format!(
+                "write to {} failed: {}",
+                path.display(),
+                err
+            ))
+        })
+}
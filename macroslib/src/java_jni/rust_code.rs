@@ -7,25 +7,28 @@ use syn::{parse_quote, spanned::Spanned, Type};
 use crate::{
     error::{panic_on_syn_error, DiagnosticError, Result},
     java_jni::{
-        calc_this_type_for_method, fmt_write_err_map, java_class_full_name, java_class_name_to_jni,
-        method_name, ForeignTypeInfo, JniForeignMethodSignature,
+        calc_this_type_for_method, exceptions::JavaException, fmt_write_err_map, futures,
+        iterators::ITERATOR_CLASS, java_class_full_name, java_class_name_to_jni,
+        log_bridge::LOG_CLASS, map_type, method_name, panics::catch_panic_code, ForeignTypeInfo,
+        JniForeignMethodSignature, RecordComponent,
     },
+    profiler::{self, Stage},
     source_registry::SourceId,
+    tracing_spans,
     typemap::ast::{fn_arg_type, list_lifetimes, normalize_ty_lifetimes, DisplayToTokens},
     typemap::{
-        ty::RustType,
         utils::{
             convert_to_heap_pointer, create_suitable_types_for_constructor_and_self,
             foreign_from_rust_convert_method_output, foreign_to_rust_convert_method_inputs,
             rust_to_foreign_convert_method_inputs, unpack_from_heap_pointer,
         },
-        TO_VAR_TEMPLATE,
+        RustTypeIdx, TO_VAR_TEMPLATE,
     },
     types::{
         ForeignEnumInfo, ForeignInterface, ForeignerClassInfo, ForeignerMethod, MethodVariant,
         SelfTypeVariant,
     },
-    TypeMap,
+    JavaConfig, TypeMap,
 };
 
 struct MethodContext<'a> {
@@ -33,17 +36,29 @@ struct MethodContext<'a> {
     method: &'a ForeignerMethod,
     f_method: &'a JniForeignMethodSignature,
     jni_func_name: &'a str,
+    no_mangle: &'a str,
+    visibility: &'a str,
     decl_func_args: &'a str,
     args_names: &'a str,
     real_output_typename: &'a str,
+    /// creation of `CompletableFuture` for `#[completable_future]` method
+    completion_code: Option<String>,
+    /// wrap body into `swig_jni_catch_panic`
+    catch_panics: bool,
+    /// enter span of `tracing` crate in body
+    tracing_spans: bool,
 }
 
 pub(in crate::java_jni) fn generate_rust_code(
     conv_map: &mut TypeMap,
-    package_name: &str,
+    cfg: &JavaConfig,
     class: &ForeignerClassInfo,
     f_methods_sign: &[JniForeignMethodSignature],
+    exceptions: &[JavaException],
+    catch_panics: bool,
 ) -> Result<Vec<TokenStream>> {
+    let package_name = cfg.package_name.as_str();
+    let register_natives = cfg.register_natives;
     //to handle java method overload
     let mut gen_fnames = FxHashMap::<String, usize>::default();
     for (method, f_method) in class.methods.iter().zip(f_methods_sign.iter()) {
@@ -64,16 +79,16 @@ pub(in crate::java_jni) fn generate_rust_code(
             );
             debug!(
                 "generate_rust_code: add implements SwigForeignClass for {}",
-                this_type.normalized_name
+                conv_map[this_type].normalized_name
             );
 
             let (this_type_for_method, code_box_this) =
-                convert_to_heap_pointer(conv_map, &this_type, "this");
+                convert_to_heap_pointer(conv_map, this_type, "this");
             let class_name_for_user = java_class_full_name(package_name, &class.name.to_string());
             let class_name_for_jni = java_class_name_to_jni(&class_name_for_user);
             let lifetimes = {
                 let mut ret = String::new();
-                let lifetimes = list_lifetimes(&this_type.ty);
+                let lifetimes = list_lifetimes(&conv_map[this_type].ty);
                 for (i, l) in lifetimes.iter().enumerate() {
                     ret.push_str(&*l.as_str());
                     if i != lifetimes.len() - 1 {
@@ -83,13 +98,19 @@ pub(in crate::java_jni) fn generate_rust_code(
                 ret
             };
 
-            let unpack_code = unpack_from_heap_pointer(&this_type, TO_VAR_TEMPLATE, true);
+            let unpack_code = unpack_from_heap_pointer(&conv_map[this_type], TO_VAR_TEMPLATE, true);
 
             let fclass_impl_code = format!(
                 r#"impl<{lifetimes}> SwigForeignClass for {class_name} {{
     fn jni_class_name() -> *const ::std::os::raw::c_char {{
         swig_c_str!("{jni_class_name}")
     }}
+    fn jni_class(env: *mut JNIEnv) -> jclass {{
+        swig_jni_find_class!(env, "{jni_class_name}")
+    }}
+    fn jni_native_ptr_field(env: *mut JNIEnv) -> jfieldID {{
+        swig_jni_get_id!(env, Self::jni_class(env), GetFieldID, "mNativeObj", "J")
+    }}
     fn box_object(this: Self) -> jlong {{
 {code_box_this}
        this as jlong
@@ -103,11 +124,11 @@ pub(in crate::java_jni) fn generate_rust_code(
     }}
 }}"#,
                 lifetimes = lifetimes,
-                class_name = DisplayToTokens(&this_type.ty),
+                class_name = DisplayToTokens(&conv_map[this_type].ty),
                 jni_class_name = class_name_for_jni,
                 code_box_this = code_box_this,
                 unpack_code = unpack_code.replace(TO_VAR_TEMPLATE, "x"),
-                this_type = this_type_for_method.normalized_name,
+                this_type = conv_map[this_type_for_method].normalized_name,
             );
 
             gen_code.push(syn::parse_str(&fclass_impl_code).unwrap_or_else(|err| {
@@ -116,7 +137,7 @@ pub(in crate::java_jni) fn generate_rust_code(
 
             (this_type_for_method, code_box_this)
         } else {
-            (dummy_rust_ty.clone(), String::new())
+            (dummy_rust_ty, String::new())
         };
 
     let no_this_info = || {
@@ -131,9 +152,20 @@ May be you need to use `private constructor = empty;` syntax?",
         )
     };
 
+    // with `RegisterNatives` JVM gets pointers to functions from table,
+    // so there is no need to export them
+    let (no_mangle, visibility) = if register_natives {
+        ("", "")
+    } else {
+        ("#[no_mangle]", "pub ")
+    };
+    let mut natives = Vec::<NativeMethod>::new();
     let mut have_constructor = false;
 
     for (method, f_method) in class.methods.iter().zip(f_methods_sign.iter()) {
+        let _timer = profiler::timer(Stage::MethodConversions, || {
+            format!("{}::{}", class.name, method.short_name())
+        });
         let java_method_name = method_name(method, f_method);
         let method_overloading = gen_fnames[&java_method_name] > 1;
         let jni_func_name = generate_jni_func_name(
@@ -145,17 +177,26 @@ May be you need to use `private constructor = empty;` syntax?",
         )?;
         trace!("generate_rust_code jni name: {}", jni_func_name);
 
-        let args_names = f_method
+        let mut args_names = f_method
             .input
             .iter()
             .enumerate()
             .map(|a| format!("a_{}, ", a.0))
             .fold(String::new(), |acc, x| acc + &x);
+        let completion_code = if method.completable_future {
+            args_names.push_str(futures::COMPLETION_VAR);
+            Some(futures::completion_code(
+                conv_map, class, method, exceptions,
+            )?)
+        } else {
+            None
+        };
 
-        let decl_func_args = generate_jni_args_with_types(f_method)
+        let decl_func_args = generate_jni_args_with_types(conv_map, f_method)
             .map_err(|err| DiagnosticError::new(class.src_id, class.span(), &err))?;
         let real_output_typename = match method.fn_decl.output {
-            syn::ReturnType::Default => "()",
+            _ if method.completable_future => "jobject".into(),
+            syn::ReturnType::Default => "()".into(),
             syn::ReturnType::Type(_, ref ty) => normalize_ty_lifetimes(&*ty),
         };
 
@@ -164,10 +205,23 @@ May be you need to use `private constructor = empty;` syntax?",
             method,
             f_method,
             jni_func_name: &jni_func_name,
+            no_mangle,
+            visibility,
             decl_func_args: &decl_func_args,
             args_names: &args_names,
             real_output_typename: &real_output_typename,
+            completion_code,
+            catch_panics,
+            tracing_spans: cfg.tracing_spans,
         };
+        if !method.is_dummy_constructor() {
+            natives.push(NativeMethod {
+                java_name: java_method_name.clone(),
+                signature: jni_native_method_signature(package_name, method.variant, f_method)
+                    .map_err(|err| DiagnosticError::new(class.src_id, method.span(), err))?,
+                func_name: jni_func_name.clone(),
+            });
+        }
 
         match method.variant {
             MethodVariant::StaticMethod => {
@@ -178,7 +232,7 @@ May be you need to use `private constructor = empty;` syntax?",
                     conv_map,
                     &method_ctx,
                     *self_variant,
-                    &this_type_for_method,
+                    this_type_for_method,
                 )?);
             }
             MethodVariant::Constructor => {
@@ -205,12 +259,12 @@ May be you need to use `private constructor = empty;` syntax?",
     }
 
     if have_constructor {
-        let this_type: RustType = conv_map.find_or_alloc_rust_type(
+        let this_type: RustTypeIdx = conv_map.find_or_alloc_rust_type(
             &calc_this_type_for_method(conv_map, class).ok_or_else(&no_this_info)?,
             class.src_id,
         );
 
-        let unpack_code = unpack_from_heap_pointer(&this_type, "this", false);
+        let unpack_code = unpack_from_heap_pointer(&conv_map[this_type], "this", false);
 
         let jni_destructor_name = generate_jni_func_name(
             package_name,
@@ -219,8 +273,9 @@ May be you need to use `private constructor = empty;` syntax?",
             &JniForeignMethodSignature {
                 output: ForeignTypeInfo {
                     name: "".into(),
-                    correspoding_rust_type: dummy_rust_ty.clone(),
-                },
+                    correspoding_rust_type: dummy_rust_ty,
+                }
+                .into(),
                 input: vec![],
             },
             false,
@@ -228,18 +283,32 @@ May be you need to use `private constructor = empty;` syntax?",
         let code = format!(
             r#"
 #[allow(unused_variables, unused_mut, non_snake_case)]
-#[no_mangle]
-pub extern "C" fn {jni_destructor_name}(env: *mut JNIEnv, _: jclass, this: jlong) {{
-    let this: *mut {this_type} = unsafe {{
-        jlong_to_pointer::<{this_type}>(this).as_mut().unwrap()
-    }};
-{unpack_code}
-    drop(this);
+{no_mangle}
+{visibility}extern "C" fn {jni_destructor_name}(env: *mut JNIEnv, _: jclass, this: jlong) {{
+{body}
 }}
 "#,
             jni_destructor_name = jni_destructor_name,
-            unpack_code = unpack_code,
-            this_type = this_type_for_method.normalized_name,
+            no_mangle = no_mangle,
+            visibility = visibility,
+            body = catch_panic_code(
+                catch_panics,
+                "()",
+                format!(
+                    r#"{span_code}    let this: *mut {this_type} = unsafe {{
+        jlong_to_pointer::<{this_type}>(this).as_mut().unwrap()
+    }};
+{unpack_code}
+    drop(this);"#,
+                    span_code = if cfg.tracing_spans {
+                        tracing_spans::destructor_span_code(class)
+                    } else {
+                        String::new()
+                    },
+                    unpack_code = unpack_code,
+                    this_type = conv_map[this_type_for_method].normalized_name,
+                )
+            ),
         );
         debug!("we generate and parse code: {}", code);
         gen_code.push(
@@ -247,11 +316,406 @@ pub extern "C" fn {jni_destructor_name}(env: *mut JNIEnv, _: jclass, this: jlong
                 panic_on_syn_error("java/jni internal desctructor", code, err)
             }),
         );
+        natives.push(NativeMethod {
+            java_name: "do_delete".into(),
+            signature: "(J)V".into(),
+            func_name: jni_destructor_name,
+        });
+    }
+
+    if register_natives && !natives.is_empty() {
+        let class_name_for_jni =
+            java_class_name_to_jni(&java_class_full_name(package_name, &class.name.to_string()));
+        gen_code.push(generate_register_natives(
+            &register_natives_func_name(class),
+            &class_name_for_jni,
+            &natives,
+        ));
     }
 
     Ok(gen_code)
 }
 
+/// Entry of table passed to `RegisterNatives`
+struct NativeMethod {
+    java_name: String,
+    signature: String,
+    func_name: String,
+}
+
+/// Name of function that registers native methods of `class`,
+/// there is such function only if class has any methods
+pub(in crate::java_jni) fn register_natives_func_name(class: &ForeignerClassInfo) -> String {
+    format!("swig_register_natives_for_{}", class.name)
+}
+
+fn generate_register_natives(
+    func_name: &str,
+    class_name_for_jni: &str,
+    natives: &[NativeMethod],
+) -> TokenStream {
+    let mut methods = String::new();
+    for native in natives {
+        methods.push_str(&format!(
+            r#"
+        JNINativeMethod {{
+            name: swig_c_str!("{java_name}") as *mut ::std::os::raw::c_char,
+            signature: swig_c_str!("{signature}") as *mut ::std::os::raw::c_char,
+            fnPtr: {func_name} as *mut ::std::os::raw::c_void,
+        }},"#,
+            java_name = native.java_name,
+            signature = native.signature,
+            func_name = native.func_name,
+        ));
+    }
+    let code = format!(
+        r#"
+#[allow(non_snake_case)]
+fn {func_name}(env: *mut JNIEnv) -> jint {{
+    let methods = [{methods}
+    ];
+    let class: jclass = unsafe {{ (**env).FindClass.unwrap()(env, swig_c_str!("{jni_class_name}")) }};
+    if class.is_null() {{
+        return JNI_ERR as jint;
+    }}
+    let ret = unsafe {{
+        (**env).RegisterNatives.unwrap()(env, class, methods.as_ptr(), methods.len() as jint)
+    }};
+    unsafe {{ (**env).DeleteLocalRef.unwrap()(env, class) }};
+    ret
+}}
+"#,
+        func_name = func_name,
+        methods = methods,
+        jni_class_name = class_name_for_jni,
+    );
+    syn::parse_str(&code)
+        .unwrap_or_else(|err| panic_on_syn_error("java/jni internal register natives", code, err))
+}
+
+/// Name of function that registers native methods of `RustIterator`
+pub(in crate::java_jni) fn register_natives_func_name_for_iterator() -> String {
+    format!("swig_register_natives_for_{}", ITERATOR_CLASS)
+}
+
+/// Native methods of `RustIterator`, they are the same for all iterators,
+/// because elements are converted to `jobject` by closure created for each iterator
+pub(in crate::java_jni) fn generate_rust_code_for_iterator(
+    package_name: &str,
+    register_natives: bool,
+) -> Vec<TokenStream> {
+    let (no_mangle, visibility) = if register_natives {
+        ("", "")
+    } else {
+        ("#[no_mangle]", "pub ")
+    };
+    let mut jni_prefix = "Java_".to_string();
+    escape_underscore(package_name, &mut jni_prefix);
+    jni_prefix.push('_');
+    escape_underscore(ITERATOR_CLASS, &mut jni_prefix);
+    let code = format!(
+        r#"
+#[allow(non_snake_case)]
+{no_mangle}
+{visibility}extern "C" fn {jni_prefix}_do_1next(env: *mut JNIEnv, _: jclass, me: jlong) -> jobject {{
+    swig_jni_iterator_next(me, env)
+}}
+
+#[allow(non_snake_case, unused_variables)]
+{no_mangle}
+{visibility}extern "C" fn {jni_prefix}_do_1delete(env: *mut JNIEnv, _: jclass, me: jlong) {{
+    swig_jni_iterator_delete(me);
+}}
+"#,
+        no_mangle = no_mangle,
+        visibility = visibility,
+        jni_prefix = jni_prefix,
+    );
+    let mut gen_code = vec![syn::parse_str(&code)
+        .unwrap_or_else(|err| panic_on_syn_error("java/jni internal RustIterator", code, err))];
+    if register_natives {
+        let natives = [
+            NativeMethod {
+                java_name: "do_next".into(),
+                signature: "(J)Ljava/lang/Object;".into(),
+                func_name: format!("{}_do_1next", jni_prefix),
+            },
+            NativeMethod {
+                java_name: "do_delete".into(),
+                signature: "(J)V".into(),
+                func_name: format!("{}_do_1delete", jni_prefix),
+            },
+        ];
+        gen_code.push(generate_register_natives(
+            &register_natives_func_name_for_iterator(),
+            &java_class_name_to_jni(&java_class_full_name(package_name, ITERATOR_CLASS)),
+            &natives,
+        ));
+    }
+    gen_code
+}
+
+/// Name of function that registers native methods of `RustLog`
+pub(in crate::java_jni) fn register_natives_func_name_for_log_bridge() -> String {
+    format!("swig_register_natives_for_{}", LOG_CLASS)
+}
+
+/// Implementation of `log::Log`, that calls `RustLog.log`,
+/// and native method of `RustLog` that installs it
+pub(in crate::java_jni) fn generate_rust_code_for_log_bridge(
+    package_name: &str,
+    register_natives: bool,
+) -> Vec<TokenStream> {
+    let (no_mangle, visibility) = if register_natives {
+        ("", "")
+    } else {
+        ("#[no_mangle]", "pub ")
+    };
+    let mut jni_prefix = "Java_".to_string();
+    escape_underscore(package_name, &mut jni_prefix);
+    jni_prefix.push('_');
+    escape_underscore(LOG_CLASS, &mut jni_prefix);
+    let jni_class_name = java_class_name_to_jni(&java_class_full_name(package_name, LOG_CLASS));
+    let code = format!(
+        r#"
+struct SwigJniLogger {{
+    java_vm: *mut JavaVM,
+    class: jclass,
+    log_method: jmethodID,
+}}
+
+unsafe impl Send for SwigJniLogger {{}}
+unsafe impl Sync for SwigJniLogger {{}}
+
+impl ::log::Log for SwigJniLogger {{
+    fn enabled(&self, metadata: &::log::Metadata) -> bool {{
+        metadata.level() <= ::log::max_level()
+    }}
+
+    fn log(&self, record: &::log::Record) {{
+        if !self.enabled(record.metadata()) {{
+            return;
+        }}
+        let mut env: *mut JNIEnv = ::std::ptr::null_mut();
+        let res = unsafe {{
+            (**self.java_vm).GetEnv.unwrap()(
+                self.java_vm,
+                (&mut env) as *mut *mut JNIEnv as *mut *mut ::std::os::raw::c_void,
+                JNI_VERSION_1_6 as jint,
+            )
+        }};
+        let need_detach = if res == (JNI_OK as jint) {{
+            // Java methods can not be called while exception is pending
+            if unsafe {{ (**env).ExceptionCheck.unwrap()(env) }} != 0 {{
+                return;
+            }}
+            false
+        }} else if res == (JNI_EDETACHED as jint) {{
+            let res = unsafe {{
+                (**self.java_vm).AttachCurrentThread.unwrap()(
+                    self.java_vm,
+                    (&mut env) as *mut *mut JNIEnv as _,
+                    ::std::ptr::null_mut(),
+                )
+            }};
+            if res != 0 {{
+                return;
+            }}
+            true
+        }} else {{
+            return;
+        }};
+        {{
+            let _frame = JniLocalFrame::new(env, 2);
+            let target = from_std_string_jstring(record.target().to_string(), env);
+            let message = from_std_string_jstring(record.args().to_string(), env);
+            unsafe {{
+                (**env).CallStaticVoidMethod.unwrap()(
+                    env,
+                    self.class,
+                    self.log_method,
+                    record.level() as usize as jint,
+                    target,
+                    message,
+                );
+                if (**env).ExceptionCheck.unwrap()(env) != 0 {{
+                    (**env).ExceptionClear.unwrap()(env);
+                }}
+            }}
+        }}
+        if need_detach {{
+            unsafe {{ (**self.java_vm).DetachCurrentThread.unwrap()(self.java_vm) }};
+        }}
+    }}
+
+    fn flush(&self) {{}}
+}}
+
+#[allow(non_snake_case)]
+{no_mangle}
+{visibility}extern "C" fn {jni_prefix}_do_1init(env: *mut JNIEnv, _: jclass, max_level: jint) {{
+    static INIT: ::std::sync::Once = ::std::sync::Once::new();
+    INIT.call_once(|| {{
+        let mut java_vm: *mut JavaVM = ::std::ptr::null_mut();
+        let ret = unsafe {{ (**env).GetJavaVM.unwrap()(env, &mut java_vm) }};
+        assert_eq!(0, ret, "GetJavaVm failed");
+        // class is found here, because `FindClass` can not find
+        // application's classes in threads attached by Rust code
+        let class: jclass = swig_jni_find_class!(env, "{jni_class_name}");
+        let log_method: jmethodID = unsafe {{
+            (**env).GetStaticMethodID.unwrap()(
+                env,
+                class,
+                swig_c_str!("log"),
+                swig_c_str!("(ILjava/lang/String;Ljava/lang/String;)V"),
+            )
+        }};
+        assert!(!log_method.is_null(), "GetStaticMethodID for {log_class}.log failed");
+        let logger = Box::new(SwigJniLogger {{
+            java_vm,
+            class,
+            log_method,
+        }});
+        // if other logger is already installed, it is kept
+        let _ = ::log::set_logger(Box::leak(logger));
+    }});
+    ::log::set_max_level(match max_level {{
+        1 => ::log::LevelFilter::Error,
+        2 => ::log::LevelFilter::Warn,
+        3 => ::log::LevelFilter::Info,
+        4 => ::log::LevelFilter::Debug,
+        x if x >= 5 => ::log::LevelFilter::Trace,
+        _ => ::log::LevelFilter::Off,
+    }});
+}}
+"#,
+        no_mangle = no_mangle,
+        visibility = visibility,
+        jni_prefix = jni_prefix,
+        jni_class_name = jni_class_name,
+        log_class = LOG_CLASS,
+    );
+    let mut gen_code = vec![syn::parse_str(&code)
+        .unwrap_or_else(|err| panic_on_syn_error("java/jni internal RustLog", code, err))];
+    if register_natives {
+        let natives = [NativeMethod {
+            java_name: "do_init".into(),
+            signature: "(I)V".into(),
+            func_name: format!("{}_do_1init", jni_prefix),
+        }];
+        gen_code.push(generate_register_natives(
+            &register_natives_func_name_for_log_bridge(),
+            &jni_class_name,
+            &natives,
+        ));
+    }
+    gen_code
+}
+
+/// `JNI_OnLoad` that calls `register_funcs`, generated by `generate_rust_code`
+pub(in crate::java_jni) fn generate_jni_onload(register_funcs: &[String]) -> TokenStream {
+    let mut calls = String::new();
+    for func in register_funcs {
+        calls.push_str(&format!(
+            r#"
+    if {func}(env) != (JNI_OK as jint) {{
+        return JNI_ERR as jint;
+    }}"#,
+            func = func
+        ));
+    }
+    let code = format!(
+        r#"
+#[no_mangle]
+pub extern "C" fn JNI_OnLoad(vm: *mut JavaVM, _reserved: *mut ::std::os::raw::c_void) -> jint {{
+    let mut env: *mut JNIEnv = ::std::ptr::null_mut();
+    let res = unsafe {{
+        (**vm).GetEnv.unwrap()(
+            vm,
+            (&mut env) as *mut *mut JNIEnv as *mut *mut ::std::os::raw::c_void,
+            JNI_VERSION_1_6 as jint,
+        )
+    }};
+    if res != (JNI_OK as jint) {{
+        return JNI_ERR as jint;
+    }}{calls}
+    JNI_VERSION_1_6 as jint
+}}
+"#,
+        calls = calls
+    );
+    syn::parse_str(&code)
+        .unwrap_or_else(|err| panic_on_syn_error("java/jni internal JNI_OnLoad", code, err))
+}
+
+/// JNI descriptor of native method, like `(IJ)Ljava/lang/String;`
+fn jni_native_method_signature(
+    package_name: &str,
+    variant: MethodVariant,
+    f_method: &JniForeignMethodSignature,
+) -> std::result::Result<String, String> {
+    let mut sig = "(".to_string();
+    if let MethodVariant::Method(_) = variant {
+        sig.push('J');
+    }
+    for arg in &f_method.input {
+        let type_name = arg
+            .java_converter
+            .as_ref()
+            .map(|x| x.java_transition_type.as_str())
+            .unwrap_or_else(|| arg.as_ref().name.as_str());
+        sig.push_str(&java_type_to_jni_descriptor(package_name, type_name)?);
+    }
+    sig.push(')');
+    match variant {
+        MethodVariant::Constructor => sig.push('J'),
+        MethodVariant::Method(_) | MethodVariant::StaticMethod => sig.push_str(
+            &java_type_to_jni_descriptor(package_name, &f_method.output.base.name)?,
+        ),
+    }
+    Ok(sig)
+}
+
+fn java_type_to_jni_descriptor(
+    package_name: &str,
+    java_type: &str,
+) -> std::result::Result<String, String> {
+    let java_type = java_type.trim();
+    if java_type.ends_with("[]") {
+        let elem = &java_type[..java_type.len() - 2];
+        return Ok(format!(
+            "[{}",
+            java_type_to_jni_descriptor(package_name, elem)?
+        ));
+    }
+    // generic parameters are erased in descriptors
+    let java_type = match java_type.find('<') {
+        Some(pos) => java_type[..pos].trim(),
+        None => java_type,
+    };
+    let descr = match java_type {
+        "void" => "V".to_string(),
+        "boolean" => "Z".to_string(),
+        "byte" => "B".to_string(),
+        "char" => "C".to_string(),
+        "short" => "S".to_string(),
+        "int" => "I".to_string(),
+        "long" => "J".to_string(),
+        "float" => "F".to_string(),
+        "double" => "D".to_string(),
+        "" => return Err("Can not generate JNI signature for empty Java type".into()),
+        "String" | "Object" | "Boolean" | "Byte" | "Character" | "Short" | "Integer" | "Long"
+        | "Float" | "Double" => format!("Ljava/lang/{};", java_type),
+        _ if java_type.contains('.') => format!("L{};", java_type.replace('.', "/")),
+        // not qualified name, so this is class, enum or interface generated by us
+        _ => format!(
+            "L{};",
+            java_class_name_to_jni(&java_class_full_name(package_name, java_type))
+        ),
+    };
+    Ok(descr)
+}
+
 pub(in crate::java_jni) fn generate_rust_code_for_enum(
     package_name: &str,
     conv_map: &mut TypeMap,
@@ -304,9 +768,8 @@ mod swig_foreign_types_map {{
 #[swig_to_foreigner_hint = "{enum_name}"]
 impl SwigFrom<{rust_enum_name}> for jobject {{
    fn swig_from(x: {rust_enum_name}, env: *mut JNIEnv) -> jobject {{
-       let cls: jclass = unsafe {{ (**env).FindClass.unwrap()(env, swig_c_str!("{class_name}")) }};
-       assert!(!cls.is_null(), "FindClass {class_name} failed");
-       let static_field_id = match x {{
+       let cls: jclass = swig_jni_find_class!(env, "{class_name}");
+       let item_id: jfieldID = match x {{
 "#,
         enum_name = enum_info.name,
         rust_enum_name = rust_enum_name,
@@ -318,10 +781,11 @@ impl SwigFrom<{rust_enum_name}> for jobject {{
         write!(
             &mut code,
             r#"
-           {rust_item} => swig_c_str!("{java_item}"),
+           {rust_item} => swig_jni_get_id!(env, cls, GetStaticFieldID, "{java_item}", "L{class_name};"),
 "#,
             rust_item = DisplayToTokens(&item.rust_name),
             java_item = item.name,
+            class_name = enum_class_name,
         )
         .unwrap();
     }
@@ -329,10 +793,6 @@ impl SwigFrom<{rust_enum_name}> for jobject {{
         &mut code,
         r#"
       }};
-      let item_id: jfieldID = unsafe {{
-          (**env).GetStaticFieldID.unwrap()(env, cls , static_field_id,
-                                             swig_c_str!("L{class_name};"))
-      }};
       assert!(!item_id.is_null(), "Can not find item in {class_name}");
       let ret: jobject = unsafe {{
         (**env).GetStaticObjectField.unwrap()(env, cls, item_id)
@@ -350,6 +810,202 @@ impl SwigFrom<{rust_enum_name}> for jobject {{
     Ok(vec![])
 }
 
+/// Conversations of value-like class from/to Java record,
+/// all values copied via getters and constructor
+pub(in crate::java_jni) fn generate_rust_code_for_record(
+    package_name: &str,
+    conv_map: &mut TypeMap,
+    pointer_target_width: usize,
+    class: &ForeignerClassInfo,
+    components: &[RecordComponent],
+    constructor: Option<(&ForeignerMethod, &JniForeignMethodSignature)>,
+) -> Result<Vec<TokenStream>> {
+    use std::fmt::Write;
+
+    let self_type = class.self_type_as_ty();
+    let rust_type_name = DisplayToTokens(&self_type).to_string();
+    let jni_class_name =
+        java_class_name_to_jni(&java_class_full_name(package_name, &class.name.to_string()));
+    let descriptor = |f_method: &JniForeignMethodSignature| {
+        java_type_to_jni_descriptor(package_name, &f_method.output.base.name)
+            .map_err(|err| DiagnosticError::new(class.src_id, class.span(), err))
+    };
+
+    let mut gen_items = Vec::<TokenStream>::new();
+    let mut constructor_sig = "(".to_string();
+    for comp in components {
+        constructor_sig.push_str(&descriptor(comp.f_method)?);
+    }
+    constructor_sig.push_str(")V");
+
+    let mut code = format!(
+        r#"
+mod swig_foreign_types_map {{
+    #![swig_foreigner_type = "{class_name}"]
+    #![swig_rust_type_not_unique = "jobject"]
+}}
+#[swig_to_foreigner_hint = "{class_name}"]
+impl SwigFrom<{rust_type}> for jobject {{
+    #[allow(unused_mut)]
+    fn swig_from(x: {rust_type}, env: *mut JNIEnv) -> jobject {{
+        let cls: jclass = swig_jni_find_class!(env, "{jni_class_name}");
+        let ctor_id: jmethodID =
+            swig_jni_get_id!(env, cls, GetMethodID, "<init>", "{constructor_sig}");
+        assert!(!ctor_id.is_null(), "Can not find constructor of {jni_class_name}");
+        let this: &{rust_type} = &x;
+"#,
+        class_name = class.name,
+        rust_type = rust_type_name,
+        jni_class_name = jni_class_name,
+        constructor_sig = constructor_sig,
+    );
+    let mut args = String::new();
+    for (i, comp) in components.iter().enumerate() {
+        let jni_type = conv_map[comp.f_method.output.base.correspoding_rust_type]
+            .typename()
+            .to_string();
+        let (mut deps, convert_output_code) = foreign_from_rust_convert_method_output(
+            conv_map,
+            class.src_id,
+            &comp.method.fn_decl.output,
+            &comp.f_method.output.base,
+            "ret",
+            "jobject",
+        )?;
+        gen_items.append(&mut deps);
+        let real_output_typename = match comp.method.fn_decl.output {
+            syn::ReturnType::Default => "()".into(),
+            syn::ReturnType::Type(_, ref ty) => normalize_ty_lifetimes(&*ty),
+        };
+        write!(
+            &mut code,
+            r#"
+        let a_{i}: {jni_type} = {{
+            let mut ret: {real_output_typename} = {rust_func_name}(this);
+{convert_output_code}
+            ret
+        }};
+"#,
+            i = i,
+            jni_type = jni_type,
+            real_output_typename = real_output_typename,
+            rust_func_name = DisplayToTokens(&comp.method.rust_id),
+            convert_output_code = convert_output_code,
+        )
+        .unwrap();
+        if i != 0 {
+            args.push_str(", ");
+        }
+        match jni_primitive_type(&jni_type) {
+            Some((field, _)) => write!(&mut args, "jvalue {{ {}: a_{} }}", field, i),
+            None => write!(&mut args, "jvalue {{ l: a_{} as jobject }}", i),
+        }
+        .unwrap();
+    }
+    write!(
+        &mut code,
+        r#"
+        let args: [jvalue; {n_args}] = [{args}];
+        let ret: jobject =
+            unsafe {{ (**env).NewObjectA.unwrap()(env, cls, ctor_id, args.as_ptr()) }};
+        assert!(!ret.is_null(), "Can not create {jni_class_name}");
+        ret
+    }}
+}}
+"#,
+        n_args = components.len(),
+        args = args,
+        jni_class_name = jni_class_name,
+    )
+    .unwrap();
+
+    if let Some((method, f_method)) = constructor {
+        write!(
+            &mut code,
+            r#"
+#[swig_from_foreigner_hint = "{class_name}"]
+impl SwigFrom<jobject> for {rust_type} {{
+    #[allow(unused_mut)]
+    fn swig_from(x: jobject, env: *mut JNIEnv) -> {rust_type} {{
+        let cls: jclass = swig_jni_find_class!(env, "{jni_class_name}");
+"#,
+            class_name = class.name,
+            rust_type = rust_type_name,
+            jni_class_name = jni_class_name,
+        )
+        .unwrap();
+        for (i, (comp, arg)) in components.iter().zip(&f_method.input).enumerate() {
+            let jni_type = conv_map[arg.base.correspoding_rust_type].typename();
+            let (get_field, cast) = match jni_primitive_type(jni_type) {
+                Some((_, name)) => (format!("Get{}Field", name), String::new()),
+                None => ("GetObjectField".to_string(), format!(" as {}", jni_type)),
+            };
+            write!(
+                &mut code,
+                r#"
+        let field_id: jfieldID =
+            swig_jni_get_id!(env, cls, GetFieldID, "{name}", "{field_sig}");
+        assert!(!field_id.is_null(), "Can not find {name} field of {jni_class_name}");
+        let a_{i}: {jni_type} =
+            unsafe {{ (**env).{get_field}.unwrap()(env, x, field_id) }}{cast};
+"#,
+                i = i,
+                name = comp.name,
+                field_sig = descriptor(comp.f_method)?,
+                jni_class_name = jni_class_name,
+                jni_type = jni_type,
+                get_field = get_field,
+                cast = cast,
+            )
+            .unwrap();
+        }
+        let (mut deps, convert_input_code) = foreign_to_rust_convert_method_inputs(
+            conv_map,
+            class.src_id,
+            method,
+            f_method,
+            (0..f_method.input.len()).map(|v| format!("a_{}", v)),
+            &rust_type_name,
+        )?;
+        gen_items.append(&mut deps);
+        let args_names: Vec<String> = (0..f_method.input.len())
+            .map(|v| format!("a_{}", v))
+            .collect();
+        write!(
+            &mut code,
+            r#"
+{convert_input_code}
+        let this: {rust_type} = {rust_func_name}({args_names});
+        this
+    }}
+}}
+"#,
+            convert_input_code = convert_input_code,
+            rust_type = rust_type_name,
+            rust_func_name = DisplayToTokens(&method.rust_id),
+            args_names = args_names.join(", "),
+        )
+        .unwrap();
+    }
+    conv_map.merge(SourceId::none(), &code, pointer_target_width)?;
+    Ok(gen_items)
+}
+
+/// Field of `jvalue` union and suffix of `Get*Field` for primitive JNI type
+fn jni_primitive_type(jni_type: &str) -> Option<(&'static str, &'static str)> {
+    match jni_type {
+        "jboolean" => Some(("z", "Boolean")),
+        "jbyte" => Some(("b", "Byte")),
+        "jchar" => Some(("c", "Char")),
+        "jshort" => Some(("s", "Short")),
+        "jint" => Some(("i", "Int")),
+        "jlong" => Some(("j", "Long")),
+        "jfloat" => Some(("f", "Float")),
+        "jdouble" => Some(("d", "Double")),
+        _ => None,
+    }
+}
+
 pub(in crate::java_jni) fn generate_interface(
     package_name: &str,
     conv_map: &mut TypeMap,
@@ -366,10 +1022,13 @@ impl SwigFrom<jobject> for Box<{trait_name}> {{
     fn swig_from(this: jobject, env: *mut JNIEnv) -> Self {{
         let mut cb = JavaCallback::new(this, env);
         cb.methods.reserve({methods_len});
-        let class = unsafe {{ (**env).GetObjectClass.unwrap()(env, cb.this) }};
-        assert!(!class.is_null(), "GetObjectClass return null class for {interface_name}");
+        let class: jclass = swig_jni_find_class!(env, "{jni_interface_name}");
 "#,
         interface_name = interface.name,
+        jni_interface_name = java_class_name_to_jni(&java_class_full_name(
+            package_name,
+            &interface.name.to_string()
+        )),
         trait_name = DisplayToTokens(&interface.self_type),
         methods_len = interface.items.len(),
     );
@@ -377,10 +1036,8 @@ impl SwigFrom<jobject> for Box<{trait_name}> {{
         write!(
             &mut new_conv_code,
             r#"
-        let method_id: jmethodID = unsafe {{
-            (**env).GetMethodID.unwrap()(env, class, swig_c_str!("{method_name}"),
-                                         swig_c_str!("{method_sig}"))
-        }};
+        let method_id: jmethodID =
+            swig_jni_get_id!(env, class, GetMethodID, "{method_name}", "{method_sig}");
         assert!(!method_id.is_null(), "Can not find {method_name} id");
         cb.methods.push(method_id);
 "#,
@@ -439,7 +1096,7 @@ impl {trait_name} for JavaCallback {{
         let args_with_types: String = [self_arg.to_string(), rest_args_with_types].concat();
         assert!(!method.fn_decl.inputs.is_empty());
         let n_args = method.fn_decl.inputs.len() - 1;
-        let (args, type_size_asserts) = convert_args_for_variadic_function_call(f_method);
+        let (args, type_size_asserts) = convert_args_for_variadic_function_call(conv_map, f_method);
         let (mut conv_deps, convert_args) = rust_to_foreign_convert_method_inputs(
             conv_map,
             interface.src_id,
@@ -457,6 +1114,7 @@ impl {trait_name} for JavaCallback {{
 {type_size_asserts}
         let env = self.get_jni_env();
         if let Some(env) = env.env {{
+            let _frame = JniLocalFrame::new(env, {frame_capacity});
 {convert_args}
             unsafe {{
                 (**env).CallVoidMethod.unwrap()(env, self.this, self.methods[{method_idx}]
@@ -476,6 +1134,7 @@ impl {trait_name} for JavaCallback {{
             args = args,
             convert_args = convert_args,
             type_size_asserts = type_size_asserts,
+            frame_capacity = n_args + 16,
         )
         .unwrap();
         gen_items.append(&mut conv_deps);
@@ -523,6 +1182,18 @@ lazy_static! {
     };
 }
 
+fn escape_underscore(input: &str, output: &mut String) {
+    for c in input.chars() {
+        match c {
+            '.' => output.push('_'),
+            '[' => output.push_str("_3"),
+            '_' => output.push_str("_1"),
+            ';' => output.push_str("_2"),
+            _ => output.push(c),
+        }
+    }
+}
+
 fn generate_jni_func_name(
     package_name: &str,
     class: &ForeignerClassInfo,
@@ -532,17 +1203,6 @@ fn generate_jni_func_name(
 ) -> Result<String> {
     let mut output = String::new();
     output.push_str("Java_");
-    fn escape_underscore(input: &str, output: &mut String) {
-        for c in input.chars() {
-            match c {
-                '.' => output.push('_'),
-                '[' => output.push_str("_3"),
-                '_' => output.push_str("_1"),
-                ';' => output.push_str("_2"),
-                _ => output.push(c),
-            }
-        }
-    }
     escape_underscore(package_name, &mut output);
     output.push_str("_");
     escape_underscore(&class.name.to_string(), &mut output);
@@ -581,6 +1241,7 @@ fn generate_jni_func_name(
 }
 
 fn generate_jni_args_with_types(
+    conv_map: &TypeMap,
     f_method: &JniForeignMethodSignature,
 ) -> std::result::Result<String, String> {
     use std::fmt::Write;
@@ -591,23 +1252,110 @@ fn generate_jni_args_with_types(
             &mut buf,
             "a_{}: {}, ",
             i,
-            f_type_info.as_ref().correspoding_rust_type.typename()
+            conv_map[f_type_info.as_ref().correspoding_rust_type].typename()
         )
         .map_err(fmt_write_err_map)?;
     }
     Ok(buf)
 }
 
-fn generate_static_method(conv_map: &mut TypeMap, mc: &MethodContext) -> Result<Vec<TokenStream>> {
-    let jni_ret_type = mc.f_method.output.correspoding_rust_type.typename();
-    let (mut deps_code_out, convert_output_code) = foreign_from_rust_convert_method_output(
+/// Returns code to convert arguments and expression to call Rust function,
+/// for `#[critical_arrays]` method both are placed into block after acquiring of
+/// arrays, so there are no JNI calls inside of critical sections
+fn call_with_critical_arrays(
+    mc: &MethodContext,
+    convert_input_code: String,
+    call: String,
+) -> (String, String) {
+    if !mc.method.critical_arrays {
+        return (convert_input_code, call);
+    }
+    let skip_n = match mc.method.variant {
+        MethodVariant::Method(_) => 1,
+        _ => 0,
+    };
+    let mut acquire_code = String::new();
+    for (i, arg) in mc.method.fn_decl.inputs.iter().skip(skip_n).enumerate() {
+        if map_type::critical_array_elem(fn_arg_type(arg)).is_some() {
+            // move array after other arguments, so it is released before them
+            acquire_code.push_str(&format!(
+                "    let mut a_{i} = a_{i};\n    let a_{i}: {ty} = unsafe {{ a_{i}.acquire() }};\n",
+                i = i,
+                ty = normalize_ty_lifetimes(fn_arg_type(arg)),
+            ));
+        }
+    }
+    (
+        String::new(),
+        format!(
+            "{{\n{}{}    {}\n    }}",
+            convert_input_code, acquire_code, call
+        ),
+    )
+}
+
+/// For `#[completable_future]` method future is created after conversation
+/// of arguments and returned instead of result of Rust function
+fn call_with_completion(
+    mc: &MethodContext,
+    convert_input_code: String,
+    call: String,
+) -> (String, String) {
+    match mc.completion_code {
+        Some(ref completion_code) => (
+            convert_input_code + completion_code,
+            format!("{{\n        {};\n        swig_future\n    }}", call),
+        ),
+        None => (convert_input_code, call),
+    }
+}
+
+fn convert_method_output(
+    conv_map: &mut TypeMap,
+    mc: &MethodContext,
+    jni_ret_type: &str,
+) -> Result<(Vec<TokenStream>, String)> {
+    if mc.completion_code.is_some() {
+        // future is already `jobject`
+        return Ok((Vec::new(), String::new()));
+    }
+    foreign_from_rust_convert_method_output(
         conv_map,
         mc.class.src_id,
         &mc.method.fn_decl.output,
-        &mc.f_method.output,
+        &mc.f_method.output.base,
         "ret",
-        &jni_ret_type,
-    )?;
+        jni_ret_type,
+    )
+}
+
+/// Arguments `JNIEnv` and `jclass` of JNI function and code before conversation
+/// of arguments, `#[critical_native]` function gets no `JNIEnv` and `jclass`,
+/// so `env` is null, that is enough for conversations of primitive types
+fn jni_env_args(mc: &MethodContext) -> (&'static str, &'static str) {
+    if mc.method.critical_native {
+        ("", "    let env: *mut JNIEnv = ::std::ptr::null_mut();\n")
+    } else {
+        ("env: *mut JNIEnv, _: jclass, ", "")
+    }
+}
+
+/// Code that enters span of `tracing` crate and records sizes of arguments,
+/// if it is enabled
+fn tracing_span_code(mc: &MethodContext) -> (String, String) {
+    if mc.tracing_spans {
+        tracing_spans::method_span_code(mc.class, mc.method, mc.f_method.input.len())
+    } else {
+        (String::new(), String::new())
+    }
+}
+
+fn generate_static_method(conv_map: &mut TypeMap, mc: &MethodContext) -> Result<Vec<TokenStream>> {
+    let jni_ret_type = conv_map[mc.f_method.output.base.correspoding_rust_type]
+        .typename()
+        .to_string();
+    let (mut deps_code_out, convert_output_code) =
+        convert_method_output(conv_map, mc, &jni_ret_type)?;
     let n_args = mc.f_method.input.len();
     let (deps_code_in, convert_input_code) = foreign_to_rust_convert_method_inputs(
         conv_map,
@@ -617,26 +1365,46 @@ fn generate_static_method(conv_map: &mut TypeMap, mc: &MethodContext) -> Result<
         (0..n_args).map(|v| format!("a_{}", v)),
         &jni_ret_type,
     )?;
+    let (convert_input_code, call) = call_with_completion(
+        mc,
+        convert_input_code,
+        format!("{}({})", DisplayToTokens(&mc.method.rust_id), mc.args_names),
+    );
+    let (convert_input_code, call) = call_with_critical_arrays(mc, convert_input_code, call);
+    let (env_args, env_code) = jni_env_args(mc);
+    let (span_code, record_code) = tracing_span_code(mc);
 
     let code = format!(
         r#"
 #[allow(non_snake_case, unused_variables, unused_mut)]
-#[no_mangle]
-pub extern "C" fn {func_name}(env: *mut JNIEnv, _: jclass, {decl_func_args}) -> {jni_ret_type} {{
-{convert_input_code}
-    let mut ret: {real_output_typename} = {rust_func_name}({args_names});
-{convert_output_code}
-    ret
+{no_mangle}
+{visibility}extern "C" fn {func_name}({env_args}{decl_func_args}) -> {jni_ret_type} {{
+{env_code}{body}
 }}
 "#,
         func_name = mc.jni_func_name,
+        no_mangle = mc.no_mangle,
+        visibility = mc.visibility,
+        env_args = env_args,
+        env_code = env_code,
         decl_func_args = mc.decl_func_args,
         jni_ret_type = jni_ret_type,
-        convert_input_code = convert_input_code,
-        rust_func_name = DisplayToTokens(&mc.method.rust_id),
-        args_names = mc.args_names,
-        convert_output_code = convert_output_code,
-        real_output_typename = mc.real_output_typename,
+        body = catch_panic_code(
+            mc.catch_panics,
+            &jni_ret_type,
+            format!(
+                r#"{span_code}{convert_input_code}
+{record_code}    let mut ret: {real_output_typename} = {call};
+{convert_output_code}
+    ret"#,
+                span_code = span_code,
+                convert_input_code = convert_input_code,
+                record_code = record_code,
+                call = call,
+                convert_output_code = convert_output_code,
+                real_output_typename = mc.real_output_typename,
+            )
+        ),
     );
     let mut gen_code = deps_code_in;
     gen_code.append(&mut deps_code_out);
@@ -663,13 +1431,19 @@ fn generate_constructor(
         (0..n_args).map(|v| format!("a_{}", v)),
         "jlong",
     )?;
+    let (convert_input_code, call) = call_with_critical_arrays(
+        mc,
+        convert_input_code,
+        format!("{}({})", DisplayToTokens(&mc.method.rust_id), mc.args_names),
+    );
 
     let this_type = conv_map.ty_to_rust_type(&this_type);
     let construct_ret_type = conv_map.ty_to_rust_type(&construct_ret_type);
+    let (span_code, record_code) = tracing_span_code(mc);
 
     let (mut deps_this, convert_this) = conv_map.convert_rust_types(
-        construct_ret_type.to_idx(),
-        this_type.to_idx(),
+        construct_ret_type,
+        this_type,
         "this",
         "jlong",
         (mc.class.src_id, mc.method.span()),
@@ -677,24 +1451,34 @@ fn generate_constructor(
 
     let code = format!(
         r#"
-#[no_mangle]
+{no_mangle}
 #[allow(unused_variables, unused_mut, non_snake_case)]
-pub extern "C" fn {func_name}(env: *mut JNIEnv, _: jclass, {decl_func_args}) -> jlong {{
-{convert_input_code}
-    let this: {real_output_typename} = {rust_func_name}({args_names});
-{convert_this}
-{box_this}
-    this as jlong
+{visibility}extern "C" fn {func_name}(env: *mut JNIEnv, _: jclass, {decl_func_args}) -> jlong {{
+{body}
 }}
 "#,
         func_name = mc.jni_func_name,
-        convert_this = convert_this,
+        no_mangle = mc.no_mangle,
+        visibility = mc.visibility,
         decl_func_args = mc.decl_func_args,
-        convert_input_code = convert_input_code,
-        rust_func_name = DisplayToTokens(&mc.method.rust_id),
-        args_names = mc.args_names,
-        box_this = code_box_this,
-        real_output_typename = mc.real_output_typename,
+        body = catch_panic_code(
+            mc.catch_panics,
+            "jlong",
+            format!(
+                r#"{span_code}{convert_input_code}
+{record_code}    let this: {real_output_typename} = {call};
+{convert_this}
+{box_this}
+    this as jlong"#,
+                convert_this = convert_this,
+                span_code = span_code,
+                convert_input_code = convert_input_code,
+                record_code = record_code,
+                call = call,
+                box_this = code_box_this,
+                real_output_typename = mc.real_output_typename,
+            )
+        ),
     );
     let mut gen_code = deps_code_in;
     gen_code.append(&mut deps_this);
@@ -709,9 +1493,11 @@ fn generate_method(
     conv_map: &mut TypeMap,
     mc: &MethodContext,
     self_variant: SelfTypeVariant,
-    this_type_for_method: &RustType,
+    this_type_for_method: RustTypeIdx,
 ) -> Result<Vec<TokenStream>> {
-    let jni_ret_type = mc.f_method.output.correspoding_rust_type.typename();
+    let jni_ret_type = conv_map[mc.f_method.output.base.correspoding_rust_type]
+        .typename()
+        .to_string();
     let n_args = mc.f_method.input.len();
     let (deps_code_in, convert_input_code) = foreign_to_rust_convert_method_inputs(
         conv_map,
@@ -722,61 +1508,79 @@ fn generate_method(
         &jni_ret_type,
     )?;
 
-    let (mut deps_code_out, convert_output_code) = foreign_from_rust_convert_method_output(
-        conv_map,
-        mc.class.src_id,
-        &mc.method.fn_decl.output,
-        &mc.f_method.output,
-        "ret",
-        &jni_ret_type,
-    )?;
+    let (mut deps_code_out, convert_output_code) =
+        convert_method_output(conv_map, mc, &jni_ret_type)?;
 
     //&mut constructor_real_type -> &mut class.self_type
 
     let (from_ty, to_ty): (Type, Type) = create_suitable_types_for_constructor_and_self(
         self_variant,
         mc.class,
-        &this_type_for_method.ty,
+        &conv_map[this_type_for_method].ty,
     );
     let from_ty = conv_map.find_or_alloc_rust_type(&from_ty, mc.class.src_id);
-    let this_type_ref = from_ty.normalized_name.as_str();
+    let this_type_ref = conv_map[from_ty].normalized_name.clone();
     let to_ty = conv_map.find_or_alloc_rust_type(&to_ty, mc.class.src_id);
 
     let (mut deps_this, convert_this) = conv_map.convert_rust_types(
-        from_ty.to_idx(),
-        to_ty.to_idx(),
+        from_ty,
+        to_ty,
         "this",
-        jni_ret_type,
+        &jni_ret_type,
         (mc.class.src_id, mc.method.span()),
     )?;
+    let (convert_input_code, call) = call_with_completion(
+        mc,
+        convert_input_code,
+        format!(
+            "{}(this, {})",
+            DisplayToTokens(&mc.method.rust_id),
+            mc.args_names
+        ),
+    );
+    let (convert_input_code, call) = call_with_critical_arrays(mc, convert_input_code, call);
+    let (env_args, env_code) = jni_env_args(mc);
+    let (span_code, record_code) = tracing_span_code(mc);
 
     let code = format!(
         r#"
 #[allow(non_snake_case, unused_variables, unused_mut)]
-#[no_mangle]
-pub extern "C"
- fn {func_name}(env: *mut JNIEnv, _: jclass, this: jlong, {decl_func_args}) -> {jni_ret_type} {{
-{convert_input_code}
-    let this: {this_type_ref} = unsafe {{
-        jlong_to_pointer::<{this_type}>(this).as_mut().unwrap()
-    }};
-{convert_this}
-    let mut ret: {real_output_typename} = {rust_func_name}(this, {args_names});
-{convert_output_code}
-    ret
+{no_mangle}
+{visibility}extern "C"
+ fn {func_name}({env_args}this: jlong, {decl_func_args}) -> {jni_ret_type} {{
+{env_code}{body}
 }}
 "#,
         func_name = mc.jni_func_name,
+        no_mangle = mc.no_mangle,
+        visibility = mc.visibility,
+        env_args = env_args,
+        env_code = env_code,
         decl_func_args = mc.decl_func_args,
-        convert_input_code = convert_input_code,
         jni_ret_type = jni_ret_type,
-        this_type_ref = this_type_ref,
-        this_type = this_type_for_method.normalized_name,
-        convert_this = convert_this,
-        rust_func_name = DisplayToTokens(&mc.method.rust_id),
-        args_names = mc.args_names,
-        convert_output_code = convert_output_code,
-        real_output_typename = mc.real_output_typename,
+        body = catch_panic_code(
+            mc.catch_panics,
+            &jni_ret_type,
+            format!(
+                r#"{span_code}{convert_input_code}
+{record_code}    let this: {this_type_ref} = unsafe {{
+        jlong_to_pointer::<{this_type}>(this).as_mut().unwrap()
+    }};
+{convert_this}
+    let mut ret: {real_output_typename} = {call};
+{convert_output_code}
+    ret"#,
+                span_code = span_code,
+                convert_input_code = convert_input_code,
+                record_code = record_code,
+                this_type_ref = this_type_ref,
+                this_type = conv_map[this_type_for_method].normalized_name,
+                convert_this = convert_this,
+                call = call,
+                convert_output_code = convert_output_code,
+                real_output_typename = mc.real_output_typename,
+            )
+        ),
     );
     let mut gen_code = deps_code_in;
     gen_code.append(&mut deps_code_out);
@@ -821,11 +1625,11 @@ fn jni_method_signature(
     }
     ret.push(')');
     let sig = JAVA_TYPE_NAMES_FOR_JNI_SIGNATURE
-        .get(&*method.output.name.as_str())
+        .get(&*method.output.base.name.as_str())
         .unwrap_or_else(|| {
             panic!(
                 "Unknown type `{}`, can not generate jni signature",
-                method.output.name
+                method.output.base.name
             )
         });
     ret.push_str(sig);
@@ -838,15 +1642,18 @@ fn jni_method_signature(
 // for more details.
 // return arg with conversation plus asserts
 fn convert_args_for_variadic_function_call(
+    conv_map: &TypeMap,
     f_method: &JniForeignMethodSignature,
 ) -> (String, &'static str) {
     use std::fmt::Write;
 
     let mut ret = String::new();
     for (i, arg) in f_method.input.iter().enumerate() {
-        if let Some(conv_type) = JNI_FOR_VARIADIC_C_FUNC_CALL
-            .get(&*arg.as_ref().correspoding_rust_type.normalized_name.as_str())
-        {
+        if let Some(conv_type) = JNI_FOR_VARIADIC_C_FUNC_CALL.get(
+            &*conv_map[arg.as_ref().correspoding_rust_type]
+                .normalized_name
+                .as_str(),
+        ) {
             write!(&mut ret, ", a_{} as {}", i, conv_type).unwrap();
         } else {
             write!(&mut ret, ", a_{}", i).unwrap();
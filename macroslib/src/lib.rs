@@ -7,7 +7,13 @@
 //! [README](https://github.com/Dushistov/rust_swig/blob/master/README.md)
 
 #![recursion_limit = "128"]
+// helpers shared by generators are partly unused if only one of them is enabled
+#![cfg_attr(not(all(feature = "java", feature = "cpp")), allow(dead_code))]
 
+#[cfg(not(any(feature = "java", feature = "cpp")))]
+compile_error!("at least one of features \"java\" or \"cpp\" should be enabled");
+
+#[cfg(feature = "cpp")]
 #[macro_use]
 extern crate strum_macros;
 
@@ -18,34 +24,62 @@ macro_rules! parse_type {
     }}
 }
 
+mod api_manifest;
+mod api_version;
+mod catch_panics;
 mod code_parse;
+mod config_file;
+#[cfg(feature = "cpp")]
 mod cpp;
+mod diagnostic_codes;
+mod doc_links;
+mod enum_check;
 mod error;
+mod expansion_cache;
 pub mod file_cache;
+mod formatter;
+#[cfg(feature = "java")]
 mod java_jni;
+mod name_collisions;
+mod profiler;
+mod send_check;
+mod signature_check;
 mod source_registry;
+mod tracing_spans;
 mod typemap;
 mod types;
+mod unsupported_types;
 
 use std::{
     cell::RefCell,
     env,
-    io::Write,
+    hash::{Hash, Hasher},
+    io::{self, Write},
     mem,
     path::{Path, PathBuf},
+    rc::Rc,
     str::FromStr,
 };
 
 use log::debug;
 use proc_macro2::TokenStream;
+use rustc_hash::FxHashMap;
+#[cfg(feature = "cpp")]
 use rustc_hash::FxHashSet;
 use syn::spanned::Spanned;
 
 use crate::{
-    error::{panic_on_parse_error, DiagnosticError, Result},
+    api_manifest::ApiManifest,
+    diagnostic_codes::DiagnosticCode,
+    error::{
+        panic_on_parse_error, parse_error_to_compile_error, DiagnosticError, Result, SourceIdSpan,
+    },
+    expansion_cache::ExpansionCache,
+    formatter::Formatter,
+    profiler::Stage,
     source_registry::{SourceId, SourceRegistry},
-    typemap::{ast::DisplayToTokens, TypeMap},
-    types::ItemToExpand,
+    typemap::{ast::DisplayToTokens, parse_file, ParseCache, RulePrecedence, TargetCfg, TypeMap},
+    types::{ExpandedItem, ItemToExpand},
 };
 
 /// Calculate target pointer width from environment variable
@@ -59,20 +93,67 @@ pub fn target_pointer_width_from_env() -> Option<usize> {
         })
 }
 
+/// Extended description with example for code of error or warning,
+/// like `SWIG0001`, that rust_swig prints with diagnostic messages
+pub fn explain_diagnostic(code: &str) -> Option<String> {
+    DiagnosticCode::from_id(code).map(|code| {
+        let kind = if code.is_warning() {
+            "warning"
+        } else {
+            "error"
+        };
+        format!("{} {}\n\n{}", kind, code, code.explanation())
+    })
+}
+
 /// `LanguageConfig` contains configuration for specific programming language
+#[derive(Debug, Hash)]
 pub enum LanguageConfig {
+    #[cfg(feature = "java")]
     JavaConfig(JavaConfig),
+    #[cfg(feature = "cpp")]
     CppConfig(CppConfig),
 }
 
 /// Configuration for Java binding generation
+#[cfg(feature = "java")]
+#[derive(Debug, Hash)]
 pub struct JavaConfig {
     output_dir: PathBuf,
     package_name: String,
     null_annotation_package: Option<String>,
     optional_package: String,
+    android_module: Option<PathBuf>,
+    register_natives: bool,
+    records: bool,
+    java_lists: bool,
+    java_optional: bool,
+    java_streams: bool,
+    /// module name and name of native library for `module-info.java`
+    module_info: Option<(String, String)>,
+    /// Rust error type and Java exception class thrown for it
+    exceptions: Vec<(String, String)>,
+    unchecked_exceptions: bool,
+    kotlin_friendly: bool,
+    /// minimal Android API level, that generated code should support
+    min_api_level: Option<u32>,
+    /// class with static initializer, that loads native library, and name of library
+    load_native_library: Option<(String, String)>,
+    /// Rust target and name of native library for it
+    native_library_targets: Vec<(String, String)>,
+    /// class with `static void loadLibrary(String)`, used instead of `System`
+    native_library_loader: Option<String>,
+    /// Java logger for records of Rust `log` crate
+    log_bridge: Option<JavaLogBackend>,
+    /// What to do with panics in methods, `None` - panics are not caught
+    catch_panics: Option<PanicAction>,
+    /// Append backtrace of panic to message of exception
+    panic_backtrace: bool,
+    /// Instrument native methods with spans of `tracing` crate
+    tracing_spans: bool,
 }
 
+#[cfg(feature = "java")]
 impl JavaConfig {
     /// Create `JavaConfig`
     /// # Arguments
@@ -84,6 +165,24 @@ impl JavaConfig {
             package_name,
             null_annotation_package: None,
             optional_package: "java.util".to_string(),
+            android_module: None,
+            register_natives: false,
+            records: false,
+            java_lists: false,
+            java_optional: true,
+            java_streams: false,
+            module_info: None,
+            exceptions: Vec::new(),
+            unchecked_exceptions: false,
+            kotlin_friendly: false,
+            min_api_level: None,
+            load_native_library: None,
+            native_library_targets: Vec::new(),
+            native_library_loader: None,
+            log_bridge: None,
+            catch_panics: None,
+            panic_backtrace: false,
+            tracing_spans: false,
         }
     }
     /// Use @NonNull for types where appropriate
@@ -121,24 +220,254 @@ impl JavaConfig {
         self.optional_package = optional_package;
         self
     }
+    /// Methods returning `Option<T>` return `java.util.Optional<T>`,
+    /// or `OptionalInt`, `OptionalLong`, `OptionalDouble` for `i32`, `i64`, `f64`.
+    /// If disabled, `Option<T>`, where `T` is exported class, `String`,
+    /// `i32`, `i64` or `f64`, is returned as nullable `T`, `String`,
+    /// `Integer`, `Long` or `Double`. Default value is true
+    pub fn use_optional(mut self, enable: bool) -> JavaConfig {
+        self.java_optional = enable;
+        self
+    }
+    /// Methods returning `Box<dyn Iterator<Item = T>>`, where `T` is exported class,
+    /// `String`, `i32`, `i64` or `f64`, return `java.util.stream.Stream<T>`
+    /// instead of `java.util.Iterator<T>`. Elements are pulled lazily from Rust,
+    /// and Rust iterator is dropped when stream is closed or exhausted.
+    /// Default value is false
+    pub fn use_java_streams(mut self, enable: bool) -> JavaConfig {
+        self.java_streams = enable;
+        self
+    }
+    /// Lay out generated files as Android library module:
+    /// java sources are placed into `module_dir/src/main/java/<package>`,
+    /// plus `build.gradle`, `AndroidManifest.xml` and `src/main/jniLibs/<abi>`
+    /// directories, where Rust library should be copied before AAR build.
+    /// Overrides `output_dir` passed to `JavaConfig::new`
+    pub fn android_library_module(mut self, module_dir: PathBuf) -> JavaConfig {
+        let mut output_dir = module_dir.join("src").join("main").join("java");
+        for part in self.package_name.split('.') {
+            output_dir.push(part);
+        }
+        self.output_dir = output_dir;
+        self.android_module = Some(module_dir);
+        self
+    }
+    /// Instead of exporting `Java_package_Class_method` symbols,
+    /// generate `JNI_OnLoad` that binds native methods via `RegisterNatives`.
+    /// So Rust library need to export only `JNI_OnLoad`,
+    /// and Java classes can be renamed by obfuscator,
+    /// if obfuscator also updates names in Rust code. Default value is false
+    pub fn use_register_natives(mut self, enable: bool) -> JavaConfig {
+        self.register_natives = enable;
+        self
+    }
+    /// Generate Java records (Java 16+) for value-like classes:
+    /// `#[derive(Copy)]` classes with only getters (`&self` methods
+    /// without arguments), plus optionally `clone` and constructor
+    /// with arguments of the same types as getters.
+    /// Values of getters are copied into record once, when object is passed
+    /// from Rust to Java, and record is converted back via constructor,
+    /// instead of keeping pointer to Rust object. Default value is false
+    pub fn use_records(mut self, enable: bool) -> JavaConfig {
+        self.records = enable;
+        self
+    }
+    /// Methods returning `Vec<T>`, where `T` is exported class or `String`,
+    /// return `java.util.List<T>` (`java.util.ArrayList` instance)
+    /// instead of array `T []`. Default value is false
+    pub fn use_java_lists(mut self, enable: bool) -> JavaConfig {
+        self.java_lists = enable;
+        self
+    }
+    /// Generate `module-info.java` for Java Platform Module System,
+    /// that exports package with generated classes.
+    /// File is placed into root of Java sources, so `output_dir`
+    /// should end with directories of package, like `src/main/java/com/example`.
+    /// # Arguments
+    /// * `module_name` - name of Java module, like `com.example`
+    /// * `native_library` - name of Rust library, that should be loaded
+    ///                      via `System.loadLibrary` before usage of module
+    pub fn module_info(mut self, module_name: String, native_library: String) -> JavaConfig {
+        self.module_info = Some((module_name, native_library));
+        self
+    }
+    /// Throw `exception_class` instead of `java.lang.Exception`
+    /// for `Err` of `Result<T, rust_error_type>`, message of exception
+    /// is created via `ToString` of error.
+    /// If `exception_class` is name without package, like `ParseException`,
+    /// class derived from `Exception` is generated in package of generated code;
+    /// if also `rust_error_type` is exported via `foreigner_class!`,
+    /// then generated exception carries error, available via `getError()`
+    pub fn exception_for_error(
+        mut self,
+        rust_error_type: String,
+        exception_class: String,
+    ) -> JavaConfig {
+        self.exceptions.push((rust_error_type, exception_class));
+        self
+    }
+    /// Throw unchecked exceptions for `Err` of `Result`: generated exceptions
+    /// are derived from `RuntimeException`, errors without own exception
+    /// are thrown as `java.lang.RuntimeException` instead of `java.lang.Exception`,
+    /// and methods have no `throws` clause, only `@throws` in Javadoc.
+    /// Default value is false
+    pub fn use_unchecked_exceptions(mut self, enable: bool) -> JavaConfig {
+        self.unchecked_exceptions = enable;
+        self
+    }
+    /// Make generated Java code convenient to use from Kotlin:
+    /// arguments and results of methods are annotated with @NonNull/@Nullable
+    /// (from `androidx.annotation`, if `use_null_annotation_from_package` is not used),
+    /// `Vec<T>` is returned as `java.util.List<T>` like with `use_java_lists(true)`,
+    /// and classes implement `AutoCloseable` without `finalize`,
+    /// so objects should be released via `close` (or Kotlin's `use`).
+    /// Default value is false
+    pub fn use_kotlin_friendly(mut self, enable: bool) -> JavaConfig {
+        self.kotlin_friendly = enable;
+        self
+    }
+    /// Generate code, that works on Android starting from API `level`.
+    /// Below 24 `Option<T>` is returned as nullable `T` instead of `java.util.Optional`
+    /// (unless `use_optional_package` is used, for example with backport or desugaring),
+    /// `RustIterator` does not implement `java.util.Spliterator`,
+    /// and usage of `use_java_streams` or `#[completable_future]` is reported as error,
+    /// as usage of `use_records` below 34. By default generated code is not restricted
+    pub fn min_api_level(mut self, level: u32) -> JavaConfig {
+        self.min_api_level = Some(level);
+        self
+    }
+    /// Generate static initializer in Java code of `class_name`,
+    /// that calls `System.loadLibrary(library_name)`,
+    /// so native library is loaded before the first usage of this class.
+    /// # Arguments
+    /// * `class_name` - name of class exported via `foreigner_class!`
+    /// * `library_name` - name of Rust library without prefix and extension
+    pub fn load_native_library(mut self, class_name: String, library_name: String) -> JavaConfig {
+        self.load_native_library = Some((class_name, library_name));
+        self
+    }
+    /// Use `library_name` instead of name passed to `load_native_library`,
+    /// if Rust target (`TARGET` environment variable of build script) is `target`,
+    /// like `x86_64-pc-windows-gnu`
+    pub fn native_library_for_target(mut self, target: String, library_name: String) -> JavaConfig {
+        self.native_library_targets.push((target, library_name));
+        self
+    }
+    /// Load native library via `loader_class.loadLibrary(library_name)`
+    /// instead of `System.loadLibrary`, for example to extract library
+    /// from resources of jar. `loader_class` should have method
+    /// `public static void loadLibrary(String name)`
+    pub fn native_library_loader(mut self, loader_class: String) -> JavaConfig {
+        self.native_library_loader = Some(loader_class);
+        self
+    }
+    /// Generate class `RustLog` in package of generated code,
+    /// that routes records of Rust `log` crate to `backend`.
+    /// `RustLog.init()` should be called once after loading of native library,
+    /// following calls only change maximum level of records.
+    /// Crate with generated code should depend on `log`
+    pub fn log_bridge(mut self, backend: JavaLogBackend) -> JavaConfig {
+        self.log_bridge = Some(backend);
+        self
+    }
+    /// Wrap bodies of generated native methods into `std::panic::catch_unwind`,
+    /// so panic doesn't unwind across FFI boundary. `PanicAction::Throw`
+    /// should contain full name of exception class with `(String)` constructor,
+    /// for example `java.lang.RuntimeException`.
+    /// Classes can override this via `#[catch_panics]` or `#[catch_panics = false]`
+    pub fn catch_panics(mut self, action: PanicAction) -> JavaConfig {
+        self.catch_panics = Some(action);
+        self
+    }
+    /// Append backtrace of panic to message of exception,
+    /// generated code installs panic hook to capture it
+    pub fn panic_backtrace(mut self, panic_backtrace: bool) -> JavaConfig {
+        self.panic_backtrace = panic_backtrace;
+        self
+    }
+    /// Enter span of `tracing` crate on each call of generated native method,
+    /// with class and method names, and lengths of strings and collections
+    /// passed as arguments. Crate with generated code should depend on `tracing`
+    pub fn tracing_spans(mut self, tracing_spans: bool) -> JavaConfig {
+        self.tracing_spans = tracing_spans;
+        self
+    }
+}
+
+/// Java logger for records of Rust `log` crate, see `JavaConfig::log_bridge`
+#[cfg(feature = "java")]
+#[derive(Clone, Copy, Debug, PartialEq, Hash)]
+pub enum JavaLogBackend {
+    /// `android.util.Log`, target of record is used as tag
+    AndroidLog,
+    /// `java.util.logging.Logger` with name of target, where `::` replaced by `.`
+    JavaUtilLogging,
+    /// `org.slf4j.Logger` with name of target, where `::` replaced by `.`
+    Slf4j,
+}
+
+/// How generated code reacts on panic, that would otherwise
+/// unwind across FFI boundary
+#[derive(Clone, Debug, PartialEq, Hash)]
+pub enum PanicAction {
+    /// Throw exception of given class with panic message
+    Throw(String),
+    /// Print panic message to stderr and abort process
+    Abort,
 }
 
 /// Configuration for C++ binding generation
+#[cfg(feature = "cpp")]
+#[derive(Debug)]
 pub struct CppConfig {
     output_dir: PathBuf,
     namespace_name: String,
     cpp_optional: CppOptional,
     cpp_variant: CppVariant,
+    cpp_result: CppResult,
     cpp_str_view: CppStrView,
+    cpp_string: CppString,
     generated_helper_files: RefCell<FxHashSet<PathBuf>>,
     to_generate: RefCell<Vec<TokenStream>>,
     /// Create separate *_impl.hpp files with methods implementations.
     /// Can be necessary for the project with circular dependencies between classes.
     separate_impl_headers: bool,
+    /// Header in `output_dir` that includes headers of all generated classes,
+    /// enums and interfaces
+    umbrella_header: Option<String>,
+    /// Name of CMake/pkg-config package and path to Rust library
+    cmake_package: Option<(String, PathBuf)>,
+    /// Map slices to `std::span` from C++20 standard
+    std_span: bool,
+    /// Return objects of generated classes as `std::shared_ptr`
+    shared_ptr: bool,
+    /// Generate `static std::unique_ptr<Foo> create(...)` for constructors
+    unique_ptr_factories: bool,
+    /// Generate only C headers, without C++ wrappers
+    c_abi_only: bool,
+    /// Put methods' implementations into `Foo.cpp`, `Foo.hpp` has only declarations
+    pimpl: bool,
+    /// Prefix for names of all exported C functions
+    symbol_prefix: Option<String>,
+    /// Base name of generated `.def` file and GNU ld version script
+    linker_exports: Option<String>,
+    /// `<NAME>` of `<NAME>_API` macro for declarations of C functions
+    dll_api: Option<String>,
+    /// Version of C++ standard, that generated code should be compatible with
+    cpp_standard: Option<CppStandard>,
+    /// Generate `rust_log.h` to route records of Rust `log` crate to callback
+    log_bridge: bool,
+    /// What to do with panics in methods, `None` - panics are not caught
+    catch_panics: Option<PanicAction>,
+    /// Append backtrace of panic to message of exception
+    panic_backtrace: bool,
+    /// Instrument exported functions with spans of `tracing` crate
+    tracing_spans: bool,
 }
 
 /// To which `C++` type map `std::option::Option`
-#[derive(Clone, Copy, EnumIter)]
+#[cfg(feature = "cpp")]
+#[derive(Clone, Copy, Debug, EnumIter, PartialEq, Hash)]
 pub enum CppOptional {
     /// `std::optional` from C++17 standard
     Std17,
@@ -146,6 +475,7 @@ pub enum CppOptional {
     Boost,
 }
 
+#[cfg(feature = "cpp")]
 impl From<CppOptional> for &'static str {
     fn from(x: CppOptional) -> Self {
         match x {
@@ -156,7 +486,8 @@ impl From<CppOptional> for &'static str {
 }
 
 /// To which `C++` type map `std::result::Result`
-#[derive(Clone, Copy, EnumIter)]
+#[cfg(feature = "cpp")]
+#[derive(Clone, Copy, Debug, EnumIter, PartialEq, Hash)]
 pub enum CppVariant {
     /// `std::variant` from C++17 standard
     Std17,
@@ -164,6 +495,7 @@ pub enum CppVariant {
     Boost,
 }
 
+#[cfg(feature = "cpp")]
 impl From<CppVariant> for &'static str {
     fn from(x: CppVariant) -> Self {
         match x {
@@ -173,8 +505,22 @@ impl From<CppVariant> for &'static str {
     }
 }
 
+/// How to return `std::result::Result` to `C++`
+#[cfg(feature = "cpp")]
+#[derive(Clone, Copy, Debug, PartialEq, Hash)]
+pub enum CppResult {
+    /// variant of ok and error types, see `CppVariant`
+    Variant,
+    /// `std::expected` from C++23 standard
+    Std23Expected,
+    /// `RustExpected` from generated "rust_expected.h",
+    /// `std::expected` like type that not use exceptions
+    RustExpected,
+}
+
 /// To whcih `C++` type map `&str`
-#[derive(Clone, Copy, EnumIter)]
+#[cfg(feature = "cpp")]
+#[derive(Clone, Copy, Debug, EnumIter, PartialEq, Hash)]
 pub enum CppStrView {
     /// `std::string_view` from C++17 standard
     Std17,
@@ -182,6 +528,7 @@ pub enum CppStrView {
     Boost,
 }
 
+#[cfg(feature = "cpp")]
 impl From<CppStrView> for &'static str {
     fn from(x: CppStrView) -> Self {
         match x {
@@ -191,6 +538,113 @@ impl From<CppStrView> for &'static str {
     }
 }
 
+/// Encoding and `C++` types for `String` and `&str`
+#[cfg(feature = "cpp")]
+#[derive(Clone, Copy, Debug, PartialEq, Hash)]
+pub enum CppString {
+    /// UTF-8: `RustString` and `std::string_view` (see `CppStrView`)
+    Utf8,
+    /// UTF-16: `std::u16string` and `std::u16string_view`
+    U16String,
+    /// UTF-16: `std::wstring` and `std::wstring_view`,
+    /// only for 16 bit `wchar_t`, like on Windows
+    WString,
+}
+
+/// Version of `C++` standard
+#[cfg(feature = "cpp")]
+#[derive(Clone, Copy, Debug, EnumIter, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CppStandard {
+    Cpp11,
+    Cpp14,
+    Cpp17,
+    Cpp20,
+}
+
+#[cfg(feature = "cpp")]
+impl From<CppStandard> for &'static str {
+    fn from(x: CppStandard) -> Self {
+        match x {
+            CppStandard::Cpp11 => "CppStandard::Cpp11",
+            CppStandard::Cpp14 => "CppStandard::Cpp14",
+            CppStandard::Cpp17 => "CppStandard::Cpp17",
+            CppStandard::Cpp20 => "CppStandard::Cpp20",
+        }
+    }
+}
+
+#[cfg(feature = "cpp")]
+impl std::fmt::Display for CppStandard {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match *self {
+            CppStandard::Cpp11 => "C++11",
+            CppStandard::Cpp14 => "C++14",
+            CppStandard::Cpp17 => "C++17",
+            CppStandard::Cpp20 => "C++20",
+        };
+        f.write_str(name)
+    }
+}
+
+/// State of generation, like `generated_helper_files`, is not hashed,
+/// fields are listed explicitly, so new field can not be forgotten
+#[cfg(feature = "cpp")]
+impl Hash for CppConfig {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let CppConfig {
+            output_dir,
+            namespace_name,
+            cpp_optional,
+            cpp_variant,
+            cpp_result,
+            cpp_str_view,
+            cpp_string,
+            generated_helper_files: _,
+            to_generate: _,
+            separate_impl_headers,
+            umbrella_header,
+            cmake_package,
+            std_span,
+            shared_ptr,
+            unique_ptr_factories,
+            c_abi_only,
+            pimpl,
+            symbol_prefix,
+            linker_exports,
+            dll_api,
+            cpp_standard,
+            log_bridge,
+            catch_panics,
+            panic_backtrace,
+            tracing_spans,
+        } = self;
+        output_dir.hash(state);
+        namespace_name.hash(state);
+        cpp_optional.hash(state);
+        cpp_variant.hash(state);
+        cpp_result.hash(state);
+        cpp_str_view.hash(state);
+        cpp_string.hash(state);
+        separate_impl_headers.hash(state);
+        umbrella_header.hash(state);
+        cmake_package.hash(state);
+        std_span.hash(state);
+        shared_ptr.hash(state);
+        unique_ptr_factories.hash(state);
+        c_abi_only.hash(state);
+        pimpl.hash(state);
+        symbol_prefix.hash(state);
+        linker_exports.hash(state);
+        dll_api.hash(state);
+        cpp_standard.hash(state);
+        log_bridge.hash(state);
+        catch_panics.hash(state);
+        panic_backtrace.hash(state);
+        tracing_spans.hash(state);
+    }
+}
+
+#[cfg(feature = "cpp")]
 impl CppConfig {
     /// Create `CppConfig`
     /// # Arguments
@@ -202,10 +656,27 @@ impl CppConfig {
             namespace_name,
             cpp_optional: CppOptional::Std17,
             cpp_variant: CppVariant::Std17,
+            cpp_result: CppResult::Variant,
             cpp_str_view: CppStrView::Std17,
+            cpp_string: CppString::Utf8,
             generated_helper_files: RefCell::new(FxHashSet::default()),
             to_generate: RefCell::new(vec![]),
             separate_impl_headers: false,
+            umbrella_header: None,
+            cmake_package: None,
+            std_span: false,
+            shared_ptr: false,
+            unique_ptr_factories: false,
+            c_abi_only: false,
+            pimpl: false,
+            symbol_prefix: None,
+            linker_exports: None,
+            dll_api: None,
+            cpp_standard: None,
+            log_bridge: false,
+            catch_panics: None,
+            panic_backtrace: false,
+            tracing_spans: false,
         }
     }
     pub fn cpp_optional(self, cpp_optional: CppOptional) -> CppConfig {
@@ -220,12 +691,22 @@ impl CppConfig {
             ..self
         }
     }
+    /// Return `Result<T, E>` as `std::expected<T, E>` or `RustExpected<T, E>`
+    /// instead of variant, useful for code compiled with `-fno-exceptions`
+    pub fn cpp_result(self, cpp_result: CppResult) -> CppConfig {
+        CppConfig { cpp_result, ..self }
+    }
     pub fn cpp_str_view(self, cpp_str_view: CppStrView) -> CppConfig {
         CppConfig {
             cpp_str_view,
             ..self
         }
     }
+    /// Pass and return `String` and `&str` as UTF-16 `std::u16string`
+    /// or `std::wstring`, conversation from/to UTF-8 happens in Rust code
+    pub fn cpp_string(self, cpp_string: CppString) -> CppConfig {
+        CppConfig { cpp_string, ..self }
+    }
     /// Use boost for that fit: Result -> boost::variant,
     /// Option -> boost::optional, &str -> boost::string_view
     pub fn use_boost(self) -> CppConfig {
@@ -244,6 +725,143 @@ impl CppConfig {
             ..self
         }
     }
+    /// Generate header with name `umbrella_header` in `output_dir`
+    /// that includes headers of all generated classes, enums and interfaces,
+    /// so user code can include only one header.
+    /// Each class still has own headers, so it is possible
+    /// to include only headers that are required.
+    pub fn umbrella_header(self, umbrella_header: String) -> CppConfig {
+        CppConfig {
+            umbrella_header: Some(umbrella_header),
+            ..self
+        }
+    }
+    /// Generate `<package_name>Config.cmake` and `<package_name>.pc`
+    /// in `output_dir`, so C++ code can use generated headers and Rust library
+    /// via `find_package(<package_name> CONFIG)` (imported target
+    /// `<package_name>::<package_name>`) or `pkg-config`.
+    /// # Arguments
+    /// * `rust_library` - path to static or shared library built by cargo
+    pub fn cmake_package(self, package_name: String, rust_library: PathBuf) -> CppConfig {
+        CppConfig {
+            cmake_package: Some((package_name, rust_library)),
+            ..self
+        }
+    }
+    /// Map `&[T]` and `&mut [T]` of primitive types to `std::span<const T>`
+    /// and `std::span<T>` from C++20 standard, by default C struct
+    /// with pointer and length is used
+    pub fn std_span(self, std_span: bool) -> CppConfig {
+        CppConfig { std_span, ..self }
+    }
+    /// Return objects of generated classes, like `Foo`, from methods
+    /// and callbacks as `std::shared_ptr<Foo>`, so they can be shared
+    /// on C++ side, Rust object is freed with the last reference
+    pub fn shared_ptr(self, shared_ptr: bool) -> CppConfig {
+        CppConfig { shared_ptr, ..self }
+    }
+    /// For each constructor of class generate also factory function
+    /// `static std::unique_ptr<Foo> create(...)` with the same arguments
+    pub fn unique_ptr_factories(self, unique_ptr_factories: bool) -> CppConfig {
+        CppConfig {
+            unique_ptr_factories,
+            ..self
+        }
+    }
+    /// Generate only C API: headers with opaque types and `extern "C"` functions,
+    /// without C++ wrappers, for code that wraps C API itself or written in C
+    pub fn c_abi_only(self, c_abi_only: bool) -> CppConfig {
+        CppConfig { c_abi_only, ..self }
+    }
+    /// Generate `Foo.cpp` with methods' implementations for each class,
+    /// so `Foo.hpp` contains only declarations and forward declarations
+    /// of other classes, and headers required for conversations are included
+    /// only into `Foo.cpp`. Conversations of enums with payload also go
+    /// to `*.cpp`. Generated `*.cpp` files should be compiled
+    /// together with user's code
+    pub fn pimpl(self, pimpl: bool) -> CppConfig {
+        CppConfig { pimpl, ..self }
+    }
+    /// Add `prefix` to names of all exported `extern "C"` functions,
+    /// so several libraries generated by `rust_swig` can be linked
+    /// into one binary without symbol collisions
+    pub fn symbol_prefix(self, prefix: String) -> CppConfig {
+        CppConfig {
+            symbol_prefix: Some(prefix),
+            ..self
+        }
+    }
+    /// Generate in `output_dir` `<name>.def` (module-definition file for Windows DLL)
+    /// and `<name>.map` (GNU ld version script), that export only
+    /// C functions generated by `rust_swig`
+    pub fn linker_exports(self, name: String) -> CppConfig {
+        CppConfig {
+            linker_exports: Some(name),
+            ..self
+        }
+    }
+    /// Generate `<name>_api.h` with `<NAME>_API` macro (`__declspec(dllexport)`/`__declspec(dllimport)`
+    /// or default visibility) and mark declarations of all C functions with it,
+    /// `<NAME>_EXPORTS` should be defined when shared library itself is built
+    pub fn dll_api(self, name: String) -> CppConfig {
+        CppConfig {
+            dll_api: Some(name),
+            ..self
+        }
+    }
+    /// Generate `rust_log.h` with `rust_log_init` function, that routes records
+    /// of Rust `log` crate to callback provided by user. It should be called once,
+    /// following calls only change maximum level of records.
+    /// Crate with generated code should depend on `log`
+    pub fn log_bridge(self, log_bridge: bool) -> CppConfig {
+        CppConfig { log_bridge, ..self }
+    }
+    /// Catch panics in generated `extern "C"` functions, so panic doesn't unwind
+    /// across FFI boundary. `PanicAction::Throw` should contain C++ exception type
+    /// constructible from `std::string`, for example `std::runtime_error`,
+    /// it is thrown by C++ wrappers, C functions just return zeroed value and
+    /// panic message is available via `rust_panic_message` from `rust_panic.h`.
+    /// Classes can override this via `#[catch_panics]` or `#[catch_panics = false]`
+    pub fn catch_panics(self, action: PanicAction) -> CppConfig {
+        CppConfig {
+            catch_panics: Some(action),
+            ..self
+        }
+    }
+    /// Append backtrace of panic to its message,
+    /// generated code installs panic hook to capture it
+    pub fn panic_backtrace(self, panic_backtrace: bool) -> CppConfig {
+        CppConfig {
+            panic_backtrace,
+            ..self
+        }
+    }
+    /// Enter span of `tracing` crate on each call of generated `extern "C"` function,
+    /// with class and method names, and lengths of strings and collections
+    /// passed as arguments. Crate with generated code should depend on `tracing`
+    pub fn tracing_spans(self, tracing_spans: bool) -> CppConfig {
+        CppConfig {
+            tracing_spans,
+            ..self
+        }
+    }
+    /// Generate code for given version of C++ standard: before C++17 boost is used
+    /// instead of `std::optional`, `std::variant` and `std::string_view`
+    /// and namespaces are not nested, since C++17 methods are `[[nodiscard]]`,
+    /// since C++20 slices are `std::span`. Features that are not available
+    /// in this version are reported as errors
+    pub fn cpp_standard(self, cpp_standard: CppStandard) -> CppConfig {
+        let cfg = CppConfig {
+            cpp_standard: Some(cpp_standard),
+            std_span: cpp_standard >= CppStandard::Cpp20,
+            ..self
+        };
+        if cpp_standard < CppStandard::Cpp17 {
+            cfg.use_boost()
+        } else {
+            cfg
+        }
+    }
 }
 
 /// `Generator` is a main point of `rust_swig`.
@@ -257,8 +875,28 @@ pub struct Generator {
     foreign_lang_helpers: Vec<SourceCode>,
     pointer_target_width: usize,
     src_reg: SourceRegistry,
+    conv_graph_dump_path: Option<PathBuf>,
+    expanded_code_dump_path: Option<PathBuf>,
+    api_manifest_path: Option<PathBuf>,
+    target_cfg: TargetCfg,
+    typemap_cache_dir: Option<PathBuf>,
+    expansion_cache_dir: Option<PathBuf>,
+    warn_unused_rules: bool,
+    validate_conv_graph: bool,
+    removed_conversions: Vec<(String, String)>,
+    formatters: Vec<Formatter>,
+    generated_file_hooks: Rc<RefCell<Vec<GeneratedFileHook>>>,
+    errors_as_compile_error: bool,
+    additional_targets: Vec<(usize, Option<String>)>,
+    api_version: Option<String>,
+    print_profiling_report: bool,
+    source_location_comments: bool,
+    check_rust_signatures: bool,
+    check_send: bool,
 }
 
+type GeneratedFileHook = Box<dyn FnMut(&Path, &mut String)>;
+
 struct SourceCode {
     id_of_code: String,
     code: String,
@@ -274,9 +912,11 @@ impl Generator {
     pub fn new(config: LanguageConfig) -> Generator {
         let pointer_target_width = target_pointer_width_from_env();
         let mut conv_map_source = Vec::new();
+        #[cfg_attr(not(feature = "cpp"), allow(unused_mut))]
         let mut foreign_lang_helpers = Vec::new();
         let mut src_reg = SourceRegistry::default();
         match config {
+            #[cfg(feature = "java")]
             LanguageConfig::JavaConfig(ref java_cfg) => {
                 conv_map_source.push(
                     src_reg.register(SourceCode {
@@ -296,10 +936,14 @@ impl Generator {
                     }),
                 );
             }
-            LanguageConfig::CppConfig(..) => {
+            #[cfg(feature = "cpp")]
+            LanguageConfig::CppConfig(ref cpp_cfg) => {
                 conv_map_source.push(src_reg.register(SourceCode {
                     id_of_code: "cpp-include.rs".into(),
-                    code: include_str!("cpp/cpp-include.rs").into(),
+                    code: cpp::add_dll_api_to_builtin_decls(
+                        cpp_cfg,
+                        &cpp::prefix_builtin_c_symbols(cpp_cfg, include_str!("cpp/cpp-include.rs")),
+                    ),
                 }));
                 foreign_lang_helpers.push(SourceCode {
                     id_of_code: "rust_vec.h".into(),
@@ -309,6 +953,18 @@ impl Generator {
                     id_of_code: "rust_result.h".into(),
                     code: include_str!("cpp/rust_result.h").into(),
                 });
+                foreign_lang_helpers.push(SourceCode {
+                    id_of_code: "rust_expected.h".into(),
+                    code: include_str!("cpp/rust_expected.h").into(),
+                });
+                foreign_lang_helpers.push(SourceCode {
+                    id_of_code: "rust_utf16.h".into(),
+                    code: include_str!("cpp/rust_utf16.h").into(),
+                });
+                foreign_lang_helpers.push(SourceCode {
+                    id_of_code: "rust_awaitable.h".into(),
+                    code: include_str!("cpp/rust_awaitable.h").into(),
+                });
                 foreign_lang_helpers.push(SourceCode {
                     id_of_code: "rust_option.h".into(),
                     code: include_str!("cpp/rust_option.h").into(),
@@ -317,19 +973,64 @@ impl Generator {
                     id_of_code: "rust_tuple.h".into(),
                     code: include_str!("cpp/rust_tuple.h").into(),
                 });
+                for helper in &mut foreign_lang_helpers {
+                    helper.code = cpp::add_dll_api_to_builtin_decls(
+                        cpp_cfg,
+                        &cpp::prefix_builtin_c_symbols(cpp_cfg, &helper.code),
+                    );
+                }
             }
         }
+        let mut conv_map = TypeMap::default();
+        for src_id in &conv_map_source {
+            conv_map.set_rule_precedence(*src_id, RulePrecedence::BuiltIn);
+        }
         Generator {
             init_done: false,
             config,
-            conv_map: TypeMap::default(),
+            conv_map,
             conv_map_source,
             foreign_lang_helpers,
             pointer_target_width: pointer_target_width.unwrap_or(0),
             src_reg,
+            conv_graph_dump_path: None,
+            expanded_code_dump_path: None,
+            api_manifest_path: None,
+            target_cfg: TargetCfg::from_env(),
+            typemap_cache_dir: None,
+            expansion_cache_dir: None,
+            warn_unused_rules: false,
+            validate_conv_graph: false,
+            removed_conversions: Vec::new(),
+            formatters: Vec::new(),
+            generated_file_hooks: Rc::new(RefCell::new(Vec::new())),
+            errors_as_compile_error: false,
+            additional_targets: Vec::new(),
+            api_version: None,
+            print_profiling_report: false,
+            source_location_comments: false,
+            check_rust_signatures: false,
+            check_send: false,
         }
     }
 
+    /// Create `Generator` from configuration file in TOML format (`swig.toml`),
+    /// with `[java]` or `[cpp]` section for language specific options
+    /// and optional `[generator]` section for options of `Generator`,
+    /// see `config_file` module for details.
+    /// Relative paths are resolved relative to directory of configuration file.
+    ///
+    /// # Panics
+    /// Panics on I/O error or invalid configuration
+    pub fn from_config_file<P: AsRef<Path>>(path: P) -> Generator {
+        let path = path.as_ref();
+        let config = std::fs::read_to_string(path)
+            .unwrap_or_else(|err| panic!("Error during read for file {}: {}", path.display(), err));
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        config_file::generator_from_toml(base_dir, &config)
+            .unwrap_or_else(|err| panic!("Invalid configuration file {}: {}", path.display(), err))
+    }
+
     /// By default we get pointer_target_width via cargo (more exactly CARGO_CFG_TARGET_POINTER_WIDTH),
     /// but you can change default value via this method
     pub fn with_pointer_target_width(mut self, pointer_target_width: usize) -> Generator {
@@ -337,12 +1038,274 @@ impl Generator {
         self
     }
 
-    /// Add new foreign langauge type <-> Rust mapping
+    /// Remove built-in conversation rule `from` -> `to`,
+    /// for example `remove_conversion("jstring", "String")`.
+    /// Both plain and generic rules (like `Vec<T>` -> `jobjectArray`) can be removed,
+    /// so it is possible to provide own rule via `merge_type_map`
+    /// instead of the default one.
+    pub fn remove_conversion(mut self, from: &str, to: &str) -> Generator {
+        self.removed_conversions.push((from.into(), to.into()));
+        self
+    }
+
+    /// After expansion print warning about each conversation rule
+    /// from types maps added via `merge_type_map` or from `foreign_typemap!`
+    /// in processed source code that was not used, false by default
+    pub fn warn_unused_rules(mut self, warn: bool) -> Generator {
+        self.warn_unused_rules = warn;
+        self
+    }
+
+    /// After merge of all types maps print warning about cycles of rules
+    /// with zero cost and about pairs of types that can be converted
+    /// via several paths with the same cost, because of the choice of path
+    /// in this case is arbitrary, false by default
+    pub fn validate_conversation_graph(mut self, validate: bool) -> Generator {
+        self.validate_conv_graph = validate;
+        self
+    }
+
+    /// Do not print warnings with `code`, like `SWIG1004`,
+    /// see `explain_diagnostic` for description of codes
+    ///
+    /// # Panics
+    /// Panics if `code` is not code of warning
+    pub fn allow_warning(mut self, code: &str) -> Generator {
+        match DiagnosticCode::from_id(code) {
+            Some(code) if code.is_warning() => self.conv_map.warnings_mut().allow(code),
+            _ => panic!("allow_warning: {} is not code of warning", code),
+        }
+        self
+    }
+
+    /// Cache parsed built-in types maps in `cache_dir`,
+    /// so they are not parsed again during next build.
+    /// Without it built-in types maps are parsed once per process.
+    /// For example `Path::new(&env::var("OUT_DIR").unwrap()).join("rust_swig_cache")`
+    pub fn cache_builtin_type_maps<P: Into<PathBuf>>(mut self, cache_dir: P) -> Generator {
+        self.typemap_cache_dir = Some(cache_dir.into());
+        self
+    }
+
+    /// Remember in `cache_dir` hash of all inputs of `expand`/`expand_many`
+    /// (interface definitions, types maps and configuration) and of generated files,
+    /// and skip expansion if nothing is changed since previous build.
+    /// Warnings are printed only when expansion is actually done,
+    /// and changes of closures passed to `with_generated_file_hook` are not detected.
+    /// For example `Path::new(&env::var("OUT_DIR").unwrap()).join("rust_swig_cache")`
+    pub fn cache_expansion<P: Into<PathBuf>>(mut self, cache_dir: P) -> Generator {
+        self.expansion_cache_dir = Some(cache_dir.into());
+        self
+    }
+
+    /// By default we get target OS via cargo (CARGO_CFG_TARGET_OS),
+    /// it is used to evaluate `#[cfg(target_os = "...")]` in types maps
+    pub fn with_target_os(mut self, target_os: &str) -> Generator {
+        self.target_cfg.os = Some(target_os.into());
+        self
+    }
+
+    /// By default we get target architecture via cargo (CARGO_CFG_TARGET_ARCH),
+    /// it is used to evaluate `#[cfg(target_arch = "...")]` in types maps
+    pub fn with_target_arch(mut self, target_arch: &str) -> Generator {
+        self.target_cfg.arch = Some(target_arch.into());
+        self
+    }
+
+    /// By default we get target family via cargo (CARGO_CFG_TARGET_FAMILY),
+    /// it is used to evaluate `#[cfg(unix)]`, `#[cfg(windows)]`
+    /// and `#[cfg(target_family = "...")]` in types maps
+    pub fn with_target_family(mut self, target_family: &str) -> Generator {
+        self.target_cfg.family = Some(target_family.into());
+        self
+    }
+
+    /// Enable feature for `#[cfg(feature = "...")]` in types maps,
+    /// features of crate that runs build script enabled by default
+    pub fn with_feature(mut self, feature: &str) -> Generator {
+        self.target_cfg.add_feature(feature);
+        self
+    }
+
+    /// Add new foreign langauge type <-> Rust mapping.
+    /// Rules from merged type maps take precedence over built-in rules,
+    /// to override rule with higher precedence use `force_override`
+    /// option of `r_type` rule inside `foreign_typemap!`.
+    /// To convert one type to another the cheapest chain of rules is used,
+    /// `r_type` rule may declare its cost via `cost = N` option, default cost is 1.
+    /// `r_type` rule with `bidirectional` option defines conversation in both directions,
+    /// like `($p:r_type, bidirectional) i32 => jint { $out = $p as jint } { $out = $p as i32 };`
+    /// Conversation code may declare temporary variable via `let $tmp = ...;`
+    /// (`{tmp_var}` in `swig_code`), its name is unique for each conversation step.
     pub fn merge_type_map(mut self, id_of_code: &str, code: &str) -> Generator {
-        self.conv_map_source.push(self.src_reg.register(SourceCode {
+        let src_id = self.src_reg.register(SourceCode {
             id_of_code: id_of_code.into(),
             code: code.into(),
-        }));
+        });
+        self.conv_map
+            .set_rule_precedence(src_id, RulePrecedence::Merged);
+        self.conv_map_source.push(src_id);
+        self
+    }
+
+    /// Add new foreign langauge type <-> Rust mapping from file,
+    /// the file should have the same syntax as code for `merge_type_map`.
+    /// This way several crates can share one set of typemaps.
+    ///
+    /// # Panics
+    /// Panics on I/O error
+    pub fn merge_type_map_from_file<P: AsRef<Path>>(self, path: P) -> Generator {
+        let path = path.as_ref();
+        let code = std::fs::read_to_string(path)
+            .unwrap_or_else(|err| panic!("Error during read for file {}: {}", path.display(), err));
+        self.merge_type_map(&path.display().to_string(), &code)
+    }
+
+    /// Dump types conversation graph in Graphviz DOT format to `path`
+    /// after expansion, successful or not. Edges labeled with conversation code and
+    /// the name of source where the rule was defined, so it is possible to see
+    /// why conversation between two types was or wasn't found.
+    pub fn dump_conversation_graph<P: Into<PathBuf>>(mut self, path: P) -> Generator {
+        self.conv_graph_dump_path = Some(path.into());
+        self
+    }
+
+    /// Write Rust code generated for foreign classes, enums and interfaces
+    /// to `path` (for example inside `OUT_DIR`), each item is preceded
+    /// by marker comment like `// ---- class Foo ----`, so it is possible
+    /// to inspect conversations and `unsafe` glue code of particular class.
+    /// Code is written as is, register `rustfmt` via `format_generated_files`
+    /// to make it more readable.
+    pub fn dump_expanded_rust_code<P: Into<PathBuf>>(mut self, path: P) -> Generator {
+        self.expanded_code_dump_path = Some(path.into());
+        self
+    }
+
+    /// Write description of generated API in JSON format to `path`
+    /// after expansion: classes, methods, enums and interfaces with doc comments,
+    /// Rust and foreign types of methods' arguments and return values.
+    pub fn write_api_manifest<P: Into<PathBuf>>(mut self, path: P) -> Generator {
+        self.api_manifest_path = Some(path.into());
+        self
+    }
+
+    /// Run `command` with `args` on each generated file with one of `extensions`
+    /// (without dot, like "rs", "hpp" or "java") before it is written.
+    /// The command should read code from stdin and print formatted code to stdout,
+    /// for example `rustfmt --edition 2018`, `clang-format -assume-filename=a.hpp`
+    /// or `google-java-format -`. Formatted content is compared with
+    /// file on disk, so unchanged files are not rewritten.
+    /// If several formatters match file, they are applied in order of registration.
+    pub fn format_generated_files(
+        mut self,
+        extensions: &[&str],
+        command: &str,
+        args: &[&str],
+    ) -> Generator {
+        self.formatters.push(Formatter {
+            extensions: extensions.iter().map(|x| x.to_string()).collect(),
+            command: command.into(),
+            args: args.iter().map(|x| x.to_string()).collect(),
+        });
+        self
+    }
+
+    /// Register callback that is called with path and content of each
+    /// generated file (foreign code and Rust code) before it is written,
+    /// after formatters from `format_generated_files`.
+    /// Callback can modify content, for example add license header,
+    /// or create additional files (via `std::fs`, not via `FileWriteCache`).
+    /// Callbacks are called in order of registration.
+    pub fn with_generated_file_hook<F>(self, hook: F) -> Generator
+    where
+        F: FnMut(&Path, &mut String) + 'static,
+    {
+        self.generated_file_hooks.borrow_mut().push(Box::new(hook));
+        self
+    }
+
+    /// If `true`, errors in processed source code (like invalid `foreigner_class!`)
+    /// are written to `dst` as `compile_error!` with location of error,
+    /// instead of panic inside `build.rs`, so they are reported by rustc
+    /// as usual compilation errors. Default value is `false`.
+    pub fn report_errors_via_compile_error(mut self, enable: bool) -> Generator {
+        self.errors_as_compile_error = enable;
+        self
+    }
+
+    /// Embed version stamp: `version` plus hash of processed source code,
+    /// into generated code as class `ApiVersion`. Foreign code gets constant
+    /// `ApiVersion.GENERATED` and method `ApiVersion.check()`, that compares it
+    /// with stamp from native library (throws exception in Java, returns `false` in C++),
+    /// so mismatch of foreign code and native library can be detected after loading of library.
+    pub fn with_api_version(mut self, version: &str) -> Generator {
+        self.api_version = Some(version.into());
+        self
+    }
+
+    /// Generate Rust code also for target with `pointer_target_width`
+    /// and `target_os` (`None` means any OS). Code for each target is guarded
+    /// by `#[cfg(target_pointer_width = "..", target_os = "..")]`,
+    /// so one generated file can be used for all targets.
+    /// Foreign code is shared, so it should be the same for all targets,
+    /// otherwise `expand` fails. For example foreign type for `usize`
+    /// depends on pointer width, and C++ code checks size of pointer,
+    /// so for C++ targets should have the same pointer width.
+    pub fn with_additional_target(
+        mut self,
+        pointer_target_width: usize,
+        target_os: Option<&str>,
+    ) -> Generator {
+        self.additional_targets
+            .push((pointer_target_width, target_os.map(str::to_string)));
+        self
+    }
+
+    /// After expansion print how much time was spent on parsing of each types map,
+    /// expansion of each item, search of conversations for each method
+    /// and writing of each generated file, to find what makes `build.rs` slow.
+    /// Output of build script can be seen with `cargo build -vv`.
+    /// False by default.
+    pub fn print_profiling_report(mut self, enable: bool) -> Generator {
+        self.print_profiling_report = enable;
+        self
+    }
+
+    /// Add to generated foreign code location in source code of each class,
+    /// enum, interface and method, as doc comment like
+    /// `Source: crate: src/glue.rs.in:12`, and similar comments to generated
+    /// Rust code for each class, enum and interface, so it is easy to find
+    /// definition of generated code. False by default.
+    pub fn source_location_comments(mut self, enable: bool) -> Generator {
+        self.source_location_comments = enable;
+        self
+    }
+
+    /// Generate for each class function, that assigns Rust function of each method
+    /// to function pointer with signature declared in `foreigner_class!`,
+    /// so rustc reports if path of Rust function is invalid, or if it has another
+    /// receiver, number or types of arguments, or result.
+    /// Declarations, that rely on implicit conversations of arguments,
+    /// like deref coercion, do not pass this check. False by default.
+    pub fn check_rust_signatures(mut self, enable: bool) -> Generator {
+        self.check_rust_signatures = enable;
+        self
+    }
+
+    /// Generate for each class assertion, that its `self_type` and return type
+    /// of constructor implement `Send`, because Java and C++ code may use
+    /// objects from any thread. Classes marked with `#[single_threaded]`
+    /// are not checked. False by default.
+    pub fn check_send(mut self, enable: bool) -> Generator {
+        self.check_send = enable;
+        self
+    }
+
+    /// Report error instead of generation of conversation, that may silently
+    /// truncate value or change its sign, like `u64` to Java's `long`,
+    /// so explicit rule for such types should be provided. False by default.
+    pub fn strict_conversions(mut self, enable: bool) -> Generator {
+        self.conv_map.set_strict_conversions(enable);
         self
     }
 
@@ -350,37 +1313,185 @@ impl Generator {
     ///
     /// # Panics
     /// Panics on error
-    pub fn expand<S, D>(mut self, crate_name: &str, src: S, dst: D)
+    pub fn expand<S, D>(self, crate_name: &str, src: S, dst: D)
     where
         S: AsRef<Path>,
         D: AsRef<Path>,
     {
-        let src_cnt = std::fs::read_to_string(src.as_ref()).unwrap_or_else(|err| {
-            panic!(
-                "Error during read for file {}: {}",
-                src.as_ref().display(),
-                err
-            )
-        });
+        self.expand_many(crate_name, &[src], dst)
+    }
 
-        let src_id = self.src_reg.register(SourceCode {
-            id_of_code: format!("{}: {}", crate_name, src.as_ref().display()),
-            code: src_cnt,
-        });
+    /// process several `srcs` and save result of macro expansion to `dst`.
+    /// Foreign classes, enums and interfaces from all `srcs` are processed together,
+    /// so class from one file can be used in methods of class from another file.
+    ///
+    /// # Panics
+    /// Panics on error
+    pub fn expand_many<S, D>(mut self, crate_name: &str, srcs: &[S], dst: D)
+    where
+        S: AsRef<Path>,
+        D: AsRef<Path>,
+    {
+        let src_ids = self.register_sources(crate_name, srcs);
+        if self.print_profiling_report {
+            let (_, profile) =
+                profiler::collect_profile(|| self.expand_and_write(&src_ids, dst.as_ref()));
+            print!("{}", profile);
+        } else {
+            self.expand_and_write(&src_ids, dst.as_ref());
+        }
+    }
 
-        if let Err(err) = self.expand_str(src_id, dst) {
+    /// Process `srcs` like `expand_many`: parse them, validate items and find
+    /// conversations for all methods for main and additional targets,
+    /// but do not write any files, for example to verify on CI
+    /// that interface description can be expanded.
+    /// Hooks registered via `with_generated_file_hook` are not called.
+    ///
+    /// # Panics
+    /// Panics on error
+    pub fn check<S: AsRef<Path>>(mut self, crate_name: &str, srcs: &[S]) {
+        let src_ids = self.register_sources(crate_name, srcs);
+        // they can create files bypassing `FileWriteCache`,
+        // and formatting is useless if nothing is written
+        self.generated_file_hooks.borrow_mut().clear();
+        self.formatters.clear();
+        if let Err(err) = file_cache::dry_run(|| self.expand_for_all_targets(&src_ids)) {
             panic_on_parse_error(&self.src_reg, &err);
         }
     }
 
-    /// process `src` and save result of macro expansion to `dst`
+    fn register_sources<S: AsRef<Path>>(&mut self, crate_name: &str, srcs: &[S]) -> Vec<SourceId> {
+        let mut src_ids = Vec::with_capacity(srcs.len());
+        for src in srcs {
+            let src_cnt = std::fs::read_to_string(src.as_ref()).unwrap_or_else(|err| {
+                panic!(
+                    "Error during read for file {}: {}",
+                    src.as_ref().display(),
+                    err
+                )
+            });
+
+            src_ids.push(self.src_reg.register(SourceCode {
+                id_of_code: format!("{}: {}", crate_name, src.as_ref().display()),
+                code: src_cnt,
+            }));
+        }
+
+        src_ids
+    }
+
+    fn expand_and_write(&mut self, src_ids: &[SourceId], dst: &Path) {
+        let cache = self
+            .expansion_cache_dir
+            .as_ref()
+            .map(|dir| ExpansionCache::new(dir, self, src_ids, dst));
+        if let Some(ref cache) = cache {
+            if cache.is_up_to_date() {
+                debug!("expand_and_write: nothing changed since previous build");
+                return;
+            }
+        }
+        let (expanded, written_files) = file_cache::collect_written_files(|| {
+            let (rust_code, expanded) = match self.expand_for_all_targets(src_ids) {
+                Ok(code) => (code, true),
+                Err(err) => {
+                    if !self.errors_as_compile_error {
+                        panic_on_parse_error(&self.src_reg, &err);
+                    }
+                    let code = parse_error_to_compile_error(&self.src_reg, &err)
+                        .to_string()
+                        .into_bytes();
+                    (code, false)
+                }
+            };
+            let _content_filter = self.content_filter().map(file_cache::set_content_filter);
+            let mut file = file_cache::FileWriteCache::new(dst);
+            file.write_all(&rust_code).expect("mem I/O failed");
+            file.update_file_if_necessary().unwrap_or_else(|err| {
+                panic!("Error during write to file {}: {}", dst.display(), err);
+            });
+            expanded
+        });
+        if let (Some(cache), true) = (cache, expanded) {
+            cache.save(&written_files);
+        }
+    }
+
+    /// Generate Rust code for main target and for each of `additional_targets`
+    fn expand_for_all_targets(&mut self, src_ids: &[SourceId]) -> Result<Vec<u8>> {
+        let mut rust_code = Vec::new();
+        self.expand_str(src_ids, &mut rust_code)?;
+        if self.additional_targets.is_empty() {
+            return Ok(rust_code);
+        }
+        let main_target = (self.pointer_target_width, self.target_cfg.os.clone());
+        let mut targets = vec![main_target.clone()];
+        for target in &self.additional_targets {
+            if let Some(prev) = targets.iter().find(|x| {
+                x.0 == target.0 && (x.1.is_none() || target.1.is_none() || x.1 == target.1)
+            }) {
+                return Err(DiagnosticError::new_without_src_info(format!(
+                    "targets {} and {} overlap",
+                    target_cfg_cond(prev),
+                    target_cfg_cond(target)
+                )));
+            }
+            targets.push(target.clone());
+        }
+        let mut ret = cfg_each_item(&main_target, &rust_code)?;
+
+        // this was generated during processing of main target
+        self.conv_graph_dump_path = None;
+        self.expanded_code_dump_path = None;
+        self.api_manifest_path = None;
+        self.warn_unused_rules = false;
+        self.validate_conv_graph = false;
+
+        for target in targets.into_iter().skip(1) {
+            self.pointer_target_width = target.0;
+            self.target_cfg.os = target.1.clone();
+            self.target_cfg.arch = None;
+            self.target_cfg.family = None;
+            let mut conv_map = TypeMap::default();
+            for src_id in &self.conv_map_source {
+                conv_map.set_rule_precedence(*src_id, self.conv_map.rule_precedence(*src_id));
+            }
+            *conv_map.warnings_mut() = self.conv_map.warnings().clone();
+            conv_map.set_strict_conversions(self.conv_map.strict_conversions());
+            self.conv_map = conv_map;
+            self.init_done = false;
+
+            let mut rust_code = Vec::new();
+            // init outside of `collect_changed_files`, because of cache of types map
+            for item in self.init_types_map(self.pointer_target_width)? {
+                write!(&mut rust_code, "{}", DisplayToTokens(&item)).expect("mem I/O failed");
+            }
+            let (res, changed_files) =
+                file_cache::collect_changed_files(|| self.expand_str(src_ids, &mut rust_code));
+            res?;
+            if !changed_files.is_empty() {
+                return Err(DiagnosticError::new_without_src_info(format!(
+                    "foreign code for target {} differs from code for target {}, changed files: {}",
+                    target_cfg_cond(&target),
+                    target_cfg_cond(&main_target),
+                    changed_files
+                        .iter()
+                        .map(|x| x.display().to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )));
+            }
+            ret.append(&mut cfg_each_item(&target, &rust_code)?);
+        }
+        Ok(ret)
+    }
+
+    /// process `src_ids` and save result of macro expansion to `file`
     ///
     /// # Panics
     /// Panics on I/O errors
-    fn expand_str<D>(&mut self, src_id: SourceId, dst: D) -> Result<()>
-    where
-        D: AsRef<Path>,
-    {
+    fn expand_str(&mut self, src_ids: &[SourceId], file: &mut Vec<u8>) -> Result<()> {
         if self.pointer_target_width == 0 {
             panic!(
                 r#"pointer target width unknown,
@@ -390,79 +1501,307 @@ impl Generator {
             );
         }
         let items = self.init_types_map(self.pointer_target_width)?;
+        // set after init of types map, to not touch cache of types map
+        let _content_filter = self.content_filter().map(file_cache::set_content_filter);
+        if self.validate_conv_graph {
+            self.report_conv_graph_problems();
+        }
 
-        let syn_file = syn::parse_file(self.src_reg.src(src_id))
-            .map_err(|err| DiagnosticError::from_syn_err(src_id, err))?;
-
-        let mut file = file_cache::FileWriteCache::new(dst.as_ref());
-
+        let mut utils_code = String::new();
         for item in items {
-            write!(&mut file, "{}", DisplayToTokens(&item)).expect("mem I/O failed");
+            utils_code.push_str(&DisplayToTokens(&item).to_string());
         }
+        write!(file, "{}", utils_code).expect("mem I/O failed");
 
-        // n / 2 - just guess
-        let mut items_to_expand = Vec::with_capacity(syn_file.items.len() / 2);
+        let mut items_to_expand = Vec::new();
 
-        for item in syn_file.items {
-            if let syn::Item::Macro(mut item_macro) = item {
-                let is_our_macro = [FOREIGNER_CLASS, FOREIGN_ENUM, FOREIGN_INTERFACE]
-                    .iter()
-                    .any(|x| item_macro.mac.path.is_ident(x));
-                if !is_our_macro {
-                    writeln!(&mut file, "{}", DisplayToTokens(&item_macro))
-                        .expect("mem I/O failed");
-                    continue;
+        for &src_id in src_ids {
+            let syn_file = syn::parse_file(self.src_reg.src(src_id))
+                .map_err(|err| DiagnosticError::from_syn_err(src_id, err))?;
+
+            for item in syn_file.items {
+                if let syn::Item::Macro(mut item_macro) = item {
+                    let is_our_macro = [FOREIGNER_CLASS, FOREIGN_ENUM, FOREIGN_INTERFACE]
+                        .iter()
+                        .any(|x| item_macro.mac.path.is_ident(x));
+                    if !is_our_macro {
+                        writeln!(file, "{}", DisplayToTokens(&item_macro)).expect("mem I/O failed");
+                        continue;
+                    }
+                    debug!("Found {}", DisplayToTokens(&item_macro.mac.path));
+                    if item_macro.mac.tts.is_empty() {
+                        return Err(DiagnosticError::new(
+                            src_id,
+                            item_macro.span(),
+                            format!(
+                                "missing tokens in call of macro '{}'",
+                                DisplayToTokens(&item_macro.mac.path)
+                            ),
+                        ));
+                    }
+                    let mut tts = TokenStream::new();
+                    mem::swap(&mut tts, &mut item_macro.mac.tts);
+                    if item_macro.mac.path.is_ident(FOREIGNER_CLASS) {
+                        let fclass = code_parse::parse_foreigner_class(src_id, &self.config, tts)?;
+                        debug!("expand_foreigner_class: self_desc {:?}", fclass.self_desc);
+                        items_to_expand.push(ItemToExpand::Class(Rc::new(fclass)));
+                    } else if item_macro.mac.path.is_ident(FOREIGN_ENUM) {
+                        let fenum = code_parse::parse_foreign_enum(src_id, tts)?;
+                        items_to_expand.push(ItemToExpand::Enum(fenum));
+                    } else if item_macro.mac.path.is_ident(FOREIGN_INTERFACE) {
+                        let finterface = code_parse::parse_foreign_interface(src_id, tts)?;
+                        items_to_expand.push(ItemToExpand::Interface(finterface));
+                    } else {
+                        unreachable!();
+                    }
+                } else {
+                    writeln!(file, "{}", DisplayToTokens(&item)).expect("mem I/O failed");
                 }
-                debug!("Found {}", DisplayToTokens(&item_macro.mac.path));
-                if item_macro.mac.tts.is_empty() {
-                    return Err(DiagnosticError::new(
-                        src_id,
-                        item_macro.span(),
-                        format!(
-                            "missing tokens in call of macro '{}'",
-                            DisplayToTokens(&item_macro.mac.path)
-                        ),
-                    ));
+            }
+        }
+
+        let mut checks = Vec::new();
+        for item in &items_to_expand {
+            match item {
+                ItemToExpand::Class(fclass) => {
+                    if self.check_rust_signatures {
+                        checks.push(signature_check::generate_signature_checks(fclass));
+                    }
+                    if self.check_send {
+                        checks.extend(send_check::generate_send_checks(fclass));
+                    }
                 }
-                let mut tts = TokenStream::new();
-                mem::swap(&mut tts, &mut item_macro.mac.tts);
-                if item_macro.mac.path.is_ident(FOREIGNER_CLASS) {
-                    let fclass = code_parse::parse_foreigner_class(src_id, &self.config, tts)?;
-                    debug!("expand_foreigner_class: self_desc {:?}", fclass.self_desc);
-                    self.conv_map.register_foreigner_class(&fclass);
-                    items_to_expand.push(ItemToExpand::Class(fclass));
-                } else if item_macro.mac.path.is_ident(FOREIGN_ENUM) {
-                    let fenum = code_parse::parse_foreign_enum(src_id, tts)?;
-                    items_to_expand.push(ItemToExpand::Enum(fenum));
-                } else if item_macro.mac.path.is_ident(FOREIGN_INTERFACE) {
-                    let finterface = code_parse::parse_foreign_interface(src_id, tts)?;
-                    items_to_expand.push(ItemToExpand::Interface(finterface));
-                } else {
-                    unreachable!();
+                ItemToExpand::Enum(fenum) => {
+                    checks.push(enum_check::generate_exhaustive_match(fenum));
                 }
-            } else {
-                writeln!(&mut file, "{}", DisplayToTokens(&item)).expect("mem I/O failed");
+                ItemToExpand::Interface(_) => {}
             }
         }
 
+        let mut src_locations = FxHashMap::default();
+        if self.source_location_comments {
+            for item in &mut items_to_expand {
+                let location = self.add_source_location_comments(item);
+                src_locations.insert(item.descr(), location);
+            }
+        }
+
+        if let Some(version) = self.api_version.as_ref() {
+            let stamp = api_version::api_version_stamp(
+                version,
+                src_ids.iter().map(|id| self.src_reg.src(*id)),
+            );
+            writeln!(file, "{}", api_version::api_version_rust_code(&stamp))
+                .expect("mem I/O failed");
+            let class_src_id = self.src_reg.register(SourceCode {
+                id_of_code: api_version::API_VERSION_CLASS.into(),
+                code: api_version::api_version_class_code(&self.config, &stamp),
+            });
+            let tts: TokenStream = self
+                .src_reg
+                .src(class_src_id)
+                .parse()
+                .expect("Internal error: can not tokenize ApiVersion class");
+            let fclass = code_parse::parse_foreigner_class(class_src_id, &self.config, tts)?;
+            items_to_expand.push(ItemToExpand::Class(Rc::new(fclass)));
+        }
+
+        let mut manifest = ApiManifest::default();
         let code = Generator::language_generator(&self.config).expand_items(
             &mut self.conv_map,
             self.pointer_target_width,
             &self.foreign_lang_helpers,
             items_to_expand,
-        )?;
-        for elem in code {
-            writeln!(&mut file, "{}", elem.to_string()).expect("mem I/O failed");
+            &mut manifest,
+        );
+        // graph is the most useful when conversation path is not found
+        if let (Err(_), Some(dump_path)) = (&code, self.conv_graph_dump_path.as_ref()) {
+            self.dump_conversation_graph_to(dump_path);
+        }
+        let code = code?;
+        for item in &code {
+            if let Some(location) = src_locations.get(&item.descr) {
+                writeln!(file, "// {}, source: {}", item.descr, location).expect("mem I/O failed");
+            }
+            for elem in &item.code {
+                writeln!(file, "{}", elem.to_string()).expect("mem I/O failed");
+            }
+        }
+        for check in &checks {
+            writeln!(file, "{}", check).expect("mem I/O failed");
+        }
+        if let Some(dump_path) = self.expanded_code_dump_path.as_ref() {
+            let mut dump = file_cache::FileWriteCache::new(dump_path);
+            if !utils_code.is_empty() {
+                writeln!(&mut dump, "// ---- type map helpers ----\n{}", utils_code)
+                    .expect("mem I/O failed");
+            }
+            for item in &code {
+                writeln!(&mut dump, "// ---- {} ----", item.descr).expect("mem I/O failed");
+                for elem in &item.code {
+                    writeln!(&mut dump, "{}\n", elem).expect("mem I/O failed");
+                }
+            }
+            dump.update_file_if_necessary().unwrap_or_else(|err| {
+                panic!(
+                    "Error during write to file {}: {}",
+                    dump_path.display(),
+                    err
+                );
+            });
+        }
+
+        if let Some(manifest_path) = self.api_manifest_path.as_ref() {
+            let mut file = file_cache::FileWriteCache::new(manifest_path);
+            write!(&mut file, "{}", manifest.to_json()).expect("mem I/O failed");
+            file.update_file_if_necessary().unwrap_or_else(|err| {
+                panic!(
+                    "Error during write to file {}: {}",
+                    manifest_path.display(),
+                    err
+                );
+            });
+        }
+        if let Some(dump_path) = self.conv_graph_dump_path.as_ref() {
+            self.dump_conversation_graph_to(dump_path);
+        }
+        if self.warn_unused_rules {
+            self.report_unused_rules();
+        }
+        Ok(())
+    }
+
+    /// Formatters and hooks that should be applied to generated files
+    fn content_filter(&self) -> Option<file_cache::ContentFilter> {
+        if self.formatters.is_empty() && self.generated_file_hooks.borrow().is_empty() {
+            return None;
+        }
+        let formatters = self.formatters.clone();
+        let hooks = self.generated_file_hooks.clone();
+        Some(Box::new(move |path, cnt| {
+            let cnt = formatters
+                .iter()
+                .filter(|x| x.is_applicable(path))
+                .try_fold(cnt, |cnt, x| x.format(path, cnt))?;
+            let mut hooks = hooks.borrow_mut();
+            if hooks.is_empty() {
+                return Ok(cnt);
+            }
+            let mut cnt = String::from_utf8(cnt).map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} is not valid UTF-8: {}", path.display(), err),
+                )
+            })?;
+            for hook in hooks.iter_mut() {
+                hook(path, &mut cnt);
+            }
+            Ok(cnt.into_bytes())
+        }))
+    }
+
+    fn report_unused_rules(&self) {
+        let warnings = self.conv_map.warnings();
+        for (sp, rule) in self.conv_map.unused_rules() {
+            warnings.print(
+                DiagnosticCode::UnusedRule,
+                format!("unused {}{}", rule, self.location_descr(sp)),
+            );
+        }
+    }
+
+    fn report_conv_graph_problems(&self) {
+        if self
+            .conv_map
+            .warnings()
+            .is_allowed(DiagnosticCode::ConvGraphProblem)
+        {
+            return;
+        }
+        for problem in self.conv_map.validate_conv_graph() {
+            println!(
+                "warning=[{}] {}",
+                DiagnosticCode::ConvGraphProblem,
+                problem.descr
+            );
+            for (sp, rule) in problem.rules {
+                println!("warning=    {}{}", rule, self.location_descr(sp));
+            }
+        }
+    }
+
+    /// Add location of `item` and its methods to their doc comments,
+    /// returns location of `item`
+    fn add_source_location_comments(&self, item: &mut ItemToExpand) -> String {
+        let location = |(src_id, span): SourceIdSpan| {
+            format!(
+                "{}:{}",
+                self.src_reg.src_with_id(src_id).id_of_code,
+                span.start().line
+            )
+        };
+        let add_comment = |doc_comments: &mut Vec<String>, location: &str| {
+            if !doc_comments.is_empty() {
+                doc_comments.push(String::new());
+            }
+            doc_comments.push(format!(" Source: {}", location));
+        };
+        match item {
+            ItemToExpand::Class(fclass) => {
+                let fclass = Rc::make_mut(fclass);
+                for method in &mut fclass.methods {
+                    let method_location = location((fclass.src_id, method.fn_decl.span));
+                    add_comment(&mut method.doc_comments, &method_location);
+                }
+                let class_location = location((fclass.src_id, fclass.span()));
+                add_comment(&mut fclass.doc_comments, &class_location);
+                class_location
+            }
+            ItemToExpand::Enum(fenum) => {
+                let enum_location = location((fenum.src_id, fenum.span()));
+                add_comment(&mut fenum.doc_comments, &enum_location);
+                enum_location
+            }
+            ItemToExpand::Interface(finterface) => {
+                for method in &mut finterface.items {
+                    let method_location = location((finterface.src_id, method.fn_decl.span));
+                    add_comment(&mut method.doc_comments, &method_location);
+                }
+                let interface_location = location(finterface.src_id_span());
+                add_comment(&mut finterface.doc_comments, &interface_location);
+                interface_location
+            }
+        }
+    }
+
+    fn location_descr(&self, (src_id, span): SourceIdSpan) -> String {
+        if src_id.is_none() {
+            String::new()
+        } else {
+            let start = span.start();
+            format!(
+                " at {}:{}:{}",
+                self.src_reg.src_with_id(src_id).id_of_code,
+                start.line,
+                start.column
+            )
         }
+    }
 
+    fn dump_conversation_graph_to(&self, dump_path: &Path) {
+        let src_reg = &self.src_reg;
+        let dot = self
+            .conv_map
+            .conv_graph_as_dot(|src_id| src_reg.src_with_id(src_id).id_of_code.clone());
+        let mut file = file_cache::FileWriteCache::new(dump_path);
+        write!(&mut file, "{}", dot).expect("mem I/O failed");
         file.update_file_if_necessary().unwrap_or_else(|err| {
             panic!(
                 "Error during write to file {}: {}",
-                dst.as_ref().display(),
+                dump_path.display(),
                 err
             );
         });
-        Ok(())
     }
 
     fn init_types_map(&mut self, target_pointer_width: usize) -> Result<Vec<syn::Item>> {
@@ -470,9 +1809,68 @@ impl Generator {
             return Ok(vec![]);
         }
         self.init_done = true;
+        self.conv_map.set_target_cfg(self.target_cfg.clone());
+        let cache = match self.typemap_cache_dir {
+            Some(ref dir) => ParseCache::OnDisk(dir),
+            None => ParseCache::InMemory,
+        };
+        // Parsing of types maps with `syn` does not depend on other types maps,
+        // so if there are merged types maps, built-in ones are parsed in background
+        // (result is passed via cache) while merged ones are parsed here,
+        // they are not cached, because of spans are required for diagnostics
+        let is_builtin = |conv_map: &TypeMap, src_id: SourceId| {
+            conv_map.rule_precedence(src_id) == RulePrecedence::BuiltIn
+        };
+        let mut background_parsing = None;
+        let mut parsed_files = FxHashMap::default();
+        for src_id in &self.conv_map_source {
+            if is_builtin(&self.conv_map, *src_id) {
+                continue;
+            }
+            if parsed_files.is_empty() {
+                let builtin_sources = self
+                    .conv_map_source
+                    .iter()
+                    .filter(|src_id| is_builtin(&self.conv_map, **src_id))
+                    .map(|src_id| (*src_id, self.src_reg.src(*src_id).to_string()))
+                    .collect();
+                background_parsing =
+                    self.conv_map
+                        .parse_in_background(builtin_sources, target_pointer_width, cache);
+            }
+            let _timer = profiler::timer(Stage::TypeMapParsing, || {
+                self.src_reg.src_with_id(*src_id).id_of_code.clone()
+            });
+            parsed_files.insert(*src_id, parse_file(*src_id, self.src_reg.src(*src_id)));
+        }
+        let mut builtin_rules_removed = false;
         for code_id in &self.conv_map_source {
             let code = self.src_reg.src(*code_id);
-            self.conv_map.merge(*code_id, code, target_pointer_width)?;
+            let _timer = profiler::timer(Stage::TypeMapParsing, || {
+                self.src_reg.src_with_id(*code_id).id_of_code.clone()
+            });
+            if is_builtin(&self.conv_map, *code_id) {
+                if let Some(handle) = background_parsing.take() {
+                    // errors and panics are ignored here, in such case types map
+                    // is not in cache and would be parsed again
+                    let _ = handle.join();
+                }
+                self.conv_map
+                    .merge_with_cache(*code_id, code, target_pointer_width, cache)?;
+                continue;
+            }
+            if !builtin_rules_removed {
+                remove_conversions(&mut self.conv_map, &self.removed_conversions)?;
+                builtin_rules_removed = true;
+            }
+            let file = parsed_files
+                .remove(code_id)
+                .expect("Internal error: merged types map was not parsed")?;
+            self.conv_map
+                .merge_parsed(*code_id, file, target_pointer_width)?;
+        }
+        if !builtin_rules_removed {
+            remove_conversions(&mut self.conv_map, &self.removed_conversions)?;
         }
 
         if self.conv_map.is_empty() {
@@ -486,12 +1884,129 @@ impl Generator {
 
     fn language_generator(cfg: &LanguageConfig) -> &LanguageGenerator {
         match cfg {
+            #[cfg(feature = "java")]
             LanguageConfig::JavaConfig(ref java_cfg) => java_cfg,
+            #[cfg(feature = "cpp")]
             LanguageConfig::CppConfig(ref cpp_cfg) => cpp_cfg,
         }
     }
 }
 
+/// `TypeMapTester` helps to unit-test custom types maps:
+/// it loads types maps of `Generator` and shows which code is generated
+/// to convert one Rust type to another.
+/// For example `TypeMapTester::new(Generator::new(config).merge_type_map("my_typemap", code))`
+pub struct TypeMapTester {
+    generator: Generator,
+}
+
+impl TypeMapTester {
+    pub fn new(generator: Generator) -> TypeMapTester {
+        TypeMapTester { generator }
+    }
+
+    /// Returns code that converts variable `var_name` from type `from` to type `to`,
+    /// `function_ret_type` is return type of function that contains this code.
+    /// For example `convert_rust_types("jboolean", "bool", "a0", "jlong")`
+    /// with built-in Java types map returns `"    let mut a0: bool = a0.swig_into(env);\n"`.
+    /// `Err` contains description why conversation is impossible
+    ///
+    /// # Panics
+    /// Panics on error in types maps
+    pub fn convert_rust_types(
+        &mut self,
+        from: &str,
+        to: &str,
+        var_name: &str,
+        function_ret_type: &str,
+    ) -> std::result::Result<String, String> {
+        let gen = &mut self.generator;
+        if gen.pointer_target_width == 0 {
+            gen.pointer_target_width = mem::size_of::<usize>() * 8;
+        }
+        if let Err(err) = gen.init_types_map(gen.pointer_target_width) {
+            panic_on_parse_error(&gen.src_reg, &err);
+        }
+        let parse_type = |name: &str| {
+            syn::parse_str::<syn::Type>(name)
+                .map_err(|err| format!("can not parse type '{}': {}", name, err))
+        };
+        let (from_ty, to_ty) = (parse_type(from)?, parse_type(to)?);
+        let from_ty = gen
+            .conv_map
+            .find_or_alloc_rust_type(&from_ty, SourceId::none());
+        let to_ty = gen
+            .conv_map
+            .find_or_alloc_rust_type(&to_ty, SourceId::none());
+        let (_, code) = gen
+            .conv_map
+            .convert_rust_types(
+                from_ty,
+                to_ty,
+                var_name,
+                function_ret_type,
+                error::invalid_src_id_span(),
+            )
+            .map_err(|err| err.to_string())?;
+        Ok(code)
+    }
+}
+
+fn remove_conversions(conv_map: &mut TypeMap, conversions: &[(String, String)]) -> Result<()> {
+    for (from, to) in conversions {
+        let parse_type = |name: &str| {
+            syn::parse_str::<syn::Type>(name).map_err(|err| {
+                DiagnosticError::new_without_src_info(format!(
+                    "remove_conversion: can not parse type '{}': {}",
+                    name, err
+                ))
+            })
+        };
+        let (from_ty, to_ty) = (parse_type(from)?, parse_type(to)?);
+        if !conv_map.remove_conv_rules(&from_ty, &to_ty) {
+            return Err(DiagnosticError::new_without_src_info(format!(
+                "remove_conversion: there is no rule to convert '{}' to '{}'",
+                from, to
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// `cfg` condition for target: pointer width and optional OS
+fn target_cfg_cond((pointer_width, os): &(usize, Option<String>)) -> String {
+    match os {
+        Some(os) => format!(
+            "all(target_pointer_width = \"{}\", target_os = \"{}\")",
+            pointer_width, os
+        ),
+        None => format!("target_pointer_width = \"{}\"", pointer_width),
+    }
+}
+
+/// Add `#[cfg(..)]` for `target` to each item in `code`
+fn cfg_each_item(target: &(usize, Option<String>), code: &[u8]) -> Result<Vec<u8>> {
+    let code = syn::parse_file(&String::from_utf8_lossy(code)).map_err(|err| {
+        DiagnosticError::new_without_src_info(format!(
+            "Internal error: can not parse generated code: {}",
+            err
+        ))
+    })?;
+    let cond: TokenStream = target_cfg_cond(target)
+        .parse()
+        .expect("Internal error: invalid cfg condition");
+    let mut ret = Vec::new();
+    for item in code.items {
+        writeln!(
+            &mut ret,
+            "{}",
+            quote::quote! { #[cfg(#cond)] #item }.to_string()
+        )
+        .expect("mem I/O failed");
+    }
+    Ok(ret)
+}
+
 trait LanguageGenerator {
     fn expand_items(
         &self,
@@ -499,5 +2014,6 @@ trait LanguageGenerator {
         pointer_target_width: usize,
         code: &[SourceCode],
         items: Vec<ItemToExpand>,
-    ) -> Result<Vec<TokenStream>>;
+        manifest: &mut ApiManifest,
+    ) -> Result<Vec<ExpandedItem>>;
 }
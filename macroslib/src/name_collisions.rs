@@ -0,0 +1,77 @@
+use std::fmt::Display;
+
+use rustc_hash::FxHashMap;
+
+use crate::{
+    diagnostic_codes::DiagnosticCode,
+    error::{invalid_src_id_span, DiagnosticError, Result, SourceIdSpan},
+    types::ItemToExpand,
+};
+
+/// Names of items in generated code, to report all collisions
+/// before generation of any file
+#[derive(Default)]
+pub(crate) struct NameRegistry {
+    names: FxHashMap<String, (Option<SourceIdSpan>, String)>,
+    err: Option<DiagnosticError>,
+}
+
+impl NameRegistry {
+    /// Register `name` of `what` defined at `sp`
+    pub(crate) fn add<T: Display>(&mut self, name: String, sp: SourceIdSpan, what: T) {
+        self.insert(name, Some(sp), what.to_string());
+    }
+
+    /// Register `name` of generated helper, that has no location in source code
+    pub(crate) fn reserve<T: Display>(&mut self, name: String, what: T) {
+        self.insert(name, None, what.to_string());
+    }
+
+    fn insert(&mut self, name: String, sp: Option<SourceIdSpan>, what: String) {
+        let (prev_sp, prev_what) = match self.names.get(&name) {
+            Some(prev) => prev.clone(),
+            None => {
+                self.names.insert(name, (sp, what));
+                return;
+            }
+        };
+        let (sp, what, prev_sp, prev_what) = match (sp, prev_sp) {
+            (Some(sp), prev_sp) => (sp, what, prev_sp, prev_what),
+            (None, Some(prev_sp)) => (prev_sp, prev_what, None, what),
+            (None, None) => (invalid_src_id_span(), what, None, prev_what),
+        };
+        let msg = format!("name `{}` of {} conflicts with {}", name, what, prev_what);
+        match self.err {
+            Some(ref mut err) => err.span_note(sp, msg),
+            None => {
+                self.err =
+                    Some(DiagnosticError::new2(sp, msg).with_code(DiagnosticCode::NameCollision))
+            }
+        }
+        if let Some(prev_sp) = prev_sp {
+            if let Some(ref mut err) = self.err {
+                err.span_note(prev_sp, format!("{} defined here", prev_what));
+            }
+        }
+    }
+
+    /// Register foreign names of classes, enums and interfaces
+    pub(crate) fn add_items(&mut self, items: &[ItemToExpand]) {
+        for item in items {
+            let (name, sp) = match item {
+                ItemToExpand::Class(fclass) => (&fclass.name, (fclass.src_id, fclass.span())),
+                ItemToExpand::Enum(fenum) => (&fenum.name, (fenum.src_id, fenum.span())),
+                ItemToExpand::Interface(finterface) => (&finterface.name, finterface.src_id_span()),
+            };
+            self.add(name.to_string(), sp, item.descr());
+        }
+    }
+
+    /// Error with all collisions found
+    pub(crate) fn into_result(self) -> Result<()> {
+        match self.err {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
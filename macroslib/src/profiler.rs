@@ -0,0 +1,185 @@
+//! Measure where time is spent during code generation:
+//! parsing of types maps, expansion of items, search of conversations
+//! for methods and writing of generated files.
+
+use std::{
+    cell::RefCell,
+    fmt,
+    time::{Duration, Instant},
+};
+
+use rustc_hash::FxHashMap;
+
+use crate::file_cache;
+
+/// How many the slowest entries of each stage are shown in report
+const MAX_ENTRIES_IN_REPORT: usize = 10;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub(crate) enum Stage {
+    TypeMapParsing,
+    ItemExpansion,
+    MethodConversions,
+    FileEmission,
+}
+
+impl Stage {
+    fn descr(self) -> &'static str {
+        match self {
+            Stage::TypeMapParsing => "types maps parsing",
+            Stage::ItemExpansion => "items expansion",
+            Stage::MethodConversions => "search of conversations for methods",
+            Stage::FileEmission => "files emission",
+        }
+    }
+}
+
+/// Time spent in stages, per name of types map, item, method or file
+#[derive(Default, Debug)]
+pub(crate) struct Profile {
+    entries: FxHashMap<Stage, FxHashMap<String, Duration>>,
+}
+
+impl Profile {
+    fn add(&mut self, stage: Stage, name: String, time: Duration) {
+        *self
+            .entries
+            .entry(stage)
+            .or_default()
+            .entry(name)
+            .or_default() += time;
+    }
+
+    pub(crate) fn total(&self, stage: Stage) -> Duration {
+        self.entries
+            .get(&stage)
+            .map(|x| x.values().sum())
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut stages: Vec<_> = self.entries.iter().collect();
+        stages.sort_by_key(|x| x.0);
+        writeln!(f, "rust_swig profiling report:")?;
+        for (stage, entries) in stages {
+            writeln!(
+                f,
+                "{}: {}",
+                stage.descr(),
+                DisplayDuration(self.total(*stage))
+            )?;
+            let mut entries: Vec<_> = entries.iter().collect();
+            entries.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+            for (name, time) in entries.iter().take(MAX_ENTRIES_IN_REPORT) {
+                writeln!(f, "    {}: {}", name, DisplayDuration(**time))?;
+            }
+            if entries.len() > MAX_ENTRIES_IN_REPORT {
+                writeln!(
+                    f,
+                    "    ... and {} more",
+                    entries.len() - MAX_ENTRIES_IN_REPORT
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct DisplayDuration(Duration);
+
+impl fmt::Display for DisplayDuration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ms = self.0.as_secs() as f64 * 1000. + f64::from(self.0.subsec_nanos()) / 1_000_000.;
+        write!(f, "{:.1}ms", ms)
+    }
+}
+
+thread_local! {
+    static PROFILE: RefCell<Option<Profile>> = const { RefCell::new(None) };
+}
+
+/// Call `f` and collect time spent by it on the current thread,
+/// including writing of files via `FileWriteCache`
+pub(crate) fn collect_profile<R, F: FnOnce() -> R>(f: F) -> (R, Profile) {
+    struct Guard(Option<Profile>);
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            let prev = self.0.take();
+            PROFILE.with(|cur| *cur.borrow_mut() = prev);
+        }
+    }
+    let _guard = Guard(PROFILE.with(|cur| cur.replace(Some(Profile::default()))));
+    let (ret, write_times) = file_cache::collect_write_times(f);
+    let mut profile = PROFILE.with(|cur| cur.borrow_mut().take().unwrap_or_default());
+    for (path, time) in write_times {
+        profile.add(Stage::FileEmission, path.display().to_string(), time);
+    }
+    (ret, profile)
+}
+
+/// Measures time until it is dropped
+pub(crate) struct Timer {
+    stage: Stage,
+    name: String,
+    start: Instant,
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        let time = self.start.elapsed();
+        let name = std::mem::take(&mut self.name);
+        PROFILE.with(|cur| {
+            if let Some(ref mut profile) = *cur.borrow_mut() {
+                profile.add(self.stage, name, time);
+            }
+        });
+    }
+}
+
+/// Start measurement of `stage` for `name`, if profile is collected
+/// on the current thread, otherwise `name` is not even called
+pub(crate) fn timer<N: FnOnce() -> String>(stage: Stage, name: N) -> Option<Timer> {
+    if PROFILE.with(|cur| cur.borrow().is_none()) {
+        return None;
+    }
+    Some(Timer {
+        stage,
+        name: name(),
+        start: Instant::now(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::Write, thread};
+
+    #[test]
+    fn test_collect_profile() {
+        assert!(timer(Stage::ItemExpansion, || unreachable!()).is_none());
+
+        let tmp_dir = tempfile::tempdir().expect("Can not create tmp directory");
+        let path = tmp_dir.path().join("a.txt");
+        let (ret, profile) = collect_profile(|| {
+            for _ in 0..2 {
+                let _timer = timer(Stage::MethodConversions, || "Foo::f".into());
+                thread::sleep(Duration::from_millis(5));
+            }
+            let mut file = file_cache::FileWriteCache::new(&path);
+            file.write_all(b"abc").unwrap();
+            file.update_file_if_necessary().unwrap();
+            42
+        });
+        assert_eq!(42, ret);
+        assert!(profile.total(Stage::MethodConversions) >= Duration::from_millis(10));
+        assert_eq!(Duration::default(), profile.total(Stage::TypeMapParsing));
+        let report = profile.to_string();
+        assert!(report.starts_with("rust_swig profiling report:\n"));
+        assert!(report.contains("\nsearch of conversations for methods: "));
+        assert!(report.contains("\n    Foo::f: "));
+        assert!(report.contains(&format!("\n    {}: ", path.display())));
+        assert!(timer(Stage::ItemExpansion, || unreachable!()).is_none());
+    }
+}
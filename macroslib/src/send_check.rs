@@ -0,0 +1,43 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use crate::{typemap::ast::list_lifetimes, types::ForeignerClassInfo};
+
+/// Function, that asserts that Rust types of `class` implement `Send`,
+/// because foreign code may use objects of class from any thread
+pub(crate) fn generate_send_checks(class: &ForeignerClassInfo) -> Option<TokenStream> {
+    if class.single_threaded {
+        return None;
+    }
+    let self_desc = class.self_desc.as_ref()?;
+    let mut types = vec![&self_desc.self_type];
+    if self_desc.constructor_ret_type != self_desc.self_type {
+        types.push(&self_desc.constructor_ret_type);
+    }
+    let mut lifetimes = Vec::new();
+    for ty in &types {
+        lifetimes.extend(list_lifetimes(ty));
+    }
+    lifetimes.retain(|x| x != "'static");
+    lifetimes.sort();
+    lifetimes.dedup();
+    let generics = if lifetimes.is_empty() {
+        quote! {}
+    } else {
+        let lifetimes = lifetimes
+            .iter()
+            .map(|x| syn::Lifetime::new(x, proc_macro2::Span::call_site()));
+        quote! { <#(#lifetimes),*> }
+    };
+    let func_name = syn::Ident::new(
+        &format!("swig_assert_send_{}", class.name),
+        proc_macro2::Span::call_site(),
+    );
+    Some(quote! {
+        #[allow(dead_code, non_snake_case)]
+        fn #func_name #generics() {
+            fn _assert_send<T: Send>() {}
+            #(_assert_send::<#types>();)*
+        }
+    })
+}
@@ -0,0 +1,81 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parse_quote, Type};
+
+use crate::{typemap::ast::list_lifetimes, types::ForeignerClassInfo};
+
+/// Function, that assigns Rust function of each method of `class`
+/// to function pointer with signature from `foreigner_class!`,
+/// so mismatch of declaration and real function is reported by rustc
+pub(crate) fn generate_signature_checks(class: &ForeignerClassInfo) -> TokenStream {
+    let self_type = class.self_desc.as_ref().map(|x| &x.self_type);
+    let mut checks = Vec::with_capacity(class.methods.len());
+    for method in &class.methods {
+        let mut args = Vec::<Type>::with_capacity(method.fn_decl.inputs.len());
+        let mut have_self_type = true;
+        for arg in &method.fn_decl.inputs {
+            let arg_ty: Type = match arg {
+                syn::FnArg::SelfRef(r) => match (self_type, r.mutability.is_some()) {
+                    (Some(self_type), true) => parse_quote! { &mut #self_type },
+                    (Some(self_type), false) => parse_quote! { &#self_type },
+                    (None, _) => {
+                        have_self_type = false;
+                        break;
+                    }
+                },
+                syn::FnArg::SelfValue(_) => match self_type {
+                    Some(self_type) => self_type.clone(),
+                    None => {
+                        have_self_type = false;
+                        break;
+                    }
+                },
+                syn::FnArg::Captured(syn::ArgCaptured { ty, .. }) | syn::FnArg::Ignored(ty) => {
+                    ty.clone()
+                }
+                syn::FnArg::Inferred(_) => unreachable!("type of method argument is always known"),
+            };
+            args.push(arg_ty);
+        }
+        // methods without `self_type` are reported during generation of code
+        if !have_self_type {
+            continue;
+        }
+        let output = match method.fn_decl.output {
+            syn::ReturnType::Default => quote! {},
+            syn::ReturnType::Type(_, ref ty) => quote! { -> #ty },
+        };
+        let mut lifetimes = Vec::new();
+        for ty in &args {
+            lifetimes.extend(list_lifetimes(ty));
+        }
+        if let syn::ReturnType::Type(_, ref ty) = method.fn_decl.output {
+            lifetimes.extend(list_lifetimes(ty));
+        }
+        lifetimes.retain(|x| x != "'static");
+        lifetimes.sort();
+        lifetimes.dedup();
+        let for_lifetimes = if lifetimes.is_empty() {
+            quote! {}
+        } else {
+            let lifetimes = lifetimes
+                .iter()
+                .map(|x| syn::Lifetime::new(x, proc_macro2::Span::call_site()));
+            quote! { for<#(#lifetimes),*> }
+        };
+        let rust_id = &method.rust_id;
+        checks.push(quote! {
+            let _: #for_lifetimes fn(#(#args),*) #output = #rust_id;
+        });
+    }
+    let func_name = syn::Ident::new(
+        &format!("swig_check_signatures_{}", class.name),
+        proc_macro2::Span::call_site(),
+    );
+    quote! {
+        #[allow(dead_code, non_snake_case)]
+        fn #func_name() {
+            #(#checks)*
+        }
+    }
+}
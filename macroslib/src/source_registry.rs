@@ -27,7 +27,7 @@ impl SourceRegistry {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub(crate) struct SourceId(Option<usize>);
 
 impl SourceId {
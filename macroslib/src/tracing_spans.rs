@@ -0,0 +1,108 @@
+//! Instrumentation of generated functions with spans of `tracing` crate,
+//! so calls through FFI layer can be profiled and debugged in production.
+//! Span is entered before conversation of arguments, and lengths of strings
+//! and collections among arguments are recorded after it.
+
+use std::fmt::Write;
+
+use syn::Type;
+
+use crate::{
+    typemap::ast::fn_arg_type,
+    types::{ForeignerClassInfo, ForeignerMethod, MethodVariant},
+};
+
+/// Code that creates and enters span for call of `method`, and code
+/// that records lengths of converted arguments `a_0`, `a_1`, ... among
+/// first `n_args` arguments, it should be placed after conversation of arguments
+pub(crate) fn method_span_code(
+    class: &ForeignerClassInfo,
+    method: &ForeignerMethod,
+    n_args: usize,
+) -> (String, String) {
+    //skip self
+    let skip_n = match method.variant {
+        MethodVariant::Method(_) => 1,
+        _ => 0,
+    };
+    let mut fields = String::new();
+    let mut record_code = String::new();
+    for (i, arg) in method
+        .fn_decl
+        .inputs
+        .iter()
+        .skip(skip_n)
+        .take(n_args)
+        .enumerate()
+    {
+        if has_len(fn_arg_type(arg)) {
+            write!(&mut fields, ", a_{}_len = ::tracing::field::Empty", i).unwrap();
+            writeln!(
+                &mut record_code,
+                "    swig_span.record(\"a_{i}_len\", &a_{i}.len());",
+                i = i
+            )
+            .unwrap();
+        }
+    }
+    (
+        span_code(&class.name.to_string(), &method.short_name(), &fields),
+        record_code,
+    )
+}
+
+/// Code that creates and enters span for destruction of object of `class`
+pub(crate) fn destructor_span_code(class: &ForeignerClassInfo) -> String {
+    span_code(&class.name.to_string(), "drop", "")
+}
+
+fn span_code(class: &str, method: &str, fields: &str) -> String {
+    format!(
+        r#"    let swig_span = ::tracing::trace_span!("{class}::{method}", class = "{class}", method = "{method}"{fields});
+    let _swig_enter = swig_span.enter();
+"#,
+        class = class,
+        method = method,
+        fields = fields,
+    )
+}
+
+/// `&str`, `String`, slices and `Vec`, or references to them
+fn has_len(ty: &Type) -> bool {
+    match ty {
+        Type::Reference(ref r) => match *r.elem {
+            Type::Slice(_) => true,
+            ref elem => has_len(elem),
+        },
+        Type::Slice(_) => true,
+        Type::Path(ref p) => p
+            .path
+            .segments
+            .last()
+            .map(|seg| {
+                let ident = &seg.value().ident;
+                ident == "str" || ident == "String" || ident == "Vec"
+            })
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn test_has_len() {
+        assert!(has_len(&parse_type! { &str }));
+        assert!(has_len(&parse_type! { String }));
+        assert!(has_len(&parse_type! { &[u8] }));
+        assert!(has_len(&parse_type! { &mut [i32] }));
+        assert!(has_len(&parse_type! { Vec<Foo> }));
+        assert!(has_len(&parse_type! { &Vec<u8> }));
+        assert!(!has_len(&parse_type! { i32 }));
+        assert!(!has_len(&parse_type! { &Foo }));
+        assert!(!has_len(&parse_type! { Option<String> }));
+    }
+}
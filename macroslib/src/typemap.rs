@@ -1,91 +1,178 @@
 pub mod ast;
+mod cache;
 mod merge;
 mod parse;
 mod parse_typemap_macro;
 pub mod ty;
 pub mod utils;
+mod validate;
+
+use std::{
+    cell::{Cell, RefCell},
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fmt::{self, Write},
+    mem, ops,
+    rc::Rc,
+};
 
-use std::{cell::RefCell, fmt, mem, ops, rc::Rc};
-
-use log::{debug, log_enabled, trace, warn};
+use log::{debug, info, log_enabled, trace, warn};
 use petgraph::{
     graph::{EdgeIndex, NodeIndex},
+    visit::EdgeRef,
     Graph,
 };
-use proc_macro2::TokenStream;
+use proc_macro2::{Span, TokenStream};
 use rustc_hash::{FxHashMap, FxHashSet};
 use smallvec::SmallVec;
 use smol_str::SmolStr;
-use syn::{parse_quote, Ident, Type};
+use syn::{parse_quote, spanned::Spanned, Ident, Type};
 
 use crate::{
+    diagnostic_codes::{DiagnosticCode, Warnings},
     error::{invalid_src_id_span, DiagnosticError, Result, SourceIdSpan},
     source_registry::SourceId,
     typemap::{
         ast::{
-            get_trait_bounds, normalize_ty_lifetimes, DisplayToTokens, GenericTypeConv, TypeName,
+            get_trait_bounds, normalize_ty_lifetimes, respan_ty, DisplayToTokens, GenericTypeConv,
+            TypeName,
         },
         ty::{
-            ForeignConversationRule, ForeignType, ForeignTypeS, ForeignTypesStorage, RustType,
-            RustTypeS,
+            add_rust_type_node, ForeignConversationRule, ForeignType, ForeignTypeS,
+            ForeignTypesStorage, RustTypeS,
         },
     },
     types::{ForeignEnumInfo, ForeignerClassInfo},
 };
 
-pub(crate) use parse_typemap_macro::{CType, CTypes, TypeMapConvRuleInfo};
+pub(crate) use cache::ParseCache;
+pub(crate) use parse::{parse_file, TargetCfg};
+pub(crate) use parse_typemap_macro::TypeMapConvRuleInfo;
+#[cfg(feature = "cpp")]
+pub(crate) use parse_typemap_macro::{CType, CTypes};
 pub(crate) static TO_VAR_TEMPLATE: &str = "{to_var}";
 pub(crate) static FROM_VAR_TEMPLATE: &str = "{from_var}";
-pub(in crate::typemap) static TO_VAR_TYPE_TEMPLATE: &str = "{to_var_type}";
-pub(in crate::typemap) static FUNCTION_RETURN_TYPE_TEMPLATE: &str = "{function_ret_type}";
+pub(crate) static TO_VAR_TYPE_TEMPLATE: &str = "{to_var_type}";
+pub(crate) static FUNCTION_RETURN_TYPE_TEMPLATE: &str = "{function_ret_type}";
+/// name of temporary variable, unique for each conversation step
+pub(in crate::typemap) static TMP_VAR_TEMPLATE: &str = "{tmp_var}";
 const MAX_TRY_BUILD_PATH_STEPS: usize = 7;
+/// Cost of conversation rule if it is not specified in typemap
+pub(crate) const DEFAULT_CONV_COST: u32 = 1;
+
+/// If several rules provide conversation between the same pair of types,
+/// the rule with higher precedence wins
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum RulePrecedence {
+    /// rules from typemaps that come with rust_swig
+    BuiltIn,
+    /// rules from typemaps added via `Generator::merge_type_map`
+    Merged,
+    /// rules generated for user's classes, enums and interfaces
+    User,
+}
 
 #[derive(Debug, Clone)]
 pub(crate) struct TypeConvEdge {
-    code_template: String,
+    /// shared with copies of edge made for class scopes and graph snapshots
+    code_template: Rc<str>,
     dependency: Rc<RefCell<Option<TokenStream>>>,
+    src_id: SourceId,
+    precedence: RulePrecedence,
+    force_override: bool,
+    /// the cheapest path between two types is used for conversation
+    cost: u32,
+    span: Span,
+    /// was rule used to generate code
+    used: Rc<Cell<bool>>,
+    /// why conversation may lose data, from `#[swig_lossy]`
+    lossy: Option<String>,
+    /// code of `fallible` rule reports error to foreign language
+    fallible: bool,
 }
 
 impl From<String> for TypeConvEdge {
     fn from(x: String) -> Self {
-        TypeConvEdge {
-            code_template: x,
-            dependency: Rc::new(RefCell::new(None)),
-        }
+        TypeConvEdge::new(x, None)
     }
 }
 
 impl TypeConvEdge {
-    fn new(code_template: String, dependency: Option<TokenStream>) -> TypeConvEdge {
+    fn new<S: Into<Rc<str>>>(code_template: S, dependency: Option<TokenStream>) -> TypeConvEdge {
         TypeConvEdge {
-            code_template,
+            code_template: code_template.into(),
             dependency: Rc::new(RefCell::new(dependency)),
+            src_id: SourceId::none(),
+            precedence: RulePrecedence::User,
+            force_override: false,
+            cost: DEFAULT_CONV_COST,
+            span: Span::call_site(),
+            used: Rc::new(Cell::new(false)),
+            lossy: None,
+            fallible: false,
         }
     }
 }
 
 pub(crate) type TypeGraphIdx = u32;
-pub(crate) type TypesConvGraph = Graph<RustType, TypeConvEdge, petgraph::Directed, TypeGraphIdx>;
+pub(crate) type TypesConvGraph = Graph<RustTypeS, TypeConvEdge, petgraph::Directed, TypeGraphIdx>;
 
 pub(crate) type RustTypeIdx = NodeIndex<TypeGraphIdx>;
 
 type RustTypeNameToGraphIdx = FxHashMap<SmolStr, RustTypeIdx>;
 
+/// The cheapest paths (or their absence) between pairs of types,
+/// should be cleared after any change of edges of conversation graph
+pub(in crate::typemap) type ConvPathsCache =
+    RefCell<FxHashMap<(RustTypeIdx, RustTypeIdx), Option<Vec<EdgeIndex<TypeGraphIdx>>>>>;
+
+/// Reusable results of `GenericTypeConv::instantiate`, key is index of rule
+/// in `generic_edges` and name of input type, value is result type and its name.
+/// On reuse type gets span of new request.
+/// Should be cleared after any change of generic rules
+pub(in crate::typemap) type GenericInstancesCache =
+    FxHashMap<(usize, SmolStr), Option<(syn::Type, SmolStr)>>;
+
 #[derive(Debug)]
 pub(crate) struct TypeMap {
     conv_graph: TypesConvGraph,
     ftypes_storage: ForeignTypesStorage,
-    rust_to_foreign_cache: FxHashMap<SmolStr, ForeignType>,
+    rust_to_foreign_cache: FxHashMap<RustTypeIdx, ForeignType>,
+    conv_paths_cache: ConvPathsCache,
     rust_names_map: RustTypeNameToGraphIdx,
     utils_code: Vec<syn::Item>,
     generic_edges: Vec<GenericTypeConv>,
-    foreign_classes: Vec<ForeignerClassInfo>,
+    generic_instances_cache: GenericInstancesCache,
+    foreign_classes: Vec<Rc<ForeignerClassInfo>>,
     exported_enums: FxHashMap<SmolStr, ForeignEnumInfo>,
     /// How to use trait to convert types, Trait Name -> Code
     traits_usage_code: FxHashMap<Ident, String>,
     /// code that parsed, but not yet integrated to TypeMap,
     /// because of it is possible only in langauge backend
     not_merged_data: Vec<TypeMapConvRuleInfo>,
+    /// Precedence of rules from merged sources
+    src_precedence: FxHashMap<SourceId, RulePrecedence>,
+    /// To filter out rules with `#[cfg]` not suitable for target
+    target_cfg: TargetCfg,
+    /// Rules of class that code is generated right now
+    class_scope: Option<TypeMapScope>,
+    /// Rules of classes, that were removed with their scope,
+    /// to report unused ones
+    class_scope_rules: Vec<(SourceIdSpan, String, Rc<Cell<bool>>)>,
+    /// How many times `fallible` rules were used in generated conversations
+    fallible_conversations: usize,
+    warnings: Warnings,
+    /// Reject conversations that may lose data
+    strict_conversions: bool,
+}
+
+/// Changes in `TypeMap` made by class scoped rules,
+/// to revert them after code for class is generated
+#[derive(Debug, Default)]
+struct TypeMapScope {
+    ftypes: Vec<ForeignType>,
+    added_edges: Vec<(RustTypeIdx, RustTypeIdx)>,
+    replaced_edges: Vec<(RustTypeIdx, RustTypeIdx, TypeConvEdge)>,
 }
 
 impl Default for TypeMap {
@@ -138,12 +225,21 @@ impl Default for TypeMap {
             rust_names_map: FxHashMap::default(),
             utils_code: Vec::new(),
             generic_edges: default_rules,
+            generic_instances_cache: GenericInstancesCache::default(),
             rust_to_foreign_cache: FxHashMap::default(),
+            conv_paths_cache: ConvPathsCache::default(),
             foreign_classes: Vec::new(),
             exported_enums: FxHashMap::default(),
             traits_usage_code: FxHashMap::default(),
             ftypes_storage: ForeignTypesStorage::default(),
             not_merged_data: vec![],
+            src_precedence: FxHashMap::default(),
+            target_cfg: TargetCfg::default(),
+            class_scope: None,
+            class_scope_rules: Vec::new(),
+            fallible_conversations: 0,
+            warnings: Warnings::default(),
+            strict_conversions: false,
         }
     }
 }
@@ -173,10 +269,62 @@ impl<'a> fmt::Display for DisplayTypesConvGraph<'a> {
     }
 }
 
+/// Display conversation graph in Graphviz DOT format,
+/// edges labeled with code templates and names of sources of rules
+pub(crate) struct DotTypesConvGraph<'a, F> {
+    conv_graph: &'a TypesConvGraph,
+    src_name: F,
+}
+
+impl<'a, F> fmt::Display for DotTypesConvGraph<'a, F>
+where
+    F: Fn(SourceId) -> String,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
+        fn escape(s: &str) -> String {
+            s.trim()
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\l")
+        }
+        let conv_graph = self.conv_graph;
+        writeln!(f, "digraph conversation_graph {{")?;
+        for node in conv_graph.node_indices() {
+            writeln!(
+                f,
+                "    n{} [label=\"{}\"];",
+                node.index(),
+                escape(&conv_graph[node].normalized_name)
+            )?;
+        }
+        for edge in conv_graph.edge_indices() {
+            let (from, to) = conv_graph
+                .edge_endpoints(edge)
+                .expect("Internal error: no edge endpoints");
+            let rule = &conv_graph[edge];
+            let src_name = if rule.src_id.is_none() {
+                "generated".to_string()
+            } else {
+                (self.src_name)(rule.src_id)
+            };
+            writeln!(
+                f,
+                "    n{} -> n{} [label=\"{}\\l({:?}: {})\\l\"];",
+                from.index(),
+                to.index(),
+                escape(&rule.code_template),
+                rule.precedence,
+                escape(&src_name),
+            )?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
 #[derive(Debug)]
 struct PossiblePath {
     path_len: usize,
-    new_edges: Vec<(RustType, RustType, TypeConvEdge)>,
+    new_edges: Vec<(RustTypeS, RustTypeS, TypeConvEdge)>,
 }
 
 impl PossiblePath {
@@ -213,7 +361,7 @@ impl PossiblePath {
 #[derive(Debug)]
 pub(crate) struct ForeignTypeInfo {
     pub name: SmolStr,
-    pub correspoding_rust_type: RustType,
+    pub correspoding_rust_type: RustTypeIdx,
 }
 
 impl AsRef<ForeignTypeInfo> for ForeignTypeInfo {
@@ -256,13 +404,7 @@ impl<'a> TypeGraphSnapshot<'a> {
                 .entry(ty_name.clone())
                 .or_insert_with(|| {
                     new_node = true;
-                    let idx = graph.add_node(Rc::new(RustTypeS::new_without_graph_idx(
-                        ty, ty_name, src_id,
-                    )));
-                    Rc::get_mut(&mut graph[idx])
-                        .expect("Internal error: can not modify Rc")
-                        .graph_idx = idx;
-                    idx
+                    add_rust_type_node(graph, RustTypeS::new_without_graph_idx(ty, ty_name, src_id))
                 })
         };
         if new_node {
@@ -271,7 +413,7 @@ impl<'a> TypeGraphSnapshot<'a> {
         idx
     }
 
-    fn find_type_by_name(&self, type_name: &str) -> Option<&RustType> {
+    fn find_type_by_name(&self, type_name: &str) -> Option<&RustTypeS> {
         self.rust_names_map
             .get(type_name)
             .map(|i| &self.conv_graph[*i])
@@ -306,6 +448,160 @@ impl TypeMap {
         self.conv_graph.node_count() == 0
     }
 
+    /// Remove rule `from` -> `to` and generic rules with the same types,
+    /// return false if there are no such rules
+    pub(crate) fn remove_conv_rules(&mut self, from: &Type, to: &Type) -> bool {
+        let from_name = normalize_ty_lifetimes(from);
+        let to_name = normalize_ty_lifetimes(to);
+        let mut removed = false;
+        if let (Some(from), Some(to)) = (
+            self.rust_names_map.get(&from_name),
+            self.rust_names_map.get(&to_name),
+        ) {
+            if let Some(edge) = self.conv_graph.find_edge(*from, *to) {
+                debug!("remove_conv_rules: remove {} -> {}", from_name, to_name);
+                self.conv_graph.remove_edge(edge);
+                self.conv_paths_cache.borrow_mut().clear();
+                removed = true;
+            }
+        }
+        let generic_rules_len = self.generic_edges.len();
+        self.generic_edges.retain(|rule| {
+            normalize_ty_lifetimes(&rule.from_ty) != from_name
+                || normalize_ty_lifetimes(&rule.to_ty) != to_name
+        });
+        if generic_rules_len != self.generic_edges.len() {
+            self.generic_instances_cache.clear();
+            removed = true;
+        }
+        removed
+    }
+
+    /// Merge conversation rules defined inside `foreigner_class!`,
+    /// they are consulted before global rules until `pop_class_scope`
+    pub(crate) fn push_class_scope(&mut self, class: &ForeignerClassInfo) -> Result<()> {
+        assert!(
+            self.class_scope.is_none(),
+            "Internal error: class scope already pushed"
+        );
+        if class.typemaps.is_empty() {
+            return Ok(());
+        }
+        let mut edges_before = FxHashMap::<(RustTypeIdx, RustTypeIdx), TypeConvEdge>::default();
+        for edge in self.conv_graph.edge_references() {
+            edges_before.insert((edge.source(), edge.target()), edge.weight().clone());
+        }
+        let ftypes_before = self.ftypes_storage.len();
+
+        for rule in &class.typemaps {
+            let mut rule = rule.clone();
+            if rule.contains_data_for_language_backend() {
+                return Err(DiagnosticError::new(
+                    class.src_id,
+                    class.span(),
+                    format!(
+                        "class {} has typemap with foreign code, C types or options, \
+                         only conversation rules allowed in class scope",
+                        class.name
+                    ),
+                ));
+            }
+            for f_rule in rule
+                .ftype_left_to_right
+                .iter()
+                .chain(rule.ftype_right_to_left.iter())
+            {
+                let f_ty = f_rule.left_right_ty.ftype_name();
+                if let Some(ftype) = self.ftypes_storage.find_ftype_by_name(&f_ty.name) {
+                    return Err(DiagnosticError::new(
+                        class.src_id,
+                        f_ty.sp,
+                        format!(
+                            "foreign type {} already defined, class scoped rule can not redefine it",
+                            f_ty.name
+                        ),
+                    )
+                    .add_span_note(self[ftype].src_id_span(), "defined here"));
+                }
+            }
+            // class scoped rules replace global rules for the same types
+            for r_rule in rule
+                .rtype_left_to_right
+                .iter_mut()
+                .chain(rule.rtype_right_to_left.iter_mut())
+            {
+                r_rule.force_override = true;
+            }
+            self.merge_conv_rule(class.src_id, rule)?;
+        }
+
+        let mut scope = TypeMapScope::default();
+        for edge in self.conv_graph.edge_references() {
+            let key = (edge.source(), edge.target());
+            match edges_before.remove(&key) {
+                Some(old_edge) => {
+                    if !Rc::ptr_eq(&old_edge.used, &edge.weight().used) {
+                        scope.replaced_edges.push((key.0, key.1, old_edge));
+                    }
+                }
+                None => scope.added_edges.push(key),
+            }
+        }
+        scope.ftypes = self
+            .ftypes_storage
+            .iter_enumerate()
+            .skip(ftypes_before)
+            .map(|(idx, _)| idx)
+            .collect();
+        debug!(
+            "push_class_scope: {}: new ftypes {}, new edges {}, replaced edges {}",
+            class.name,
+            scope.ftypes.len(),
+            scope.added_edges.len(),
+            scope.replaced_edges.len()
+        );
+        self.class_scope = Some(scope);
+        Ok(())
+    }
+
+    /// Revert changes made by `push_class_scope`
+    pub(crate) fn pop_class_scope(&mut self) {
+        let scope = match self.class_scope.take() {
+            Some(x) => x,
+            None => return,
+        };
+        self.conv_paths_cache.borrow_mut().clear();
+        let scope_edges = scope.added_edges.iter().cloned().chain(
+            scope
+                .replaced_edges
+                .iter()
+                .map(|(from, to, _)| (*from, *to)),
+        );
+        for (from, to) in scope_edges {
+            if let Some(edge) = self.conv_graph.find_edge(from, to) {
+                let edge = &self.conv_graph[edge];
+                if !edge.src_id.is_none() {
+                    self.class_scope_rules.push((
+                        (edge.src_id, edge.span),
+                        format!("conversation rule '{}' -> '{}'", self[from], self[to]),
+                        edge.used.clone(),
+                    ));
+                }
+            }
+        }
+        for (from, to) in scope.added_edges {
+            if let Some(edge) = self.conv_graph.find_edge(from, to) {
+                self.conv_graph.remove_edge(edge);
+            }
+        }
+        for (from, to, edge) in scope.replaced_edges {
+            self.conv_graph.update_edge(from, to, edge);
+        }
+        for ftype in scope.ftypes {
+            self.ftypes_storage.forget(ftype);
+        }
+    }
+
     pub(crate) fn take_utils_code(&mut self) -> Vec<syn::Item> {
         let mut ret = Vec::new();
         ret.append(&mut self.utils_code);
@@ -314,24 +610,10 @@ impl TypeMap {
 
     pub(crate) fn add_foreign(
         &mut self,
-        correspoding_rty: RustType,
+        correspoding_rty: RustTypeIdx,
         foreign_name: TypeName,
     ) -> Result<ForeignType> {
-        trace!("add_foreign: {} / {}", foreign_name, correspoding_rty);
-        self.ftypes_storage
-            .alloc_new(foreign_name, correspoding_rty.graph_idx)
-    }
-
-    pub(crate) fn add_foreign_rust_ty_idx(
-        &mut self,
-        foreign_name: TypeName,
-        correspoding_rty: NodeIndex,
-    ) -> Result<ForeignType> {
-        trace!(
-            "add_foreign_rust_ty_idx: {} / {}",
-            foreign_name,
-            self[correspoding_rty]
-        );
+        trace!("add_foreign: {} / {}", foreign_name, self[correspoding_rty]);
         self.ftypes_storage
             .alloc_new(foreign_name, correspoding_rty)
     }
@@ -350,13 +632,24 @@ impl TypeMap {
             };
             Some(ForeignTypeInfo {
                 name: ftype.name.typename.clone(),
-                correspoding_rust_type: self.conv_graph[ty_idx].clone(),
+                correspoding_rust_type: ty_idx,
             })
         } else {
             None
         }
     }
 
+    pub(crate) fn find_foreign_type_by_name(&self, foreign_name: &str) -> Option<ForeignType> {
+        self.ftypes_storage.find_ftype_by_name(foreign_name)
+    }
+
+    pub(crate) fn add_foreign_type_module(&mut self, ft: ForeignType, module: SmolStr) {
+        let modules = &mut self.ftypes_storage[ft].provides_by_module;
+        if !modules.contains(&module) {
+            modules.push(module);
+        }
+    }
+
     pub(crate) fn alloc_foreign_type(&mut self, ft: ForeignTypeS) -> Result<ForeignType> {
         self.ftypes_storage.add_new_ftype(ft)
     }
@@ -388,11 +681,11 @@ impl TypeMap {
     //TODO: deprecate and remove this method
     pub(crate) fn cache_rust_to_foreign_conv(
         &mut self,
-        from: &RustType,
+        from: RustTypeIdx,
         to: ForeignTypeInfo,
     ) -> Result<()> {
-        trace!("cache_rust_to_foreign_conv: {} / {}", to.name, from);
-        let to_id = to.correspoding_rust_type.graph_idx;
+        trace!("cache_rust_to_foreign_conv: {} / {}", to.name, self[from]);
+        let to_id = to.correspoding_rust_type;
         let ftype = self.ftypes_storage.alloc_new(
             TypeName::new(
                 to.name,
@@ -401,19 +694,23 @@ impl TypeMap {
             ),
             to_id,
         )?;
-        self.rust_to_foreign_cache
-            .insert(from.normalized_name.clone(), ftype);
+        self.rust_to_foreign_cache.insert(from, ftype);
         Ok(())
     }
 
-    pub(crate) fn is_ty_implements(&self, ty: &RustType, trait_name: &str) -> Option<RustType> {
-        if ty.implements.contains(trait_name) {
-            Some(ty.clone())
-        } else if let syn::Type::Reference(syn::TypeReference { ref elem, .. }) = ty.ty {
+    pub(crate) fn is_ty_implements(
+        &self,
+        ty: RustTypeIdx,
+        trait_name: &str,
+    ) -> Option<RustTypeIdx> {
+        let rty = &self.conv_graph[ty];
+        if rty.implements.contains(trait_name) {
+            Some(ty)
+        } else if let syn::Type::Reference(syn::TypeReference { ref elem, .. }) = rty.ty {
             let ty_name = normalize_ty_lifetimes(&*elem);
-            self.rust_names_map.get(ty_name).and_then(|idx| {
+            self.rust_names_map.get(&ty_name).and_then(|idx| {
                 if self.conv_graph[*idx].implements.contains(trait_name) {
-                    Some(self.conv_graph[*idx].clone())
+                    Some(*idx)
                 } else {
                     None
                 }
@@ -425,14 +722,15 @@ impl TypeMap {
 
     pub(crate) fn find_foreigner_class_with_such_self_type(
         &self,
-        may_be_self_ty: &RustType,
+        may_be_self_ty: RustTypeIdx,
         if_ref_search_reftype: bool,
     ) -> Option<&ForeignerClassInfo> {
+        let may_be_self_ty = &self.conv_graph[may_be_self_ty];
         let type_name = match may_be_self_ty.ty {
             syn::Type::Reference(syn::TypeReference { ref elem, .. }) if if_ref_search_reftype => {
                 normalize_ty_lifetimes(&*elem)
             }
-            _ => may_be_self_ty.normalized_name.as_str(),
+            _ => may_be_self_ty.normalized_name.clone(),
         };
 
         trace!("find self type: possible name {}", type_name);
@@ -445,8 +743,8 @@ impl TypeMap {
                         DisplayToTokens(&fc.self_type_as_ty())
                     )
                 });
-            trace!("self_type {}", self_rust_ty);
-            if self_rust_ty.normalized_name == type_name {
+            trace!("self_type {}", self[self_rust_ty]);
+            if self[self_rust_ty].normalized_name == type_name {
                 return Some(fc);
             }
         }
@@ -463,7 +761,189 @@ impl TypeMap {
             "TypesConvMap::add_conversation_rule '{}' -> '{}': {:?}",
             self[from], self[to], rule
         );
+        self.update_conv_edge(from, to, rule);
+    }
+
+    /// Add new edge or replace existing one, if new edge has not lower precedence
+    /// or marked as forced override
+    pub(in crate::typemap) fn update_conv_edge(
+        &mut self,
+        from: RustTypeIdx,
+        to: RustTypeIdx,
+        rule: TypeConvEdge,
+    ) {
+        if let Some(existing_edge) = self.conv_graph.find_edge(from, to) {
+            let existing = &self.conv_graph[existing_edge];
+            if !rule.force_override && existing.precedence > rule.precedence {
+                debug!(
+                    "update_conv_edge: ignore {:?} rule for {} -> {}, there is {:?} rule",
+                    rule.precedence, self[from], self[to], existing.precedence
+                );
+                return;
+            }
+            if rule.force_override {
+                info!(
+                    "update_conv_edge: forced override of {:?} rule for {} -> {}",
+                    existing.precedence, self[from], self[to],
+                );
+            } else if existing.src_id != rule.src_id && !rule.src_id.is_none() {
+                self.warnings.print(
+                    DiagnosticCode::OverriddenConvRule,
+                    format!(
+                        "{:?} conversation rule for '{}' -> '{}' overrides {:?} rule, \
+ mark it with force_override if it is intended",
+                        rule.precedence, self[from], self[to], existing.precedence
+                    ),
+                );
+            } else {
+                info!(
+                    "update_conv_edge: replace {:?} with new conversation rule {:?}, for {} -> {}",
+                    existing, rule, self[from], self[to],
+                );
+            }
+        }
         self.conv_graph.update_edge(from, to, rule);
+        self.conv_paths_cache.borrow_mut().clear();
+    }
+
+    /// Conversation graph in Graphviz DOT format, `src_name` used
+    /// to get name of source code where rule was defined
+    pub(crate) fn conv_graph_as_dot<F>(&self, src_name: F) -> DotTypesConvGraph<'_, F>
+    where
+        F: Fn(SourceId) -> String,
+    {
+        DotTypesConvGraph {
+            conv_graph: &self.conv_graph,
+            src_name,
+        }
+    }
+
+    pub(crate) fn set_target_cfg(&mut self, target_cfg: TargetCfg) {
+        self.target_cfg = target_cfg;
+    }
+
+    pub(crate) fn set_rule_precedence(&mut self, src_id: SourceId, precedence: RulePrecedence) {
+        self.src_precedence.insert(src_id, precedence);
+    }
+
+    /// Warnings printed during work with types map,
+    /// and by generator, that can be turned off
+    pub(crate) fn warnings(&self) -> &Warnings {
+        &self.warnings
+    }
+
+    pub(crate) fn warnings_mut(&mut self) -> &mut Warnings {
+        &mut self.warnings
+    }
+
+    pub(crate) fn strict_conversions(&self) -> bool {
+        self.strict_conversions
+    }
+
+    pub(crate) fn set_strict_conversions(&mut self, strict: bool) {
+        self.strict_conversions = strict;
+    }
+
+    pub(in crate::typemap) fn new_rule_edge(
+        &self,
+        (src_id, span): SourceIdSpan,
+        code_template: String,
+        force_override: bool,
+        cost: u32,
+    ) -> TypeConvEdge {
+        TypeConvEdge {
+            src_id,
+            precedence: self.rule_precedence(src_id),
+            force_override,
+            cost,
+            span,
+            ..TypeConvEdge::new(code_template, None)
+        }
+    }
+
+    /// How many times rules, that report error of conversation
+    /// to foreign language, were used in generated code so far
+    pub(crate) fn fallible_conversations(&self) -> usize {
+        self.fallible_conversations
+    }
+
+    /// Rules from merged types maps and user's code, that were not used
+    /// to generate code, with description of each rule.
+    /// Built-in rules and rules generated for classes are not reported
+    pub(crate) fn unused_rules(&self) -> Vec<(SourceIdSpan, String)> {
+        let mut ret = vec![];
+        // instances of generic rules and copies of rules made for class scopes
+        // share flag of usage with their origin, so reported only once
+        let mut reported: FxHashSet<*const Cell<bool>> = self
+            .generic_edges
+            .iter()
+            .map(|rule| Rc::as_ptr(&rule.used))
+            .collect();
+        for edge_idx in self.conv_graph.edge_indices() {
+            let edge = &self.conv_graph[edge_idx];
+            if edge.used.get()
+                || edge.src_id.is_none()
+                || self.rule_precedence(edge.src_id) == RulePrecedence::BuiltIn
+                || !reported.insert(Rc::as_ptr(&edge.used))
+            {
+                continue;
+            }
+            let (from, to) = self
+                .conv_graph
+                .edge_endpoints(edge_idx)
+                .expect("Internal error: no edge");
+            ret.push((
+                (edge.src_id, edge.span),
+                format!(
+                    "conversation rule '{}' -> '{}'",
+                    self.conv_graph[from], self.conv_graph[to]
+                ),
+            ));
+        }
+        // class is generated in several passes, each with own copy of its rules
+        let mut scope_rules = Vec::<(SourceIdSpan, &str, bool)>::new();
+        let mut scope_rules_idx = FxHashMap::<(SourceId, usize, usize, &str), usize>::default();
+        for (sp, descr, used) in &self.class_scope_rules {
+            let start = sp.1.start();
+            let key = (sp.0, start.line, start.column, descr.as_str());
+            match scope_rules_idx.get(&key) {
+                Some(idx) => scope_rules[*idx].2 |= used.get(),
+                None => {
+                    scope_rules_idx.insert(key, scope_rules.len());
+                    scope_rules.push((*sp, descr, used.get()));
+                }
+            }
+        }
+        ret.extend(
+            scope_rules
+                .into_iter()
+                .filter(|(_, _, used)| !used)
+                .map(|(sp, descr, _)| (sp, descr.to_string())),
+        );
+        for rule in &self.generic_edges {
+            if rule.used.get()
+                || rule.src_id.is_none()
+                || self.rule_precedence(rule.src_id) == RulePrecedence::BuiltIn
+            {
+                continue;
+            }
+            ret.push((
+                (rule.src_id, rule.from_ty.span()),
+                format!(
+                    "generic conversation rule '{}' -> '{}'",
+                    DisplayToTokens(&rule.from_ty),
+                    DisplayToTokens(&rule.to_ty)
+                ),
+            ));
+        }
+        ret
+    }
+
+    pub(crate) fn rule_precedence(&self, src_id: SourceId) -> RulePrecedence {
+        self.src_precedence
+            .get(&src_id)
+            .cloned()
+            .unwrap_or(RulePrecedence::User)
     }
 
     pub(crate) fn register_exported_enum(&mut self, enum_info: &ForeignEnumInfo) {
@@ -471,8 +951,9 @@ impl TypeMap {
             .insert(enum_info.name.to_string().into(), enum_info.clone());
     }
 
-    pub(crate) fn is_this_exported_enum(&self, ty: &RustType) -> Option<&ForeignEnumInfo> {
-        self.exported_enums.get(&ty.normalized_name)
+    pub(crate) fn is_this_exported_enum(&self, ty: RustTypeIdx) -> Option<&ForeignEnumInfo> {
+        self.exported_enums
+            .get(&self.conv_graph[ty].normalized_name)
     }
 
     pub(crate) fn is_generated_foreign_type(&self, foreign_name: &str) -> bool {
@@ -495,12 +976,105 @@ impl TypeMap {
             Err(_err) => {
                 debug!("convert_rust_types: no path, trying to build it");
                 self.build_path_if_possible(from, to, build_for_sp);
-                self.find_path(from, to, build_for_sp)?
+                self.find_path(from, to, build_for_sp).map_err(|mut err| {
+                    self.add_no_path_hints(&mut err, from, to, build_for_sp);
+                    err
+                })?
             }
         };
         Ok(path)
     }
 
+    /// Explain why there is no path `from` -> `to`: which types
+    /// are near to both ends, and which generic rules almost matched
+    fn add_no_path_hints(
+        &self,
+        err: &mut DiagnosticError,
+        from: RustTypeIdx,
+        to: RustTypeIdx,
+        build_for_sp: SourceIdSpan,
+    ) {
+        const MAX_HINT_TYPES: usize = 5;
+        let nearest = |start: RustTypeIdx, dir: petgraph::Direction| {
+            let mut visited = FxHashSet::default();
+            visited.insert(start);
+            let mut ret = Vec::new();
+            let mut cur_step = vec![start];
+            while !cur_step.is_empty() && ret.len() < MAX_HINT_TYPES {
+                let mut next_step = Vec::new();
+                for node in cur_step {
+                    for neighbor in self.conv_graph.neighbors_directed(node, dir) {
+                        if visited.insert(neighbor) {
+                            ret.push(neighbor);
+                            next_step.push(neighbor);
+                        }
+                    }
+                }
+                cur_step = next_step;
+            }
+            ret.truncate(MAX_HINT_TYPES);
+            ret
+        };
+        let list_types = |types: &[RustTypeIdx]| {
+            types
+                .iter()
+                .map(|idx| format!("'{}'", self[*idx]))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        let reachable_from = nearest(from, petgraph::Outgoing);
+        if reachable_from.is_empty() {
+            err.span_note(
+                build_for_sp,
+                format!("there are no rules to convert '{}' to any type", self[from]),
+            );
+        } else {
+            err.span_note(
+                build_for_sp,
+                format!(
+                    "'{}' can be converted to: {}",
+                    self[from],
+                    list_types(&reachable_from)
+                ),
+            );
+        }
+
+        let reach_to = nearest(to, petgraph::Incoming);
+        if reach_to.is_empty() {
+            err.span_note(
+                build_for_sp,
+                format!("there are no rules to convert any type to '{}'", self[to]),
+            );
+        } else {
+            err.span_note(
+                build_for_sp,
+                format!(
+                    "'{}' can be obtained from: {}",
+                    self[to],
+                    list_types(&reach_to)
+                ),
+            );
+        }
+
+        for edge in &self.generic_edges {
+            if let Some(reason) = edge.failed_trait_bound(&self[from], |name| {
+                self.rust_names_map.get(name).map(|i| &self.conv_graph[*i])
+            }) {
+                err.span_note(
+                    build_for_sp,
+                    format!(
+                        "generic rule '{}' -> '{}' can be applied to '{}', but {}",
+                        DisplayToTokens(&edge.from_ty),
+                        DisplayToTokens(&edge.to_ty),
+                        self[from],
+                        reason
+                    ),
+                );
+            }
+        }
+    }
+
     pub(crate) fn convert_rust_types(
         &mut self,
         from: RustTypeIdx,
@@ -509,27 +1083,83 @@ impl TypeMap {
         function_ret_type: &str,
         build_for_sp: SourceIdSpan,
     ) -> Result<(Vec<TokenStream>, String)> {
-        let path = self.find_or_build_path(from, to, build_for_sp)?;
-        let mut ret_code = String::new();
         let mut code_deps = Vec::<TokenStream>::new();
+        let mut ret_code = String::new();
+        self.append_conversation_code(
+            from,
+            to,
+            var_name,
+            function_ret_type,
+            build_for_sp,
+            (&mut code_deps, &mut ret_code),
+        )?;
+        Ok((code_deps, ret_code))
+    }
 
-        for edge in path {
+    /// The same as `convert_rust_types`, but dependencies and code
+    /// are appended to the given buffers, to not allocate new ones
+    /// for each argument of method
+    pub(crate) fn append_conversation_code(
+        &mut self,
+        from: RustTypeIdx,
+        to: RustTypeIdx,
+        var_name: &str,
+        function_ret_type: &str,
+        build_for_sp: SourceIdSpan,
+        (code_deps, code): (&mut Vec<TokenStream>, &mut String),
+    ) -> Result<()> {
+        let path = self.find_or_build_path(from, to, build_for_sp)?;
+        if self.strict_conversions {
+            self.check_no_lossy_conversations(&path, build_for_sp)?;
+        }
+        let mut tmp_name = String::with_capacity(var_name.len() + 6);
+        for (step, edge) in path.into_iter().enumerate() {
             let (_, target) = self.conv_graph.edge_endpoints(edge).unwrap();
-            let target_typename: SmolStr = self.conv_graph[target].typename().into();
-            let edge = &mut self.conv_graph[edge];
+            let target_typename = self.conv_graph[target].typename();
+            let edge = &self.conv_graph[edge];
+            edge.used.set(true);
+            if edge.fallible {
+                self.fallible_conversations += 1;
+            }
             if let Some(dep) = edge.dependency.borrow_mut().take() {
                 code_deps.push(dep);
             }
-            let code = apply_code_template(
+            tmp_name.clear();
+            write!(&mut tmp_name, "{}_tmp{}", var_name, step).expect("mem I/O failed");
+            apply_code_template(
+                code,
                 &edge.code_template,
                 var_name,
                 var_name,
-                &target_typename,
+                target_typename,
                 function_ret_type,
+                &tmp_name,
             );
-            ret_code.push_str(&code);
         }
-        Ok((code_deps, ret_code))
+        Ok(())
+    }
+
+    fn check_no_lossy_conversations(
+        &self,
+        path: &[EdgeIndex<TypeGraphIdx>],
+        build_for_sp: SourceIdSpan,
+    ) -> Result<()> {
+        for edge_idx in path {
+            let edge = &self.conv_graph[*edge_idx];
+            if let Some(ref why) = edge.lossy {
+                let (from, to) = self.conv_graph.edge_endpoints(*edge_idx).unwrap();
+                return Err(DiagnosticError::new2(
+                    build_for_sp,
+                    format!(
+                        "conversation from {} to {} may lose data ({}), \
+                         add explicit rule for it via foreign_typemap!",
+                        self[from], self[to], why
+                    ),
+                )
+                .with_code(DiagnosticCode::LossyConversion));
+            }
+        }
+        Ok(())
     }
 
     fn find_path(
@@ -542,7 +1172,24 @@ impl TypeMap {
         if from == to {
             return Ok(vec![]);
         }
-        find_conversation_path(&self.conv_graph, from, to, build_for_sp)
+        self.find_cheapest_path(from, to)
+            .ok_or_else(|| no_conversation_path_error(&self.conv_graph, from, to, build_for_sp))
+    }
+
+    /// `find_cheapest_path` with memoization of result
+    fn find_cheapest_path(
+        &self,
+        from: RustTypeIdx,
+        to: RustTypeIdx,
+    ) -> Option<Vec<EdgeIndex<TypeGraphIdx>>> {
+        if let Some(path) = self.conv_paths_cache.borrow().get(&(from, to)) {
+            return path.clone();
+        }
+        let path = find_cheapest_path(&self.conv_graph, from, to);
+        self.conv_paths_cache
+            .borrow_mut()
+            .insert((from, to), path.clone());
+        path
     }
 
     fn build_path_if_possible(
@@ -563,7 +1210,7 @@ impl TypeMap {
             build_for_sp,
             &mut self.conv_graph,
             &self.rust_names_map,
-            &self.generic_edges,
+            (&self.generic_edges, &mut self.generic_instances_cache),
             MAX_TRY_BUILD_PATH_STEPS,
         ) {
             merge_path_to_conv_map(path, self);
@@ -575,15 +1222,21 @@ impl TypeMap {
         F: Fn(&TypeMap, &ForeignerClassInfo) -> Option<Type>,
     >(
         &mut self,
-        rust_ty: &RustType,
+        rust_ty: RustTypeIdx,
         direction: petgraph::Direction,
         build_for_sp: SourceIdSpan,
         calc_this_type_for_method: F,
     ) -> Option<ForeignType> {
-        debug!("map foreign: {} {:?}", rust_ty, direction);
+        debug!("map foreign: {} {:?}", self[rust_ty], direction);
+
+        if let Some(ftype) =
+            self.map_through_class_scope_conversation(rust_ty, direction, build_for_sp)
+        {
+            return Some(ftype);
+        }
 
         if direction == petgraph::Direction::Outgoing {
-            if let Some(ftype) = self.rust_to_foreign_cache.get(&rust_ty.normalized_name) {
+            if let Some(ftype) = self.rust_to_foreign_cache.get(&rust_ty) {
                 let fts = &self.ftypes_storage[*ftype];
                 if fts.into_from_rust.is_some() {
                     return Some(*ftype);
@@ -592,26 +1245,21 @@ impl TypeMap {
         }
 
         {
-            debug!(
-                "map foreign: graph node {:?}",
-                self.conv_graph[rust_ty.graph_idx]
-            );
-            let find_path = |from, to| {
-                find_conversation_path(&self.conv_graph, from, to, invalid_src_id_span()).ok()
-            };
+            debug!("map foreign: graph node {:?}", self.conv_graph[rust_ty]);
+            let find_path = |from, to| self.find_cheapest_path(from, to);
             let mut min_path: Option<(usize, RustTypeIdx, ForeignType)> = None;
             for (ftype_idx, ftype) in self.ftypes_storage.iter_enumerate() {
                 let (related_rty_idx, path) = match direction {
                     petgraph::Direction::Outgoing => {
                         if let Some(rule) = ftype.into_from_rust.as_ref() {
-                            (rule.rust_ty, find_path(rust_ty.graph_idx, rule.rust_ty))
+                            (rule.rust_ty, find_path(rust_ty, rule.rust_ty))
                         } else {
                             continue;
                         }
                     }
                     petgraph::Direction::Incoming => {
                         if let Some(rule) = ftype.from_into_rust.as_ref() {
-                            (rule.rust_ty, find_path(rule.rust_ty, rust_ty.graph_idx))
+                            (rule.rust_ty, find_path(rule.rust_ty, rust_ty))
                         } else {
                             continue;
                         }
@@ -639,7 +1287,10 @@ impl TypeMap {
             if let Some((path_len, rust_type_idx, ftype)) = min_path {
                 debug!(
                     "map foreign: we found min path ({}) {} <-> {} ({})",
-                    path_len, rust_ty, self.conv_graph[rust_type_idx], self[ftype].name
+                    path_len,
+                    self.conv_graph[rust_ty],
+                    self.conv_graph[rust_type_idx],
+                    self[ftype].name
                 );
 
                 return Some(ftype);
@@ -648,7 +1299,7 @@ impl TypeMap {
 
         debug!(
             "map foreign: No paths exists, may be we can create one for '{}' {:?}?",
-            rust_ty, direction
+            self.conv_graph[rust_ty], direction
         );
 
         let mut new_foreign_types = FxHashSet::default();
@@ -676,9 +1327,12 @@ impl TypeMap {
                                     TypeName::new(foreign_name, (class.src_id, class.name.span())),
                                 ));
                             } else {
-                                println!(
-                                    "warning=No foreign_class for type '{}'",
-                                    rust_ty.normalized_name
+                                self.warnings.print(
+                                    DiagnosticCode::NoForeignClass,
+                                    format!(
+                                        "No foreign_class for type '{}'",
+                                        rust_ty.normalized_name
+                                    ),
                                 );
                             }
                         }
@@ -703,17 +1357,17 @@ impl TypeMap {
             if let Some(r) = conv_rule {
                 warn!(
                     "attempt to rewrite rule {}/{} with {}/{}",
-                    fname, self.conv_graph[r.rust_ty], fname, rust_ty
+                    fname, self.conv_graph[r.rust_ty], fname, self.conv_graph[rust_ty]
                 );
                 continue;
             }
             *conv_rule = Some(ForeignConversationRule {
-                rust_ty: rust_ty.graph_idx,
+                rust_ty,
                 intermediate: None,
             });
         }
 
-        let from: RustType = rust_ty.clone();
+        let from: RustTypeIdx = rust_ty;
         let mut possible_paths =
             Vec::<(PossiblePath, ForeignType, RustTypeIdx, Option<RustTypeIdx>)>::new();
         for max_steps in 1..=MAX_TRY_BUILD_PATH_STEPS {
@@ -729,8 +1383,8 @@ impl TypeMap {
                 };
                 let other = rule.rust_ty;
                 let (from, to) = match direction {
-                    petgraph::Direction::Outgoing => (from.to_idx(), other),
-                    petgraph::Direction::Incoming => (other, from.to_idx()),
+                    petgraph::Direction::Outgoing => (from, other),
+                    petgraph::Direction::Incoming => (other, from),
                 };
                 let path = try_build_path(
                     from,
@@ -738,7 +1392,7 @@ impl TypeMap {
                     build_for_sp,
                     &mut self.conv_graph,
                     &self.rust_names_map,
-                    &self.generic_edges,
+                    (&self.generic_edges, &mut self.generic_instances_cache),
                     max_steps,
                 );
 
@@ -771,10 +1425,13 @@ impl TypeMap {
                     addon_path_len = match addon_path {
                         Ok(addon_path) => addon_path.len(),
                         Err(_err) => {
-                            println!(
-                                "warning=can not build path between foreign type
+                            self.warnings.print(
+                                DiagnosticCode::NoConvPathToIntermediate,
+                                format!(
+                                    "can not build path between foreign type
  '{}' / '{}' and it's intermidiate '{}'",
-                                self[*ftype_idx].name, self[*other], self[*inter_ty]
+                                    self[*ftype_idx].name, self[*other], self[*inter_ty]
+                                ),
                             );
                             0_usize
                         }
@@ -787,7 +1444,10 @@ impl TypeMap {
                 merge_path_to_conv_map(pp, self);
                 debug!(
                     "map foreign: we found min path ({}) '{}' <-> '{}' ({})",
-                    path_len, rust_ty, self.conv_graph[rtype_idx], self[ftype].name
+                    path_len,
+                    self.conv_graph[rust_ty],
+                    self.conv_graph[rtype_idx],
+                    self[ftype].name
                 );
                 ftype
             });
@@ -801,6 +1461,74 @@ impl TypeMap {
         ret
     }
 
+    /// Search foreign type among types defined by class scoped rules
+    fn map_through_class_scope_conversation(
+        &mut self,
+        rust_ty: RustTypeIdx,
+        direction: petgraph::Direction,
+        build_for_sp: SourceIdSpan,
+    ) -> Option<ForeignType> {
+        let scope_ftypes = match self.class_scope {
+            Some(ref scope) if !scope.ftypes.is_empty() => scope.ftypes.clone(),
+            _ => return None,
+        };
+        let mut related_rust_types = Vec::with_capacity(scope_ftypes.len());
+        for ftype_idx in scope_ftypes {
+            let rule = match direction {
+                petgraph::Direction::Outgoing => self[ftype_idx].into_from_rust.as_ref(),
+                petgraph::Direction::Incoming => self[ftype_idx].from_into_rust.as_ref(),
+            };
+            if let Some(rule) = rule {
+                let (from, to) = match direction {
+                    petgraph::Direction::Outgoing => (rust_ty, rule.rust_ty),
+                    petgraph::Direction::Incoming => (rule.rust_ty, rust_ty),
+                };
+                related_rust_types.push((ftype_idx, from, to));
+            }
+        }
+
+        let mut min_path: Option<(usize, ForeignType)> = None;
+        for (ftype_idx, from, to) in &related_rust_types {
+            if let Ok(path) = self.find_path(*from, *to, build_for_sp) {
+                if min_path.map(|x| path.len() < x.0).unwrap_or(true) {
+                    min_path = Some((path.len(), *ftype_idx));
+                }
+            }
+        }
+        if let Some((path_len, ftype)) = min_path {
+            debug!(
+                "map foreign: class scope: we found min path ({}) {} <-> {}",
+                path_len, self[rust_ty], self[ftype].name
+            );
+            return Some(ftype);
+        }
+
+        let mut min_path: Option<(PossiblePath, ForeignType)> = None;
+        for (ftype_idx, from, to) in related_rust_types {
+            if let Some(path) = try_build_path(
+                from,
+                to,
+                build_for_sp,
+                &mut self.conv_graph,
+                &self.rust_names_map,
+                (&self.generic_edges, &mut self.generic_instances_cache),
+                MAX_TRY_BUILD_PATH_STEPS,
+            ) {
+                if min_path
+                    .as_ref()
+                    .map(|x| path.len() < x.0.len())
+                    .unwrap_or(true)
+                {
+                    min_path = Some((path, ftype_idx));
+                }
+            }
+        }
+        min_path.map(|(path, ftype)| {
+            merge_path_to_conv_map(path, self);
+            ftype
+        })
+    }
+
     pub(crate) fn find_foreigner_class_with_such_this_type<
         F: Fn(&TypeMap, &ForeignerClassInfo) -> Option<Type>,
     >(
@@ -820,40 +1548,40 @@ impl TypeMap {
         None
     }
 
-    pub(crate) fn register_foreigner_class(&mut self, class: &ForeignerClassInfo) {
-        self.foreign_classes.push(class.clone());
+    pub(crate) fn register_foreigner_class(&mut self, class: &Rc<ForeignerClassInfo>) {
+        self.foreign_classes.push(Rc::clone(class));
     }
 
+    /// `key` should be equal to `normalized_name` of created type,
+    /// names map and type share the same string
     fn add_node<F: FnOnce() -> RustTypeS>(
         &mut self,
-        key: SmolStr,
+        key: &str,
         init_without_graph_idx: F,
     ) -> NodeIndex {
-        let rust_names_map = &mut self.rust_names_map;
-        let conv_graph = &mut self.conv_graph;
-        *rust_names_map.entry(key).or_insert_with(|| {
-            let idx = conv_graph.add_node(Rc::new(init_without_graph_idx()));
-            Rc::get_mut(&mut conv_graph[idx])
-                .expect("Internal error: can not modify Rc")
-                .graph_idx = idx;
-            idx
-        })
+        if let Some(idx) = self.rust_names_map.get(key) {
+            return *idx;
+        }
+        let rty = init_without_graph_idx();
+        debug_assert_eq!(key, rty.normalized_name);
+        let key = rty.normalized_name.clone();
+        let idx = add_rust_type_node(&mut self.conv_graph, rty);
+        self.rust_names_map.insert(key, idx);
+        idx
     }
 
-    pub(crate) fn find_or_alloc_rust_type(&mut self, ty: &Type, src_id: SourceId) -> RustType {
+    pub(crate) fn find_or_alloc_rust_type(&mut self, ty: &Type, src_id: SourceId) -> RustTypeIdx {
         let name = normalize_ty_lifetimes(ty);
-        let idx = self.add_node(name.into(), || {
-            RustTypeS::new_without_graph_idx(ty.clone(), name, src_id)
-        });
-        self.conv_graph[idx].clone()
+        self.add_node(&name, || {
+            RustTypeS::new_without_graph_idx(ty.clone(), name.clone(), src_id)
+        })
     }
 
-    pub(crate) fn find_or_alloc_rust_type_no_src_id(&mut self, ty: &Type) -> RustType {
+    pub(crate) fn find_or_alloc_rust_type_no_src_id(&mut self, ty: &Type) -> RustTypeIdx {
         let name = normalize_ty_lifetimes(ty);
-        let idx = self.add_node(name.into(), || {
-            RustTypeS::new_without_graph_idx(ty.clone(), name, SourceId::none())
-        });
-        self.conv_graph[idx].clone()
+        self.add_node(&name, || {
+            RustTypeS::new_without_graph_idx(ty.clone(), name.clone(), SourceId::none())
+        })
     }
 
     pub(crate) fn find_or_alloc_rust_type_that_implements(
@@ -861,12 +1589,12 @@ impl TypeMap {
         ty: &Type,
         trait_name: &str,
         src_id: SourceId,
-    ) -> RustType {
+    ) -> RustTypeIdx {
         let name = normalize_ty_lifetimes(ty);
-        let idx = self.add_node(name.into(), || {
-            RustTypeS::new_without_graph_idx(ty.clone(), name, src_id).implements(trait_name)
-        });
-        self.conv_graph[idx].clone()
+        self.add_node(&name, || {
+            RustTypeS::new_without_graph_idx(ty.clone(), name.clone(), src_id)
+                .implements(trait_name)
+        })
     }
 
     pub(crate) fn find_or_alloc_rust_type_with_suffix(
@@ -874,13 +1602,11 @@ impl TypeMap {
         ty: &Type,
         suffix: &str,
         src_id: SourceId,
-    ) -> RustType {
-        let name: SmolStr =
-            RustTypeS::make_unique_typename(normalize_ty_lifetimes(ty), suffix).into();
-        let idx = self.add_node(name.clone(), || {
-            RustTypeS::new_without_graph_idx(ty.clone(), name, src_id)
-        });
-        self.conv_graph[idx].clone()
+    ) -> RustTypeIdx {
+        let name = RustTypeS::make_unique_typename(&normalize_ty_lifetimes(ty), suffix);
+        self.add_node(&name, || {
+            RustTypeS::new_without_graph_idx(ty.clone(), name.as_str(), src_id)
+        })
     }
 
     pub(crate) fn find_or_alloc_rust_type_with_may_be_suffix(
@@ -888,7 +1614,7 @@ impl TypeMap {
         ty: &Type,
         suffix: Option<String>,
         src_id: SourceId,
-    ) -> RustType {
+    ) -> RustTypeIdx {
         if let Some(suffix) = suffix {
             self.find_or_alloc_rust_type_with_suffix(ty, &suffix, src_id)
         } else {
@@ -897,7 +1623,7 @@ impl TypeMap {
     }
 
     /// # Panics
-    pub(crate) fn ty_to_rust_type(&self, ty: &Type) -> RustType {
+    pub(crate) fn ty_to_rust_type(&self, ty: &Type) -> RustTypeIdx {
         self.ty_to_rust_type_checked(ty).unwrap_or_else(|| {
             panic!(
                 "Internal Error: type '{}' unknown (ty_to_rust_type)",
@@ -906,11 +1632,9 @@ impl TypeMap {
         })
     }
 
-    pub(crate) fn ty_to_rust_type_checked(&self, ty: &Type) -> Option<RustType> {
+    pub(crate) fn ty_to_rust_type_checked(&self, ty: &Type) -> Option<RustTypeIdx> {
         let name = normalize_ty_lifetimes(ty);
-        self.rust_names_map
-            .get(name)
-            .map(|idx| self.conv_graph[*idx].clone())
+        self.rust_names_map.get(&name).copied()
     }
 
     pub(crate) fn take_not_merged_data(&mut self) -> Vec<TypeMapConvRuleInfo> {
@@ -926,45 +1650,104 @@ impl ops::Index<ForeignType> for TypeMap {
 }
 
 impl ops::Index<RustTypeIdx> for TypeMap {
-    type Output = Rc<RustTypeS>;
+    type Output = RustTypeS;
     fn index(&self, idx: RustTypeIdx) -> &Self::Output {
         &self.conv_graph[idx]
     }
 }
 
 pub(in crate::typemap) fn validate_code_template(sp: SourceIdSpan, code: &str) -> Result<()> {
-    if code.contains(TO_VAR_TEMPLATE)
-        && code.contains(FROM_VAR_TEMPLATE)
-        && code.contains(TO_VAR_TYPE_TEMPLATE)
+    if !code.contains(TO_VAR_TEMPLATE)
+        || !code.contains(FROM_VAR_TEMPLATE)
+        || !code.contains(TO_VAR_TYPE_TEMPLATE)
     {
-        Ok(())
-    } else {
-        Err(DiagnosticError::new(
+        return Err(DiagnosticError::new(
             sp.0,
             sp.1,
             format!(
                 "{} not contains one of {}, {}, {}",
                 code, TO_VAR_TEMPLATE, FROM_VAR_TEMPLATE, TO_VAR_TYPE_TEMPLATE
             ),
-        ))
+        ));
     }
+    if code.contains(TMP_VAR_TEMPLATE) && !is_tmp_var_declared(code) {
+        return Err(DiagnosticError::new(
+            sp.0,
+            sp.1,
+            format!(
+                "{} uses {}, but not declares it via let",
+                code, TMP_VAR_TEMPLATE
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// `let {tmp_var}` or `let mut {tmp_var}`, may be with type
+pub(in crate::typemap) fn is_tmp_var_declared(code: &str) -> bool {
+    code.split(';').any(|stmt| {
+        let mut words = stmt.split_whitespace();
+        match (words.next(), words.next(), words.next()) {
+            (Some("let"), Some("mut"), Some(name)) | (Some("let"), Some(name), _) => {
+                name.trim_end_matches(':') == TMP_VAR_TEMPLATE
+            }
+            _ => false,
+        }
+    })
 }
 
+/// Append `code_temlate` with substituted names to `out`,
+/// each statement on its own line
 fn apply_code_template(
+    out: &mut String,
     code_temlate: &str,
     to_name: &str,
     from_name: &str,
     to_typename: &str,
     func_ret_type: &str,
-) -> String {
-    let mut ret = String::new();
-    ret.push_str("    ");
-    ret.push_str(code_temlate);
-    ret.push('\n');
-    ret.replace(TO_VAR_TEMPLATE, to_name)
-        .replace(FROM_VAR_TEMPLATE, from_name)
-        .replace(TO_VAR_TYPE_TEMPLATE, to_typename)
-        .replace(FUNCTION_RETURN_TYPE_TEMPLATE, func_ret_type)
+    tmp_name: &str,
+) {
+    let substitutions = [
+        (TO_VAR_TEMPLATE, to_name),
+        (FROM_VAR_TEMPLATE, from_name),
+        (TO_VAR_TYPE_TEMPLATE, to_typename),
+        (FUNCTION_RETURN_TYPE_TEMPLATE, func_ret_type),
+        (TMP_VAR_TEMPLATE, tmp_name),
+    ];
+    let mut append_line = |mut line: &str| {
+        out.push_str("    ");
+        while let Some(pos) = line.find('{') {
+            out.push_str(&line[..pos]);
+            line = &line[pos..];
+            match substitutions
+                .iter()
+                .find(|(template, _)| line.starts_with(template))
+            {
+                Some((template, value)) => {
+                    out.push_str(value);
+                    line = &line[template.len()..];
+                }
+                None => {
+                    out.push('{');
+                    line = &line[1..];
+                }
+            }
+        }
+        out.push_str(line);
+        out.push('\n');
+    };
+    if code_temlate.contains('\n') {
+        // several statements on separate lines
+        for line in code_temlate
+            .lines()
+            .map(str::trim)
+            .filter(|x| !x.is_empty())
+        {
+            append_line(line);
+        }
+    } else {
+        append_line(code_temlate);
+    }
 }
 
 fn find_conversation_path(
@@ -979,45 +1762,89 @@ fn find_conversation_path(
         conv_graph[to]
     );
 
-    if let Some((_, nodes_path)) = petgraph::algo::astar(
-        conv_graph,
-        from,
-        |idx| idx == to,
-        |_| 1,
-        |idx| if idx != from { 1 } else { 0 },
-    ) {
-        let mut edges = Vec::with_capacity(nodes_path.len());
-        for (cur_node, next_node) in nodes_path.iter().zip(nodes_path.iter().skip(1)) {
-            edges.push(
-                conv_graph
-                    .find_edge(*cur_node, *next_node)
-                    .expect("Internal error: find_conversation_path no edge"),
-            );
+    find_cheapest_path(conv_graph, from, to)
+        .ok_or_else(|| no_conversation_path_error(conv_graph, from, to, build_for_sp))
+}
+
+fn no_conversation_path_error(
+    conv_graph: &TypesConvGraph,
+    from: RustTypeIdx,
+    to: RustTypeIdx,
+    build_for_sp: SourceIdSpan,
+) -> DiagnosticError {
+    let mut err = DiagnosticError::new2(
+        conv_graph[from].src_id_span(),
+        format!("Can not find conversation from type '{}'", conv_graph[from]),
+    );
+    err.span_note(
+        conv_graph[to].src_id_span(),
+        format!("to type '{}'", conv_graph[to]),
+    );
+    err.span_note(build_for_sp, "In this context");
+    err
+}
+
+/// Dijkstra's algorithm with total cost of rules as path weight.
+/// If there are several paths with the same cost, the path with less
+/// conversations wins, and then the path that was found first,
+/// since nodes are visited in order of their indexes this is deterministic.
+fn find_cheapest_path(
+    conv_graph: &TypesConvGraph,
+    from: RustTypeIdx,
+    to: RustTypeIdx,
+) -> Option<Vec<EdgeIndex<TypeGraphIdx>>> {
+    let mut best = FxHashMap::<RustTypeIdx, (u64, usize)>::default();
+    let mut came_from = FxHashMap::<RustTypeIdx, EdgeIndex<TypeGraphIdx>>::default();
+    let mut visited = FxHashSet::<RustTypeIdx>::default();
+    let mut queue = BinaryHeap::new();
+
+    best.insert(from, (0, 0));
+    queue.push(Reverse((0u64, 0usize, from)));
+    while let Some(Reverse((cost, steps, node))) = queue.pop() {
+        if node == to {
+            let mut edges = Vec::with_capacity(steps);
+            let mut cur = to;
+            while cur != from {
+                let edge = came_from[&cur];
+                edges.push(edge);
+                cur = conv_graph
+                    .edge_endpoints(edge)
+                    .expect("Internal error: find_cheapest_path no edge")
+                    .0;
+            }
+            edges.reverse();
+            return Some(edges);
+        }
+        if !visited.insert(node) {
+            continue;
+        }
+        for edge in conv_graph.edges(node) {
+            let next = edge.target();
+            if visited.contains(&next) {
+                continue;
+            }
+            let next_score = (cost + u64::from(edge.weight().cost), steps + 1);
+            let better = best.get(&next).map_or(true, |prev| next_score < *prev);
+            if better {
+                best.insert(next, next_score);
+                came_from.insert(next, edge.id());
+                queue.push(Reverse((next_score.0, next_score.1, next)));
+            }
         }
-        Ok(edges)
-    } else {
-        let mut err = DiagnosticError::new2(
-            conv_graph[from].src_id_span(),
-            format!("Can not find conversation from type '{}'", conv_graph[from]),
-        );
-        err.span_note(
-            conv_graph[to].src_id_span(),
-            format!("to type '{}'", conv_graph[to]),
-        );
-        err.span_note(build_for_sp, "In this context");
-        Err(err)
     }
+    None
 }
 
 fn merge_path_to_conv_map(path: PossiblePath, conv_map: &mut TypeMap) {
     let PossiblePath { new_edges, .. } = path;
 
     for (from, to, conv_rule) in new_edges {
-        let from_idx = conv_map.add_node(from.normalized_name.clone(), || (*from).clone());
-        let to_idx = conv_map.add_node(to.normalized_name.clone(), || (*to).clone());
+        let from_idx = conv_map.add_node(&from.normalized_name.clone(), || from);
+        let to_idx = conv_map.add_node(&to.normalized_name.clone(), || to);
         assert!(conv_map.conv_graph.find_edge(from_idx, to_idx).is_none());
         conv_map.conv_graph.add_edge(from_idx, to_idx, conv_rule);
     }
+    conv_map.conv_paths_cache.borrow_mut().clear();
 }
 
 fn try_build_path(
@@ -1026,14 +1853,13 @@ fn try_build_path(
     build_for_sp: SourceIdSpan,
     conv_graph: &mut TypesConvGraph,
     rust_names_map: &RustTypeNameToGraphIdx,
-    generic_edges: &[GenericTypeConv],
+    (generic_edges, generic_instances): (&[GenericTypeConv], &mut GenericInstancesCache),
     max_steps: usize,
 ) -> Option<PossiblePath> {
-    let goal_to = conv_graph[goal_to_idx].clone();
     debug!(
         "try_build_path: from {} to {}, ty names len {}, graph nodes {}, edges {}",
         conv_graph[start_from_idx],
-        goal_to,
+        conv_graph[goal_to_idx],
         rust_names_map.len(),
         conv_graph.node_count(),
         conv_graph.edge_count()
@@ -1050,7 +1876,6 @@ fn try_build_path(
             break;
         }
         if log_enabled!(log::Level::Debug) {
-            use std::fmt::Write;
             let mut step_types = String::new();
             for from_ty in &cur_step {
                 write!(step_types, "{} | ", ty_graph.conv_graph[*from_ty]).unwrap();
@@ -1058,26 +1883,41 @@ fn try_build_path(
             debug!("try_build_path: step({}): {}", step, step_types);
         }
         for from_ty in &cur_step {
-            let from: RustType = ty_graph.conv_graph[*from_ty].clone();
+            let from_name = ty_graph.conv_graph[*from_ty].normalized_name.clone();
             for neighbor in ty_graph
                 .conv_graph
                 .neighbors_directed(*from_ty, petgraph::Outgoing)
             {
                 next_step.insert(neighbor);
             }
-            for edge in generic_edges {
+            for (rule_idx, edge) in generic_edges.iter().enumerate() {
                 trace!(
                     "try_build_path: we check edge({:?} -> {:?}) for {}",
                     edge.from_ty,
                     edge.to_ty,
-                    from
+                    from_name
                 );
-                if let Some((to_ty, to_ty_name)) =
-                    edge.is_conv_possible(&from, Some(&goal_to), |name| {
-                        ty_graph.find_type_by_name(name)
-                    })
-                {
-                    if from.normalized_name == to_ty_name {
+                let key = (rule_idx, from_name.clone());
+                let instance = match generic_instances.get(&key) {
+                    Some(instance) => instance.as_ref().map(|(ty, ty_name)| {
+                        let mut ty = ty.clone();
+                        respan_ty(&mut ty, build_for_sp.1);
+                        (ty, ty_name.clone())
+                    }),
+                    None => {
+                        let (instance, reusable) = edge.instantiate(
+                            &ty_graph.conv_graph[*from_ty],
+                            Some(&ty_graph.conv_graph[goal_to_idx]),
+                            |name| ty_graph.find_type_by_name(name),
+                        );
+                        if reusable {
+                            generic_instances.insert(key, instance.clone());
+                        }
+                        instance
+                    }
+                };
+                if let Some((to_ty, to_ty_name)) = instance {
+                    if from_name == to_ty_name {
                         continue;
                     }
                     let to = ty_graph.node_for_ty(edge.src_id, (to_ty, to_ty_name));
@@ -1087,6 +1927,14 @@ fn try_build_path(
                         TypeConvEdge {
                             code_template: edge.code_template.clone(),
                             dependency: edge.dependency.clone(),
+                            src_id: edge.src_id,
+                            precedence: RulePrecedence::User,
+                            force_override: false,
+                            cost: edge.cost,
+                            span: edge.from_ty.span(),
+                            used: edge.used.clone(),
+                            lossy: None,
+                            fallible: false,
                         },
                     );
 
@@ -1132,7 +1980,10 @@ fn try_build_path(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{source_registry::SourceRegistry, types::SelfTypeDesc, SourceCode};
+    use crate::{
+        source_registry::SourceRegistry, types::SelfTypeDesc, JavaConfig, LanguageConfig,
+        SourceCode,
+    };
     use proc_macro2::Span;
 
     #[test]
@@ -1146,23 +1997,27 @@ mod tests {
         });
         types_map.merge(src_id, src_reg.src(src_id), 64).unwrap();
 
-        let foo_rt: RustType = types_map.find_or_alloc_rust_type_that_implements(
+        let foo_rt: RustTypeIdx = types_map.find_or_alloc_rust_type_that_implements(
             &parse_type! { Foo },
             "SwigForeignClass",
             SourceId::none(),
         );
-        types_map.register_foreigner_class(&ForeignerClassInfo {
+        types_map.register_foreigner_class(&Rc::new(ForeignerClassInfo {
             src_id: SourceId::none(),
             name: Ident::new("Foo", Span::call_site()),
             methods: vec![],
             self_desc: Some(SelfTypeDesc {
-                self_type: foo_rt.ty.clone(),
-                constructor_ret_type: foo_rt.ty.clone(),
+                self_type: types_map[foo_rt].ty.clone(),
+                constructor_ret_type: types_map[foo_rt].ty.clone(),
             }),
             foreigner_code: String::new(),
             doc_comments: vec![],
             copy_derived: false,
-        });
+            cpp_namespace: None,
+            single_threaded: false,
+            catch_panics: None,
+            typemaps: vec![],
+        }));
 
         let rc_refcell_foo_ty = types_map
             .find_or_alloc_rust_type(&parse_type! { &mut Rc<RefCell<Foo>> }, SourceId::none());
@@ -1177,8 +2032,8 @@ mod tests {
 "#,
             types_map
                 .convert_rust_types(
-                    rc_refcell_foo_ty.to_idx(),
-                    foo_ref_ty.to_idx(),
+                    rc_refcell_foo_ty,
+                    foo_ref_ty,
                     "a0",
                     "jlong",
                     invalid_src_id_span(),
@@ -1197,8 +2052,8 @@ mod tests {
 "#,
             types_map
                 .convert_rust_types(
-                    rc_refcell_foo_ty.to_idx(),
-                    foo_ref_ty.to_idx(),
+                    rc_refcell_foo_ty,
+                    foo_ref_ty,
                     "a0",
                     "jlong",
                     invalid_src_id_span(),
@@ -1212,7 +2067,7 @@ mod tests {
 
         let fti = types_map
             .map_through_conversation_to_foreign(
-                &vec_foo_ty,
+                vec_foo_ty,
                 petgraph::Direction::Outgoing,
                 invalid_src_id_span(),
                 |_, fc| {
@@ -1225,18 +2080,430 @@ mod tests {
         assert_eq!("Foo []", types_map[fti].name.as_str());
 
         assert!(try_build_path(
-            types_map
-                .find_or_alloc_rust_type(&parse_type! { Vec<i32> }, SourceId::none())
-                .to_idx(),
-            types_map
-                .find_or_alloc_rust_type(&parse_type! { jlong }, SourceId::none())
-                .to_idx(),
+            types_map.find_or_alloc_rust_type(&parse_type! { Vec<i32> }, SourceId::none()),
+            types_map.find_or_alloc_rust_type(&parse_type! { jlong }, SourceId::none()),
             invalid_src_id_span(),
             &mut types_map.conv_graph,
             &mut types_map.rust_names_map,
-            &types_map.generic_edges,
+            (
+                &types_map.generic_edges,
+                &mut types_map.generic_instances_cache
+            ),
             MAX_TRY_BUILD_PATH_STEPS,
         )
         .is_none());
     }
+
+    #[test]
+    fn test_conv_graph_as_dot() {
+        let mut types_map = TypeMap::default();
+        let mut src_reg = SourceRegistry::default();
+        let src_id = src_reg.register(SourceCode {
+            id_of_code: "test_conv_graph_as_dot".into(),
+            code:
+                r#"foreign_typemap!(($p:r_type) bool => i32 { $out = if $p { 1 } else { 0 } };);"#
+                    .into(),
+        });
+        types_map.set_rule_precedence(src_id, RulePrecedence::Merged);
+        types_map.merge(src_id, src_reg.src(src_id), 64).unwrap();
+        let dot = types_map
+            .conv_graph_as_dot(|src_id| src_reg.src_with_id(src_id).id_of_code.clone())
+            .to_string();
+        println!("dot: {}", dot);
+        assert!(dot.starts_with("digraph conversation_graph {"));
+        assert!(dot.contains(r#"n0 [label="bool"];"#));
+        assert!(dot.contains(r#"n1 [label="i32"];"#));
+        assert!(dot.contains(
+            r#"n0 -> n1 [label="let {to_var}: {to_var_type} = if {from_var} { 1 } else { 0 };\l(Merged: test_conv_graph_as_dot)\l"];"#
+        ));
+    }
+
+    #[test]
+    fn test_no_path_hints() {
+        let _ = env_logger::try_init();
+        let mut types_map = TypeMap::default();
+        let mut src_reg = SourceRegistry::default();
+        let src_id = src_reg.register(SourceCode {
+            id_of_code: "test_no_path_hints".into(),
+            code: include_str!("java_jni/jni-include.rs").into(),
+        });
+        types_map.merge(src_id, src_reg.src(src_id), 64).unwrap();
+
+        types_map.find_or_alloc_rust_type(&parse_type! { Bar }, SourceId::none());
+        let vec_bar =
+            types_map.find_or_alloc_rust_type(&parse_type! { Vec<Bar> }, SourceId::none());
+        let jobject_array =
+            types_map.find_or_alloc_rust_type(&parse_type! { jobjectArray }, SourceId::none());
+        let err = types_map
+            .convert_rust_types(vec_bar, jobject_array, "a0", "jlong", invalid_src_id_span())
+            .unwrap_err()
+            .to_string();
+        println!("err: {}", err);
+        assert!(err.contains("there are no rules to convert 'Vec < Bar >' to any type"));
+        assert!(err.contains(
+            "generic rule 'Vec < T >' -> 'jobjectArray' can be applied to 'Vec < Bar >', \
+             but type 'Bar' does not implement SwigForeignClass, required for T"
+        ));
+        assert!(!err.contains("generic rule 'T' ->"));
+    }
+
+    #[test]
+    fn test_cheapest_path() {
+        let _ = env_logger::try_init();
+        let mut types_map = TypeMap::default();
+        let mut src_reg = SourceRegistry::default();
+        let src_id = src_reg.register(SourceCode {
+            id_of_code: "test_cheapest_path".into(),
+            code: r#"
+foreign_typemap!(($p:r_type) Foo => Bar { $out = bar_from_foo($p) };);
+foreign_typemap!(($p:r_type, cost = 10) Bar => Qux { $out = alloc_qux_from_bar($p) };);
+foreign_typemap!(($p:r_type, cost = 2) Foo => Baz { $out = baz_from_foo($p) };);
+foreign_typemap!(($p:r_type, cost = 2) Baz => Qux { $out = qux_from_baz($p) };);
+foreign_typemap!(($p:r_type, cost = 2) Foo => Boo { $out = boo_from_foo($p) };);
+foreign_typemap!(($p:r_type, cost = 0) Boo => Baz { $out = baz_from_boo($p) };);
+"#
+            .into(),
+        });
+        types_map.merge(src_id, src_reg.src(src_id), 64).unwrap();
+
+        let foo = types_map.rust_names_map["Foo"];
+        let qux = types_map.rust_names_map["Qux"];
+        let (_, code) = types_map
+            .convert_rust_types(foo, qux, "a0", "jlong", invalid_src_id_span())
+            .unwrap();
+        assert!(code.contains("baz_from_foo"));
+        assert!(code.contains("qux_from_baz"));
+        assert!(!code.contains("alloc_qux_from_bar"));
+        assert!(!code.contains("baz_from_boo"));
+
+        // memoized path should be forgotten after change of rules
+        let src_id = src_reg.register(SourceCode {
+            id_of_code: "test_cheapest_path_2".into(),
+            code: r#"foreign_typemap!(($p:r_type, cost = 1) Foo => Qux { $out = qux_from_foo($p) };);"#
+                .into(),
+        });
+        types_map.merge(src_id, src_reg.src(src_id), 64).unwrap();
+        let (_, code) = types_map
+            .convert_rust_types(foo, qux, "a0", "jlong", invalid_src_id_span())
+            .unwrap();
+        assert!(code.contains("qux_from_foo"));
+        assert!(!code.contains("baz_from_foo"));
+
+        assert!(types_map.remove_conv_rules(&parse_type! { Foo }, &parse_type! { Qux }));
+        let (_, code) = types_map
+            .convert_rust_types(foo, qux, "a0", "jlong", invalid_src_id_span())
+            .unwrap();
+        assert!(code.contains("qux_from_baz"));
+    }
+
+    #[test]
+    fn test_tmp_var_in_code_template() {
+        let _ = env_logger::try_init();
+        let mut types_map = TypeMap::default();
+        let mut src_reg = SourceRegistry::default();
+        let src_id = src_reg.register(SourceCode {
+            id_of_code: "test_tmp_var_in_code_template".into(),
+            code: r#"
+foreign_typemap!(($p:r_type) Foo => Bar {
+    let mut $tmp = buf_from_foo($p);
+    $out = bar_from_buf(&mut $tmp)
+};);
+foreign_typemap!(($p:r_type) Bar => Baz {
+    let $tmp = buf_from_bar($p);
+    $out = baz_from_buf(&$tmp)
+};);
+"#
+            .into(),
+        });
+        types_map.merge(src_id, src_reg.src(src_id), 64).unwrap();
+
+        let foo = types_map.rust_names_map["Foo"];
+        let baz = types_map.rust_names_map["Baz"];
+        let (_, code) = types_map
+            .convert_rust_types(foo, baz, "a0", "jlong", invalid_src_id_span())
+            .unwrap();
+        assert_eq!(
+            "    let mut a0_tmp0 = buf_from_foo ( a0 ) ; let a0: Bar = bar_from_buf ( & mut a0_tmp0 );\n\
+             \x20   let a0_tmp1 = buf_from_bar ( a0 ) ; let a0: Baz = baz_from_buf ( &a0_tmp1 );\n",
+            code
+        );
+
+        let mut code = "    let x = 1;\n".to_string();
+        apply_code_template(
+            &mut code,
+            "let mut {tmp_var} = Vec::new();\n\
+             let {to_var}: {to_var_type} = foo({from_var}, &mut {tmp_var}, {{ 1 }});",
+            "a0",
+            "a0",
+            "Foo",
+            "jlong",
+            "a0_tmp2",
+        );
+        assert_eq!(
+            "    let x = 1;\n    let mut a0_tmp2 = Vec::new();\n    let a0: Foo = foo(a0, &mut a0_tmp2, {{ 1 }});\n",
+            code
+        );
+        assert!(validate_code_template(
+            invalid_src_id_span(),
+            "let {to_var}: {to_var_type} = foo({from_var}, &{tmp_var});"
+        )
+        .is_err());
+        assert!(validate_code_template(
+            invalid_src_id_span(),
+            "let {tmp_var}: Vec<u8> = Vec::new(); let {to_var}: {to_var_type} = foo({from_var}, &{tmp_var});"
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_unused_rules() {
+        let _ = env_logger::try_init();
+        let mut types_map = TypeMap::default();
+        let mut src_reg = SourceRegistry::default();
+        let src_id = src_reg.register(SourceCode {
+            id_of_code: "test_unused_rules".into(),
+            code: r#"
+foreign_typemap!(($p:r_type) Foo => Bar { $out = bar_from_foo($p) };);
+foreign_typemap!(($p:r_type) Foo => Baz { $out = baz_from_foo($p) };);
+"#
+            .into(),
+        });
+        types_map.set_rule_precedence(src_id, RulePrecedence::Merged);
+        types_map.merge(src_id, src_reg.src(src_id), 64).unwrap();
+        assert_eq!(2, types_map.unused_rules().len());
+
+        let foo = types_map.rust_names_map["Foo"];
+        let bar = types_map.rust_names_map["Bar"];
+        types_map
+            .convert_rust_types(foo, bar, "a0", "jlong", invalid_src_id_span())
+            .unwrap();
+        let unused = types_map.unused_rules();
+        assert_eq!(1, unused.len());
+        let ((unused_src_id, unused_span), ref descr) = unused[0];
+        assert_eq!(src_id, unused_src_id);
+        assert_eq!(3, unused_span.start().line);
+        assert_eq!("conversation rule 'Foo' -> 'Baz'", descr);
+
+        // rules from user's code are reported, built-in rules are not
+        for (precedence, code) in &[
+            (
+                RulePrecedence::User,
+                "foreign_typemap!(($p:r_type) Boo => Bar { $out = bar_from_boo($p) };);",
+            ),
+            (
+                RulePrecedence::BuiltIn,
+                "foreign_typemap!(($p:r_type) Boo => Baz { $out = baz_from_boo($p) };);",
+            ),
+        ] {
+            let src_id = src_reg.register(SourceCode {
+                id_of_code: format!("test_unused_rules_{:?}", precedence),
+                code: (*code).into(),
+            });
+            if *precedence != RulePrecedence::User {
+                types_map.set_rule_precedence(src_id, *precedence);
+            }
+            types_map.merge(src_id, src_reg.src(src_id), 64).unwrap();
+        }
+        let unused = types_map.unused_rules();
+        assert_eq!(2, unused.len());
+        assert_eq!("conversation rule 'Boo' -> 'Bar'", unused[1].1);
+    }
+
+    #[test]
+    fn test_unused_class_scope_rules() {
+        let _ = env_logger::try_init();
+        let mut types_map = TypeMap::default();
+        let mut src_reg = SourceRegistry::default();
+        let src_id = src_reg.register(SourceCode {
+            id_of_code: "test_unused_class_scope_rules".into(),
+            code: r#"class Foo {
+    self_type Foo;
+    constructor Foo::new() -> Foo;
+    foreign_typemap!(($p:r_type) Boo => i32 { $out = $p.0 };);
+    foreign_typemap!(($p:r_type) Coo => i32 { $out = $p.0 };);
+}"#
+            .into(),
+        });
+        let cfg = LanguageConfig::JavaConfig(JavaConfig::new("java".into(), "com.example".into()));
+        let class = crate::code_parse::parse_foreigner_class(
+            src_id,
+            &cfg,
+            src_reg.src(src_id).parse().unwrap(),
+        )
+        .unwrap();
+        // rule used only during one of passes over class is not reported
+        for pass in 0..2 {
+            types_map.push_class_scope(&class).unwrap();
+            if pass == 0 {
+                let boo = types_map.rust_names_map["Boo"];
+                let i32_ty = types_map.rust_names_map["i32"];
+                types_map
+                    .convert_rust_types(boo, i32_ty, "a0", "i32", invalid_src_id_span())
+                    .unwrap();
+            }
+            types_map.pop_class_scope();
+        }
+        let unused = types_map.unused_rules();
+        assert_eq!(1, unused.len());
+        let ((unused_src_id, unused_span), ref descr) = unused[0];
+        assert_eq!(src_id, unused_src_id);
+        assert_eq!(5, unused_span.start().line);
+        assert_eq!("conversation rule 'Coo' -> 'i32'", descr);
+    }
+
+    #[test]
+    fn test_generic_instances_cache() {
+        let _ = env_logger::try_init();
+        let mut types_map = TypeMap::default();
+        let mut src_reg = SourceRegistry::default();
+        let src_id = src_reg.register(SourceCode {
+            id_of_code: "test_generic_instances_cache".into(),
+            code: r#"
+#[swig_code = "let {to_var}: {to_var_type} = <{to_var_type}>::swig_from({from_var});"]
+trait SwigFrom<T> {
+    fn swig_from(_: T) -> Self;
+}
+impl<T: SwigForeignClass> SwigFrom<Vec<T>> for jlong {
+    fn swig_from(x: Vec<T>) -> Self {
+        unimplemented!();
+    }
+}
+"#
+            .into(),
+        });
+        types_map.merge(src_id, src_reg.src(src_id), 64).unwrap();
+        let from = types_map.find_or_alloc_rust_type(&parse_type! { Vec<Foo> }, SourceId::none());
+        let to = types_map.find_or_alloc_rust_type(&parse_type! { jlong }, SourceId::none());
+        let build_path = |types_map: &mut TypeMap| {
+            try_build_path(
+                from,
+                to,
+                invalid_src_id_span(),
+                &mut types_map.conv_graph,
+                &types_map.rust_names_map,
+                (
+                    &types_map.generic_edges,
+                    &mut types_map.generic_instances_cache,
+                ),
+                MAX_TRY_BUILD_PATH_STEPS,
+            )
+        };
+
+        assert!(build_path(&mut types_map).is_none());
+        assert!(!types_map.generic_instances_cache.is_empty());
+        // trait bound failed, so result was not saved
+        types_map.find_or_alloc_rust_type_that_implements(
+            &parse_type! { Foo },
+            "SwigForeignClass",
+            SourceId::none(),
+        );
+        assert!(build_path(&mut types_map).is_some());
+
+        assert!(types_map.remove_conv_rules(&parse_type! { Vec<T> }, &parse_type! { jlong }));
+        assert!(types_map.generic_instances_cache.is_empty());
+        assert!(build_path(&mut types_map).is_none());
+    }
+
+    #[test]
+    fn test_generic_instances_cache_span() {
+        let _ = env_logger::try_init();
+        let mut types_map = TypeMap::default();
+        let mut src_reg = SourceRegistry::default();
+        let src_id = src_reg.register(SourceCode {
+            id_of_code: "test_generic_instances_cache_span".into(),
+            code: r#"
+#[swig_code = "let {to_var}: {to_var_type} = <{to_var_type}>::swig_from({from_var});"]
+trait SwigFrom<T> {
+    fn swig_from(_: T) -> Self;
+}
+impl<T: SwigForeignClass> SwigFrom<Vec<T>> for Wrapper<T> {
+    fn swig_from(x: Vec<T>) -> Self {
+        unimplemented!();
+    }
+}
+impl<T: SwigForeignClass> SwigFrom<Wrapper<T>> for jlong {
+    fn swig_from(x: Wrapper<T>) -> Self {
+        unimplemented!();
+    }
+}
+"#
+            .into(),
+        });
+        types_map.merge(src_id, src_reg.src(src_id), 64).unwrap();
+        types_map.find_or_alloc_rust_type_that_implements(
+            &parse_type! { Foo },
+            "SwigForeignClass",
+            SourceId::none(),
+        );
+        let from = types_map.find_or_alloc_rust_type(&parse_type! { Vec<Foo> }, SourceId::none());
+        let to = types_map.find_or_alloc_rust_type(&parse_type! { jlong }, SourceId::none());
+        let mut build_path_for_line = |line: usize| {
+            let sp = syn::parse_str::<Type>(&format!("{}Foo", "\n".repeat(line - 1)))
+                .unwrap()
+                .span();
+            let path = try_build_path(
+                from,
+                to,
+                (SourceId::none(), sp),
+                &mut types_map.conv_graph,
+                &types_map.rust_names_map,
+                (
+                    &types_map.generic_edges,
+                    &mut types_map.generic_instances_cache,
+                ),
+                MAX_TRY_BUILD_PATH_STEPS,
+            )
+            .unwrap();
+            let (_, ref wrapper, _) = path.new_edges[0];
+            assert_eq!("Wrapper < Foo >", wrapper.normalized_name);
+            wrapper.ty.span().start().line
+        };
+        build_path_for_line(2);
+        assert_eq!(5, build_path_for_line(5));
+        assert_eq!(7, build_path_for_line(7));
+    }
+
+    #[test]
+    fn test_remove_conv_rules() {
+        let _ = env_logger::try_init();
+        let mut types_map = TypeMap::default();
+        let mut src_reg = SourceRegistry::default();
+        let src_id = src_reg.register(SourceCode {
+            id_of_code: "test_remove_conv_rules".into(),
+            code: include_str!("java_jni/jni-include.rs").into(),
+        });
+        types_map.merge(src_id, src_reg.src(src_id), 64).unwrap();
+
+        let from = types_map.rust_names_map["bool"];
+        let to = types_map.rust_names_map["jboolean"];
+        assert!(types_map.conv_graph.find_edge(from, to).is_some());
+        assert!(types_map.remove_conv_rules(&parse_type! { bool }, &parse_type! { jboolean }));
+        assert!(types_map.conv_graph.find_edge(from, to).is_none());
+        assert!(!types_map.remove_conv_rules(&parse_type! { bool }, &parse_type! { jboolean }));
+
+        let generic_rules_len = types_map.generic_edges.len();
+        assert!(types_map.remove_conv_rules(&parse_type! { Vec<T> }, &parse_type! { jobjectArray }));
+        assert_eq!(generic_rules_len - 1, types_map.generic_edges.len());
+
+        assert!(!types_map.remove_conv_rules(&parse_type! { Foo }, &parse_type! { Bar }));
+    }
+
+    #[test]
+    fn test_rust_type_names_interned() {
+        let mut types_map = TypeMap::default();
+        let ty = parse_type! { ::std::collections::HashMap<String, Vec<u8>> };
+        let rty = types_map.find_or_alloc_rust_type(&ty, SourceId::none());
+        let rty2 = types_map.find_or_alloc_rust_type_no_src_id(&ty);
+        assert_eq!(rty, rty2);
+        let (key, idx) = types_map
+            .rust_names_map
+            .iter()
+            .find(|(_, idx)| **idx == rty)
+            .unwrap();
+        assert_eq!(rty, *idx);
+        assert_eq!(
+            key.as_str().as_ptr(),
+            types_map[rty].normalized_name.as_str().as_ptr()
+        );
+    }
 }
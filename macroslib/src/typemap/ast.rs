@@ -1,7 +1,7 @@
 mod subst_map;
 
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     fmt::Display,
     hash::{Hash, Hasher},
     mem,
@@ -11,15 +11,14 @@ use std::{
 use log::trace;
 use proc_macro2::{Ident, Span, TokenStream};
 use quote::ToTokens;
-use rustc_hash::FxHashMap;
 use smallvec::SmallVec;
 use smol_str::SmolStr;
 use syn::{
     parse_quote,
     visit::{visit_lifetime, Visit},
     visit_mut::{
-        visit_angle_bracketed_generic_arguments_mut, visit_type_mut, visit_type_reference_mut,
-        VisitMut,
+        visit_angle_bracketed_generic_arguments_mut, visit_expr_mut, visit_generic_argument_mut,
+        visit_type_mut, visit_type_reference_mut, VisitMut,
     },
     Type,
 };
@@ -28,7 +27,10 @@ use self::subst_map::{TyParamsSubstItem, TyParamsSubstMap};
 use crate::{
     error::{panic_on_syn_error, SourceIdSpan},
     source_registry::SourceId,
-    typemap::ty::{RustType, RustTypeS, TraitNamesSet},
+    typemap::{
+        ty::{RustTypeS, TraitNamesSet},
+        DEFAULT_CONV_COST,
+    },
 };
 
 #[derive(Debug)]
@@ -73,7 +75,7 @@ impl TypeName {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct SpannedSmolStr {
     pub sp: Span,
     pub value: SmolStr,
@@ -97,35 +99,17 @@ impl PartialEq<SmolStr> for SpannedSmolStr {
     }
 }
 
-struct NormalizeTyLifetimesCache {
-    inner: FxHashMap<syn::Type, Box<str>>,
-}
-
-impl NormalizeTyLifetimesCache {
-    fn new() -> Self {
-        NormalizeTyLifetimesCache {
-            inner: FxHashMap::default(),
+pub(crate) fn normalize_ty_lifetimes(ty: &syn::Type) -> SmolStr {
+    struct HasLifetime(bool);
+    impl<'ast> Visit<'ast> for HasLifetime {
+        fn visit_lifetime(&mut self, _: &'ast syn::Lifetime) {
+            self.0 = true;
         }
     }
-    fn insert(&mut self, ty: &syn::Type, val: String) -> &'static str {
-        self.inner.insert(ty.clone(), val.into_boxed_str());
-        self.get(ty).expect("empty after insert")
-    }
-    fn get(&self, ty: &syn::Type) -> Option<&'static str> {
-        self.inner.get(ty).map(|x| unsafe { mem::transmute(&**x) })
-    }
-}
-
-fn with_normalize_ty_lifetimes_cache<T, F: FnOnce(&mut NormalizeTyLifetimesCache) -> T>(f: F) -> T {
-    thread_local!(static INTERNER: RefCell<NormalizeTyLifetimesCache> = {
-        RefCell::new(NormalizeTyLifetimesCache::new())
-    });
-    INTERNER.with(|interner| f(&mut *interner.borrow_mut()))
-}
-
-pub(crate) fn normalize_ty_lifetimes(ty: &syn::Type) -> &'static str {
-    if let Some(cached_str) = with_normalize_ty_lifetimes_cache(|cache| cache.get(ty)) {
-        return cached_str;
+    let mut has_lifetime = HasLifetime(false);
+    has_lifetime.visit_type(ty);
+    if !has_lifetime.0 {
+        return SmolStr::new(ty.into_token_stream().to_string());
     }
 
     struct StripLifetime;
@@ -158,9 +142,7 @@ pub(crate) fn normalize_ty_lifetimes(ty: &syn::Type) -> &'static str {
     let mut strip_lifetime = StripLifetime;
     let mut new_ty = ty.clone();
     strip_lifetime.visit_type_mut(&mut new_ty);
-    let type_str = new_ty.into_token_stream().to_string();
-
-    with_normalize_ty_lifetimes_cache(|cache| cache.insert(ty, type_str))
+    SmolStr::new(new_ty.into_token_stream().to_string())
 }
 
 #[derive(Debug)]
@@ -168,11 +150,14 @@ pub(crate) struct GenericTypeConv {
     pub src_id: SourceId,
     pub from_ty: syn::Type,
     pub to_ty: syn::Type,
-    pub code_template: String,
+    pub code_template: Rc<str>,
     pub dependency: Rc<RefCell<Option<TokenStream>>>,
     pub generic_params: syn::Generics,
     pub to_foreigner_hint: Option<String>,
     pub from_foreigner_hint: Option<String>,
+    pub cost: u32,
+    /// was rule used to generate code
+    pub used: Rc<Cell<bool>>,
 }
 
 impl GenericTypeConv {
@@ -184,23 +169,107 @@ impl GenericTypeConv {
         GenericTypeConv {
             from_ty,
             to_ty,
-            code_template: String::new(),
+            code_template: "".into(),
             dependency: Rc::new(RefCell::new(None)),
             generic_params,
             to_foreigner_hint: None,
             from_foreigner_hint: None,
             src_id: SourceId::none(),
+            cost: DEFAULT_CONV_COST,
+            used: Rc::new(Cell::new(false)),
         }
     }
 
+    /// If `ty` matches `from_ty` of this rule, but some of trait bounds
+    /// are not satisfied, returns description of the failed bound
+    pub(crate) fn failed_trait_bound<'a, OtherRustTypes>(
+        &self,
+        ty: &RustTypeS,
+        others: OtherRustTypes,
+    ) -> Option<String>
+    where
+        OtherRustTypes: Fn(&str) -> Option<&'a RustTypeS>,
+    {
+        let mut subst_map = TyParamsSubstMap::default();
+        for ty_p in self.generic_params.type_params() {
+            if let syn::Type::Path(syn::TypePath {
+                qself: None,
+                ref path,
+            }) = self.from_ty
+            {
+                // rule like T -> U matches any type, so it is useless as hint
+                if path.is_ident(ty_p.ident.clone()) {
+                    return None;
+                }
+            }
+            subst_map.insert(&ty_p.ident, None);
+        }
+        for const_p in self.generic_params.const_params() {
+            subst_map.insert_const(&const_p.ident, None);
+        }
+        for lifetime_p in self.generic_params.lifetimes() {
+            subst_map.insert_lifetime(&lifetime_p.lifetime.ident, None);
+        }
+        if !is_second_subst_of_first(&self.from_ty, &ty.ty, &mut subst_map) {
+            return None;
+        }
+        let trait_bounds = get_trait_bounds(&self.generic_params);
+        for subst_it in subst_map.as_slice() {
+            let val = match subst_it.ty {
+                Some(ref val) => val,
+                None => continue,
+            };
+            let bound = match trait_bounds
+                .iter()
+                .find(|it| it.ty_param.as_ref() == subst_it.ident)
+            {
+                Some(bound) => bound,
+                None => continue,
+            };
+            let val_name = normalize_ty_lifetimes(val);
+            match others(&val_name) {
+                None => {
+                    return Some(format!(
+                        "type '{}' is unknown, but {} requires {}",
+                        val_name, subst_it.ident, bound.trait_names
+                    ));
+                }
+                Some(rt) if !rt.implements.contains_subset(&bound.trait_names) => {
+                    return Some(format!(
+                        "type '{}' does not implement {}, required for {}",
+                        val_name, bound.trait_names, subst_it.ident
+                    ));
+                }
+                Some(_) => {}
+            }
+        }
+        None
+    }
+
     pub(crate) fn is_conv_possible<'a, OtherRustTypes>(
         &self,
-        ty: &RustType,
-        goal_ty: Option<&RustType>,
+        ty: &RustTypeS,
+        goal_ty: Option<&RustTypeS>,
         others: OtherRustTypes,
     ) -> Option<(syn::Type, SmolStr)>
     where
-        OtherRustTypes: Fn(&str) -> Option<&'a RustType>,
+        OtherRustTypes: Fn(&str) -> Option<&'a RustTypeS>,
+    {
+        self.instantiate(ty, goal_ty, others).0
+    }
+
+    /// The same as `is_conv_possible`, plus flag that result depends only on `ty`,
+    /// so it can be reused for other goal types and after addition of new types.
+    /// Negative result of trait bounds check can not be reused,
+    /// because of types may get new traits implementations later.
+    pub(crate) fn instantiate<'a, OtherRustTypes>(
+        &self,
+        ty: &RustTypeS,
+        goal_ty: Option<&RustTypeS>,
+        others: OtherRustTypes,
+    ) -> (Option<(syn::Type, SmolStr)>, bool)
+    where
+        OtherRustTypes: Fn(&str) -> Option<&'a RustTypeS>,
     {
         let mut subst_map = TyParamsSubstMap::default();
         trace!(
@@ -212,8 +281,14 @@ impl GenericTypeConv {
         for ty_p in self.generic_params.type_params() {
             subst_map.insert(&ty_p.ident, None);
         }
+        for const_p in self.generic_params.const_params() {
+            subst_map.insert_const(&const_p.ident, None);
+        }
+        for lifetime_p in self.generic_params.lifetimes() {
+            subst_map.insert_lifetime(&lifetime_p.lifetime.ident, None);
+        }
         if !is_second_subst_of_first(&self.from_ty, &ty.ty, &mut subst_map) {
-            return None;
+            return (None, true);
         }
         trace!(
             "is_conv_possible: {} is subst of {:?}, check trait bounds",
@@ -233,7 +308,7 @@ impl GenericTypeConv {
                     let requires = &trait_bounds[idx].trait_names;
                     let val_name = normalize_ty_lifetimes(val);
 
-                    others(val_name).map_or(true, |rt| !rt.implements.contains_subset(requires))
+                    others(&val_name).map_or(true, |rt| !rt.implements.contains_subset(requires))
                 };
                 if trait_bounds
                     .iter()
@@ -241,16 +316,25 @@ impl GenericTypeConv {
                     .map_or(false, traits_bound_not_match)
                 {
                     trace!("is_conv_possible: trait bounds check failed");
-                    return None;
+                    return (None, false);
                 }
             } else {
                 has_unbinded = true;
             }
         }
+        if subst_map
+            .consts_as_slice()
+            .iter()
+            .any(|it| it.value.is_none())
+        {
+            has_unbinded = true;
+        }
+        let mut reusable = true;
         if has_unbinded {
             trace!("is_conv_possible: has_unbinded: goal_ty {:?}", goal_ty);
             if let Some(goal_ty) = goal_ty {
                 is_second_subst_of_first(&self.to_ty, &goal_ty.ty, &mut subst_map);
+                reusable = false;
             }
         }
 
@@ -275,7 +359,7 @@ impl GenericTypeConv {
                     != RustTypeS::make_unique_typename(&clean_from_ty, &foreign_name)
                 {
                     trace!("is_conv_possible: check failed by from_foreigner_hint check");
-                    return None;
+                    return (None, reusable);
                 }
             }
         }
@@ -303,7 +387,7 @@ impl GenericTypeConv {
             to_suffix,
         )
         .into();
-        Some((to_ty, normalized_name))
+        (Some((to_ty, normalized_name)), reusable)
     }
 }
 
@@ -326,6 +410,19 @@ fn is_second_subst_of_first(ty1: &Type, ty2: &Type, subst_map: &mut TyParamsSubs
                         return true;
                     }
                 }
+                // const parameter used as generic argument, like `N` in `Matrix<T, N>`
+                if subst_map.get_const(&p1.segments[0].ident).is_some() {
+                    let val: syn::Expr = syn::Expr::Path(syn::ExprPath {
+                        attrs: vec![],
+                        qself: None,
+                        path: p2.clone(),
+                    });
+                    return is_second_subst_of_first_const(
+                        &const_param_as_expr(&p1.segments[0].ident),
+                        &val,
+                        subst_map,
+                    );
+                }
             }
             for (s1, s2) in p1.segments.iter().zip(p2.segments.iter()) {
                 if s1.ident != s2.ident {
@@ -347,12 +444,20 @@ fn is_second_subst_of_first(ty1: &Type, ty2: &Type, subst_map: &mut TyParamsSubs
                 trace!("is_second_substitude_of_first mutable not match");
                 false
             } else {
-                is_second_subst_of_first(&*mut_ty1.elem, &*mut_ty2.elem, subst_map)
+                is_second_subst_of_first_lifetime(
+                    mut_ty1.lifetime.as_ref(),
+                    mut_ty2.lifetime.as_ref(),
+                    subst_map,
+                ) && is_second_subst_of_first(&*mut_ty1.elem, &*mut_ty2.elem, subst_map)
             }
         }
         (Type::Slice(ref ty1), Type::Slice(ref ty2)) => {
             is_second_subst_of_first(&*ty1.elem, &*ty2.elem, subst_map)
         }
+        (Type::Array(ref ty1), Type::Array(ref ty2)) => {
+            is_second_subst_of_first(&*ty1.elem, &*ty2.elem, subst_map)
+                && is_second_subst_of_first_const(&ty1.len, &ty2.len, subst_map)
+        }
         (Type::Tuple(ref ty1), Type::Tuple(ref ty2)) => {
             if ty1.elems.len() != ty2.elems.len() {
                 trace!("is_second_subst_of_first: tuple elems length not match");
@@ -388,19 +493,90 @@ fn is_second_subst_of_first_ppath(
             syn::PathArguments::AngleBracketed(ref p1),
             syn::PathArguments::AngleBracketed(ref p2),
         ) => {
-            if p1.args.len() != p2.args.len() {
+            let (lifetimes1, args1) = split_lifetimes_args(&p1.args);
+            let (lifetimes2, args2) = split_lifetimes_args(&p2.args);
+            // `Cow<str>` is ok for `Cow<'a, str>`, lifetimes just elided
+            if !lifetimes1.is_empty() && !lifetimes2.is_empty() {
+                if lifetimes1.len() != lifetimes2.len() {
+                    trace!(
+                        "is_second_subst_of_first_ppath: lifetimes len not match {} vs {}",
+                        lifetimes1.len(),
+                        lifetimes2.len()
+                    );
+                    return false;
+                }
+                for (l1, l2) in lifetimes1.into_iter().zip(lifetimes2.into_iter()) {
+                    if !is_second_subst_of_first_lifetime(Some(l1), Some(l2), subst_map) {
+                        return false;
+                    }
+                }
+            }
+            if args1.len() != args2.len() {
                 trace!(
                     "is_second_subst_of_first_ppath: param types len not match {} vs {}",
-                    p1.args.len(),
-                    p2.args.len()
+                    args1.len(),
+                    args2.len()
                 );
                 return false;
             }
-            for (type_p1, type_p2) in p1.args.iter().zip(p2.args.iter()) {
+            for (type_p1, type_p2) in args1.into_iter().zip(args2.into_iter()) {
                 let (type_p1, type_p2) = match (type_p1, type_p2) {
                     (syn::GenericArgument::Type(ref ty1), syn::GenericArgument::Type(ref ty2)) => {
                         (ty1, ty2)
                     }
+                    (syn::GenericArgument::Type(ref ty1), syn::GenericArgument::Const(ref e2)) => {
+                        match if_ty_is_const_param(ty1, subst_map) {
+                            Some(ident) => {
+                                if !is_second_subst_of_first_const(
+                                    &const_param_as_expr(ident),
+                                    e2,
+                                    subst_map,
+                                ) {
+                                    return false;
+                                }
+                                continue;
+                            }
+                            None => {
+                                trace!(
+                                    "is_second_subst_of_first_ppath: type vs const {:?} != {:?}",
+                                    type_p1,
+                                    type_p2
+                                );
+                                return false;
+                            }
+                        }
+                    }
+                    (syn::GenericArgument::Const(ref e1), syn::GenericArgument::Type(ref ty2)) => {
+                        // `Matrix<i32, SIZE>` parsed `SIZE` as type
+                        let e2: syn::Expr = match ty2 {
+                            Type::Path(syn::TypePath {
+                                qself: None,
+                                ref path,
+                            }) => syn::Expr::Path(syn::ExprPath {
+                                attrs: Vec::new(),
+                                qself: None,
+                                path: path.clone(),
+                            }),
+                            _ => {
+                                trace!(
+                                    "is_second_subst_of_first_ppath: const vs type {:?} != {:?}",
+                                    type_p1,
+                                    type_p2
+                                );
+                                return false;
+                            }
+                        };
+                        if !is_second_subst_of_first_const(e1, &e2, subst_map) {
+                            return false;
+                        }
+                        continue;
+                    }
+                    (syn::GenericArgument::Const(ref e1), syn::GenericArgument::Const(ref e2)) => {
+                        if !is_second_subst_of_first_const(e1, e2, subst_map) {
+                            return false;
+                        }
+                        continue;
+                    }
                     _ => {
                         if type_p1 != type_p2 {
                             trace!(
@@ -446,6 +622,135 @@ fn is_second_subst_of_first_ppath(
     }
 }
 
+type LifetimesArgs<'a> = SmallVec<[&'a syn::Lifetime; 2]>;
+type NotLifetimesArgs<'a> = SmallVec<[&'a syn::GenericArgument; 4]>;
+
+fn split_lifetimes_args(
+    args: &syn::punctuated::Punctuated<syn::GenericArgument, syn::token::Comma>,
+) -> (LifetimesArgs<'_>, NotLifetimesArgs<'_>) {
+    let mut lifetimes = LifetimesArgs::new();
+    let mut others = NotLifetimesArgs::new();
+    for arg in args {
+        match arg {
+            syn::GenericArgument::Lifetime(ref l) => lifetimes.push(l),
+            _ => others.push(arg),
+        }
+    }
+    (lifetimes, others)
+}
+
+/// Lifetime parameter of rule binds to lifetime of type,
+/// elided lifetime matches anything, because of it can not be checked,
+/// other lifetimes (like `'static`) should be the same
+fn is_second_subst_of_first_lifetime(
+    l1: Option<&syn::Lifetime>,
+    l2: Option<&syn::Lifetime>,
+    subst_map: &mut TyParamsSubstMap,
+) -> bool {
+    let (l1, l2) = match (l1, l2) {
+        (Some(l1), Some(l2)) => (l1, l2),
+        _ => return true,
+    };
+    if let Some(subst) = subst_map.get_lifetime_mut(&l1.ident) {
+        return match *subst {
+            Some(ref val) => val.ident == l2.ident,
+            None => {
+                *subst = Some(l2.clone());
+                true
+            }
+        };
+    }
+    let ret = l1.ident == l2.ident;
+    trace!(
+        "is_second_subst_of_first_lifetime just check equal {} vs {} => {}",
+        DisplayToTokens(l1),
+        DisplayToTokens(l2),
+        ret
+    );
+    ret
+}
+
+/// `N` in `[T; N]` or in `Matrix<T, N>` (as expression or as type) matches the same value,
+/// other expressions are compared as tokens
+fn is_second_subst_of_first_const(
+    e1: &syn::Expr,
+    e2: &syn::Expr,
+    subst_map: &mut TyParamsSubstMap,
+) -> bool {
+    let e1 = unwrap_const_block(e1);
+    let e2 = unwrap_const_block(e2);
+    if let Some(ident) = if_expr_is_ident(e1) {
+        if let Some(subst) = subst_map.get_const_mut(ident) {
+            match *subst {
+                Some(ref val) => return const_expr_eq(val, e2),
+                None => {
+                    *subst = Some(e2.clone());
+                    return true;
+                }
+            }
+        }
+    }
+    let ret = const_expr_eq(e1, e2);
+    trace!(
+        "is_second_subst_of_first_const just check equal {} vs {} => {}",
+        DisplayToTokens(e1),
+        DisplayToTokens(e2),
+        ret
+    );
+    ret
+}
+
+fn const_expr_eq(e1: &syn::Expr, e2: &syn::Expr) -> bool {
+    unwrap_const_block(e1).into_token_stream().to_string()
+        == unwrap_const_block(e2).into_token_stream().to_string()
+}
+
+/// `{ N }` -> `N`
+fn unwrap_const_block(e: &syn::Expr) -> &syn::Expr {
+    if let syn::Expr::Block(syn::ExprBlock { ref block, .. }) = e {
+        if let [syn::Stmt::Expr(ref inner)] = block.stmts.as_slice() {
+            return unwrap_const_block(inner);
+        }
+    }
+    e
+}
+
+fn if_expr_is_ident(e: &syn::Expr) -> Option<&Ident> {
+    match e {
+        syn::Expr::Path(syn::ExprPath {
+            qself: None,
+            ref path,
+            ..
+        }) if path.leading_colon.is_none()
+            && path.segments.len() == 1
+            && path.segments[0].arguments.is_empty() =>
+        {
+            Some(&path.segments[0].ident)
+        }
+        _ => None,
+    }
+}
+
+fn if_ty_is_const_param<'a>(ty: &'a Type, subst_map: &TyParamsSubstMap) -> Option<&'a Ident> {
+    match ty {
+        Type::Path(syn::TypePath {
+            qself: None,
+            ref path,
+        }) if path.leading_colon.is_none()
+            && path.segments.len() == 1
+            && path.segments[0].arguments.is_empty() =>
+        {
+            let ident = &path.segments[0].ident;
+            subst_map.get_const(ident).map(|_| ident)
+        }
+        _ => None,
+    }
+}
+
+fn const_param_as_expr(ident: &Ident) -> syn::Expr {
+    parse_quote! { #ident }
+}
+
 fn replace_all_types_with(in_ty: &Type, subst_map: &TyParamsSubstMap) -> Type {
     struct ReplaceTypes<'a, 'b> {
         subst_map: &'a TyParamsSubstMap<'b>,
@@ -459,6 +764,60 @@ fn replace_all_types_with(in_ty: &Type, subst_map: &TyParamsSubstMap) -> Type {
                 visit_type_mut(self, t);
             }
         }
+        fn visit_generic_argument_mut(&mut self, arg: &mut syn::GenericArgument) {
+            let val = match arg {
+                syn::GenericArgument::Type(ref ty) => if_ty_is_const_param(ty, self.subst_map)
+                    .and_then(|ident| self.subst_map.get_const(ident))
+                    .and_then(|val| val.clone()),
+                _ => None,
+            };
+            if let Some(val) = val {
+                *arg = syn::GenericArgument::Const(val);
+            } else {
+                visit_generic_argument_mut(self, arg);
+            }
+        }
+        fn visit_type_reference_mut(&mut self, r: &mut syn::TypeReference) {
+            if let Some(ref l) = r.lifetime {
+                if let Some(val) = self.subst_map.get_lifetime(&l.ident) {
+                    r.lifetime = val.clone();
+                }
+            }
+            visit_type_reference_mut(self, r);
+        }
+        fn visit_angle_bracketed_generic_arguments_mut(
+            &mut self,
+            i: &mut syn::AngleBracketedGenericArguments,
+        ) {
+            let mut args =
+                syn::punctuated::Punctuated::<syn::GenericArgument, syn::token::Comma>::new();
+            mem::swap(&mut args, &mut i.args);
+            // not binded lifetime parameter is just elided
+            i.args = args
+                .into_iter()
+                .filter_map(|arg| match arg {
+                    syn::GenericArgument::Lifetime(ref l) => {
+                        match self.subst_map.get_lifetime(&l.ident) {
+                            Some(Some(val)) => Some(syn::GenericArgument::Lifetime(val.clone())),
+                            Some(None) => None,
+                            None => Some(arg.clone()),
+                        }
+                    }
+                    _ => Some(arg),
+                })
+                .collect();
+            visit_angle_bracketed_generic_arguments_mut(self, i);
+        }
+        fn visit_expr_mut(&mut self, e: &mut syn::Expr) {
+            let val = if_expr_is_ident(e)
+                .and_then(|ident| self.subst_map.get_const(ident))
+                .and_then(|val| val.clone());
+            if let Some(val) = val {
+                *e = val;
+            } else {
+                visit_expr_mut(self, e);
+            }
+        }
     }
 
     trace!(
@@ -582,7 +941,7 @@ pub(crate) fn if_type_slice_return_elem_type(ty: &Type, accept_mutbl_slice: bool
     }
 }
 
-pub(crate) fn if_option_return_some_type(ty: &RustType) -> Option<Type> {
+pub(crate) fn if_option_return_some_type(ty: &RustTypeS) -> Option<Type> {
     let generic_params: syn::Generics = parse_quote! { <T> };
     let from_ty: Type = parse_quote! { Option<T> };
     let to_ty: Type = parse_quote! { T };
@@ -592,7 +951,7 @@ pub(crate) fn if_option_return_some_type(ty: &RustType) -> Option<Type> {
         .map(|x| x.0)
 }
 
-pub(crate) fn if_vec_return_elem_type(ty: &RustType) -> Option<Type> {
+pub(crate) fn if_vec_return_elem_type(ty: &RustTypeS) -> Option<Type> {
     let from_ty: Type = parse_quote! { Vec<T> };
     let to_ty: Type = parse_quote! { T };
     let generic_params: syn::Generics = parse_quote! { <T> };
@@ -602,7 +961,100 @@ pub(crate) fn if_vec_return_elem_type(ty: &RustType) -> Option<Type> {
         .map(|x| x.0)
 }
 
-pub(crate) fn if_result_return_ok_err_types(ty: &RustType) -> Option<(Type, Type)> {
+/// `T` for `Box<dyn Iterator<Item = T>>`
+pub(crate) fn if_box_iterator_return_item_type(ty: &Type) -> Option<Type> {
+    let box_arg = match *ty {
+        Type::Path(syn::TypePath {
+            qself: None,
+            ref path,
+        }) => {
+            let last = path.segments.last()?.into_value();
+            if last.ident != "Box" {
+                return None;
+            }
+            match last.arguments {
+                syn::PathArguments::AngleBracketed(ref args) if args.args.len() == 1 => {
+                    &args.args[0]
+                }
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    let bounds = match *box_arg {
+        syn::GenericArgument::Type(Type::TraitObject(ref trait_obj)) => &trait_obj.bounds,
+        _ => return None,
+    };
+    bounds.iter().find_map(|bound| match bound {
+        syn::TypeParamBound::Trait(ref trait_bound) => {
+            let last = trait_bound.path.segments.last()?.into_value();
+            if last.ident != "Iterator" {
+                return None;
+            }
+            match last.arguments {
+                syn::PathArguments::AngleBracketed(ref args) => {
+                    args.args.iter().find_map(|arg| match arg {
+                        syn::GenericArgument::Binding(ref binding) if binding.ident == "Item" => {
+                            Some(binding.ty.clone())
+                        }
+                        _ => None,
+                    })
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    })
+}
+
+/// Arguments of `FnOnce` for `Box<dyn FnOnce(..) + Send>`
+pub(crate) fn if_box_fn_once_return_args(ty: &Type) -> Option<Vec<Type>> {
+    let box_arg = match *ty {
+        Type::Path(syn::TypePath {
+            qself: None,
+            ref path,
+        }) => {
+            let last = path.segments.last()?.into_value();
+            if last.ident != "Box" {
+                return None;
+            }
+            match last.arguments {
+                syn::PathArguments::AngleBracketed(ref args) if args.args.len() == 1 => {
+                    &args.args[0]
+                }
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    let bounds = match *box_arg {
+        syn::GenericArgument::Type(Type::TraitObject(ref trait_obj)) => &trait_obj.bounds,
+        _ => return None,
+    };
+    let mut has_send = false;
+    let mut args = None;
+    for bound in bounds {
+        if let syn::TypeParamBound::Trait(ref trait_bound) = bound {
+            let last = trait_bound.path.segments.last()?.into_value();
+            if last.ident == "Send" {
+                has_send = true;
+            } else if last.ident == "FnOnce" {
+                if let syn::PathArguments::Parenthesized(ref fn_args) = last.arguments {
+                    if let syn::ReturnType::Default = fn_args.output {
+                        args = Some(fn_args.inputs.iter().cloned().collect());
+                    }
+                }
+            }
+        }
+    }
+    if has_send {
+        args
+    } else {
+        None
+    }
+}
+
+pub(crate) fn if_result_return_ok_err_types(ty: &RustTypeS) -> Option<(Type, Type)> {
     let from_ty: Type = parse_quote! { Result<T, E> };
     let ok_ty: Type = parse_quote! { T };
     let err_ty: Type = parse_quote! { E };
@@ -642,7 +1094,7 @@ pub(crate) fn if_ty_result_return_ok_type(ty: &Type) -> Option<Type> {
 }
 
 pub(crate) fn check_if_smart_pointer_return_inner_type(
-    ty: &RustType,
+    ty: &RustTypeS,
     smart_ptr_name: &str,
 ) -> Option<Type> {
     let generic_params: syn::Generics = parse_quote! { <T> };
@@ -695,6 +1147,20 @@ pub(crate) fn parse_ty_with_given_span(
     syn::LitStr::new(type_str, span).parse::<syn::Type>()
 }
 
+/// Set `span` to all tokens of `ty`
+pub(crate) fn respan_ty(ty: &mut Type, span: Span) {
+    struct Respan(Span);
+    impl VisitMut for Respan {
+        fn visit_span_mut(&mut self, i: &mut Span) {
+            *i = self.0;
+        }
+        fn visit_ident_mut(&mut self, i: &mut Ident) {
+            i.set_span(self.0);
+        }
+    }
+    Respan(span).visit_type_mut(ty);
+}
+
 pub(crate) fn parse_ty_with_given_span_checked(type_str: &str, span: Span) -> Type {
     parse_ty_with_given_span(type_str, span).unwrap_or_else(|err| {
         panic_on_syn_error("internal parse_ty_with_given_span", type_str.into(), err)
@@ -717,6 +1183,11 @@ mod tests {
             normalize_ty_lifetimes(&str_to_ty("Foo<'a, T>")),
             "Foo < T >"
         );
+        let name = normalize_ty_lifetimes(&str_to_ty("HashMap<&'a str, Vec<&'b str>>"));
+        let name = ::std::thread::spawn(move || name.to_string())
+            .join()
+            .unwrap();
+        assert_eq!("HashMap < & str , Vec < & str > >", name);
     }
 
     macro_rules! get_generic_params_from_code {
@@ -738,28 +1209,24 @@ mod tests {
             }
         };
 
-        let foo_spec = Rc::new(
-            RustTypeS::new_without_graph_idx(str_to_ty("Foo"), "Foo", SourceId::none())
-                .implements("SwigForeignClass"),
-        );
+        let foo_spec = RustTypeS::new_without_graph_idx(str_to_ty("Foo"), "Foo", SourceId::none())
+            .implements("SwigForeignClass");
 
-        let refcell_foo_spec = Rc::new(
-            RustTypeS::new_without_graph_idx(
-                str_to_ty("RefCell<Foo>"),
-                "RefCell<Foo>",
-                SourceId::none(),
-            )
-            .implements("SwigForeignClass"),
-        );
+        let refcell_foo_spec = RustTypeS::new_without_graph_idx(
+            str_to_ty("RefCell<Foo>"),
+            "RefCell<Foo>",
+            SourceId::none(),
+        )
+        .implements("SwigForeignClass");
 
-        fn check_subst<'a, FT: Fn(&str) -> Option<&'a RustType>>(
+        fn check_subst<'a, FT: Fn(&str) -> Option<&'a RustTypeS>>(
             generic: &syn::Generics,
             from_ty_name: &str,
             to_ty_name: &str,
             ty_check_name: &str,
             expect_to_ty_name: &str,
             map_others: FT,
-        ) -> RustType {
+        ) -> RustTypeS {
             println!(
                 "check_subst: conv {} -> {} with {}",
                 from_ty_name, to_ty_name, ty_check_name
@@ -776,11 +1243,7 @@ mod tests {
                 normalize_ty_lifetimes(&str_to_ty(expect_to_ty_name))
             );
 
-            Rc::new(RustTypeS::new_without_graph_idx(
-                ret_ty,
-                ret_ty_name,
-                SourceId::none(),
-            ))
+            RustTypeS::new_without_graph_idx(ret_ty, ret_ty_name, SourceId::none())
         }
 
         let pair_generic = get_generic_params_from_code! {
@@ -791,14 +1254,10 @@ mod tests {
             }
         };
 
-        let one_spec = Rc::new(
-            RustTypeS::new_without_graph_idx(str_to_ty("One"), "One", SourceId::none())
-                .implements("SwigForeignClass"),
-        );
-        let two_spec = Rc::new(
-            RustTypeS::new_without_graph_idx(str_to_ty("One"), "One", SourceId::none())
-                .implements("SwigForeignClass"),
-        );
+        let one_spec = RustTypeS::new_without_graph_idx(str_to_ty("One"), "One", SourceId::none())
+            .implements("SwigForeignClass");
+        let two_spec = RustTypeS::new_without_graph_idx(str_to_ty("One"), "One", SourceId::none())
+            .implements("SwigForeignClass");
         check_subst(
             &pair_generic,
             "(T1, T2)",
@@ -909,7 +1368,7 @@ mod tests {
             "& Foo"
         );
 
-        let box_foo: RustType = str_to_rust_ty("Box<Foo>");
+        let box_foo: RustTypeS = str_to_rust_ty("Box<Foo>");
 
         assert_eq!(
             &*GenericTypeConv::simple_new(str_to_ty("jlong"), str_to_ty("Box<T>"), generic,)
@@ -951,6 +1410,95 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_generic_type_conv_with_const_params() {
+        let _ = env_logger::try_init();
+        let generic = get_generic_params_from_code! {
+            impl<T, const N: usize> SwigFrom<[T; N]> for Matrix<T, N> {
+                fn swig_from(x: [T; N]) -> Self {
+                    unimplemented!();
+                }
+            }
+        };
+        let conv_possible = |from_ty: &str, to_ty: &str, ty: &str| {
+            GenericTypeConv::simple_new(str_to_ty(from_ty), str_to_ty(to_ty), generic.clone())
+                .is_conv_possible(&str_to_rust_ty(ty), None, |_| None)
+                .map(|(_, name)| name)
+        };
+        assert_eq!(
+            conv_possible("[T; N]", "Matrix<T, N>", "[f32; 3]").unwrap(),
+            normalize_ty_lifetimes(&str_to_ty("Matrix<f32, 3>"))
+        );
+        assert_eq!(
+            conv_possible("Matrix<T, N>", "[T; N]", "Matrix<f32, 4>").unwrap(),
+            normalize_ty_lifetimes(&str_to_ty("[f32; 4]"))
+        );
+        assert_eq!(
+            conv_possible("Matrix<T, { N }>", "Vec<[T; N]>", "Matrix<i32, SIZE>").unwrap(),
+            normalize_ty_lifetimes(&str_to_ty("Vec<[i32; SIZE]>"))
+        );
+        assert_eq!(
+            conv_possible("([T; N], [u8; N])", "Matrix<T, N>", "([u8; 2], [u8; 2])").unwrap(),
+            normalize_ty_lifetimes(&str_to_ty("Matrix<u8, 2>"))
+        );
+        assert!(conv_possible("([T; N], [u8; N])", "Matrix<T, N>", "([u8; 2], [u8; 3])").is_none());
+        assert!(conv_possible("[T; 2]", "Matrix<T, 2>", "[u8; 3]").is_none());
+        assert!(conv_possible("[T; N]", "Matrix<T, N>", "&[u8]").is_none());
+    }
+
+    #[test]
+    fn test_generic_type_conv_with_lifetimes() {
+        let _ = env_logger::try_init();
+        let generic = get_generic_params_from_code! {
+            impl<'a, T> SwigFrom<&'a [T]> for SliceView<'a, T> {
+                fn swig_from(x: &'a [T]) -> Self {
+                    unimplemented!();
+                }
+            }
+        };
+        let conv_possible = |from_ty: &str, to_ty: &str, ty: &str| {
+            GenericTypeConv::simple_new(str_to_ty(from_ty), str_to_ty(to_ty), generic.clone())
+                .is_conv_possible(&str_to_rust_ty(ty), None, |_| None)
+                .map(|(ty, _)| ty.into_token_stream().to_string())
+        };
+        assert_eq!(
+            "SliceView < 'b , u8 >",
+            conv_possible("&'a [T]", "SliceView<'a, T>", "&'b [u8]").unwrap()
+        );
+        assert_eq!(
+            "SliceView < u8 >",
+            conv_possible("&'a [T]", "SliceView<'a, T>", "&[u8]").unwrap()
+        );
+        assert_eq!(
+            "& 'b [ i32 ]",
+            conv_possible("SliceView<'a, T>", "&'a [T]", "SliceView<'b, i32>").unwrap()
+        );
+        assert_eq!(
+            "& [ i32 ]",
+            conv_possible("SliceView<'a, T>", "&'a [T]", "SliceView<i32>").unwrap()
+        );
+        assert_eq!(
+            "SliceView < 'b , u8 >",
+            conv_possible(
+                "(&'a [T], &'a str)",
+                "SliceView<'a, T>",
+                "(&'b [u8], &'b str)"
+            )
+            .unwrap()
+        );
+        assert!(conv_possible(
+            "(&'a [T], &'a str)",
+            "SliceView<'a, T>",
+            "(&'b [u8], &'c str)"
+        )
+        .is_none());
+        assert!(conv_possible("&'static [T]", "SliceView<T>", "&'b [u8]").is_none());
+        assert_eq!(
+            "SliceView < u8 >",
+            conv_possible("&'static [T]", "SliceView<T>", "&'static [u8]").unwrap()
+        );
+    }
+
     #[test]
     fn test_get_trait_bounds() {
         let _ = env_logger::try_init();
@@ -1025,7 +1573,7 @@ mod tests {
             if_result_return_ok_err_types(&str_to_rust_ty("Result<bool, String>"))
                 .map(|(x, y)| (normalize_ty_lifetimes(&x), normalize_ty_lifetimes(&y)))
                 .unwrap(),
-            ("bool", "String")
+            ("bool".into(), "String".into())
         );
 
         assert_eq!(
@@ -1064,7 +1612,9 @@ mod tests {
         assert_eq!(
             "bool",
             GenericTypeConv::simple_new(str_to_ty("RefCell<T>"), str_to_ty("T"), generic_params,)
-                .is_conv_possible(&str_to_rust_ty(normalize_ty_lifetimes(&ty)), None, |_| None)
+                .is_conv_possible(&str_to_rust_ty(&normalize_ty_lifetimes(&ty)), None, |_| {
+                    None
+                })
                 .unwrap()
                 .1
         );
@@ -1113,9 +1663,53 @@ mod tests {
         syn::parse_str::<syn::Type>(code).unwrap()
     }
 
-    fn str_to_rust_ty(code: &str) -> RustType {
+    fn str_to_rust_ty(code: &str) -> RustTypeS {
         let ty = syn::parse_str::<syn::Type>(code).unwrap();
         let name = normalize_ty_lifetimes(&ty);
-        Rc::new(RustTypeS::new_without_graph_idx(ty, name, SourceId::none()))
+        RustTypeS::new_without_graph_idx(ty, name, SourceId::none())
+    }
+
+    #[test]
+    fn test_if_box_iterator_return_item_type() {
+        let item = if_box_iterator_return_item_type(&parse_type! { Box<dyn Iterator<Item = Foo>> });
+        assert_eq!(Some(parse_type! { Foo }), item);
+        let item = if_box_iterator_return_item_type(
+            &parse_type! { Box<dyn Iterator<Item = String> + Send> },
+        );
+        assert_eq!(Some(parse_type! { String }), item);
+        assert_eq!(
+            None,
+            if_box_iterator_return_item_type(&parse_type! { Box<Foo> })
+        );
+        assert_eq!(
+            None,
+            if_box_iterator_return_item_type(&parse_type! { Vec<Foo> })
+        );
+    }
+
+    #[test]
+    fn test_if_box_fn_once_return_args() {
+        assert_eq!(
+            Some(vec![parse_type! { Result<Foo, String> }]),
+            if_box_fn_once_return_args(
+                &parse_type! { Box<dyn FnOnce(Result<Foo, String>) + Send> }
+            )
+        );
+        assert_eq!(
+            Some(vec![]),
+            if_box_fn_once_return_args(&parse_type! { Box<dyn FnOnce() + Send> })
+        );
+        assert_eq!(
+            None,
+            if_box_fn_once_return_args(&parse_type! { Box<dyn FnOnce(i32)> })
+        );
+        assert_eq!(
+            None,
+            if_box_fn_once_return_args(&parse_type! { Box<dyn FnOnce(i32) -> i32 + Send> })
+        );
+        assert_eq!(
+            None,
+            if_box_fn_once_return_args(&parse_type! { Box<dyn Fn(i32) + Send> })
+        );
     }
 }
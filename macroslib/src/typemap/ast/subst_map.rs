@@ -15,9 +15,26 @@ pub(crate) struct TyParamsSubstItem<'a> {
     pub(crate) ty: Option<syn::Type>,
 }
 
+/// Value of const generic parameter, like `N` in `[T; N]`
+#[derive(Debug)]
+pub(crate) struct TyConstParamsSubstItem<'a> {
+    pub(crate) ident: &'a Ident,
+    pub(crate) value: Option<syn::Expr>,
+}
+
+/// Value of lifetime generic parameter, like `'a` in `&'a [T]`,
+/// `None` if not binded yet, for example lifetime was elided in matched type
+#[derive(Debug)]
+pub(crate) struct TyLifetimesSubstItem<'a> {
+    pub(crate) ident: &'a Ident,
+    pub(crate) value: Option<syn::Lifetime>,
+}
+
 #[derive(Default, Debug)]
 pub(crate) struct TyParamsSubstMap<'a> {
     inner: SmallVec<[TyParamsSubstItem<'a>; 10]>,
+    consts: SmallVec<[TyConstParamsSubstItem<'a>; 2]>,
+    lifetimes: SmallVec<[TyLifetimesSubstItem<'a>; 2]>,
 }
 
 impl<'a> TyParamsSubstMap<'a> {
@@ -54,4 +71,44 @@ impl<'a> TyParamsSubstMap<'a> {
             None => None,
         }
     }
+    pub(crate) fn insert_const(&mut self, ident: &'a Ident, value: Option<syn::Expr>) {
+        match self.consts.iter().position(|it| it.ident == ident) {
+            Some(idx) => self.consts[idx].value = value,
+            None => self.consts.push(TyConstParamsSubstItem { ident, value }),
+        }
+    }
+    #[inline]
+    pub(crate) fn consts_as_slice(&self) -> &[TyConstParamsSubstItem<'a>] {
+        &self.consts
+    }
+    pub fn get_const_mut(&mut self, k: &Ident) -> Option<&mut Option<syn::Expr>> {
+        match self.consts.iter().position(|it| it.ident == k) {
+            Some(idx) => Some(&mut self.consts[idx].value),
+            None => None,
+        }
+    }
+    pub fn get_const(&self, k: &Ident) -> Option<&Option<syn::Expr>> {
+        match self.consts.iter().position(|it| it.ident == k) {
+            Some(idx) => Some(&self.consts[idx].value),
+            None => None,
+        }
+    }
+    pub(crate) fn insert_lifetime(&mut self, ident: &'a Ident, value: Option<syn::Lifetime>) {
+        match self.lifetimes.iter().position(|it| it.ident == ident) {
+            Some(idx) => self.lifetimes[idx].value = value,
+            None => self.lifetimes.push(TyLifetimesSubstItem { ident, value }),
+        }
+    }
+    pub fn get_lifetime_mut(&mut self, k: &Ident) -> Option<&mut Option<syn::Lifetime>> {
+        match self.lifetimes.iter().position(|it| it.ident == k) {
+            Some(idx) => Some(&mut self.lifetimes[idx].value),
+            None => None,
+        }
+    }
+    pub fn get_lifetime(&self, k: &Ident) -> Option<&Option<syn::Lifetime>> {
+        match self.lifetimes.iter().position(|it| it.ident == k) {
+            Some(idx) => Some(&self.lifetimes[idx].value),
+            None => None,
+        }
+    }
 }
@@ -0,0 +1,900 @@
+//! Cache of parsed types maps.
+//! Parsing of big built-in types maps with `syn` is slow,
+//! so result of `parse::parse` is saved in simple binary format,
+//! shared by all `Generator`s of process and optionally saved on disk
+//! to be loaded during next build. Spans are not saved, so all items
+//! loaded from cache point to `Span::call_site()`.
+
+use std::{
+    cell::Cell,
+    fs,
+    hash::{Hash, Hasher},
+    io::Write,
+    path::{Path, PathBuf},
+    rc::Rc,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use lazy_static::lazy_static;
+use log::{debug, warn};
+use proc_macro2::{Ident, Span, TokenStream};
+use rustc_hash::{FxHashMap, FxHasher};
+use smol_str::SmolStr;
+use syn::{parse::Parse, Type};
+
+use crate::{
+    diagnostic_codes::Warnings,
+    error::Result,
+    file_cache::{self, FileWriteCache},
+    source_registry::SourceId,
+    typemap::{
+        ast::{DisplayToTokens, GenericTypeConv, SpannedSmolStr, TypeName},
+        parse::{self, TargetCfg},
+        parse_typemap_macro::{
+            CType, CTypes, FTypeConvRule, FTypeLeftRightPair, FTypeName, ForeignCode,
+            RTypeConvRule, TypeMapConvRuleInfo,
+        },
+        ty::{
+            add_rust_type_node, FTypeConvCode, ForeignConversationIntermediate,
+            ForeignConversationRule, ForeignTypeS, ForeignTypesStorage, RustTypeS,
+        },
+        ConvPathsCache, GenericInstancesCache, RulePrecedence, RustTypeIdx, TypeConvEdge, TypeMap,
+        TypesConvGraph,
+    },
+};
+
+static CACHE_MAGIC: &[u8] = b"RSWIGTMC";
+/// Increase if format of cache or content of `TypeMap` changed
+const CACHE_FORMAT_VERSION: u32 = 4;
+
+/// Where to look for already parsed types map
+#[derive(Clone, Copy)]
+pub(crate) enum ParseCache<'a> {
+    Disabled,
+    /// Share result of parsing between all `Generator`s of process
+    InMemory,
+    /// The same as `InMemory`, plus save result in directory
+    OnDisk(&'a Path),
+}
+
+lazy_static! {
+    static ref IN_MEMORY_CACHE: Mutex<FxHashMap<u64, Arc<Vec<u8>>>> =
+        Mutex::new(FxHashMap::default());
+}
+
+/// Load result of `parse::parse` from `cache`,
+/// if there is no suitable cache parse `code` and save result in cache
+pub(in crate::typemap) fn parse_with_cache(
+    cache: ParseCache,
+    name: SourceId,
+    code: &str,
+    target_cfg: &TargetCfg,
+    traits_usage_code: FxHashMap<Ident, String>,
+) -> Result<TypeMap> {
+    let cache_dir = match cache {
+        ParseCache::Disabled => return parse::parse(name, code, target_cfg, traits_usage_code),
+        ParseCache::InMemory => None,
+        ParseCache::OnDisk(dir) => Some(dir),
+    };
+    let key = cache_key(code, target_cfg, &traits_usage_code);
+    let cache_path = cache_dir.map(|dir| cache_path(dir, key));
+
+    let in_memory = IN_MEMORY_CACHE
+        .lock()
+        .ok()
+        .and_then(|cache| cache.get(&key).cloned());
+    if let Some(data) = in_memory {
+        if let Some(tmap) = load_type_map(&data, name, target_cfg) {
+            debug!("parse_with_cache: use in memory cache for {:?}", name);
+            if let (Some(cache_dir), Some(cache_path)) = (cache_dir, cache_path.as_ref()) {
+                if !cache_path.exists() {
+                    write_cache_file(cache_dir, cache_path, &data);
+                }
+            }
+            return Ok(tmap);
+        }
+    }
+    if let Some(cache_path) = cache_path.as_ref() {
+        if let Ok(data) = fs::read(cache_path) {
+            match load_type_map(&data, name, target_cfg) {
+                Some(tmap) => {
+                    debug!("parse_with_cache: use {}", cache_path.display());
+                    save_in_memory(key, data);
+                    return Ok(tmap);
+                }
+                None => warn!("Invalid types map cache {}", cache_path.display()),
+            }
+        }
+    }
+    let tmap = parse::parse(name, code, target_cfg, traits_usage_code)?;
+    if let Some(data) = save_type_map(&tmap, name) {
+        if let (Some(cache_dir), Some(cache_path)) = (cache_dir, cache_path.as_ref()) {
+            write_cache_file(cache_dir, cache_path, &data);
+        }
+        save_in_memory(key, data);
+    } else {
+        debug!("parse_with_cache: types map can not be cached");
+    }
+    Ok(tmap)
+}
+
+/// Parse `sources` in background thread and save results in in-memory cache.
+/// Nothing is written on disk, `parse_with_cache` does it as usual.
+/// Errors are ignored, they are reported by `parse_with_cache`,
+/// that has to parse code again in such case.
+pub(in crate::typemap) fn parse_in_background(
+    sources: Vec<(SourceId, String)>,
+    target_cfg: &TargetCfg,
+    traits_usage_code: Vec<(String, String)>,
+    cache: ParseCache,
+) -> Option<thread::JoinHandle<()>> {
+    let cache_dir = match cache {
+        ParseCache::Disabled => return None,
+        ParseCache::InMemory => None,
+        ParseCache::OnDisk(dir) => Some(dir.to_path_buf()),
+    };
+    let target_cfg = target_cfg.clone();
+    let res = thread::Builder::new()
+        .name("rust_swig typemap parser".into())
+        .spawn(move || {
+            let mut traits_usage_code: FxHashMap<Ident, String> = traits_usage_code
+                .into_iter()
+                .map(|(k, v)| (Ident::new(&k, Span::call_site()), v))
+                .collect();
+            for (name, code) in sources {
+                let key = cache_key(&code, &target_cfg, &traits_usage_code);
+                let in_memory = IN_MEMORY_CACHE
+                    .lock()
+                    .ok()
+                    .and_then(|cache| cache.get(&key).cloned())
+                    .and_then(|data| load_type_map(&data, name, &target_cfg));
+                let on_disk = || {
+                    let data = fs::read(cache_path(cache_dir.as_ref()?, key)).ok()?;
+                    let tmap = load_type_map(&data, name, &target_cfg)?;
+                    save_in_memory(key, data);
+                    Some(tmap)
+                };
+                let tmap = match in_memory.or_else(on_disk) {
+                    Some(tmap) => tmap,
+                    None => match parse::parse(name, &code, &target_cfg, traits_usage_code) {
+                        Ok(tmap) => {
+                            if let Some(data) = save_type_map(&tmap, name) {
+                                save_in_memory(key, data);
+                            }
+                            tmap
+                        }
+                        Err(_) => return,
+                    },
+                };
+                debug!("parse_in_background: {:?} done", name);
+                traits_usage_code = tmap.traits_usage_code;
+            }
+        });
+    match res {
+        Ok(handle) => Some(handle),
+        Err(err) => {
+            warn!("Can not start thread to parse types maps: {}", err);
+            None
+        }
+    }
+}
+
+fn save_in_memory(key: u64, data: Vec<u8>) {
+    if let Ok(mut cache) = IN_MEMORY_CACHE.lock() {
+        cache.insert(key, Arc::new(data));
+    }
+}
+
+fn write_cache_file(cache_dir: &Path, cache_path: &Path, data: &[u8]) {
+    let mut file = FileWriteCache::new(cache_path);
+    let res = file_cache::create_dir_all(cache_dir)
+        .and_then(|_| file.write_all(data))
+        .and_then(|_| file.update_file_if_necessary());
+    if let Err(err) = res {
+        warn!(
+            "Can not write types map cache {}: {}",
+            cache_path.display(),
+            err
+        );
+    }
+}
+
+fn cache_path(cache_dir: &Path, key: u64) -> PathBuf {
+    cache_dir.join(format!("typemap-{:016x}.bin", key))
+}
+
+fn cache_key(
+    code: &str,
+    target_cfg: &TargetCfg,
+    traits_usage_code: &FxHashMap<Ident, String>,
+) -> u64 {
+    let mut hasher = FxHasher::default();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    CACHE_FORMAT_VERSION.hash(&mut hasher);
+    code.hash(&mut hasher);
+    target_cfg.hash(&mut hasher);
+    let mut traits: Vec<(String, &String)> = traits_usage_code
+        .iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+    traits.sort();
+    traits.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn save_type_map(tmap: &TypeMap, name: SourceId) -> Option<Vec<u8>> {
+    if !tmap.foreign_classes.is_empty() || !tmap.exported_enums.is_empty() {
+        return None;
+    }
+    let mut enc = Encoder::default();
+    enc.buf.extend_from_slice(CACHE_MAGIC);
+    enc.u32(CACHE_FORMAT_VERSION);
+    enc.str(env!("CARGO_PKG_VERSION"));
+
+    let src_id = |id: SourceId| -> Option<bool> {
+        if id == name {
+            Some(true)
+        } else if id == SourceId::none() {
+            Some(false)
+        } else {
+            None
+        }
+    };
+
+    enc.usize(tmap.conv_graph.node_count());
+    for idx in tmap.conv_graph.node_indices() {
+        let rty = &tmap.conv_graph[idx];
+        enc.bool(src_id(rty.src_id)?);
+        enc.tokens(&rty.ty);
+        enc.str(&rty.normalized_name);
+        let implements: Vec<&SmolStr> = rty.implements.iter().collect();
+        enc.usize(implements.len());
+        for trait_name in implements {
+            enc.str(trait_name);
+        }
+    }
+    let mut rust_names: Vec<(&SmolStr, &RustTypeIdx)> = tmap.rust_names_map.iter().collect();
+    rust_names.sort();
+    enc.usize(rust_names.len());
+    for (name, idx) in rust_names {
+        enc.str(name);
+        enc.usize(idx.index());
+    }
+
+    enc.usize(tmap.conv_graph.edge_count());
+    for edge_idx in tmap.conv_graph.edge_indices() {
+        let (from, to) = tmap.conv_graph.edge_endpoints(edge_idx)?;
+        let edge = &tmap.conv_graph[edge_idx];
+        enc.usize(from.index());
+        enc.usize(to.index());
+        enc.str(&edge.code_template);
+        enc.opt_tokens(edge.dependency.borrow().as_ref());
+        enc.bool(src_id(edge.src_id)?);
+        enc.u32(edge.precedence as u32);
+        enc.bool(edge.force_override);
+        enc.u32(edge.cost);
+        enc.opt(edge.lossy.as_ref(), |enc, why| enc.str(why));
+        enc.bool(edge.fallible);
+    }
+
+    enc.usize(tmap.ftypes_storage.iter().count());
+    for ftype in tmap.ftypes_storage.iter() {
+        if ftype.name_prefix.is_some() {
+            return None;
+        }
+        enc.bool(src_id(ftype.name.span.0)?);
+        enc.str(&ftype.name.typename);
+        enc.smol_strs(&ftype.provides_by_module);
+        for rule in &[&ftype.into_from_rust, &ftype.from_into_rust] {
+            enc.opt(rule.as_ref(), |enc, rule| {
+                enc.usize(rule.rust_ty.index());
+                enc.opt(rule.intermediate.as_ref(), |enc, intermediate| {
+                    enc.usize(intermediate.intermediate_ty.index());
+                    enc.str(&intermediate.conv_code.to_string());
+                });
+            });
+        }
+    }
+
+    enc.usize(tmap.utils_code.len());
+    for item in &tmap.utils_code {
+        enc.tokens(item);
+    }
+
+    enc.usize(tmap.generic_edges.len());
+    for edge in &tmap.generic_edges {
+        enc.bool(src_id(edge.src_id)?);
+        enc.tokens(&edge.from_ty);
+        enc.tokens(&edge.to_ty);
+        enc.str(&edge.code_template);
+        enc.opt_tokens(edge.dependency.borrow().as_ref());
+        enc.tokens(&edge.generic_params);
+        enc.opt_tokens(edge.generic_params.where_clause.as_ref());
+        enc.opt(edge.to_foreigner_hint.as_ref(), |enc, x| enc.str(x));
+        enc.opt(edge.from_foreigner_hint.as_ref(), |enc, x| enc.str(x));
+        enc.u32(edge.cost);
+    }
+
+    let mut traits: Vec<(String, &String)> = tmap
+        .traits_usage_code
+        .iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+    traits.sort();
+    enc.usize(traits.len());
+    for (trait_name, code) in traits {
+        enc.str(&trait_name);
+        enc.str(code);
+    }
+
+    enc.usize(tmap.not_merged_data.len());
+    for rule in &tmap.not_merged_data {
+        enc.bool(src_id(rule.src_id)?);
+        for r_rule in &[&rule.rtype_left_to_right, &rule.rtype_right_to_left] {
+            enc.opt(r_rule.as_ref(), |enc, r_rule| {
+                enc.tokens(&r_rule.left_ty);
+                enc.opt_tokens(r_rule.right_ty.as_ref());
+                enc.opt(r_rule.code.as_ref(), |enc, x| enc.str(&x.to_string()));
+                enc.bool(r_rule.force_override);
+                enc.u32(r_rule.cost);
+                enc.bool(r_rule.fallible);
+            });
+        }
+        for f_rules in &[&rule.ftype_left_to_right, &rule.ftype_right_to_left] {
+            enc.usize(f_rules.len());
+            for f_rule in f_rules.iter() {
+                enc.smol_strs(&f_rule.req_modules);
+                enc.opt(f_rule.cfg_option.as_ref(), |enc, x| enc.str(&x.value));
+                let (left, right) = match f_rule.left_right_ty {
+                    FTypeLeftRightPair::OnlyLeft(ref l) => (Some(l), None),
+                    FTypeLeftRightPair::OnlyRight(ref r) => (None, Some(r)),
+                    FTypeLeftRightPair::Both(ref l, ref r) => (Some(l), Some(r)),
+                };
+                enc.opt(left, |enc, x| enc.str(&x.name));
+                enc.opt(right, |enc, x| enc.str(&x.name));
+                enc.opt(f_rule.code.as_ref(), |enc, x| enc.str(&x.to_string()));
+            }
+        }
+        let mut c_types_src_ok = true;
+        enc.opt(rule.c_types.as_ref(), |enc, c_types| {
+            c_types_src_ok = src_id(c_types.src_id).is_some();
+            enc.bool(c_types.src_id == name);
+            enc.str(&c_types.header_name);
+            enc.usize(c_types.types.len());
+            for c_type in &c_types.types {
+                match c_type {
+                    CType::Struct(ref s) => {
+                        enc.bool(true);
+                        enc.tokens(s);
+                    }
+                    CType::Union(ref u) => {
+                        enc.bool(false);
+                        enc.tokens(u);
+                    }
+                }
+            }
+        });
+        if !c_types_src_ok {
+            return None;
+        }
+        enc.usize(rule.f_code.len());
+        for f_code in &rule.f_code {
+            enc.str(&f_code.module_name);
+            enc.opt(f_code.cfg_option.as_ref(), |enc, x| enc.str(&x.value));
+            enc.str(&f_code.code);
+        }
+    }
+    Some(enc.buf)
+}
+
+fn load_type_map(data: &[u8], name: SourceId, target_cfg: &TargetCfg) -> Option<TypeMap> {
+    let mut dec = Decoder { data };
+    if dec.bytes(CACHE_MAGIC.len())? != CACHE_MAGIC
+        || dec.u32()? != CACHE_FORMAT_VERSION
+        || dec.str()? != env!("CARGO_PKG_VERSION")
+    {
+        return None;
+    }
+    let src_id = |is_our: bool| {
+        if is_our {
+            name
+        } else {
+            SourceId::none()
+        }
+    };
+
+    let mut conv_graph = TypesConvGraph::new();
+    for _ in 0..dec.len()? {
+        let rty_src_id = src_id(dec.bool()?);
+        let ty: Type = dec.parsed()?;
+        let normalized_name = dec.str()?;
+        let mut rty = RustTypeS::new_without_graph_idx(ty, normalized_name, rty_src_id);
+        for _ in 0..dec.len()? {
+            rty.implements.insert(dec.str()?.into());
+        }
+        add_rust_type_node(&mut conv_graph, rty);
+    }
+    let node_count = conv_graph.node_count();
+    let node_idx = |dec: &mut Decoder| -> Option<RustTypeIdx> {
+        let idx = RustTypeIdx::new(dec.usize()?);
+        if idx.index() < node_count {
+            Some(idx)
+        } else {
+            None
+        }
+    };
+    let mut rust_names_map = FxHashMap::default();
+    for _ in 0..dec.len()? {
+        let name = dec.str()?;
+        let idx = node_idx(&mut dec)?;
+        let name = if conv_graph[idx].normalized_name == name {
+            conv_graph[idx].normalized_name.clone()
+        } else {
+            name.into()
+        };
+        rust_names_map.insert(name, idx);
+    }
+
+    let mut edges = vec![];
+    for _ in 0..dec.len()? {
+        let from = node_idx(&mut dec)?;
+        let to = node_idx(&mut dec)?;
+        let code_template = dec.str()?;
+        let dependency = dec.opt(|dec| dec.tokens())?;
+        let edge = TypeConvEdge {
+            src_id: src_id(dec.bool()?),
+            precedence: match dec.u32()? {
+                x if x == RulePrecedence::BuiltIn as u32 => RulePrecedence::BuiltIn,
+                x if x == RulePrecedence::Merged as u32 => RulePrecedence::Merged,
+                x if x == RulePrecedence::User as u32 => RulePrecedence::User,
+                _ => return None,
+            },
+            force_override: dec.bool()?,
+            cost: dec.u32()?,
+            lossy: dec.opt(|dec| dec.str().map(str::to_string))?,
+            fallible: dec.bool()?,
+            ..TypeConvEdge::new(code_template, dependency)
+        };
+        edges.push((from, to, edge));
+    }
+    for (from, to, edge) in edges {
+        conv_graph.add_edge(from, to, edge);
+    }
+
+    let mut ftypes_storage = ForeignTypesStorage::default();
+    for _ in 0..dec.len()? {
+        let ftype_src_id = src_id(dec.bool()?);
+        let typename: SmolStr = dec.str()?.into();
+        let provides_by_module = dec.smol_strs()?;
+        let mut rules = [None, None];
+        for rule in &mut rules {
+            *rule = dec.opt(|dec| {
+                Some(ForeignConversationRule {
+                    rust_ty: node_idx(dec)?,
+                    intermediate: dec.opt(|dec| {
+                        Some(ForeignConversationIntermediate {
+                            intermediate_ty: node_idx(dec)?,
+                            conv_code: FTypeConvCode::new(dec.str()?, Span::call_site()),
+                        })
+                    })?,
+                })
+            })?;
+        }
+        let [into_from_rust, from_into_rust] = rules;
+        ftypes_storage
+            .add_new_ftype(ForeignTypeS {
+                name: TypeName::new(typename, (ftype_src_id, Span::call_site())),
+                provides_by_module,
+                into_from_rust,
+                from_into_rust,
+                name_prefix: None,
+            })
+            .ok()?;
+    }
+
+    let utils_code_len = dec.len()?;
+    let mut utils_code = Vec::with_capacity(utils_code_len);
+    for _ in 0..utils_code_len {
+        utils_code.push(syn::Item::Verbatim(syn::ItemVerbatim {
+            tts: dec.tokens()?,
+        }));
+    }
+
+    let mut generic_edges = vec![];
+    for _ in 0..dec.len()? {
+        let edge_src_id = src_id(dec.bool()?);
+        let from_ty: Type = dec.parsed()?;
+        let to_ty: Type = dec.parsed()?;
+        let code_template = dec.str()?;
+        let dependency = dec.opt(|dec| dec.tokens())?;
+        let mut generic_params: syn::Generics = dec.parsed()?;
+        generic_params.where_clause = dec.opt(|dec| dec.parsed())?;
+        generic_edges.push(GenericTypeConv {
+            src_id: edge_src_id,
+            from_ty,
+            to_ty,
+            code_template: code_template.into(),
+            dependency: Rc::new(dependency.into()),
+            generic_params,
+            to_foreigner_hint: dec.opt(|dec| dec.str().map(str::to_string))?,
+            from_foreigner_hint: dec.opt(|dec| dec.str().map(str::to_string))?,
+            cost: dec.u32()?,
+            used: Rc::new(Cell::new(false)),
+        });
+    }
+
+    let mut traits_usage_code = FxHashMap::default();
+    for _ in 0..dec.len()? {
+        let trait_name: Ident = dec.parsed()?;
+        traits_usage_code.insert(trait_name, dec.str()?.to_string());
+    }
+
+    let mut not_merged_data = vec![];
+    for _ in 0..dec.len()? {
+        let rule_src_id = src_id(dec.bool()?);
+        let mut r_rules = [None, None];
+        for r_rule in &mut r_rules {
+            *r_rule = dec.opt(|dec| {
+                Some(RTypeConvRule {
+                    left_ty: dec.parsed()?,
+                    right_ty: dec.opt(|dec| dec.parsed())?,
+                    code: dec.opt(|dec| Some(FTypeConvCode::new(dec.str()?, Span::call_site())))?,
+                    force_override: dec.bool()?,
+                    cost: dec.u32()?,
+                    fallible: dec.bool()?,
+                })
+            })?;
+        }
+        let [rtype_left_to_right, rtype_right_to_left] = r_rules;
+        let mut f_rules = [vec![], vec![]];
+        for f_rules in &mut f_rules {
+            for _ in 0..dec.len()? {
+                let req_modules = dec.smol_strs()?;
+                let cfg_option = dec.opt(|dec| dec.spanned_smol_str())?;
+                let left = dec.opt(|dec| dec.ftype_name())?;
+                let right = dec.opt(|dec| dec.ftype_name())?;
+                let left_right_ty = match (left, right) {
+                    (Some(l), None) => FTypeLeftRightPair::OnlyLeft(l),
+                    (None, Some(r)) => FTypeLeftRightPair::OnlyRight(r),
+                    (Some(l), Some(r)) => FTypeLeftRightPair::Both(l, r),
+                    (None, None) => return None,
+                };
+                f_rules.push(FTypeConvRule {
+                    req_modules,
+                    cfg_option,
+                    left_right_ty,
+                    code: dec.opt(|dec| Some(FTypeConvCode::new(dec.str()?, Span::call_site())))?,
+                });
+            }
+        }
+        let [ftype_left_to_right, ftype_right_to_left] = f_rules;
+        let c_types = dec.opt(|dec| {
+            let c_types_src_id = src_id(dec.bool()?);
+            let header_name: SmolStr = dec.str()?.into();
+            let mut types = vec![];
+            for _ in 0..dec.len()? {
+                types.push(if dec.bool()? {
+                    CType::Struct(dec.parsed()?)
+                } else {
+                    CType::Union(dec.parsed()?)
+                });
+            }
+            Some(CTypes {
+                header_name,
+                src_id: c_types_src_id,
+                types,
+            })
+        })?;
+        let mut f_code = vec![];
+        for _ in 0..dec.len()? {
+            f_code.push(ForeignCode {
+                module_name: dec.str()?.into(),
+                cfg_option: dec.opt(|dec| dec.spanned_smol_str())?,
+                code: dec.str()?.to_string(),
+            });
+        }
+        not_merged_data.push(TypeMapConvRuleInfo {
+            src_id: rule_src_id,
+            rtype_left_to_right,
+            rtype_right_to_left,
+            ftype_left_to_right,
+            ftype_right_to_left,
+            c_types,
+            f_code,
+        });
+    }
+    if !dec.data.is_empty() {
+        return None;
+    }
+
+    Some(TypeMap {
+        conv_graph,
+        ftypes_storage,
+        rust_to_foreign_cache: FxHashMap::default(),
+        conv_paths_cache: ConvPathsCache::default(),
+        generic_instances_cache: GenericInstancesCache::default(),
+        rust_names_map,
+        utils_code,
+        generic_edges,
+        foreign_classes: Vec::new(),
+        exported_enums: FxHashMap::default(),
+        traits_usage_code,
+        not_merged_data,
+        src_precedence: FxHashMap::default(),
+        target_cfg: target_cfg.clone(),
+        class_scope: None,
+        class_scope_rules: Vec::new(),
+        fallible_conversations: 0,
+        warnings: Warnings::default(),
+        strict_conversions: false,
+    })
+}
+
+#[derive(Default)]
+struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    fn u32(&mut self, x: u32) {
+        self.buf.extend_from_slice(&x.to_le_bytes());
+    }
+    fn usize(&mut self, x: usize) {
+        self.buf.extend_from_slice(&(x as u64).to_le_bytes());
+    }
+    fn bool(&mut self, x: bool) {
+        self.buf.push(x as u8);
+    }
+    fn str(&mut self, x: &str) {
+        self.usize(x.len());
+        self.buf.extend_from_slice(x.as_bytes());
+    }
+    fn smol_strs(&mut self, x: &[SmolStr]) {
+        self.usize(x.len());
+        for s in x {
+            self.str(s);
+        }
+    }
+    fn tokens<T: quote::ToTokens>(&mut self, x: &T) {
+        self.str(&DisplayToTokens(x).to_string());
+    }
+    fn opt_tokens<T: quote::ToTokens>(&mut self, x: Option<&T>) {
+        self.opt(x, |enc, x| enc.tokens(x));
+    }
+    fn opt<T, F: FnOnce(&mut Encoder, T)>(&mut self, x: Option<T>, f: F) {
+        match x {
+            Some(x) => {
+                self.bool(true);
+                f(self, x);
+            }
+            None => self.bool(false),
+        }
+    }
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn bytes(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.data.len() < n {
+            return None;
+        }
+        let (ret, tail) = self.data.split_at(n);
+        self.data = tail;
+        Some(ret)
+    }
+    fn u32(&mut self) -> Option<u32> {
+        let mut x = [0u8; 4];
+        x.copy_from_slice(self.bytes(4)?);
+        Some(u32::from_le_bytes(x))
+    }
+    fn usize(&mut self) -> Option<usize> {
+        let mut x = [0u8; 8];
+        x.copy_from_slice(self.bytes(8)?);
+        let x = u64::from_le_bytes(x);
+        if x > usize::MAX as u64 {
+            None
+        } else {
+            Some(x as usize)
+        }
+    }
+    /// Length of sequence, each element takes at least one byte
+    fn len(&mut self) -> Option<usize> {
+        let len = self.usize()?;
+        if len > self.data.len() {
+            None
+        } else {
+            Some(len)
+        }
+    }
+    fn bool(&mut self) -> Option<bool> {
+        match self.bytes(1)?[0] {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+    fn str(&mut self) -> Option<&'a str> {
+        let len = self.len()?;
+        std::str::from_utf8(self.bytes(len)?).ok()
+    }
+    fn smol_strs(&mut self) -> Option<Vec<SmolStr>> {
+        let len = self.len()?;
+        let mut ret = Vec::with_capacity(len);
+        for _ in 0..len {
+            ret.push(self.str()?.into());
+        }
+        Some(ret)
+    }
+    fn tokens(&mut self) -> Option<TokenStream> {
+        TokenStream::from_str(self.str()?).ok()
+    }
+    fn parsed<T: Parse>(&mut self) -> Option<T> {
+        syn::LitStr::new(self.str()?, Span::call_site())
+            .parse()
+            .ok()
+    }
+    fn spanned_smol_str(&mut self) -> Option<SpannedSmolStr> {
+        Some(SpannedSmolStr {
+            sp: Span::call_site(),
+            value: self.str()?.into(),
+        })
+    }
+    fn ftype_name(&mut self) -> Option<FTypeName> {
+        Some(FTypeName {
+            name: self.str()?.into(),
+            sp: Span::call_site(),
+        })
+    }
+    fn opt<T, F: FnOnce(&mut Decoder<'a>) -> Option<T>>(&mut self, f: F) -> Option<Option<T>> {
+        if self.bool()? {
+            f(self).map(Some)
+        } else {
+            Some(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{source_registry::SourceRegistry, SourceCode};
+
+    #[test]
+    fn test_type_map_cache() {
+        let _ = env_logger::try_init();
+        let tmp_dir = tempfile::tempdir().expect("Can not create tmp dir");
+        for (name, code) in &[
+            ("jni-include.rs", include_str!("../java_jni/jni-include.rs")),
+            ("cpp-include.rs", include_str!("../cpp/cpp-include.rs")),
+        ] {
+            let mut src_reg = SourceRegistry::default();
+            let src_id = src_reg.register(SourceCode {
+                id_of_code: name.to_string(),
+                code: code.to_string(),
+            });
+            let target_cfg = TargetCfg {
+                pointer_width: 64,
+                ..TargetCfg::default()
+            };
+            let parsed = parse_with_cache(
+                ParseCache::OnDisk(tmp_dir.path()),
+                src_id,
+                code,
+                &target_cfg,
+                FxHashMap::default(),
+            )
+            .unwrap();
+            let key = cache_key(code, &target_cfg, &FxHashMap::default());
+            let data = fs::read(cache_path(tmp_dir.path(), key)).unwrap();
+            assert_eq!(
+                Some(&data),
+                IN_MEMORY_CACHE.lock().unwrap().get(&key).map(|x| &**x)
+            );
+            let cached = load_type_map(&data, src_id, &target_cfg).unwrap();
+            assert_eq!(data, save_type_map(&cached, src_id).unwrap());
+            assert!(load_type_map(&data[..data.len() - 1], src_id, &target_cfg).is_none());
+            assert_eq!(
+                format!("{}", parsed.conv_graph_as_dot(|_| String::new())),
+                format!("{}", cached.conv_graph_as_dot(|_| String::new()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_in_memory_type_map_cache() {
+        let _ = env_logger::try_init();
+        let code = r#"
+foreign_typemap!(
+    ($p:r_type) u8 => i16 { $out = $p as i16; };
+);
+"#;
+        let mut src_reg = SourceRegistry::default();
+        let src_id = src_reg.register(SourceCode {
+            id_of_code: "test_in_memory_type_map_cache".into(),
+            code: code.to_string(),
+        });
+        let target_cfg = TargetCfg {
+            pointer_width: 64,
+            ..TargetCfg::default()
+        };
+        let key = cache_key(code, &target_cfg, &FxHashMap::default());
+        let parse = |cache| {
+            parse_with_cache(cache, src_id, code, &target_cfg, FxHashMap::default()).unwrap()
+        };
+        let not_cached = parse(ParseCache::Disabled);
+        assert!(!IN_MEMORY_CACHE.lock().unwrap().contains_key(&key));
+        let parsed = parse(ParseCache::InMemory);
+        assert!(IN_MEMORY_CACHE.lock().unwrap().contains_key(&key));
+        let cached = parse(ParseCache::InMemory);
+        for tmap in &[parsed, cached] {
+            assert_eq!(
+                format!("{}", not_cached.conv_graph_as_dot(|_| String::new())),
+                format!("{}", tmap.conv_graph_as_dot(|_| String::new()))
+            );
+        }
+
+        let tmp_dir = tempfile::tempdir().expect("Can not create tmp dir");
+        parse(ParseCache::OnDisk(tmp_dir.path()));
+        assert!(cache_path(tmp_dir.path(), key).exists());
+    }
+
+    #[test]
+    fn test_parse_in_background() {
+        let _ = env_logger::try_init();
+        let traits_code = r#"
+#[swig_code = "let {to_var}: {to_var_type} = <{to_var_type}>::swig_from({from_var});"]
+trait SwigFrom<T> {
+    fn swig_from(_: T) -> Self;
+}
+"#;
+        let rules_code = r#"
+impl SwigFrom<u8> for i16 {
+    fn swig_from(x: u8) -> Self {
+        x as i16
+    }
+}
+"#;
+        let mut src_reg = SourceRegistry::default();
+        let sources: Vec<(SourceId, String)> = [traits_code, rules_code]
+            .iter()
+            .enumerate()
+            .map(|(i, code)| {
+                let src_id = src_reg.register(SourceCode {
+                    id_of_code: format!("test_parse_in_background{}", i),
+                    code: code.to_string(),
+                });
+                (src_id, code.to_string())
+            })
+            .collect();
+        let target_cfg = TargetCfg {
+            pointer_width: 64,
+            ..TargetCfg::default()
+        };
+        assert!(
+            parse_in_background(sources.clone(), &target_cfg, vec![], ParseCache::Disabled)
+                .is_none()
+        );
+        parse_in_background(sources.clone(), &target_cfg, vec![], ParseCache::InMemory)
+            .unwrap()
+            .join()
+            .unwrap();
+
+        let mut traits_usage_code = FxHashMap::default();
+        for (src_id, code) in &sources {
+            let key = cache_key(code, &target_cfg, &traits_usage_code);
+            let data = IN_MEMORY_CACHE.lock().unwrap().get(&key).cloned().unwrap();
+            let cached = load_type_map(&data, *src_id, &target_cfg).unwrap();
+            let parsed = parse::parse(*src_id, code, &target_cfg, traits_usage_code).unwrap();
+            assert_eq!(
+                format!("{}", parsed.conv_graph_as_dot(|_| String::new())),
+                format!("{}", cached.conv_graph_as_dot(|_| String::new()))
+            );
+            traits_usage_code = parsed.traits_usage_code;
+        }
+    }
+}
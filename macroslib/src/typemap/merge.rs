@@ -1,19 +1,22 @@
-use crate::typemap::ty::ForeignConversationRule;
-use std::{mem, rc::Rc};
+use crate::typemap::ty::{add_rust_type_node, ForeignConversationRule};
+use std::{mem, thread};
 
-use log::{debug, info};
+use log::debug;
 use petgraph::graph::NodeIndex;
+use proc_macro2::{Ident, Span};
 use rustc_hash::FxHashMap;
 use syn::spanned::Spanned;
 
 use crate::{
-    error::{DiagnosticError, Result},
+    diagnostic_codes::DiagnosticCode,
+    error::{DiagnosticError, Result, SourceIdSpan},
     source_registry::SourceId,
     typemap::{
         ast::TypeName,
+        parse::{self, TargetCfg, SWIG_FALLIBLE_TRAIT},
         parse_typemap_macro::{FTypeLeftRightPair, TypeMapConvRuleInfo},
         ty::{ForeignConversationIntermediate, ForeignTypeS, ForeignTypesStorage},
-        TypeConvEdge, TypeMap,
+        ParseCache, TypeConvEdge, TypeMap, FROM_VAR_TEMPLATE, TO_VAR_TEMPLATE,
     },
 };
 
@@ -24,21 +27,94 @@ impl TypeMap {
         code: &str,
         target_pointer_width: usize,
     ) -> Result<()> {
+        self.merge_with_cache(id_of_code, code, target_pointer_width, ParseCache::Disabled)
+    }
+
+    /// The same as `merge`, but try to load parsed `code`
+    /// from `cache`, instead of parsing it
+    pub(crate) fn merge_with_cache(
+        &mut self,
+        id_of_code: SourceId,
+        code: &str,
+        target_pointer_width: usize,
+        cache: ParseCache,
+    ) -> Result<()> {
+        self.merge_new_data(
+            id_of_code,
+            target_pointer_width,
+            |target_cfg, traits_usage_code| {
+                crate::typemap::cache::parse_with_cache(
+                    cache,
+                    id_of_code,
+                    code,
+                    target_cfg,
+                    traits_usage_code,
+                )
+            },
+        )
+    }
+
+    /// The same as `merge`, but for `file` returned by `parse_file`
+    pub(crate) fn merge_parsed(
+        &mut self,
+        id_of_code: SourceId,
+        file: syn::File,
+        target_pointer_width: usize,
+    ) -> Result<()> {
+        self.merge_new_data(
+            id_of_code,
+            target_pointer_width,
+            |target_cfg, traits_usage_code| {
+                parse::parse_items(id_of_code, file, target_cfg, traits_usage_code)
+            },
+        )
+    }
+
+    /// Parse `sources` in background thread, and put results into in-memory cache,
+    /// so `merge_with_cache` for them just loads them. `sources` should be merged
+    /// in the same order and before any other types maps.
+    pub(crate) fn parse_in_background(
+        &self,
+        sources: Vec<(SourceId, String)>,
+        target_pointer_width: usize,
+        cache: ParseCache,
+    ) -> Option<thread::JoinHandle<()>> {
+        let target_cfg = TargetCfg {
+            pointer_width: target_pointer_width,
+            ..self.target_cfg.clone()
+        };
+        let traits_usage_code = self
+            .traits_usage_code
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect();
+        crate::typemap::cache::parse_in_background(sources, &target_cfg, traits_usage_code, cache)
+    }
+
+    fn merge_new_data<F>(
+        &mut self,
+        id_of_code: SourceId,
+        target_pointer_width: usize,
+        parse_new_data: F,
+    ) -> Result<()>
+    where
+        F: FnOnce(&TargetCfg, FxHashMap<Ident, String>) -> Result<TypeMap>,
+    {
         debug!("TypeMap::merge {:?} with our rules", id_of_code);
         self.rust_to_foreign_cache.clear();
+        self.conv_paths_cache.borrow_mut().clear();
         let mut was_traits_usage_code = FxHashMap::default();
         mem::swap(&mut was_traits_usage_code, &mut self.traits_usage_code);
-        let mut new_data = crate::typemap::parse::parse(
-            id_of_code,
-            code,
-            target_pointer_width,
-            was_traits_usage_code,
-        )?;
+        let target_cfg = TargetCfg {
+            pointer_width: target_pointer_width,
+            ..self.target_cfg.clone()
+        };
+        let mut new_data = parse_new_data(&target_cfg, was_traits_usage_code)?;
         mem::swap(&mut new_data.traits_usage_code, &mut self.traits_usage_code);
 
         let mut new_node_to_our_map = FxHashMap::<NodeIndex, NodeIndex>::default();
         add_new_nodes(&new_data, self, &mut new_node_to_our_map);
-        add_new_edges(&new_data, self, &new_node_to_our_map);
+        add_new_edges(id_of_code, &new_data, self, &new_node_to_our_map);
         let TypeMap {
             ftypes_storage: new_ftypes_storage,
             generic_edges: mut new_generic_edges,
@@ -51,6 +127,7 @@ impl TypeMap {
         self.utils_code.append(&mut new_utils_code);
         //TODO: more intellect to process new generics
         self.generic_edges.append(&mut new_generic_edges);
+        self.generic_instances_cache.clear();
         //TODO: add more checks
         self.not_merged_data.append(&mut new_not_merged_data);
         Ok(())
@@ -76,12 +153,10 @@ impl TypeMap {
     ) -> Result<()> {
         assert!(!ri.contains_data_for_language_backend());
         if let Some((r_ty, f_ty, req_modules)) = ri.if_simple_rtype_ftype_map() {
-            let r_ty = self.find_or_alloc_rust_type(r_ty, src_id).graph_idx;
+            let r_ty = self.find_or_alloc_rust_type(r_ty, src_id);
 
-            let ftype_idx = self.add_foreign_rust_ty_idx(
-                TypeName::new(f_ty.name.clone(), (src_id, f_ty.sp)),
-                r_ty,
-            )?;
+            let ftype_idx =
+                self.add_foreign(r_ty, TypeName::new(f_ty.name.clone(), (src_id, f_ty.sp)))?;
             self.ftypes_storage[ftype_idx].provides_by_module =
                 req_modules.iter().cloned().collect();
 
@@ -100,12 +175,23 @@ impl TypeMap {
                 ));
             };
 
-            let from_ty = self
-                .find_or_alloc_rust_type(&rule.left_ty, src_id)
-                .graph_idx;
-            let to_ty = self.find_or_alloc_rust_type(&right_ty, src_id).graph_idx;
-            self.conv_graph
-                .update_edge(from_ty, to_ty, TypeConvEdge::new(code.into(), None));
+            let from_ty = self.find_or_alloc_rust_type(&rule.left_ty, src_id);
+            let to_ty = self.find_or_alloc_rust_type(&right_ty, src_id);
+            let code = if rule.fallible {
+                self.fallible_conv_code((src_id, rule.left_ty.span()), code.into())?
+            } else {
+                code.into()
+            };
+            let edge = TypeConvEdge {
+                fallible: rule.fallible,
+                ..self.new_rule_edge(
+                    (src_id, rule.left_ty.span()),
+                    code,
+                    rule.force_override,
+                    rule.cost,
+                )
+            };
+            self.update_conv_edge(from_ty, to_ty, edge);
             rtype_left_to_right = Some((from_ty, to_ty));
         }
 
@@ -121,12 +207,23 @@ impl TypeMap {
                 ));
             };
 
-            let to_ty = self
-                .find_or_alloc_rust_type(&rule.left_ty, src_id)
-                .graph_idx;
-            let from_ty = self.find_or_alloc_rust_type(&right_ty, src_id).graph_idx;
-            self.conv_graph
-                .update_edge(from_ty, to_ty, TypeConvEdge::new(code.into(), None));
+            let to_ty = self.find_or_alloc_rust_type(&rule.left_ty, src_id);
+            let from_ty = self.find_or_alloc_rust_type(&right_ty, src_id);
+            let code = if rule.fallible {
+                self.fallible_conv_code((src_id, rule.left_ty.span()), code.into())?
+            } else {
+                code.into()
+            };
+            let edge = TypeConvEdge {
+                fallible: rule.fallible,
+                ..self.new_rule_edge(
+                    (src_id, rule.left_ty.span()),
+                    code,
+                    rule.force_override,
+                    rule.cost,
+                )
+            };
+            self.update_conv_edge(from_ty, to_ty, edge);
             rtype_right_to_left = Some((from_ty, to_ty));
         }
 
@@ -232,22 +329,46 @@ impl TypeMap {
                     )
                     .add_span_note((src_id, ft2.sp), format!("another type is {}", ft2.name)));
                 }
-                let name = TypeName::new(ft1.name, (src_id, ft1.sp));
+                let sp = (src_id, ft1.sp);
+                let name = TypeName::new(ft1.name, sp);
                 let ftype_idx = self.ftypes_storage.find_or_alloc(name);
+                check_ftype_rules_conflict(
+                    self,
+                    &self.ftypes_storage[ftype_idx],
+                    sp,
+                    Some(&into_from_rust),
+                    Some(&from_into_rust),
+                )?;
                 let res_ftype = &mut self.ftypes_storage[ftype_idx];
                 res_ftype.into_from_rust = Some(into_from_rust);
                 res_ftype.from_into_rust = Some(from_into_rust);
                 res_ftype.provides_by_module = req_modules;
             }
             (Some((ft, into_from_rust)), None) => {
-                let name = TypeName::new(ft.name, (src_id, ft.sp));
+                let sp = (src_id, ft.sp);
+                let name = TypeName::new(ft.name, sp);
                 let ftype_idx = self.ftypes_storage.find_or_alloc(name);
+                check_ftype_rules_conflict(
+                    self,
+                    &self.ftypes_storage[ftype_idx],
+                    sp,
+                    Some(&into_from_rust),
+                    None,
+                )?;
                 self.ftypes_storage[ftype_idx].into_from_rust = Some(into_from_rust);
                 self.ftypes_storage[ftype_idx].provides_by_module = req_modules;
             }
             (None, Some((ft, from_into_rust))) => {
-                let name = TypeName::new(ft.name, (src_id, ft.sp));
+                let sp = (src_id, ft.sp);
+                let name = TypeName::new(ft.name, sp);
                 let ftype_idx = self.ftypes_storage.find_or_alloc(name);
+                check_ftype_rules_conflict(
+                    self,
+                    &self.ftypes_storage[ftype_idx],
+                    sp,
+                    None,
+                    Some(&from_into_rust),
+                )?;
                 self.ftypes_storage[ftype_idx].from_into_rust = Some(from_into_rust);
                 self.ftypes_storage[ftype_idx].provides_by_module = req_modules;
             }
@@ -256,6 +377,29 @@ impl TypeMap {
 
         Ok(())
     }
+
+    /// Code of fallible rule produces `Result`, so add code
+    /// to report error to foreign language, it is language specific
+    /// and defined by `swig_code` attribute of `SwigFallible` trait
+    fn fallible_conv_code(&self, sp: SourceIdSpan, code: String) -> Result<String> {
+        let report_err_code = self
+            .traits_usage_code
+            .get(&Ident::new(SWIG_FALLIBLE_TRAIT, Span::call_site()))
+            .ok_or_else(|| {
+                DiagnosticError::new2(
+                    sp,
+                    format!(
+                        "fallible rules not supported: no {} trait in types maps",
+                        SWIG_FALLIBLE_TRAIT
+                    ),
+                )
+            })?;
+        Ok(format!(
+            "{}\n    {}",
+            code,
+            report_err_code.replace(FROM_VAR_TEMPLATE, TO_VAR_TEMPLATE)
+        ))
+    }
 }
 
 fn add_new_nodes(
@@ -269,21 +413,19 @@ fn add_new_nodes(
         let data_conv_graph = &mut data.conv_graph;
         let data_idx = *data_rust_names_map
             .entry(new_node.normalized_name.clone())
-            .or_insert_with(|| {
-                let idx = data_conv_graph.add_node((*new_node).clone());
-                Rc::make_mut(&mut data_conv_graph[idx]).graph_idx = idx;
-                idx
-            });
-        Rc::make_mut(&mut data_conv_graph[data_idx]).merge(&new_data.conv_graph[new_node_idx]);
+            .or_insert_with(|| add_rust_type_node(data_conv_graph, new_node.clone()));
+        data_conv_graph[data_idx].merge(&new_data.conv_graph[new_node_idx]);
         new_node_to_our_map.insert(new_node_idx, data_idx);
     }
 }
 
 fn add_new_edges(
+    src_id: SourceId,
     new_data: &TypeMap,
     data: &mut TypeMap,
     new_node_to_our_map: &FxHashMap<NodeIndex, NodeIndex>,
 ) {
+    let precedence = data.rule_precedence(src_id);
     for (new_node_idx, our_idx) in new_node_to_our_map {
         let mut new_edges = new_data
             .conv_graph
@@ -293,20 +435,12 @@ fn add_new_edges(
             let our_target = *new_node_to_our_map
                 .get(&new_target)
                 .expect("At this step we should have full map new -> our");
-            if let Some(existing_edge) = data.conv_graph.find_edge(*our_idx, our_target) {
-                info!(
-                    "typemap merge: replace {:?} with new conversation rule {:?}, for {} -> {}",
-                    data.conv_graph[existing_edge],
-                    new_data.conv_graph[new_edge],
-                    data.conv_graph[*our_idx],
-                    data.conv_graph[our_target],
-                );
-            }
-            data.conv_graph.update_edge(
-                *our_idx,
-                our_target,
-                new_data.conv_graph[new_edge].clone(),
-            );
+            let edge = TypeConvEdge {
+                src_id,
+                precedence,
+                ..new_data.conv_graph[new_edge].clone()
+            };
+            data.update_conv_edge(*our_idx, our_target, edge);
         }
     }
 }
@@ -323,6 +457,13 @@ fn add_new_ftypes(
             .find_ftype_by_name(new_ftype.name.as_str())
         {
             Some(ftype_idx) => {
+                check_ftype_rules_conflict(
+                    data,
+                    &data.ftypes_storage[ftype_idx],
+                    new_ftype.name.span,
+                    new_ftype.into_from_rust.as_ref(),
+                    new_ftype.from_into_rust.as_ref(),
+                )?;
                 ftype_merge(&mut data.ftypes_storage[ftype_idx], new_ftype);
             }
             None => {
@@ -359,6 +500,45 @@ fn ftype_rule_map_rust_type(
     }
 }
 
+/// Rules for the same foreign type, that bind it to different Rust types,
+/// are conflicting: later rule silently breaks usage of earlier one
+fn check_ftype_rules_conflict(
+    data: &TypeMap,
+    our: &ForeignTypeS,
+    new_sp: SourceIdSpan,
+    new_into_from_rust: Option<&ForeignConversationRule>,
+    new_from_into_rust: Option<&ForeignConversationRule>,
+) -> Result<()> {
+    let rules = [
+        (our.into_from_rust.as_ref(), new_into_from_rust, "from"),
+        (our.from_into_rust.as_ref(), new_from_into_rust, "to"),
+    ];
+    for &(our_rule, new_rule, direction) in &rules {
+        let (our_rule, new_rule) = match (our_rule, new_rule) {
+            (Some(our_rule), Some(new_rule)) => (our_rule, new_rule),
+            _ => continue,
+        };
+        if our_rule.rust_ty == new_rule.rust_ty {
+            continue;
+        }
+        let mut err = DiagnosticError::new2(
+            new_sp,
+            format!(
+                "conflicting rules for foreign type {}: it is converted {} Rust type {}, \
+                 but previous rule converts it {} {}",
+                our.name, direction, data[new_rule.rust_ty], direction, data[our_rule.rust_ty]
+            ),
+        )
+        .with_code(DiagnosticCode::ConflictingForeignTypeRules);
+        err.span_note(
+            our.name.span,
+            format!("previous rule for foreign type {} defined here", our.name),
+        );
+        return Err(err);
+    }
+    Ok(())
+}
+
 fn ftype_merge(our: &mut ForeignTypeS, extrn_ft: ForeignTypeS) {
     if let Some(rule) = extrn_ft.into_from_rust {
         our.into_from_rust = Some(rule);
@@ -371,7 +551,12 @@ fn ftype_merge(our: &mut ForeignTypeS, extrn_ft: ForeignTypeS) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{error::invalid_src_id_span, typemap::find_conversation_path};
+    use crate::{
+        error::invalid_src_id_span,
+        source_registry::SourceRegistry,
+        typemap::{find_conversation_path, RulePrecedence},
+        SourceCode,
+    };
     use rustc_hash::FxHashSet;
     use syn::{parse_quote, Type};
 
@@ -460,7 +645,7 @@ fn helper3() {
         let ty_i32 = types_map.find_or_alloc_rust_type(&parse_type! { i32 }, SourceId::none());
         let fti = types_map
             .map_through_conversation_to_foreign(
-                &ty_i32,
+                ty_i32,
                 petgraph::Direction::Outgoing,
                 invalid_src_id_span(),
                 |_, fc| {
@@ -477,7 +662,7 @@ fn helper3() {
                 let from = types_map.rust_names_map["jboolean"];
                 let to = types_map.rust_names_map["bool"];
                 let conv = &types_map.conv_graph[types_map.conv_graph.find_edge(from, to).unwrap()];
-                conv.code_template.clone()
+                conv.code_template.to_string()
             },
         );
 
@@ -510,4 +695,122 @@ fn helper3() {
             vec!["helper1", "SwigInto", "SwigFrom", "helper2", "helper3"]
         );
     }
+
+    #[test]
+    fn test_merge_rules_precedence() {
+        let _ = env_logger::try_init();
+        let mut src_reg = SourceRegistry::default();
+        let mut types_map = TypeMap::default();
+        fn merge_rule(
+            (src_reg, types_map): (&mut SourceRegistry, &mut TypeMap),
+            name: &str,
+            code: &str,
+            precedence: RulePrecedence,
+        ) {
+            let src_id = src_reg.register(SourceCode {
+                id_of_code: name.into(),
+                code: code.into(),
+            });
+            types_map.set_rule_precedence(src_id, precedence);
+            types_map.merge(src_id, src_reg.src(src_id), 64).unwrap();
+        }
+        merge_rule(
+            (&mut src_reg, &mut types_map),
+            "builtin",
+            r#"foreign_typemap!(($p:r_type) i32 => i64 { $out = $p as i64 + 1 };);"#,
+            RulePrecedence::BuiltIn,
+        );
+        merge_rule(
+            (&mut src_reg, &mut types_map),
+            "merged",
+            r#"foreign_typemap!(($p:r_type) i32 => i64 { $out = $p as i64 + 2 };);"#,
+            RulePrecedence::Merged,
+        );
+        merge_rule(
+            (&mut src_reg, &mut types_map),
+            "builtin2",
+            r#"foreign_typemap!(($p:r_type) i32 => i64 { $out = $p as i64 + 3 };);"#,
+            RulePrecedence::BuiltIn,
+        );
+        let from = types_map.rust_names_map["i32"];
+        let to = types_map.rust_names_map["i64"];
+        let edge_code = |types_map: &TypeMap| {
+            let edge = types_map.conv_graph.find_edge(from, to).unwrap();
+            types_map.conv_graph[edge].code_template.clone()
+        };
+        assert!(edge_code(&types_map).contains("+ 2"));
+
+        merge_rule(
+            (&mut src_reg, &mut types_map),
+            "builtin3",
+            r#"foreign_typemap!(($p:r_type, force_override) i32 => i64 { $out = $p as i64 + 4 };);"#,
+            RulePrecedence::BuiltIn,
+        );
+        assert!(edge_code(&types_map).contains("+ 4"));
+    }
+
+    #[test]
+    fn test_merge_conflicting_ftype_rules() {
+        let _ = env_logger::try_init();
+        let mut src_reg = SourceRegistry::default();
+        let mut types_map = TypeMap::default();
+        let mut merge = |name: &str, code: &str| {
+            let src_id = src_reg.register(SourceCode {
+                id_of_code: name.into(),
+                code: code.into(),
+            });
+            types_map.merge(src_id, src_reg.src(src_id), 64)
+        };
+        merge(
+            "builtin",
+            r#"
+mod swig_foreign_types_map {
+    #![swig_foreigner_type="int"]
+    #![swig_rust_type="jint"]
+}
+"#,
+        )
+        .unwrap();
+        merge(
+            "same",
+            r#"
+mod swig_foreign_types_map {
+    #![swig_foreigner_type="int"]
+    #![swig_rust_type="jint"]
+}
+"#,
+        )
+        .unwrap();
+        let err = merge(
+            "user1",
+            r#"
+mod swig_foreign_types_map {
+    #![swig_foreigner_type="int"]
+    #![swig_rust_type="jlong"]
+}
+"#,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains(
+            "conflicting rules for foreign type int: it is converted from Rust type jlong, \
+             but previous rule converts it from jint"
+        ));
+        assert!(err.contains("previous rule for foreign type int defined here"));
+        let err = merge(
+            "user2",
+            r#"
+foreign_typemap!(
+    ($p:r_type) u32 => jint { $out = $p as jint; };
+    ($p:f_type) => "int" "$out = $p;";
+);
+"#,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains(
+            "conflicting rules for foreign type int: it is converted from Rust type u32, \
+             but previous rule converts it from jint"
+        ));
+    }
 }
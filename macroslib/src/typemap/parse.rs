@@ -1,9 +1,15 @@
-use std::{cell::RefCell, rc::Rc, str::FromStr};
+use std::{
+    cell::{Cell, RefCell},
+    env,
+    hash::{Hash, Hasher},
+    rc::Rc,
+    str::FromStr,
+};
 
 use log::{debug, trace};
 use proc_macro2::{Ident, Span, TokenStream};
 use quote::ToTokens;
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 use syn::{
     parse_quote,
     punctuated::Punctuated,
@@ -13,6 +19,7 @@ use syn::{
 };
 
 use crate::{
+    diagnostic_codes::Warnings,
     error::{invalid_src_id_span, DiagnosticError, Result, SourceIdSpan},
     source_registry::SourceId,
     typemap::{
@@ -21,8 +28,9 @@ use crate::{
             TypeName,
         },
         parse_typemap_macro::TypeMapConvRuleInfo,
-        ty::{ForeignTypesStorage, RustTypeS},
-        validate_code_template, TypeConvEdge, TypeMap, TypesConvGraph,
+        ty::{add_rust_type_node, ForeignTypesStorage, RustTypeS},
+        validate_code_template, ConvPathsCache, GenericInstancesCache, TypeConvEdge, TypeMap,
+        TypesConvGraph, DEFAULT_CONV_COST,
     },
 };
 
@@ -37,23 +45,172 @@ static SWIG_CODE: &str = "swig_code";
 static SWIG_GENERIC_ARG: &str = "swig_generic_arg";
 static SWIG_FROM_ATTR_NAME: &str = "swig_from";
 static SWIG_TO_ATTR_NAME: &str = "swig_to";
+static SWIG_LOSSY: &str = "swig_lossy";
 
 static SWIG_INTO_TRAIT: &str = "SwigInto";
 static SWIG_FROM_TRAIT: &str = "SwigFrom";
 static SWIG_DEREF_TRAIT: &str = "SwigDeref";
 static SWIG_DEREF_MUT_TRAIT: &str = "SwigDerefMut";
+pub(in crate::typemap) static SWIG_FALLIBLE_TRAIT: &str = "SwigFallible";
 static TARGET_ASSOC_TYPE: &str = "Target";
 
 type MyAttrs = FxHashMap<String, Vec<(String, Span)>>;
 
+/// What we know about target, to evaluate `#[cfg(..)]` of typemap items.
+/// `None` means that we don't know, and such item is not filtered.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TargetCfg {
+    pub(crate) pointer_width: usize,
+    pub(crate) os: Option<String>,
+    pub(crate) arch: Option<String>,
+    pub(crate) family: Option<String>,
+    pub(crate) features: FxHashSet<String>,
+}
+
+/// `features` are hashed in sorted order, so hash does not depend
+/// on order of environment variables
+impl Hash for TargetCfg {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.pointer_width.hash(state);
+        self.os.hash(state);
+        self.arch.hash(state);
+        self.family.hash(state);
+        let mut features: Vec<&String> = self.features.iter().collect();
+        features.sort();
+        features.hash(state);
+    }
+}
+
+impl TargetCfg {
+    #[cfg(test)]
+    pub(crate) fn new(pointer_width: usize) -> TargetCfg {
+        TargetCfg {
+            pointer_width,
+            ..Default::default()
+        }
+    }
+
+    /// Get information about target from environment variables
+    /// that `cargo` sets for build scripts
+    pub(crate) fn from_env() -> TargetCfg {
+        let var = |name: &str| env::var(name).ok();
+        let features = env::vars()
+            .filter_map(|(k, _)| {
+                if k.starts_with(CARGO_FEATURE_PREFIX) {
+                    Some(normalize_feature_name(&k[CARGO_FEATURE_PREFIX.len()..]))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        TargetCfg {
+            pointer_width: 0,
+            os: var("CARGO_CFG_TARGET_OS"),
+            arch: var("CARGO_CFG_TARGET_ARCH"),
+            family: var("CARGO_CFG_TARGET_FAMILY"),
+            features,
+        }
+    }
+
+    pub(crate) fn add_feature(&mut self, feature: &str) {
+        self.features.insert(normalize_feature_name(feature));
+    }
+
+    /// `Some(true)` if `cfg` predicate is true, `None` if we don't know
+    fn eval(&self, pred: &syn::NestedMeta) -> Option<bool> {
+        let meta = match pred {
+            syn::NestedMeta::Meta(ref meta) => meta,
+            syn::NestedMeta::Literal(_) => return None,
+        };
+        match meta {
+            syn::Meta::Word(ref ident) => {
+                let family = self.family.as_ref()?;
+                if ident == "unix" || ident == "windows" {
+                    Some(ident == family.as_str())
+                } else {
+                    None
+                }
+            }
+            syn::Meta::NameValue(ref name_val) => {
+                let val = match name_val.lit {
+                    syn::Lit::Str(ref s) => s.value(),
+                    _ => return None,
+                };
+                let known = |x: &Option<String>| x.as_ref().map(|x| *x == val);
+                if name_val.ident == "target_pointer_width" {
+                    if self.pointer_width == 0 {
+                        return None;
+                    }
+                    usize::from_str(&val)
+                        .ok()
+                        .map(|width| width == self.pointer_width)
+                } else if name_val.ident == "target_os" {
+                    known(&self.os)
+                } else if name_val.ident == "target_arch" {
+                    known(&self.arch)
+                } else if name_val.ident == "target_family" {
+                    known(&self.family)
+                } else if name_val.ident == "feature" {
+                    Some(self.features.contains(&normalize_feature_name(&val)))
+                } else {
+                    None
+                }
+            }
+            syn::Meta::List(ref list) => {
+                let mut args = list.nested.iter().map(|x| self.eval(x));
+                if list.ident == "not" {
+                    if list.nested.len() != 1 {
+                        return None;
+                    }
+                    args.next()?.map(|x| !x)
+                } else if list.ident == "all" || list.ident == "any" {
+                    // one false argument of `all` (true of `any`) decides result,
+                    // even if other arguments are unknown
+                    let decisive = list.ident == "any";
+                    let args: Vec<Option<bool>> = args.collect();
+                    if args.contains(&Some(decisive)) {
+                        Some(decisive)
+                    } else if args.contains(&None) {
+                        None
+                    } else {
+                        Some(!decisive)
+                    }
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+static CARGO_FEATURE_PREFIX: &str = "CARGO_FEATURE_";
+
+fn normalize_feature_name(name: &str) -> String {
+    name.to_lowercase().replace('-', "_")
+}
+
 pub(in crate::typemap) fn parse(
     name: SourceId,
     code: &str,
-    target_pointer_width: usize,
+    target_cfg: &TargetCfg,
+    traits_usage_code: FxHashMap<Ident, String>,
+) -> Result<TypeMap> {
+    let file = parse_file(name, code)?;
+    parse_items(name, file, target_cfg, traits_usage_code)
+}
+
+/// The first, independent from other types maps, part of `parse`
+pub(crate) fn parse_file(name: SourceId, code: &str) -> Result<syn::File> {
+    syn::parse_str::<syn::File>(code).map_err(|err| DiagnosticError::from_syn_err(name, err))
+}
+
+/// The second part of `parse`, it depends on traits from previous types maps
+pub(in crate::typemap) fn parse_items(
+    name: SourceId,
+    file: syn::File,
+    target_cfg: &TargetCfg,
     traits_usage_code: FxHashMap<Ident, String>,
 ) -> Result<TypeMap> {
-    let file = syn::parse_str::<syn::File>(code)
-        .map_err(|err| DiagnosticError::from_syn_err(name, err))?;
     let sym_foreign_types_map = Ident::new(MOD_NAME_WITH_FOREIGN_TYPES, Span::call_site());
 
     let mut types_map_span: Option<Span> = None;
@@ -64,16 +221,25 @@ pub(in crate::typemap) fn parse(
         utils_code: Vec::with_capacity(file.items.len()),
         generic_edges: Vec::<GenericTypeConv>::new(),
         rust_to_foreign_cache: FxHashMap::default(),
+        conv_paths_cache: ConvPathsCache::default(),
+        generic_instances_cache: GenericInstancesCache::default(),
         foreign_classes: Vec::new(),
         exported_enums: FxHashMap::default(),
         traits_usage_code,
         ftypes_storage: ForeignTypesStorage::default(),
         not_merged_data: vec![],
+        src_precedence: FxHashMap::default(),
+        target_cfg: target_cfg.clone(),
+        class_scope: None,
+        class_scope_rules: Vec::new(),
+        fallible_conversations: 0,
+        warnings: Warnings::default(),
+        strict_conversions: false,
     };
 
     macro_rules! handle_attrs {
         ($item:expr) => {{
-            if is_wrong_cfg_pointer_width(&$item.attrs, target_pointer_width) {
+            if is_wrong_cfg(&$item.attrs, target_cfg) {
                 continue;
             }
             my_syn_attrs_to_hashmap(name, &$item.attrs)?
@@ -146,6 +312,9 @@ pub(in crate::typemap) fn parse(
             }
             Item::Macro(mut item_macro) => {
                 if item_macro.mac.path.is_ident("foreign_typemap") {
+                    if is_wrong_cfg(&item_macro.attrs, target_cfg) {
+                        continue;
+                    }
                     let tmap_conv_rule: TypeMapConvRuleInfo = syn::parse2(item_macro.mac.tts)
                         .map_err(|err| DiagnosticError::from_syn_err(name, err))?;
 
@@ -186,16 +355,13 @@ fn fill_foreign_types_map(
         let rust_names_map = &mut ret.rust_names_map;
         let conv_graph = &mut ret.conv_graph;
         let graph_idx = *rust_names_map.entry(rust_name.clone()).or_insert_with(|| {
-            let idx = conv_graph.add_node(Rc::new(RustTypeS::new_without_graph_idx(
-                rust_ty, rust_name, src_id,
-            )));
-            Rc::get_mut(&mut conv_graph[idx])
-                .expect("Internal error: can not modify Rc")
-                .graph_idx = idx;
-            idx
+            add_rust_type_node(
+                conv_graph,
+                RustTypeS::new_without_graph_idx(rust_ty, rust_name, src_id),
+            )
         });
 
-        ret.add_foreign_rust_ty_idx(foreign_name, graph_idx)?;
+        ret.add_foreign(graph_idx, foreign_name)?;
     }
     Ok(())
 }
@@ -253,7 +419,7 @@ fn parse_foreign_types_map_mod(src_id: SourceId, item: &ItemMod) -> Result<Vec<T
 
                 let rust_ty = parse_ty_with_given_span(&attr_value_tn.typename, span)
                     .map_err(|err| DiagnosticError::from_syn_err(src_id, err))?;
-                attr_value_tn.typename = normalize_ty_lifetimes(&rust_ty).into();
+                attr_value_tn.typename = normalize_ty_lifetimes(&rust_ty);
                 names_map.insert(ftype, (attr_value_tn, rust_ty));
             } else {
                 return Err(DiagnosticError::new(
@@ -285,7 +451,7 @@ fn parse_foreign_types_map_mod(src_id: SourceId, item: &ItemMod) -> Result<Vec<T
                 let mut attr_value_tn = TypeName::new(attr_value.value(), (src_id, span));
                 let rust_ty = parse_ty_with_given_span(&attr_value_tn.typename, span)
                     .map_err(|err| DiagnosticError::from_syn_err(src_id, err))?;
-                attr_value_tn.typename = normalize_ty_lifetimes(&rust_ty).into();
+                attr_value_tn.typename = normalize_ty_lifetimes(&rust_ty);
                 let unique_name =
                     RustTypeS::make_unique_typename(&attr_value_tn.typename, &ftype.typename);
                 names_map.insert(
@@ -321,29 +487,12 @@ fn parse_foreign_types_map_mod(src_id: SourceId, item: &ItemMod) -> Result<Vec<T
         .collect())
 }
 
-fn is_wrong_cfg_pointer_width(attrs: &[syn::Attribute], target_pointer_width: usize) -> bool {
+fn is_wrong_cfg(attrs: &[syn::Attribute], target_cfg: &TargetCfg) -> bool {
     for a in attrs {
         if a.path.is_ident("cfg") {
             if let Ok(syn::Meta::List(syn::MetaList { ref nested, .. })) = a.parse_meta() {
-                if nested.len() == 1 {
-                    if let syn::NestedMeta::Meta(syn::Meta::NameValue(ref name_val)) = nested[0] {
-                        if name_val.ident == "target_pointer_width" {
-                            let val = name_val.lit.clone().into_token_stream().to_string();
-                            let val = if val.starts_with('"') {
-                                &val[1..]
-                            } else {
-                                &val
-                            };
-                            let val = if val.ends_with('"') {
-                                &val[..val.len() - 1]
-                            } else {
-                                &val
-                            };
-                            if let Ok(width) = <usize>::from_str(val) {
-                                return target_pointer_width != width;
-                            }
-                        }
-                    }
+                if nested.len() == 1 && target_cfg.eval(&nested[0]) == Some(false) {
+                    return true;
                 }
             }
         }
@@ -353,13 +502,14 @@ fn is_wrong_cfg_pointer_width(attrs: &[syn::Attribute], target_pointer_width: us
 }
 
 fn my_syn_attrs_to_hashmap(src_id: SourceId, attrs: &[syn::Attribute]) -> Result<MyAttrs> {
-    static KNOWN_SWIG_ATTRS: [&str; 6] = [
+    static KNOWN_SWIG_ATTRS: [&str; 7] = [
         SWIG_TO_FOREIGNER_HINT,
         SWIG_FROM_FOREIGNER_HINT,
         SWIG_CODE,
         SWIG_GENERIC_ARG,
         SWIG_FROM_ATTR_NAME,
         SWIG_TO_ATTR_NAME,
+        SWIG_LOSSY,
     ];
     let mut ret = FxHashMap::default();
     for a in attrs {
@@ -384,9 +534,9 @@ fn my_syn_attrs_to_hashmap(src_id: SourceId, attrs: &[syn::Attribute]) -> Result
     Ok(ret)
 }
 
-fn get_swig_code_from_attrs<'a, 'b>(
+fn get_swig_code_from_attrs<'b>(
     item_span: SourceIdSpan,
-    swig_code_attr_name: &'a str,
+    swig_code_attr_name: &str,
     attrs: &'b MyAttrs,
 ) -> Result<&'b str> {
     if let Some(swig_code) = attrs.get(swig_code_attr_name) {
@@ -475,14 +625,14 @@ fn handle_into_from_impl(
             )
         })?;
 
-    if item_impl.generics.type_params().next().is_some() {
+    if is_generic_impl(&item_impl.generics) {
         trace!("handle_into_from_impl: generics {:?}", item_impl.generics);
         let item_code = item_impl.into_token_stream();
         ret.generic_edges.push(GenericTypeConv {
             src_id,
             from_ty,
             to_ty,
-            code_template: conv_code.to_string(),
+            code_template: conv_code.to_string().into(),
             dependency: Rc::new(RefCell::new(Some(item_code))),
             generic_params: item_impl.generics.clone(),
             to_foreigner_hint: get_foreigner_hint_for_generic(
@@ -497,21 +647,30 @@ fn handle_into_from_impl(
                 &swig_attrs,
                 ForeignHintVariant::From,
             )?,
+            cost: DEFAULT_CONV_COST,
+            used: Rc::new(Cell::new(false)),
         });
     } else {
         let item_code = item_impl.into_token_stream();
+        let lossy = swig_attrs.get(SWIG_LOSSY).map(|x| x[0].0.clone());
         add_conv_code(
             src_id,
             (from_ty, from_suffix),
             (to_ty, to_suffix),
             item_code,
             conv_code.clone(),
+            lossy,
             ret,
         );
     }
     Ok(())
 }
 
+/// `impl<T>` or `impl<const N: usize>`, lifetimes do not count
+fn is_generic_impl(generics: &syn::Generics) -> bool {
+    generics.type_params().next().is_some() || generics.const_params().next().is_some()
+}
+
 fn handle_deref_impl(
     src_id: SourceId,
     swig_attrs: &MyAttrs,
@@ -561,12 +720,12 @@ fn handle_deref_impl(
     let item_code = item_impl.into_token_stream();
 
     //for_type -> &Target
-    if item_impl.generics.type_params().next().is_some() {
+    if is_generic_impl(&item_impl.generics) {
         ret.generic_edges.push(GenericTypeConv {
             src_id,
             from_ty,
             to_ty: to_ref_ty,
-            code_template: conv_code.to_string(),
+            code_template: conv_code.to_string().into(),
             dependency: Rc::new(RefCell::new(Some(item_code))),
             generic_params: item_impl.generics.clone(),
             to_foreigner_hint: get_foreigner_hint_for_generic(
@@ -581,10 +740,12 @@ fn handle_deref_impl(
                 &swig_attrs,
                 ForeignHintVariant::From,
             )?,
+            cost: DEFAULT_CONV_COST,
+            used: Rc::new(Cell::new(false)),
         });
     } else {
         let to_typename = normalize_ty_lifetimes(&to_ref_ty);
-        let to_ty = if let Some(ty_type_idx) = ret.rust_names_map.get(to_typename) {
+        let to_ty = if let Some(ty_type_idx) = ret.rust_names_map.get(&to_typename) {
             ret.conv_graph[*ty_type_idx].ty.clone()
         } else {
             to_ref_ty
@@ -596,6 +757,7 @@ fn handle_deref_impl(
             (to_ty, None),
             item_code,
             conv_code.to_string(),
+            None,
             ret,
         );
     }
@@ -676,11 +838,13 @@ fn handle_macro(
             src_id,
             from_ty,
             to_ty,
-            code_template: code_template.to_string(),
+            code_template: code_template.into(),
             dependency: Rc::new(RefCell::new(Some(item_code))),
             generic_params,
             to_foreigner_hint,
             from_foreigner_hint,
+            cost: DEFAULT_CONV_COST,
+            used: Rc::new(Cell::new(false)),
         });
     } else {
         unimplemented!();
@@ -795,15 +959,21 @@ fn add_conv_code(
     (to_ty, to_suffix): (Type, Option<String>),
     item_code: TokenStream,
     conv_code: String,
+    lossy: Option<String>,
     ret: &mut TypeMap,
 ) {
     let from = ret.find_or_alloc_rust_type_with_may_be_suffix(&from_ty, from_suffix, src_id);
     let to = ret.find_or_alloc_rust_type_with_may_be_suffix(&to_ty, to_suffix, src_id);
-    debug!("add_conv_code: from {} to {}", from, to);
+    debug!("add_conv_code: from {} to {}", ret[from], ret[to]);
+    let span = item_code.span();
     ret.conv_graph.update_edge(
-        from.graph_idx,
-        to.graph_idx,
-        TypeConvEdge::new(conv_code, Some(item_code)),
+        from,
+        to,
+        TypeConvEdge {
+            span,
+            lossy,
+            ..TypeConvEdge::new(conv_code, Some(item_code))
+        },
     );
 }
 
@@ -865,7 +1035,7 @@ mod swig_foreign_types_map {
     #![swig_rust_type="jint"]
 }
 "#,
-            64,
+            &TargetCfg::new(64),
             FxHashMap::default(),
         )
         .unwrap();
@@ -935,7 +1105,7 @@ mod swig_foreign_types_map {
 mod swig_foreign_types_map {}
 mod swig_foreign_types_map {}
 "#,
-            64,
+            &TargetCfg::new(64),
             FxHashMap::default(),
         )
         .unwrap_err();
@@ -953,8 +1123,50 @@ mod swig_foreign_types_map {}
                 }
             }
         };
-        assert!(is_wrong_cfg_pointer_width(&item_impl.attrs, 32));
-        assert!(!is_wrong_cfg_pointer_width(&item_impl.attrs, 64));
+        assert!(is_wrong_cfg(&item_impl.attrs, &TargetCfg::new(32)));
+        assert!(!is_wrong_cfg(&item_impl.attrs, &TargetCfg::new(64)));
+    }
+
+    #[test]
+    fn test_parse_cfg_target() {
+        let target_cfg = TargetCfg {
+            os: Some("android".into()),
+            arch: Some("aarch64".into()),
+            family: Some("unix".into()),
+            features: vec!["chrono_support".to_string()].into_iter().collect(),
+            ..TargetCfg::new(64)
+        };
+        let is_wrong = |attr: syn::Attribute| is_wrong_cfg(&[attr], &target_cfg);
+        assert!(!is_wrong(parse_quote! { #[cfg(target_os = "android")] }));
+        assert!(is_wrong(parse_quote! { #[cfg(target_os = "windows")] }));
+        assert!(!is_wrong(parse_quote! { #[cfg(unix)] }));
+        assert!(is_wrong(parse_quote! { #[cfg(windows)] }));
+        assert!(!is_wrong(
+            parse_quote! { #[cfg(feature = "chrono-support")] }
+        ));
+        assert!(is_wrong(parse_quote! { #[cfg(feature = "serde")] }));
+        assert!(is_wrong(
+            parse_quote! { #[cfg(all(unix, not(target_arch = "aarch64")))] }
+        ));
+        assert!(!is_wrong(
+            parse_quote! { #[cfg(any(windows, target_pointer_width = "64"))] }
+        ));
+        assert!(!is_wrong(
+            parse_quote! { #[cfg(all(unix, debug_assertions))] }
+        ));
+        assert!(!is_wrong(
+            parse_quote! { #[cfg(any(windows, debug_assertions))] }
+        ));
+        assert!(is_wrong(
+            parse_quote! { #[cfg(all(debug_assertions, windows))] }
+        ));
+        assert!(is_wrong(parse_quote! { #[cfg(target_os = "linux")] }));
+
+        let unknown_target = TargetCfg::new(64);
+        assert!(!is_wrong_cfg(
+            &[parse_quote! { #[cfg(target_os = "windows")] }],
+            &unknown_target
+        ));
     }
 
     #[test]
@@ -1111,7 +1323,7 @@ impl SwigFrom<bool> for jboolean {
     }
 }
 "#,
-            64,
+            &TargetCfg::new(64),
             FxHashMap::default(),
         )
         .unwrap();
@@ -1121,24 +1333,12 @@ impl SwigFrom<bool> for jboolean {
         let bool_ty = conv_map.find_or_alloc_rust_type(&parse_type! { bool }, SourceId::none());
 
         let (_, code) = conv_map
-            .convert_rust_types(
-                jboolean_ty.to_idx(),
-                bool_ty.to_idx(),
-                "a0",
-                "jlong",
-                invalid_src_id_span(),
-            )
+            .convert_rust_types(jboolean_ty, bool_ty, "a0", "jlong", invalid_src_id_span())
             .unwrap();
         assert_eq!("    let a0: bool = a0.swig_into(env);\n".to_string(), code);
 
         let (_, code) = conv_map
-            .convert_rust_types(
-                bool_ty.to_idx(),
-                jboolean_ty.to_idx(),
-                "a0",
-                "jlong",
-                invalid_src_id_span(),
-            )
+            .convert_rust_types(bool_ty, jboolean_ty, "a0", "jlong", invalid_src_id_span())
             .unwrap();
 
         assert_eq!(
@@ -1166,20 +1366,14 @@ impl SwigDeref for String {
     }
 }
 "#,
-            64,
+            &TargetCfg::new(64),
             FxHashMap::default(),
         )
         .unwrap();
         let string_ty = conv_map.find_or_alloc_rust_type(&parse_type! { String }, SourceId::none());
         let str_ty = conv_map.find_or_alloc_rust_type(&parse_type! { &str }, SourceId::none());
         let (_, code) = conv_map
-            .convert_rust_types(
-                string_ty.to_idx(),
-                str_ty.to_idx(),
-                "a0",
-                "jlong",
-                invalid_src_id_span(),
-            )
+            .convert_rust_types(string_ty, str_ty, "a0", "jlong", invalid_src_id_span())
             .unwrap();
         assert_eq!("    let a0: & str = a0.swig_deref();\n".to_string(), code);
     }
@@ -1230,7 +1424,7 @@ impl<'a, T> SwigDeref for MutexGuard<'a, T> {
     }
 }
 "#,
-            64,
+            &TargetCfg::new(64),
             FxHashMap::default(),
         )
         .unwrap();
@@ -1245,13 +1439,7 @@ impl<'a, T> SwigDeref for MutexGuard<'a, T> {
         let foo_ref = conv_map.find_or_alloc_rust_type(&parse_type! { &Foo }, SourceId::none());
 
         let (_, code) = conv_map
-            .convert_rust_types(
-                arc_mutex_foo.to_idx(),
-                foo_ref.to_idx(),
-                "a0",
-                "jlong",
-                invalid_src_id_span(),
-            )
+            .convert_rust_types(arc_mutex_foo, foo_ref, "a0", "jlong", invalid_src_id_span())
             .unwrap();
         assert_eq!(
             r#"    let a0: & Mutex < Foo > = a0.swig_deref();
@@ -1308,7 +1496,7 @@ macro_rules! jni_unpack_return {
     }
 }
 "#,
-            64,
+            &TargetCfg::new(64),
             FxHashMap::default(),
         )
         .unwrap();
@@ -1319,8 +1507,8 @@ macro_rules! jni_unpack_return {
 
         let (_, code) = conv_map
             .convert_rust_types(
-                result_foo_str_ty.to_idx(),
-                foo_ty.to_idx(),
+                result_foo_str_ty,
+                foo_ty,
                 "a0",
                 "jlong",
                 invalid_src_id_span(),
@@ -1338,8 +1526,8 @@ macro_rules! jni_unpack_return {
 
         let (_, code) = conv_map
             .convert_rust_types(
-                result_u8_str_ty.to_idx(),
-                jshort_ty.to_idx(),
+                result_u8_str_ty,
+                jshort_ty,
                 "a0",
                 "jlong",
                 invalid_src_id_span(),
@@ -1358,14 +1546,14 @@ macro_rules! jni_unpack_return {
         parse(
             SourceId::none(),
             include_str!("../java_jni/jni-include.rs"),
-            64,
+            &TargetCfg::new(64),
             FxHashMap::default(),
         )
         .unwrap();
         parse(
             SourceId::none(),
             include_str!("../cpp/cpp-include.rs"),
-            64,
+            &TargetCfg::new(64),
             FxHashMap::default(),
         )
         .unwrap();
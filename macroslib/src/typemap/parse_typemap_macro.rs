@@ -1,21 +1,23 @@
 use proc_macro2::{Span, TokenStream};
 use smol_str::SmolStr;
 use syn::{
-    braced, bracketed, parenthesized, parse_quote, spanned::Spanned, token, Ident, LitStr, Token,
-    Type,
+    braced, bracketed, parenthesized, parse_quote, spanned::Spanned, token, Ident, LitInt, LitStr,
+    Token, Type,
 };
 
 use crate::{
     source_registry::SourceId,
     typemap::{
         ast::{DisplayToTokens, SpannedSmolStr},
+        is_tmp_var_declared,
         ty::FTypeConvCode,
-        FROM_VAR_TEMPLATE, TO_VAR_TEMPLATE, TO_VAR_TYPE_TEMPLATE,
+        DEFAULT_CONV_COST, FROM_VAR_TEMPLATE, TMP_VAR_TEMPLATE, TO_VAR_TEMPLATE,
+        TO_VAR_TYPE_TEMPLATE,
     },
     FOREIGNER_CODE, FOREIGN_CODE,
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct TypeMapConvRuleInfo {
     pub src_id: SourceId,
     pub rtype_left_to_right: Option<RTypeConvRule>,
@@ -46,6 +48,7 @@ impl TypeMapConvRuleInfo {
                     left_ty: ref r_ty,
                     right_ty: None,
                     code: None,
+                    ..
                 }),
                 Some(FTypeConvRule {
                     left_right_ty: FTypeLeftRightPair::OnlyLeft(ref f_ty),
@@ -74,14 +77,21 @@ impl TypeMapConvRuleInfo {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub(crate) struct RTypeConvRule {
     pub left_ty: Type,
     pub right_ty: Option<Type>,
     pub code: Option<FTypeConvCode>,
+    /// replace existing rule for the same types, even if it has higher precedence
+    pub force_override: bool,
+    /// cost of conversation, the cheapest path between types is used
+    pub cost: u32,
+    /// conversation code returns `Result<_, String>`,
+    /// error reported to foreign language
+    pub fallible: bool,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub(crate) struct FTypeConvRule {
     pub req_modules: Vec<SmolStr>,
     pub cfg_option: Option<SpannedSmolStr>,
@@ -89,7 +99,7 @@ pub(crate) struct FTypeConvRule {
     pub code: Option<FTypeConvCode>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub(crate) enum FTypeLeftRightPair {
     OnlyLeft(FTypeName),
     OnlyRight(FTypeName),
@@ -97,6 +107,15 @@ pub(crate) enum FTypeLeftRightPair {
 }
 
 impl FTypeLeftRightPair {
+    /// Name of foreign type that rule defines
+    pub(crate) fn ftype_name(&self) -> &FTypeName {
+        use FTypeLeftRightPair::*;
+        match self {
+            OnlyRight(ref x) => x,
+            OnlyLeft(ref x) => x,
+            Both(_, ref x) => x,
+        }
+    }
     pub(crate) fn span(&self) -> Span {
         use FTypeLeftRightPair::*;
         match self {
@@ -136,6 +155,10 @@ mod kw {
     custom_keyword!(req_modules);
     custom_keyword!(module);
     custom_keyword!(option);
+    custom_keyword!(force_override);
+    custom_keyword!(cost);
+    custom_keyword!(fallible);
+    custom_keyword!(bidirectional);
 }
 
 enum RuleType {
@@ -181,6 +204,10 @@ impl syn::parse::Parse for TypeMapConvRuleInfo {
                 };
                 let mut ftype_cfg: Option<SpannedSmolStr> = None;
                 let mut ftype_req_modules = Vec::<SmolStr>::new();
+                let mut force_override = false;
+                let mut cost = DEFAULT_CONV_COST;
+                let mut fallible = false;
+                let mut bidirectional = None;
                 while !params.is_empty() && params.peek(Token![,]) {
                     params.parse::<Token![,]>()?;
                     let la = params.lookahead1();
@@ -223,6 +250,47 @@ impl syn::parse::Parse for TypeMapConvRuleInfo {
                             sp: lit_str.span(),
                             value: lit_str.value().into(),
                         });
+                    } else if la.peek(kw::force_override) {
+                        let keyword = params.parse::<kw::force_override>()?;
+                        if let RuleType::FType(_) = rule {
+                            return Err(syn::Error::new(
+                                keyword.span(),
+                                "force_override allowed only for r_type",
+                            ));
+                        }
+                        force_override = true;
+                    } else if la.peek(kw::cost) {
+                        let keyword = params.parse::<kw::cost>()?;
+                        if let RuleType::FType(_) = rule {
+                            return Err(syn::Error::new(
+                                keyword.span(),
+                                "cost allowed only for r_type",
+                            ));
+                        }
+                        params.parse::<Token![=]>()?;
+                        let lit_int = params.parse::<LitInt>()?;
+                        if lit_int.value() > u64::from(u32::max_value()) {
+                            return Err(syn::Error::new(lit_int.span(), "cost is too big"));
+                        }
+                        cost = lit_int.value() as u32;
+                    } else if la.peek(kw::fallible) {
+                        let keyword = params.parse::<kw::fallible>()?;
+                        if let RuleType::FType(_) = rule {
+                            return Err(syn::Error::new(
+                                keyword.span(),
+                                "fallible allowed only for r_type",
+                            ));
+                        }
+                        fallible = true;
+                    } else if la.peek(kw::bidirectional) {
+                        let keyword = params.parse::<kw::bidirectional>()?;
+                        if let RuleType::FType(_) = rule {
+                            return Err(syn::Error::new(
+                                keyword.span(),
+                                "bidirectional allowed only for r_type",
+                            ));
+                        }
+                        bidirectional = Some(keyword);
                     } else {
                         return Err(la.error());
                     }
@@ -243,38 +311,59 @@ impl syn::parse::Parse for TypeMapConvRuleInfo {
                         }
 
                         let code = if conv_rule_type.is_some() && input.peek(syn::token::Brace) {
-                            let content;
-                            braced!(content in input);
-                            let conv_body = content.parse::<TokenStream>()?;
-
-                            let var_name = var_name.ok_or_else(|| {
+                            let var_name = var_name.as_ref().ok_or_else(|| {
                                 syn::Error::new(keyword.span(), "there is conversation code, but name of input variable not defined here")
                             })?;
-                            //because of $var most likely will be reformated to "$ var", so
-                            //without clue how syn formatted token -> string, just convert text to tokens and back
-                            let d_var_name: TokenStream = parse_quote!($#var_name);
-                            let d_var_name = d_var_name.to_string();
-                            let out_var: TokenStream = parse_quote!($out);
-                            let out_var = out_var.to_string();
-                            let mut code_str = conv_body.to_string();
-                            if !code_str.contains(&d_var_name) || !code_str.contains(&out_var) {
+                            Some(parse_rtype_conv_code(input, var_name, fallible)?)
+                        } else {
+                            None
+                        };
+                        let reverse_code = if let Some(ref keyword) = bidirectional {
+                            if code.is_none() || !input.peek(syn::token::Brace) {
                                 return Err(syn::Error::new(
-                                    conv_body.span(),
-                                    format!("no $out or ${} in conversation code", var_name),
+                                    keyword.span(),
+                                    "bidirectional rule requires conversation code for both directions",
                                 ));
                             }
-                            code_str.push(';');
-
-                            Some(FTypeConvCode::new2(
-                                code_str.replace(&d_var_name, FROM_VAR_TEMPLATE).replace(
-                                    &out_var,
-                                    &format!("let {}: {}", TO_VAR_TEMPLATE, TO_VAR_TYPE_TEMPLATE),
-                                ),
-                                conv_body.span(),
-                            ))
+                            let var_name = var_name.as_ref().expect("var_name checked above");
+                            Some(parse_rtype_conv_code(input, var_name, fallible)?)
                         } else {
                             None
                         };
+                        if fallible && code.is_none() {
+                            return Err(syn::Error::new(
+                                keyword.span(),
+                                "fallible rule without conversation code",
+                            ));
+                        }
+                        // `A => B { code } { reverse_code }` also defines `A <= B { reverse_code }`
+                        // and vice versa
+                        if let (Some(reverse_code), Some(conv_rule_type)) =
+                            (reverse_code, conv_rule_type.as_ref())
+                        {
+                            let (reverse_rule, right_ty) = match conv_rule_type {
+                                ConvertRuleType::LeftToRight(right_ty) => {
+                                    (&mut rtype_right_to_left, right_ty)
+                                }
+                                ConvertRuleType::RightToLeft(right_ty) => {
+                                    (&mut rtype_left_to_right, right_ty)
+                                }
+                            };
+                            if reverse_rule.is_some() {
+                                return Err(syn::Error::new(
+                                    keyword.span(),
+                                    "duplicate of r_type rule, reverse rule defined by bidirectional",
+                                ));
+                            }
+                            *reverse_rule = Some(RTypeConvRule {
+                                left_ty: left_ty.clone(),
+                                right_ty: Some(right_ty.clone()),
+                                code: Some(reverse_code),
+                                force_override,
+                                cost,
+                                fallible,
+                            });
+                        }
                         match conv_rule_type {
                             Some(ConvertRuleType::LeftToRight(right_ty)) => {
                                 if rtype_left_to_right.is_some() {
@@ -287,6 +376,9 @@ impl syn::parse::Parse for TypeMapConvRuleInfo {
                                     left_ty,
                                     right_ty: Some(right_ty),
                                     code,
+                                    force_override,
+                                    cost,
+                                    fallible,
                                 });
                             }
                             Some(ConvertRuleType::RightToLeft(right_ty)) => {
@@ -300,6 +392,9 @@ impl syn::parse::Parse for TypeMapConvRuleInfo {
                                     left_ty,
                                     right_ty: Some(right_ty),
                                     code,
+                                    force_override,
+                                    cost,
+                                    fallible,
                                 });
                             }
                             None => {
@@ -313,6 +408,9 @@ impl syn::parse::Parse for TypeMapConvRuleInfo {
                                     left_ty,
                                     right_ty: None,
                                     code: None,
+                                    force_override,
+                                    cost,
+                                    fallible,
                                 });
                             }
                         }
@@ -446,6 +544,53 @@ impl syn::parse::Parse for TypeMapConvRuleInfo {
     }
 }
 
+/// Parse `{ $out = f($p); }` part of r_type rule
+fn parse_rtype_conv_code(
+    input: syn::parse::ParseStream,
+    var_name: &Ident,
+    fallible: bool,
+) -> syn::Result<FTypeConvCode> {
+    let content;
+    braced!(content in input);
+    let conv_body = content.parse::<TokenStream>()?;
+
+    //because of $var most likely will be reformated to "$ var", so
+    //without clue how syn formatted token -> string, just convert text to tokens and back
+    let d_var_name: TokenStream = parse_quote!($#var_name);
+    let d_var_name = d_var_name.to_string();
+    let out_var: TokenStream = parse_quote!($out);
+    let out_var = out_var.to_string();
+    let mut code_str = conv_body.to_string();
+    if !code_str.contains(&d_var_name) || !code_str.contains(&out_var) {
+        return Err(syn::Error::new(
+            conv_body.span(),
+            format!("no $out or ${} in conversation code", var_name),
+        ));
+    }
+    code_str.push(';');
+
+    let out_var_decl = if fallible {
+        format!(
+            "let {}: Result<{}, String>",
+            TO_VAR_TEMPLATE, TO_VAR_TYPE_TEMPLATE
+        )
+    } else {
+        format!("let {}: {}", TO_VAR_TEMPLATE, TO_VAR_TYPE_TEMPLATE)
+    };
+    let tmp_var: TokenStream = parse_quote!($tmp);
+    let code_str = code_str
+        .replace(&d_var_name, FROM_VAR_TEMPLATE)
+        .replace(&out_var, &out_var_decl)
+        .replace(&tmp_var.to_string(), TMP_VAR_TEMPLATE);
+    if code_str.contains(TMP_VAR_TEMPLATE) && !is_tmp_var_declared(&code_str) {
+        return Err(syn::Error::new(
+            conv_body.span(),
+            "$tmp used in conversation code, but not declared via let",
+        ));
+    }
+    Ok(FTypeConvCode::new2(code_str, conv_body.span()))
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) enum CType {
     Struct(syn::ItemStruct),
@@ -505,7 +650,7 @@ fn has_repr_c_attr(attrs: &[syn::Attribute]) -> bool {
     attrs.iter().any(|a| *a == repr_c_attr)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct ForeignCode {
     pub module_name: SmolStr,
     pub cfg_option: Option<SpannedSmolStr>,
@@ -589,6 +734,9 @@ $out = QDateTime::fromMSecsSinceEpoch($pin * 1000, Qt::UTC, 0);
                     "let {to_var}: {to_var_type} = if {from_var} { 1 } else { 0 };",
                     Span::call_site()
                 )),
+                force_override: false,
+                cost: DEFAULT_CONV_COST,
+                fallible: false,
             },
             rule.rtype_left_to_right.unwrap()
         );
@@ -601,6 +749,9 @@ $out = QDateTime::fromMSecsSinceEpoch($pin * 1000, Qt::UTC, 0);
                     "let {to_var}: {to_var_type} = ( {from_var} != 0 );",
                     Span::call_site()
                 )),
+                force_override: false,
+                cost: DEFAULT_CONV_COST,
+                fallible: false,
             },
             rule.rtype_right_to_left.unwrap()
         );
@@ -746,6 +897,9 @@ $out = QString::fromUtf8($pin.data, $pin.len);
                 left_ty: parse_type!(jlong),
                 right_ty: None,
                 code: None,
+                force_override: false,
+                cost: DEFAULT_CONV_COST,
+                fallible: false,
             },
             rule.rtype_left_to_right.unwrap()
         );
@@ -840,6 +994,141 @@ $out = QString::fromUtf8($pin.data, $pin.len);
         assert!(rule.contains_data_for_language_backend());
     }
 
+    #[test]
+    fn test_foreign_typemap_cost() {
+        let rule = macro_to_conv_rule(parse_quote! {
+            foreign_typemap!(
+                ($p:r_type, cost = 5, force_override) Vec<u8> => &[u8] {
+                    $out = $p.as_slice()
+                };
+            )
+        });
+        let rule = rule.rtype_left_to_right.unwrap();
+        assert_eq!(5, rule.cost);
+        assert!(rule.force_override);
+
+        let err =
+            syn::parse_str::<TypeMapConvRuleInfo>(r#"($p:f_type, cost = 2) => "int" "$out = $p";"#)
+                .unwrap_err();
+        assert_eq!("cost allowed only for r_type", err.to_string());
+    }
+
+    #[test]
+    fn test_foreign_typemap_fallible() {
+        let rule = macro_to_conv_rule(parse_quote! {
+            foreign_typemap!(
+                ($p:r_type, fallible) Utf16Name <= jstring {
+                    $out = Utf16Name::from_jstring($p)
+                };
+            )
+        });
+        let rule = rule.rtype_right_to_left.unwrap();
+        assert!(rule.fallible);
+        assert!(rule.code.unwrap().to_string().starts_with(
+            "let {to_var}: Result<{to_var_type}, String> = Utf16Name :: from_jstring"
+        ));
+
+        let err = syn::parse_str::<TypeMapConvRuleInfo>(r#"($p:r_type, fallible) i32 => jint;"#)
+            .unwrap_err();
+        assert_eq!("fallible rule without conversation code", err.to_string());
+    }
+
+    #[test]
+    fn test_foreign_typemap_bidirectional() {
+        let rule = macro_to_conv_rule(parse_quote! {
+            foreign_typemap!(
+                ($pin:r_type, bidirectional) bool => ::std::os::raw::c_char {
+                    $out = if $pin  { 1 } else { 0 }
+                } {
+                    $out = ($pin != 0)
+                };
+            )
+        });
+        assert_eq!(
+            RTypeConvRule {
+                left_ty: parse_type!(bool),
+                right_ty: Some(parse_type!(::std::os::raw::c_char)),
+                code: Some(FTypeConvCode::new(
+                    "let {to_var}: {to_var_type} = if {from_var} { 1 } else { 0 };",
+                    Span::call_site()
+                )),
+                force_override: false,
+                cost: DEFAULT_CONV_COST,
+                fallible: false,
+            },
+            rule.rtype_left_to_right.unwrap()
+        );
+        assert_eq!(
+            RTypeConvRule {
+                left_ty: parse_type!(bool),
+                right_ty: Some(parse_type!(::std::os::raw::c_char)),
+                code: Some(FTypeConvCode::new(
+                    "let {to_var}: {to_var_type} = ( {from_var} != 0 );",
+                    Span::call_site()
+                )),
+                force_override: false,
+                cost: DEFAULT_CONV_COST,
+                fallible: false,
+            },
+            rule.rtype_right_to_left.unwrap()
+        );
+
+        let rule = macro_to_conv_rule(parse_quote! {
+            foreign_typemap!(
+                ($p:r_type, bidirectional) i32 <= jint { $out = $p as i32 } { $out = $p as jint };
+            )
+        });
+        assert_eq!(
+            "let {to_var}: {to_var_type} = {from_var} as jint;",
+            rule.rtype_left_to_right.unwrap().code.unwrap().to_string()
+        );
+        assert_eq!(
+            "let {to_var}: {to_var_type} = {from_var} as i32;",
+            rule.rtype_right_to_left.unwrap().code.unwrap().to_string()
+        );
+
+        let err = syn::parse_str::<TypeMapConvRuleInfo>(
+            r#"($p:r_type, bidirectional) i32 => jint { $out = $p as jint };"#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            "bidirectional rule requires conversation code for both directions",
+            err.to_string()
+        );
+        let err = syn::parse_str::<TypeMapConvRuleInfo>(
+            r#"($p:r_type, bidirectional) i32 => jint { $out = $p as jint } { $out = $p as i32 };
+               ($p:r_type) i32 <= jint { $out = $p as i32 };"#,
+        )
+        .unwrap_err();
+        assert_eq!("duplicate of r_type right to left rule", err.to_string());
+    }
+
+    #[test]
+    fn test_foreign_typemap_tmp_var() {
+        let rule = macro_to_conv_rule(parse_quote! {
+            foreign_typemap!(
+                ($p:r_type) Vec<u8> => CVecU8 {
+                    let $tmp = $p.into_boxed_slice();
+                    $out = CVecU8::from_boxed_slice($tmp)
+                };
+            )
+        });
+        assert_eq!(
+            "let {tmp_var} = {from_var} . into_boxed_slice ( ) ; \
+             let {to_var}: {to_var_type} = CVecU8 :: from_boxed_slice ( {tmp_var} );",
+            rule.rtype_left_to_right.unwrap().code.unwrap().to_string()
+        );
+
+        let err = syn::parse_str::<TypeMapConvRuleInfo>(
+            r#"($p:r_type) Vec<u8> => CVecU8 { $out = CVecU8::new($p, $tmp) };"#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            "$tmp used in conversation code, but not declared via let",
+            err.to_string()
+        );
+    }
+
     fn macro_to_conv_rule(mac: syn::Macro) -> TypeMapConvRuleInfo {
         let _ = env_logger::try_init();
         let code = mac.tts.to_string();
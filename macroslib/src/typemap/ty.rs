@@ -1,7 +1,11 @@
 use crate::{
+    diagnostic_codes::DiagnosticCode,
     error::DiagnosticError,
     source_registry::SourceId,
-    typemap::{ast::TypeName, RustTypeIdx, FROM_VAR_TEMPLATE, TO_VAR_TEMPLATE},
+    typemap::{
+        ast::{DisplayToTokens, TypeName},
+        RustTypeIdx, TypesConvGraph, FROM_VAR_TEMPLATE, TO_VAR_TEMPLATE,
+    },
 };
 use proc_macro2::Span;
 use rustc_hash::FxHashMap;
@@ -46,17 +50,16 @@ impl RustTypeS {
         self.implements.insert(trait_name.into());
         self
     }
+    /// `other` should have the same `normalized_name`,
+    /// so our already interned name is kept
     pub(in crate::typemap) fn merge(&mut self, other: &RustTypeS) {
+        debug_assert_eq!(self.normalized_name, other.normalized_name);
         self.ty = other.ty.clone();
-        self.normalized_name = other.normalized_name.clone();
         self.implements.insert_set(&other.implements);
     }
     pub(crate) fn src_id_span(&self) -> (SourceId, Span) {
         (self.src_id, self.ty.span())
     }
-    pub(crate) fn to_idx(&self) -> RustTypeIdx {
-        self.graph_idx
-    }
 
     pub(crate) fn make_unique_typename(
         not_unique_name: &str,
@@ -84,7 +87,18 @@ impl RustTypeS {
     }
 }
 
-pub(crate) type RustType = Rc<RustTypeS>;
+/// Conversation graph is storage of all rust types of `TypeMap`,
+/// node index is handle of type. `rty.normalized_name` should be clone
+/// of key in names map, so they share the same string
+pub(in crate::typemap) fn add_rust_type_node(
+    conv_graph: &mut TypesConvGraph,
+    mut rty: RustTypeS,
+) -> RustTypeIdx {
+    rty.graph_idx = RustTypeIdx::new(conv_graph.node_count());
+    let idx = conv_graph.add_node(rty);
+    debug_assert_eq!(idx, conv_graph[idx].graph_idx);
+    idx
+}
 
 #[derive(Default, Debug, Clone)]
 pub(crate) struct ImplementsSet {
@@ -117,6 +131,9 @@ impl ImplementsSet {
     pub(crate) fn contains(&self, trait_name: &str) -> bool {
         self.inner.iter().any(|it| *it == trait_name)
     }
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &SmolStr> {
+        self.inner.iter()
+    }
 }
 
 #[derive(Debug, Default, PartialEq)]
@@ -139,6 +156,18 @@ impl<'a> TraitNamesSet<'a> {
     }
 }
 
+impl<'a> fmt::Display for TraitNamesSet<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, path) in self.inner.iter().enumerate() {
+            if i != 0 {
+                write!(f, " + ")?;
+            }
+            write!(f, "{}", DisplayToTokens(*path))?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct ForeignTypeS {
     pub name: TypeName,
@@ -183,7 +212,7 @@ pub(crate) struct ForeignConversationIntermediate {
 #[derive(Debug, Clone)]
 pub(crate) struct FTypeConvCode {
     span: Span,
-    code: String,
+    code: Rc<str>,
 }
 
 impl PartialEq for FTypeConvCode {
@@ -201,7 +230,10 @@ impl FTypeConvCode {
             "code: '{}'",
             code
         );
-        FTypeConvCode { code, span }
+        FTypeConvCode {
+            code: code.into(),
+            span,
+        }
     }
     /// # Panics
     pub(crate) fn new2<S: Into<String>>(code: S, span: Span) -> FTypeConvCode {
@@ -211,19 +243,22 @@ impl FTypeConvCode {
             "code: '{}'",
             code
         );
-        FTypeConvCode { code, span }
+        FTypeConvCode {
+            code: code.into(),
+            span,
+        }
     }
 }
 
 impl ToString for FTypeConvCode {
     fn to_string(&self) -> String {
-        self.code.clone()
+        self.code.to_string()
     }
 }
 
 impl From<FTypeConvCode> for String {
     fn from(x: FTypeConvCode) -> Self {
-        x.code
+        x.code.to_string()
     }
 }
 
@@ -263,7 +298,8 @@ impl ForeignTypesStorage {
             let mut err = DiagnosticError::new2(
                 self.ftypes[ft2.0].name.span,
                 format!("Type {} already defined here", ft.name),
-            );
+            )
+            .with_code(DiagnosticCode::DuplicateForeignType);
             err.span_note(ft.name.span, format!("second mention of type {}", ft.name));
             return Err(err);
         }
@@ -296,6 +332,18 @@ impl ForeignTypesStorage {
         self.name_to_ftype.get(ftype_name).cloned()
     }
 
+    /// Make type invisible for search by name and via conversation rules
+    pub(in crate::typemap) fn forget(&mut self, idx: ForeignType) {
+        let ftype = &mut self.ftypes[idx.0];
+        self.name_to_ftype.remove(ftype.name.as_str());
+        ftype.into_from_rust = None;
+        ftype.from_into_rust = None;
+    }
+
+    pub(in crate::typemap) fn len(&self) -> usize {
+        self.ftypes.len()
+    }
+
     pub(in crate::typemap) fn iter(&self) -> impl Iterator<Item = &ForeignTypeS> {
         self.ftypes.iter()
     }
@@ -11,8 +11,8 @@ use crate::{
             parse_ty_with_given_span_checked, DisplayToTokens,
         },
         parse_typemap_macro::{FTypeConvRule, TypeMapConvRuleInfo},
-        ty::RustType,
-        ForeignTypeInfo, TypeMap,
+        ty::RustTypeS,
+        ForeignTypeInfo, RustTypeIdx, TypeMap,
     },
     types::{
         ForeignInterfaceMethod, ForeignerClassInfo, ForeignerMethod, MethodVariant, SelfTypeVariant,
@@ -21,15 +21,15 @@ use crate::{
 
 pub(crate) trait ForeignTypeInfoT {
     fn name(&self) -> &str;
-    fn correspoding_rust_type(&self) -> &RustType;
+    fn correspoding_rust_type(&self) -> RustTypeIdx;
 }
 
 impl ForeignTypeInfoT for ForeignTypeInfo {
     fn name(&self) -> &str {
         self.name.as_str()
     }
-    fn correspoding_rust_type(&self) -> &RustType {
-        &self.correspoding_rust_type
+    fn correspoding_rust_type(&self) -> RustTypeIdx {
+        self.correspoding_rust_type
     }
 }
 
@@ -64,8 +64,8 @@ pub(crate) fn foreign_from_rust_convert_method_output(
     let context_span = rust_ret_ty.span();
     let rust_ret_ty = conv_map.find_or_alloc_rust_type(&rust_ret_ty, src_id);
     conv_map.convert_rust_types(
-        rust_ret_ty.to_idx(),
-        f_output.correspoding_rust_type().to_idx(),
+        rust_ret_ty,
+        f_output.correspoding_rust_type(),
         var_name,
         func_ret_type,
         (src_id, context_span),
@@ -99,16 +99,15 @@ pub(crate) fn foreign_to_rust_convert_method_inputs<
         .zip(f_method.input().iter())
         .zip(arg_names)
     {
-        let to: RustType = conv_map.find_or_alloc_rust_type(fn_arg_type(to_type), src_id);
-        let (mut cur_deps, cur_code) = conv_map.convert_rust_types(
-            f_from.correspoding_rust_type().to_idx(),
-            to.to_idx(),
+        let to: RustTypeIdx = conv_map.find_or_alloc_rust_type(fn_arg_type(to_type), src_id);
+        conv_map.append_conversation_code(
+            f_from.correspoding_rust_type(),
+            to,
             &arg_name,
             func_ret_type,
             (src_id, to_type.span()),
+            (&mut code_deps, &mut ret_code),
         )?;
-        code_deps.append(&mut cur_deps);
-        ret_code.push_str(&cur_code);
     }
     Ok((code_deps, ret_code))
 }
@@ -119,11 +118,8 @@ pub(crate) fn create_suitable_types_for_constructor_and_self(
     constructor_real_type: &Type,
 ) -> (Type, Type) {
     match self_variant {
-        SelfTypeVariant::Default => {
-            unimplemented!();
-        }
-        SelfTypeVariant::Mut => {
-            unimplemented!();
+        SelfTypeVariant::Default | SelfTypeVariant::Mut => {
+            unreachable!("methods with self by value are rejected by parser");
         }
         SelfTypeVariant::Rptr | SelfTypeVariant::RptrMut => {
             let self_type = class.self_type_as_ty();
@@ -176,10 +172,10 @@ pub(crate) fn rust_to_foreign_convert_method_inputs<
         .zip(f_method.input().iter())
         .zip(arg_names)
     {
-        let from: RustType = conv_map.find_or_alloc_rust_type(fn_arg_type(from_ty), src_id);
+        let from: RustTypeIdx = conv_map.find_or_alloc_rust_type(fn_arg_type(from_ty), src_id);
         let (mut cur_deps, cur_code) = conv_map.convert_rust_types(
-            from.to_idx(),
-            to_f.correspoding_rust_type().to_idx(),
+            from,
+            to_f.correspoding_rust_type(),
             &arg_name,
             func_ret_type,
             (src_id, from_ty.span()),
@@ -234,40 +230,44 @@ pub(crate) fn validate_cfg_options(
     Ok(())
 }
 
-pub(crate) fn boxed_type(tmap: &mut TypeMap, from: &RustType) -> RustType {
+pub(crate) fn boxed_type(tmap: &mut TypeMap, from: RustTypeIdx) -> RustTypeIdx {
     for smart_pointer in &["Box", "Rc", "Arc"] {
-        if let Some(inner_ty) = check_if_smart_pointer_return_inner_type(from, *smart_pointer) {
-            let inner_ty: RustType = tmap.find_or_alloc_rust_type(&inner_ty, from.src_id);
-            return inner_ty;
+        if let Some(inner_ty) =
+            check_if_smart_pointer_return_inner_type(&tmap[from], *smart_pointer)
+        {
+            let src_id = tmap[from].src_id;
+            return tmap.find_or_alloc_rust_type(&inner_ty, src_id);
         }
     }
-    from.clone()
+    from
 }
 
 pub(crate) fn convert_to_heap_pointer(
     tmap: &mut TypeMap,
-    from: &RustType,
+    from: RustTypeIdx,
     var_name: &str,
-) -> (RustType, String) {
+) -> (RustTypeIdx, String) {
     for smart_pointer in &["Box", "Rc", "Arc"] {
-        if let Some(inner_ty) = check_if_smart_pointer_return_inner_type(from, *smart_pointer) {
-            let inner_ty: RustType = tmap.find_or_alloc_rust_type(&inner_ty, from.src_id);
+        if let Some(inner_ty) =
+            check_if_smart_pointer_return_inner_type(&tmap[from], *smart_pointer)
+        {
+            let src_id = tmap[from].src_id;
+            let inner_ty: RustTypeIdx = tmap.find_or_alloc_rust_type(&inner_ty, src_id);
             let code = format!(
                 r#"
     let {var_name}: *const {inner_ty} = {smart_pointer}::into_raw({var_name});
 "#,
                 var_name = var_name,
-                inner_ty = inner_ty.normalized_name,
+                inner_ty = tmap[inner_ty].normalized_name,
                 smart_pointer = *smart_pointer,
             );
             return (inner_ty, code);
         }
     }
 
-    let inner_ty = from.clone();
-    let inner_ty_str = normalize_ty_lifetimes(&inner_ty.ty);
+    let inner_ty_str = normalize_ty_lifetimes(&tmap[from].ty);
     (
-        inner_ty,
+        from,
         format!(
             r#"
     let {var_name}: Box<{inner_ty}> = Box::new({var_name});
@@ -280,7 +280,7 @@ pub(crate) fn convert_to_heap_pointer(
 }
 
 pub(crate) fn unpack_from_heap_pointer(
-    from: &RustType,
+    from: &RustTypeS,
     var_name: &str,
     unbox_if_boxed: bool,
 ) -> String {
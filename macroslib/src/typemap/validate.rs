@@ -0,0 +1,346 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use petgraph::{
+    algo::tarjan_scc,
+    graph::{EdgeIndex, NodeIndex},
+    visit::EdgeRef,
+    Graph,
+};
+use rustc_hash::{FxHashMap, FxHashSet};
+use smallvec::SmallVec;
+
+use crate::{
+    error::SourceIdSpan,
+    typemap::{RulePrecedence, RustTypeIdx, TypeGraphIdx, TypeMap},
+};
+
+/// Problem in conversation graph and rules that cause it
+#[derive(Debug)]
+pub(crate) struct ConvGraphProblem {
+    pub(crate) descr: String,
+    pub(crate) rules: Vec<(SourceIdSpan, String)>,
+}
+
+type ConvEdge = EdgeIndex<TypeGraphIdx>;
+
+impl TypeMap {
+    /// Check conversation graph after merge of all types maps:
+    /// find cycles of rules without cost and pairs of types connected
+    /// via several paths with the same cost and length, in this case
+    /// choice of path is arbitrary.
+    /// Only pairs where at least one type is binded to foreign type are checked,
+    /// and only ambiguities caused by not built-in rules are reported.
+    pub(crate) fn validate_conv_graph(&self) -> Vec<ConvGraphProblem> {
+        let mut ret = self.find_pathological_cycles();
+        ret.extend(self.find_ambiguous_paths());
+        ret
+    }
+
+    fn find_pathological_cycles(&self) -> Vec<ConvGraphProblem> {
+        let mut ret = vec![];
+        let mut zero_cost_graph =
+            Graph::<(), ConvEdge, petgraph::Directed, TypeGraphIdx>::with_capacity(
+                self.conv_graph.node_count(),
+                0,
+            );
+        for _ in self.conv_graph.node_indices() {
+            zero_cost_graph.add_node(());
+        }
+        for edge in self.conv_graph.edge_references() {
+            if edge.source() == edge.target() {
+                ret.push(ConvGraphProblem {
+                    descr: format!(
+                        "rule converts type '{}' to itself",
+                        self.conv_graph[edge.source()]
+                    ),
+                    rules: vec![self.rule_descr(edge.id())],
+                });
+            } else if edge.weight().cost == 0 {
+                zero_cost_graph.add_edge(edge.source(), edge.target(), edge.id());
+            }
+        }
+        for scc in tarjan_scc(&zero_cost_graph) {
+            if scc.len() < 2 {
+                continue;
+            }
+            let scc: FxHashSet<NodeIndex<TypeGraphIdx>> = scc.into_iter().collect();
+            let mut types: Vec<_> = scc
+                .iter()
+                .map(|idx| self.conv_graph[*idx].normalized_name.as_str())
+                .collect();
+            types.sort();
+            let mut rules: Vec<ConvEdge> = zero_cost_graph
+                .edge_references()
+                .filter(|e| scc.contains(&e.source()) && scc.contains(&e.target()))
+                .map(|e| *e.weight())
+                .collect();
+            rules.sort();
+            ret.push(ConvGraphProblem {
+                descr: format!(
+                    "cycle of rules with zero cost between types: {}",
+                    types.join(", ")
+                ),
+                rules: rules.into_iter().map(|e| self.rule_descr(e)).collect(),
+            });
+        }
+        ret
+    }
+
+    fn find_ambiguous_paths(&self) -> Vec<ConvGraphProblem> {
+        let mut binded_to_ftype = FxHashSet::<RustTypeIdx>::default();
+        for ftype in self.ftypes_storage.iter() {
+            for rule in ftype
+                .into_from_rust
+                .iter()
+                .chain(ftype.from_into_rust.iter())
+            {
+                binded_to_ftype.insert(rule.rust_ty);
+                if let Some(ref intermediate) = rule.intermediate {
+                    binded_to_ftype.insert(intermediate.intermediate_ty);
+                }
+            }
+        }
+
+        let mut ret = vec![];
+        let mut reported = FxHashSet::<Vec<ConvEdge>>::default();
+        for from in self.conv_graph.node_indices() {
+            let paths = all_cheapest_paths(self, from);
+            for to in &paths.settle_order {
+                let to = *to;
+                if !paths.ambiguous[&to]
+                    || !(binded_to_ftype.contains(&from) || binded_to_ftype.contains(&to))
+                {
+                    continue;
+                }
+                let (path1, path2) = paths.two_paths(self, from, to);
+                let mut diff: Vec<ConvEdge> = path1
+                    .iter()
+                    .filter(|e| !path2.contains(e))
+                    .chain(path2.iter().filter(|e| !path1.contains(e)))
+                    .cloned()
+                    .collect();
+                diff.sort();
+                if diff
+                    .iter()
+                    .all(|e| self.conv_graph[*e].precedence == RulePrecedence::BuiltIn)
+                    || !reported.insert(diff.clone())
+                {
+                    continue;
+                }
+                ret.push(ConvGraphProblem {
+                    descr: format!(
+                        "several paths with the same cost to convert '{}' to '{}': {} and {}",
+                        self.conv_graph[from],
+                        self.conv_graph[to],
+                        self.path_descr(from, &path1),
+                        self.path_descr(from, &path2),
+                    ),
+                    rules: diff.into_iter().map(|e| self.rule_descr(e)).collect(),
+                });
+            }
+        }
+        ret
+    }
+
+    fn rule_descr(&self, edge: ConvEdge) -> (SourceIdSpan, String) {
+        let (from, to) = self
+            .conv_graph
+            .edge_endpoints(edge)
+            .expect("Internal error: no edge");
+        let rule = &self.conv_graph[edge];
+        (
+            (rule.src_id, rule.span),
+            format!(
+                "conversation rule '{}' -> '{}'",
+                self.conv_graph[from], self.conv_graph[to]
+            ),
+        )
+    }
+
+    fn path_descr(&self, from: RustTypeIdx, path: &[ConvEdge]) -> String {
+        let mut ret = format!("'{}'", self.conv_graph[from]);
+        for edge in path {
+            let (_, to) = self
+                .conv_graph
+                .edge_endpoints(*edge)
+                .expect("Internal error: no edge");
+            ret.push_str(&format!(" -> '{}'", self.conv_graph[to]));
+        }
+        ret
+    }
+}
+
+struct CheapestPaths {
+    /// all edges that are the last step of one of the cheapest paths
+    preds: FxHashMap<RustTypeIdx, SmallVec<[ConvEdge; 2]>>,
+    ambiguous: FxHashMap<RustTypeIdx, bool>,
+    settle_order: Vec<RustTypeIdx>,
+}
+
+impl CheapestPaths {
+    /// Path that is used for conversation and an alternative one
+    /// with the same cost and length
+    fn two_paths(
+        &self,
+        tmap: &TypeMap,
+        from: RustTypeIdx,
+        to: RustTypeIdx,
+    ) -> (Vec<ConvEdge>, Vec<ConvEdge>) {
+        let source = |edge: ConvEdge| {
+            tmap.conv_graph
+                .edge_endpoints(edge)
+                .expect("Internal error: no edge")
+                .0
+        };
+        let path_to = |mut cur: RustTypeIdx| {
+            let mut edges = vec![];
+            while cur != from {
+                let edge = self.preds[&cur][0];
+                edges.push(edge);
+                cur = source(edge);
+            }
+            edges.reverse();
+            edges
+        };
+        let path1 = path_to(to);
+        let mut path2 = path1.clone();
+        for (i, edge) in path1.iter().enumerate().rev() {
+            let (_, target) = tmap
+                .conv_graph
+                .edge_endpoints(*edge)
+                .expect("Internal error: no edge");
+            let preds = &self.preds[&target];
+            let other = preds
+                .iter()
+                .find(|e| **e != *edge && !self.ambiguous_or_false(source(**e)))
+                .or_else(|| preds.iter().find(|e| **e != *edge));
+            let other = match other {
+                Some(x) => *x,
+                None => continue,
+            };
+            let mut alt = path_to(source(other));
+            alt.push(other);
+            alt.extend_from_slice(&path1[i + 1..]);
+            path2 = alt;
+            break;
+        }
+        (path1, path2)
+    }
+
+    fn ambiguous_or_false(&self, idx: RustTypeIdx) -> bool {
+        self.ambiguous.get(&idx).cloned().unwrap_or(false)
+    }
+}
+
+/// The same weights as in `find_cheapest_path`: cost, then number of steps,
+/// but remember all edges that give the best weight
+fn all_cheapest_paths(tmap: &TypeMap, from: RustTypeIdx) -> CheapestPaths {
+    let conv_graph = &tmap.conv_graph;
+    let mut best = FxHashMap::<RustTypeIdx, (u64, usize)>::default();
+    let mut preds = FxHashMap::<RustTypeIdx, SmallVec<[ConvEdge; 2]>>::default();
+    let mut ambiguous = FxHashMap::<RustTypeIdx, bool>::default();
+    let mut settle_order = vec![];
+    let mut queue = BinaryHeap::new();
+
+    best.insert(from, (0, 0));
+    queue.push(Reverse((0u64, 0usize, from)));
+    while let Some(Reverse((cost, steps, node))) = queue.pop() {
+        if ambiguous.contains_key(&node) || best[&node] != (cost, steps) {
+            continue;
+        }
+        let node_ambiguous = preds.get(&node).is_some_and(|node_preds| {
+            node_preds.len() > 1
+                || node_preds.iter().any(|e| {
+                    let src = conv_graph
+                        .edge_endpoints(*e)
+                        .expect("Internal error: no edge")
+                        .0;
+                    ambiguous.get(&src).cloned().unwrap_or(false)
+                })
+        });
+        ambiguous.insert(node, node_ambiguous);
+        if node != from {
+            settle_order.push(node);
+        }
+        for edge in conv_graph.edges(node) {
+            let next = edge.target();
+            if ambiguous.contains_key(&next) {
+                continue;
+            }
+            let next_score = (cost + u64::from(edge.weight().cost), steps + 1);
+            match best.get(&next) {
+                Some(prev) if *prev < next_score => {}
+                Some(prev) if *prev == next_score => {
+                    preds.entry(next).or_default().push(edge.id());
+                }
+                _ => {
+                    best.insert(next, next_score);
+                    let next_preds = preds.entry(next).or_default();
+                    next_preds.clear();
+                    next_preds.push(edge.id());
+                    queue.push(Reverse((next_score.0, next_score.1, next)));
+                }
+            }
+        }
+    }
+    CheapestPaths {
+        preds,
+        ambiguous,
+        settle_order,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{source_registry::SourceRegistry, SourceCode};
+
+    #[test]
+    fn test_validate_conv_graph() {
+        let _ = env_logger::try_init();
+        let mut types_map = TypeMap::default();
+        let mut src_reg = SourceRegistry::default();
+        let src_id = src_reg.register(SourceCode {
+            id_of_code: "test_validate_conv_graph".into(),
+            code: r#"
+foreign_typemap!(($p:r_type) Foo => jlong { $out = jlong_from_foo($p) };);
+foreign_typemap!(($p:r_type) Foo => Bar { $out = bar_from_foo($p) };);
+foreign_typemap!(($p:r_type) Foo => Baz { $out = baz_from_foo($p) };);
+foreign_typemap!(($p:r_type) Bar => jint { $out = jint_from_bar($p) };);
+foreign_typemap!(($p:r_type) Baz => jint { $out = jint_from_baz($p) };);
+foreign_typemap!(($p:r_type, cost = 0) Qux => Boo { $out = boo_from_qux($p) };);
+foreign_typemap!(($p:r_type, cost = 0) Boo => Qux { $out = qux_from_boo($p) };);
+foreign_typemap!(($p:r_type) jint => Qux { $out = qux_from_jint($p) };);
+foreign_typemap!(($p:r_type) jlong; ($p:f_type) "long";);
+foreign_typemap!(($p:r_type) jint; ($p:f_type) "int";);
+"#
+            .into(),
+        });
+        types_map.merge(src_id, src_reg.src(src_id), 64).unwrap();
+
+        let problems = types_map.validate_conv_graph();
+        println!("problems: {:?}", problems);
+        assert_eq!(2, problems.len());
+        assert_eq!(
+            "cycle of rules with zero cost between types: Boo, Qux",
+            problems[0].descr
+        );
+        assert_eq!(
+            vec![
+                "conversation rule 'Qux' -> 'Boo'",
+                "conversation rule 'Boo' -> 'Qux'"
+            ],
+            problems[0]
+                .rules
+                .iter()
+                .map(|x| x.1.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            "several paths with the same cost to convert 'Foo' to 'jint': \
+             'Foo' -> 'Bar' -> 'jint' and 'Foo' -> 'Baz' -> 'jint'",
+            problems[1].descr
+        );
+        assert_eq!(4, problems[1].rules.len());
+    }
+}
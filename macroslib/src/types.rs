@@ -1,10 +1,12 @@
-use proc_macro2::{Ident, Span};
+use proc_macro2::{Ident, Span, TokenStream};
 
+use std::rc::Rc;
 use syn::{parse_quote, spanned::Spanned, Token, Type};
 
 use crate::{
     error::{DiagnosticError, Result, SourceIdSpan},
     source_registry::SourceId,
+    typemap::TypeMapConvRuleInfo,
 };
 
 #[derive(Debug, Clone)]
@@ -16,6 +18,18 @@ pub(crate) struct ForeignerClassInfo {
     pub foreigner_code: String,
     pub doc_comments: Vec<String>,
     pub copy_derived: bool,
+    /// `#[cpp_namespace = "a::b"]`: C++ namespace for class
+    /// in addition to namespace from `CppConfig`
+    pub cpp_namespace: Option<String>,
+    /// `#[single_threaded]`: objects of class are used only from one thread,
+    /// so Rust types of class are not checked for `Send`
+    pub single_threaded: bool,
+    /// `#[catch_panics]` or `#[catch_panics = false]`: catch panics in methods
+    /// of class or not, regardless of configuration of generator
+    pub catch_panics: Option<bool>,
+    /// conversation rules that used only for this class,
+    /// they have priority over global rules
+    pub typemaps: Vec<TypeMapConvRuleInfo>,
 }
 
 /// Two types instead of one, to simplify live to developer
@@ -80,6 +94,23 @@ pub(crate) struct ForeignerMethod {
     pub(crate) name_alias: Option<Ident>,
     pub(crate) access: MethodAccess,
     pub(crate) doc_comments: Vec<String>,
+    /// `#[direct_buffer]`: pass `&[u8]`/`&mut [u8]` arguments
+    /// as direct `java.nio.ByteBuffer` without copying
+    pub(crate) direct_buffer: bool,
+    /// `#[critical_arrays]`: access primitive arrays with
+    /// `GetPrimitiveArrayCritical` instead of copying
+    pub(crate) critical_arrays: bool,
+    /// `#[completable_future]`: last argument is completion callback,
+    /// method returns `java.util.concurrent.CompletableFuture` instead
+    pub(crate) completable_future: bool,
+    /// `#[fast_native]`: mark native method with `@FastNative` (Android)
+    pub(crate) fast_native: bool,
+    /// `#[critical_native]`: mark native method with `@CriticalNative` (Android),
+    /// only primitive types, native function gets no `JNIEnv`
+    pub(crate) critical_native: bool,
+    /// `#[awaitable]`: last argument is completion callback,
+    /// C++ method returns `RustAwaitable` for `co_await` instead
+    pub(crate) awaitable: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -166,6 +197,10 @@ impl ForeignEnumInfo {
     pub(crate) fn span(&self) -> Span {
         self.name.span()
     }
+    /// Has at least one item with payload, like `Shape::Circle(f64)`
+    pub(crate) fn is_data_enum(&self) -> bool {
+        self.items.iter().any(|x| !x.fields.is_empty())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -173,6 +208,32 @@ pub(crate) struct ForeignEnumItem {
     pub(crate) name: Ident,
     pub(crate) rust_name: syn::Path,
     pub(crate) doc_comments: Vec<String>,
+    /// Payload of data-carrying item, empty for C-like item
+    pub(crate) fields: Vec<ForeignEnumItemField>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ForeignEnumItemField {
+    /// `None` for tuple-like item
+    pub(crate) name: Option<Ident>,
+    pub(crate) ty: Type,
+}
+
+impl ForeignEnumItemField {
+    /// name of field on foreign side, `f0`, `f1` and so on for tuple-like item
+    pub(crate) fn foreign_name(&self, idx: usize) -> String {
+        match self.name {
+            Some(ref name) => name.to_string(),
+            None => format!("f{}", idx),
+        }
+    }
+    /// name of field in Rust, `0`, `1` and so on for tuple-like item
+    pub(crate) fn rust_name(&self, idx: usize) -> String {
+        match self.name {
+            Some(ref name) => name.to_string(),
+            None => idx.to_string(),
+        }
+    }
 }
 
 pub(crate) struct ForeignInterface {
@@ -200,7 +261,28 @@ pub(crate) struct ForeignInterfaceMethod {
 }
 
 pub(crate) enum ItemToExpand {
-    Class(ForeignerClassInfo),
+    /// shared with `TypeMap` after language backend registers it,
+    /// so should be modified only before registration
+    Class(Rc<ForeignerClassInfo>),
     Interface(ForeignInterface),
     Enum(ForeignEnumInfo),
 }
+
+impl ItemToExpand {
+    /// like "class Foo"
+    pub(crate) fn descr(&self) -> String {
+        match self {
+            ItemToExpand::Class(fclass) => format!("class {}", fclass.name),
+            ItemToExpand::Enum(fenum) => format!("enum {}", fenum.name),
+            ItemToExpand::Interface(finterface) => format!("interface {}", finterface.name),
+        }
+    }
+}
+
+/// Rust code generated for one `ItemToExpand`,
+/// or for helpers that are common for all items
+pub(crate) struct ExpandedItem {
+    /// like "class Foo", used as marker in dump of generated code
+    pub(crate) descr: String,
+    pub(crate) code: Vec<TokenStream>,
+}
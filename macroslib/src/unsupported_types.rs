@@ -0,0 +1,104 @@
+use petgraph::Direction;
+use syn::{spanned::Spanned, Type};
+
+use crate::{
+    diagnostic_codes::DiagnosticCode,
+    error::{collect_error, DiagnosticError, Result},
+    typemap::ast::{normalize_ty_lifetimes, DisplayToTokens},
+    types::{ForeignerClassInfo, ForeignerMethod},
+};
+
+/// Failures of mapping of types of methods' arguments and results,
+/// collected to report all of them at once
+#[derive(Default)]
+pub(crate) struct UnsupportedTypes {
+    err: Option<DiagnosticError>,
+}
+
+impl UnsupportedTypes {
+    /// Record failure `err` of mapping of `arg` argument of `method`
+    /// (numbered from 1, without `self`), or of its result if `arg` is `None`
+    pub(crate) fn add(
+        &mut self,
+        class: &ForeignerClassInfo,
+        method: &ForeignerMethod,
+        arg: Option<(usize, &syn::FnArg)>,
+        ty: &Type,
+        err: DiagnosticError,
+    ) {
+        let (place, direction) = match arg {
+            Some((idx, arg)) => (
+                match arg_name(arg) {
+                    Some(name) => format!("argument {} `{}`", idx, name),
+                    None => format!("argument {}", idx),
+                },
+                Direction::Incoming,
+            ),
+            None => ("result".to_string(), Direction::Outgoing),
+        };
+        let sp = (class.src_id, ty.span());
+        let mut report = DiagnosticError::new2(
+            sp,
+            format!(
+                "class {}, method {}, {}: type `{}` is not supported",
+                class.name,
+                DisplayToTokens(&method.rust_id),
+                place,
+                normalize_ty_lifetimes(ty)
+            ),
+        )
+        .with_code(DiagnosticCode::UnsupportedType);
+        report.merge(err);
+        report.span_note(sp, hint(ty, direction));
+        collect_error(&mut self.err, report);
+    }
+
+    pub(crate) fn into_result(self) -> Result<()> {
+        match self.err {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+fn arg_name(arg: &syn::FnArg) -> Option<String> {
+    match arg {
+        syn::FnArg::Captured(syn::ArgCaptured {
+            pat: syn::Pat::Ident(ref pat),
+            ..
+        }) => Some(pat.ident.to_string()),
+        _ => None,
+    }
+}
+
+fn hint(ty: &Type, direction: Direction) -> String {
+    let mut ty = ty;
+    while let Type::Reference(ref r) = ty {
+        ty = &*r.elem;
+    }
+    let ty_name = normalize_ty_lifetimes(ty);
+    let rule = match direction {
+        Direction::Incoming => format!(
+            "add foreign_typemap! rule from foreign type to `{}`",
+            ty_name
+        ),
+        Direction::Outgoing => format!(
+            "add foreign_typemap! rule from `{}` to foreign type",
+            ty_name
+        ),
+    };
+    let is_plain_type = match ty {
+        Type::Path(ref p) => {
+            p.qself.is_none() && p.path.segments.iter().all(|s| s.arguments.is_empty())
+        }
+        _ => false,
+    };
+    if is_plain_type {
+        format!(
+            "hint: wrap `{}` in foreigner_class! (or foreign_enum! for enum), or {}",
+            ty_name, rule
+        )
+    } else {
+        format!("hint: {}", rule)
+    }
+}
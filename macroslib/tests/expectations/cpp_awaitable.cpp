@@ -0,0 +1,11 @@
+"RustAwaitable<Item> fetch(int32_t a_0) const  noexcept(false);";
+
+"RustAwaitable<int64_t> count() const  noexcept(false);";
+
+"static RustAwaitable<void> ping() noexcept(false);";
+
+"Client_fetch(this->self_, a_0, ret.opaque(), [](void *opaque, ItemOpaque * x) noexcept { RustAwaitable<Item>::complete(opaque, Item(x)); });";
+
+"void Client_count(const ClientOpaque * const self, void *swig_opaque, void (*swig_done)(void *, int64_t));";
+
+"void Client_ping(void *swig_opaque, void (*swig_done)(void *));";
@@ -0,0 +1,15 @@
+foreigner_class!(class Item {
+    self_type Item;
+    constructor Item::new(_: i32) -> Item;
+});
+
+foreigner_class!(class Client {
+    self_type Client;
+    constructor Client::new() -> Client;
+    #[awaitable]
+    method Client::fetch(&self, id: i32, done: Box<dyn FnOnce(Item) + Send>);
+    #[awaitable]
+    method Client::count(&self, done: Box<dyn FnOnce(i64) + Send>);
+    #[awaitable]
+    static_method Client::ping(done: Box<dyn FnOnce() + Send>);
+});
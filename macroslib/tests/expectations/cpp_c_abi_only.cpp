@@ -0,0 +1,5 @@
+"#include \"c_Foo.h\"\n#include \"c_Observer.h\"\n#include \"c_Boo.h\"\n";
+"typedef struct FooOpaque FooOpaque;";
+"#include \"c_Observer.h\"";
+"#include \"rust_result.h\"";
+"#ifndef FooOpaque_DEFINED\n#define FooOpaque_DEFINED\n    typedef struct FooOpaque FooOpaque;\n#endif";
@@ -0,0 +1,7 @@
+"const char *example_rust_panic_message(void);";
+"inline void rust_check_panic()";
+"throw std::logic_error(what);";
+"int32_t ret = example_Foo_f(this->self_, a_0);\n        rust_check_panic();";
+"example_Foo_g();\n        rust_check_panic();";
+"this->self_ = example_Foo_new();\n        rust_check_panic();\n        if (this->self_ == nullptr) {";
+"static void g() noexcept(false)";
@@ -0,0 +1,3 @@
+"using Shape = boost::variant<Circle, Empty>;";
+"switch (x.which()) {";
+"const auto &v = boost::get<Circle>(x);";
@@ -0,0 +1,10 @@
+"#ifdef EXAMPLE_EXPORTS\n#define EXAMPLE_API __declspec(dllexport)";
+"#define EXAMPLE_API __declspec(dllimport)";
+"#if defined(EXAMPLE_STATIC)\n#define EXAMPLE_API\n";
+"EXAMPLE_API FooOpaque *example_Foo_new();";
+"EXAMPLE_API void example_Foo_delete(const FooOpaque *self);";
+"EXAMPLE_API struct CRustString example_Foo_name(const FooOpaque * const self);";
+"EXAMPLE_API uint32_t example_Shape_tag(const ShapeOpaque * self);";
+"EXAMPLE_API extern void example_RustForeignVecFoo_free(struct CRustForeignVec);";
+"EXAMPLE_API void example_crust_string_free(struct CRustString str);";
+"EXAMPLE_API void example_CRustVecU8_free(struct CRustVecU8 vec);";
@@ -0,0 +1,5 @@
+"throw std::runtime_error(what);";
+"int32_t ret = Foo_check(a_0);\n        rust_check_panic();";
+"static int32_t check(int64_t a_0) noexcept(false)";
+"int32_t ret = Foo_f(this->self_, a_0);\n        return ret;";
+"int32_t f(int64_t a_0) const  noexcept;";
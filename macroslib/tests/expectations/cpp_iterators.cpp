@@ -0,0 +1,9 @@
+"RustVecI64 ids() const  noexcept";
+
+"RustVecU16 small() const  noexcept";
+
+"RustVecU64 iter_ids() const  noexcept";
+
+"RustForeignVecFoo iter_foos() const  noexcept";
+
+"using RustVecU64 = RustVec<CRustVecU64, CRustVecU64_free>;";
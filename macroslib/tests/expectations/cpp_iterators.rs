@@ -0,0 +1,13 @@
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::new(_: i32) -> Foo;
+});
+
+foreigner_class!(class Boo {
+    self_type Boo;
+    constructor Boo::default() -> Boo;
+    method Boo::ids(&self) -> Vec<i64>;
+    method Boo::small(&self) -> Vec<u16>;
+    method Boo::iter_ids(&self) -> Box<dyn Iterator<Item = u64>>;
+    method Boo::iter_foos(&self) -> Box<dyn Iterator<Item = Foo>>;
+});
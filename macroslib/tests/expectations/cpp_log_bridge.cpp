@@ -0,0 +1 @@
+"char example_rust_log_init(RustLogCallback callback, void *opaque, uint32_t max_level);";
@@ -0,0 +1,3 @@
+"namespace acme::storage::v2 {";
+"} // namespace acme::storage::v2";
+"namespace acme::ui {\nusing ::acme::storage::v2::Foo;\nusing ::acme::storage::v2::FooRef;";
@@ -0,0 +1,3 @@
+"namespace acme { namespace storage { namespace v2 {";
+"} } } // namespace acme::storage::v2";
+"namespace acme { namespace ui {\nusing ::acme::storage::v2::Foo;\nusing ::acme::storage::v2::FooRef;";
@@ -0,0 +1,23 @@
+"#include \"RustForeignVecFoo.h\"";
+"typedef struct BooOpaque BooOpaque;";
+"    BooWrapper() noexcept;";
+"    Foo foo() const  noexcept;";
+"#include \"Boo.hpp\"\n#include \"Foo.hpp\"";
+r#"
+    template<bool OWN_DATA>
+    Foo BooWrapper<OWN_DATA>::foo() const  noexcept
+    {
+        FooOpaque * ret = Boo_foo(this->self_);
+        return Foo(ret);
+    }
+"#;
+"    template Foo BooWrapper<true>::foo() const noexcept;\n";
+"    template Foo BooWrapper<false>::foo() const noexcept;\n";
+"    template void BooWrapper<true>::set(int32_t a_0) noexcept;\n";
+"    template BooWrapper<true>::BooWrapper() noexcept;\n";
+"    template void BooWrapper<true>::free_mem(BooWrapper<true>::SelfType &) noexcept;\n";
+"typedef struct ShapeOpaque ShapeOpaque;";
+"Shape Shape_from_rust(ShapeOpaque *p);\nShapeOpaque *Shape_to_rust(const Shape &x);\n";
+"#include \"Shape.hpp\"\n#include \"c_Shape.h\"";
+"\nShape Shape_from_rust(ShapeOpaque *p)\n{";
+"\nShapeOpaque *Shape_to_rust(const Shape &x)\n{";
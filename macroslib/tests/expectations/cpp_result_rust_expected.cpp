@@ -0,0 +1,3 @@
+"RustExpected<void, RustString> f() const  noexcept;";
+"static RustExpected<Foo, RustString> create() noexcept;";
+"RustExpected<Foo, RustString>{RustUnexpected<RustString>{RustString{ret.data.err}}};";
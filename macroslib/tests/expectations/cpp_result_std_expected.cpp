@@ -0,0 +1,3 @@
+"std::expected<void, RustString> f() const  noexcept;";
+"static std::expected<Foo, RustString> create() noexcept;";
+"std::expected<Foo, RustString>{std::unexpected<RustString>{RustString{ret.data.err}}};";
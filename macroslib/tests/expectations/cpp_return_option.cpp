@@ -24,3 +24,18 @@ r#"template<bool OWN_DATA>
 "std::optional<RustString> f9()";
 
 "std::optional<bool> f10()";
+
+"std::optional<int8_t> f11()";
+
+"std::optional<uint16_t> f12()";
+
+"std::optional<RustVecI32> f13()";
+
+r#"template<bool OWN_DATA>
+    inline std::optional<RustVecI32> FooWrapper<OWN_DATA>::f13() const  noexcept
+    {
+        struct CRustVecI32 ret = Foo_f13(this->self_);
+        return ret.data != nullptr ? RustVecI32{ret} : std::optional<RustVecI32>();
+    }"#;
+
+"std::optional<RustForeignVecBoo> f14()";
@@ -24,4 +24,8 @@ foreigner_class!(class Foo {
    method Foo::f8(&self) -> Option<&str>;
    method Foo::f9(&self) -> Option<String>;
    method Foo::f10(&self) -> Option<bool>;
+   method Foo::f11(&self) -> Option<i8>;
+   method Foo::f12(&self) -> Option<u16>;
+   method Foo::f13(&self) -> Option<Vec<i32>>;
+   method Foo::f14(&self) -> Option<Vec<Boo>>;
 });
@@ -0,0 +1,5 @@
+"#include <memory>";
+"std::shared_ptr<Foo> f() const  noexcept(false);";
+"return std::make_shared<Foo>(ret);";
+"static void consume(Foo a_0) noexcept;";
+"virtual void on_foo(std::shared_ptr<Foo> a_0) = 0;";
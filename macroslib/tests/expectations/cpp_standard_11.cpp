@@ -0,0 +1,2 @@
+"namespace org { namespace examples {";
+"static boost::optional<Foo> find(";
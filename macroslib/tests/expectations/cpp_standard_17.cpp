@@ -0,0 +1,4 @@
+"namespace org::examples {";
+"[[nodiscard]] int32_t f() const  noexcept;";
+"[[nodiscard]] static std::optional<Foo> find(";
+"    void set_f(";
@@ -0,0 +1,6 @@
+"#include <span>";
+"int32_t sum(std::span<const int32_t> a_0) const  noexcept;";
+"void fill(std::span<uint8_t> a_0) const  noexcept;";
+"std::span<const uint32_t> ids() const  noexcept;";
+"Foo_sum(this->self_, CRustSliceI32{ a_0.data(), a_0.size() });";
+"return std::span<const uint32_t>{ ret.data, ret.len };";
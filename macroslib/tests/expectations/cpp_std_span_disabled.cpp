@@ -0,0 +1,2 @@
+"int32_t sum(struct CRustSliceI32 a_0) const  noexcept;";
+"void fill(struct CRustSliceU8 a_0) const  noexcept;";
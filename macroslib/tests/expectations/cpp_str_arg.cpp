@@ -0,0 +1,2 @@
+"void set_name(const char * a_0)  noexcept;";
+"void set_nick(std::optional<const char *> a_0)  noexcept;";
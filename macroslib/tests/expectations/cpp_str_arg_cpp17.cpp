@@ -0,0 +1,4 @@
+"void set_name(std::string_view a_0)  noexcept;";
+"Foo_set_name(this->self_, CRustStrView{ a_0.data(), a_0.size() });";
+"void set_nick(std::optional<std::string_view> a_0)  noexcept;";
+"Foo_set_nick(this->self_, !!a_0 ? CRustOptionStr{CRustStrView{ a_0->data(), a_0->size() }, 1} : c_option_empty<CRustOptionStr>());";
@@ -0,0 +1,5 @@
+"FooOpaque *example_Foo_new();";
+"void example_Foo_delete(const FooOpaque *self);";
+"example_Foo_name(this->self_)";
+"extern void example_RustForeignVecFoo_free(struct CRustForeignVec);";
+"void example_crust_string_free(struct CRustString str);";
@@ -0,0 +1,3 @@
+"#include <memory>";
+"static std::unique_ptr<FooWrapper<true>> create(int32_t a_0, const char * a_1) noexcept(false)";
+"return std::unique_ptr<FooWrapper<true>>(new FooWrapper<true>(std::move(a_0), std::move(a_1)));";
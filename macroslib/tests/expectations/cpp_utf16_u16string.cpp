@@ -0,0 +1,11 @@
+"#include \"rust_utf16.h\"";
+"    std::u16string name() const  noexcept(false);";
+"    void set_name(std::u16string_view a_0)  noexcept;";
+"    std::u16string greet(std::u16string_view a_0) const  noexcept(false);";
+"return rust_to_utf16_string<char16_t>(ret);";
+"Foo_set_name(this->self_, rust_utf16_slice<char16_t>(a_0));";
+"    std::optional<std::u16string> nick() const  noexcept(false);";
+"    void set_nick(std::optional<std::u16string_view> a_0)  noexcept;";
+"return ret.data != nullptr ? std::optional<std::u16string>{rust_to_utf16_string<char16_t>(ret)} : std::optional<std::u16string>();";
+"Foo_set_nick(this->self_, rust_utf16_option_slice(a_0));";
+"struct CRustVecU16 Foo_greet(const FooOpaque * const self, struct CRustSliceU16 a_0);";
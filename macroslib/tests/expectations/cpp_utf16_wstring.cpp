@@ -0,0 +1,11 @@
+"#include \"rust_utf16.h\"";
+"    std::wstring name() const  noexcept(false);";
+"    void set_name(std::wstring_view a_0)  noexcept;";
+"    std::wstring greet(std::wstring_view a_0) const  noexcept(false);";
+"return rust_to_utf16_string<wchar_t>(ret);";
+"Foo_set_name(this->self_, rust_utf16_slice<wchar_t>(a_0));";
+"    std::optional<std::wstring> nick() const  noexcept(false);";
+"    void set_nick(std::optional<std::wstring_view> a_0)  noexcept;";
+"return ret.data != nullptr ? std::optional<std::wstring>{rust_to_utf16_string<wchar_t>(ret)} : std::optional<std::wstring>();";
+"Foo_set_nick(this->self_, rust_utf16_option_slice(a_0));";
+"struct CRustVecU16 Foo_greet(const FooOpaque * const self, struct CRustSliceU16 a_0);";
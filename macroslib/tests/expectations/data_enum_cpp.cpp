@@ -0,0 +1,12 @@
+r#"struct Rect {
+    double w;
+    double h;
+};"#;
+"using Shape = std::variant<Circle, Rect, Named, Empty>;";
+"template <typename... Fs> ShapeVisitor(Fs...)->ShapeVisitor<Fs...>;";
+r#"ret = Named{std::string(name.data, name.len), (visible != 0)};"#;
+r#"return Shape_new_Named(CRustStrView{ v.name.data(), v.name.size() }, v.visible ? 1 : 0);"#;
+"ShapeOpaque * Shape_new_Empty(void);";
+"double area(Shape a_0) const  noexcept;";
+"double ret = Boo_area(this->self_, internal::Shape_to_rust(a_0));";
+"return internal::Shape_from_rust(ret);";
@@ -0,0 +1,15 @@
+foreign_enum!(
+    enum Shape {
+        Circle = Shape::Circle(f64),
+        Rect = Shape::Rect { w: f64, h: f64 },
+        Named = Shape::Named { name: String, visible: bool },
+        Empty = Shape::Empty,
+    }
+);
+
+foreigner_class!(class Boo {
+    self_type Boo;
+    constructor Boo::default() -> Boo;
+    method Boo::area(&self, _: Shape) -> f64;
+    method Boo::shape(&self) -> Shape;
+});
@@ -0,0 +1,4 @@
+"Opposite of \\ref RED";
+"Created by \\ref FactoryWrapper::create, see also [`Unknown`]";
+"Same as \\ref ItemWrapper::name, but in \\ref Color";
+"Returns new \\ref Item, look at [docs](https://example.com)";
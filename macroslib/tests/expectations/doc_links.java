@@ -0,0 +1,4 @@
+"Opposite of {@link Color#RED}";
+"Created by {@link Factory#create}, see also [`Unknown`]";
+"Same as {@link Item#name}, but in {@link Color}";
+"Returns new {@link Item}, look at [docs](https://example.com)";
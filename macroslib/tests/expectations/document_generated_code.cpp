@@ -1,10 +1,27 @@
-r#"//This is class Foo
+r#"/**
+ * This is class Foo
+ */
 template<bool OWN_DATA>
 class FooWrapper {"#;
 
-r#"//Some documentation comment
+r#"/**
+     * Some documentation comment
+     * @param a_0
+     * @param a_1
+     */
     FooWrapper(int32_t a_0, const char * a_1) noexcept"#;
 
-r#"//1 Some documentation comment
-    //2 Some documentation comment
+r#"/**
+     * 1 Some documentation comment
+     * 2 Some documentation comment
+     * @param a_0
+     * @param a_1
+     * @return
+     */
     int32_t f(int32_t a_0, int32_t a_1) const  noexcept;"#;
+
+r#"/**
+     * Some static method
+     * @param a_0
+     */
+    static void g(int32_t a_0) noexcept;"#;
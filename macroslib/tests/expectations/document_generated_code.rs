@@ -7,4 +7,6 @@ class Foo {
     /// 1 Some documentation comment
     /// 2 Some documentation comment
     method Foo::f(&self, _: i32, _: i32) -> i32;
+    /// Some static method
+    static_method Foo::g(_: i32);
 });
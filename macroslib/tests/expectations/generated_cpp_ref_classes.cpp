@@ -1 +1,12 @@
 "bool eq(const Foo & a_0) const";
+r#"FooWrapper &operator=(FooWrapper &&o) noexcept
+    {
+        if (this != &o) {
+            free_mem(this->self_);
+            self_ = o.self_;
+            o.self_ = nullptr;
+        }
+        return *this;
+    }"#;
+r#"FooWrapper(const FooWrapper&) = delete;
+    FooWrapper &operator=(const FooWrapper&) = delete;"#;
@@ -0,0 +1,2 @@
+"public final java.util.concurrent.CompletableFuture<Foo> fetch(int a0)";
+"static native java.util.concurrent.CompletableFuture<Void> flush()";
@@ -0,0 +1 @@
+"public final String scale(float [] a0, int [] a1, String a2)";
@@ -0,0 +1 @@
+"public final int decode(java.nio.ByteBuffer a0, java.nio.ByteBuffer a1)";
@@ -0,0 +1,5 @@
+"public final class ParseException extends Exception {";
+"public com.example.ParseError getError() {";
+"public final int parse(String a0) throws com.example.ParseException {";
+"public final void check() throws java.lang.IllegalStateException {";
+"public final int plain() throws Exception {";
@@ -0,0 +1,4 @@
+"    @dalvik.annotation.optimization.FastNative\n    private static native long init(String a0) ;";
+"    @dalvik.annotation.optimization.CriticalNative\n    private static native int do_len(long me) ;";
+"    @dalvik.annotation.optimization.CriticalNative\n    public static native double add(int a0, double a1) ;";
+"    @dalvik.annotation.optimization.FastNative\n    public static native String name() ;";
@@ -0,0 +1,3 @@
+"public final java.util.Iterator<Foo> children()";
+"static native java.util.Iterator<String> names()";
+"private static native Object do_next(long me);";
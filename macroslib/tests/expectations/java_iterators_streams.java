@@ -0,0 +1,3 @@
+"public final java.util.stream.Stream<Foo> children()";
+"static native java.util.stream.Stream<String> names()";
+"private static native Object do_next(long me);";
@@ -0,0 +1,10 @@
+"import androidx.annotation.NonNull;";
+"import androidx.annotation.Nullable;";
+"public final class Node implements AutoCloseable {";
+"public Node(@NonNull String a0)";
+"@NonNull\n    public final String name()";
+"@NonNull\n    public final java.util.List<Node> children()";
+"@Nullable\n    public final Node parent()";
+"\n    public final int depth()";
+"@NonNull\n    public static native Node root()";
+"public void close() {";
@@ -0,0 +1,3 @@
+"public final java.util.List<Foo> children()";
+"static native java.util.List<java.lang.String> names()";
+"static native int [] nums()";
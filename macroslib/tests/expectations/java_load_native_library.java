@@ -0,0 +1,2 @@
+"    static {\n        com.example.util.NativeLoader.loadLibrary(\"foo\");\n    }\n";
+"public int javaFunc() { return 17; }";
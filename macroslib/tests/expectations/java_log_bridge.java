@@ -0,0 +1,4 @@
+"package com.example;";
+"public static void init(int maxLevel) {";
+"private static void log(int level, String target, String message) {";
+"private static native void do_init(int maxLevel);";
@@ -0,0 +1,3 @@
+"public final Node parent()";
+"public final java.util.Iterator<String> names()";
+"implements java.util.Iterator<T>, AutoCloseable {";
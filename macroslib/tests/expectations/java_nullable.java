@@ -0,0 +1,4 @@
+"public final Foo parent()";
+"static native String name()";
+"static native Integer count()";
+"static native Long size()";
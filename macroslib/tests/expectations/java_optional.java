@@ -0,0 +1,4 @@
+"java.util.Optional<Foo> parent()";
+"static native java.util.Optional<String> name()";
+"static native java.util.OptionalInt count()";
+"static native java.util.OptionalLong size()";
@@ -0,0 +1,3 @@
+"public record Point(int x, double y) {";
+"private static native long init(Point a0)";
+"private static native Point do_center(long me)";
@@ -0,0 +1,5 @@
+"public final class ParseException extends RuntimeException {";
+"    /**\n     * Parse text\n     * @throws com.example.ParseException if Rust function returns error\n     */\n";
+"public final int parse(String a0)  {";
+"@throws java.lang.RuntimeException if Rust function returns error";
+"public final int plain()  {";
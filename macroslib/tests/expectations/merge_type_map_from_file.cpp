@@ -0,0 +1,2 @@
+"MyIdWrapper id() const";
+r#"#include "my_id.hpp""#;
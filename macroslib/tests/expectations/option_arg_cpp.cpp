@@ -7,3 +7,5 @@ r#"inline void FooWrapper<OWN_DATA>::f6(std::optional<const char *> a_0) noexcep
     {
         Foo_f6(!!a_0 ? *a_0 : nullptr);
     }"#;
+"static void f7(std::optional<int8_t> a_0, std::optional<uint16_t> a_1) noexcept;";
+"static void f8(std::optional<const char *> a_0) noexcept;";
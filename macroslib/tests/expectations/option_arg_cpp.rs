@@ -21,4 +21,6 @@ foreigner_class!(class Foo {
    method Foo::f4(&self, x: Option<usize>);
    static_method Foo::f5(x: Option<f64>, y: Option<usize>);
    static_method Foo::f6(x: Option<&str>);
+   static_method Foo::f7(x: Option<i8>, y: Option<u16>);
+   static_method Foo::f8(x: Option<String>);
 });
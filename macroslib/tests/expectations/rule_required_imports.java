@@ -0,0 +1,2 @@
+"import java.time.Instant;";
+"public final Instant created()";
@@ -0,0 +1,2 @@
+"Source: source_location_comments: $OUT_DIR/src.rs:10";
+"Source: source_location_comments: $OUT_DIR/src.rs:11";
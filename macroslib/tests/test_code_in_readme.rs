@@ -25,7 +25,7 @@ fn test_code_in_readme() {
             src.write_all(test.text.as_bytes()).unwrap();
             let rust_path_dst = tmp_dir.path().join(&test.name).join("test.rs");
 
-            {
+            if test.lang != Some(Lang::Cpp) {
                 let java_path = tmp_dir.path().join(&test.name).join("java");
 
                 fs::create_dir_all(&java_path).unwrap();
@@ -38,7 +38,7 @@ fn test_code_in_readme() {
                 swig_gen.expand("rust_swig_test_jni", &rust_path_src, &rust_path_dst);
             }
 
-            {
+            if test.lang != Some(Lang::Java) {
                 let cpp_path = tmp_dir.path().join(&test.name).join("c++");
 
                 fs::create_dir_all(&cpp_path).unwrap();
@@ -53,8 +53,17 @@ fn test_code_in_readme() {
     }
 }
 
+/// Foreign language of snippet with language specific attributes,
+/// marked as `java-only` or `cpp-only` in code block info
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Lang {
+    Java,
+    Cpp,
+}
+
 struct CodeBlockInfo {
     is_rust: bool,
+    lang: Option<Lang>,
     should_panic: bool,
     ignore: bool,
     no_run: bool,
@@ -70,6 +79,7 @@ struct Test {
     no_run: bool,
     should_panic: bool,
     template: Option<String>,
+    lang: Option<Lang>,
 }
 
 fn parse_readme() -> Vec<Test> {
@@ -104,6 +114,7 @@ fn parse_readme() -> Vec<Test> {
                         no_run: code_block_info.no_run,
                         should_panic: code_block_info.should_panic,
                         template: code_block_info.template,
+                        lang: code_block_info.lang,
                     });
                     test_number += 1;
                 }
@@ -123,6 +134,7 @@ fn parse_code_block_info(info: &str) -> CodeBlockInfo {
     let mut seen_other_tags = false;
     let mut info = CodeBlockInfo {
         is_rust: false,
+        lang: None,
         should_panic: false,
         ignore: false,
         no_run: false,
@@ -145,6 +157,14 @@ fn parse_code_block_info(info: &str) -> CodeBlockInfo {
                 info.ignore = true;
                 seen_rust_tags = true
             }
+            "java-only" => {
+                info.lang = Some(Lang::Java);
+                seen_rust_tags = true;
+            }
+            "cpp-only" => {
+                info.lang = Some(Lang::Cpp);
+                seen_rust_tags = true;
+            }
             "no_run" => {
                 info.no_run = true;
                 seen_rust_tags = true;
@@ -1,11 +1,17 @@
 use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
     env,
     ffi::OsString,
     fs, panic,
     path::{Path, PathBuf},
+    rc::Rc,
 };
 
-use rust_swig::{CppConfig, Generator, JavaConfig, LanguageConfig};
+use rust_swig::{
+    CppConfig, CppOptional, CppResult, CppStandard, CppString, Generator, JavaConfig,
+    JavaLogBackend, LanguageConfig, PanicAction, TypeMapTester,
+};
 use syn::Token;
 use tempfile::tempdir;
 
@@ -33,36 +39,11 @@ fn test_expectations_main() {
     let mut ntests = 0_usize;
 
     fn check_expectation(test_name: &str, test_case: &Path, lang: ForeignLang) -> bool {
-        let (main_ext, rust_ext) = match lang {
-            ForeignLang::Cpp => (".cpp", ".cpp_rs"),
-            ForeignLang::Java => (".java", ".java_rs"),
-        };
-        let main_expectation = new_path(test_case, main_ext);
-        if main_expectation.exists() {
+        let (main_ext, _) = expectation_exts(lang);
+        if new_path(test_case, main_ext).exists() {
             let code_pair =
                 parse_code(&test_name, Source::Path(&test_case), lang).expect("parse_code failed");
-            let pats =
-                parse_code_expectation(&main_expectation).expect("parsing of patterns failed");
-
-            let mut print_test_info = PrintTestInfo::new(code_pair.clone(), test_name.into(), lang);
-            for pat in pats {
-                print_test_info.foreign_code_search_pattern = pat.clone();
-                assert!(code_pair.foreign_code.contains(&pat));
-            }
-            print_test_info.foreign_code_search_pattern.clear();
-
-            let rust_cpp_expectation = new_path(&test_case, rust_ext);
-            if rust_cpp_expectation.exists() {
-                let pats = parse_code_expectation(&rust_cpp_expectation)
-                    .expect("parsing of patterns failed");
-                let pats: Vec<String> = pats.into_iter().map(|v| v.replace("\n", "")).collect();
-                for pat in pats {
-                    print_test_info.rust_pat = pat.clone();
-                    assert!(code_pair.rust_code.contains(&pat));
-                }
-                print_test_info.rust_pat.clear();
-            }
-            print_test_info.success();
+            check_code_pair(test_name, test_case, &code_pair, lang);
             true
         } else {
             false
@@ -89,7 +70,7 @@ fn test_expectations_main() {
         }
     }
 
-    assert_eq!(44, ntests);
+    assert_eq!(47, ntests);
 }
 
 #[test]
@@ -200,7 +181,6 @@ foreigner_class!(class FooImpl {
     alias setAlternateBoarding;
 });
 "#;
-    ;
     for _ in 0..100 {
         let cpp_code = parse_code(name, Source::Str(src), ForeignLang::Cpp).expect("parse failed");
         println!("c/c++: {}", cpp_code.foreign_code);
@@ -360,176 +340,2633 @@ foreign_interface!(interface RepoChangedCallback {
     }
 }
 
-#[derive(PartialEq, Debug, Clone, Copy)]
-enum ForeignLang {
-    Java,
-    Cpp,
-}
+#[test]
+fn test_merge_type_map_from_file() {
+    let _ = env_logger::try_init();
 
-#[derive(Clone)]
-struct CodePair {
-    rust_code: String,
-    foreign_code: String,
+    parse_and_check(
+        "merge_type_map_from_file",
+        r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    method Foo::id(&self) -> MyId;
+});
+"#,
+        TestConfig::from(ForeignLang::Cpp).generator(|gen, dir| {
+            let typemap_path = dir.join("my_typemap.rs");
+            fs::write(
+                &typemap_path,
+                r#"
+foreign_typemap!(
+    ($p:r_type) MyId => u32 {
+        $out = $p.0
+    };
+    ($p:f_type, req_modules = ["\"my_id.hpp\""]) => "MyIdWrapper" "MyIdWrapper{$p}";
+);
+"#,
+            )
+            .unwrap();
+            gen.merge_type_map_from_file(&typemap_path)
+        }),
+    );
 }
 
-struct PrintTestInfo {
-    code_pair: CodePair,
-    test_name: String,
-    lang: ForeignLang,
-    print_on_drop: bool,
-    foreign_code_search_pattern: String,
-    rust_pat: String,
+#[test]
+fn test_fallible_conversation_rule() {
+    let _ = env_logger::try_init();
+
+    parse_and_check(
+        "fallible_conversation_rule",
+        r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::new(_: Utf16Name) -> Foo;
+});
+"#,
+        TestConfig::java(|dir| JavaConfig::new(dir, "org.example".into())).generator(|gen, _| {
+            gen.merge_type_map(
+                "fallible_typemap",
+                r#"
+foreign_typemap!(
+    ($p:r_type, fallible) Utf16Name <= jlong {
+        $out = Utf16Name::from_jlong($p)
+    };
+);
+"#,
+            )
+        }),
+    );
 }
 
-impl PrintTestInfo {
-    fn new(code_pair: CodePair, test_name: String, lang: ForeignLang) -> Self {
-        PrintTestInfo {
-            code_pair,
-            test_name,
-            lang,
-            print_on_drop: true,
-            foreign_code_search_pattern: String::new(),
-            rust_pat: String::new(),
-        }
-    }
-    fn success(&mut self) {
-        self.print_on_drop = false;
-    }
+#[test]
+fn test_cpp_fallible_conversation_rule() {
+    let _ = env_logger::try_init();
+
+    let code = parse_and_check(
+        "cpp_fallible_conversation_rule",
+        r#"
+foreigner_class!(class Foo {
+    foreign_typemap!(
+        ($p:r_type, fallible) Utf16Name <= i64 {
+            $out = Utf16Name::from_i64($p)
+        };
+    );
+    self_type Foo;
+    constructor Foo::new(_: Utf16Name) -> Foo;
+    static_method Foo::check(_: Utf16Name) -> i32;
+    method Foo::f(&self, _: i64) -> i32;
+});
+foreigner_class!(class Boo {
+    self_type Boo;
+    constructor Boo::default() -> Boo;
+    method Boo::f(&self) -> i32;
+});
+"#,
+        ForeignLang::Cpp,
+    );
+    assert!(!code.rust_code.contains("swig_catch_panic"));
+    assert!(code.file("Foo.hpp").contains("#include \"rust_panic.h\""));
+    assert!(!code.file("Boo.hpp").contains("rust_check_panic"));
 }
 
-impl Drop for PrintTestInfo {
-    fn drop(&mut self) {
-        if self.print_on_drop {
-            if !self.foreign_code_search_pattern.is_empty() {
-                println!(
-                    "{} / {:?}: search foreign pat '{}'",
-                    self.test_name, self.lang, self.foreign_code_search_pattern
-                );
-            }
+#[test]
+fn test_rule_required_imports() {
+    let _ = env_logger::try_init();
 
-            if !self.rust_pat.is_empty() {
-                println!(
-                    "{} / {:?}: search rust pat '{}'",
-                    self.test_name, self.lang, self.rust_pat,
-                );
-            }
+    let code = parse_and_check(
+        "rule_required_imports",
+        r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    method Foo::created(&self) -> Timestamp;
+});
+foreigner_class!(class Boo {
+    self_type Boo;
+    constructor Boo::default() -> Boo;
+    method Boo::name(&self) -> String;
+});
+"#,
+        TestConfig::java(|dir| JavaConfig::new(dir, "org.example".into())).generator(|gen, _| {
+            gen.merge_type_map(
+                "instant_typemap",
+                r#"
+foreign_typemap!(
+    ($p:r_type) JInstant;
+    ($p:f_type, req_modules = ["java.time.Instant"]) "Instant";
+);
+foreign_typemap!(
+    ($p:r_type) Timestamp => JInstant {
+        $out = timestamp_to_instant($p, env);
+    };
+);
+"#,
+            )
+        }),
+    );
+    assert!(code.file("Foo.java").contains("import java.time.Instant;"));
+    assert!(!code.file("Boo.java").contains("import java.time.Instant;"));
+}
 
-            println!(
-                "{} / {:?}: rust_swig generated such foreign_code: {}",
-                self.test_name, self.lang, self.code_pair.foreign_code
-            );
-            println!(
-                "{} / {:?}: rust_swig generated such rust_code: {}",
-                self.test_name, self.lang, self.code_pair.rust_code
-            );
-        }
-    }
+#[test]
+fn test_class_scoped_typemap() {
+    let _ = env_logger::try_init();
+
+    let code = parse_and_check(
+        "class_scoped_typemap",
+        r#"
+foreigner_class!(class Foo {
+    foreign_typemap!(
+        ($p:r_type) JByteBuffer;
+        ($p:f_type, req_modules = ["java.nio.ByteBuffer"]) "ByteBuffer";
+    );
+    foreign_typemap!(
+        ($p:r_type) Vec<i8> => JByteBuffer {
+            $out = vec_to_byte_buffer($p, env);
+        };
+    );
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    method Foo::data(&self) -> Vec<i8>;
+});
+foreigner_class!(class Boo {
+    self_type Boo;
+    constructor Boo::default() -> Boo;
+    method Boo::data(&self) -> Vec<i8>;
+});
+"#,
+        TestConfig::java(|dir| JavaConfig::new(dir, "org.example".into())),
+    );
+    assert!(code
+        .file("Foo.java")
+        .contains("import java.nio.ByteBuffer;"));
+    assert!(code
+        .file("Foo.java")
+        .contains("public final ByteBuffer data()"));
+    assert!(code
+        .file("Boo.java")
+        .contains("public final byte [] data()"));
 }
 
-#[derive(Debug)]
-struct Error {
-    msg: String,
+#[test]
+fn test_type_map_tester() {
+    let _ = env_logger::try_init();
+
+    let tmp_dir = tempdir().expect("Can not create tmp directory");
+    let mut tester = TypeMapTester::new(
+        Generator::new(LanguageConfig::JavaConfig(JavaConfig::new(
+            tmp_dir.path().into(),
+            "org.example".into(),
+        )))
+        .with_pointer_target_width(64)
+        .merge_type_map(
+            "my_typemap",
+            r#"
+foreign_typemap!(
+    ($p:r_type) MyId => jlong {
+        $out = $p.0 as jlong
+    };
+);
+"#,
+        ),
+    );
+    assert_eq!(
+        "    let mut a0: bool = a0.swig_into(env);\n",
+        tester
+            .convert_rust_types("jboolean", "bool", "a0", "jlong")
+            .unwrap()
+    );
+    assert_eq!(
+        "    let a0: jlong = a0 . 0 as jlong;\n",
+        tester
+            .convert_rust_types("MyId", "jlong", "a0", "jlong")
+            .unwrap()
+    );
+    assert!(tester
+        .convert_rust_types("jlong", "MyId", "a0", "jlong")
+        .is_err());
 }
 
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}", self.msg)
+#[test]
+fn test_cache_builtin_type_maps() {
+    let _ = env_logger::try_init();
+
+    let cache_dir = tempdir().expect("Can not create tmp directory");
+    for lang in &[ForeignLang::Java, ForeignLang::Cpp] {
+        let generate = |cache_dir: Option<PathBuf>| -> (String, String) {
+            let code = parse_code(
+                "cache_builtin_type_maps",
+                Source::Str(
+                    r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    method Foo::name(&self) -> &str;
+    method Foo::set_name(&mut self, _: String);
+    method Foo::find(&self, _: i32) -> Option<f64>;
+});
+"#,
+                ),
+                TestConfig::from(*lang).generator(move |gen, _| match cache_dir {
+                    Some(cache_dir) => gen.cache_builtin_type_maps(cache_dir),
+                    None => gen,
+                }),
+            )
+            .unwrap();
+            (code.rust_code, code.foreign_code)
+        };
+        let without_cache = generate(None);
+        let cold_cache = generate(Some(cache_dir.path().into()));
+        assert!(fs::read_dir(cache_dir.path()).unwrap().next().is_some());
+        let warm_cache = generate(Some(cache_dir.path().into()));
+        assert_eq!(without_cache, cold_cache);
+        assert_eq!(without_cache, warm_cache);
     }
 }
 
-impl From<std::io::Error> for Error {
-    fn from(x: std::io::Error) -> Self {
-        Error {
-            msg: format!("io: {}", x),
-        }
-    }
+#[test]
+fn test_cache_expansion() {
+    let _ = env_logger::try_init();
+
+    let tmp_dir = tempdir().expect("Can not create tmp directory");
+    let cache_dir = tmp_dir.path().join("cache");
+    let src_path = tmp_dir.path().join("src.rs");
+    let rust_path = tmp_dir.path().join("test.rs");
+    let java_path = tmp_dir.path().join("Foo.java");
+    let src = r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    method Foo::f(&self, _: i32) -> i32;
+});
+"#;
+    fs::write(&src_path, src).unwrap();
+    // returns number of files passed to hook, zero if expansion was skipped
+    let expand = || -> usize {
+        let generated = Rc::new(Cell::new(0));
+        let generated2 = generated.clone();
+        Generator::new(LanguageConfig::JavaConfig(JavaConfig::new(
+            tmp_dir.path().into(),
+            "org.example".into(),
+        )))
+        .with_pointer_target_width(64)
+        .cache_expansion(&cache_dir)
+        .with_generated_file_hook(move |_, _| generated2.set(generated2.get() + 1))
+        .expand("cache_expansion", &src_path, &rust_path);
+        generated.get()
+    };
+
+    assert!(expand() > 0);
+    let rust_code = fs::read_to_string(&rust_path).unwrap();
+    let java_code = fs::read_to_string(&java_path).unwrap();
+    assert_eq!(0, expand());
+
+    fs::write(&java_path, "modified").unwrap();
+    assert!(expand() > 0);
+    assert_eq!(java_code, fs::read_to_string(&java_path).unwrap());
+    assert_eq!(0, expand());
+
+    fs::remove_file(&rust_path).unwrap();
+    assert!(expand() > 0);
+    assert_eq!(rust_code, fs::read_to_string(&rust_path).unwrap());
+
+    fs::write(&src_path, src.replace("i32", "i64")).unwrap();
+    assert!(expand() > 0);
+    assert_ne!(java_code, fs::read_to_string(&java_path).unwrap());
+    assert_eq!(0, expand());
 }
 
-impl From<syn::Error> for Error {
-    fn from(x: syn::Error) -> Self {
-        Error {
-            msg: format!("syn: {}", x),
+#[test]
+fn test_api_manifest() {
+    let _ = env_logger::try_init();
+
+    for lang in &[ForeignLang::Java, ForeignLang::Cpp] {
+        let code = parse_code(
+            "api_manifest",
+            Source::Str(
+                r#"
+foreigner_class!(
+/// Class comment
+class Foo {
+    self_type Foo;
+    constructor Foo::new(_: i32) -> Foo;
+    /// Method comment
+    method Foo::name(&self) -> &str;
+    static_method Foo::sum(_: i32, _: i32) -> i32;
+});
+foreign_enum!(enum MyEnum {
+    ITEM1 = MyEnum::Item1,
+    ITEM2 = MyEnum::Item2,
+});
+"#,
+            ),
+            TestConfig::from(*lang)
+                .generator(|gen, dir| gen.write_api_manifest(dir.join("api.json"))),
+        )
+        .unwrap();
+        let manifest = code.file("api.json");
+        println!("manifest: {}", manifest);
+        let str_type = match lang {
+            ForeignLang::Java => "String",
+            ForeignLang::Cpp => "std::string_view",
+        };
+        for pat in &[
+            "\"name\": \"Foo\"",
+            "\"self_type\": \"Foo\"",
+            "\"doc\": \"Class comment\"",
+            "\"doc\": \"Method comment\"",
+            "\"kind\": \"static_method\"",
+            "\"ret\": { \"rust\": \"Foo\", \"foreign\": \"Foo\" }",
+            "\"rust_path\": \"Foo :: sum\"",
+            "\"params\": [{ \"rust\": \"i32\", \"foreign\": \"int",
+            &format!(
+                "\"ret\": {{ \"rust\": \"& str\", \"foreign\": \"{}\" }}",
+                str_type
+            ),
+            "{ \"name\": \"ITEM2\", \"doc\": \"\" }",
+            "\"interfaces\": []",
+        ] {
+            assert!(manifest.contains(pat), "no {} in manifest", pat);
         }
     }
 }
 
-impl std::error::Error for Error {}
+#[test]
+fn test_cpp_umbrella_header() {
+    let _ = env_logger::try_init();
 
-fn collect_code_in_dir(dir_with_code: &Path, exts: &[&str]) -> Result<String, Error> {
-    let mut code = String::new();
-    for path in fs::read_dir(dir_with_code)? {
-        let path = path?;
-        if path.file_type()?.is_file()
-            && exts
-                .iter()
-                .any(|ext| path.path().to_str().map_or(false, |x| x.ends_with(ext)))
-        {
-            code.push_str(&fs::read_to_string(path.path())?);
-            code.push('\n');
-        }
+    let code = parse_code(
+        "umbrella_header",
+        Source::Str(
+            r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+});
+foreign_enum!(enum MyEnum {
+    ITEM1 = MyEnum::Item1,
+});
+foreign_interface!(interface Observer {
+    self_type Observer;
+    on_event = Observer::on_event(&self, _: i32);
+});
+"#,
+        ),
+        TestConfig::cpp(|dir| {
+            CppConfig::new(dir, "org_examples".into())
+                .separate_impl_headers(true)
+                .umbrella_header("all.hpp".into())
+        }),
+    )
+    .unwrap();
+    assert_eq!(
+        r#"// Automaticaly generated by rust_swig
+#pragma once
+
+#include "Foo.hpp"
+#include "Foo_impl.hpp"
+#include "c_MyEnum.h"
+#include "Observer.hpp"
+"#,
+        code.file("all.hpp")
+    );
+    for header in &["Foo.hpp", "Foo_impl.hpp", "c_MyEnum.h", "Observer.hpp"] {
+        assert!(code.files.contains_key(*header));
     }
-    Ok(code)
 }
 
-enum Source<'a> {
-    Str(&'a str),
-    Path(&'a Path),
+#[test]
+fn test_cpp_cmake_package() {
+    let _ = env_logger::try_init();
+
+    let code = parse_code(
+        "cmake_package",
+        Source::Str(
+            r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+});
+"#,
+        ),
+        TestConfig::cpp(|dir| {
+            let rust_library = dir.join("target").join("debug").join("libfoo.a");
+            CppConfig::new(dir, "org_examples".into()).cmake_package("foo_api".into(), rust_library)
+        }),
+    )
+    .unwrap();
+    let cmake_config = code.file("foo_apiConfig.cmake");
+    println!("cmake_config: {}", cmake_config);
+    assert!(cmake_config.contains("add_library(foo_api::foo_api UNKNOWN IMPORTED)"));
+    assert!(cmake_config.contains("IMPORTED_LOCATION \"$OUT_DIR/target/debug/libfoo.a\""));
+    assert!(cmake_config.contains("INTERFACE_INCLUDE_DIRECTORIES \"$OUT_DIR\""));
+    let pkg_config = code.file("foo_api.pc");
+    println!("pkg_config: {}", pkg_config);
+    assert!(pkg_config.contains("Cflags: -I$OUT_DIR\n"));
+    assert!(pkg_config.contains("Libs: -L$OUT_DIR/target/debug -lfoo\n"));
 }
 
-fn parse_code(test_name: &str, rust_src: Source, lang: ForeignLang) -> Result<CodePair, Error> {
-    let tmp_dir = tempdir().expect("Can not create tmp directory");
-    let (swig_gen, ext_list): (Generator, &[&'static str]) = match lang {
-        ForeignLang::Java => {
-            let swig_gen = Generator::new(LanguageConfig::JavaConfig(
-                JavaConfig::new(tmp_dir.path().into(), "org.example".into())
-                    .use_null_annotation_from_package("android.support.annotation".into()),
-            ))
-            .with_pointer_target_width(64);
-
-            (swig_gen, &[".java"])
-        }
-        ForeignLang::Cpp => {
-            let swig_gen = Generator::new(LanguageConfig::CppConfig(CppConfig::new(
-                tmp_dir.path().into(),
-                "org_examples".into(),
-            )))
-            .with_pointer_target_width(64);
-            (swig_gen, &[".h", ".hpp"])
-        }
-    };
+#[test]
+fn test_cpp_std_span() {
+    let _ = env_logger::try_init();
 
-    let rust_code_path = tmp_dir.path().join("test.rs");
-    match rust_src {
-        Source::Path(rust_src_path) => swig_gen.expand(test_name, rust_src_path, &rust_code_path),
-        Source::Str(rust_src) => {
-            let rust_src_path = tmp_dir.path().join("src.rs");
-            fs::write(&rust_src_path, rust_src)?;
-            swig_gen.expand(test_name, rust_src_path, &rust_code_path);
-        }
+    for (std_span, exp_name) in &[(false, "cpp_std_span_disabled"), (true, "cpp_std_span")] {
+        let std_span = *std_span;
+        parse_and_check(
+            exp_name,
+            r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    method Foo::sum(&self, _: &[i32]) -> i32;
+    method Foo::fill(&self, _: &mut [u8]);
+    method Foo::ids(&self) -> &[u32];
+});
+"#,
+            TestConfig::cpp(move |dir| {
+                CppConfig::new(dir, "org_examples".into()).std_span(std_span)
+            }),
+        );
     }
+}
 
-    let rust_code = fs::read_to_string(rust_code_path)?;
-    let foreign_code = collect_code_in_dir(tmp_dir.path(), ext_list)?;
-    tmp_dir.close()?;
+#[test]
+fn test_cpp_str_arg() {
+    let _ = env_logger::try_init();
 
-    Ok(CodePair {
-        rust_code,
-        foreign_code,
-    })
+    for (cpp17, exp_name) in &[(false, "cpp_str_arg"), (true, "cpp_str_arg_cpp17")] {
+        let cpp17 = *cpp17;
+        parse_and_check(
+            exp_name,
+            r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    method Foo::set_name(&mut self, _: &str);
+    method Foo::set_nick(&mut self, _: Option<String>);
+});
+"#,
+            TestConfig::cpp(move |dir| {
+                let cfg = CppConfig::new(dir, "org_examples".into());
+                if cpp17 {
+                    cfg.cpp_standard(CppStandard::Cpp17)
+                } else {
+                    cfg
+                }
+            }),
+        );
+    }
 }
 
-struct ExpectationPatterns(Vec<String>);
+#[test]
+fn test_cpp_data_enum_boost() {
+    let _ = env_logger::try_init();
 
-impl syn::parse::Parse for ExpectationPatterns {
-    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        let lit_vec: syn::punctuated::Punctuated<syn::LitStr, Token![;]> =
-            syn::punctuated::Punctuated::parse_terminated(input)?;
-        Ok(ExpectationPatterns(
-            lit_vec.into_iter().map(|v| v.value()).collect(),
-        ))
+    let code = parse_and_check(
+        "cpp_data_enum_boost",
+        r#"
+foreign_enum!(enum Shape {
+    Circle = Shape::Circle(f64),
+    Empty = Shape::Empty,
+});
+"#,
+        TestConfig::cpp(|dir| CppConfig::new(dir, "org_examples".into()).use_boost()),
+    );
+    assert!(!code.file("Shape.hpp").contains("ShapeVisitor"));
+}
+
+#[test]
+fn test_cpp_result_expected() {
+    let _ = env_logger::try_init();
+
+    for (mode, exp_name) in &[
+        (CppResult::Std23Expected, "cpp_result_std_expected"),
+        (CppResult::RustExpected, "cpp_result_rust_expected"),
+    ] {
+        let mode = *mode;
+        parse_and_check(
+            exp_name,
+            r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    method Foo::f(&self) -> Result<(), String>;
+    static_method Foo::create() -> Result<Foo, String>;
+});
+"#,
+            TestConfig::cpp(move |dir| CppConfig::new(dir, "org_examples".into()).cpp_result(mode)),
+        );
     }
 }
 
+#[test]
+fn test_cpp_shared_ptr() {
+    let _ = env_logger::try_init();
+
+    parse_and_check(
+        "cpp_shared_ptr",
+        r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    method Foo::f(&self) -> Foo;
+    static_method Foo::consume(_: Foo);
+});
+foreign_interface!(interface Observer {
+    self_type Observer;
+    on_foo = Observer::on_foo(&self, _: Foo);
+});
+"#,
+        TestConfig::cpp(|dir| CppConfig::new(dir, "org_examples".into()).shared_ptr(true)),
+    );
+}
+
+#[test]
+fn test_cpp_unique_ptr_factories() {
+    let _ = env_logger::try_init();
+
+    let code = parse_and_check(
+        "cpp_unique_ptr_factories",
+        r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::new(_: i32, _: String) -> Foo;
+    method Foo::f(&self) -> i32;
+});
+foreigner_class!(class Boo {
+    self_type Boo;
+    private constructor = empty;
+    method Boo::f(&self) -> i32;
+});
+"#,
+        TestConfig::cpp(|dir| {
+            CppConfig::new(dir, "org_examples".into()).unique_ptr_factories(true)
+        }),
+    );
+    assert!(!code.file("Boo.hpp").contains("create("));
+
+    let ret = panic::catch_unwind(|| {
+        parse_code(
+            "cpp_unique_ptr_factories_conflict",
+            Source::Str(
+                r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    static_method Foo::create() -> Foo;
+});
+"#,
+            ),
+            TestConfig::cpp(|dir| {
+                CppConfig::new(dir, "org_examples".into()).unique_ptr_factories(true)
+            }),
+        )
+    });
+    assert!(ret.is_err());
+}
+
+#[test]
+fn test_cpp_pimpl() {
+    let _ = env_logger::try_init();
+
+    let code = parse_and_check(
+        "cpp_pimpl",
+        r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::new(_: i32) -> Foo;
+    method Foo::f(&self) -> i32;
+});
+foreign_enum!(enum Shape {
+    Circle = Shape::Circle(f64),
+    Empty = Shape::Empty,
+});
+foreigner_class!(class Boo {
+    self_type Boo;
+    constructor Boo::default() -> Boo;
+    method Boo::set(&mut self, _: i32);
+    method Boo::foos(&self) -> Vec<Foo>;
+    method Boo::foo(&self) -> Foo;
+    method Boo::shape(&self) -> Shape;
+});
+"#,
+        TestConfig::cpp(|dir| {
+            let rust_library = dir.join("libfoo.a");
+            CppConfig::new(dir, "org_examples".into())
+                .pimpl(true)
+                .cmake_package("foo_api".into(), rust_library)
+        }),
+    );
+    let boo_hpp = code.file("Boo.hpp");
+    assert!(boo_hpp.contains("#include \"Foo_fwd.hpp\""));
+    assert!(!boo_hpp.contains("#include \"Foo.hpp\""));
+    assert!(!boo_hpp.contains("#include \"c_Boo.h\""));
+    assert!(!boo_hpp.contains("Boo_foo("));
+    assert!(!boo_hpp.contains("Boo_delete("));
+    assert!(code.file("Boo.cpp").contains("#include \"c_Boo.h\""));
+    assert!(!code.file("Boo.cpp").contains("BooWrapper<false>::set("));
+    assert!(!code.files.contains_key("Boo_impl.hpp"));
+    assert!(!code.file("Shape.hpp").contains("#include \"c_Shape.h\""));
+    assert!(!code.file("Shape.hpp").contains("Shape_tag("));
+    assert!(!code.file("Shape.cpp").contains("inline "));
+    let cmake_config = code.file("foo_apiConfig.cmake");
+    println!("cmake_config: {}", cmake_config);
+    assert!(cmake_config
+        .contains("INTERFACE_SOURCES \"$OUT_DIR/Foo.cpp;$OUT_DIR/Shape.cpp;$OUT_DIR/Boo.cpp\")"));
+}
+
+#[test]
+fn test_cpp_utf16_strings() {
+    let _ = env_logger::try_init();
+
+    for (cpp_string, exp_name) in &[
+        (CppString::U16String, "cpp_utf16_u16string"),
+        (CppString::WString, "cpp_utf16_wstring"),
+    ] {
+        let cpp_string = *cpp_string;
+        parse_and_check(
+            exp_name,
+            r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::new(_: &str) -> Foo;
+    method Foo::name(&self) -> &str;
+    method Foo::set_name(&mut self, _: String);
+    method Foo::greet(&self, _: &str) -> String;
+    method Foo::nick(&self) -> Option<String>;
+    method Foo::set_nick(&mut self, _: Option<String>);
+});
+"#,
+            TestConfig::cpp(move |dir| {
+                CppConfig::new(dir, "org_examples".into()).cpp_string(cpp_string)
+            }),
+        );
+    }
+}
+
+#[test]
+fn test_cpp_symbol_prefix() {
+    let _ = env_logger::try_init();
+
+    let code = parse_and_check(
+        "cpp_symbol_prefix",
+        r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::new() -> Foo;
+    method Foo::name(&self) -> String;
+    static_method Foo::all() -> Vec<Foo>;
+});
+"#,
+        TestConfig::cpp(|dir| {
+            CppConfig::new(dir, "org_examples".into())
+                .symbol_prefix("example_".into())
+                .linker_exports("example".into())
+        }),
+    );
+    assert!(!code.rust_code.contains("fn Foo_new"));
+    let def = code.file("example.def");
+    println!("example.def: {}", def);
+    assert!(def.contains("EXPORTS\n"));
+    assert!(def.contains("    example_Foo_new\n"));
+    assert!(def.contains("    example_CRustVecU8_free\n"));
+    let map = code.file("example.map");
+    assert!(map.contains("    example_Foo_delete;\n"));
+    assert!(map.contains("  local: *;\n};\n"));
+}
+
+#[test]
+fn test_cpp_dll_api() {
+    let _ = env_logger::try_init();
+
+    let code = parse_and_check(
+        "cpp_dll_api",
+        r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::new() -> Foo;
+    method Foo::name(&self) -> String;
+    static_method Foo::all() -> Vec<Foo>;
+});
+foreign_enum!(enum Shape {
+    Circle = Shape::Circle(f64),
+    Empty = Shape::Empty,
+});
+"#,
+        TestConfig::cpp(|dir| {
+            CppConfig::new(dir, "org_examples".into())
+                .symbol_prefix("example_".into())
+                .dll_api("EXAMPLE".into())
+        }),
+    );
+    assert!(code.file("c_Foo.h").contains("#include \"example_api.h\""));
+    assert!(code
+        .file("rust_str.h")
+        .contains("#include \"example_api.h\""));
+    assert!(code
+        .file("rust_vec.h")
+        .starts_with("#pragma once\n\n#include \"example_api.h\"\n"));
+}
+
+#[test]
+fn test_cpp_log_bridge() {
+    let _ = env_logger::try_init();
+
+    for &c_abi_only in &[false, true] {
+        let code = parse_and_check(
+            "cpp_log_bridge",
+            r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::new() -> Foo;
+});
+"#,
+            TestConfig::cpp(move |dir| {
+                CppConfig::new(dir, "org_examples".into())
+                    .symbol_prefix("example_".into())
+                    .c_abi_only(c_abi_only)
+                    .umbrella_header("example.hpp".into())
+                    .log_bridge(true)
+            }),
+        );
+        assert_eq!(
+            !c_abi_only,
+            code.file("rust_log.h").contains("inline void rust_log_init(std::function<void(RustLogLevel, const char *, const char *)> callback,")
+        );
+        assert!(code.file("example.hpp").contains("#include \"rust_log.h\""));
+    }
+}
+
+#[test]
+fn test_cpp_catch_panics() {
+    let _ = env_logger::try_init();
+
+    for &(c_abi_only, ref action, exp_name) in &[
+        (
+            false,
+            PanicAction::Throw("std::logic_error".into()),
+            "cpp_catch_panics_throw",
+        ),
+        (true, PanicAction::Abort, "cpp_catch_panics_abort"),
+    ] {
+        let action = action.clone();
+        let code = parse_and_check(
+            exp_name,
+            r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::new() -> Foo;
+    method Foo::f(&self, _: i32) -> i32;
+    static_method Foo::g();
+});
+foreigner_class!(#[catch_panics = false] class Boo {
+    static_method Boo::h() -> i32;
+});
+"#,
+            TestConfig::cpp(move |dir| {
+                CppConfig::new(dir, "org_examples".into())
+                    .symbol_prefix("example_".into())
+                    .c_abi_only(c_abi_only)
+                    .umbrella_header("example.hpp".into())
+                    .catch_panics(action)
+            }),
+        );
+        if c_abi_only {
+            assert!(!code.files.contains_key("rust_panic.h"));
+            continue;
+        }
+        assert!(code
+            .file("example.hpp")
+            .contains("#include \"rust_panic.h\""));
+        assert!(code.file("Foo.hpp").contains("#include \"rust_panic.h\""));
+        assert!(!code.file("Boo.hpp").contains("rust_check_panic"));
+    }
+}
+
+#[test]
+fn test_cpp_tracing_spans() {
+    let _ = env_logger::try_init();
+
+    let src = r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::new(_: &str) -> Foo;
+    method Foo::f(&self, _: i32) -> i32;
+});
+"#;
+    parse_and_check(
+        "cpp_tracing_spans",
+        src,
+        TestConfig::cpp(|dir| CppConfig::new(dir, "org_examples".into()).tracing_spans(true)),
+    );
+    let code = parse_code(
+        "cpp_tracing_spans",
+        Source::Str(src),
+        TestConfig::cpp(|dir| CppConfig::new(dir, "org_examples".into()).tracing_spans(false)),
+    )
+    .unwrap();
+    assert!(!code.rust_code.contains("tracing"));
+}
+
+#[test]
+fn test_cpp_c_abi_only() {
+    let _ = env_logger::try_init();
+
+    let code = parse_and_check(
+        "cpp_c_abi_only",
+        r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    method Foo::name(&self) -> String;
+});
+foreign_interface!(interface Observer {
+    self_type Observer;
+    on_foo = Observer::on_foo(&self, _: &Foo);
+});
+foreigner_class!(class Boo {
+    static_method Boo::make() -> Result<Foo, String>;
+    static_method Boo::subscribe(_: Box<Observer>);
+    static_method Boo::is_named(_: &Foo) -> bool;
+});
+"#,
+        TestConfig::cpp(|dir| {
+            CppConfig::new(dir, "org_examples".into())
+                .c_abi_only(true)
+                .umbrella_header("api.h".into())
+        }),
+    );
+    for header in &["Foo.hpp", "Foo_fwd.hpp", "Boo.hpp", "Observer.hpp"] {
+        assert!(!code.files.contains_key(*header));
+    }
+    assert!(code.file("c_Foo.h").contains("#include \"rust_str.h\""));
+    assert!(code.file("c_Observer.h").contains("#include <stdint.h>"));
+    assert!(code
+        .file("c_Observer.h")
+        .contains("typedef struct FooOpaque FooOpaque;"));
+}
+
+#[test]
+fn test_cpp_nested_namespace() {
+    let _ = env_logger::try_init();
+
+    for &(boost, exp_name) in &[
+        (false, "cpp_nested_namespace"),
+        (true, "cpp_nested_namespace_boost"),
+    ] {
+        let code = parse_and_check(
+            exp_name,
+            r#"
+foreigner_class!(
+#[cpp_namespace = "acme::ui"]
+class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    method Foo::f(&self) -> String;
+});
+"#,
+            TestConfig::cpp(move |dir| {
+                let cfg = CppConfig::new(dir, "acme::storage::v2".into());
+                if boost {
+                    cfg.use_boost()
+                } else {
+                    cfg
+                }
+            }),
+        );
+        assert!(!code.file("rust_str.h").contains("RUST_SWIG_USER_NAMESPACE"));
+    }
+
+    let ret = panic::catch_unwind(|| {
+        parse_code(
+            "cpp_namespace_invalid",
+            Source::Str(
+                r#"
+foreigner_class!(
+#[cpp_namespace = "acme::"]
+class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+});
+"#,
+            ),
+            ForeignLang::Cpp,
+        )
+        .expect("cpp_namespace_invalid");
+    });
+    assert!(ret.is_err());
+}
+
+#[test]
+fn test_cpp_standard() {
+    let _ = env_logger::try_init();
+
+    let src = r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    method Foo::f(&self) -> i32;
+    method Foo::set_f(&mut self, _: i32);
+    static_method Foo::find(_: i32) -> Option<Foo>;
+});
+"#;
+    let code = parse_and_check(
+        "cpp_standard_11",
+        src,
+        TestConfig::cpp(|dir| {
+            CppConfig::new(dir, "org::examples".into()).cpp_standard(CppStandard::Cpp11)
+        }),
+    );
+    assert!(!code.foreign_code.contains("[[nodiscard]]"));
+
+    let code = parse_and_check(
+        "cpp_standard_17",
+        src,
+        TestConfig::cpp(|dir| {
+            CppConfig::new(dir, "org::examples".into())
+                .cpp_standard(CppStandard::Cpp17)
+                .cpp_optional(CppOptional::Std17)
+        }),
+    );
+    assert!(!code.foreign_code.contains("[[nodiscard]] void"));
+
+    for (cpp_optional, method) in &[
+        (CppOptional::Std17, "method Foo::f(&self) -> i32;"),
+        (
+            CppOptional::Boost,
+            "#[awaitable]\n    method Foo::load(&self, _: Box<dyn FnOnce(i32) + Send>);",
+        ),
+    ] {
+        let cpp_optional = *cpp_optional;
+        let result = panic::catch_unwind(|| {
+            parse_code(
+                "cpp_standard_errors",
+                Source::Str(&format!(
+                    "foreigner_class!(class Foo {{\n    self_type Foo;\n    constructor Foo::default() -> Foo;\n    {}\n}});",
+                    method
+                )),
+                TestConfig::cpp(move |dir| {
+                    CppConfig::new(dir, "org::examples".into())
+                        .cpp_standard(CppStandard::Cpp14)
+                        .cpp_optional(cpp_optional)
+                }),
+            )
+        });
+        assert!(result.is_err(), "{} should fail", method);
+    }
+}
+
+#[test]
+fn test_data_enum_errors() {
+    let _ = env_logger::try_init();
+
+    let unsupported_field = r#"
+foreign_enum!(enum Shape {
+    Circle = Shape::Circle(Vec<f64>),
+});
+"#;
+    let with_payload = r#"
+foreign_enum!(enum Shape {
+    Circle = Shape::Circle(f64),
+});
+"#;
+    for (lang, code) in &[
+        (ForeignLang::Cpp, unsupported_field),
+        (ForeignLang::Java, with_payload),
+    ] {
+        let ret = panic::catch_unwind(|| {
+            let name = format!("data_enum_errors {:?}", lang);
+            parse_code(&name, Source::Str(code), *lang).expect(&name);
+        });
+        assert!(ret.is_err());
+    }
+}
+
+#[test]
+fn test_name_collisions() {
+    let _ = env_logger::try_init();
+
+    let class_and_enum = r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+});
+foreign_enum!(enum Foo {
+    A = Boo::A,
+});
+"#;
+    let helper = r#"
+foreigner_class!(class RustString {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+});
+"#;
+    let cpp_generated = r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+});
+foreigner_class!(class FooRef {
+    self_type FooRef;
+    constructor FooRef::default() -> FooRef;
+});
+"#;
+    let same_jni_func = r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    method Foo::f(&self, _: i32);
+    method Foo::g(&self, _: i32); alias f;
+});
+"#;
+    let destructor = r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    method Foo::delete(&self, _: i32);
+});
+"#;
+    for (lang, code) in &[
+        (ForeignLang::Cpp, class_and_enum),
+        (ForeignLang::Java, class_and_enum),
+        (ForeignLang::Cpp, helper),
+        (ForeignLang::Cpp, cpp_generated),
+        (ForeignLang::Java, same_jni_func),
+        (ForeignLang::Java, destructor),
+    ] {
+        let ret = panic::catch_unwind(|| {
+            let name = format!("name_collisions {:?}", lang);
+            parse_code(&name, Source::Str(code), *lang).expect(&name);
+        });
+        assert!(ret.is_err(), "{:?}: {} should fail", lang, code);
+    }
+
+    parse_code(
+        "name_collisions_overloading",
+        Source::Str(
+            r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    method Foo::f(&self, _: i32);
+    method Foo::g(&self, _: &str); alias f;
+    static_method Foo::f(_: i32);
+});
+"#,
+        ),
+        ForeignLang::Java,
+    )
+    .expect("name_collisions_overloading");
+}
+
+#[test]
+fn test_java_android_library_module() {
+    let _ = env_logger::try_init();
+
+    let tmp_dir = tempdir().expect("Can not create tmp directory");
+    let src_path = tmp_dir.path().join("src.rs");
+    fs::write(
+        &src_path,
+        r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+});
+"#,
+    )
+    .unwrap();
+    let module_dir = tmp_dir.path().join("foo-lib");
+    Generator::new(LanguageConfig::JavaConfig(
+        JavaConfig::new(tmp_dir.path().into(), "com.example.foo".into())
+            .android_library_module(module_dir.clone()),
+    ))
+    .with_pointer_target_width(64)
+    .expand("android_module", &src_path, tmp_dir.path().join("test.rs"));
+    let build_gradle = fs::read_to_string(module_dir.join("build.gradle")).unwrap();
+    println!("build.gradle: {}", build_gradle);
+    assert!(build_gradle.contains("apply plugin: 'com.android.library'"));
+    assert!(build_gradle.contains("consumerProguardFiles 'proguard-rules.pro'"));
+    assert!(module_dir.join("proguard-rules.pro").is_file());
+    let manifest = fs::read_to_string(module_dir.join("src/main/AndroidManifest.xml")).unwrap();
+    assert!(manifest.contains("package=\"com.example.foo\""));
+    assert!(module_dir
+        .join("src/main/java/com/example/foo/Foo.java")
+        .is_file());
+    assert!(module_dir.join("src/main/jniLibs/arm64-v8a").is_dir());
+}
+
+#[test]
+fn test_java_direct_buffer() {
+    let _ = env_logger::try_init();
+
+    parse_and_check(
+        "java_direct_buffer",
+        r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    #[direct_buffer]
+    method Foo::decode(&mut self, _: &[u8], _: &mut [u8]) -> i32;
+});
+"#,
+        TestConfig::java(|dir| JavaConfig::new(dir, "com.example".into())),
+    );
+}
+
+#[test]
+fn test_java_critical_arrays() {
+    let _ = env_logger::try_init();
+
+    let code = parse_and_check(
+        "java_critical_arrays",
+        r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    #[critical_arrays]
+    method Foo::scale(&mut self, _: &[f32], _: &mut [i32], _: &str) -> String;
+});
+"#,
+        TestConfig::java(|dir| JavaConfig::new(dir, "com.example".into())),
+    );
+    let rust_code = without_whitespaces(&code.rust_code);
+    let pos_str = rust_code.find("letmuta_2:&str=").unwrap();
+    let pos_acquire = rust_code
+        .find("letmuta_0=a_0;leta_0:&[f32]=unsafe{a_0.acquire()};letmuta_1=a_1;leta_1:&mut[i32]=unsafe{a_1.acquire()};Foo::scale(this,a_0,a_1,a_2,)};")
+        .unwrap();
+    assert!(pos_str < pos_acquire);
+    let pos_ret = rust_code.find("letmutret:jstring=").unwrap();
+    assert!(pos_acquire < pos_ret);
+}
+
+#[test]
+fn test_java_str_arg_conversion() {
+    let _ = env_logger::try_init();
+
+    let code = parse_and_check(
+        "java_str_arg_conversion",
+        r#"
+foreigner_class!(class Foo {
+    static_method Foo::f(_: &str) -> i32;
+});
+"#,
+        TestConfig::java(|dir| JavaConfig::new(dir, "com.example".into())),
+    );
+    assert!(!code.rust_code.contains("GetStringUTFChars"));
+}
+
+#[test]
+fn test_java_exceptions() {
+    let _ = env_logger::try_init();
+
+    let code = parse_and_check(
+        "java_exceptions",
+        r#"
+foreigner_class!(class ParseError {
+    self_type ParseError;
+    private constructor = empty;
+    method ParseError::line(&self) -> i32;
+});
+foreigner_class!(class Parser {
+    self_type Parser;
+    constructor Parser::new() -> Parser;
+    method Parser::parse(&self, _: &str) -> Result<i32, ParseError>;
+    method Parser::check(&self) -> Result<(), BadState>;
+    method Parser::plain(&self) -> Result<i32, String>;
+});
+"#,
+        TestConfig::java(|dir| {
+            JavaConfig::new(dir, "com.example".into())
+                .exception_for_error("ParseError".into(), "ParseException".into())
+                .exception_for_error("BadState".into(), "java.lang.IllegalStateException".into())
+        }),
+    );
+    let rules = code.file("proguard-rules.pro");
+    assert!(rules.contains("-keep class com.example.ParseException { <init>(...); }"));
+    assert!(!rules.contains("IllegalStateException"));
+    assert_eq!(1, code.rust_code.matches("fn swig_jni_throw_0").count());
+}
+
+#[test]
+fn test_java_unchecked_exceptions() {
+    let _ = env_logger::try_init();
+
+    parse_and_check(
+        "java_unchecked_exceptions",
+        r#"
+foreigner_class!(class Parser {
+    self_type Parser;
+    constructor Parser::new() -> Parser;
+    /// Parse text
+    method Parser::parse(&self, _: &str) -> Result<i32, ParseError>;
+    method Parser::plain(&self) -> Result<i32, String>;
+});
+"#,
+        TestConfig::java(|dir| {
+            JavaConfig::new(dir, "com.example".into())
+                .exception_for_error("ParseError".into(), "ParseException".into())
+                .use_unchecked_exceptions(true)
+        }),
+    );
+}
+
+#[test]
+fn test_doc_links() {
+    let _ = env_logger::try_init();
+
+    let src = r#"
+foreign_enum!(enum Color {
+    RED = Color::Red,
+    /// Opposite of [`Color::RED`]
+    BLUE = Color::Blue,
+});
+
+foreigner_class!(
+/// Created by [`Factory::create`], see also [`Unknown`]
+class Item {
+    self_type Item;
+    constructor Item::default() -> Item;
+    /// Same as [`Self::name()`], but in [Color]
+    method Item::color(&self) -> Color;
+    method Item::name(&self) -> String;
+});
+
+foreigner_class!(class Factory {
+    /// Returns new [`Item`], look at [docs](https://example.com)
+    static_method Factory::create() -> Item;
+});
+"#;
+    parse_and_check(
+        "doc_links",
+        src,
+        TestConfig::java(|dir| JavaConfig::new(dir, "com.example".into())),
+    );
+    parse_and_check("doc_links", src, ForeignLang::Cpp);
+}
+
+#[test]
+fn test_java_kotlin_friendly() {
+    let _ = env_logger::try_init();
+
+    let code = parse_and_check(
+        "java_kotlin_friendly",
+        r#"
+foreigner_class!(class Node {
+    self_type Node;
+    constructor Node::new(_: &str) -> Node;
+    method Node::name(&self) -> String;
+    method Node::children(&self) -> Vec<Node>;
+    method Node::parent(&self) -> Option<Node>;
+    method Node::depth(&self) -> i32;
+    static_method Node::root() -> Node;
+});
+"#,
+        TestConfig::java(|dir| {
+            JavaConfig::new(dir, "com.example".into())
+                .use_optional(false)
+                .use_kotlin_friendly(true)
+        }),
+    );
+    assert!(!code.file("Node.java").contains("finalize"));
+}
+
+#[test]
+fn test_java_min_api_level() {
+    let _ = env_logger::try_init();
+
+    let code = parse_and_check(
+        "java_min_api_level",
+        r#"
+foreigner_class!(class Node {
+    self_type Node;
+    constructor Node::default() -> Node;
+    method Node::parent(&self) -> Option<Node>;
+    method Node::names(&self) -> Box<dyn Iterator<Item = String>>;
+});
+"#,
+        TestConfig::java(|dir| JavaConfig::new(dir, "com.example".into()).min_api_level(21)),
+    );
+    let iter_code = code.file("RustIterator.java");
+    assert!(!iter_code.contains("java.util.function"));
+    assert!(!iter_code.contains("java.util.stream"));
+
+    for (method, streams) in &[
+        ("method Node::parent(&self) -> Option<Node>;", true),
+        (
+            "#[completable_future]\n    method Node::load(&self, _: Box<dyn FnOnce(i32) + Send>);",
+            false,
+        ),
+    ] {
+        let streams = *streams;
+        let result = panic::catch_unwind(|| {
+            parse_code(
+                "min_api_level_errors",
+                Source::Str(&format!(
+                    "foreigner_class!(class Node {{\n    self_type Node;\n    constructor Node::default() -> Node;\n    {}\n}});",
+                    method
+                )),
+                TestConfig::java(move |dir| {
+                    JavaConfig::new(dir, "com.example".into())
+                        .min_api_level(23)
+                        .use_java_streams(streams)
+                }),
+            )
+        });
+        assert!(result.is_err(), "{} should fail", method);
+    }
+}
+
+#[test]
+fn test_java_lists() {
+    let _ = env_logger::try_init();
+
+    parse_and_check(
+        "java_lists",
+        r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    method Foo::children(&self) -> Vec<Foo>;
+    static_method Foo::names() -> Vec<String>;
+    static_method Foo::nums() -> Vec<i32>;
+});
+"#,
+        TestConfig::java(|dir| JavaConfig::new(dir, "com.example".into()).use_java_lists(true)),
+    );
+}
+
+#[test]
+fn test_java_optional_or_nullable() {
+    let _ = env_logger::try_init();
+
+    for &(use_optional, exp_name) in &[(true, "java_optional"), (false, "java_nullable")] {
+        parse_and_check(
+            exp_name,
+            r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    method Foo::parent(&self) -> Option<Foo>;
+    static_method Foo::name() -> Option<String>;
+    static_method Foo::count() -> Option<i32>;
+    static_method Foo::size() -> Option<i64>;
+});
+"#,
+            TestConfig::java(move |dir| {
+                JavaConfig::new(dir, "com.example".into()).use_optional(use_optional)
+            }),
+        );
+    }
+}
+
+#[test]
+fn test_java_iterators() {
+    let _ = env_logger::try_init();
+
+    for &(use_streams, exp_name) in &[(false, "java_iterators"), (true, "java_iterators_streams")] {
+        parse_and_check(
+            exp_name,
+            r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    method Foo::children(&self) -> Box<dyn Iterator<Item = Foo>>;
+    static_method Foo::names() -> Box<dyn Iterator<Item = String>>;
+});
+"#,
+            TestConfig::java(move |dir| {
+                JavaConfig::new(dir, "com.example".into()).use_java_streams(use_streams)
+            }),
+        );
+    }
+}
+
+#[test]
+fn test_java_completable_future() {
+    let _ = env_logger::try_init();
+
+    parse_and_check(
+        "java_completable_future",
+        r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    #[completable_future]
+    method Foo::fetch(&self, id: i32, done: Box<dyn FnOnce(Result<Foo, FetchError>) + Send>);
+    #[completable_future]
+    static_method Foo::flush(done: Box<dyn FnOnce() + Send>);
+});
+"#,
+        TestConfig::java(|dir| {
+            JavaConfig::new(dir, "com.example".into())
+                .exception_for_error("FetchError".into(), "FetchException".into())
+        }),
+    );
+}
+
+#[test]
+fn test_java_callback_local_frame() {
+    let _ = env_logger::try_init();
+
+    parse_and_check(
+        "java_callback_local_frame",
+        r#"
+foreign_interface!(interface SomeObserver {
+    self_type SomeTrait;
+    onStateChanged = SomeTrait::on_state_changed(&self, x: i32, s: &str);
+});
+"#,
+        TestConfig::java(|dir| JavaConfig::new(dir, "com.example".into())),
+    );
+}
+
+#[test]
+fn test_java_fast_and_critical_native() {
+    let _ = env_logger::try_init();
+
+    parse_and_check(
+        "java_fast_and_critical_native",
+        r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    #[fast_native]
+    constructor Foo::new(_: &str) -> Foo;
+    #[critical_native]
+    method Foo::len(&self) -> i32;
+    #[critical_native]
+    static_method Foo::add(_: i32, _: f64) -> f64;
+    #[fast_native]
+    static_method Foo::name() -> String;
+});
+"#,
+        TestConfig::java(|dir| JavaConfig::new(dir, "com.example".into())),
+    );
+
+    for (method, err) in &[
+        (
+            "#[critical_native]\n    static_method Foo::name() -> String;",
+            "#[critical_native] method should have only arguments and result of primitive types",
+        ),
+        (
+            "#[critical_native]\n    static_method Foo::check() -> Result<i32, String>;",
+            "#[critical_native] method should have only arguments and result of primitive types",
+        ),
+        (
+            "#[fast_native]\n    #[critical_native]\n    static_method Foo::len() -> i32;",
+            "#[fast_native] and #[critical_native] can not be used together",
+        ),
+    ] {
+        let result = panic::catch_unwind(|| {
+            parse_code(
+                "critical_native_errors",
+                Source::Str(&format!(
+                    "foreigner_class!(class Foo {{\n    self_type Foo;\n    constructor Foo::default() -> Foo;\n    {}\n}});",
+                    method
+                )),
+                TestConfig::java(|dir| JavaConfig::new(dir, "com.example".into())),
+            )
+        });
+        assert!(result.is_err(), "{} should fail with: {}", method, err);
+    }
+}
+
+#[test]
+fn test_java_proguard_rules() {
+    let _ = env_logger::try_init();
+
+    let code = parse_code(
+        "proguard",
+        Source::Str(
+            r#"
+foreign_enum!(enum Kind {
+    A = Kind::A,
+});
+
+foreign_interface!(interface Observer {
+    self_type Observer;
+    on_event = Observer::on_event(&self, _: i32);
+});
+
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    method Foo::f(&self) -> Kind;
+});
+
+foreigner_class!(class Utils {
+    static_method subscribe(_: Box<Observer>);
+});
+"#,
+        ),
+        TestConfig::java(|dir| JavaConfig::new(dir, "com.example".into())),
+    )
+    .unwrap();
+    let rules = code.file("proguard-rules.pro");
+    println!("proguard-rules.pro: {}", rules);
+    assert!(rules.contains(
+        "-keep class com.example.Foo {\n    native <methods>;\n    long mNativeObj;\n}\n"
+    ));
+    assert!(rules.contains("-keep class com.example.Utils {\n    native <methods>;\n}\n"));
+    assert!(rules.contains("-keep class com.example.Kind { *; }\n"));
+    assert!(rules.contains("-keep interface com.example.Observer { <methods>; }\n"));
+}
+
+#[test]
+fn test_java_log_bridge() {
+    let _ = env_logger::try_init();
+
+    for &(backend, register_natives, log_call) in &[
+        (
+            JavaLogBackend::AndroidLog,
+            false,
+            "android.util.Log.println(priority, target, message);",
+        ),
+        (
+            JavaLogBackend::JavaUtilLogging,
+            false,
+            "java.util.logging.Logger.getLogger(target.replace(\"::\", \".\")).log(julLevel, message);",
+        ),
+        (JavaLogBackend::Slf4j, true, "logger.trace(message);"),
+    ] {
+        let code = parse_and_check(
+            "java_log_bridge",
+            r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+});
+"#,
+            TestConfig::java(move |dir| {
+                JavaConfig::new(dir, "com.example".into())
+                    .use_register_natives(register_natives)
+                    .log_bridge(backend)
+            }),
+        );
+        assert!(code.file("RustLog.java").contains(log_call));
+        assert!(code.file("proguard-rules.pro").contains(
+            "-keep class com.example.RustLog {\n    native <methods>;\n    \
+             static void log(int, java.lang.String, java.lang.String);\n}\n"
+        ));
+        let rust_code = without_whitespaces(&code.rust_code);
+        if register_natives {
+            assert!(rust_code.contains("extern\"C\"fnJava_com_example_RustLog_do_1init("));
+            assert!(!rust_code.contains("pubextern\"C\"fnJava_com_example_RustLog_do_1init("));
+            assert!(rust_code.contains("ifswig_register_natives_for_RustLog(env)!=(JNI_OKasjint)"));
+        } else {
+            assert!(rust_code.contains("pubextern\"C\"fnJava_com_example_RustLog_do_1init("));
+        }
+    }
+}
+
+#[test]
+fn test_java_catch_panics() {
+    let _ = env_logger::try_init();
+
+    for &(global, exception) in &[
+        (true, "java/lang/IllegalStateException"),
+        (false, "java/lang/RuntimeException"),
+    ] {
+        let code = parse_and_check(
+            "java_catch_panics",
+            r#"
+foreigner_class!(#[catch_panics] class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    method Foo::f(&self, _: i32) -> bool;
+    static_method Foo::g() -> String;
+});
+foreigner_class!(#[catch_panics = false] class Boo {
+    static_method Boo::h() -> i32;
+});
+"#,
+            TestConfig::java(move |dir| {
+                let cfg = JavaConfig::new(dir, "com.example".into());
+                if global {
+                    cfg.catch_panics(PanicAction::Throw("java.lang.IllegalStateException".into()))
+                        .panic_backtrace(true)
+                } else {
+                    cfg
+                }
+            }),
+        );
+        let rust_code = without_whitespaces(&code.rust_code);
+        assert!(rust_code.contains(&format!(
+            "jni_throw(env,swig_c_str!(\"{}\"),&msg);",
+            exception
+        )));
+        assert_eq!(global, rust_code.contains("fnswig_init_panic_hook()"));
+    }
+}
+
+#[test]
+fn test_java_tracing_spans() {
+    let _ = env_logger::try_init();
+
+    let src = r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    method Foo::f(&self, _: i32, _: &str) -> bool;
+    static_method Foo::g() -> String;
+});
+"#;
+    parse_and_check(
+        "java_tracing_spans",
+        src,
+        TestConfig::java(|dir| JavaConfig::new(dir, "com.example".into()).tracing_spans(true)),
+    );
+    let code = parse_code(
+        "java_tracing_spans",
+        Source::Str(src),
+        TestConfig::java(|dir| JavaConfig::new(dir, "com.example".into()).tracing_spans(false)),
+    )
+    .unwrap();
+    assert!(!code.rust_code.contains("tracing"));
+}
+
+#[test]
+fn test_java_module_info() {
+    let _ = env_logger::try_init();
+
+    let code = parse_code(
+        "module_info",
+        Source::Str(
+            r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+});
+"#,
+        ),
+        TestConfig::java(|dir| {
+            let output_dir = dir.join("java").join("com").join("example").join("foo");
+            fs::create_dir_all(&output_dir).unwrap();
+            JavaConfig::new(output_dir, "com.example.foo".into())
+                .module_info("com.example.foo".into(), "foo".into())
+        }),
+    )
+    .unwrap();
+    assert!(code.files.contains_key("java/com/example/foo/Foo.java"));
+    let module_info = code.file("java/module-info.java");
+    println!("module-info.java: {}", module_info);
+    assert!(module_info.contains("module com.example.foo {\n    exports com.example.foo;\n}"));
+    assert!(module_info.contains("System.loadLibrary(\"foo\")"));
+}
+
+#[test]
+fn test_java_load_native_library() {
+    let _ = env_logger::try_init();
+
+    let code = parse_and_check(
+        "java_load_native_library",
+        r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    foreigner_code "    public int javaFunc() { return 17; }\n";
+});
+foreigner_class!(class Boo {
+    self_type Boo;
+    constructor Boo::default() -> Boo;
+});
+"#,
+        TestConfig::java(|dir| {
+            JavaConfig::new(dir, "com.example".into())
+                .load_native_library("Foo".into(), "foo".into())
+                .native_library_loader("com.example.util.NativeLoader".into())
+        }),
+    );
+    assert!(!code.file("Boo.java").contains("loadLibrary"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_format_generated_files() {
+    let _ = env_logger::try_init();
+
+    let tmp_dir = tempdir().expect("Can not create tmp directory");
+    let src_path = tmp_dir.path().join("src.rs");
+    fs::write(
+        &src_path,
+        r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+});
+"#,
+    )
+    .unwrap();
+    let java_path = tmp_dir.path().join("Foo.java");
+    let expand = || {
+        Generator::new(LanguageConfig::JavaConfig(JavaConfig::new(
+            tmp_dir.path().into(),
+            "com.example".into(),
+        )))
+        .with_pointer_target_width(64)
+        .format_generated_files(&["java"], "sed", &["s/rust_swig/RUST_SWIG/"])
+        .expand("format", &src_path, tmp_dir.path().join("test.rs"));
+        fs::metadata(&java_path).unwrap().modified().unwrap()
+    };
+    let mtime = expand();
+    let java_code = fs::read_to_string(&java_path).unwrap();
+    println!("java_code: {}", java_code);
+    assert!(java_code.contains("// Automaticaly generated by RUST_SWIG"));
+    let rust_code = fs::read_to_string(tmp_dir.path().join("test.rs")).unwrap();
+    assert!(!rust_code.contains("RUST_SWIG"));
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    assert_eq!(mtime, expand());
+}
+
+#[test]
+fn test_generated_file_hook() {
+    let _ = env_logger::try_init();
+
+    let tmp_dir = tempdir().expect("Can not create tmp directory");
+    let src_path = tmp_dir.path().join("src.rs");
+    fs::write(
+        &src_path,
+        r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+});
+"#,
+    )
+    .unwrap();
+    let rust_path = tmp_dir.path().join("test.rs");
+    let generated = Rc::new(RefCell::new(Vec::<PathBuf>::new()));
+    let generated2 = generated.clone();
+    Generator::new(LanguageConfig::CppConfig(CppConfig::new(
+        tmp_dir.path().into(),
+        "org_examples".into(),
+    )))
+    .with_pointer_target_width(64)
+    .with_generated_file_hook(move |path, cnt| {
+        generated2.borrow_mut().push(path.into());
+        if path.extension().map_or(false, |x| x == "hpp") {
+            cnt.insert_str(0, "// SPDX-License-Identifier: MIT\n");
+            fs::write(path.with_extension("txt"), "companion").unwrap();
+        }
+    })
+    .expand("generated_file_hook", &src_path, &rust_path);
+    let generated = generated.borrow();
+    println!("generated: {:?}", generated);
+    assert!(generated.contains(&rust_path));
+    assert!(generated.contains(&tmp_dir.path().join("Foo.hpp")));
+    let cpp_code = fs::read_to_string(tmp_dir.path().join("Foo.hpp")).unwrap();
+    assert!(cpp_code.starts_with("// SPDX-License-Identifier: MIT\n"));
+    assert_eq!(
+        "companion",
+        fs::read_to_string(tmp_dir.path().join("Foo.txt")).unwrap()
+    );
+    let c_code = fs::read_to_string(tmp_dir.path().join("c_Foo.h")).unwrap();
+    assert!(!c_code.contains("SPDX"));
+}
+
+#[test]
+fn test_expand_many() {
+    let _ = env_logger::try_init();
+
+    let tmp_dir = tempdir().expect("Can not create tmp directory");
+    let foo_path = tmp_dir.path().join("foo.rs");
+    fs::write(
+        &foo_path,
+        r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+});
+"#,
+    )
+    .unwrap();
+    let bar_path = tmp_dir.path().join("bar.rs");
+    fs::write(
+        &bar_path,
+        r#"
+foreigner_class!(class Bar {
+    self_type Bar;
+    constructor Bar::new(_: &Foo) -> Bar;
+    method Bar::foo(&self) -> Foo;
+});
+"#,
+    )
+    .unwrap();
+    for lang in &[ForeignLang::Java, ForeignLang::Cpp] {
+        let out_dir = tmp_dir.path().join(format!("{:?}", lang));
+        fs::create_dir(&out_dir).unwrap();
+        let config = match lang {
+            ForeignLang::Java => {
+                LanguageConfig::JavaConfig(JavaConfig::new(out_dir.clone(), "com.example".into()))
+            }
+            ForeignLang::Cpp => {
+                LanguageConfig::CppConfig(CppConfig::new(out_dir.clone(), "org_examples".into()))
+            }
+        };
+        let rust_path = out_dir.join("test.rs");
+        Generator::new(config)
+            .with_pointer_target_width(64)
+            .expand_many("expand_many", &[&bar_path, &foo_path], &rust_path);
+        let rust_code = fs::read_to_string(&rust_path).unwrap();
+        let (foo_file, bar_file, bar_method) = match lang {
+            ForeignLang::Java => ("Foo.java", "Bar.java", "public final Foo foo()"),
+            ForeignLang::Cpp => ("Foo.hpp", "Bar.hpp", "Foo foo() const"),
+        };
+        assert!(out_dir.join(foo_file).is_file());
+        let bar_code = fs::read_to_string(out_dir.join(bar_file)).unwrap();
+        println!("{:?}: {}", lang, bar_code);
+        assert!(bar_code.contains(bar_method));
+        assert!(rust_code.contains("Foo"));
+        assert!(rust_code.contains("Bar"));
+    }
+}
+
+#[test]
+fn test_report_errors_via_compile_error() {
+    let _ = env_logger::try_init();
+
+    let code = parse_code(
+        "compile_error",
+        Source::Str(
+            r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    method Foo::f(&self) -> i32;
+    alias;
+});
+"#,
+        ),
+        TestConfig::java(|dir| JavaConfig::new(dir, "com.example".into()))
+            .generator(|gen, _| gen.report_errors_via_compile_error(true)),
+    )
+    .unwrap();
+    println!("rust_code: {}", code.rust_code);
+    assert!(code.rust_code.starts_with("compile_error !"));
+    assert!(code
+        .rust_code
+        .contains("At compile_error: $OUT_DIR/src.rs:6:"));
+}
+
+#[test]
+fn test_dump_conversation_graph_on_error() {
+    let _ = env_logger::try_init();
+
+    let code = parse_code(
+        "dump_graph_on_error",
+        Source::Str(
+            r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    method Foo::f(&self, _: Bar) -> i32;
+});
+"#,
+        ),
+        TestConfig::java(|dir| JavaConfig::new(dir, "com.example".into())).generator(|gen, dir| {
+            gen.report_errors_via_compile_error(true)
+                .dump_conversation_graph(dir.join("graph.dot"))
+        }),
+    )
+    .unwrap();
+    assert!(code.rust_code.starts_with("compile_error !"));
+    assert!(code
+        .file("graph.dot")
+        .starts_with("digraph conversation_graph {"));
+}
+
+#[test]
+fn test_report_unsupported_types() {
+    let _ = env_logger::try_init();
+
+    for lang in &[ForeignLang::Java, ForeignLang::Cpp] {
+        let code = parse_code(
+            "unsupported_types",
+            Source::Str(
+                r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    method Foo::f(&self, _: i32, bar: &Bar) -> HashMap<i32, i32>;
+    method Foo::g(&self) -> i32;
+});
+foreigner_class!(class Boo {
+    self_type Boo;
+    constructor Boo::default() -> Boo;
+    method Boo::h(&self) -> Baz;
+});
+"#,
+            ),
+            TestConfig::from(*lang).generator(|gen, _| gen.report_errors_via_compile_error(true)),
+        )
+        .unwrap();
+        println!("{:?}: rust_code: {}", lang, code.rust_code);
+        assert!(code.rust_code.starts_with("compile_error !"));
+        for msg in &[
+            "error[SWIG0002]: class Foo, method Foo :: f, argument 2 `bar`: \
+             type `& Bar` is not supported",
+            "hint: wrap `Bar` in foreigner_class! (or foreign_enum! for enum), \
+             or add foreign_typemap! rule from foreign type to `Bar`",
+            "class Foo, method Foo :: f, result: type `HashMap < i32 , i32 >` is not supported",
+            "hint: add foreign_typemap! rule from `HashMap < i32 , i32 >` to foreign type",
+            "class Boo, method Boo :: h, result: type `Baz` is not supported",
+        ] {
+            assert!(code.rust_code.contains(msg), "{:?}: no {}", lang, msg);
+        }
+    }
+}
+
+#[test]
+fn test_strict_conversions() {
+    let _ = env_logger::try_init();
+
+    let src = r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    method Foo::size(&self) -> u64;
+    method Foo::f(&self, _: u32) -> i64;
+});
+"#;
+    let code = parse_code(
+        "strict_conversions",
+        Source::Str(src),
+        TestConfig::java(|dir| JavaConfig::new(dir, "com.example".into())).generator(|gen, _| {
+            gen.strict_conversions(true)
+                .report_errors_via_compile_error(true)
+        }),
+    )
+    .unwrap();
+    println!("rust_code: {}", code.rust_code);
+    assert!(code.rust_code.starts_with("compile_error !"));
+    assert!(code.rust_code.contains(
+        "error[SWIG0007]: conversation from u64 to jlong may lose data \
+         (values greater than i64::MAX are saturated)"
+    ));
+
+    parse_and_check(
+        "strict_conversions",
+        src,
+        TestConfig::java(|dir| JavaConfig::new(dir, "com.example".into())).generator(|gen, _| {
+            gen.strict_conversions(true).merge_type_map(
+                "checked_u64",
+                r#"
+foreign_typemap!(
+    ($p:r_type, fallible, force_override) u64 => jlong {
+        $out = if $p <= i64::max_value() as u64 {
+            Ok($p as jlong)
+        } else {
+            Err(format!("{} is too big for long", $p))
+        }
+    };
+);
+"#,
+            )
+        }),
+    );
+}
+
+#[test]
+fn test_dump_expanded_rust_code() {
+    let _ = env_logger::try_init();
+
+    let code = parse_code(
+        "dump_expanded",
+        Source::Str(
+            r#"
+foreign_enum!(enum MyEnum {
+  ITEM1 = MyEnum::Item1,
+  ITEM2 = MyEnum::Item2,
+});
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+    method Foo::f(&self, _: MyEnum) -> i32;
+});
+"#,
+        ),
+        TestConfig::java(|dir| JavaConfig::new(dir, "com.example".into()))
+            .generator(|gen, dir| gen.dump_expanded_rust_code(dir.join("expanded.rs"))),
+    )
+    .unwrap();
+    let dump = code.file("expanded.rs");
+    println!("dump: {}", dump);
+    let enum_pos = dump.find("// ---- enum MyEnum ----\n").unwrap();
+    let class_pos = dump.find("// ---- class Foo ----\n").unwrap();
+    assert!(enum_pos < class_pos);
+    assert!(dump[class_pos..].contains("Java_com_example_Foo_do_1f"));
+}
+
+#[test]
+fn test_additional_targets() {
+    let _ = env_logger::try_init();
+
+    let src = r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::new(_: i32) -> Foo;
+    method Foo::f(&self) -> i64;
+});
+"#;
+    let code = parse_code(
+        "additional_targets",
+        Source::Str(src),
+        TestConfig::java(|dir| JavaConfig::new(dir, "com.example".into())).generator(|gen, _| {
+            gen.with_target_os("android")
+                .with_additional_target(32, Some("android"))
+                .with_additional_target(64, Some("linux"))
+        }),
+    )
+    .unwrap();
+    let n_items = |cond: &str| {
+        code.rust_code
+            .matches(&format!("# [ cfg ( {} ) ] # [ no_mangle ]", cond))
+            .count()
+    };
+    let android64 = n_items(r#"all ( target_pointer_width = "64" , target_os = "android" )"#);
+    assert!(android64 > 0);
+    assert_eq!(
+        android64,
+        n_items(r#"all ( target_pointer_width = "32" , target_os = "android" )"#)
+    );
+    assert_eq!(
+        android64,
+        n_items(r#"all ( target_pointer_width = "64" , target_os = "linux" )"#)
+    );
+
+    let result = panic::catch_unwind(|| {
+        parse_code(
+            "additional_targets",
+            Source::Str(src),
+            TestConfig::from(ForeignLang::Cpp)
+                .generator(|gen, _| gen.with_additional_target(32, None)),
+        )
+    });
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_api_version() {
+    let _ = env_logger::try_init();
+
+    for lang in &[ForeignLang::Java, ForeignLang::Cpp] {
+        let code = parse_code(
+            "api_version",
+            Source::Str(
+                r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::default() -> Foo;
+});
+"#,
+            ),
+            match lang {
+                ForeignLang::Java => {
+                    TestConfig::java(|dir| JavaConfig::new(dir, "com.example".into()))
+                }
+                ForeignLang::Cpp => TestConfig::from(ForeignLang::Cpp),
+            }
+            .generator(|gen, _| gen.with_api_version("1.2.3")),
+        )
+        .unwrap();
+        let rust_code = &code.rust_code;
+        println!("{:?} rust: {}", lang, rust_code);
+        let stamp_pos = rust_code.find("\"1.2.3-").expect("no stamp in Rust code");
+        let stamp = &rust_code[stamp_pos..stamp_pos + "\"1.2.3-".len() + 17];
+        let (foreign_file, check) = match lang {
+            ForeignLang::Java => {
+                assert!(rust_code.contains("Java_com_example_ApiVersion_nativeVersion"));
+                ("ApiVersion.java", "public static void check()")
+            }
+            ForeignLang::Cpp => ("ApiVersion.hpp", "static bool check()"),
+        };
+        let foreign_code = code.file(foreign_file);
+        println!("{:?} foreign: {}", lang, foreign_code);
+        assert!(foreign_code.contains(&format!("GENERATED = {};", stamp)));
+        assert!(foreign_code.contains(check));
+    }
+}
+
+#[test]
+fn test_source_location_comments() {
+    let _ = env_logger::try_init();
+
+    for lang in &[ForeignLang::Java, ForeignLang::Cpp] {
+        let code = parse_and_check(
+            "source_location_comments",
+            r#"
+foreign_enum!(enum Kind {
+    A = Kind::A,
+});
+
+foreigner_class!(
+/// Foo doc
+class Foo {
+    self_type Foo;
+    constructor Foo::new(_: i32) -> Foo;
+    method Foo::f(&self) -> Kind;
+});
+"#,
+            TestConfig::from(*lang).generator(|gen, _| gen.source_location_comments(true)),
+        );
+        let class_code = code.file(match lang {
+            ForeignLang::Java => "Foo.java",
+            ForeignLang::Cpp => "Foo.hpp",
+        });
+        let class_pos = class_code
+            .find("Source: source_location_comments: $OUT_DIR/src.rs:8")
+            .unwrap();
+        assert!(class_code.find("Foo doc").unwrap() < class_pos);
+    }
+}
+
+#[test]
+fn test_check_rust_signatures() {
+    let _ = env_logger::try_init();
+
+    let src = r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::new(_: i32) -> Foo;
+    method Foo::f(&self, _: &str) -> i32;
+    method Foo::set(&mut self, _: i32);
+});
+"#;
+    parse_and_check(
+        "check_rust_signatures",
+        src,
+        TestConfig::java(|dir| JavaConfig::new(dir, "com.example".into()))
+            .generator(|gen, _| gen.check_rust_signatures(true)),
+    );
+    let code = parse_code(
+        "check_rust_signatures",
+        Source::Str(src),
+        TestConfig::java(|dir| JavaConfig::new(dir, "com.example".into())),
+    )
+    .unwrap();
+    assert!(!code.rust_code.contains("swig_check_signatures_Foo"));
+}
+
+#[test]
+fn test_check_send() {
+    let _ = env_logger::try_init();
+
+    let src = r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::new() -> Arc<Mutex<Foo>>;
+    method Foo::f(&self) -> i32;
+});
+foreigner_class!(class Boo {
+    self_type Boo<'a>;
+    constructor Boo::new() -> Boo<'a>;
+    method Boo::f(&self) -> i32;
+});
+foreigner_class!(#[single_threaded] class Moo {
+    self_type Moo;
+    constructor Moo::new() -> Rc<RefCell<Moo>>;
+    method Moo::f(&self) -> i32;
+});
+"#;
+    let code = parse_and_check(
+        "check_send",
+        src,
+        TestConfig::from(ForeignLang::Cpp).generator(|gen, _| gen.check_send(true)),
+    );
+    assert!(!code.rust_code.contains("swig_assert_send_Moo"));
+    let code = parse_code("check_send", Source::Str(src), ForeignLang::Cpp).unwrap();
+    assert!(!code.rust_code.contains("swig_assert_send"));
+}
+
+#[test]
+fn test_foreign_enum_exhaustive_match() {
+    let _ = env_logger::try_init();
+    for lang in &[ForeignLang::Java, ForeignLang::Cpp] {
+        parse_and_check(
+            "foreign_enum_exhaustive_match",
+            r#"
+foreign_enum!(enum Kind {
+    A = Kind::A,
+    B = Kind::B,
+});
+"#,
+            *lang,
+        );
+    }
+}
+
+#[test]
+fn test_java_register_natives() {
+    let _ = env_logger::try_init();
+
+    let code = parse_and_check(
+        "java_register_natives",
+        r#"
+foreign_enum!(enum Kind {
+    A = Kind::A,
+});
+
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::new(_: i32) -> Foo;
+    method Foo::f(&self, _: &str) -> Kind;
+    static_method Foo::g(_: f64) -> String;
+});
+"#,
+        TestConfig::java(|dir| {
+            JavaConfig::new(dir, "com.example".into()).use_register_natives(true)
+        }),
+    );
+    assert!(!without_whitespaces(&code.rust_code).contains("no_mangle]pubextern\"C\"fnJava_"));
+}
+
+#[test]
+fn test_java_records() {
+    let _ = env_logger::try_init();
+
+    let code = parse_and_check(
+        "java_records",
+        r#"
+foreigner_class!(#[derive(Copy)] class Point {
+    self_type Point;
+    constructor Point::new(_: i32, _: f64) -> Point;
+    method Point::x(&self) -> i32;
+    method Point::y(&self) -> f64;
+    method Point::clone(&self) -> Point;
+});
+
+foreigner_class!(class Shape {
+    self_type Shape;
+    constructor Shape::new(_: Point) -> Shape;
+    method Shape::center(&self) -> Point;
+});
+"#,
+        TestConfig::java(|dir| JavaConfig::new(dir, "com.example".into()).use_records(true)),
+    );
+    assert!(!code.file("Point.java").contains("mNativeObj"));
+    assert!(!without_whitespaces(&code.rust_code).contains("SwigForeignClassforPoint"));
+}
+
+#[test]
+fn test_check() {
+    let _ = env_logger::try_init();
+
+    let tmp_dir = tempdir().expect("Can not create tmp directory");
+    let src_path = tmp_dir.path().join("src.rs");
+    fs::write(
+        &src_path,
+        r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::new(_: i32) -> Foo;
+    method Foo::f(&self) -> i64;
+});
+"#,
+    )
+    .unwrap();
+    let out_dir = tmp_dir.path().join("out");
+    let new_generator = || {
+        Generator::new(LanguageConfig::CppConfig(CppConfig::new(
+            out_dir.clone(),
+            "org_examples".into(),
+        )))
+        .with_pointer_target_width(64)
+        .with_target_os("linux")
+        .with_additional_target(64, Some("android"))
+        .cache_builtin_type_maps(out_dir.join("cache"))
+        .dump_expanded_rust_code(out_dir.join("expanded.rs"))
+    };
+    new_generator().check("check", &[&src_path]);
+    assert!(!out_dir.exists());
+
+    fs::write(
+        &src_path,
+        r#"
+foreigner_class!(class Foo {
+    self_type Foo;
+    constructor Foo::new(_: i32) -> Foo;
+    method Foo::f(&self) -> NoSuchType;
+});
+"#,
+    )
+    .unwrap();
+    let result = panic::catch_unwind(|| new_generator().check("check", &[&src_path]));
+    assert!(result.is_err());
+    assert!(!out_dir.exists());
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum ForeignLang {
+    Java,
+    Cpp,
+}
+
+type GeneratorSetup = Box<dyn FnOnce(Generator, &Path) -> Generator>;
+
+/// How `parse_code` configures `Generator`, `ForeignLang` converts to configuration
+/// used for `tests/expectations/*.rs`
+struct TestConfig {
+    lang: ForeignLang,
+    config: Box<dyn FnOnce(PathBuf) -> LanguageConfig>,
+    generator: GeneratorSetup,
+}
+
+impl TestConfig {
+    /// `config` gets output directory
+    fn cpp<F: FnOnce(PathBuf) -> CppConfig + 'static>(config: F) -> Self {
+        TestConfig {
+            lang: ForeignLang::Cpp,
+            config: Box::new(move |dir| LanguageConfig::CppConfig(config(dir))),
+            generator: Box::new(|gen, _| gen),
+        }
+    }
+    /// `config` gets output directory
+    fn java<F: FnOnce(PathBuf) -> JavaConfig + 'static>(config: F) -> Self {
+        TestConfig {
+            lang: ForeignLang::Java,
+            config: Box::new(move |dir| LanguageConfig::JavaConfig(config(dir))),
+            generator: Box::new(|gen, _| gen),
+        }
+    }
+    /// Additional `Generator` settings, `generator` gets output directory
+    fn generator<F: FnOnce(Generator, &Path) -> Generator + 'static>(self, generator: F) -> Self {
+        TestConfig {
+            generator: Box::new(generator),
+            ..self
+        }
+    }
+}
+
+impl From<ForeignLang> for TestConfig {
+    fn from(lang: ForeignLang) -> Self {
+        match lang {
+            ForeignLang::Java => TestConfig::java(|dir| {
+                JavaConfig::new(dir, "org.example".into())
+                    .use_null_annotation_from_package("android.support.annotation".into())
+            }),
+            ForeignLang::Cpp => TestConfig::cpp(|dir| CppConfig::new(dir, "org_examples".into())),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct CodePair {
+    rust_code: String,
+    foreign_code: String,
+    /// All generated files, except Rust code, by path relative to output directory
+    files: BTreeMap<String, String>,
+}
+
+impl CodePair {
+    fn file(&self, name: &str) -> &str {
+        self.files
+            .get(name)
+            .unwrap_or_else(|| panic!("{} was not generated", name))
+    }
+}
+
+struct PrintTestInfo {
+    code_pair: CodePair,
+    test_name: String,
+    lang: ForeignLang,
+    print_on_drop: bool,
+    foreign_code_search_pattern: String,
+    rust_pat: String,
+}
+
+impl PrintTestInfo {
+    fn new(code_pair: CodePair, test_name: String, lang: ForeignLang) -> Self {
+        PrintTestInfo {
+            code_pair,
+            test_name,
+            lang,
+            print_on_drop: true,
+            foreign_code_search_pattern: String::new(),
+            rust_pat: String::new(),
+        }
+    }
+    fn success(&mut self) {
+        self.print_on_drop = false;
+    }
+}
+
+impl Drop for PrintTestInfo {
+    fn drop(&mut self) {
+        if self.print_on_drop {
+            if !self.foreign_code_search_pattern.is_empty() {
+                println!(
+                    "{} / {:?}: search foreign pat '{}'",
+                    self.test_name, self.lang, self.foreign_code_search_pattern
+                );
+            }
+
+            if !self.rust_pat.is_empty() {
+                println!(
+                    "{} / {:?}: search rust pat '{}'",
+                    self.test_name, self.lang, self.rust_pat,
+                );
+            }
+
+            println!(
+                "{} / {:?}: rust_swig generated such foreign_code: {}",
+                self.test_name, self.lang, self.code_pair.foreign_code
+            );
+            println!(
+                "{} / {:?}: rust_swig generated such rust_code: {}",
+                self.test_name, self.lang, self.code_pair.rust_code
+            );
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Error {
+    msg: String,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(x: std::io::Error) -> Self {
+        Error {
+            msg: format!("io: {}", x),
+        }
+    }
+}
+
+impl From<syn::Error> for Error {
+    fn from(x: syn::Error) -> Self {
+        Error {
+            msg: format!("syn: {}", x),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Path of output directory in generated code is replaced with `$OUT_DIR`,
+/// so expectations do not depend on temporary directory
+fn collect_files_in_dir(
+    out_dir: &Path,
+    dir: &Path,
+    files: &mut BTreeMap<String, String>,
+) -> Result<(), Error> {
+    for path in fs::read_dir(dir)? {
+        let path = path?.path();
+        if path.is_dir() {
+            collect_files_in_dir(out_dir, &path, files)?;
+        } else {
+            let name = path
+                .strip_prefix(out_dir)
+                .expect("file in output directory")
+                .to_string_lossy()
+                .replace('\\', "/");
+            let code =
+                fs::read_to_string(&path)?.replace(&out_dir.display().to_string(), "$OUT_DIR");
+            files.insert(name, code);
+        }
+    }
+    Ok(())
+}
+
+enum Source<'a> {
+    Str(&'a str),
+    Path(&'a Path),
+}
+
+fn parse_code<C: Into<TestConfig>>(
+    test_name: &str,
+    rust_src: Source,
+    cfg: C,
+) -> Result<CodePair, Error> {
+    let TestConfig {
+        lang,
+        config,
+        generator,
+    } = cfg.into();
+    let tmp_dir = tempdir().expect("Can not create tmp directory");
+    let swig_gen = Generator::new(config(tmp_dir.path().into())).with_pointer_target_width(64);
+    let swig_gen = generator(swig_gen, tmp_dir.path());
+    let ext_list: &[&str] = match lang {
+        ForeignLang::Java => &[".java"],
+        ForeignLang::Cpp => &[".h", ".hpp", ".cpp"],
+    };
+
+    let rust_code_path = tmp_dir.path().join("test.rs");
+    let rust_src_path = tmp_dir.path().join("src.rs");
+    match rust_src {
+        Source::Path(rust_src_path) => swig_gen.expand(test_name, rust_src_path, &rust_code_path),
+        Source::Str(rust_src) => {
+            fs::write(&rust_src_path, rust_src)?;
+            swig_gen.expand(test_name, &rust_src_path, &rust_code_path);
+        }
+    }
+
+    let rust_code = fs::read_to_string(&rust_code_path)?
+        .replace(&tmp_dir.path().display().to_string(), "$OUT_DIR");
+    let mut files = BTreeMap::new();
+    collect_files_in_dir(tmp_dir.path(), tmp_dir.path(), &mut files)?;
+    files.remove("test.rs");
+    files.remove("src.rs");
+    tmp_dir.close()?;
+
+    let mut foreign_code = String::new();
+    for (name, code) in &files {
+        if ext_list.iter().any(|ext| name.ends_with(ext)) {
+            foreign_code.push_str(code);
+            foreign_code.push('\n');
+        }
+    }
+
+    Ok(CodePair {
+        rust_code,
+        foreign_code,
+        files,
+    })
+}
+
+/// Generate code for `rust_src` and check it against patterns from
+/// `tests/expectations/{exp_name}.cpp` and `.cpp_rs` (`.java` and `.java_rs` for Java)
+fn parse_and_check<C: Into<TestConfig>>(exp_name: &str, rust_src: &str, cfg: C) -> CodePair {
+    let cfg = cfg.into();
+    let lang = cfg.lang;
+    let code_pair = parse_code(exp_name, Source::Str(rust_src), cfg).expect("parse_code failed");
+    let test_case = Path::new("tests")
+        .join("expectations")
+        .join(format!("{}.rs", exp_name));
+    assert!(
+        check_code_pair(exp_name, &test_case, &code_pair, lang) > 0,
+        "no expectations for {}",
+        exp_name
+    );
+    code_pair
+}
+
+fn expectation_exts(lang: ForeignLang) -> (&'static str, &'static str) {
+    match lang {
+        ForeignLang::Cpp => (".cpp", ".cpp_rs"),
+        ForeignLang::Java => (".java", ".java_rs"),
+    }
+}
+
+/// Check `code_pair` against expectation files of `test_case`,
+/// patterns for Rust code ignore whitespaces. Returns number of checked patterns
+fn check_code_pair(
+    test_name: &str,
+    test_case: &Path,
+    code_pair: &CodePair,
+    lang: ForeignLang,
+) -> usize {
+    let (main_ext, rust_ext) = expectation_exts(lang);
+    let mut npats = 0;
+    let mut print_test_info = PrintTestInfo::new(code_pair.clone(), test_name.into(), lang);
+
+    let main_expectation = new_path(test_case, main_ext);
+    if main_expectation.exists() {
+        let pats = parse_code_expectation(&main_expectation).expect("parsing of patterns failed");
+        for pat in pats {
+            print_test_info.foreign_code_search_pattern = pat.clone();
+            assert!(code_pair.foreign_code.contains(&pat));
+            npats += 1;
+        }
+        print_test_info.foreign_code_search_pattern.clear();
+    }
+
+    let rust_expectation = new_path(test_case, rust_ext);
+    if rust_expectation.exists() {
+        let pats = parse_code_expectation(&rust_expectation).expect("parsing of patterns failed");
+        let rust_code = without_whitespaces(&code_pair.rust_code);
+        for pat in pats {
+            print_test_info.rust_pat = pat.clone();
+            assert!(rust_code.contains(&without_whitespaces(&pat)));
+            npats += 1;
+        }
+        print_test_info.rust_pat.clear();
+    }
+    print_test_info.success();
+    npats
+}
+
+struct ExpectationPatterns(Vec<String>);
+
+impl syn::parse::Parse for ExpectationPatterns {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let lit_vec: syn::punctuated::Punctuated<syn::LitStr, Token![;]> =
+            syn::punctuated::Punctuated::parse_terminated(input)?;
+        Ok(ExpectationPatterns(
+            lit_vec.into_iter().map(|v| v.value()).collect(),
+        ))
+    }
+}
+
+fn without_whitespaces(code: &str) -> String {
+    code.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
 fn parse_code_expectation(exp_path: &Path) -> Result<Vec<String>, Error> {
     let patterns_str = fs::read_to_string(exp_path)?;
     let pats: ExpectationPatterns = syn::parse_str(&patterns_str)?;